@@ -0,0 +1,229 @@
+use archive;
+use counts_toward_total;
+use exit::PartialFailure;
+use failure::Error;
+use fdt;
+use input_records;
+use map_file;
+use serde_json;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+use Section;
+
+/// The sizes of one object file or archive found by `analyze`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FileSizes {
+    pub categories: BTreeMap<Section, u64>,
+    pub total: u64,
+    /// The layout of a device tree blob, which counts as data.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dtb: Option<fdt::Breakdown>,
+}
+
+/// One file in an `analyze` report, as emitted by `analyze --format json`.
+#[derive(Clone, Debug, Serialize)]
+pub struct Entry {
+    pub path: String,
+    #[serde(flatten)]
+    pub sizes: FileSizes,
+    /// Whether the sizes came from the cache rather than from parsing the file.
+    pub cached: bool,
+}
+
+/// What the cache knows about a file. Files that aren't object files are cached too, so that
+/// they aren't parsed again only to be skipped.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    /// The modification time, in nanoseconds since the epoch.
+    mtime: u64,
+    len: u64,
+    hash: u64,
+    /// The sizes, if the file is an object file or archive.
+    sizes: Option<FileSizes>,
+}
+
+/// The cache file read and written by `analyze --cache`, keyed by path.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Cache {
+    /// The sizes depend on `--include-non-alloc`, so the cache is only valid for one setting.
+    include_non_alloc: bool,
+    files: BTreeMap<String, CacheEntry>,
+}
+
+/// A 64-bit FNV-1a hash of `buf`. This only has to notice changed contents, not resist
+/// deliberate collisions, and unlike `DefaultHasher` it is stable across Rust versions.
+fn hash(buf: &[u8]) -> u64 {
+    buf.iter().fold(0xcbf2_9ce4_8422_2325, |h, &b| (h ^ b as u64).wrapping_mul(0x100_0000_01b3))
+}
+
+/// Append the paths of the regular files under `dir` to `paths`, in sorted order.
+pub fn walk(dir: &Path, paths: &mut Vec<String>) -> Result<(), Error> {
+    let mut entries = fs::read_dir(dir)
+        .map_err(|e| format_err!("{}: {}", dir.display(), e))?
+        .collect::<Result<Vec<_>, io::Error>>()?;
+    entries.sort_by_key(|e| e.file_name());
+    for entry in entries {
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            walk(&entry.path(), paths)?;
+        } else if file_type.is_file() {
+            paths.push(entry.path().to_string_lossy().into_owned());
+        }
+    }
+    Ok(())
+}
+
+/// Measure the file `buf`, read from `path`, or return `None` if it isn't an object file or a
+/// device tree blob.
+fn measure(path: &Path, buf: &[u8], include_non_alloc: bool) -> Result<Option<FileSizes>, Error> {
+    if fdt::is_fdt(buf) {
+        let dtb = fdt::breakdown(buf)?;
+        let mut sizes = FileSizes { categories: BTreeMap::new(), total: dtb.total, dtb: None };
+        sizes.categories.insert(Section::Data, dtb.total);
+        sizes.dtb = Some(dtb);
+        return Ok(Some(sizes));
+    }
+    if !archive::is_archive(buf) && !archive::is_object(buf) {
+        return Ok(None);
+    }
+    let mut sizes = FileSizes { categories: BTreeMap::new(), total: 0, dtb: None };
+    for record in input_records(path, buf)? {
+        *sizes.categories.entry(record.category).or_insert(0) += record.size;
+        if counts_toward_total(record.category, include_non_alloc) {
+            sizes.total += record.size;
+        }
+    }
+    Ok(Some(sizes))
+}
+
+/// Measure the file at `path`, unless `cached` is still up to date. Returns the new cache entry,
+/// and whether it came from the cache.
+fn analyze_file(path: &str, cached: Option<CacheEntry>, include_non_alloc: bool)
+                -> Result<(CacheEntry, bool), Error> {
+    let metadata = fs::metadata(path)?;
+    let mtime = metadata.modified()?.duration_since(UNIX_EPOCH)?.as_nanos() as u64;
+    let len = metadata.len();
+    if let Some(ref entry) = cached {
+        if entry.mtime == mtime && entry.len == len {
+            return Ok((entry.clone(), true));
+        }
+    }
+    let buf = map_file(path.as_ref())?;
+    let hash = hash(&buf);
+    if let Some(entry) = cached {
+        if entry.len == len && entry.hash == hash {
+            return Ok((CacheEntry { mtime, ..entry }, true));
+        }
+    }
+    Ok((CacheEntry {
+        mtime,
+        len,
+        hash,
+        sizes: measure(Path::new(path), &buf, include_non_alloc)?,
+    }, false))
+}
+
+/// Measure every object file and archive under `dir`, skipping other files.
+///
+/// With a `cache` file, files whose modification time and length are unchanged since the last
+/// run are not read again, and files whose modification time changed are hashed and only parsed
+/// again if their contents changed too. The cache is updated afterwards.
+///
+/// Files that can't be read or parsed are reported on stderr and left out, and the report is
+/// returned along with a `PartialFailure` counting them.
+pub fn analyze(dir: &Path, cache_path: Option<&Path>, include_non_alloc: bool)
+               -> Result<(Vec<Entry>, Option<PartialFailure>), Error> {
+    let mut cache = match cache_path {
+        Some(path) if path.exists() => {
+            let file = File::open(path).map_err(|e| format_err!("{}: {}", path.display(), e))?;
+            let cache: Cache = serde_json::from_reader(BufReader::new(file))
+                .map_err(|e| format_err!("{}: {}", path.display(), e))?;
+            if cache.include_non_alloc == include_non_alloc { cache } else { Cache::default() }
+        }
+        _ => Cache::default(),
+    };
+    cache.include_non_alloc = include_non_alloc;
+
+    let mut paths = Vec::new();
+    walk(dir, &mut paths)?;
+    let mut entries = Vec::new();
+    let mut files = BTreeMap::new();
+    let mut failed = 0;
+    for path in paths {
+        let cached = cache.files.remove(&path);
+        match analyze_file(&path, cached, include_non_alloc) {
+            Ok((entry, cached)) => {
+                if let Some(ref sizes) = entry.sizes {
+                    entries.push(Entry { path: path.clone(), sizes: sizes.clone(), cached });
+                }
+                files.insert(path, entry);
+            }
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                failed += 1;
+            }
+        }
+    }
+
+    // Files that disappeared are dropped from the cache along with the ones that failed.
+    cache.files = files;
+    if let Some(path) = cache_path {
+        let file = File::create(path).map_err(|e| format_err!("{}: {}", path.display(), e))?;
+        serde_json::to_writer(BufWriter::new(file), &cache)?;
+    }
+    Ok((entries, if failed > 0 { Some(PartialFailure { failed }) } else { None }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::elf::section_header::{SHF_ALLOC, SHF_EXECINSTR, SHT_PROGBITS};
+    use std::env;
+    use std::process;
+    use testelf::Elf;
+
+    #[test]
+    fn fnv1a_hashes() {
+        assert_eq!(hash(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(hash(b"foobar"), 0x8594_4171_f739_67e8);
+    }
+
+    #[test]
+    fn cached_runs_skip_unchanged_files() {
+        let dir = env::temp_dir().join(format!("rust-size-{}-analyze", process::id()));
+        let cache = dir.with_extension("cache");
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        let object = |size| {
+            Elf::object().section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &vec![0; size])
+                .build()
+        };
+        fs::write(dir.join("sub/b.o"), object(40)).unwrap();
+        fs::write(dir.join("a.o"), object(8)).unwrap();
+        fs::write(dir.join("notes.txt"), "not an object").unwrap();
+        // Too short for its section headers, so not taken for an object file.
+        fs::write(dir.join("short.o"), &object(8)[..70]).unwrap();
+
+        let run = || {
+            let (entries, failure) = analyze(&dir, Some(&cache), false).unwrap();
+            let entries: Vec<_> = entries.into_iter().map(|e| {
+                let name = Path::new(&e.path).strip_prefix(&dir).unwrap().display().to_string();
+                (name, e.sizes.total, e.cached)
+            }).collect();
+            (entries, failure.map(|f| f.failed))
+        };
+        assert_eq!(run(), (vec![("a.o".to_string(), 8, false),
+                                ("sub/b.o".to_string(), 40, false)], None));
+        fs::write(dir.join("a.o"), object(24)).unwrap();
+        fs::remove_file(dir.join("short.o")).unwrap();
+        assert_eq!(run(), (vec![("a.o".to_string(), 24, false),
+                                ("sub/b.o".to_string(), 40, true)], None));
+        let cached: Cache = serde_json::from_slice(&fs::read(&cache).unwrap()).unwrap();
+        assert_eq!(cached.files.len(), 3);
+        fs::remove_dir_all(&dir).unwrap();
+        fs::remove_file(&cache).unwrap();
+    }
+}
@@ -0,0 +1,295 @@
+use analyze;
+use compare::Column;
+use exit::UsageError;
+use failure::Error;
+use goblin::elf::program_header::{ProgramHeader, PT_LOAD};
+use goblin::Object;
+use map_file;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use zip;
+
+/// The Android ABIs, in the order they are listed.
+const ABIS: &[&str] = &[
+    "arm64-v8a", "armeabi-v7a", "x86_64", "x86", "riscv64", "armeabi", "mips64", "mips",
+];
+
+/// The ABI and library name of the native library at `path`, if it is in a directory named
+/// after an ABI, as under `lib/` in an APK, `<module>/lib/` in an AAB, or `jniLibs/` in a
+/// source tree.
+pub fn abi_library(path: &str) -> Option<(&'static str, &str)> {
+    let mut components = path.rsplit(['/', '\\']);
+    let library = components.next()?;
+    let abi = components.next()?;
+    if !library.ends_with(".so") {
+        return None;
+    }
+    ABIS.iter().find(|a| **a == abi).map(|a| (*a, library))
+}
+
+/// One native library, built for several ABIs.
+#[derive(Clone, Debug, Serialize)]
+pub struct Library {
+    pub name: String,
+    /// The total size of the allocated sections of each build of the library, by ABI.
+    pub sizes: BTreeMap<String, u64>,
+    /// The ABIs whose build is disproportionately large, each with its size relative to the
+    /// median of the other ABIs, as a percentage.
+    pub outliers: BTreeMap<String, f64>,
+}
+
+/// The sizes of the native libraries of an app across ABIs, as emitted by
+/// `android-abis --format json`.
+#[derive(Clone, Debug, Serialize)]
+pub struct AbiReport {
+    /// The ABIs the app has libraries for.
+    pub abis: Vec<String>,
+    pub libraries: Vec<Library>,
+}
+
+/// The median of `values`, which mustn't be empty.
+fn median(values: &mut [u64]) -> f64 {
+    values.sort();
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) as f64 / 2.0
+    } else {
+        values[mid] as f64
+    }
+}
+
+/// A native library of an app.
+pub struct NativeLibrary<'a> {
+    pub abi: &'static str,
+    /// The file name of the library.
+    pub name: &'a str,
+    /// The path of the library, in the app or on disk.
+    pub path: &'a str,
+    pub buf: &'a [u8],
+    /// For a library stored uncompressed in an APK or AAB, the offset of its contents there.
+    pub stored_offset: Option<usize>,
+}
+
+/// Call `f` on each native library in `path`, which is an APK, an AAB or a directory.
+pub fn for_each_library<F>(path: &Path, mut f: F) -> Result<(), Error>
+    where F: FnMut(NativeLibrary) -> Result<(), Error>
+{
+    if fs::metadata(path).map_err(|e| format_err!("{}: {}", path.display(), e))?.is_dir() {
+        let mut paths = Vec::new();
+        analyze::walk(path, &mut paths)?;
+        for file in &paths {
+            if let Some((abi, name)) = abi_library(file) {
+                let buf = map_file(file.as_ref())?;
+                f(NativeLibrary { abi, name, path: file, buf: &buf, stored_offset: None })?;
+            }
+        }
+        return Ok(());
+    }
+    let buf = map_file(path.as_os_str())?;
+    if !zip::is_zip(&buf) {
+        return Err(UsageError(format!("{}: not an APK, AAB or directory", path.display()))
+                   .into());
+    }
+    for entry in zip::entries(&buf)? {
+        if let Some((abi, name)) = abi_library(&entry.name) {
+            f(NativeLibrary {
+                abi,
+                name,
+                path: &entry.name,
+                buf: &entry.read(&buf)?,
+                stored_offset: if entry.is_stored() { Some(entry.data_offset) } else { None },
+            })?;
+        }
+    }
+    Ok(())
+}
+
+impl AbiReport {
+    /// Compare the native libraries in `path`, which is an APK, an AAB or a directory. A build
+    /// of a library is an outlier if it is more than `threshold` percent larger than the median
+    /// of its builds for the other ABIs.
+    pub fn new(path: &Path, threshold: f64) -> Result<AbiReport, Error> {
+        let mut libraries: BTreeMap<String, BTreeMap<String, u64>> = BTreeMap::new();
+        for_each_library(path, |library| {
+            let column = Column::new(library.path.to_string(), library.buf, false, false)?;
+            *libraries.entry(library.name.to_string()).or_default()
+                .entry(library.abi.to_string()).or_insert(0) += column.total;
+            Ok(())
+        })?;
+        let abis = ABIS.iter()
+            .filter(|abi| libraries.values().any(|sizes| sizes.contains_key(**abi)))
+            .map(|abi| abi.to_string())
+            .collect();
+        let libraries = libraries.into_iter().map(|(name, sizes)| {
+            let mut outliers = BTreeMap::new();
+            for (abi, &size) in &sizes {
+                let mut others: Vec<u64> = sizes.iter()
+                    .filter(|(other, _)| *other != abi)
+                    .map(|(_, &size)| size)
+                    .collect();
+                if others.is_empty() {
+                    continue;
+                }
+                let median = median(&mut others);
+                let excess = (size as f64 - median) * 100.0 / median;
+                if median > 0.0 && excess > threshold {
+                    outliers.insert(abi.clone(), excess);
+                }
+            }
+            Library { name, sizes, outliers }
+        }).collect();
+        Ok(AbiReport { abis, libraries })
+    }
+}
+
+/// How ready a shared library is for devices with a larger page size, as emitted by
+/// `page-size --format json`.
+#[derive(Clone, Debug, Serialize)]
+pub struct PageAlignment {
+    pub path: String,
+    /// The smallest alignment of the library's LOAD segments.
+    pub alignment: u64,
+    /// Whether every LOAD segment is aligned to the page size, so the library can be loaded.
+    pub aligned: bool,
+    /// For a library stored uncompressed in an APK or AAB, whether its contents start on a page
+    /// boundary, so that it can be mapped straight from the app (as `zipalign -P` arranges).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub zip_aligned: Option<bool>,
+    /// The bytes that re-aligning would add to the file, if each LOAD segment after the first
+    /// starts on a new page (as in the layouts of `-z separate-loadable-segments`, or GNU ld's
+    /// `-z separate-code`). lld's default layout only pads in memory.
+    pub file_padding: u64,
+    /// The bytes that re-aligning would add to the pages the LOAD segments are mapped into.
+    pub memory_padding: u64,
+}
+
+fn align_up(value: u64, alignment: u64) -> u64 {
+    value.div_ceil(alignment) * alignment
+}
+
+impl PageAlignment {
+    /// Check the alignment of the LOAD segments of the ELF shared library `buf` against
+    /// `page_size`, which must be a power of two.
+    pub fn new(path: &str, buf: &[u8], stored_offset: Option<usize>, page_size: u64)
+               -> Result<PageAlignment, Error> {
+        let elf = match Object::parse(buf)? {
+            Object::Elf(elf) => elf,
+            _ => bail!("{}: not an ELF shared library", path),
+        };
+        let mut loads: Vec<&ProgramHeader> = elf.program_headers.iter()
+            .filter(|ph| ph.p_type == PT_LOAD)
+            .collect();
+        loads.sort_by_key(|ph| ph.p_offset);
+        let alignment = loads.iter().map(|ph| ph.p_align).min().unwrap_or(0);
+        let aligned = !loads.is_empty() && alignment >= page_size &&
+            loads.iter().all(|ph| ph.p_offset % page_size == ph.p_vaddr % page_size);
+
+        let (mut file_padding, mut memory_padding) = (0, 0);
+        if !aligned {
+            // Move each segment after the first forward to the next page boundary, keeping
+            // the distance from the one before.
+            let mut shift = 0;
+            for ph in loads.iter().skip(1) {
+                shift = align_up(ph.p_offset + shift, page_size) - ph.p_offset;
+            }
+            file_padding = shift;
+            let pages = |size: u64| -> u64 {
+                loads.iter()
+                    .map(|ph| align_up(ph.p_vaddr + ph.p_memsz, size) - ph.p_vaddr / size * size)
+                    .sum()
+            };
+            let current = alignment.clamp(1, page_size);
+            memory_padding = pages(page_size).saturating_sub(pages(current));
+        }
+        Ok(PageAlignment {
+            path: path.to_string(),
+            alignment,
+            aligned,
+            zip_aligned: stored_offset.map(|offset| (offset as u64).is_multiple_of(page_size)),
+            file_padding,
+            memory_padding,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::elf::program_header::{PF_R, PF_W, PF_X};
+    use goblin::elf::section_header::{SHF_ALLOC, SHF_EXECINSTR, SHF_WRITE, SHT_PROGBITS};
+    use std::env;
+    use std::process;
+    use testelf::Elf;
+
+    fn library(size: usize) -> Vec<u8> {
+        Elf::shared().section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &vec![0; size])
+            .build()
+    }
+
+    #[test]
+    fn libraries_by_abi() {
+        assert_eq!(abi_library("lib/arm64-v8a/libxul.so"), Some(("arm64-v8a", "libxul.so")));
+        assert_eq!(abi_library("base/lib/x86/liba.so"), Some(("x86", "liba.so")));
+        assert_eq!(abi_library("src\\main\\jniLibs\\x86_64\\libb.so"), Some(("x86_64", "libb.so")));
+        assert_eq!(abi_library("lib/arm64-v8a/wrap.sh"), None);
+        assert_eq!(abi_library("lib/arm64/libxul.so"), None);
+        assert_eq!(abi_library("libxul.so"), None);
+        assert_eq!(median(&mut [5, 1, 3]), 3.0);
+        assert_eq!(median(&mut [4, 1, 3, 10]), 3.5);
+    }
+
+    #[test]
+    fn outliers_among_abis() {
+        let dir = env::temp_dir().join(format!("rust-size-{}-android", process::id()));
+        let libraries = [("arm64-v8a", "libxul.so", 100), ("armeabi-v7a", "libxul.so", 90),
+                         ("x86_64", "libxul.so", 130), ("x86_64", "libonly.so", 10)];
+        for &(abi, name, size) in &libraries {
+            fs::create_dir_all(dir.join("lib").join(abi)).unwrap();
+            fs::write(dir.join("lib").join(abi).join(name), library(size)).unwrap();
+        }
+        fs::write(dir.join("lib/x86_64/README"), "not a library").unwrap();
+        let report = AbiReport::new(&dir, 20.0).unwrap();
+        assert_eq!(report.abis, vec!["arm64-v8a", "armeabi-v7a", "x86_64"]);
+        let only = &report.libraries[0];
+        assert_eq!((only.name.as_str(), only.sizes.len(), only.outliers.len()),
+                   ("libonly.so", 1, 0));
+        let xul = &report.libraries[1];
+        assert_eq!(xul.sizes.values().cloned().collect::<Vec<_>>(), vec![100, 90, 130]);
+        // 130 is more than 20% over the median of 100 and 90.
+        assert_eq!(xul.outliers.keys().collect::<Vec<_>>(), vec!["x86_64"]);
+        assert!((xul.outliers["x86_64"] - 36.842).abs() < 0.001);
+        fs::remove_dir_all(&dir).unwrap();
+
+        let err = AbiReport::new(Path::new("Cargo.toml"), 20.0).err().unwrap();
+        assert!(err.downcast_ref::<UsageError>().is_some());
+    }
+
+    fn segmented(align: u64) -> Vec<u8> {
+        Elf::shared()
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &[0; 256])
+            .section(".data", SHT_PROGBITS, SHF_ALLOC | SHF_WRITE, &[0; 64])
+            .segment(PT_LOAD, PF_R | PF_X, &[".text"], align)
+            .segment(PT_LOAD, PF_R | PF_W, &[".data"], align)
+            .build()
+    }
+
+    #[test]
+    fn load_segments_against_page_sizes() {
+        let buf = segmented(0x1000);
+        let page = PageAlignment::new("libxul.so", &buf, Some(100), 0x4000).unwrap();
+        assert_eq!((page.alignment, page.aligned, page.zip_aligned), (0x1000, false, Some(false)));
+        // `.data` starts 432 bytes into the file, and would move to the next 16KB page.
+        assert_eq!(page.file_padding, 0x4000 - 432);
+        // Each segment's single 4KB page becomes a 16KB one.
+        assert_eq!(page.memory_padding, 2 * (0x4000 - 0x1000));
+
+        let page = PageAlignment::new("libxul.so", &buf, None, 0x1000).unwrap();
+        assert!(page.aligned && page.zip_aligned.is_none());
+        let buf = segmented(0x4000);
+        let page = PageAlignment::new("libxul.so", &buf, Some(0x8000), 0x4000).unwrap();
+        assert_eq!((page.aligned, page.zip_aligned, page.file_padding, page.memory_padding),
+                   (true, Some(true), 0, 0));
+        assert!(PageAlignment::new("x.dex", b"dex\n035\0", None, 0x4000).is_err());
+    }
+}
@@ -0,0 +1,179 @@
+use archive;
+use coff;
+use failure::Error;
+use goblin::elf::header::{EM_386, EM_AARCH64, EM_ARM, EM_MIPS, EM_PPC, EM_PPC64};
+use goblin::elf::header::{EM_RISCV, EM_S390, EM_SPARCV9, EM_X86_64, ELFCLASS64};
+use goblin::mach::constants::cputype::{CPU_TYPE_ARM, CPU_TYPE_ARM64};
+use goblin::mach::constants::cputype::{CPU_TYPE_I386, CPU_TYPE_POWERPC, CPU_TYPE_POWERPC64};
+use goblin::mach::constants::cputype::{CPU_TYPE_X86_64, CpuType};
+use goblin::mach::fat::FatArch;
+use goblin::mach::Mach;
+use goblin::Object;
+use linkmap;
+use saved;
+use te;
+use wasm;
+
+/// Newer machine and CPU types that goblin doesn't know about yet.
+const EM_LOONGARCH: u16 = 258;
+const CPU_TYPE_ARM64_32: CpuType = 0x0200_000c;
+
+/// The name of a Mach-O CPU type, using the same architecture names as for other formats.
+pub fn mach_arch(cputype: CpuType) -> &'static str {
+    match cputype {
+        CPU_TYPE_X86_64 => "x86_64",
+        CPU_TYPE_I386 => "x86",
+        CPU_TYPE_ARM64 => "aarch64",
+        CPU_TYPE_ARM64_32 => "arm64_32",
+        CPU_TYPE_ARM => "arm",
+        CPU_TYPE_POWERPC => "powerpc",
+        CPU_TYPE_POWERPC64 => "powerpc64",
+        _ => "unknown",
+    }
+}
+
+/// The slice of the universal binary `buf` that `arch` describes, or an error if the file ends
+/// before it does, which goblin's `FatArch::slice` would panic on.
+pub fn fat_slice<'a>(buf: &'a [u8], arch: &FatArch) -> Result<&'a [u8], Error> {
+    buf.get(arch.offset as usize..).and_then(|b| b.get(..arch.size as usize))
+        .ok_or_else(|| {
+            format_err!("The {} slice of the universal binary is past the end of the file",
+                        mach_arch(arch.cputype))
+        })
+}
+
+/// A slice of a universal binary and its architecture, or a whole file with none.
+pub type Slice<'a> = (Option<String>, &'a [u8]);
+
+/// The slices of the universal binary `buf` by architecture, or `buf` alone, with no
+/// architecture, if it isn't one.
+pub fn universal_slices(buf: &[u8]) -> Result<Vec<Slice<'_>>, Error> {
+    match Mach::parse(buf) {
+        Ok(Mach::Fat(fat)) => {
+            fat.arches()?.iter()
+                .map(|arch| Ok((Some(mach_arch(arch.cputype).to_string()), fat_slice(buf, arch)?)))
+                .collect()
+        }
+        _ => Ok(vec![(None, buf)]),
+    }
+}
+
+/// The name of a PE and COFF machine type.
+fn pe_arch(machine: u16) -> &'static str {
+    match machine {
+        0x8664 => "x86_64",
+        0x14c => "x86",
+        0xaa64 | 0xa641 => "aarch64",
+        0x1c0 | 0x1c2 | 0x1c4 => "arm",
+        0x5032 => "riscv32",
+        0x5064 => "riscv64",
+        0x6264 => "loongarch64",
+        // EFI byte code, which UEFI drivers are compiled to for an interpreter.
+        0xebc => "ebc",
+        _ => "unknown",
+    }
+}
+
+/// Return the name of the architecture that the object file in `buf` targets, using the naming
+/// of Rust target triples (`x86_64`, `aarch64`, `wasm32`, ...) regardless of the file format.
+/// Universal binaries report all of their architectures joined with `+`.
+pub fn arch(buf: &[u8]) -> Result<String, Error> {
+    if wasm::is_wasm(buf) {
+        return Ok("wasm32".to_string());
+    }
+    if archive::is_archive(buf) {
+        return Ok("unknown".to_string());
+    }
+    if let Some(machine) = coff::machine(buf).or_else(|| te::machine(buf)) {
+        return Ok(pe_arch(machine).to_string());
+    }
+    if linkmap::is_map(buf) {
+        return Ok(linkmap::arch(buf));
+    }
+    if saved::is_report(buf) {
+        return Ok(saved::arch(buf));
+    }
+    Ok(match Object::parse(buf)? {
+        Object::Elf(elf) => {
+            let is_64 = elf.header.e_ident[4] == ELFCLASS64;
+            match elf.header.e_machine {
+                EM_X86_64 => "x86_64",
+                EM_386 => "x86",
+                EM_AARCH64 => "aarch64",
+                EM_ARM => "arm",
+                EM_RISCV if is_64 => "riscv64",
+                EM_RISCV => "riscv32",
+                EM_PPC64 => "powerpc64",
+                EM_PPC => "powerpc",
+                EM_MIPS if is_64 => "mips64",
+                EM_MIPS => "mips",
+                EM_S390 => "s390x",
+                EM_SPARCV9 => "sparc64",
+                EM_LOONGARCH => "loongarch64",
+                _ => "unknown",
+            }.to_string()
+        },
+        Object::PE(pe) => pe_arch(pe.header.coff_header.machine).to_string(),
+        Object::Mach(Mach::Binary(mach)) => mach_arch(mach.header.cputype()).to_string(),
+        Object::Mach(Mach::Fat(fat)) => {
+            let arches: Vec<&str> = fat.arches()?.iter().map(|arch| mach_arch(arch.cputype))
+                .collect();
+            arches.join("+")
+        },
+        Object::Archive(_) => "unknown".to_string(),
+        Object::Unknown(magic) => bail!("Unknown file magic: {:#x}", magic),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use testelf::Elf;
+
+    #[test]
+    fn fat_slice_in_bounds() {
+        let buf: Vec<u8> = (0..32).collect();
+        let arch = FatArch { cputype: CPU_TYPE_ARM64, offset: 8, size: 4, ..Default::default() };
+        assert_eq!(fat_slice(&buf, &arch).unwrap(), &[8, 9, 10, 11]);
+    }
+
+    #[test]
+    fn universal_slices_by_arch() {
+        // A universal header with an x86_64 slice of 4 bytes at offset 28.
+        let mut fat = vec![0xca, 0xfe, 0xba, 0xbe, 0, 0, 0, 1, 1, 0, 0, 7, 0, 0, 0, 3,
+                           0, 0, 0, 28, 0, 0, 0, 4, 0, 0, 0, 0];
+        fat.extend_from_slice(b"abcd");
+        let slices = universal_slices(&fat).unwrap();
+        assert_eq!(slices, vec![(Some("x86_64".to_string()), &b"abcd"[..])]);
+        let thin = b"\x7fELF";
+        assert_eq!(universal_slices(thin).unwrap(), vec![(None, &thin[..])]);
+    }
+
+    #[test]
+    fn fat_slice_past_end() {
+        let buf = [0u8; 208];
+        let arch = FatArch { cputype: CPU_TYPE_X86_64, offset: 28, size: 100_000,
+                             ..Default::default() };
+        let err = fat_slice(&buf, &arch).unwrap_err().to_string();
+        assert!(err.contains("x86_64"), "{}", err);
+        let arch = FatArch { offset: 300, size: 0, ..Default::default() };
+        assert!(fat_slice(&buf, &arch).is_err());
+        let arch = FatArch { offset: u32::MAX, size: u32::MAX, ..Default::default() };
+        assert!(fat_slice(&buf, &arch).is_err());
+    }
+
+    #[test]
+    fn arch_names() {
+        assert_eq!(arch(&Elf::object().build()).unwrap(), "x86_64");
+        assert_eq!(arch(b"\0asm\x01\0\0\0").unwrap(), "wasm32");
+        let mut fat = vec![0xca, 0xfe, 0xba, 0xbe, 0, 0, 0, 2];
+        for &(cputype, offset) in &[(7u32 | 0x0100_0000, 48u32), (12 | 0x0100_0000, 52)] {
+            for field in &[cputype, 3, offset, 4, 0] {
+                fat.extend_from_slice(&field.to_be_bytes());
+            }
+        }
+        fat.extend_from_slice(&[0; 8]);
+        assert_eq!(arch(&fat).unwrap(), "x86_64+aarch64");
+    }
+}
+
@@ -0,0 +1,327 @@
+use coff;
+use failure::Error;
+use goblin::Object;
+use memmap::Mmap;
+use std::fs::File;
+use std::ops::Deref;
+use std::path::Path;
+use std::str;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use wasm;
+
+const ARCHIVE_MAGIC: &[u8] = b"!<arch>\n";
+const THIN_ARCHIVE_MAGIC: &[u8] = b"!<thin>\n";
+const HEADER_SIZE: usize = 60;
+
+/// Whether `buf` is a static archive, either regular or GNU thin.
+pub fn is_archive(buf: &[u8]) -> bool {
+    buf.starts_with(ARCHIVE_MAGIC) || buf.starts_with(THIN_ARCHIVE_MAGIC)
+}
+
+/// The contents of an archive member: either part of the archive itself, or, for members of
+/// thin archives, a separate file. Members of archives nested in thin archives are copied out,
+/// since they can't borrow from the nested archive's mapping.
+pub enum MemberData<'a> {
+    Inline(&'a [u8]),
+    File(Mmap),
+    Owned(Vec<u8>),
+}
+
+impl<'a> Deref for MemberData<'a> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match *self {
+            MemberData::Inline(data) => data,
+            MemberData::File(ref mmap) => mmap,
+            MemberData::Owned(ref data) => data,
+        }
+    }
+}
+
+/// An object file in an archive.
+pub struct Member<'a> {
+    /// The name of the member, prefixed with the names of the archives it is nested in, as in
+    /// `inner.a(foo.o)`.
+    pub name: String,
+    pub data: MemberData<'a>,
+}
+
+/// Whether `buf` looks like an object file that `sections` can handle, as opposed to e.g. the
+/// `lib.rmeta` in an rlib.
+pub fn is_object(buf: &[u8]) -> bool {
+    wasm::is_wasm(buf) || coff::is_coff(buf) || match Object::parse(buf) {
+        Ok(Object::Unknown(_)) | Err(_) => false,
+        Ok(_) => true,
+    }
+}
+
+/// Parse a decimal header field.
+fn parse_field(field: &[u8]) -> Result<usize, Error> {
+    match str::from_utf8(field).ok().and_then(|s| s.trim().parse().ok()) {
+        Some(n) => Ok(n),
+        None => bail!("Invalid archive member header"),
+    }
+}
+
+/// Look up the name at `offset` in the GNU long name table `names`.
+fn long_name(names: &[u8], offset: usize) -> Result<String, Error> {
+    let name = match names.get(offset..) {
+        Some(rest) => rest.split(|&b| b == b'\n').next().unwrap_or(rest),
+        None => bail!("Archive member name offset {} is out of range", offset),
+    };
+    let name = name.strip_suffix(b"/").unwrap_or(name);
+    Ok(String::from_utf8_lossy(name).into_owned())
+}
+
+/// Append the object file members of the archive `buf` to `members`, recursing into nested
+/// archives. `path` is the path of the archive, against which the members of thin archives are
+/// resolved. The names of nested members are prefixed with `prefix`. With `objects_only`,
+/// members that aren't object files are skipped.
+fn collect<'a>(buf: &'a [u8], path: &Path, prefix: &str, objects_only: bool,
+               members: &mut Vec<Member<'a>>) -> Result<(), Error> {
+    let thin = buf.starts_with(THIN_ARCHIVE_MAGIC);
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut names: &[u8] = &[];
+    let mut pos = ARCHIVE_MAGIC.len();
+    while pos + HEADER_SIZE <= buf.len() {
+        let header = &buf[pos..pos + HEADER_SIZE];
+        if &header[58..60] != b"`\n" {
+            bail!("Invalid archive member header at offset {}", pos);
+        }
+        let raw_name = str::from_utf8(&header[..16]).unwrap_or("").trim_end();
+        let size = parse_field(&header[48..58])?;
+        let mut start = pos + HEADER_SIZE;
+
+        let symbol_table = raw_name == "/" || raw_name == "/SYM64/" ||
+            raw_name.starts_with("__.SYMDEF");
+        let name = if symbol_table {
+            None
+        } else if raw_name == "//" {
+            names = buf.get(start..start + size).unwrap_or(&[]);
+            None
+        } else if let Some(offset) = raw_name.strip_prefix('/') {
+            Some(long_name(names, parse_field(offset.as_bytes())?)?)
+        } else if let Some(len) = raw_name.strip_prefix("#1/") {
+            // BSD archives store long names right before the member's contents.
+            let len = parse_field(len.as_bytes())?;
+            let name = buf.get(start..start + len).unwrap_or(&[]);
+            start += len;
+            let name = name.split(|&b| b == 0).next().unwrap_or(name);
+            Some(String::from_utf8_lossy(name).into_owned())
+        } else {
+            Some(raw_name.strip_suffix('/').unwrap_or(raw_name).to_string())
+        };
+
+        // Thin archives only embed their symbol and name tables, and reference every other
+        // member by its path relative to the archive.
+        let inline = !thin || name.is_none();
+        let end = pos + HEADER_SIZE + size;
+        if let Some(name) = name {
+            let data = if inline {
+                match buf.get(start..end) {
+                    Some(data) => MemberData::Inline(data),
+                    None => bail!("Archive member {} extends past the end of the file", name),
+                }
+            } else {
+                let member_path = dir.join(&name);
+                // Thin archives break when their members are moved or deleted, so say which
+                // archive the missing file belongs to.
+                let f = File::open(&member_path).map_err(|e| {
+                    format_err!("Member {} of thin archive {}: {}: {}", name, path.display(),
+                                member_path.display(), e)
+                })?;
+                MemberData::File(unsafe { Mmap::map(&f)? })
+            };
+            let full_name = if prefix.is_empty() {
+                name.clone()
+            } else {
+                format!("{}({})", prefix, name)
+            };
+            if is_archive(&data) {
+                let nested_path = if inline { path.to_path_buf() } else { dir.join(&name) };
+                collect_nested(data, &nested_path, &full_name, objects_only, members)?;
+            } else if !objects_only || is_object(&data) {
+                members.push(Member { name: full_name, data });
+            }
+        }
+
+        pos = if inline { end } else { pos + HEADER_SIZE };
+        // Members are aligned to two bytes.
+        pos += pos % 2;
+    }
+    Ok(())
+}
+
+/// Collect the members of a nested archive, which may be part of its parent or be a file of
+/// its own.
+fn collect_nested<'a>(data: MemberData<'a>, path: &Path, prefix: &str, objects_only: bool,
+                      members: &mut Vec<Member<'a>>) -> Result<(), Error> {
+    if let MemberData::Inline(data) = data {
+        return collect(data, path, prefix, objects_only, members);
+    }
+    let mut nested = Vec::new();
+    collect(&data, path, prefix, objects_only, &mut nested)?;
+    for member in nested {
+        let data = match member.data {
+            MemberData::Inline(data) => MemberData::Owned(data.to_vec()),
+            MemberData::File(mmap) => MemberData::File(mmap),
+            MemberData::Owned(data) => MemberData::Owned(data),
+        };
+        members.push(Member { name: member.name, data });
+    }
+    Ok(())
+}
+
+/// Return the object files in the archive `buf`, read from `path`, including those in nested
+/// archives and, for thin archives, those stored next to the archive. Members that aren't
+/// object files are skipped.
+pub fn members<'a>(buf: &'a [u8], path: &Path) -> Result<Vec<Member<'a>>, Error> {
+    let mut members = Vec::new();
+    collect(buf, path, "", true, &mut members)?;
+    Ok(members)
+}
+
+/// Like `members`, but including the members that aren't object files.
+pub fn all_members<'a>(buf: &'a [u8], path: &Path) -> Result<Vec<Member<'a>>, Error> {
+    let mut members = Vec::new();
+    collect(buf, path, "", false, &mut members)?;
+    Ok(members)
+}
+
+/// Call `f` on each of `members` on a pool of threads, one per CPU, and return the results in
+/// member order. Members parse independently of each other, and large archives (libxul's, say)
+/// have thousands of them.
+pub fn par_map<'a, T, F>(members: &[Member<'a>], f: F) -> Vec<T>
+    where T: Send,
+          F: Fn(&Member<'a>) -> T + Sync,
+{
+    let threads = thread::available_parallelism().map_or(1, |n| n.get()).min(members.len());
+    if threads <= 1 {
+        return members.iter().map(f).collect();
+    }
+    // Members vary a lot in size, so threads take the next member as they finish rather than
+    // being handed an equal share up front.
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(Vec::with_capacity(members.len()));
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                match members.get(i) {
+                    Some(member) => {
+                        let result = f(member);
+                        results.lock().unwrap().push((i, result));
+                    }
+                    None => break,
+                }
+            });
+        }
+    });
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|&(i, _)| i);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::elf::section_header::SHT_PROGBITS;
+    use std::{env, fs, process};
+    use testarchive::{archive, member};
+    use testelf::Elf;
+
+    fn names(members: &[Member]) -> Vec<(String, usize)> {
+        members.iter().map(|m| (m.name.clone(), m.data.len())).collect()
+    }
+
+    #[test]
+    fn regular_gnu_and_bsd_names() {
+        let object = Elf::object().build();
+        let long = b"a_rather_long_member_name.o/\nother_long_member.o/\n";
+        let mut bsd_name = b"bsd_long_name.o\0".to_vec();
+        bsd_name.extend_from_slice(&object);
+        let buf = archive(&[
+            member("/", &[0, 0, 0, 0]),
+            member("//", long),
+            member("short.o/", &object),
+            member("/29", &object),
+            member("#1/16", &bsd_name),
+            member("lib.rmeta/", b"not an object"),
+        ]);
+        let path = Path::new("libfoo.a");
+        assert!(is_archive(&buf));
+        assert_eq!(names(&members(&buf, path).unwrap()), vec![
+            ("short.o".to_string(), object.len()),
+            ("other_long_member.o".to_string(), object.len()),
+            ("bsd_long_name.o".to_string(), object.len()),
+        ]);
+        let all = all_members(&buf, path).unwrap();
+        assert_eq!(all.last().map(|m| &*m.name), Some("lib.rmeta"));
+    }
+
+    #[test]
+    fn nested_archives_prefix_names() {
+        let object = Elf::object().build();
+        let inner = archive(&[member("x.o/", &object), member("y.o/", &object)]);
+        let buf = archive(&[member("inner.a/", &inner), member("z.o/", &object)]);
+        let names: Vec<_> = names(&members(&buf, Path::new("outer.a")).unwrap()).into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(names, vec!["inner.a(x.o)", "inner.a(y.o)", "z.o"]);
+    }
+
+    #[test]
+    fn malformed_archives() {
+        let object = Elf::object().build();
+        let mut buf = archive(&[member("a.o/", &object)]);
+        buf.truncate(buf.len() - 10);
+        let err = members(&buf, Path::new("a.a")).err().unwrap().to_string();
+        assert!(err.contains("past the end"), "{}", err);
+        let mut buf = archive(&[member("a.o/", &object)]);
+        buf[8 + 58] = b'x';
+        assert!(members(&buf, Path::new("a.a")).is_err());
+        let buf = archive(&[member("/99", &object)]);
+        assert!(members(&buf, Path::new("a.a")).is_err());
+    }
+
+    #[test]
+    fn par_map_keeps_member_order() {
+        let data: Vec<Vec<u8>> = (0..100).map(|i| vec![0; i * 37 % 101]).collect();
+        let members: Vec<Member> = data.iter().enumerate().map(|(i, data)| {
+            Member { name: format!("{}.o", i), data: MemberData::Inline(data) }
+        }).collect();
+        let results = par_map(&members, |m| (m.name.clone(), m.data.len()));
+        assert_eq!(results, names(&members));
+        assert!(par_map(&[], |m| m.data.len()).is_empty());
+    }
+
+    #[test]
+    fn thin_archives_reference_files_next_to_them() {
+        let dir = env::temp_dir().join(format!("rust-size-{}-thin", process::id()));
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        let (a, b) = (Elf::object().build(),
+                      Elf::object().section(".data", SHT_PROGBITS, 0, &[0; 32]).build());
+        fs::write(dir.join("a.o"), &a).unwrap();
+        fs::write(dir.join("sub/b.o"), &b).unwrap();
+        // Only the symbol and name tables are embedded; the headers of the other members give
+        // the sizes of their files.
+        let mut buf = b"!<thin>\n".to_vec();
+        buf.extend_from_slice(&member("/", &[0, 0, 0, 0]));
+        buf.extend_from_slice(&member("//", b"sub/b.o/\n"));
+        buf.extend_from_slice(&member("a.o/", &a)[..HEADER_SIZE]);
+        buf.extend_from_slice(&member("/0", &b)[..HEADER_SIZE]);
+        let path = dir.join("libthin.a");
+        assert!(is_archive(&buf));
+        assert_eq!(names(&members(&buf, &path).unwrap()),
+                   vec![("a.o".to_string(), a.len()), ("sub/b.o".to_string(), b.len())]);
+
+        fs::remove_file(dir.join("sub/b.o")).unwrap();
+        let err = members(&buf, &path).err().unwrap().to_string();
+        assert!(err.starts_with(&format!("Member sub/b.o of thin archive {}", path.display())),
+                "{}", err);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
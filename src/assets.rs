@@ -0,0 +1,445 @@
+use dwarf;
+use failure::Error;
+use gimli::{self, AttributeValue, EndianSlice, Operation, RunTimeEndian};
+use goblin::Object;
+use rustc_demangle;
+use section_records;
+use std::collections::HashMap;
+
+type Reader<'a> = EndianSlice<'a, RunTimeEndian>;
+type Unit<'a> = gimli::Unit<Reader<'a>>;
+
+/// A static byte array or string, as `include_bytes!` and `include_str!` produce, as emitted by
+/// `assets --format json`.
+#[derive(Clone, Debug, Serialize)]
+pub struct Asset {
+    /// The name of the static, with its module path if the debug info has a linkage name.
+    pub name: String,
+    /// The size of the bytes.
+    pub size: u64,
+    /// The address of the bytes, which for references is where they point.
+    pub address: u64,
+    /// The file and line that declare the static.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    /// `array`, `reference` (`&[u8; N]`), `slice` or `str`.
+    pub kind: &'static str,
+}
+
+/// How to read the statics of a binary.
+struct Memory<'a> {
+    buf: &'a [u8],
+    /// The address, file offset and size of each section with contents.
+    sections: Vec<(u64, u64, u64)>,
+    /// Relative relocations by the address they apply to, with the address they produce, for
+    /// pointers that are only filled in at load time.
+    relocated: HashMap<u64, u64>,
+    pointer_size: usize,
+    little_endian: bool,
+}
+
+impl<'a> Memory<'a> {
+    /// Read the pointer-sized integer at `address`.
+    fn pointer(&self, address: u64) -> Option<u64> {
+        if let Some(&target) = self.relocated.get(&address) {
+            return Some(target);
+        }
+        let &(start, offset, _) = self.sections.iter()
+            .find(|&&(start, _, size)| address >= start && address < start + size)?;
+        let offset = (offset + address - start) as usize;
+        let chunk = self.buf.get(offset..offset + self.pointer_size)?;
+        let mut bytes = [0; 8];
+        Some(if self.little_endian {
+            bytes[..self.pointer_size].copy_from_slice(chunk);
+            u64::from_le_bytes(bytes)
+        } else {
+            bytes[8 - self.pointer_size..].copy_from_slice(chunk);
+            u64::from_be_bytes(bytes)
+        })
+    }
+}
+
+/// The string value of the attribute `name` of `entry`.
+fn string_attr(dwarf: &gimli::Dwarf<Reader>, unit: &Unit,
+               entry: &gimli::DebuggingInformationEntry<Reader>, name: gimli::DwAt)
+               -> Result<Option<String>, Error> {
+    Ok(match entry.attr_value(name)? {
+        Some(value) => Some(dwarf.attr_string(unit, value)?.to_string_lossy().into_owned()),
+        None => None,
+    })
+}
+
+/// The type that the `DW_AT_type` of `entry` refers to, looking through typedefs and
+/// qualifiers.
+fn type_of<'u>(unit: &'u Unit, entry: &gimli::DebuggingInformationEntry<Reader>)
+               -> Result<Option<gimli::DebuggingInformationEntry<'u, 'u, Reader<'u>>>, Error> {
+    let mut offset = match entry.attr_value(gimli::DW_AT_type)? {
+        Some(AttributeValue::UnitRef(offset)) => offset,
+        _ => return Ok(None),
+    };
+    loop {
+        let ty = unit.entry(offset)?;
+        if ![gimli::DW_TAG_typedef, gimli::DW_TAG_const_type, gimli::DW_TAG_volatile_type]
+            .contains(&ty.tag()) {
+            return Ok(Some(ty));
+        }
+        offset = match ty.attr_value(gimli::DW_AT_type)? {
+            Some(AttributeValue::UnitRef(offset)) => offset,
+            _ => return Ok(None),
+        };
+    }
+}
+
+/// The length of `ty`, if it is an array of `u8`.
+fn byte_array_len(dwarf: &gimli::Dwarf<Reader>, unit: &Unit,
+                  ty: &gimli::DebuggingInformationEntry<Reader>) -> Result<Option<u64>, Error> {
+    if ty.tag() != gimli::DW_TAG_array_type {
+        return Ok(None);
+    }
+    let element = match type_of(unit, ty)? {
+        Some(element) => element,
+        None => return Ok(None),
+    };
+    if element.tag() != gimli::DW_TAG_base_type ||
+        string_attr(dwarf, unit, &element, gimli::DW_AT_name)?.as_deref() != Some("u8") {
+        return Ok(None);
+    }
+    let mut tree = unit.entries_tree(Some(ty.offset()))?;
+    let mut children = tree.root()?.children();
+    while let Some(child) = children.next()? {
+        let child = child.entry();
+        if child.tag() != gimli::DW_TAG_subrange_type {
+            continue;
+        }
+        if let Some(count) = child.attr_value(gimli::DW_AT_count)?.and_then(|v| v.udata_value()) {
+            return Ok(Some(count));
+        }
+        if let Some(upper) = child.attr_value(gimli::DW_AT_upper_bound)?
+            .and_then(|v| v.udata_value()) {
+            return Ok(Some(upper + 1));
+        }
+    }
+    Ok(None)
+}
+
+/// The offsets of the `data_ptr` and `length` members of `ty`, if it is a Rust `&[u8]` or
+/// `&str` fat pointer.
+fn fat_pointer(dwarf: &gimli::Dwarf<Reader>, unit: &Unit,
+               ty: &gimli::DebuggingInformationEntry<Reader>)
+               -> Result<Option<(&'static str, u64, u64)>, Error> {
+    if ty.tag() != gimli::DW_TAG_structure_type {
+        return Ok(None);
+    }
+    let kind = match string_attr(dwarf, unit, ty, gimli::DW_AT_name)?.as_deref() {
+        Some("&[u8]") => "slice",
+        Some("&str") => "str",
+        _ => return Ok(None),
+    };
+    let (mut data, mut length) = (None, None);
+    let mut tree = unit.entries_tree(Some(ty.offset()))?;
+    let mut children = tree.root()?.children();
+    while let Some(child) = children.next()? {
+        let child = child.entry();
+        let offset = child.attr_value(gimli::DW_AT_data_member_location)?
+            .and_then(|v| v.udata_value());
+        match string_attr(dwarf, unit, child, gimli::DW_AT_name)?.as_deref() {
+            Some("data_ptr") => data = offset,
+            Some("length") => length = offset,
+            _ => {}
+        }
+    }
+    Ok(data.and_then(|data| length.map(|length| (kind, data, length))))
+}
+
+/// The `file:line` that declares `entry`.
+fn location(dwarf: &gimli::Dwarf<Reader>, unit: &Unit,
+            entry: &gimli::DebuggingInformationEntry<Reader>) -> Result<Option<String>, Error> {
+    let file = match entry.attr_value(gimli::DW_AT_decl_file)? {
+        Some(AttributeValue::FileIndex(index)) => index,
+        Some(value) => match value.udata_value() {
+            Some(index) => index,
+            None => return Ok(None),
+        },
+        None => return Ok(None),
+    };
+    let program = match unit.line_program {
+        Some(ref program) => program,
+        None => return Ok(None),
+    };
+    let header = program.header();
+    let file = match header.file(file) {
+        Some(file) => file,
+        None => return Ok(None),
+    };
+    let mut path = dwarf.attr_string(unit, file.path_name())?.to_string_lossy().into_owned();
+    if !path.starts_with('/') {
+        if let Some(dir) = file.directory(header) {
+            let dir = dwarf.attr_string(unit, dir)?.to_string_lossy().into_owned();
+            if !dir.is_empty() {
+                path = format!("{}/{}", dir, path);
+            }
+        }
+    }
+    if let (false, Some(dir)) = (path.starts_with('/'), unit.comp_dir) {
+        path = format!("{}/{}", dir.to_string_lossy(), path);
+    }
+    Ok(Some(match entry.attr_value(gimli::DW_AT_decl_line)?.and_then(|v| v.udata_value()) {
+        Some(line) => format!("{}:{}", path, line),
+        None => path,
+    }))
+}
+
+/// The address of the static `entry`, if its location is a plain address.
+fn static_address(dwarf: &gimli::Dwarf<Reader>, unit: &Unit,
+                  entry: &gimli::DebuggingInformationEntry<Reader>) -> Result<Option<u64>, Error> {
+    let expr = match entry.attr_value(gimli::DW_AT_location)? {
+        Some(AttributeValue::Exprloc(expr)) => expr,
+        _ => return Ok(None),
+    };
+    let mut ops = expr.operations(unit.encoding());
+    let address = match ops.next()? {
+        Some(Operation::Address { address }) => address,
+        Some(Operation::AddressIndex { index }) => dwarf.address(unit, index)?,
+        _ => return Ok(None),
+    };
+    Ok(if ops.next()?.is_none() { Some(address) } else { None })
+}
+
+/// Find the static byte arrays and strings of at least `min_size` bytes in the DWARF debug
+/// info of `buf`, largest first. Arrays that only references point to are found through the
+/// references, so their size is that of the referenced bytes.
+pub fn assets(buf: &[u8], min_size: u64) -> Result<Vec<Asset>, Error> {
+    let mut memory = Memory {
+        buf,
+        sections: section_records(buf)?.iter()
+            .filter_map(|r| Some((r.address?, r.offset?, r.size)))
+            .collect(),
+        relocated: HashMap::new(),
+        pointer_size: 8,
+        little_endian: true,
+    };
+    match Object::parse(buf)? {
+        Object::Elf(elf) => {
+            memory.pointer_size = if elf.is_64 { 8 } else { 4 };
+            memory.little_endian = elf.little_endian;
+            for reloc in elf.dynrelas.iter().filter(|reloc| reloc.r_sym == 0) {
+                if let Some(addend) = reloc.r_addend {
+                    memory.relocated.insert(reloc.r_offset, addend as u64);
+                }
+            }
+        }
+        Object::Mach(goblin::mach::Mach::Binary(mach)) => {
+            memory.pointer_size = if mach.is_64 { 8 } else { 4 };
+            memory.little_endian = mach.little_endian;
+        }
+        _ => {}
+    }
+
+    let dwarf = dwarf::load(buf)?;
+    let mut assets = Vec::new();
+    let mut headers = dwarf.units();
+    while let Some(header) = headers.next()? {
+        let unit = dwarf.unit(header)?;
+        let mut entries = unit.entries();
+        while let Some((_, entry)) = entries.next_dfs()? {
+            if entry.tag() != gimli::DW_TAG_variable {
+                continue;
+            }
+            let address = match static_address(&dwarf, &unit, entry)? {
+                Some(address) => address,
+                None => continue,
+            };
+            let ty = match type_of(&unit, entry)? {
+                Some(ty) => ty,
+                None => continue,
+            };
+            let found = if let Some(len) = byte_array_len(&dwarf, &unit, &ty)? {
+                Some(("array", address, len))
+            } else if ty.tag() == gimli::DW_TAG_pointer_type {
+                match type_of(&unit, &ty)? {
+                    Some(target) => byte_array_len(&dwarf, &unit, &target)?
+                        .and_then(|len| Some(("reference", memory.pointer(address)?, len))),
+                    None => None,
+                }
+            } else if let Some((kind, data, length)) = fat_pointer(&dwarf, &unit, &ty)? {
+                memory.pointer(address + data).and_then(|data| {
+                    Some((kind, data, memory.pointer(address + length)?))
+                })
+            } else {
+                None
+            };
+            let (kind, address, size) = match found {
+                Some(found) if found.2 >= min_size => found,
+                _ => continue,
+            };
+            let name = match string_attr(&dwarf, &unit, entry, gimli::DW_AT_linkage_name)? {
+                Some(name) => format!("{:#}", rustc_demangle::demangle(&name)),
+                None => string_attr(&dwarf, &unit, entry, gimli::DW_AT_name)?
+                    .unwrap_or_default(),
+            };
+            assets.push(Asset {
+                name,
+                size,
+                address,
+                location: location(&dwarf, &unit, entry)?,
+                kind,
+            });
+        }
+    }
+    assets.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+    Ok(assets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::elf::section_header::{SHF_ALLOC, SHF_WRITE, SHT_PROGBITS};
+    use std::iter;
+    use testelf::Elf;
+
+    /// A tag, whether it has children, and the attributes and their forms.
+    type Abbreviation = (u8, u8, &'static [(u8, u8)]);
+
+    /// The abbreviations of `debug_info`, by code from 1.
+    const ABBREVIATIONS: &[Abbreviation] = &[
+        // A compile unit with a `DW_FORM_string` name and directory, and a line program.
+        (0x11, 1, &[(0x03, 0x08), (0x1b, 0x08), (0x10, 0x17)]),
+        // A variable with a name, a `DW_FORM_ref4` type, a location expression, and a
+        // `DW_FORM_data1` file and line.
+        (0x34, 0, &[(0x03, 0x08), (0x49, 0x13), (0x02, 0x18), (0x3a, 0x0b), (0x3b, 0x0b)]),
+        // A variable with a linkage name rather than a declaration.
+        (0x34, 0, &[(0x03, 0x08), (0x6e, 0x08), (0x49, 0x13), (0x02, 0x18)]),
+        // A base type with a name and size.
+        (0x24, 0, &[(0x03, 0x08), (0x0b, 0x0b)]),
+        // An array of a type, and its subrange with a count.
+        (0x01, 1, &[(0x49, 0x13)]),
+        (0x21, 0, &[(0x37, 0x0b)]),
+        // A pointer to a type, and a `const` one.
+        (0x0f, 0, &[(0x49, 0x13)]),
+        (0x26, 0, &[(0x49, 0x13)]),
+        // A structure with a name, and its members with a name and offset.
+        (0x13, 1, &[(0x03, 0x08)]),
+        (0x0d, 0, &[(0x03, 0x08), (0x38, 0x0b)]),
+    ];
+
+    fn abbrev() -> Vec<u8> {
+        let mut out = Vec::new();
+        for (code, &(tag, children, attrs)) in ABBREVIATIONS.iter().enumerate() {
+            out.extend_from_slice(&[code as u8 + 1, tag, children]);
+            for &(name, form) in attrs {
+                out.extend_from_slice(&[name, form]);
+            }
+            out.extend_from_slice(&[0, 0]);
+        }
+        out.push(0);
+        out
+    }
+
+    /// A DWARF 4 line program header naming the file `src/assets.rs`, with no rows.
+    fn debug_line() -> Vec<u8> {
+        let mut header = vec![1, 1, 1, -5i8 as u8, 14, 13, 0, 1, 1, 1, 1, 0, 0, 0, 1, 0, 0, 1];
+        header.extend_from_slice(b"src\0\0assets.rs\0\x01\0\0\0");
+        let mut out = ((6 + header.len()) as u32).to_le_bytes().to_vec();
+        out.extend_from_slice(&[4, 0]);
+        out.extend_from_slice(&(header.len() as u32).to_le_bytes());
+        out.extend_from_slice(&header);
+        out
+    }
+
+    /// Debugging information entries, and the offsets in the unit they start at.
+    struct Dies(Vec<u8>);
+
+    impl Dies {
+        /// Add an entry with the abbreviation `code` and the encoded attributes `attrs`,
+        /// returning its offset from the start of the unit's header.
+        fn add(&mut self, code: u8, attrs: &[&[u8]]) -> u32 {
+            let offset = 11 + self.0.len() as u32;
+            self.0.push(code);
+            for attr in attrs {
+                self.0.extend_from_slice(attr);
+            }
+            offset
+        }
+
+        fn end_children(&mut self) {
+            self.0.push(0);
+        }
+    }
+
+    fn string(s: &str) -> Vec<u8> {
+        s.bytes().chain(iter::once(0)).collect()
+    }
+
+    /// A `DW_OP_addr` location expression.
+    fn addr(address: u64) -> Vec<u8> {
+        let mut expr = vec![9, 0x03];
+        expr.extend_from_slice(&address.to_le_bytes());
+        expr
+    }
+
+    /// An executable with `include_bytes!` arrays in `.rodata`, and references and a `&str`
+    /// pointing at them in `.data`.
+    fn executable(rodata: u64, data: u64) -> Vec<u8> {
+        let mut dies = Dies(Vec::new());
+        dies.add(1, &[&string("src/lib.rs"), &string("/build"), &[0; 4]]);
+        let u8_type = dies.add(4, &[&string("u8"), &[1]]).to_le_bytes();
+        let array = |dies: &mut Dies, count: u8| {
+            let array = dies.add(5, &[&u8_type]);
+            dies.add(6, &[&[count]]);
+            dies.end_children();
+            array.to_le_bytes()
+        };
+        let logo = array(&mut dies, 16);
+        let logo = dies.add(8, &[&logo]).to_le_bytes();
+        let icon = array(&mut dies, 24);
+        let icon = dies.add(7, &[&icon]).to_le_bytes();
+        let tiny = array(&mut dies, 2);
+        let str_type = dies.add(9, &[&string("&str")]).to_le_bytes();
+        dies.add(10, &[&string("data_ptr"), &[0]]);
+        dies.add(10, &[&string("length"), &[8]]);
+        dies.end_children();
+        dies.add(2, &[&string("LOGO"), &logo, &addr(rodata), &[1], &[3]]);
+        dies.add(3, &[&string("ICON"), &string("_ZN5icons4ICON17h0123456789abcdefE"), &icon,
+                      &addr(data)]);
+        dies.add(2, &[&string("README"), &str_type, &addr(data + 8), &[1], &[7]]);
+        dies.add(2, &[&string("TINY"), &tiny, &addr(rodata + 62), &[1], &[9]]);
+        dies.end_children();
+        let mut info = ((7 + dies.0.len()) as u32).to_le_bytes().to_vec();
+        info.extend_from_slice(&[4, 0, 0, 0, 0, 0, 8]);
+        info.extend_from_slice(&dies.0);
+
+        let mut pointers = Vec::new();
+        for &word in &[rodata + 16, rodata + 40, 20, 0] {
+            pointers.extend_from_slice(&word.to_le_bytes());
+        }
+        Elf::executable()
+            .section(".rodata", SHT_PROGBITS, SHF_ALLOC, &[0; 64])
+            .section(".data", SHT_PROGBITS, SHF_ALLOC | SHF_WRITE, &pointers)
+            .section(".debug_abbrev", SHT_PROGBITS, 0, &abbrev())
+            .section(".debug_info", SHT_PROGBITS, 0, &info)
+            .section(".debug_line", SHT_PROGBITS, 0, &debug_line())
+            .build()
+    }
+
+    #[test]
+    fn arrays_references_and_strs() {
+        let layout = executable(0, 0);
+        let records = section_records(&layout).unwrap();
+        let address = |name: &str| records.iter().find(|r| r.name == name)
+            .and_then(|r| r.address).unwrap();
+        let (rodata, data) = (address(".rodata"), address(".data"));
+        let buf = executable(rodata, data);
+        let found: Vec<_> = assets(&buf, 4).unwrap().into_iter()
+            .map(|a| (a.name, a.kind, a.address, a.size, a.location))
+            .collect();
+        assert_eq!(found, vec![
+            ("icons::ICON".to_string(), "reference", rodata + 16, 24, None),
+            ("README".to_string(), "str", rodata + 40, 20,
+             Some("/build/src/assets.rs:7".to_string())),
+            ("LOGO".to_string(), "array", rodata, 16, Some("/build/src/assets.rs:3".to_string())),
+        ]);
+        // `TINY` is only 2 bytes.
+        assert_eq!(assets(&buf, 0).unwrap().len(), 4);
+        assert!(assets(&Elf::executable().build(), 0).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,188 @@
+use components::{BudgetSource, Components};
+use group::NO_CRATE;
+use std::collections::BTreeMap;
+
+/// A crate or component over its budget.
+#[derive(Clone, Debug, Serialize)]
+pub struct Violation {
+    /// `crate` or `component`.
+    pub kind: &'static str,
+    pub name: String,
+    pub size: u64,
+    pub budget: u64,
+    pub source: BudgetSource,
+    /// How far `size` is over `budget`.
+    pub excess: u64,
+}
+
+/// The crates and components of a binary checked against their budgets, as emitted by
+/// `budget --format json`.
+#[derive(Clone, Debug, Serialize)]
+pub struct BudgetReport {
+    /// The components file the budgets come from.
+    pub config: String,
+    /// The number of crates and components that have budgets.
+    pub checked: u64,
+    /// Those over their budgets, furthest over first.
+    pub violations: Vec<Violation>,
+}
+
+/// The most violations that the `text` of a notification lists.
+const NOTIFIED_VIOLATIONS: usize = 10;
+
+/// What `budget --notify-webhook` posts when budgets are exceeded: the report of the file, and
+/// a summary of it in `text`, which chat webhooks like Slack's show.
+#[derive(Debug, Serialize)]
+pub struct Notification<'a> {
+    pub text: String,
+    pub file: String,
+    #[serde(flatten)]
+    pub report: &'a BudgetReport,
+}
+
+impl BudgetReport {
+    /// The notification of this report, of the file at `file`.
+    pub fn notification(&self, file: &str) -> Notification<'_> {
+        let mut text = format!("{}: {} of {} budgets exceeded", file, self.violations.len(),
+                               self.checked);
+        for violation in self.violations.iter().take(NOTIFIED_VIOLATIONS) {
+            text.push_str(&format!("\n{} {}: {} bytes, {} over its budget of {}",
+                                   violation.kind, violation.name, violation.size,
+                                   violation.excess, violation.budget));
+        }
+        if self.violations.len() > NOTIFIED_VIOLATIONS {
+            text.push_str(&format!("\nand {} more", self.violations.len() - NOTIFIED_VIOLATIONS));
+        }
+        Notification { text, file: file.to_string(), report: self }
+    }
+}
+
+/// Check the sizes of the crates `crates` and the components `components` of a binary against
+/// the budgets of `config`. Every component of `config` has a size, if only 0, while crates
+/// that the binary doesn't have aren't checked.
+pub fn check(config: &Components, crates: &BTreeMap<String, u64>,
+             components: &BTreeMap<String, u64>) -> BudgetReport {
+    let mut checked = 0;
+    let mut violations = Vec::new();
+    let mut check = |kind, name: &str, size, budget: Option<(u64, BudgetSource)>| {
+        if let Some((budget, source)) = budget {
+            checked += 1;
+            if size > budget {
+                violations.push(Violation {
+                    kind,
+                    name: name.to_string(),
+                    size,
+                    budget,
+                    source,
+                    excess: size - budget,
+                });
+            }
+        }
+    };
+    for (name, &size) in crates.iter().filter(|&(name, _)| name != NO_CRATE) {
+        check("crate", name, size, config.crate_budget(name));
+    }
+    for name in config.names() {
+        let size = components.get(name).cloned().unwrap_or(0);
+        check("component", name, size, config.component_budget(name));
+    }
+    violations.sort_by(|a, b| (b.excess, a.kind, &a.name).cmp(&(a.excess, b.kind, &b.name)));
+    BudgetReport { config: config.path.display().to_string(), checked, violations }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    fn load(contents: &str) -> Components {
+        let path = env::temp_dir().join(format!("rust-size-{}-budgets.toml", process::id()));
+        fs::write(&path, contents).unwrap();
+        let components = Components::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        components
+    }
+
+    fn sizes(sizes: &[(&str, u64)]) -> BTreeMap<String, u64> {
+        sizes.iter().map(|&(name, size)| (name.to_string(), size)).collect()
+    }
+
+    #[test]
+    fn defaults_and_overrides() {
+        let config = load(r#"
+            [budgets]
+            crate = "1K"
+            component = 100
+
+            [budgets.crates]
+            regex = "2K"
+
+            [[component]]
+            name = "media"
+            crates = ["image"]
+            budget = 500
+
+            [[component]]
+            name = "net"
+            crates = ["necko"]
+
+            [[component]]
+            name = "ui"
+        "#);
+        let crates = sizes(&[("image", 1500), ("regex", 2000), ("serde", 900), ("necko", 50),
+                             (NO_CRATE, 5000)]);
+        let components = sizes(&[("media", 1500), ("net", 50)]);
+        let report = check(&config, &crates, &components);
+        // Symbols outside any crate aren't checked, while components the binary doesn't have
+        // are, at 0 bytes.
+        assert_eq!(report.checked, 7);
+        let violations: Vec<_> = report.violations.iter()
+            .map(|v| (v.kind, v.name.as_str(), v.size, v.budget, v.source, v.excess))
+            .collect();
+        assert_eq!(violations, vec![("component", "media", 1500, 500, BudgetSource::Override, 1000),
+                                    ("crate", "image", 1500, 1024, BudgetSource::Default, 476)]);
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!((&json["violations"][0]["source"], &json["violations"][1]["source"]),
+                   (&"override".into(), &"default".into()));
+
+        // Without budgets, nothing is checked.
+        let report = check(&load("[[component]]\nname = \"ui\"\n"), &crates, &components);
+        assert_eq!((report.checked, report.violations.len()), (0, 0));
+    }
+
+    #[test]
+    fn notifications_summarize_the_report() {
+        let violation = |excess| Violation {
+            kind: "crate",
+            name: format!("c{}", excess),
+            size: 100 + excess,
+            budget: 100,
+            source: BudgetSource::Default,
+            excess,
+        };
+        let mut report = BudgetReport {
+            config: "components.toml".to_string(),
+            checked: 20,
+            violations: vec![violation(8)],
+        };
+        let notification = report.notification("a.out");
+        assert_eq!(notification.text, "a.out: 1 of 20 budgets exceeded\n\
+                                       crate c8: 108 bytes, 8 over its budget of 100");
+        // The report is posted along with the summary.
+        let json = serde_json::to_value(&notification).unwrap();
+        assert_eq!((&json["file"], &json["config"], &json["checked"]),
+                   (&"a.out".into(), &"components.toml".into(), &20.into()));
+        assert_eq!(json["violations"][0]["excess"], 8);
+
+        report.violations = (1..=12).rev().map(violation).collect();
+        let text = report.notification("a.out").text;
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 1 + NOTIFIED_VIOLATIONS + 1);
+        assert_eq!(lines[0], "a.out: 12 of 20 budgets exceeded");
+        assert_eq!(lines[1], "crate c12: 112 bytes, 12 over its budget of 100");
+        assert_eq!(lines[11], "and 2 more");
+    }
+}
@@ -0,0 +1,172 @@
+use failure::Error;
+use goblin::pe::section_table::{IMAGE_SCN_CNT_CODE, IMAGE_SCN_MEM_WRITE};
+use goblin::pe::PE;
+use pdb::{self, FallibleIterator, PdbInternalSectionOffset, SymbolData};
+use std::collections::BTreeMap;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use symbols::Symbol;
+
+/// Find the PDB for the PE image `pe` read from `path`: the file that its CodeView debug
+/// record names, or else a file of that name next to the image, or else the image's name with
+/// `.pdb` next to it.
+pub fn find_pdb(path: &Path, pe: &PE) -> Option<PathBuf> {
+    let mut candidates = Vec::new();
+    if let Some(info) = pe.debug_data.and_then(|data| data.codeview_pdb70_debug_info) {
+        let end = info.filename.iter().position(|&b| b == 0).unwrap_or(info.filename.len());
+        let recorded = String::from_utf8_lossy(&info.filename[..end]).into_owned();
+        // The record has the path on the machine that linked the image, often a Windows one.
+        let name = recorded.rsplit(['/', '\\']).next().unwrap_or(&recorded).to_string();
+        candidates.push(PathBuf::from(&recorded));
+        candidates.push(path.with_file_name(name));
+    }
+    candidates.push(path.with_extension("pdb"));
+    candidates.into_iter().find(|candidate| candidate.is_file())
+}
+
+/// Format the GUID `bytes`, as stored in CodeView records, the way Windows tools do.
+fn guid(bytes: [u8; 16]) -> String {
+    let hex = |bytes: &[u8]| bytes.iter().map(|b| format!("{:02X}", b)).collect::<String>();
+    format!("{:08X}-{:04X}-{:04X}-{}-{}",
+            u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            u16::from_le_bytes([bytes[4], bytes[5]]), u16::from_le_bytes([bytes[6], bytes[7]]),
+            hex(&bytes[8..10]), hex(&bytes[10..]))
+}
+
+/// A symbol from a PDB, before sizing.
+struct Found {
+    name: String,
+    section: u16,
+    offset: u32,
+    size: Option<u32>,
+    code: bool,
+}
+
+impl Found {
+    fn new(name: pdb::RawString, offset: PdbInternalSectionOffset, size: Option<u32>, code: bool)
+           -> Found {
+        Found {
+            name: name.to_string().into_owned(),
+            section: offset.section,
+            offset: offset.offset,
+            size,
+            code,
+        }
+    }
+}
+
+/// The functions and globals that the PDB `pdb` records for the PE image `buf`, which it must
+/// match. Functions are sized by their records; globals and public symbols, which records
+/// don't size, by the gap to the next symbol in their section.
+pub fn symbols(buf: &[u8], pdb: &[u8]) -> Result<Vec<Symbol>, Error> {
+    let pe = PE::parse(buf)?;
+    let mut pdb = pdb::PDB::open(Cursor::new(pdb))?;
+    let info = pdb.pdb_information()?;
+    let dbi = pdb.debug_information()?;
+    if let Some(record) = pe.debug_data.and_then(|data| data.codeview_pdb70_debug_info) {
+        let age = dbi.age().unwrap_or(info.age);
+        if record.signature != info.guid.to_bytes_le() || record.age != age {
+            bail!("The PDB doesn't match the image: it is {} age {}, the image wants {} age {}",
+                  guid(info.guid.to_bytes_le()), age, guid(record.signature), record.age);
+        }
+    }
+
+    // The records of functions and module-level data come first, so that their names and sizes
+    // win over those of the globals and public symbols at the same address.
+    let mut found = Vec::new();
+    let mut modules = dbi.modules()?;
+    while let Some(module) = modules.next()? {
+        let module = match pdb.module_info(&module)? {
+            Some(module) => module,
+            None => continue,
+        };
+        let mut iter = module.symbols()?;
+        while let Some(symbol) = iter.next()? {
+            match symbol.parse() {
+                Ok(SymbolData::Procedure(proc)) => {
+                    found.push(Found::new(proc.name, proc.offset, Some(proc.len), true));
+                }
+                Ok(SymbolData::Data(data)) => {
+                    found.push(Found::new(data.name, data.offset, None, false));
+                }
+                Ok(SymbolData::ThreadStorage(data)) => {
+                    found.push(Found::new(data.name, data.offset, None, false));
+                }
+                _ => {}
+            }
+        }
+    }
+    // Linkers always write the global symbols, but other tools may not.
+    let globals = match pdb.global_symbols() {
+        Err(pdb::Error::GlobalSymbolsNotFound) => None,
+        globals => Some(globals?),
+    };
+    if let Some(ref globals) = globals {
+        let mut iter = globals.iter();
+        while let Some(symbol) = iter.next()? {
+            match symbol.parse() {
+                Ok(SymbolData::Data(data)) => {
+                    found.push(Found::new(data.name, data.offset, None, false));
+                }
+                Ok(SymbolData::Public(public)) => {
+                    let code = public.code || public.function;
+                    found.push(Found::new(public.name, public.offset, None, code));
+                }
+                _ => {}
+            }
+        }
+    }
+    let mut by_address = BTreeMap::new();
+    for symbol in found {
+        by_address.entry((symbol.section, symbol.offset)).or_insert(symbol);
+    }
+
+    let addresses: Vec<(u16, u32)> = by_address.keys().cloned().collect();
+    let mut symbols = Vec::new();
+    for (i, symbol) in by_address.into_values().enumerate() {
+        // Section numbers are 1-based.
+        let section = match pe.sections.get((symbol.section as usize).wrapping_sub(1)) {
+            Some(section) => section,
+            None => continue,
+        };
+        // Objects and some linkers leave the virtual size zero.
+        let end = match section.virtual_size {
+            0 => section.size_of_raw_data,
+            size => size,
+        };
+        let size = symbol.size.unwrap_or_else(|| match addresses.get(i + 1) {
+            Some(&(next_section, next)) if next_section == symbol.section => next - symbol.offset,
+            _ => end.saturating_sub(symbol.offset),
+        });
+        if size == 0 {
+            continue;
+        }
+        symbols.push(Symbol {
+            name: symbol.name,
+            size: u64::from(size),
+            address: u64::from(section.virtual_address + symbol.offset),
+            section: section.name().ok().map(str::to_string),
+            offset: if symbol.offset < section.size_of_raw_data {
+                Some(u64::from(section.pointer_to_raw_data + symbol.offset))
+            } else {
+                None
+            },
+            code: symbol.code || section.characteristics & IMAGE_SCN_CNT_CODE != 0,
+            writable: section.characteristics & IMAGE_SCN_MEM_WRITE != 0,
+            inferred: symbol.size.is_none(),
+        });
+    }
+    Ok(symbols)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn guids_as_windows_formats_them() {
+        let bytes = [0x78, 0x56, 0x34, 0x12, 0xbc, 0x9a, 0xf0, 0xde,
+                     0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef];
+        assert_eq!(guid(bytes), "12345678-9ABC-DEF0-0123-456789ABCDEF");
+    }
+}
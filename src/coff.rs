@@ -0,0 +1,271 @@
+use failure::Error;
+use flags;
+use goblin::pe::section_table::{SectionTable, IMAGE_SCN_CNT_UNINITIALIZED_DATA};
+use goblin::pe::section_table::{IMAGE_SCN_LNK_REMOVE, IMAGE_SCN_MEM_DISCARDABLE};
+use goblin::pe::section_table::{IMAGE_SCN_MEM_WRITE, SIZEOF_SECTION_TABLE};
+use Section;
+use SectionRecord;
+
+/// The machine types of the COFF objects that MSVC, clang-cl and rustc emit.
+const MACHINES: &[u16] = &[0x14c, 0x8664, 0xaa64, 0xa641, 0x1c0, 0x1c2, 0x1c4];
+
+/// The characteristics of sections that the linker leaves out of the image.
+const DISCARDED: u32 = IMAGE_SCN_LNK_REMOVE | IMAGE_SCN_MEM_DISCARDABLE;
+
+/// The size of a COFF file header, as objects start with (images have a PE signature first).
+const HEADER_SIZE: usize = 20;
+
+/// The size of the header of a `/bigobj` object, which has 32-bit section numbers.
+const BIGOBJ_HEADER_SIZE: usize = 56;
+
+/// The class ID that marks an anonymous object header as that of a `/bigobj` object.
+const BIGOBJ_CLASS_ID: [u8; 16] = [0xc7, 0xa1, 0xba, 0xd1, 0xee, 0xba, 0xa9, 0x4b,
+                                   0xaf, 0x20, 0xfa, 0xf6, 0x6a, 0xa4, 0xdc, 0xb8];
+
+/// The sizes of a symbol table entry in regular and `/bigobj` objects.
+const SYMBOL_SIZE: usize = 18;
+const BIGOBJ_SYMBOL_SIZE: usize = 20;
+
+fn le16(buf: &[u8], offset: usize) -> Option<u16> {
+    let bytes = buf.get(offset..offset + 2)?;
+    Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn le32(buf: &[u8], offset: usize) -> Option<u32> {
+    let bytes = buf.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// The layout of a COFF object: its machine, the number of sections and where their table
+/// starts, and where its string table starts.
+struct Header {
+    machine: u16,
+    sections: usize,
+    section_table: usize,
+    string_table: usize,
+}
+
+impl Header {
+    fn parse(buf: &[u8]) -> Option<Header> {
+        let (machine, sections, section_table, symbols, symbol_count, symbol_size) =
+            if le16(buf, 0)? == 0 && le16(buf, 2)? == 0xffff {
+                if le16(buf, 4)? < 2 || buf.get(12..28)? != BIGOBJ_CLASS_ID {
+                    return None;
+                }
+                (le16(buf, 6)?, le32(buf, 44)? as usize, BIGOBJ_HEADER_SIZE,
+                 le32(buf, 48)? as usize, le32(buf, 52)? as usize, BIGOBJ_SYMBOL_SIZE)
+            } else {
+                // Images have an optional header; objects don't.
+                if le16(buf, 16)? != 0 {
+                    return None;
+                }
+                (le16(buf, 0)?, le16(buf, 2)? as usize, HEADER_SIZE,
+                 le32(buf, 8)? as usize, le32(buf, 12)? as usize, SYMBOL_SIZE)
+            };
+        if !MACHINES.contains(&machine) ||
+            section_table + sections * SIZEOF_SECTION_TABLE > buf.len() {
+            return None;
+        }
+        Some(Header {
+            machine,
+            sections,
+            section_table,
+            string_table: symbols + symbol_count * symbol_size,
+        })
+    }
+}
+
+/// Whether `buf` looks like a COFF object file, as opposed to a linked PE image.
+pub fn is_coff(buf: &[u8]) -> bool {
+    Header::parse(buf).is_some()
+}
+
+/// The machine type of the COFF object `buf`.
+pub fn machine(buf: &[u8]) -> Option<u16> {
+    Header::parse(buf).map(|header| header.machine)
+}
+
+/// The name of `section`, looking up names longer than 8 bytes, which are written as `/` and
+/// their decimal offset in the string table.
+fn section_name(buf: &[u8], header: &Header, section: &SectionTable) -> String {
+    let end = section.name.iter().position(|&b| b == 0).unwrap_or(8);
+    let name = String::from_utf8_lossy(&section.name[..end]).into_owned();
+    let offset = match name.strip_prefix('/').and_then(|n| n.parse::<usize>().ok()) {
+        Some(offset) => header.string_table + offset,
+        None => return name,
+    };
+    match buf.get(offset..) {
+        Some(strings) => {
+            let end = strings.iter().position(|&b| b == 0).unwrap_or(strings.len());
+            String::from_utf8_lossy(&strings[..end]).into_owned()
+        }
+        None => name,
+    }
+}
+
+/// Parse `buf` as a COFF object file, as MSVC, clang-cl and rustc emit for Windows targets,
+/// and return a record for each section.
+///
+/// Sections are classified as for PE images, with uninitialized data as `Bss`. Sections that
+/// the linker discards, like `.drectve` and the CodeView debug info in `.debug$S`, are `Other`.
+/// Objects usually have many sections of each name, one for each COMDAT.
+///
+/// Sections have no addresses, only file offsets.
+pub fn sections(buf: &[u8]) -> Result<Vec<SectionRecord>, Error> {
+    let header = match Header::parse(buf) {
+        Some(header) => header,
+        None => bail!("Not a COFF object file"),
+    };
+    let mut vec = Vec::new();
+    let mut offset = header.section_table;
+    for _ in 0..header.sections {
+        let section = SectionTable::parse(buf, &mut offset)?;
+        let characteristics = section.characteristics;
+        let category = if characteristics & DISCARDED != 0 {
+            Section::Other
+        } else if characteristics & IMAGE_SCN_CNT_UNINITIALIZED_DATA != 0 {
+            Section::Bss
+        } else if characteristics & IMAGE_SCN_MEM_WRITE == 0 {
+            Section::Text
+        } else {
+            Section::Data
+        };
+        let name = section_name(buf, &header, &section);
+        let mut record = SectionRecord::synthetic(&name, u64::from(section.size_of_raw_data),
+                                                  category);
+        if section.pointer_to_raw_data != 0 && category != Section::Bss {
+            record.offset = Some(u64::from(section.pointer_to_raw_data));
+        }
+        record.alignment = flags::pe_alignment(characteristics);
+        record.flags = Some(u64::from(characteristics));
+        record.flag_names = flags::pe_flag_names(characteristics);
+        vec.push(record);
+    }
+    Ok(vec)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arch;
+    use goblin::pe::section_table::{IMAGE_SCN_CNT_CODE, IMAGE_SCN_CNT_INITIALIZED_DATA};
+    use goblin::pe::section_table::IMAGE_SCN_MEM_READ;
+    use testelf::Elf;
+
+    /// `IMAGE_SCN_ALIGN_16BYTES`.
+    const ALIGN_16: u32 = 0x50_0000;
+
+    /// An x86-64 object, in the `/bigobj` format if `bigobj`, with sections of the given names,
+    /// characteristics and contents (`None` for uninitialized data of 8 bytes). Names longer
+    /// than 8 bytes go in the string table.
+    fn object(bigobj: bool, sections: &[(&str, u32, Option<&[u8]>)]) -> Vec<u8> {
+        let header = if bigobj { BIGOBJ_HEADER_SIZE } else { HEADER_SIZE };
+        let mut table = Vec::new();
+        let mut contents = Vec::new();
+        let mut strings = Vec::new();
+        let mut offset = header + sections.len() * SIZEOF_SECTION_TABLE;
+        for &(name, characteristics, data) in sections {
+            let mut field = [0; 8];
+            if name.len() > 8 {
+                let long = format!("/{}", 4 + strings.len());
+                field[..long.len()].copy_from_slice(long.as_bytes());
+                strings.extend_from_slice(name.as_bytes());
+                strings.push(0);
+            } else {
+                field[..name.len()].copy_from_slice(name.as_bytes());
+            }
+            table.extend_from_slice(&field);
+            let (size, pointer) = match data {
+                Some(data) => (data.len(), offset),
+                None => (8, 0),
+            };
+            for &word in &[0, 0, size as u32, pointer as u32, 0, 0] {
+                table.extend_from_slice(&u32::to_le_bytes(word));
+            }
+            // No relocations or line numbers.
+            table.extend_from_slice(&[0; 4]);
+            table.extend_from_slice(&characteristics.to_le_bytes());
+            if let Some(data) = data {
+                contents.extend_from_slice(data);
+                offset += data.len();
+            }
+        }
+        // The symbol table is empty, so the string table follows the contents.
+        let mut out = Vec::new();
+        let (machine, count) = (0x8664u16, sections.len() as u32);
+        if bigobj {
+            for &half in &[0, 0xffff, 2, machine] {
+                out.extend_from_slice(&u16::to_le_bytes(half));
+            }
+            out.extend_from_slice(&[0; 4]);
+            out.extend_from_slice(&BIGOBJ_CLASS_ID);
+            out.extend_from_slice(&[0; 16]);
+            for &word in &[count, offset as u32, 0] {
+                out.extend_from_slice(&word.to_le_bytes());
+            }
+        } else {
+            out.extend_from_slice(&machine.to_le_bytes());
+            out.extend_from_slice(&(count as u16).to_le_bytes());
+            for &word in &[0, offset as u32, 0, 0] {
+                out.extend_from_slice(&u32::to_le_bytes(word));
+            }
+        }
+        assert_eq!(out.len(), header);
+        out.extend_from_slice(&table);
+        out.extend_from_slice(&contents);
+        out.extend_from_slice(&(4 + strings.len() as u32).to_le_bytes());
+        out.extend_from_slice(&strings);
+        out
+    }
+
+    /// A section's name, category, size, file offset and alignment.
+    type Summary = (String, Section, u64, Option<u64>, Option<u64>);
+
+    fn records(buf: &[u8]) -> Vec<Summary> {
+        sections(buf).unwrap().into_iter()
+            .map(|r| (r.name, r.category, r.size, r.offset, r.alignment))
+            .collect()
+    }
+
+    #[test]
+    fn sections_of_regular_and_bigobj_objects() {
+        let code = IMAGE_SCN_CNT_CODE | IMAGE_SCN_MEM_READ | ALIGN_16;
+        let data = IMAGE_SCN_CNT_INITIALIZED_DATA | IMAGE_SCN_MEM_READ | IMAGE_SCN_MEM_WRITE;
+        let sections = [
+            (".text$mn", code, Some(&[0xc3; 16][..])),
+            (".rdata$zzzzzzzz", IMAGE_SCN_CNT_INITIALIZED_DATA | IMAGE_SCN_MEM_READ,
+             Some(&[1; 4][..])),
+            (".data", data, Some(&[2; 8][..])),
+            (".bss", IMAGE_SCN_CNT_UNINITIALIZED_DATA | IMAGE_SCN_MEM_WRITE, None),
+            (".drectve", IMAGE_SCN_LNK_REMOVE, Some(&b" /DEFAULTLIB:msvcrt"[..])),
+        ];
+        for &bigobj in &[false, true] {
+            let buf = object(bigobj, &sections);
+            assert!(is_coff(&buf));
+            assert_eq!(machine(&buf), Some(0x8664));
+            assert_eq!(arch::arch(&buf).unwrap(), "x86_64");
+            let contents = if bigobj { BIGOBJ_HEADER_SIZE } else { HEADER_SIZE } as u64 + 200;
+            assert_eq!(records(&buf), vec![
+                (".text$mn".to_string(), Section::Text, 16, Some(contents), Some(16)),
+                (".rdata$zzzzzzzz".to_string(), Section::Text, 4, Some(contents + 16), None),
+                (".data".to_string(), Section::Data, 8, Some(contents + 20), None),
+                (".bss".to_string(), Section::Bss, 8, None, None),
+                (".drectve".to_string(), Section::Other, 19, Some(contents + 28), None),
+            ]);
+        }
+    }
+
+    #[test]
+    fn images_and_other_formats_are_not_objects() {
+        let mut buf = object(true, &[(".text", IMAGE_SCN_CNT_CODE, Some(&[0xc3][..]))]);
+        assert!(is_coff(&buf));
+        buf[12] ^= 1;
+        assert!(!is_coff(&buf));
+        // An optional header makes it an image.
+        let mut buf = object(false, &[(".text", IMAGE_SCN_CNT_CODE, Some(&[0xc3][..]))]);
+        buf[16] = 0xf0;
+        assert!(!is_coff(&buf));
+        assert!(!is_coff(&Elf::object().build()));
+        assert!(sections(b"not an object at all").is_err());
+    }
+}
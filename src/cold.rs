@@ -0,0 +1,382 @@
+use exit::UsageError;
+use failure::Error;
+use rustc_demangle;
+use std::collections::HashMap;
+use symbols;
+
+/// The magic numbers of LLVM's binary profiles: indexed (`.profdata`) and raw (`.profraw`).
+const BINARY_PROFILE_MAGICS: &[&[u8]] = &[b"\xfflprofi\x81", b"\xfflprofr\x81"];
+
+/// The words in the names of functions that only run when something fails, which are cold
+/// whether or not a profile says so.
+const ERROR_PATH_WORDS: &[&str] = &["panic", "unwrap_failed", "expect_failed", "assert_failed",
+                                    "handle_alloc_error", "capacity_overflow", "abort",
+                                    "slice_index_fail", "slice_start_index_len_fail",
+                                    "slice_end_index_len_fail", "index_out_of_bounds"];
+
+/// Where the execution counts come from.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Profile {
+    /// An LLVM profile in the text format (`llvm-profdata merge --text`).
+    Profdata,
+    /// The samples of `perf script`.
+    Perf,
+}
+
+/// A function that is worth marking `#[cold]` or splitting out of the hot code.
+#[derive(Clone, Debug, Serialize)]
+pub struct ColdFunction {
+    /// The demangled name, without the hash.
+    pub name: String,
+    pub size: u64,
+    /// The sum of the function's counters in the profile, or its samples.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub count: Option<u64>,
+    /// `never-executed`, `rarely-executed` (at most `--max-count`), or without a profile,
+    /// `error-path` for functions that only run when something fails.
+    pub reason: &'static str,
+}
+
+/// The cold functions of a binary, as emitted by `cold --format json`.
+#[derive(Clone, Debug, Serialize)]
+pub struct ColdReport {
+    /// `profdata`, `perf`, or `names` when there is no profile and functions are judged by
+    /// their names.
+    pub source: &'static str,
+    /// The number of functions, counting aliases once.
+    pub functions: u64,
+    pub code_size: u64,
+    /// The functions that the profile has counts for. With `profdata`, the others weren't
+    /// instrumented and aren't candidates; with `perf`, they weren't sampled and count as
+    /// never executed.
+    pub profiled: u64,
+    /// The size of the cold functions, including those under `--min-size`.
+    pub cold_size: u64,
+    /// The size of the parts that the compiler already split out (`.cold` suffixes).
+    pub already_split: u64,
+    /// The cold functions of at least `--min-size` bytes, largest first.
+    pub candidates: Vec<ColdFunction>,
+}
+
+/// The name of `name` that functions are matched by across the symbol table and profiles:
+/// demangled, without the Rust hash and the compiler's numeric suffixes.
+pub fn key(name: &str) -> String {
+    let name = symbols::canonical_name(name);
+    // `perf` demangles Rust names itself, keeping the hash.
+    match name.rfind("::h") {
+        Some(i) if name.len() - i == 19 &&
+            name[i + 3..].bytes().all(|b| b.is_ascii_hexdigit()) => name[..i].to_string(),
+        _ => name,
+    }
+}
+
+/// The next of the numbers of the function `name` in a text format LLVM profile.
+fn number<'a, I: Iterator<Item = &'a str>>(lines: &mut I, name: &str) -> Result<u64, Error> {
+    match lines.next().map(str::parse::<u64>) {
+        Some(Ok(number)) => Ok(number),
+        _ => bail!("Malformed text profile at the counters of {}", name),
+    }
+}
+
+/// The counts of the functions in the text format LLVM profile `text`: the sum of each
+/// function's counters, by name. Local functions are named after their file, as
+/// `file.c;name` (or `file.c:name` before LLVM 18).
+fn profdata_counts(text: &str) -> Result<HashMap<String, u64>, Error> {
+    let mut counts = HashMap::new();
+    let mut lines = text.lines().map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#') && !line.starts_with(':'))
+        .peekable();
+    while let Some(name) = lines.next() {
+        let name = match name.rsplit_once(';') {
+            Some((_, name)) => name,
+            None => match name.split_once(':') {
+                Some((file, name)) if file.contains('.') && !name.starts_with(':') => name,
+                _ => name,
+            },
+        };
+        let _hash = number(&mut lines, name)?;
+        let counters = number(&mut lines, name)?;
+        let mut sum = 0u64;
+        for _ in 0..counters {
+            sum = sum.saturating_add(number(&mut lines, name)?);
+        }
+        // Value profiles, like the targets of indirect calls, follow as the number of kinds,
+        // and for each kind, its number of sites and for each site, its number of values and
+        // the values. Names are never numbers, so a number means they are there.
+        let kinds = match lines.peek().map(|line| line.parse::<u64>()) {
+            Some(Ok(_)) => number(&mut lines, name)?,
+            _ => 0,
+        };
+        for _ in 0..kinds {
+            let _kind = number(&mut lines, name)?;
+            for _ in 0..number(&mut lines, name)? {
+                for _ in 0..number(&mut lines, name)? {
+                    lines.next();
+                }
+            }
+        }
+        *counts.entry(key(name)).or_insert(0) += sum;
+    }
+    Ok(counts)
+}
+
+/// The symbol of a `perf script` frame, `<address> <symbol>+<offset> (<dso>)`, if the words
+/// `words` are one.
+fn perf_frame<'a>(words: &[&'a str]) -> Option<&'a str> {
+    let (address, symbol) = match *words {
+        [address, symbol, ..] => (address, symbol),
+        _ => return None,
+    };
+    if !address.bytes().all(|b| b.is_ascii_hexdigit()) || symbol == "[unknown]" {
+        return None;
+    }
+    Some(symbol.rsplit_once("+0x").map_or(symbol, |(symbol, _)| symbol))
+}
+
+/// The samples of each function in the output of `perf script` `text`, counting only the frame
+/// that was executing, not its callers. Samples have a header line, with the frame on it
+/// (without `-g`) or on the next line (with `-g`, where the callers follow); `-F ip,sym`
+/// output has only frame lines.
+pub fn perf_counts(text: &str) -> HashMap<String, u64> {
+    let mut counts = HashMap::new();
+    let mut headers = false;
+    let mut pending = false;
+    for line in text.lines() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let symbol = if line.starts_with(char::is_whitespace) {
+            if headers && !pending {
+                continue;
+            }
+            pending = false;
+            perf_frame(&words)
+        } else {
+            headers = true;
+            // The frame follows the event name, like `cycles:` or `cpu-clock:ppp:`.
+            match words.iter().rposition(|word| word.ends_with(':')) {
+                Some(i) if i + 1 < words.len() => {
+                    pending = false;
+                    perf_frame(&words[i + 1..])
+                }
+                _ => {
+                    pending = true;
+                    None
+                }
+            }
+        };
+        if let Some(symbol) = symbol {
+            *counts.entry(key(symbol)).or_insert(0) += 1;
+        }
+    }
+    counts
+}
+
+/// Whether the function with the demangled name `name` only runs when something fails.
+fn is_error_path(name: &str) -> bool {
+    ERROR_PATH_WORDS.iter().any(|word| name.contains(word))
+}
+
+/// List the functions of `buf` of at least `min_size` bytes that the profile `profile` counts
+/// at most `max_count` executions or samples for, or without a profile, that only run on error
+/// paths.
+pub fn cold(buf: &[u8], profile: Option<(Profile, &[u8])>, max_count: u64, min_size: u64)
+            -> Result<ColdReport, Error> {
+    let counts = match profile {
+        Some((_, data)) if BINARY_PROFILE_MAGICS.iter().any(|magic| data.starts_with(magic)) => {
+            return Err(UsageError("The profile is in LLVM's binary format; convert it with \
+                                   `llvm-profdata merge --text`".to_string()).into());
+        }
+        Some((Profile::Profdata, data)) => Some(profdata_counts(&String::from_utf8_lossy(data))?),
+        Some((Profile::Perf, data)) => Some(perf_counts(&String::from_utf8_lossy(data))),
+        None => None,
+    };
+
+    let mut functions: Vec<_> = symbols::symbols(buf)?.into_iter()
+        .filter(|sym| sym.code)
+        .collect();
+    // Aliases share their function's address; keep the largest of each.
+    functions.sort_by(|a, b| {
+        (&a.section, a.address, b.size, &a.name).cmp(&(&b.section, b.address, a.size, &b.name))
+    });
+    functions.dedup_by(|a, b| a.section == b.section && a.address == b.address);
+
+    let mut report = ColdReport {
+        source: match profile {
+            Some((Profile::Profdata, _)) => "profdata",
+            Some((Profile::Perf, _)) => "perf",
+            None => "names",
+        },
+        functions: functions.len() as u64,
+        code_size: functions.iter().map(|sym| sym.size).sum(),
+        profiled: 0,
+        cold_size: 0,
+        already_split: 0,
+        candidates: Vec::new(),
+    };
+    for sym in functions {
+        let name = key(&sym.name);
+        if sym.name.contains(".cold") {
+            report.already_split += sym.size;
+            continue;
+        }
+        let (count, reason) = match counts {
+            Some(ref counts) => {
+                let count = counts.get(&name).cloned();
+                report.profiled += count.is_some() as u64;
+                let count = match (count, profile) {
+                    (Some(count), _) => count,
+                    (None, Some((Profile::Perf, _))) => 0,
+                    (None, _) => continue,
+                };
+                match count {
+                    0 => (Some(0), "never-executed"),
+                    count if count <= max_count => (Some(count), "rarely-executed"),
+                    _ => continue,
+                }
+            }
+            None if is_error_path(&name) => (None, "error-path"),
+            None => continue,
+        };
+        report.cold_size += sym.size;
+        if sym.size >= min_size {
+            report.candidates.push(ColdFunction {
+                name: format!("{:#}", rustc_demangle::demangle(&sym.name)),
+                size: sym.size,
+                count,
+                reason,
+            });
+        }
+    }
+    report.candidates.sort_by(|a, b| (b.size, &a.name).cmp(&(a.size, &b.name)));
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::elf::section_header::{SHF_ALLOC, SHF_EXECINSTR, SHT_PROGBITS};
+    use goblin::elf::sym::STT_FUNC;
+    use testelf::Elf;
+
+    const PROFDATA: &str = "\
+# IR level Instrumentation Flag
+:ir
+main
+# Func Hash:
+1234
+# Num Counters:
+2
+# Counter Values:
+100
+5
+
+foo.c;helper
+99
+1
+0
+
+_ZN3app4rare17h0123456789abcdefE
+1
+1
+3
+# Num Value Kinds:
+1
+# ValueKind = IPVK_IndirectCallTarget:
+0
+# NumValueSites:
+1
+2
+target1:10
+target2:20
+
+old.c:legacy
+5
+1
+0
+";
+
+    fn sorted(counts: HashMap<String, u64>) -> Vec<(String, u64)> {
+        let mut counts: Vec<_> = counts.into_iter().collect();
+        counts.sort();
+        counts
+    }
+
+    #[test]
+    fn keys_drop_hashes_and_suffixes() {
+        assert_eq!(key("_ZN3app4rare17h0123456789abcdefE"), "app::rare");
+        assert_eq!(key("app::rare::h0123456789abcdef"), "app::rare");
+        assert_eq!(key("helper.llvm.123"), "helper");
+        assert_eq!(key("app::rare::hello"), "app::rare::hello");
+    }
+
+    #[test]
+    fn text_profiles() {
+        assert_eq!(sorted(profdata_counts(PROFDATA).unwrap()), vec![
+            ("app::rare".to_string(), 3),
+            ("helper".to_string(), 0),
+            ("legacy".to_string(), 0),
+            ("main".to_string(), 105),
+        ]);
+        assert!(profdata_counts("main\n1234\n2\n100\n").is_err());
+    }
+
+    #[test]
+    fn perf_samples() {
+        let script = "\
+app  1234 100.0:     250000 cycles:  401000 main+0x10 (/app)
+app  1234 100.1:     250000 cycles:
+\t  401020 helper+0x0 (/app)
+\t  401000 main+0x20 (/app)
+
+app  1234 100.2:     250000 cycles:
+\t  7f0000 [unknown] (/lib/libc.so)
+\t  401000 main+0x20 (/app)
+";
+        assert_eq!(sorted(perf_counts(script)), vec![("helper".to_string(), 1),
+                                                     ("main".to_string(), 1)]);
+        // `-F ip,sym`
+        assert_eq!(sorted(perf_counts("  401000 main\n  401004 main\n")),
+                   vec![("main".to_string(), 2)]);
+    }
+
+    #[test]
+    fn cold_functions() {
+        let buf = Elf::object()
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &[0; 152])
+            .symbol("main", STT_FUNC, ".text", 0, 64)
+            .symbol("helper", STT_FUNC, ".text", 64, 32)
+            .symbol("_ZN3app4rare17h0123456789abcdefE", STT_FUNC, ".text", 96, 16)
+            .symbol("unprofiled", STT_FUNC, ".text", 112, 8)
+            .symbol("main.cold.1", STT_FUNC, ".text", 120, 12)
+            .symbol("begin_panic", STT_FUNC, ".text", 132, 20)
+            .build();
+        let candidates = |report: &ColdReport| -> Vec<(String, Option<u64>, &'static str)> {
+            report.candidates.iter().map(|c| (c.name.clone(), c.count, c.reason)).collect()
+        };
+
+        let profile = Some((Profile::Profdata, PROFDATA.as_bytes()));
+        let report = cold(&buf, profile, 5, 16).unwrap();
+        assert_eq!((report.source, report.functions, report.code_size), ("profdata", 6, 152));
+        assert_eq!((report.profiled, report.cold_size, report.already_split), (3, 48, 12));
+        assert_eq!(candidates(&report), vec![
+            ("helper".to_string(), Some(0), "never-executed"),
+            ("app::rare".to_string(), Some(3), "rarely-executed"),
+        ]);
+
+        // Functions that perf never sampled count as never executed.
+        let profile = Some((Profile::Perf, &b"  401000 main\n"[..]));
+        let report = cold(&buf, profile, 0, 20).unwrap();
+        assert_eq!((report.profiled, report.cold_size), (1, 32 + 16 + 8 + 20));
+        let names: Vec<_> = candidates(&report).into_iter().map(|(name, _, _)| name).collect();
+        assert_eq!(names, vec!["helper", "begin_panic"]);
+
+        let report = cold(&buf, None, 0, 0).unwrap();
+        assert_eq!(candidates(&report), vec![("begin_panic".to_string(), None, "error-path")]);
+
+        let profile = Some((Profile::Profdata, &b"\xfflprofi\x81\0\0\0"[..]));
+        let err = cold(&buf, profile, 0, 0).unwrap_err();
+        assert!(err.downcast_ref::<UsageError>().is_some(), "{}", err);
+    }
+}
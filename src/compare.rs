@@ -0,0 +1,159 @@
+use arch::arch;
+use failure::Error;
+use counts_toward_total;
+use named_sections;
+use std::cmp;
+use std::collections::BTreeMap;
+use std::path::Path;
+use units::SizeFormat;
+use Section;
+
+/// The sizes of one artifact in a cross-target comparison.
+#[derive(Clone, Debug, Serialize)]
+pub struct Column {
+    pub path: String,
+    pub arch: String,
+    /// The total size of the sections in each category.
+    pub categories: BTreeMap<Section, u64>,
+    /// The total size of the sections with each normalized name, with `--normalize-names`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sections: Option<BTreeMap<String, u64>>,
+    /// The total size of the sections that count toward totals.
+    pub total: u64,
+    pub file_size: u64,
+}
+
+impl Column {
+    /// Summarize the object file `buf`, read from `path`, also totalling the sizes by
+    /// normalized section name if `normalize` is set. Non-allocated sections only count toward
+    /// the total, and are only listed by name, if `include_non_alloc` is set.
+    pub fn new(path: String, buf: &[u8], normalize: bool, include_non_alloc: bool)
+               -> Result<Column, Error> {
+        let mut categories = BTreeMap::new();
+        for section in &[Section::Text, Section::Data, Section::Bss, Section::Other] {
+            categories.insert(*section, 0);
+        }
+        let mut sections = BTreeMap::new();
+        let mut total = 0;
+        for (name, size, section, _) in named_sections(Path::new(&path), buf, normalize)? {
+            *categories.entry(section).or_insert(0) += size;
+            if counts_toward_total(section, include_non_alloc) {
+                *sections.entry(name).or_insert(0) += size;
+                total += size;
+            }
+        }
+        Ok(Column {
+            path,
+            arch: arch(buf)?,
+            categories,
+            sections: if normalize { Some(sections) } else { None },
+            total,
+            file_size: buf.len() as u64,
+        })
+    }
+}
+
+/// Format `value` relative to `baseline` as a percentage change.
+fn relative(value: u64, baseline: u64) -> String {
+    if baseline == 0 {
+        String::new()
+    } else {
+        format!(" ({:+.1}%)", (value as f64 - baseline as f64) * 100.0 / baseline as f64)
+    }
+}
+
+/// Print a matrix with one column per artifact and one row per section category (or per
+/// normalized section name, largest in the first column first), with each column after the
+/// first also showing its difference from the first. Sizes are written in `format`.
+pub fn print_matrix(columns: &[Column], format: &SizeFormat) {
+    let mut rows: Vec<(String, Vec<u64>)> = Vec::new();
+    if columns.iter().all(|c| c.sections.is_some()) {
+        let mut names: Vec<&String> = columns.iter()
+            .flat_map(|c| c.sections.as_ref().unwrap().keys())
+            .collect();
+        names.sort();
+        names.dedup();
+        for name in names {
+            let values = columns.iter()
+                .map(|c| c.sections.as_ref().unwrap().get(name).cloned().unwrap_or(0))
+                .collect();
+            rows.push((name.clone(), values));
+        }
+        rows.sort_by_key(|(_, values)| cmp::Reverse(values[0]));
+    } else {
+        for section in &[Section::Text, Section::Data, Section::Bss, Section::Other] {
+            rows.push((format!("{:?}", section),
+                       columns.iter().map(|c| c.categories[section]).collect()));
+        }
+    }
+    rows.push(("(total)".to_string(), columns.iter().map(|c| c.total).collect()));
+    rows.push(("(file size)".to_string(), columns.iter().map(|c| c.file_size).collect()));
+
+    let headers: Vec<String> = columns.iter().map(|c| c.arch.clone()).collect();
+    let cells: Vec<Vec<String>> = rows.iter().map(|(_, values)| {
+        values.iter().enumerate().map(|(i, &v)| {
+            if i == 0 {
+                format.size(v)
+            } else {
+                format!("{}{}", format.size(v), relative(v, values[0]))
+            }
+        }).collect()
+    }).collect();
+    let widths: Vec<usize> = (0..columns.len()).map(|i| {
+        cells.iter().map(|row| row[i].len()).chain(Some(headers[i].len())).max().unwrap_or(0)
+    }).collect();
+
+    let name_width = rows.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    print!("{:<width$}", "", width = name_width);
+    for (header, width) in headers.iter().zip(&widths) {
+        print!("  {:>width$}", header, width = width);
+    }
+    println!();
+    for ((name, _), row) in rows.iter().zip(&cells) {
+        print!("{:<width$}", name, width = name_width);
+        for (cell, width) in row.iter().zip(&widths) {
+            print!("  {:>width$}", cell, width = width);
+        }
+        println!();
+    }
+    println!();
+    for c in columns {
+        println!("{}: {}", c.arch, c.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::elf::section_header::{SHF_ALLOC, SHF_EXECINSTR, SHF_WRITE, SHT_PROGBITS};
+    use testelf::Elf;
+
+    #[test]
+    fn columns_total_loaded_sections() {
+        let buf = Elf::object()
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &[0; 40])
+            .section(".data", SHT_PROGBITS, SHF_ALLOC | SHF_WRITE, &[0; 8])
+            .section(".comment", SHT_PROGBITS, 0, &[0; 5])
+            .build();
+        let column = Column::new("a.o".to_string(), &buf, false, false).unwrap();
+        assert_eq!(column.arch, "x86_64");
+        assert_eq!(column.categories[&Section::Text], 40);
+        assert_eq!(column.categories[&Section::Data], 8);
+        // `.comment` and `.shstrtab`.
+        assert_eq!(column.categories[&Section::Other], 5 + 32);
+        assert_eq!(column.total, 48);
+        assert!(column.sections.is_none());
+
+        let column = Column::new("a.o".to_string(), &buf, true, true).unwrap();
+        assert_eq!(column.total, 48 + 37);
+        let sections = column.sections.unwrap();
+        assert_eq!((sections["text"], sections["data"], sections["metadata"]), (40, 8, 37));
+    }
+
+    #[test]
+    fn relative_changes() {
+        assert_eq!(relative(150, 100), " (+50.0%)");
+        assert_eq!(relative(99, 100), " (-1.0%)");
+        assert_eq!(relative(5, 0), "");
+    }
+}
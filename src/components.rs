@@ -0,0 +1,301 @@
+use failure::Error;
+use owners::pattern_regex;
+use regex::Regex;
+use spill::parse_limit;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml;
+
+/// The name of the components file that is looked for in the current directory and the
+/// directories above it, like `Cargo.toml`.
+pub const FILE_NAME: &str = "components.toml";
+
+/// The component of symbols that no component claims.
+pub const NO_COMPONENT: &str = "(no component)";
+
+/// One component of a components file, as written in TOML.
+#[derive(Deserialize)]
+struct RawComponent {
+    name: String,
+    #[serde(default)]
+    crates: Vec<String>,
+    #[serde(default)]
+    paths: Vec<String>,
+    #[serde(default)]
+    symbols: Vec<String>,
+    budget: Option<RawSize>,
+}
+
+/// A size in a components file: a number of bytes, or a string with a `K`, `M` or `G` suffix
+/// (powers of 1024), as in `"512K"`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawSize {
+    Bytes(u64),
+    Text(String),
+}
+
+/// The `[budgets]` table of a components file.
+#[derive(Default, Deserialize)]
+struct RawBudgets {
+    #[serde(rename = "crate")]
+    krate: Option<RawSize>,
+    component: Option<RawSize>,
+    #[serde(default)]
+    crates: BTreeMap<String, RawSize>,
+}
+
+#[derive(Deserialize)]
+struct RawComponents {
+    #[serde(default)]
+    component: Vec<RawComponent>,
+    #[serde(default)]
+    budgets: RawBudgets,
+}
+
+/// Where the budget of a crate or component comes from.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BudgetSource {
+    /// The default budget of every crate or component.
+    Default,
+    /// A budget of its own, which overrides the default.
+    Override,
+}
+
+/// A component, claiming the symbols of `crates`, those compiled from sources matching `paths`
+/// and those whose demangled names match `symbols`.
+struct Component {
+    name: String,
+    crates: Vec<String>,
+    paths: Vec<Regex>,
+    symbols: Vec<Regex>,
+    budget: Option<u64>,
+}
+
+/// The logical components of a project, read from a TOML file like:
+///
+/// ```toml
+/// [[component]]
+/// name = "media"
+/// crates = ["symphonia", "image"]
+/// paths = ["src/media/"]
+///
+/// [[component]]
+/// name = "networking"
+/// symbols = ["^necko::"]
+/// ```
+///
+/// `crates` are Rust crates or C++ top-level namespaces, `paths` are CODEOWNERS patterns over
+/// the source files of compile units, relative to the directory of the file, or absolute if
+/// they are outside it, and `symbols` are regexes. The first component with a rule that
+/// matches a symbol claims it.
+///
+/// The file can also give size budgets: a default for every crate and for every component,
+/// which those with budgets of their own override:
+///
+/// ```toml
+/// [budgets]
+/// crate = "256K"
+/// component = "2M"
+///
+/// [budgets.crates]
+/// regex = "512K"
+///
+/// [[component]]
+/// name = "media"
+/// crates = ["symphonia"]
+/// budget = "4M"
+/// ```
+pub struct Components {
+    /// The file the components were read from.
+    pub path: PathBuf,
+    components: Vec<Component>,
+    crate_budget: Option<u64>,
+    component_budget: Option<u64>,
+    crate_budgets: BTreeMap<String, u64>,
+    /// The directory of the file, which relative paths are matched from.
+    root: String,
+}
+
+impl Components {
+    /// Read the components file at `path`.
+    pub fn load(path: &Path) -> Result<Components, Error> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format_err!("{}: {}", path.display(), e))?;
+        let raw: RawComponents = toml::from_str(&contents)
+            .map_err(|e| format_err!("{}: {}", path.display(), e))?;
+        let size = |size: RawSize| match size {
+            RawSize::Bytes(bytes) => Ok(bytes),
+            RawSize::Text(text) => parse_limit(&text).map(|bytes| bytes as u64)
+                .map_err(|_| format_err!("{}: Invalid budget: {}", path.display(), text)),
+        };
+        let mut components = Vec::new();
+        for component in raw.component {
+            let paths = component.paths.iter().map(|pattern| pattern_regex(pattern))
+                .collect::<Result<_, _>>()
+                .map_err(|e| format_err!("{}: {}", path.display(), e))?;
+            let symbols = component.symbols.iter().map(|pattern| Regex::new(pattern))
+                .collect::<Result<_, _>>()
+                .map_err(|e| format_err!("{}: {}", path.display(), e))?;
+            components.push(Component {
+                name: component.name,
+                crates: component.crates,
+                paths,
+                symbols,
+                budget: component.budget.map(size).transpose()?,
+            });
+        }
+        let mut crate_budgets = BTreeMap::new();
+        for (name, budget) in raw.budgets.crates {
+            crate_budgets.insert(name, size(budget)?);
+        }
+        let root = fs::canonicalize(path).ok()
+            .and_then(|path| Some(path.parent()?.to_string_lossy().into_owned()))
+            .unwrap_or_default();
+        Ok(Components {
+            path: path.to_path_buf(),
+            components,
+            crate_budget: raw.budgets.krate.map(size).transpose()?,
+            component_budget: raw.budgets.component.map(size).transpose()?,
+            crate_budgets,
+            root: format!("{}/", root),
+        })
+    }
+
+    /// The `components.toml` in the current directory or the nearest directory above it.
+    pub fn find() -> Option<PathBuf> {
+        let dir = env::current_dir().ok()?;
+        dir.ancestors().map(|dir| dir.join(FILE_NAME)).find(|path| path.is_file())
+    }
+
+    /// Whether any component has `paths`, which need the debug info of binaries.
+    pub fn has_paths(&self) -> bool {
+        self.components.iter().any(|component| !component.paths.is_empty())
+    }
+
+    /// The names of the components, in the order of the file.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.components.iter().map(|component| component.name.as_str())
+    }
+
+    /// The budget of the crate `name`, and where it comes from, if it has one.
+    pub fn crate_budget(&self, name: &str) -> Option<(u64, BudgetSource)> {
+        match self.crate_budgets.get(name) {
+            Some(&budget) => Some((budget, BudgetSource::Override)),
+            None => self.crate_budget.map(|budget| (budget, BudgetSource::Default)),
+        }
+    }
+
+    /// The budget of the component `name`, and where it comes from, if it has one.
+    pub fn component_budget(&self, name: &str) -> Option<(u64, BudgetSource)> {
+        match self.components.iter().find(|c| c.name == name).and_then(|c| c.budget) {
+            Some(budget) => Some((budget, BudgetSource::Override)),
+            None => self.component_budget.map(|budget| (budget, BudgetSource::Default)),
+        }
+    }
+
+    /// The component of the symbol with demangled name `name`, in crate `krate` and compiled
+    /// from `source`, or `NO_COMPONENT`.
+    pub fn component(&self, name: &str, krate: Option<&str>, source: Option<&str>) -> &str {
+        // Sources outside the directory of the file are matched by their absolute paths, whose
+        // leading `/` anchored patterns leave out, as they do for relative ones.
+        let source = source.map(|source| {
+            source.strip_prefix(self.root.as_str()).unwrap_or(source).trim_start_matches('/')
+        });
+        self.components.iter()
+            .find(|component| {
+                krate.is_some_and(|krate| component.crates.iter().any(|c| c == krate)) ||
+                    source.is_some_and(|source| {
+                        component.paths.iter().any(|re| re.is_match(source))
+                    }) ||
+                    component.symbols.iter().any(|re| re.is_match(name))
+            })
+            .map_or(NO_COMPONENT, |component| &component.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+
+    fn load(name: &str, contents: &str) -> Result<Components, Error> {
+        let dir = env::temp_dir().join(format!("rust-size-{}-{}", process::id(), name));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(FILE_NAME), contents).unwrap();
+        let components = Components::load(&dir.join(FILE_NAME));
+        fs::remove_dir_all(&dir).unwrap();
+        components
+    }
+
+    #[test]
+    fn first_matching_component_claims() {
+        let components = load("components", r#"
+            [[component]]
+            name = "media"
+            crates = ["symphonia"]
+            paths = ["src/media/"]
+
+            [[component]]
+            name = "networking"
+            crates = ["media_net"]
+            symbols = ["^necko::"]
+
+            [[component]]
+            name = "vendored"
+            paths = ["/vendor/"]
+        "#).unwrap();
+        assert_eq!(components.names().collect::<Vec<_>>(), vec!["media", "networking", "vendored"]);
+        assert!(components.has_paths());
+        let root = fs::canonicalize(env::temp_dir()).unwrap()
+            .join(format!("rust-size-{}-components", process::id()));
+        let source = |path: &str| root.join(path).to_string_lossy().into_owned();
+        assert_eq!(components.component("symphonia::decode", Some("symphonia"), None), "media");
+        assert_eq!(components.component("f", None, Some(&source("src/media/a.rs"))), "media");
+        assert_eq!(components.component("necko::open", Some("necko"), None), "networking");
+        // Components are tried in order, so the crate of the second loses to the path of the
+        // first.
+        assert_eq!(components.component("g", Some("media_net"), Some(&source("src/media/b.rs"))),
+                   "media");
+        // Sources outside the directory of the file are matched by their absolute paths.
+        assert_eq!(components.component("h", None, Some("/vendor/zlib/inflate.c")), "vendored");
+        assert_eq!(components.component("h", None, Some(&source("lib/vendor/a.c"))),
+                   NO_COMPONENT);
+        assert_eq!(components.component("main", None, None), NO_COMPONENT);
+    }
+
+    #[test]
+    fn invalid_components() {
+        let err = load("components-regex", "[[component]]\nname = \"a\"\nsymbols = [\"(\"]\n")
+            .err().unwrap();
+        assert!(err.to_string().contains(FILE_NAME));
+        assert!(load("components-name", "[[component]]\ncrates = [\"a\"]\n").is_err());
+    }
+
+    #[test]
+    fn budgets_and_their_sources() {
+        let components = load("components-budgets", r#"
+            [budgets]
+            crate = "256K"
+
+            [budgets.crates]
+            regex = 1000
+
+            [[component]]
+            name = "media"
+            budget = "4M"
+
+            [[component]]
+            name = "ui"
+        "#).unwrap();
+        assert_eq!(components.crate_budget("regex"), Some((1000, BudgetSource::Override)));
+        assert_eq!(components.crate_budget("serde"), Some((256 << 10, BudgetSource::Default)));
+        assert_eq!(components.component_budget("media"), Some((4 << 20, BudgetSource::Override)));
+        assert_eq!(components.component_budget("ui"), None);
+        let err = load("components-size", "[budgets]\ncomponent = \"4 MB\"\n").err().unwrap();
+        assert!(err.to_string().ends_with("Invalid budget: 4 MB"));
+    }
+}
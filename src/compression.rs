@@ -0,0 +1,167 @@
+use failure::Error;
+use goblin::elf::compression_header::ELFCOMPRESS_ZLIB;
+use goblin::elf::section_header::{SectionHeader, SHF_COMPRESSED};
+use goblin::elf::Elf;
+use normalize::normalize_name;
+use section_records;
+
+/// The `ch_type` of zstd, which goblin doesn't know yet.
+const ELFCOMPRESS_ZSTD: u32 = 2;
+
+/// The magic number that starts the GNU-style compressed `.zdebug_` sections, followed by the
+/// uncompressed size as a big-endian 64-bit number and the zlib stream.
+const ZDEBUG_MAGIC: &[u8] = b"ZLIB";
+
+/// How a compressed ELF debug section is compressed.
+#[derive(Clone, Debug, Serialize)]
+pub struct Compression {
+    /// `zlib` or `zstd`, or the `ch_type` of other compression.
+    pub algorithm: String,
+    pub uncompressed_size: u64,
+}
+
+/// The compression of the ELF section `sh` of `elf`, read from `buf`, if it is compressed:
+/// `SHF_COMPRESSED` sections start with an `Elf_Chdr` (as `-gz` or `-gz=zstd` write), and
+/// `.zdebug_` sections with a `ZLIB` header (as `-gz=zlib-gnu` writes).
+pub fn elf_compression(buf: &[u8], elf: &Elf, sh: &SectionHeader, name: &str)
+                       -> Option<Compression> {
+    let end = sh.sh_offset.checked_add(sh.sh_size)?;
+    let data = buf.get(sh.sh_offset as usize..end as usize)?;
+    if sh.sh_flags & u64::from(SHF_COMPRESSED) != 0 {
+        let read = |i: usize, size: usize| -> Option<u64> {
+            let bytes = data.get(i..i + size)?;
+            Some(bytes.iter().enumerate().fold(0u64, |value, (j, &b)| {
+                let shift = if elf.little_endian { 8 * j } else { 8 * (size - 1 - j) };
+                value | u64::from(b) << shift
+            }))
+        };
+        // `ch_type`, then in 64-bit files a reserved word, then `ch_size`.
+        let algorithm = read(0, 4)? as u32;
+        let uncompressed_size = if elf.is_64 { read(8, 8)? } else { read(4, 4)? };
+        return Some(Compression {
+            algorithm: match algorithm {
+                ELFCOMPRESS_ZLIB => "zlib".to_string(),
+                ELFCOMPRESS_ZSTD => "zstd".to_string(),
+                other => format!("type {}", other),
+            },
+            uncompressed_size,
+        });
+    }
+    if name.starts_with(".zdebug_") && data.starts_with(ZDEBUG_MAGIC) {
+        let size = data.get(4..12)?;
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(size);
+        return Some(Compression {
+            algorithm: "zlib".to_string(),
+            uncompressed_size: u64::from_be_bytes(bytes),
+        });
+    }
+    None
+}
+
+/// A debug section, with its size before compression.
+#[derive(Clone, Debug, Serialize)]
+pub struct DebugSection {
+    pub name: String,
+    /// The size in the file.
+    pub size: u64,
+    /// The size of the contents once decompressed, which is `size` if the section isn't
+    /// compressed.
+    pub uncompressed_size: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub algorithm: Option<String>,
+}
+
+/// The debug sections of a binary, as emitted by `debug-sections --format json`.
+#[derive(Clone, Debug, Serialize)]
+pub struct DebugSectionsReport {
+    pub sections: Vec<DebugSection>,
+    /// The size of the debug sections in the file, which stripping them saves.
+    pub size: u64,
+    pub uncompressed_size: u64,
+    /// The bytes that compression saves.
+    pub compression_saved: u64,
+    pub file_size: u64,
+}
+
+/// List the debug sections of `buf`, with their sizes in the file and, for compressed ELF
+/// sections, once decompressed, as read from their compression headers.
+pub fn debug_sections(buf: &[u8]) -> Result<DebugSectionsReport, Error> {
+    let sections: Vec<DebugSection> = section_records(buf)?.into_iter()
+        .filter(|record| normalize_name(&record.name) == "debug")
+        .map(|record| {
+            let (uncompressed_size, algorithm) = match record.compression {
+                Some(compression) => (compression.uncompressed_size, Some(compression.algorithm)),
+                None => (record.size, None),
+            };
+            DebugSection { name: record.name, size: record.size, uncompressed_size, algorithm }
+        })
+        .collect();
+    let size = sections.iter().map(|section| section.size).sum();
+    let uncompressed_size = sections.iter().map(|section| section.uncompressed_size).sum();
+    Ok(DebugSectionsReport {
+        sections,
+        size,
+        uncompressed_size,
+        compression_saved: uncompressed_size.saturating_sub(size),
+        file_size: buf.len() as u64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::elf::section_header::{SHF_ALLOC, SHF_EXECINSTR, SHT_PROGBITS};
+    use testelf;
+
+    /// An `Elf64_Chdr` of type `ch_type` for `size` bytes, then some of the stream.
+    fn chdr(ch_type: u32, size: u64) -> Vec<u8> {
+        let mut data = ch_type.to_le_bytes().to_vec();
+        data.extend_from_slice(&[0; 4]);
+        data.extend_from_slice(&size.to_le_bytes());
+        data.extend_from_slice(&1u64.to_le_bytes());
+        data.extend_from_slice(&[0x78; 8]);
+        data
+    }
+
+    #[test]
+    fn compressed_and_uncompressed_debug_sections() {
+        let mut zdebug = b"ZLIB".to_vec();
+        zdebug.extend_from_slice(&500u64.to_be_bytes());
+        zdebug.extend_from_slice(&[0x78; 4]);
+        let compressed = SHF_COMPRESSED;
+        let buf = testelf::Elf::object()
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &[0; 16])
+            .section(".debug_info", SHT_PROGBITS, compressed, &chdr(ELFCOMPRESS_ZLIB, 1000))
+            .section(".debug_abbrev", SHT_PROGBITS, compressed, &chdr(ELFCOMPRESS_ZSTD, 300))
+            .section(".debug_ranges", SHT_PROGBITS, compressed, &chdr(9, 40))
+            .section(".zdebug_line", SHT_PROGBITS, 0, &zdebug)
+            .section(".debug_str", SHT_PROGBITS, 0, &[0; 10])
+            .build();
+        let report = debug_sections(&buf).unwrap();
+        let sections: Vec<_> = report.sections.iter()
+            .map(|s| (&*s.name, s.size, s.uncompressed_size, s.algorithm.as_deref()))
+            .collect();
+        assert_eq!(sections, vec![
+            (".debug_info", 32, 1000, Some("zlib")),
+            (".debug_abbrev", 32, 300, Some("zstd")),
+            (".debug_ranges", 32, 40, Some("type 9")),
+            (".zdebug_line", 16, 500, Some("zlib")),
+            (".debug_str", 10, 10, None),
+        ]);
+        assert_eq!((report.size, report.uncompressed_size), (122, 1850));
+        assert_eq!(report.compression_saved, 1850 - 122);
+        assert_eq!(report.file_size, buf.len() as u64);
+    }
+
+    #[test]
+    fn sections_past_the_end_are_not_compressed() {
+        let mut buf = testelf::Elf::object()
+            .section(".debug_info", SHT_PROGBITS, SHF_COMPRESSED, &chdr(ELFCOMPRESS_ZLIB, 1000))
+            .build();
+        testelf::corrupt_section(&mut buf, 0, u64::MAX - 2, 32);
+        let report = debug_sections(&buf).unwrap();
+        assert_eq!((report.size, report.uncompressed_size), (32, 32));
+        assert_eq!(report.sections[0].algorithm, None);
+    }
+}
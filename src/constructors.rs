@@ -0,0 +1,254 @@
+use failure::Error;
+use goblin::elf::dyn::DT_INIT;
+use goblin::elf::sym::STT_FUNC;
+use goblin::mach::Mach;
+use goblin::Object;
+use section_records;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use symbols;
+
+/// The sections holding pointers to functions run at startup, before `main`.
+const POINTER_TABLES: &[&str] = &[".preinit_array", ".init_array", ".ctors", "__mod_init_func",
+                                  ".CRT"];
+
+/// The Mach-O section holding 32-bit offsets to initializers from the start of the image.
+const OFFSET_TABLE: &str = "__init_offsets";
+
+/// A function run at startup.
+#[derive(Clone, Debug, Serialize)]
+pub struct Constructor {
+    /// The table that runs the function, or `DT_INIT` for the ELF `.init` entry point.
+    pub table: String,
+    pub address: u64,
+    /// The function's (mangled) name, if the symbol table has it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// The size of the function's code, not counting what it calls.
+    pub size: u64,
+}
+
+/// The static initialization of a binary, as emitted by `constructors --format json`.
+#[derive(Clone, Debug, Serialize)]
+pub struct ConstructorReport {
+    /// The size of each initializer table.
+    pub tables: BTreeMap<String, u64>,
+    pub constructors: Vec<Constructor>,
+    /// The code size of the distinct constructors found in the symbol table.
+    pub code_size: u64,
+    /// The entries whose functions aren't in the symbol table, e.g. in stripped binaries or
+    /// relocatable objects, where the entries are still relocations. Functions in the symbol
+    /// table without a size, like `_init`, count as resolved, with no code.
+    pub unresolved: u64,
+}
+
+/// How to read the pointers of a binary, and where the functions they point at are.
+struct Layout {
+    pointer_size: usize,
+    little_endian: bool,
+    /// The address the image is linked at, which Mach-O `__init_offsets` entries are relative
+    /// to, and PE pointers include.
+    image_base: u64,
+    /// Relative relocations by the address they apply to, with the address they produce, for
+    /// pointers that are only filled in at load time.
+    relocated: HashMap<u64, u64>,
+    /// The symbols of the functions, by address.
+    functions: BTreeMap<u64, (String, u64)>,
+    macho: bool,
+}
+
+impl Layout {
+    fn read(&self, data: &[u8], size: usize) -> u64 {
+        let mut bytes = [0; 8];
+        if self.little_endian {
+            bytes[..size].copy_from_slice(&data[..size]);
+            u64::from_le_bytes(bytes)
+        } else {
+            bytes[8 - size..].copy_from_slice(&data[..size]);
+            u64::from_be_bytes(bytes)
+        }
+    }
+
+    /// The function that the pointer at `address` with contents `value` calls, if it is in
+    /// the symbol table.
+    fn target(&self, address: u64, value: u64) -> u64 {
+        if let Some(&target) = self.relocated.get(&address) {
+            return target;
+        }
+        if self.macho && !self.functions.contains_key(&value) {
+            // With chained fixups, a rebase keeps its target in the low 36 bits, as an address
+            // or as an offset from the image.
+            let target = value & 0xf_ffff_ffff;
+            if self.functions.contains_key(&target) {
+                return target;
+            }
+            return target + self.image_base;
+        }
+        value
+    }
+
+    fn constructor(&self, table: &str, address: u64) -> Constructor {
+        let function = self.functions.get(&address);
+        Constructor {
+            table: table.to_string(),
+            address,
+            name: function.map(|f| f.0.clone()),
+            size: function.map_or(0, |f| f.1),
+        }
+    }
+}
+
+/// List the initializer tables of `buf` and the functions they run.
+pub fn constructors(buf: &[u8]) -> Result<ConstructorReport, Error> {
+    let mut layout = Layout {
+        pointer_size: 8,
+        little_endian: true,
+        image_base: 0,
+        relocated: HashMap::new(),
+        functions: BTreeMap::new(),
+        macho: false,
+    };
+    let mut init = None;
+    let mut unsized_functions = Vec::new();
+    match Object::parse(buf)? {
+        Object::Elf(elf) => {
+            layout.pointer_size = if elf.is_64 { 8 } else { 4 };
+            layout.little_endian = elf.little_endian;
+            for reloc in elf.dynrelas.iter().filter(|reloc| reloc.r_sym == 0) {
+                if let Some(addend) = reloc.r_addend {
+                    layout.relocated.insert(reloc.r_offset, addend as u64);
+                }
+            }
+            // Startup code like `_init` and `frame_dummy` has no size, so `symbols` skips it.
+            let functions = elf.syms.iter().filter(|sym| {
+                sym.st_type() == STT_FUNC && sym.st_value != 0
+            });
+            for sym in functions {
+                if let Some(Ok(name)) = elf.strtab.get(sym.st_name) {
+                    unsized_functions.push((sym.st_value, name.to_string()));
+                }
+            }
+            init = elf.dynamic.as_ref()
+                .and_then(|dynamic| dynamic.dyns.iter().find(|d| d.d_tag == DT_INIT))
+                .map(|d| d.d_val);
+        }
+        Object::Mach(Mach::Binary(mach)) => {
+            layout.pointer_size = if mach.is_64 { 8 } else { 4 };
+            layout.little_endian = mach.little_endian;
+            layout.image_base = mach.segments.iter()
+                .find(|seg| seg.name().ok() == Some("__TEXT"))
+                .map_or(0, |seg| seg.vmaddr);
+            layout.macho = true;
+        }
+        Object::Mach(Mach::Fat(_)) => bail!("A universal binary is analyzed a slice at a time"),
+        Object::PE(pe) => {
+            layout.pointer_size = if pe.is_64 { 8 } else { 4 };
+            layout.image_base = pe.image_base as u64;
+        }
+        _ => bail!("Unhandled file type!"),
+    }
+    for sym in symbols::symbols(buf)?.into_iter().filter(|sym| sym.code) {
+        layout.functions.entry(sym.address).or_insert((sym.name, sym.size));
+    }
+    for (address, name) in unsized_functions {
+        layout.functions.entry(address).or_insert((name, 0));
+    }
+
+    let mut tables = BTreeMap::new();
+    let mut constructors = Vec::new();
+    if let Some(address) = init {
+        constructors.push(layout.constructor("DT_INIT", address));
+    }
+    for record in section_records(buf)? {
+        let is_offsets = record.name == OFFSET_TABLE;
+        if !is_offsets && !POINTER_TABLES.contains(&&*record.name) {
+            continue;
+        }
+        *tables.entry(record.name.clone()).or_insert(0) += record.size;
+        let data = match record.offset {
+            Some(offset) => buf.get(offset as usize..(offset + record.size) as usize),
+            None => None,
+        };
+        let (data, address) = match (data, record.address) {
+            (Some(data), Some(address)) => (data, address),
+            _ => continue,
+        };
+        let entry_size = if is_offsets { 4 } else { layout.pointer_size };
+        for (i, entry) in data.chunks_exact(entry_size).enumerate() {
+            let value = layout.read(entry, entry_size);
+            let slot = address + (i * entry_size) as u64;
+            let target = if is_offsets {
+                layout.image_base + value
+            } else if record.name == ".CRT" {
+                // PE pointers are absolute, and the tables have null sentinels.
+                if value == 0 {
+                    continue;
+                }
+                value.saturating_sub(layout.image_base)
+            } else {
+                layout.target(slot, value)
+            };
+            // `.ctors` starts with -1 and ends with 0.
+            let max = if entry_size == 8 { u64::MAX } else { u64::from(u32::MAX) };
+            if record.name == ".ctors" && (target == 0 || target == max) {
+                continue;
+            }
+            constructors.push(layout.constructor(&record.name, target));
+        }
+    }
+
+    let code_size = constructors.iter()
+        .filter(|c| c.name.is_some())
+        .map(|c| (c.address, c.size))
+        .collect::<BTreeSet<_>>()
+        .iter()
+        .map(|&(_, size)| size)
+        .sum();
+    let unresolved = constructors.iter().filter(|c| c.name.is_none()).count() as u64;
+    Ok(ConstructorReport { tables, constructors, code_size, unresolved })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout(little_endian: bool, macho: bool) -> Layout {
+        Layout {
+            pointer_size: 8,
+            little_endian,
+            image_base: 0x1_0000_0000,
+            relocated: HashMap::new(),
+            functions: BTreeMap::new(),
+            macho,
+        }
+    }
+
+    #[test]
+    fn read_pointers() {
+        let data = [1, 2, 3, 4, 5, 6, 7, 8];
+        assert_eq!(layout(true, false).read(&data, 4), 0x0403_0201);
+        assert_eq!(layout(false, false).read(&data, 4), 0x0102_0304);
+        assert_eq!(layout(true, false).read(&data, 8), 0x0807_0605_0403_0201);
+    }
+
+    #[test]
+    fn targets() {
+        let mut elf = layout(true, false);
+        elf.relocated.insert(0x2000, 0x1234);
+        assert_eq!(elf.target(0x2000, 0), 0x1234);
+        assert_eq!(elf.target(0x3000, 0x5678), 0x5678);
+
+        // A chained rebase keeps an offset from the image, or an address, in its low 36 bits.
+        let mut mach = layout(true, true);
+        assert_eq!(mach.target(0, 0x8010_0000_0000_4000), 0x1_0000_4000);
+        mach.functions.insert(0x4000, ("_f".to_string(), 16));
+        assert_eq!(mach.target(0, 0x8010_0000_0000_4000), 0x4000);
+        assert_eq!(mach.constructor("__mod_init_func", 0x4000).name.as_deref(), Some("_f"));
+    }
+
+    #[test]
+    fn universal_binary_is_an_error() {
+        let fat = [0xca, 0xfe, 0xba, 0xbe, 0, 0, 0, 1, 1, 0, 0, 7, 0, 0, 0, 3,
+                   0, 0, 0, 28, 0, 0, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0];
+        assert!(constructors(&fat).is_err());
+    }
+}
@@ -0,0 +1,254 @@
+use exit::UsageError;
+use failure::Error;
+use goblin::elf::header::ET_CORE;
+use goblin::elf::program_header::{PF_R, PF_W, PF_X, PT_LOAD};
+use goblin::elf::Elf;
+use std::collections::BTreeMap;
+
+/// The note types of the process's name and of the files that it had mapped.
+const NT_PRPSINFO: u32 = 3;
+const NT_FILE: u32 = 0x4649_4c45;
+
+/// The name that mappings of no file are grouped under.
+const ANONYMOUS: &str = "[anonymous]";
+
+/// A LOAD segment of a core file: a mapping of the process that dumped it.
+#[derive(Clone, Debug, Serialize)]
+pub struct CoreSegment {
+    pub address: u64,
+    /// The size of the mapping in the process's address space.
+    pub memory_size: u64,
+    /// The bytes of it that the dump holds. Dumps leave out the pages of mapped files that the
+    /// process didn't write to, by default.
+    pub dumped_size: u64,
+    /// The bytes of `dumped_size` that aren't zero pages, as an estimate of what the process had
+    /// in memory: the kernel dumps pages that were never touched as zeros, or holes.
+    pub resident_size: u64,
+    /// Like `r-x`.
+    pub permissions: String,
+    /// The file that it maps, if it maps one, and the offset in the file where it starts.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_offset: Option<u64>,
+}
+
+/// The segments of one mapped file, or of the anonymous mappings.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct CoreMapping {
+    pub name: String,
+    pub segments: u64,
+    pub memory_size: u64,
+    pub dumped_size: u64,
+    pub resident_size: u64,
+}
+
+/// The memory of the process that dumped a core file, as emitted by `core --format json`.
+#[derive(Clone, Debug, Serialize)]
+pub struct CoreReport {
+    /// The name of the process's executable, as the kernel recorded it (at most 15 bytes).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub process: Option<String>,
+    pub segments: Vec<CoreSegment>,
+    /// The segments summed by file, largest first.
+    pub mappings: Vec<CoreMapping>,
+    /// The sizes of the segments summed by permissions.
+    pub permissions: BTreeMap<String, u64>,
+    pub memory_size: u64,
+    pub dumped_size: u64,
+    pub resident_size: u64,
+    pub file_size: u64,
+}
+
+/// The `rwx` form of the segment flags `flags`.
+fn permissions(flags: u32) -> String {
+    [(PF_R, 'r'), (PF_W, 'w'), (PF_X, 'x')].iter()
+        .map(|&(flag, c)| if flags & flag != 0 { c } else { '-' })
+        .collect()
+}
+
+/// The files that the `NT_FILE` note `desc` says were mapped: the start and end address of each
+/// mapping, its offset in the file and the file's path.
+fn mapped_files(desc: &[u8], elf: &Elf) -> Vec<(u64, u64, u64, String)> {
+    let word = if elf.is_64 { 8 } else { 4 };
+    let read = |i: usize| desc.get(i * word..(i + 1) * word).map(|bytes| {
+        let mut value = [0; 8];
+        if elf.little_endian {
+            value[..word].copy_from_slice(bytes);
+            u64::from_le_bytes(value)
+        } else {
+            value[8 - word..].copy_from_slice(bytes);
+            u64::from_be_bytes(value)
+        }
+    });
+    // The count and page size, a (start, end, page offset) triple for each file, then the
+    // paths.
+    let (count, page_size) = match (read(0), read(1)) {
+        (Some(count), Some(page_size)) => (count as usize, page_size),
+        _ => return Vec::new(),
+    };
+    let names_start = (2 + count * 3) * word;
+    let names = desc.get(names_start..).unwrap_or_default().split(|&b| b == 0);
+    (0..count).zip(names).filter_map(|(i, name)| {
+        let (start, end, page) = (read(2 + i * 3)?, read(3 + i * 3)?, read(4 + i * 3)?);
+        Some((start, end, page * page_size, String::from_utf8_lossy(name).into_owned()))
+    }).collect()
+}
+
+/// The name of the process in the `NT_PRPSINFO` note `desc`, which follows the state, flags and
+/// IDs, whose size depends on the word size.
+fn process_name(desc: &[u8], elf: &Elf) -> Option<String> {
+    let start = if elf.is_64 { 40 } else { 28 };
+    let name = desc.get(start..start + 16)?;
+    let len = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+    Some(String::from_utf8_lossy(&name[..len]).into_owned())
+}
+
+/// The bytes of `data` in pages of `page_size` that aren't all zeros.
+fn nonzero_size(data: &[u8], page_size: usize) -> u64 {
+    data.chunks(page_size)
+        .filter(|page| page.iter().any(|&b| b != 0))
+        .map(|page| page.len() as u64)
+        .sum()
+}
+
+/// Report the LOAD segments of the core file `buf` with the files that they map, their
+/// permissions and how much of them the process had in memory.
+pub fn core(buf: &[u8]) -> Result<CoreReport, Error> {
+    let elf = match Elf::parse(buf) {
+        Ok(elf) if elf.header.e_type == ET_CORE => elf,
+        _ => return Err(UsageError("core needs an ELF core file".to_string()).into()),
+    };
+    let (mut process, mut files) = (None, Vec::new());
+    for note in elf.iter_note_headers(buf).into_iter().flatten() {
+        let note = note?;
+        match (note.name, note.n_type) {
+            ("CORE", NT_PRPSINFO) => process = process_name(note.desc, &elf),
+            ("CORE", NT_FILE) => files = mapped_files(note.desc, &elf),
+            _ => {}
+        }
+    }
+
+    let mut report = CoreReport {
+        process,
+        segments: Vec::new(),
+        mappings: Vec::new(),
+        permissions: BTreeMap::new(),
+        memory_size: 0,
+        dumped_size: 0,
+        resident_size: 0,
+        file_size: buf.len() as u64,
+    };
+    let mut mappings: BTreeMap<String, CoreMapping> = BTreeMap::new();
+    for ph in elf.program_headers.iter().filter(|ph| ph.p_type == PT_LOAD) {
+        let data = buf.get(ph.p_offset as usize..(ph.p_offset + ph.p_filesz) as usize)
+            .unwrap_or_default();
+        let page_size = (ph.p_align as usize).clamp(1, 1 << 16);
+        let file = files.iter().find(|&&(start, end, _, _)| {
+            ph.p_vaddr >= start && ph.p_vaddr < end
+        });
+        let segment = CoreSegment {
+            address: ph.p_vaddr,
+            memory_size: ph.p_memsz,
+            dumped_size: data.len() as u64,
+            resident_size: nonzero_size(data, page_size),
+            permissions: permissions(ph.p_flags),
+            file: file.map(|(_, _, _, name)| name.clone()),
+            file_offset: file.map(|&(start, _, offset, _)| offset + (ph.p_vaddr - start)),
+        };
+        let name = segment.file.clone().unwrap_or_else(|| ANONYMOUS.to_string());
+        let mapping = mappings.entry(name.clone()).or_default();
+        mapping.name = name;
+        mapping.segments += 1;
+        mapping.memory_size += segment.memory_size;
+        mapping.dumped_size += segment.dumped_size;
+        mapping.resident_size += segment.resident_size;
+        *report.permissions.entry(segment.permissions.clone()).or_default() += segment.memory_size;
+        report.memory_size += segment.memory_size;
+        report.dumped_size += segment.dumped_size;
+        report.resident_size += segment.resident_size;
+        report.segments.push(segment);
+    }
+    report.mappings = mappings.into_values().collect();
+    report.mappings.sort_by(|a, b| (b.memory_size, &a.name).cmp(&(a.memory_size, &b.name)));
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::elf::program_header::PT_NOTE;
+    use goblin::elf::section_header::{SHF_ALLOC, SHF_EXECINSTR, SHF_WRITE, SHT_NOTE};
+    use goblin::elf::section_header::SHT_PROGBITS;
+    use testelf;
+
+    /// A `CORE` note of type `n_type`.
+    fn note(n_type: u32, desc: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        for word in &[5, desc.len() as u32, n_type] {
+            out.extend_from_slice(&word.to_le_bytes());
+        }
+        out.extend_from_slice(b"CORE\0\0\0\0");
+        out.extend_from_slice(desc);
+        out.resize(out.len().div_ceil(4) * 4, 0);
+        out
+    }
+
+    /// A core file of `app`, which mapped `/usr/lib/libc.so.6` from its third page at `start`
+    /// and had 32 bytes of anonymous memory.
+    fn dump(start: u64) -> Vec<u8> {
+        let mut prpsinfo = vec![0; 40];
+        prpsinfo.extend_from_slice(b"app\0\0\0\0\0\0\0\0\0\0\0\0\0");
+        prpsinfo.extend_from_slice(&[0; 80]);
+        let mut file = Vec::new();
+        for word in &[1, 0x1000, start, start + 32, 2] {
+            file.extend_from_slice(&u64::to_le_bytes(*word));
+        }
+        file.extend_from_slice(b"/usr/lib/libc.so.6\0");
+        let mut notes = note(NT_PRPSINFO, &prpsinfo);
+        notes.extend_from_slice(&note(NT_FILE, &file));
+
+        let mut text = vec![0xc3; 16];
+        text.extend_from_slice(&[0; 16]);
+        testelf::Elf::core()
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &text)
+            .nobits(".bss", SHF_ALLOC | SHF_WRITE, 32)
+            .section(".note", SHT_NOTE, 0, &notes)
+            .segment(PT_LOAD, PF_R | PF_X, &[".text"], 16)
+            .segment(PT_LOAD, PF_R | PF_W, &[".bss"], 16)
+            .segment(PT_NOTE, 0, &[".note"], 4)
+            .build()
+    }
+
+    #[test]
+    fn segments_of_a_core_file() {
+        let start = core(&dump(0)).unwrap().segments[0].address;
+        let buf = dump(start);
+        let report = core(&buf).unwrap();
+        assert_eq!(report.process.as_deref(), Some("app"));
+        let segments: Vec<_> = report.segments.iter()
+            .map(|s| (s.memory_size, s.dumped_size, s.resident_size, &*s.permissions,
+                      s.file.as_deref(), s.file_offset))
+            .collect();
+        assert_eq!(segments, vec![(32, 32, 16, "r-x", Some("/usr/lib/libc.so.6"), Some(0x2000)),
+                                  (32, 0, 0, "rw-", None, None)]);
+        let mappings: Vec<_> = report.mappings.iter()
+            .map(|m| (&*m.name, m.segments, m.memory_size))
+            .collect();
+        assert_eq!(mappings, vec![("/usr/lib/libc.so.6", 1, 32), ("[anonymous]", 1, 32)]);
+        let permissions: Vec<_> = report.permissions.iter().map(|(p, &s)| (&**p, s)).collect();
+        assert_eq!(permissions, vec![("r-x", 32), ("rw-", 32)]);
+        assert_eq!((report.memory_size, report.dumped_size, report.resident_size), (64, 32, 16));
+    }
+
+    #[test]
+    fn only_core_files() {
+        let buf = testelf::Elf::executable().build();
+        match core(&buf) {
+            Err(err) => assert!(err.downcast_ref::<UsageError>().is_some()),
+            Ok(_) => panic!("not a core file"),
+        }
+        assert_eq!(permissions(PF_R | PF_X), "r-x");
+        assert_eq!(nonzero_size(&[0, 0, 1, 0, 0], 2), 2);
+    }
+}
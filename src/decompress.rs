@@ -0,0 +1,203 @@
+use digest::crc32;
+use failure::Error;
+use memmap::Mmap;
+use miniz_oxide::inflate::core::inflate_flags::TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF;
+use miniz_oxide::inflate::core::{decompress as inflate_into, DecompressorOxide};
+use miniz_oxide::inflate::TINFLStatus;
+use std::ops::Deref;
+use xz::{self, XZ_MAGIC};
+
+/// The magic number of gzip files, and the compression method, deflate, that they all use.
+const GZIP_MAGIC: &[u8] = b"\x1f\x8b\x08";
+
+/// The gzip header flags that say which optional fields follow the fixed header.
+const FHCRC: u8 = 0x02;
+const FEXTRA: u8 = 0x04;
+const FNAME: u8 = 0x08;
+const FCOMMENT: u8 = 0x10;
+
+/// The contents of an input file: mapped, or read into memory if the file is compressed or is
+/// standard input.
+pub enum Input {
+    Mapped(Mmap),
+    Buffered(Vec<u8>),
+}
+
+impl Deref for Input {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match *self {
+            Input::Mapped(ref mmap) => mmap,
+            Input::Buffered(ref data) => data,
+        }
+    }
+}
+
+impl Input {
+    /// The contents of the file mapped as `mmap`, decompressed if it is a gzip or xz file.
+    pub fn new(mmap: Mmap) -> Result<Input, Error> {
+        Ok(match decompress(&mmap)? {
+            Some(data) => Input::Buffered(data),
+            None => Input::Mapped(mmap),
+        })
+    }
+
+    /// The contents of a file that was read as `data`, decompressed if it is a gzip or xz file.
+    pub fn from_vec(data: Vec<u8>) -> Result<Input, Error> {
+        Ok(Input::Buffered(decompress(&data)?.unwrap_or(data)))
+    }
+}
+
+/// The decompressed contents of `buf`, if it is a gzip or xz file.
+fn decompress(buf: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+    Ok(if buf.starts_with(GZIP_MAGIC) {
+        Some(gunzip(buf)?)
+    } else if buf.starts_with(XZ_MAGIC) {
+        Some(xz::decompress(buf)?)
+    } else {
+        None
+    })
+}
+
+/// Skip the NUL-terminated string at `*pos` in `buf`.
+fn skip_string(buf: &[u8], pos: &mut usize) -> Result<(), Error> {
+    match buf.get(*pos..).and_then(|rest| rest.iter().position(|&b| b == 0)) {
+        Some(len) => *pos += len + 1,
+        None => bail!("Truncated gzip header"),
+    }
+    Ok(())
+}
+
+/// Inflate the deflate stream at the start of `data`, returning the inflated bytes and the
+/// length of the stream, after which `data` goes on.
+fn inflate(mut data: &[u8]) -> Result<(Vec<u8>, usize), Error> {
+    let mut decompressor = DecompressorOxide::default();
+    let mut out = vec![0; data.len().saturating_mul(2).max(64)];
+    let (mut consumed, mut written) = (0, 0);
+    loop {
+        let (status, read, wrote) = inflate_into(&mut decompressor, data, &mut out, written,
+                                                 TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF);
+        consumed += read;
+        written += wrote;
+        match status {
+            TINFLStatus::Done => {
+                out.truncate(written);
+                return Ok((out, consumed));
+            }
+            TINFLStatus::HasMoreOutput => {
+                data = &data[read..];
+                let len = out.len().saturating_mul(2);
+                out.resize(len, 0);
+            }
+            status => bail!("Invalid gzip data ({:?})", status),
+        }
+    }
+}
+
+/// Decompress the gzip file `buf`, checking the CRC-32 and the size in its trailer. Only the
+/// first member is read: files that `gzip` wrote in one go have only one.
+fn gunzip(buf: &[u8]) -> Result<Vec<u8>, Error> {
+    let flags = match buf.get(3) {
+        Some(&flags) => flags,
+        None => bail!("Truncated gzip header"),
+    };
+    let mut pos = 10;
+    if flags & FEXTRA != 0 {
+        match buf.get(pos..pos + 2) {
+            Some(len) => pos += 2 + usize::from(u16::from_le_bytes([len[0], len[1]])),
+            None => bail!("Truncated gzip header"),
+        }
+    }
+    if flags & FNAME != 0 {
+        skip_string(buf, &mut pos)?;
+    }
+    if flags & FCOMMENT != 0 {
+        skip_string(buf, &mut pos)?;
+    }
+    if flags & FHCRC != 0 {
+        pos += 2;
+    }
+    let (data, len) = match buf.get(pos..) {
+        Some(data) => inflate(data)?,
+        None => bail!("Truncated gzip header"),
+    };
+    let trailer = match buf.get(pos + len..pos + len + 8) {
+        Some(trailer) => trailer,
+        None => bail!("Truncated gzip file"),
+    };
+    let word = |i: usize| u32::from_le_bytes([trailer[i], trailer[i + 1], trailer[i + 2],
+                                               trailer[i + 3]]);
+    if word(0) != crc32(&data) {
+        bail!("Corrupt gzip file: the CRC-32 doesn't match the data");
+    }
+    // The size is kept modulo 2^32.
+    if word(4) != data.len() as u32 {
+        bail!("Corrupt gzip file: the size doesn't match the data");
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use miniz_oxide::deflate::compress_to_vec;
+
+    /// `data` as gzip compresses it, with the header flags `flags` and the optional fields
+    /// `fields` that they call for.
+    fn gzip(data: &[u8], flags: u8, fields: &[u8]) -> Vec<u8> {
+        let mut out = GZIP_MAGIC.to_vec();
+        out.extend_from_slice(&[flags, 0, 0, 0, 0, 0, 3]);
+        out.extend_from_slice(fields);
+        out.extend_from_slice(&compress_to_vec(data, 6));
+        out.extend_from_slice(&crc32(data).to_le_bytes());
+        out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        out
+    }
+
+    #[test]
+    fn gzip_files_with_optional_fields() {
+        let elf = b"\x7fELF and the rest of the file".to_vec();
+        assert_eq!(gunzip(&gzip(&elf, 0, &[])).unwrap(), elf);
+        let fields = b"\x02\0ab libxul.so\0a comment\0\xff\xff";
+        let buf = gzip(&elf, FEXTRA | FNAME | FCOMMENT | FHCRC, fields);
+        assert_eq!(gunzip(&buf).unwrap(), elf);
+        assert_eq!(&*Input::from_vec(buf).unwrap(), &elf[..]);
+
+        // Other files are read as they are.
+        assert_eq!(decompress(&elf).unwrap(), None);
+        assert_eq!(&*Input::from_vec(elf.clone()).unwrap(), &elf[..]);
+    }
+
+    #[test]
+    fn truncated_gzip_files() {
+        assert!(gunzip(GZIP_MAGIC).is_err());
+        let buf = gzip(b"data", FNAME, b"no terminator");
+        assert!(gunzip(&buf[..10 + 13]).is_err());
+        assert!(gunzip(&gzip(b"data", FEXTRA, &[])[..11]).is_err());
+        let mut buf = gzip(b"data", 0, &[]);
+        buf.truncate(12);
+        assert!(decompress(&buf).is_err());
+    }
+
+    #[test]
+    fn gzip_files_with_bad_trailers() {
+        let data = b"\x7fELF and the rest of the file, which is long enough to compress".repeat(8);
+        let buf = gzip(&data, 0, &[]);
+        // A second member after the first is left alone.
+        let mut members = buf.clone();
+        members.extend_from_slice(&gzip(b"more", 0, &[]));
+        assert_eq!(gunzip(&members).unwrap(), data);
+
+        let mut crc = buf.clone();
+        let at = crc.len() - 8;
+        crc[at] ^= 1;
+        assert!(gunzip(&crc).unwrap_err().to_string().contains("CRC-32"));
+        let mut size = buf.clone();
+        let at = size.len() - 4;
+        size[at] ^= 1;
+        assert!(gunzip(&size).unwrap_err().to_string().contains("size"));
+        let err = gunzip(&buf[..buf.len() - 3]).unwrap_err();
+        assert_eq!(err.to_string(), "Truncated gzip file");
+    }
+}
@@ -0,0 +1,49 @@
+use std::borrow::Cow;
+
+/// The delimited text formats, for spreadsheets and database imports.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Delimited {
+    /// Comma-separated, with fields that need it quoted as in RFC 4180.
+    Csv,
+    /// Tab-separated, with tabs, line breaks and backslashes in fields escaped with
+    /// backslashes, as PostgreSQL's `COPY` and most TSV readers expect.
+    Tsv,
+}
+
+impl Delimited {
+    /// `field`, quoted or escaped if it needs to be.
+    fn field(self, field: &str) -> Cow<'_, str> {
+        match self {
+            Delimited::Csv if field.contains([',', '"', '\n', '\r']) => {
+                Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+            }
+            Delimited::Tsv if field.contains(['\t', '\n', '\r', '\\']) => {
+                Cow::Owned(field.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n")
+                           .replace('\r', "\\r"))
+            }
+            _ => Cow::Borrowed(field),
+        }
+    }
+
+    /// The line of `fields`, without its line break.
+    pub fn row(self, fields: &[&str]) -> String {
+        let separator = if self == Delimited::Csv { "," } else { "\t" };
+        fields.iter().map(|field| self.field(field)).collect::<Vec<_>>().join(separator)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quoting_and_escaping() {
+        let fields = ["a.o", "Text", "odd,\"name\"", "12"];
+        assert_eq!(Delimited::Csv.row(&fields), "a.o,Text,\"odd,\"\"name\"\"\",12");
+        assert_eq!(Delimited::Tsv.row(&fields), "a.o\tText\todd,\"name\"\t12");
+        let fields = ["tab\there", "line\nbreak", "back\\slash"];
+        assert_eq!(Delimited::Csv.row(&fields), "tab\there,\"line\nbreak\",back\\slash");
+        assert_eq!(Delimited::Tsv.row(&fields), "tab\\there\tline\\nbreak\tback\\\\slash");
+        assert_eq!(Delimited::Csv.row(&[]), "");
+    }
+}
@@ -0,0 +1,402 @@
+use miniz_oxide::deflate::compress_to_vec;
+use std::cmp::Ordering;
+
+/// The deflate level to compress patches and whole files with.
+const LEVEL: u8 = 9;
+
+/// The size of the header of a bsdiff patch: the magic and the three lengths.
+const HEADER_SIZE: u64 = 32;
+
+/// What updating from one version of a file to another costs, as emitted by
+/// `delta --format json`.
+#[derive(Clone, Debug, Serialize)]
+pub struct DeltaReport {
+    pub old_size: u64,
+    pub new_size: u64,
+    /// The raw difference in size.
+    pub size_change: i64,
+    /// The size of the new file compressed: what downloading it whole costs.
+    pub full_download: u64,
+    /// The estimated size of a bsdiff patch from the old file to the new one, with its parts
+    /// compressed with deflate where bsdiff uses bzip2.
+    pub patch: u64,
+    /// The compressed sizes of the parts of the patch: the control entries, which say where to
+    /// copy from, the differences of the copied bytes, and the bytes that are inserted.
+    pub control: u64,
+    pub diff: u64,
+    pub extra: u64,
+    /// The bytes of the new file that are copied from the old one, with differences.
+    pub copied: u64,
+    /// Of those, the ones that are the same as in the old file.
+    pub unchanged: u64,
+    /// The bytes of the new file that aren't in the old one.
+    pub inserted: u64,
+}
+
+/// Sort the suffixes `sa[start..start + len]`, which have the same first `h` bytes, by the
+/// group of the suffixes `h` after them, splitting them into groups, as qsufsort does.
+/// `group` is the index of the last suffix of the group of each suffix in `sa`, and sorted
+/// runs in `sa` are marked by their negated lengths.
+fn split(sa: &mut [isize], group: &mut [isize], mut start: usize, mut len: usize, h: usize) {
+    let key = |group: &[isize], i: isize| group[i as usize + h];
+    loop {
+        if len < 16 {
+            // Selection sort, a whole group of the smallest key at a time.
+            let mut k = start;
+            while k < start + len {
+                let mut j = 1;
+                let mut x = key(group, sa[k]);
+                for i in 1..start + len - k {
+                    let y = key(group, sa[k + i]);
+                    if y < x {
+                        x = y;
+                        j = 0;
+                    }
+                    if y == x {
+                        sa.swap(k + j, k + i);
+                        j += 1;
+                    }
+                }
+                for i in 0..j {
+                    group[sa[k + i] as usize] = (k + j - 1) as isize;
+                }
+                if j == 1 {
+                    sa[k] = -1;
+                }
+                k += j;
+            }
+            return;
+        }
+
+        // A three-way partition around the middle key.
+        let x = key(group, sa[start + len / 2]);
+        let (mut less, mut equal) = (0, 0);
+        for &i in &sa[start..start + len] {
+            let y = key(group, i);
+            less += usize::from(y < x);
+            equal += usize::from(y == x);
+        }
+        let (jj, kk) = (start + less, start + less + equal);
+        let (mut i, mut j, mut k) = (start, 0, 0);
+        while i < jj {
+            let y = key(group, sa[i]);
+            if y < x {
+                i += 1;
+            } else if y == x {
+                sa.swap(i, jj + j);
+                j += 1;
+            } else {
+                sa.swap(i, kk + k);
+                k += 1;
+            }
+        }
+        while jj + j < kk {
+            if key(group, sa[jj + j]) == x {
+                j += 1;
+            } else {
+                sa.swap(jj + j, kk + k);
+                k += 1;
+            }
+        }
+        if jj > start {
+            split(sa, group, start, jj - start, h);
+        }
+        for i in jj..kk {
+            group[sa[i] as usize] = kk as isize - 1;
+        }
+        if jj == kk - 1 {
+            sa[jj] = -1;
+        }
+        if start + len <= kk {
+            return;
+        }
+        len = start + len - kk;
+        start = kk;
+    }
+}
+
+/// The suffix array of `buf`, with the empty suffix first, by Larsson and Sadakane's
+/// qsufsort, as bsdiff builds it.
+fn suffix_array(buf: &[u8]) -> Vec<usize> {
+    let n = buf.len();
+    let mut buckets = [0usize; 256];
+    for &b in buf {
+        buckets[usize::from(b)] += 1;
+    }
+    for i in 1..256 {
+        buckets[i] += buckets[i - 1];
+    }
+    for i in (1..256).rev() {
+        buckets[i] = buckets[i - 1];
+    }
+    buckets[0] = 0;
+    let mut sa = vec![0isize; n + 1];
+    for (i, &b) in buf.iter().enumerate() {
+        buckets[usize::from(b)] += 1;
+        sa[buckets[usize::from(b)]] = i as isize;
+    }
+    sa[0] = n as isize;
+    let mut group: Vec<isize> = buf.iter().map(|&b| buckets[usize::from(b)] as isize)
+        .chain(Some(0))
+        .collect();
+    for i in 1..256 {
+        if buckets[i] == buckets[i - 1] + 1 {
+            sa[buckets[i]] = -1;
+        }
+    }
+    sa[0] = -1;
+    let mut h = 1;
+    while sa[0] != -(n as isize + 1) {
+        let mut len = 0;
+        let mut i = 0;
+        while i < n + 1 {
+            if sa[i] < 0 {
+                len -= sa[i];
+                i = (i as isize - sa[i]) as usize;
+            } else {
+                if len != 0 {
+                    sa[i - len as usize] = -len;
+                }
+                let group_len = group[sa[i] as usize] as usize + 1 - i;
+                split(&mut sa, &mut group, i, group_len, h);
+                i += group_len;
+                len = 0;
+            }
+        }
+        if len != 0 {
+            sa[i - len as usize] = -len;
+        }
+        h += h;
+    }
+    let mut sorted = vec![0; n + 1];
+    for (i, &g) in group.iter().enumerate() {
+        sorted[g as usize] = i;
+    }
+    sorted
+}
+
+/// The length of the common prefix of `a` and `b`.
+fn match_len(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(x, y)| x == y).count()
+}
+
+/// The longest prefix of `new` in `old`, whose suffix array is `sa`: its position and length.
+fn search(sa: &[usize], old: &[u8], new: &[u8]) -> (usize, usize) {
+    let (mut start, mut end) = (0, sa.len() - 1);
+    while end - start >= 2 {
+        let mid = start + (end - start) / 2;
+        let suffix = &old[sa[mid]..];
+        let len = suffix.len().min(new.len());
+        if suffix[..len].cmp(&new[..len]) == Ordering::Less {
+            start = mid;
+        } else {
+            end = mid;
+        }
+    }
+    let x = match_len(&old[sa[start]..], new);
+    let y = match_len(&old[sa[end]..], new);
+    if x > y { (sa[start], x) } else { (sa[end], y) }
+}
+
+/// The parts of a bsdiff patch from `old` to `new`, uncompressed: the control entries, the
+/// differences and the inserted bytes. This is the matching of bsdiff 4.
+fn bsdiff(old: &[u8], new: &[u8]) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+    let sa = suffix_array(old);
+    let (mut control, mut diff, mut extra) = (Vec::new(), Vec::new(), Vec::new());
+    let (mut scan, mut len, mut pos) = (0, 0, 0);
+    let (mut last_scan, mut last_pos, mut last_offset) = (0, 0, 0isize);
+    let old_at = |i: isize| if i >= 0 { old.get(i as usize).copied() } else { None };
+    while scan < new.len() {
+        // Find the next match that is more than eight bytes better than just carrying on
+        // from the last one.
+        let mut old_score = 0;
+        scan += len;
+        let mut scsc = scan;
+        while scan < new.len() {
+            let (found, found_len) = search(&sa, old, &new[scan..]);
+            pos = found;
+            len = found_len;
+            while scsc < scan + len {
+                if old_at(scsc as isize + last_offset) == Some(new[scsc]) {
+                    old_score += 1;
+                }
+                scsc += 1;
+            }
+            if (len == old_score && len != 0) || len > old_score + 8 {
+                break;
+            }
+            if old_at(scan as isize + last_offset) == Some(new[scan]) {
+                old_score -= 1;
+            }
+            scan += 1;
+        }
+        if len == old_score && scan != new.len() {
+            continue;
+        }
+
+        // Extend the last match forward and this one backward, as far as they are more
+        // alike than not, and split any overlap between them.
+        let (mut s, mut best, mut len_forward) = (0isize, 0isize, 0usize);
+        let mut i = 0;
+        while last_scan + i < scan && last_pos + i < old.len() {
+            if old[last_pos + i] == new[last_scan + i] {
+                s += 1;
+            }
+            i += 1;
+            if s * 2 - i as isize > best * 2 - len_forward as isize {
+                best = s;
+                len_forward = i;
+            }
+        }
+        let mut len_back = 0usize;
+        if scan < new.len() {
+            let (mut s, mut best) = (0isize, 0isize);
+            let mut i = 1;
+            while scan >= last_scan + i && pos >= i {
+                if old[pos - i] == new[scan - i] {
+                    s += 1;
+                }
+                if s * 2 - i as isize > best * 2 - len_back as isize {
+                    best = s;
+                    len_back = i;
+                }
+                i += 1;
+            }
+        }
+        if last_scan + len_forward > scan - len_back {
+            let overlap = last_scan + len_forward - (scan - len_back);
+            let (mut s, mut best, mut len_split) = (0isize, 0isize, 0usize);
+            for i in 0..overlap {
+                if new[last_scan + len_forward - overlap + i] ==
+                    old[last_pos + len_forward - overlap + i]
+                {
+                    s += 1;
+                }
+                if new[scan - len_back + i] == old[pos - len_back + i] {
+                    s -= 1;
+                }
+                if s > best {
+                    best = s;
+                    len_split = i + 1;
+                }
+            }
+            len_forward = len_forward + len_split - overlap;
+            len_back -= len_split;
+        }
+
+        diff.extend((0..len_forward).map(|i| new[last_scan + i].wrapping_sub(old[last_pos + i])));
+        let inserted = scan - len_back - (last_scan + len_forward);
+        extra.extend_from_slice(&new[last_scan + len_forward..scan - len_back]);
+        let seek = (pos - len_back) as i64 - (last_pos + len_forward) as i64;
+        for value in &[len_forward as i64, inserted as i64, seek] {
+            control.extend_from_slice(&value.to_le_bytes());
+        }
+        last_scan = scan - len_back;
+        last_pos = pos - len_back;
+        last_offset = pos as isize - scan as isize;
+    }
+    (control, diff, extra)
+}
+
+/// Estimate what updating `old` to `new` costs, as a bsdiff patch and as a whole download.
+pub fn delta(old: &[u8], new: &[u8]) -> DeltaReport {
+    let (control, diff, extra) = bsdiff(old, new);
+    let compressed = |part: &[u8]| compress_to_vec(part, LEVEL).len() as u64;
+    let (control_size, diff_size, extra_size) =
+        (compressed(&control), compressed(&diff), compressed(&extra));
+    DeltaReport {
+        old_size: old.len() as u64,
+        new_size: new.len() as u64,
+        size_change: new.len() as i64 - old.len() as i64,
+        full_download: compressed(new),
+        patch: HEADER_SIZE + control_size + diff_size + extra_size,
+        control: control_size,
+        diff: diff_size,
+        extra: extra_size,
+        copied: diff.len() as u64,
+        unchanged: diff.iter().filter(|&&b| b == 0).count() as u64,
+        inserted: extra.len() as u64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `n` bytes of noise from a linear congruential generator, seeded with `seed`.
+    fn noise(n: usize, seed: u32) -> Vec<u8> {
+        let mut x = seed;
+        (0..n).map(|_| {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (x >> 24) as u8
+        }).collect()
+    }
+
+    /// Apply the parts of a bsdiff patch to `old`, as bspatch does.
+    fn bspatch(old: &[u8], (control, diff, extra): (Vec<u8>, Vec<u8>, Vec<u8>)) -> Vec<u8> {
+        let mut new = Vec::new();
+        let (mut pos, mut diff_pos, mut extra_pos) = (0i64, 0, 0);
+        for entry in control.chunks(24) {
+            let value = |i: usize| {
+                let mut bytes = [0; 8];
+                bytes.copy_from_slice(&entry[i * 8..i * 8 + 8]);
+                i64::from_le_bytes(bytes)
+            };
+            let (copied, inserted, seek) = (value(0) as usize, value(1) as usize, value(2));
+            for i in 0..copied {
+                new.push(old[pos as usize + i].wrapping_add(diff[diff_pos + i]));
+            }
+            diff_pos += copied;
+            pos += copied as i64 + seek;
+            new.extend_from_slice(&extra[extra_pos..extra_pos + inserted]);
+            extra_pos += inserted;
+        }
+        new
+    }
+
+    #[test]
+    fn suffix_arrays() {
+        for buf in [&b""[..], b"banana", b"aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa", b"abracadabra",
+                    &noise(300, 1), &b"mississippi".repeat(20)] {
+            let mut expected: Vec<usize> = (0..=buf.len()).collect();
+            expected.sort_by_key(|&i| &buf[i..]);
+            assert_eq!(suffix_array(buf), expected, "{:?}", buf);
+        }
+    }
+
+    #[test]
+    fn longest_matches() {
+        let old = b"the quick brown fox jumps over the lazy dog";
+        let sa = suffix_array(old);
+        assert_eq!(search(&sa, old, b"the lazy cat"), (31, 9));
+        assert_eq!(search(&sa, old, b"brown"), (10, 5));
+        assert_eq!(search(&sa, old, b"QED").1, 0);
+        assert_eq!(match_len(b"abcd", b"abxd"), 2);
+    }
+
+    #[test]
+    fn patches_rebuild_the_new_file() {
+        let old = noise(4000, 1);
+        let mut changed = old.clone();
+        // Code that moved, a few bytes that changed, and some that are new.
+        changed[100..200].copy_from_slice(&old[2000..2100]);
+        for i in (500..3000).step_by(97) {
+            changed[i] = changed[i].wrapping_add(1);
+        }
+        changed.splice(3500..3500, noise(64, 2));
+        for (old, new) in [(&old, &changed), (&old, &old), (&changed, &old),
+                           (&Vec::new(), &old), (&old, &Vec::new())] {
+            assert_eq!(bspatch(old, bsdiff(old, new)), *new);
+        }
+
+        let report = delta(&old, &changed);
+        assert_eq!((report.old_size, report.new_size, report.size_change), (4000, 4064, 64));
+        assert_eq!(report.copied + report.inserted, 4064);
+        assert!(report.inserted >= 64 && report.unchanged > 3800, "{:?}", report);
+        assert!(report.patch < report.full_download / 4, "{:?}", report);
+        assert_eq!(report.patch, 32 + report.control + report.diff + report.extra);
+
+        let report = delta(&old, &old);
+        assert_eq!((report.copied, report.unchanged, report.inserted), (4000, 4000, 0));
+    }
+}
@@ -0,0 +1,170 @@
+/// The DER tags that code signatures are made of.
+pub const INTEGER: u8 = 0x02;
+pub const BIT_STRING: u8 = 0x03;
+pub const OCTET_STRING: u8 = 0x04;
+pub const OID: u8 = 0x06;
+pub const SEQUENCE: u8 = 0x30;
+pub const SET: u8 = 0x31;
+
+/// The tag of the context-specific field `n` of a structure, like `[0]`.
+pub fn context(n: u8) -> u8 {
+    0xa0 | n
+}
+
+/// A DER value: its tag, its contents and its whole encoding.
+#[derive(Copy, Clone, Debug)]
+pub struct Value<'a> {
+    pub tag: u8,
+    pub contents: &'a [u8],
+    pub raw: &'a [u8],
+}
+
+/// Split the DER value that `buf` starts with from what follows it.
+pub fn read(buf: &[u8]) -> Option<(Value<'_>, &[u8])> {
+    let tag = *buf.first()?;
+    let first = *buf.get(1)?;
+    let (len, start) = if first < 0x80 {
+        (usize::from(first), 2)
+    } else {
+        let bytes = usize::from(first & 0x7f);
+        if bytes == 0 || bytes > 4 {
+            return None;
+        }
+        let len = buf.get(2..2 + bytes)?.iter().fold(0, |len, &b| len << 8 | usize::from(b));
+        (len, 2 + bytes)
+    };
+    let end = start.checked_add(len)?;
+    let value = Value { tag, contents: buf.get(start..end)?, raw: &buf[..end] };
+    Some((value, &buf[end..]))
+}
+
+/// The values that `buf` holds one after another, up to the first that doesn't parse.
+pub fn values(mut buf: &[u8]) -> Vec<Value<'_>> {
+    let mut values = Vec::new();
+    while let Some((value, rest)) = read(buf) {
+        values.push(value);
+        buf = rest;
+    }
+    values
+}
+
+impl<'a> Value<'a> {
+    /// The values in this one, a sequence, set or context-specific field.
+    pub fn children(&self) -> Vec<Value<'a>> {
+        values(self.contents)
+    }
+
+    /// The values in this one, if it has `tag`.
+    pub fn children_of(&self, tag: u8) -> Option<Vec<Value<'a>>> {
+        if self.tag == tag { Some(self.children()) } else { None }
+    }
+
+    /// This object identifier, in dotted form.
+    pub fn oid(&self) -> Option<String> {
+        if self.tag != OID || self.contents.is_empty() {
+            return None;
+        }
+        let first = self.contents[0];
+        let mut parts = vec![u64::from(first / 40).min(2), 0];
+        parts[1] = u64::from(first) - parts[0] * 40;
+        let mut arc = 0u64;
+        for &b in &self.contents[1..] {
+            arc = arc << 7 | u64::from(b & 0x7f);
+            if b & 0x80 == 0 {
+                parts.push(arc);
+                arc = 0;
+            }
+        }
+        Some(parts.iter().map(u64::to_string).collect::<Vec<_>>().join("."))
+    }
+
+    /// The bytes of this integer, without the leading zero that keeps it positive.
+    pub fn unsigned(&self) -> Option<&'a [u8]> {
+        if self.tag != INTEGER {
+            return None;
+        }
+        Some(match self.contents.split_first() {
+            Some((0, rest)) if !rest.is_empty() => rest,
+            _ => self.contents,
+        })
+    }
+
+    /// This string, of any of the string types that names use.
+    pub fn string(&self) -> Option<String> {
+        match self.tag {
+            // UTF8String, PrintableString, T61String, IA5String.
+            0x0c | 0x13 | 0x14 | 0x16 => Some(String::from_utf8_lossy(self.contents).into_owned()),
+            // BMPString, in UTF-16BE.
+            0x1e => {
+                let units: Vec<u16> = self.contents.chunks(2)
+                    .map(|unit| u16::from_be_bytes([unit[0], *unit.get(1).unwrap_or(&0)]))
+                    .collect();
+                Some(String::from_utf16_lossy(&units))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_short_and_long_lengths() {
+        let (value, rest) = read(&[OCTET_STRING, 2, 0xab, 0xcd, 0xff]).unwrap();
+        assert_eq!((value.tag, value.contents, value.raw), (OCTET_STRING, &[0xab, 0xcd][..],
+                                                            &[4, 2, 0xab, 0xcd][..]));
+        assert_eq!(rest, &[0xff]);
+
+        let mut long = vec![OCTET_STRING, 0x82, 0x01, 0x00];
+        long.extend_from_slice(&[7; 256]);
+        let (value, rest) = read(&long).unwrap();
+        assert_eq!((value.contents.len(), rest.len()), (256, 0));
+    }
+
+    #[test]
+    fn rejects_malformed_lengths() {
+        // No length, contents shorter than their length, and a long form without its bytes.
+        assert!(read(&[SEQUENCE]).is_none());
+        assert!(read(&[SEQUENCE, 3, 1, 2]).is_none());
+        assert!(read(&[SEQUENCE, 0x82, 0x01]).is_none());
+        // The indefinite form, which DER doesn't allow, and lengths of more than four bytes.
+        assert!(read(&[SEQUENCE, 0x80, 0, 0]).is_none());
+        assert!(read(&[SEQUENCE, 0x88, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]).is_none());
+        // A length that would overflow the buffer, or the address space on 32-bit targets.
+        assert!(read(&[SEQUENCE, 0x84, 0xff, 0xff, 0xff, 0xff, 0]).is_none());
+        assert!(read(&[]).is_none());
+    }
+
+    #[test]
+    fn values_stop_at_malformed() {
+        let buf = [INTEGER, 1, 5, INTEGER, 1, 6, SEQUENCE, 9, 0];
+        let values = values(&buf);
+        assert_eq!(values.len(), 2);
+        assert_eq!(values[1].contents, &[6]);
+    }
+
+    #[test]
+    fn oids_integers_and_strings() {
+        let (sha256, _) = read(&[OID, 9, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01])
+            .unwrap();
+        assert_eq!(sha256.oid().as_deref(), Some("2.16.840.1.101.3.4.2.1"));
+        let (sha1, _) = read(&[OID, 5, 0x2b, 0x0e, 0x03, 0x02, 0x1a]).unwrap();
+        assert_eq!(sha1.oid().as_deref(), Some("1.3.14.3.2.26"));
+        assert_eq!(read(&[OID, 0]).unwrap().0.oid(), None);
+
+        assert_eq!(read(&[INTEGER, 2, 0, 0x80]).unwrap().0.unsigned(), Some(&[0x80][..]));
+        assert_eq!(read(&[INTEGER, 1, 0]).unwrap().0.unsigned(), Some(&[0][..]));
+        assert_eq!(read(&[OCTET_STRING, 1, 0]).unwrap().0.unsigned(), None);
+
+        assert_eq!(read(&[0x0c, 2, b'h', b'i']).unwrap().0.string().as_deref(), Some("hi"));
+        assert_eq!(read(&[0x1e, 4, 0, b'h', 0, b'i']).unwrap().0.string().as_deref(),
+                   Some("hi"));
+        let buf = [SEQUENCE, 6, INTEGER, 1, 1, context(0), 1, 2];
+        let (sequence, _) = read(&buf).unwrap();
+        let children = sequence.children_of(SEQUENCE).unwrap();
+        assert_eq!((children[0].tag, children[1].tag), (INTEGER, 0xa0));
+        assert!(sequence.children_of(SET).is_none());
+    }
+}
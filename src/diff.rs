@@ -0,0 +1,536 @@
+use exit::UsageError;
+use failure::Error;
+use layout::LayoutChurn;
+use std::cmp;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::{self, Write};
+use std::iter::FromIterator;
+use std::str::FromStr;
+use symbols::{canonical_name, Symbol};
+use units::SizeFormat;
+
+/// A single entity (section or symbol) in a size diff.
+#[derive(Clone, Debug)]
+pub struct DiffEntry {
+    /// The name of the entity in the new input, or the old input if it was removed.
+    pub name: String,
+    /// The name of the entity in the old input, if it was paired up despite being renamed.
+    pub old_name: Option<String>,
+    /// The size in the old input, if the entity was present there.
+    pub old: Option<u64>,
+    /// The size in the new input, if the entity is present there.
+    pub new: Option<u64>,
+}
+
+impl DiffEntry {
+    /// The change in size from the old input to the new input.
+    pub fn delta(&self) -> i64 {
+        self.new.unwrap_or(0) as i64 - self.old.unwrap_or(0) as i64
+    }
+
+    /// What happened to this entity.
+    pub fn status(&self) -> Status {
+        match (self.old, self.new) {
+            (None, _) => Status::Added,
+            (_, None) => Status::Removed,
+            _ => Status::Changed,
+        }
+    }
+
+    /// The change in size relative to the old size, in percent, if there was an old size.
+    pub fn percent(&self) -> Option<f64> {
+        match self.old {
+            Some(old) if old != 0 => Some(self.delta() as f64 * 100.0 / old as f64),
+            _ => None,
+        }
+    }
+}
+
+/// What happened to an entity between the old and new inputs.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Added,
+    Removed,
+    Changed,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(match *self {
+            Status::Added => "added",
+            Status::Removed => "removed",
+            Status::Changed => "changed",
+        })
+    }
+}
+
+/// The smallest change that is worth reporting in a diff.
+#[derive(Copy, Clone, Debug)]
+pub enum Threshold {
+    /// An absolute change in bytes.
+    Bytes(u64),
+    /// A change relative to the old size, in percent.
+    Percent(f64),
+}
+
+impl Threshold {
+    /// Whether `entry` changed by at least this threshold.
+    pub fn is_significant(&self, entry: &DiffEntry) -> bool {
+        match *self {
+            Threshold::Bytes(bytes) => entry.delta().unsigned_abs() >= bytes,
+            Threshold::Percent(percent) => match entry.percent() {
+                Some(p) if entry.new.is_some() => p.abs() >= percent,
+                // Additions and removals count as 100% changes.
+                _ => percent <= 100.0,
+            },
+        }
+    }
+}
+
+impl FromStr for Threshold {
+    type Err = Error;
+
+    /// Parse a threshold given either as a number of bytes (`64`) or a percentage (`0.5%`).
+    fn from_str(s: &str) -> Result<Threshold, Error> {
+        if let Some(percent) = s.strip_suffix('%') {
+            match percent.trim().parse::<f64>() {
+                Ok(p) if p >= 0.0 => Ok(Threshold::Percent(p)),
+                _ => Err(UsageError(format!("Invalid percentage: {}", s)).into()),
+            }
+        } else {
+            match s.trim().parse::<u64>() {
+                Ok(bytes) => Ok(Threshold::Bytes(bytes)),
+                Err(_) => Err(UsageError(format!("Invalid size threshold: {}", s)).into()),
+            }
+        }
+    }
+}
+
+/// Compare two sets of named sizes, matching entries by exact name.
+pub fn diff_sizes(old: &BTreeMap<String, u64>, new: &BTreeMap<String, u64>) -> Vec<DiffEntry> {
+    let mut entries: Vec<DiffEntry> = new.iter().map(|(name, &size)| DiffEntry {
+        name: name.clone(),
+        old_name: None,
+        old: old.get(name).cloned(),
+        new: Some(size),
+    }).collect();
+    entries.extend(old.iter().filter(|&(name, _)| !new.contains_key(name)).map(|(name, &size)| {
+        DiffEntry {
+            name: name.clone(),
+            old_name: None,
+            old: Some(size),
+            new: None,
+        }
+    }));
+    entries
+}
+
+/// Sum symbol sizes by name, since local symbols with the same name can appear many times.
+fn sizes_by_name(symbols: &[Symbol]) -> BTreeMap<String, u64> {
+    let mut map = BTreeMap::new();
+    for sym in symbols {
+        *map.entry(sym.name.clone()).or_insert(0) += sym.size;
+    }
+    map
+}
+
+/// Symbols that didn't match by exact name, as (removed, added) lists of names and sizes,
+/// keyed by canonical name.
+type Unmatched = HashMap<String, (Vec<(String, u64)>, Vec<(String, u64)>)>;
+
+/// Compare two symbol lists.
+///
+/// Symbols are first matched by exact name. The remaining symbols are then grouped by
+/// `canonical_name`, and within each group old and new symbols are paired up in size order, so
+/// that functions whose hash suffix changed in a recompile show up as one changed entry rather
+/// than as an unrelated removal and addition.
+pub fn diff_symbols(old: &[Symbol], new: &[Symbol]) -> Vec<DiffEntry> {
+    let old = sizes_by_name(old);
+    let new = sizes_by_name(new);
+    let mut entries = Vec::new();
+    let mut unmatched = Unmatched::new();
+    for (name, &size) in &new {
+        match old.get(name) {
+            Some(&old_size) => entries.push(DiffEntry {
+                name: name.clone(),
+                old_name: None,
+                old: Some(old_size),
+                new: Some(size),
+            }),
+            None => {
+                unmatched.entry(canonical_name(name)).or_default().1.push((name.clone(), size))
+            }
+        }
+    }
+    for (name, &size) in &old {
+        if !new.contains_key(name) {
+            unmatched.entry(canonical_name(name)).or_default().0.push((name.clone(), size));
+        }
+    }
+    pair_renamed(unmatched, &mut entries);
+    entries
+}
+
+/// Like `diff_symbols`, for symbol tables too big to hold in memory: `old` and `new` yield
+/// symbol sizes summed by name, in name order (see `spill::SortedSizes`), and are merged as they
+/// are read. Only the entries that changed are returned, along with the sum over the unchanged
+/// ones. The symbols that don't match by exact name are still collected in memory to be paired
+/// up.
+pub fn diff_sorted_sizes<O, N>(mut old: O, mut new: N) -> Result<(Vec<DiffEntry>, DiffTotal), Error>
+    where O: Iterator<Item = Result<(String, u64), Error>>,
+          N: Iterator<Item = Result<(String, u64), Error>>,
+{
+    let mut entries = Vec::new();
+    let mut unchanged = DiffTotal::default();
+    let mut unmatched = Unmatched::new();
+    let (mut o, mut n) = (old.next().transpose()?, new.next().transpose()?);
+    loop {
+        let ordering = match (&o, &n) {
+            (None, None) => break,
+            (Some(_), None) => cmp::Ordering::Less,
+            (None, Some(_)) => cmp::Ordering::Greater,
+            (Some((old_name, _)), Some((new_name, _))) => old_name.cmp(new_name),
+        };
+        match ordering {
+            cmp::Ordering::Less => {
+                let (name, size) = o.unwrap();
+                unmatched.entry(canonical_name(&name)).or_default().0.push((name, size));
+                o = old.next().transpose()?;
+            }
+            cmp::Ordering::Greater => {
+                let (name, size) = n.unwrap();
+                unmatched.entry(canonical_name(&name)).or_default().1.push((name, size));
+                n = new.next().transpose()?;
+            }
+            cmp::Ordering::Equal => {
+                let ((name, old_size), (_, new_size)) = (o.unwrap(), n.unwrap());
+                if old_size == new_size {
+                    unchanged.count += 1;
+                    unchanged.old += old_size;
+                    unchanged.new += new_size;
+                } else {
+                    entries.push(DiffEntry {
+                        name,
+                        old_name: None,
+                        old: Some(old_size),
+                        new: Some(new_size),
+                    });
+                }
+                o = old.next().transpose()?;
+                n = new.next().transpose()?;
+            }
+        }
+    }
+    pair_renamed(unmatched, &mut entries);
+    Ok((entries, unchanged))
+}
+
+/// Pair up the symbols in each group of `unmatched` in size order, and append the resulting
+/// entries to `entries`. Symbols left over in a group are additions or removals.
+fn pair_renamed(unmatched: Unmatched, entries: &mut Vec<DiffEntry>) {
+    for (_, (mut removed, mut added)) in unmatched {
+        removed.sort_by_key(|&(_, size)| cmp::Reverse(size));
+        added.sort_by_key(|&(_, size)| cmp::Reverse(size));
+        let pairs = cmp::max(removed.len(), added.len());
+        for i in 0..pairs {
+            entries.push(match (removed.get(i), added.get(i)) {
+                (Some(&(ref old_name, old_size)), Some(&(ref name, size))) => DiffEntry {
+                    name: name.clone(),
+                    old_name: Some(old_name.clone()),
+                    old: Some(old_size),
+                    new: Some(size),
+                },
+                (Some(&(ref old_name, old_size)), None) => DiffEntry {
+                    name: old_name.clone(),
+                    old_name: None,
+                    old: Some(old_size),
+                    new: None,
+                },
+                (None, Some(&(ref name, size))) => DiffEntry {
+                    name: name.clone(),
+                    old_name: None,
+                    old: None,
+                    new: Some(size),
+                },
+                (None, None) => unreachable!(),
+            });
+        }
+    }
+}
+
+/// One reported row of a diff.
+#[derive(Clone, Debug, Serialize)]
+pub struct DiffRow {
+    /// The display name of the entity.
+    pub name: String,
+    /// The display name of the entity in the old input, if it was renamed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_name: Option<String>,
+    pub old: Option<u64>,
+    pub new: Option<u64>,
+    pub delta: i64,
+    /// The change relative to the old size, in percent. Absent for added entities.
+    pub percent: Option<f64>,
+    pub status: Status,
+}
+
+/// The sum over a group of diff entries.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct DiffTotal {
+    /// The number of entries in the group.
+    pub count: usize,
+    pub old: u64,
+    pub new: u64,
+    pub delta: i64,
+}
+
+impl<'a> FromIterator<&'a DiffEntry> for DiffTotal {
+    fn from_iter<I: IntoIterator<Item = &'a DiffEntry>>(iter: I) -> DiffTotal {
+        let mut total = DiffTotal::default();
+        for e in iter {
+            total.count += 1;
+            total.old += e.old.unwrap_or(0);
+            total.new += e.new.unwrap_or(0);
+            total.delta += e.delta();
+        }
+        total
+    }
+}
+
+/// The reportable form of a diff: the entries that changed size, or were added or removed,
+/// sorted by the magnitude of the change.
+#[derive(Clone, Debug, Serialize)]
+pub struct DiffTable {
+    pub rows: Vec<DiffRow>,
+    /// The entries that changed by less than the diff threshold, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub insignificant: Option<DiffTotal>,
+    /// The sum over all entries, including unchanged ones.
+    pub total: DiffTotal,
+}
+
+impl DiffTable {
+    /// Build a table from `entries`, passing names through `display`.
+    ///
+    /// Entries that changed by less than `threshold` are not listed individually, but summed up in
+    /// `insignificant`.
+    pub fn new<F>(entries: &[DiffEntry], threshold: Option<Threshold>, display: F) -> DiffTable
+        where F: Fn(&str) -> String,
+    {
+        let total = entries.iter().collect();
+        let (mut changed, insignificant): (Vec<&DiffEntry>, Vec<&DiffEntry>) = entries.iter()
+            .filter(|e| e.delta() != 0 || e.old.is_none() || e.new.is_none())
+            .partition(|e| threshold.is_none_or(|t| t.is_significant(e)));
+        changed.sort_by(|a, b| {
+            b.delta().abs().cmp(&a.delta().abs()).then_with(|| a.name.cmp(&b.name))
+        });
+        let rows = changed.into_iter().map(|e| {
+            let name = display(&e.name);
+            DiffRow {
+                old_name: e.old_name.as_ref().map(|n| display(n)).filter(|n| *n != name),
+                name,
+                old: e.old,
+                new: e.new,
+                delta: e.delta(),
+                percent: e.percent(),
+                status: e.status(),
+            }
+        }).collect();
+        DiffTable {
+            rows,
+            insignificant: if insignificant.is_empty() {
+                None
+            } else {
+                Some(insignificant.into_iter().collect())
+            },
+            total,
+        }
+    }
+
+    /// Add the sum over entries that were left out of the table because they didn't change
+    /// (see `diff_sorted_sizes`) to its total.
+    pub fn with_unchanged(mut self, unchanged: DiffTotal) -> DiffTable {
+        self.total.count += unchanged.count;
+        self.total.old += unchanged.old;
+        self.total.new += unchanged.new;
+        self
+    }
+
+    /// Print the table in human-readable form, with sizes written in `format`.
+    pub fn print<W: Write>(&self, out: &mut W, title: &str, format: &SizeFormat)
+                           -> io::Result<()> {
+        writeln!(out, "{}:", title)?;
+        writeln!(out, "{:>10} {:>10} {:>10}  STATUS   NAME", "OLD", "NEW", "DELTA")?;
+        for row in &self.rows {
+            let name = match row.old_name {
+                Some(ref old_name) => format!("{} (was {})", row.name, old_name),
+                None => row.name.clone(),
+            };
+            writeln!(out, "{:>10} {:>10} {:>10}  {:<8} {}",
+                     format.size(row.old.unwrap_or(0)), format.size(row.new.unwrap_or(0)),
+                     format.delta(row.delta), row.status, name)?;
+        }
+        if let Some(ref t) = self.insignificant {
+            writeln!(out, "{:>10} {:>10} {:>10}           ({} insignificant changes)",
+                     format.size(t.old), format.size(t.new), format.delta(t.delta), t.count)?;
+        }
+        writeln!(out, "{:>10} {:>10} {:>10}           (total)",
+                 format.size(self.total.old), format.size(self.total.new),
+                 format.delta(self.total.delta))?;
+        Ok(())
+    }
+}
+
+/// A complete diff between two object files, as emitted by `diff --format json`.
+#[derive(Clone, Debug, Serialize)]
+pub struct DiffReport {
+    pub sections: DiffTable,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symbols: Option<DiffTable>,
+    /// The address ranges covered by each compile unit, by source file name, with
+    /// `--compile-units`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compile_units: Option<DiffTable>,
+    /// How much of the code moved, with `--symbols`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layout: Option<LayoutChurn>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spill::SpillingMap;
+
+    fn symbol(name: &str, size: u64) -> Symbol {
+        Symbol {
+            name: name.to_string(),
+            size,
+            address: 0,
+            section: None,
+            offset: None,
+            code: true,
+            writable: false,
+            inferred: false,
+        }
+    }
+
+    type Row = (String, Option<String>, Option<u64>, Option<u64>);
+
+    fn sorted(mut entries: Vec<DiffEntry>) -> Vec<Row> {
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        entries.into_iter().map(|e| (e.name, e.old_name, e.old, e.new)).collect()
+    }
+
+    #[test]
+    fn pairs_symbols_whose_hash_changed() {
+        let old = [symbol("_ZN3foo3bar17h0000000000000000E", 10), symbol("kept", 4),
+                   symbol("kept", 2), symbol("gone", 3), symbol("local.1", 7),
+                   symbol("local.2", 1)];
+        let new = [symbol("_ZN3foo3bar17h1111111111111111E", 12), symbol("kept", 6),
+                   symbol("local.5", 2), symbol("local.6", 7), symbol("fresh", 5)];
+        let entry = |name: &str, old_name: Option<&str>, old, new| {
+            (name.to_string(), old_name.map(str::to_string), old, new)
+        };
+        assert_eq!(sorted(diff_symbols(&old, &new)), vec![
+            entry("_ZN3foo3bar17h1111111111111111E", Some("_ZN3foo3bar17h0000000000000000E"),
+                  Some(10), Some(12)),
+            entry("fresh", None, None, Some(5)),
+            entry("gone", None, Some(3), None),
+            // Local symbols that share a canonical name pair up largest first.
+            entry("kept", None, Some(6), Some(6)),
+            entry("local.5", Some("local.2"), Some(1), Some(2)),
+            entry("local.6", Some("local.1"), Some(7), Some(7)),
+        ]);
+    }
+
+    #[test]
+    fn entries_report_their_change() {
+        let old = vec![("a".to_string(), 100), ("b".to_string(), 5)].into_iter().collect();
+        let new = vec![("a".to_string(), 90), ("c".to_string(), 7)].into_iter().collect();
+        let entries = diff_sizes(&old, &new);
+        let a = &entries[0];
+        assert_eq!((a.delta(), a.status(), a.percent()), (-10, Status::Changed, Some(-10.0)));
+        let c = &entries[1];
+        assert_eq!((c.name.as_str(), c.delta(), c.status(), c.percent()),
+                   ("c", 7, Status::Added, None));
+        let b = &entries[2];
+        assert_eq!((b.name.as_str(), b.delta(), b.status(), b.percent()),
+                   ("b", -5, Status::Removed, Some(-100.0)));
+
+        let table = DiffTable::new(&entries, None, str::to_uppercase);
+        let rows: Vec<_> = table.rows.iter().map(|r| (r.name.as_str(), r.delta)).collect();
+        assert_eq!(rows, vec![("A", -10), ("C", 7), ("B", -5)]);
+        assert_eq!((table.total.count, table.total.old, table.total.new, table.total.delta),
+                   (3, 105, 97, -8));
+    }
+
+    #[test]
+    fn thresholds_fold_small_changes() {
+        assert!(matches!("64".parse(), Ok(Threshold::Bytes(64))));
+        assert!(matches!(" 0.5%".parse(), Ok(Threshold::Percent(p)) if p == 0.5));
+        for bad in &["", "-1", "1.5", "-2%", "x%", "64K"] {
+            let err = bad.parse::<Threshold>().unwrap_err();
+            assert!(err.downcast_ref::<UsageError>().is_some(), "{}", bad);
+        }
+
+        let entry = |old, new| DiffEntry { name: String::new(), old_name: None, old, new };
+        let bytes = Threshold::Bytes(10);
+        assert!(bytes.is_significant(&entry(Some(100), Some(90))));
+        assert!(!bytes.is_significant(&entry(Some(100), Some(109))));
+        assert!(!bytes.is_significant(&entry(None, Some(9))));
+        let percent = Threshold::Percent(5.0);
+        assert!(percent.is_significant(&entry(Some(100), Some(95))));
+        assert!(!percent.is_significant(&entry(Some(1000), Some(1049))));
+        assert!(percent.is_significant(&entry(None, Some(1))));
+        assert!(percent.is_significant(&entry(Some(1), None)));
+        assert!(!Threshold::Percent(150.0).is_significant(&entry(None, Some(1))));
+
+        let entries = [entry(Some(100), Some(200)), entry(Some(50), Some(52)),
+                       entry(Some(7), Some(6)), entry(Some(3), Some(3))];
+        let table = DiffTable::new(&entries, Some(bytes), str::to_string);
+        assert_eq!(table.rows.len(), 1);
+        let small = table.insignificant.unwrap();
+        assert_eq!((small.count, small.old, small.new, small.delta), (2, 57, 58, 1));
+        assert_eq!((table.total.count, table.total.delta), (4, 101));
+    }
+
+    #[test]
+    fn json_rows_leave_out_what_is_absent() {
+        let entries = [
+            DiffEntry { name: "new".to_string(), old_name: Some("old".to_string()), old: Some(4),
+                        new: Some(6) },
+            DiffEntry { name: "added".to_string(), old_name: None, old: None, new: Some(1) },
+        ];
+        let table = DiffTable::new(&entries, None, str::to_string);
+        assert_eq!(serde_json::to_value(&table).unwrap(), serde_json::json!({
+            "rows": [
+                {"name": "new", "old_name": "old", "old": 4, "new": 6, "delta": 2,
+                 "percent": 50.0, "status": "changed"},
+                {"name": "added", "old": null, "new": 1, "delta": 1, "percent": null,
+                 "status": "added"},
+            ],
+            "total": {"count": 2, "old": 4, "new": 7, "delta": 3},
+        }));
+    }
+
+    #[test]
+    fn sorted_streams_diff_like_symbol_lists() {
+        let old = [symbol("_ZN3foo3bar17h0000000000000000E", 10), symbol("kept", 4),
+                   symbol("kept", 2), symbol("same", 9), symbol("gone", 3)];
+        let new = [symbol("_ZN3foo3bar17h1111111111111111E", 12), symbol("kept", 7),
+                   symbol("same", 9), symbol("fresh", 5)];
+        let stream = |symbols: &[Symbol]| {
+            let mut map = SpillingMap::new(64);
+            for sym in symbols {
+                map.add(&sym.name, sym.size).unwrap();
+            }
+            map.finish().unwrap()
+        };
+        let (entries, unchanged) = diff_sorted_sizes(stream(&old), stream(&new)).unwrap();
+        let in_memory = diff_symbols(&old, &new).into_iter().filter(|e| e.name != "same");
+        assert_eq!(sorted(entries), sorted(in_memory.collect()));
+        assert_eq!((unchanged.count, unchanged.old, unchanged.new), (1, 9, 9));
+    }
+}
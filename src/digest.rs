@@ -0,0 +1,236 @@
+/// The hash algorithms that code signatures use, as far as they can be checked.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Algorithm {
+    Sha1,
+    Sha256,
+}
+
+/// The SHA-256 round constants.
+const K256: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+impl Algorithm {
+    /// The algorithm with the ASN.1 object identifier `oid`, in dotted form.
+    pub fn from_oid(oid: &str) -> Option<Algorithm> {
+        match oid {
+            "1.3.14.3.2.26" => Some(Algorithm::Sha1),
+            "2.16.840.1.101.3.4.2.1" => Some(Algorithm::Sha256),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Algorithm::Sha1 => "sha1",
+            Algorithm::Sha256 => "sha256",
+        }
+    }
+
+    /// The hash of the concatenation of `parts`.
+    pub fn digest(self, parts: &[&[u8]]) -> Vec<u8> {
+        let mut state = match self {
+            Algorithm::Sha1 => vec![0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0],
+            Algorithm::Sha256 => vec![0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f,
+                                      0x9b05688c, 0x1f83d9ab, 0x5be0cd19],
+        };
+        let compress = match self {
+            Algorithm::Sha1 => sha1_block,
+            Algorithm::Sha256 => sha256_block,
+        };
+        let mut block = [0; 64];
+        let (mut filled, mut len) = (0, 0u64);
+        for part in parts {
+            len += part.len() as u64;
+            for &b in *part {
+                block[filled] = b;
+                filled += 1;
+                if filled == 64 {
+                    compress(&mut state, &block);
+                    filled = 0;
+                }
+            }
+        }
+        // The padding: a 1 bit, zeros, and the length in bits, ending a block.
+        block[filled] = 0x80;
+        if filled >= 56 {
+            block[filled + 1..].iter_mut().for_each(|b| *b = 0);
+            compress(&mut state, &block);
+            filled = 0;
+        } else {
+            filled += 1;
+        }
+        block[filled..56].iter_mut().for_each(|b| *b = 0);
+        block[56..].copy_from_slice(&(len * 8).to_be_bytes());
+        compress(&mut state, &block);
+        state.iter().flat_map(|word| word.to_be_bytes()).collect()
+    }
+}
+
+/// The CRC-32 of `data`, which gzip ends with and xz checks blocks with.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &b in data {
+        crc ^= u32::from(b);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// The CRC-64 of `data`, with the ECMA-182 polynomial, as xz checks blocks with by default.
+pub fn crc64(data: &[u8]) -> u64 {
+    let mut crc = !0u64;
+    for &b in data {
+        crc ^= u64::from(b);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xc96c_5795_d787_0f42 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// The big-endian words of `block`.
+fn words(block: &[u8; 64]) -> [u32; 16] {
+    let mut w = [0; 16];
+    for (i, chunk) in block.chunks(4).enumerate() {
+        w[i] = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+    }
+    w
+}
+
+fn sha1_block(state: &mut [u32], block: &[u8; 64]) {
+    let mut w = [0; 80];
+    w[..16].copy_from_slice(&words(block));
+    for i in 16..80 {
+        w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+    }
+    let (mut a, mut b, mut c, mut d, mut e) = (state[0], state[1], state[2], state[3], state[4]);
+    for (i, &word) in w.iter().enumerate() {
+        let (f, k) = match i {
+            0..=19 => ((b & c) | (!b & d), 0x5a827999),
+            20..=39 => (b ^ c ^ d, 0x6ed9eba1),
+            40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+            _ => (b ^ c ^ d, 0xca62c1d6),
+        };
+        let t = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k)
+            .wrapping_add(word);
+        e = d;
+        d = c;
+        c = b.rotate_left(30);
+        b = a;
+        a = t;
+    }
+    for (s, v) in state.iter_mut().zip(&[a, b, c, d, e]) {
+        *s = s.wrapping_add(*v);
+    }
+}
+
+fn sha256_block(state: &mut [u32], block: &[u8; 64]) {
+    let mut w = [0; 64];
+    w[..16].copy_from_slice(&words(block));
+    for i in 16..64 {
+        let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+        let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+        w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+    }
+    let mut v = [0; 8];
+    v.copy_from_slice(state);
+    for i in 0..64 {
+        let s1 = v[4].rotate_right(6) ^ v[4].rotate_right(11) ^ v[4].rotate_right(25);
+        let ch = (v[4] & v[5]) ^ (!v[4] & v[6]);
+        let t1 = v[7].wrapping_add(s1).wrapping_add(ch).wrapping_add(K256[i]).wrapping_add(w[i]);
+        let s0 = v[0].rotate_right(2) ^ v[0].rotate_right(13) ^ v[0].rotate_right(22);
+        let maj = (v[0] & v[1]) ^ (v[0] & v[2]) ^ (v[1] & v[2]);
+        let t2 = s0.wrapping_add(maj);
+        v = [t1.wrapping_add(t2), v[0], v[1], v[2], v[3].wrapping_add(t1), v[4], v[5], v[6]];
+    }
+    for (s, v) in state.iter_mut().zip(&v) {
+        *s = s.wrapping_add(*v);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    const MESSAGE_448: &[u8] = b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq";
+
+    #[test]
+    fn sha1_fips_180() {
+        let sha1 = |data: &[u8]| hex(&Algorithm::Sha1.digest(&[data]));
+        assert_eq!(sha1(b"abc"), "a9993e364706816aba3e25717850c26c9cd0d89d");
+        assert_eq!(sha1(b""), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(sha1(MESSAGE_448), "84983e441c3bd26ebaae4aa1f95129e5e54670f1");
+        assert_eq!(sha1(&[b'a'; 1_000_000]), "34aa973cd4c4daa4f61eeb2bdbad27316534016f");
+    }
+
+    #[test]
+    fn sha256_fips_180() {
+        let sha256 = |data: &[u8]| hex(&Algorithm::Sha256.digest(&[data]));
+        assert_eq!(sha256(b"abc"),
+                   "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        assert_eq!(sha256(b""),
+                   "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+        assert_eq!(sha256(MESSAGE_448),
+                   "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1");
+        assert_eq!(sha256(&[b'a'; 1_000_000]),
+                   "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0");
+    }
+
+    #[test]
+    fn padding_at_block_boundaries() {
+        // The length fits after the 1 bit in the last block up to 55 bytes, and takes another
+        // block from 56.
+        for &(len, sha1, sha256) in &[
+            (55, "c1c8bbdc22796e28c0e15163d20899b65621d65a",
+             "9f4390f8d30c2dd92ec9f095b65e2b9ae9b0a925a5258e241c9f1e910f734318"),
+            (56, "c2db330f6083854c99d4b5bfb6e8f29f201be699",
+             "b35439a4ac6f0948b6d6f9e3c6af0f5f590ce20f1bde7090ef7970686ec6738a"),
+            (63, "03f09f5b158a7a8cdad920bddc29b81c18a551f5",
+             "7d3e74a05d7db15bce4ad9ec0658ea98e3f06eeecf16b4c6fff2da457ddc2f34"),
+            (64, "0098ba824b5c16427bd7a1122a5a442a25ec644d",
+             "ffe054fe7ae0cb6dc65c3af9b61d5209f439851db43d0ba5997337df154668eb"),
+            (119, "ee971065aaa017e0632a8ca6c77bb3bf8b1dfc56",
+             "31eba51c313a5c08226adf18d4a359cfdfd8d2e816b13f4af952f7ea6584dcfb"),
+        ] {
+            let data = vec![b'a'; len];
+            assert_eq!(hex(&Algorithm::Sha1.digest(&[&data])), sha1, "{}", len);
+            assert_eq!(hex(&Algorithm::Sha256.digest(&[&data])), sha256, "{}", len);
+        }
+    }
+
+    #[test]
+    fn parts_hash_as_one() {
+        let (a, b) = MESSAGE_448.split_at(13);
+        for &algorithm in &[Algorithm::Sha1, Algorithm::Sha256] {
+            assert_eq!(algorithm.digest(&[a, b"", b]), algorithm.digest(&[MESSAGE_448]));
+        }
+    }
+
+    #[test]
+    fn algorithms_by_oid() {
+        assert_eq!(Algorithm::from_oid("1.3.14.3.2.26"), Some(Algorithm::Sha1));
+        assert_eq!(Algorithm::from_oid("2.16.840.1.101.3.4.2.1"), Some(Algorithm::Sha256));
+        assert_eq!(Algorithm::from_oid("2.16.840.1.101.3.4.2.3"), None);
+    }
+
+    #[test]
+    fn crc_check_values() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc64(b"123456789"), 0x995d_c9bb_df19_39fa);
+        assert_eq!((crc32(b""), crc64(b"")), (0, 0));
+    }
+}
@@ -0,0 +1,295 @@
+use arch;
+use failure::Error;
+use iced_x86::{Decoder, DecoderError, DecoderOptions, FlowControl, Mnemonic};
+use rustc_demangle;
+use section_records;
+use std::cmp;
+use symbols;
+use CODE_FLAGS;
+
+/// A function whose instructions don't fit the bounds that its symbol gives it.
+#[derive(Clone, Debug, Serialize)]
+pub struct Issue {
+    /// The demangled name, without the hash.
+    pub name: String,
+    pub address: u64,
+    pub size: u64,
+    /// `overlaps-next` if it runs into the next function, `invalid` if it doesn't decode,
+    /// `truncated` if its last instruction runs past its end, or `falls-through` if its last
+    /// instruction neither returns, jumps, calls nor traps.
+    pub issue: &'static str,
+}
+
+/// A code section, split into the functions that symbols cover and the bytes between them.
+#[derive(Clone, Debug, Serialize)]
+pub struct TextSection {
+    pub name: String,
+    pub size: u64,
+    pub functions: u64,
+    /// The `nop` and `int3` bytes inside functions, which align branch targets.
+    pub alignment: u64,
+    /// The `nop`, `int3` and zero bytes that pad between functions.
+    pub filler: u64,
+    /// The other bytes between functions, typically code without symbols.
+    pub unattributed: u64,
+}
+
+/// The disassembled code of a binary, as emitted by `disasm --format json`.
+#[derive(Clone, Debug, Serialize)]
+pub struct DisasmReport {
+    pub arch: String,
+    /// The number of function symbols, counting aliases once.
+    pub functions: u64,
+    /// The number of those whose instructions fit their bounds.
+    pub verified: u64,
+    pub issues: Vec<Issue>,
+    pub sections: Vec<TextSection>,
+    pub alignment: u64,
+    /// The number of runs of `nop` and `int3` instructions inside functions.
+    pub alignment_runs: u64,
+    pub filler: u64,
+    pub unattributed: u64,
+}
+
+/// The bitness of the instructions of `arch`, if they can be disassembled.
+fn bitness(arch: &str) -> Option<u32> {
+    match arch {
+        "x86_64" => Some(64),
+        "x86" => Some(32),
+        _ => None,
+    }
+}
+
+/// What disassembling a function found.
+struct Examined {
+    /// What is wrong with the function, if anything.
+    issue: Option<&'static str>,
+    /// The bytes of `nop` and `int3` instructions inside the function.
+    alignment: u64,
+    /// The number of runs of those instructions.
+    alignment_runs: u64,
+}
+
+/// Disassemble the function `code` at `address`.
+fn examine(bitness: u32, code: &[u8], address: u64) -> Examined {
+    let mut examined = Examined { issue: None, alignment: 0, alignment_runs: 0 };
+    let mut decoder = Decoder::with_ip(bitness, code, address, DecoderOptions::NONE);
+    let mut flow = FlowControl::Return;
+    let mut in_run = false;
+    while decoder.can_decode() {
+        let instr = decoder.decode();
+        if instr.is_invalid() {
+            examined.issue = Some(match decoder.last_error() {
+                DecoderError::NoMoreBytes => "truncated",
+                _ => "invalid",
+            });
+            return examined;
+        }
+        // Compilers align loop heads and other branch targets with (multi-byte) `nop`s, and
+        // some pad after calls that don't return with `int3`.
+        let filler = matches!(instr.mnemonic(), Mnemonic::Nop | Mnemonic::Int3);
+        if filler {
+            examined.alignment += instr.len() as u64;
+            if !in_run {
+                examined.alignment_runs += 1;
+            }
+        }
+        in_run = filler;
+        // `hlt` ends the entry point, after the call that never returns.
+        flow = match instr.mnemonic() {
+            Mnemonic::Hlt => FlowControl::Exception,
+            _ => instr.flow_control(),
+        };
+    }
+    examined.issue = match flow {
+        FlowControl::Next | FlowControl::ConditionalBranch |
+        FlowControl::XbeginXabortXend => Some("falls-through"),
+        _ => None,
+    };
+    examined
+}
+
+/// Split the bytes `gap` at `address` between functions into filler and unattributed bytes.
+fn measure_gap(bitness: u32, gap: &[u8], address: u64) -> (u64, u64) {
+    let (mut filler, mut unattributed) = (0, 0);
+    let mut i = 0;
+    while i < gap.len() {
+        // Zero bytes decode as `add [rax], al`, but only ever pad.
+        if gap[i] == 0 {
+            filler += 1;
+            i += 1;
+            continue;
+        }
+        let mut decoder = Decoder::with_ip(bitness, &gap[i..], address + i as u64,
+                                           DecoderOptions::NONE);
+        let instr = decoder.decode();
+        let len = cmp::max(decoder.position(), 1);
+        match instr.mnemonic() {
+            Mnemonic::Nop | Mnemonic::Int3 if !instr.is_invalid() => filler += len as u64,
+            _ => unattributed += len as u64,
+        }
+        i += len;
+    }
+    (filler, unattributed)
+}
+
+/// Disassemble the functions of the x86 or x86-64 binary `buf` to check that their symbols'
+/// bounds hold whole instructions that end in a return, jump, call or trap, and measure the
+/// alignment padding inside them and the filler that pads between them in the code sections.
+pub fn disasm(buf: &[u8]) -> Result<DisasmReport, Error> {
+    let arch = arch::arch(buf)?;
+    let bitness = match bitness(&arch) {
+        Some(bitness) => bitness,
+        None => bail!("Can't disassemble {} code, only x86 and x86_64", arch),
+    };
+    let mut functions: Vec<_> = symbols::symbols(buf)?.into_iter()
+        .filter(|sym| sym.code && sym.size > 0 && sym.offset.is_some())
+        .collect();
+    // Aliases share their function's offset; keep the largest of each.
+    functions.sort_by(|a, b| (a.offset, b.size, &a.name).cmp(&(b.offset, a.size, &b.name)));
+    functions.dedup_by_key(|sym| sym.offset);
+
+    let mut report = DisasmReport {
+        arch,
+        functions: functions.len() as u64,
+        verified: 0,
+        issues: Vec::new(),
+        sections: Vec::new(),
+        alignment: 0,
+        alignment_runs: 0,
+        filler: 0,
+        unattributed: 0,
+    };
+    // The alignment bytes inside each function.
+    let mut alignment = Vec::with_capacity(functions.len());
+    for (i, sym) in functions.iter().enumerate() {
+        let start = sym.offset.unwrap();
+        let overlaps = functions.get(i + 1)
+            .is_some_and(|next| next.offset.is_some_and(|next| next < start + sym.size));
+        let examined = match sym.data(buf) {
+            Some(code) => examine(bitness, code, sym.address),
+            None => Examined { issue: Some("truncated"), alignment: 0, alignment_runs: 0 },
+        };
+        report.alignment += examined.alignment;
+        report.alignment_runs += examined.alignment_runs;
+        alignment.push(examined.alignment);
+        let issue = if overlaps { Some("overlaps-next") } else { examined.issue };
+        match issue {
+            Some(issue) => report.issues.push(Issue {
+                name: format!("{:#}", rustc_demangle::demangle(&sym.name)),
+                address: sym.address,
+                size: sym.size,
+                issue,
+            }),
+            None => report.verified += 1,
+        }
+    }
+
+    for record in section_records(buf)? {
+        if !record.flag_names.iter().any(|flag| CODE_FLAGS.contains(flag)) {
+            continue;
+        }
+        let (offset, address) = match record.offset {
+            Some(offset) => (offset, record.address.unwrap_or(0)),
+            None => continue,
+        };
+        let end = offset + record.size;
+        let mut section = TextSection {
+            name: record.name,
+            size: record.size,
+            functions: 0,
+            alignment: 0,
+            filler: 0,
+            unattributed: 0,
+        };
+        let mut gaps = Vec::new();
+        let mut position = offset;
+        for (sym, &alignment) in functions.iter().zip(&alignment) {
+            let start = sym.offset.unwrap();
+            if start < offset || start >= end {
+                continue;
+            }
+            section.alignment += alignment;
+            let sym_end = cmp::min(start + sym.size, end);
+            if start > position {
+                gaps.push((position, start));
+            }
+            section.functions += sym_end.saturating_sub(cmp::max(start, position));
+            position = cmp::max(position, sym_end);
+        }
+        gaps.push((position, end));
+        for (start, end) in gaps {
+            let gap = match buf.get(start as usize..end as usize) {
+                Some(gap) => gap,
+                None => continue,
+            };
+            let (filler, unattributed) = measure_gap(bitness, gap, address + start - offset);
+            section.filler += filler;
+            section.unattributed += unattributed;
+        }
+        report.filler += section.filler;
+        report.unattributed += section.unattributed;
+        report.sections.push(section);
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::elf::section_header::{SHF_ALLOC, SHF_EXECINSTR, SHT_PROGBITS};
+    use goblin::elf::sym::STT_FUNC;
+    use testelf::Elf;
+
+    #[test]
+    fn instructions_of_a_function() {
+        // push rbp; nop; nop dword [rax]; pop rbp; ret
+        let examined = examine(64, &[0x55, 0x90, 0x0f, 0x1f, 0x00, 0x5d, 0xc3], 0x1000);
+        assert_eq!(examined.issue, None);
+        assert_eq!((examined.alignment, examined.alignment_runs), (4, 1));
+        // push rbp; pop rbp
+        assert_eq!(examine(64, &[0x55, 0x5d], 0).issue, Some("falls-through"));
+        // ud2, and hlt
+        assert_eq!(examine(64, &[0x0f, 0x0b], 0).issue, None);
+        assert_eq!(examine(64, &[0xf4], 0).issue, None);
+        // A REX prefix without an opcode, and `push es`, which 64-bit code doesn't have.
+        assert_eq!(examine(64, &[0x48], 0).issue, Some("truncated"));
+        assert_eq!(examine(64, &[0x06, 0xc3], 0).issue, Some("invalid"));
+        assert_eq!(examine(32, &[0x06, 0xc3], 0).issue, None);
+    }
+
+    #[test]
+    fn gaps_between_functions() {
+        // Zeros, int3, nop and xchg ax, ax, then push rbp.
+        assert_eq!(measure_gap(64, &[0, 0, 0xcc, 0x90, 0x66, 0x90, 0x55], 0), (6, 1));
+    }
+
+    #[test]
+    fn functions_and_the_bytes_between_them() {
+        let text = [
+            0x55, 0x5d, 0xc3, // f
+            0xcc, 0xcc, 0x00,
+            0x55, 0x5d, // g
+            0x55, 0xc3,
+            0x55, 0x5d, 0xc3, 0x90, // h, which overlaps i
+            0xc3, 0x00,
+        ];
+        let buf = Elf::object()
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &text)
+            .symbol("f", STT_FUNC, ".text", 0, 3)
+            .symbol("f_alias", STT_FUNC, ".text", 0, 3)
+            .symbol("g", STT_FUNC, ".text", 6, 2)
+            .symbol("h", STT_FUNC, ".text", 10, 4)
+            .symbol("i", STT_FUNC, ".text", 12, 1)
+            .build();
+        let report = disasm(&buf).unwrap();
+        assert_eq!((report.functions, report.verified), (4, 2));
+        let issues: Vec<_> = report.issues.iter().map(|i| (&*i.name, i.issue)).collect();
+        assert_eq!(issues, vec![("g", "falls-through"), ("h", "overlaps-next")]);
+        assert_eq!((report.alignment, report.alignment_runs), (1, 1));
+        let section = &report.sections[0];
+        assert_eq!((section.size, section.functions, section.alignment), (16, 9, 1));
+        assert_eq!((section.filler, section.unattributed), (4, 3));
+        assert_eq!((report.filler, report.unattributed), (4, 3));
+    }
+}
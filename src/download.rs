@@ -0,0 +1,209 @@
+use analyze;
+use android::abi_library;
+use exit::UsageError;
+use failure::Error;
+use map_file;
+use miniz_oxide::deflate::compress_to_vec;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+use thinning::{self, ThinningReport};
+use zip;
+
+/// The deflate level to estimate Play's downloads with. Play compresses them with brotli,
+/// which usually does a little better than the best deflate.
+const PLAY_LEVEL: u8 = 9;
+
+/// What one kind of device downloads and installs of an app.
+#[derive(Clone, Debug, Serialize)]
+pub struct Configuration {
+    /// The Android ABI or Apple architecture of the device, or `universal` for an Android app
+    /// without native libraries.
+    pub name: String,
+    /// The size of the files the device gets, uncompressed.
+    pub install: u64,
+    /// The approximate size of their download.
+    pub download: u64,
+}
+
+/// The downloads of an app from its store, by device, as emitted by `download --format json`.
+#[derive(Clone, Debug, Serialize)]
+pub struct DownloadReport {
+    /// `app-store` or `play`.
+    pub store: &'static str,
+    /// The size of the files of the app as built, before the store splits it.
+    pub size: u64,
+    pub configurations: Vec<Configuration>,
+}
+
+/// Whether the files `paths` of the app at `path` are those of an iOS or macOS app: an `.ipa`,
+/// an `.app` directory or a single Mach-O file. `paths` is only `path` unless it is a directory.
+fn is_apple(path: &Path, paths: &[String]) -> Result<bool, Error> {
+    if path.extension().is_some_and(|extension| extension == "ipa" || extension == "app") ||
+        paths.iter().any(|path| path.contains(".app/"))
+    {
+        return Ok(true);
+    }
+    if paths.len() != 1 || Path::new(&paths[0]) != path {
+        return Ok(false);
+    }
+    let buf = map_file(path.as_os_str())?;
+    Ok(if zip::is_zip(&buf) {
+        zip::entries(&buf)?.iter().any(|entry| entry.name.contains(".app/"))
+    } else {
+        thinning::is_mach(&buf)
+    })
+}
+
+/// The Apple app at `path`, thinned for each of the architectures its binaries have.
+fn app_store(path: &Path) -> Result<DownloadReport, Error> {
+    let universal = ThinningReport::new(path, "")?;
+    let arches: BTreeSet<&String> =
+        universal.binaries.iter().flat_map(|binary| &binary.arches).collect();
+    let mut configurations = Vec::new();
+    for arch in arches {
+        let report = ThinningReport::new(path, arch)?;
+        configurations.push(Configuration {
+            name: arch.clone(),
+            install: report.private + report.resources,
+            download: report.download,
+        });
+    }
+    Ok(DownloadReport { store: "app-store", size: universal.size, configurations })
+}
+
+/// The Android app at `path`, split by ABI as Play delivers it: each device gets the files
+/// that aren't native libraries, and the libraries of its ABI.
+fn play(path: &Path, paths: &[String]) -> Result<DownloadReport, Error> {
+    let mut abis: BTreeMap<&str, (u64, u64)> = BTreeMap::new();
+    let (mut base, mut size) = ((0, 0), 0);
+    let mut add = |name: &str, buf: &[u8], compressed_size: Option<u64>| {
+        // Libraries stored uncompressed, to be mapped from the app, are still compressed for
+        // the download.
+        let download = compressed_size
+            .unwrap_or_else(|| compress_to_vec(buf, PLAY_LEVEL).len() as u64);
+        let sizes = match abi_library(name) {
+            Some((abi, _)) => abis.entry(abi).or_default(),
+            None => &mut base,
+        };
+        sizes.0 += buf.len() as u64;
+        sizes.1 += download;
+        size += buf.len() as u64;
+    };
+    if paths.len() == 1 && Path::new(&paths[0]) == path {
+        let buf = map_file(path.as_os_str())?;
+        if zip::is_zip(&buf) {
+            for entry in zip::entries(&buf)? {
+                let compressed_size = if entry.is_stored() {
+                    None
+                } else {
+                    Some(entry.compressed_size as u64)
+                };
+                add(&entry.name, &entry.read(&buf)?, compressed_size);
+            }
+        } else {
+            return Err(UsageError(format!("{}: not an app, an APK, an AAB or a directory",
+                                          path.display())).into());
+        }
+    } else {
+        for file in paths {
+            add(file, &map_file(file.as_ref())?, None);
+        }
+    }
+    let configurations = if abis.is_empty() {
+        vec![Configuration { name: "universal".to_string(), install: base.0, download: base.1 }]
+    } else {
+        abis.into_iter().map(|(abi, (install, download))| Configuration {
+            name: abi.to_string(),
+            install: base.0 + install,
+            download: base.1 + download,
+        }).collect()
+    };
+    Ok(DownloadReport { store: "play", size, configurations })
+}
+
+impl DownloadReport {
+    /// Estimate what the store delivers of the app at `path` to each kind of device: for an
+    /// `.ipa`, an `.app` directory or a Mach-O file, what App Store thinning leaves for each
+    /// architecture, and for an APK, an AAB or a directory, what Play's split by ABI does.
+    pub fn new(path: &Path) -> Result<DownloadReport, Error> {
+        let paths = if fs::metadata(path).map_err(|e| format_err!("{}: {}", path.display(), e))?
+            .is_dir()
+        {
+            let mut paths = Vec::new();
+            analyze::walk(path, &mut paths)?;
+            paths
+        } else {
+            vec![path.to_string_lossy().into_owned()]
+        };
+        if is_apple(path, &paths)? {
+            app_store(path)
+        } else {
+            play(path, &paths)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::mach::constants::S_ATTR_PURE_INSTRUCTIONS;
+    use std::{env, process};
+    use testmacho::MachO;
+    use testzip;
+
+    fn configurations(report: &DownloadReport) -> Vec<(&str, u64, u64)> {
+        report.configurations.iter().map(|c| (&*c.name, c.install, c.download)).collect()
+    }
+
+    #[test]
+    fn play_splits_by_abi() {
+        let dir = env::temp_dir().join(format!("rust-size-{}-download", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (dex, arm64, x86_64) = (vec![b'd'; 300], vec![b'a'; 800], vec![0; 1000]);
+        let apk = testzip::archive(&[("classes.dex", zip::DEFLATED, 0, &dex),
+                                     ("lib/arm64-v8a/libapp.so", zip::DEFLATED, 0, &arm64),
+                                     ("lib/x86_64/libapp.so", zip::STORED, 0, &x86_64)]);
+        let path = dir.join("app.apk");
+        fs::write(&path, &apk).unwrap();
+        let report = DownloadReport::new(&path).unwrap();
+        assert_eq!((report.store, report.size), ("play", 2100));
+        let deflated = |data: &[u8], level| compress_to_vec(data, level).len() as u64;
+        let dex_download = deflated(&dex, 6);
+        assert_eq!(configurations(&report),
+                   vec![("arm64-v8a", 1100, dex_download + deflated(&arm64, 6)),
+                        ("x86_64", 1300, dex_download + deflated(&x86_64, PLAY_LEVEL))]);
+
+        // Without native libraries, every device gets the same.
+        fs::write(&path, testzip::archive(&[("classes.dex", zip::DEFLATED, 0, &dex)])).unwrap();
+        let report = DownloadReport::new(&path).unwrap();
+        assert_eq!(configurations(&report), vec![("universal", 300, dex_download)]);
+
+        fs::write(&path, b"not an app").unwrap();
+        match DownloadReport::new(&path) {
+            Err(err) => assert!(err.downcast_ref::<UsageError>().is_some()),
+            Ok(_) => panic!("not an app"),
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn app_store_thins_by_architecture() {
+        let dir = env::temp_dir().join(format!("rust-size-{}-download-ipa", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let app = MachO::executable()
+            .section("__TEXT", "__text", S_ATTR_PURE_INSTRUCTIONS, &[0xc3; 64])
+            .build();
+        let ipa = testzip::archive(&[("Payload/App.app/App", zip::DEFLATED, 0, &app),
+                                     ("Payload/App.app/Info.plist", zip::STORED, 0, b"plist")]);
+        let path = dir.join("App.ipa");
+        fs::write(&path, &ipa).unwrap();
+        assert!(is_apple(&path, &[path.to_string_lossy().into_owned()]).unwrap());
+        let report = DownloadReport::new(&path).unwrap();
+        assert_eq!((report.store, report.size), ("app-store", app.len() as u64 + 5));
+        let thinned = ThinningReport::new(&path, "x86_64").unwrap();
+        assert_eq!(configurations(&report),
+                   vec![("x86_64", app.len() as u64 + 5, thinned.download)]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
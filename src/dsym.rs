@@ -0,0 +1,162 @@
+use arch::fat_slice;
+use exit::UsageError;
+use failure::Error;
+use goblin::mach::load_command::CommandVariant;
+use goblin::mach::{Mach, MachO};
+use metadata;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The DWARF file of the dSYM at `path`: `path` itself, or for a `.dSYM` bundle, the file in
+/// its `Contents/Resources/DWARF`, which is named after the binary.
+pub fn dwarf_file(path: &Path) -> Result<PathBuf, Error> {
+    if !path.is_dir() {
+        return Ok(path.to_path_buf());
+    }
+    let dir = path.join("Contents").join("Resources").join("DWARF");
+    if let Some(stem) = path.file_stem() {
+        if dir.join(stem).is_file() {
+            return Ok(dir.join(stem));
+        }
+    }
+    // The binary may have been renamed after the bundle was made.
+    let mut files = Vec::new();
+    if let Ok(entries) = fs::read_dir(&dir) {
+        for entry in entries {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                files.push(entry.path());
+            }
+        }
+    }
+    match files.len() {
+        1 => Ok(files.remove(0)),
+        0 => Err(UsageError(format!("{}: no DWARF file in the dSYM bundle", path.display()))
+                 .into()),
+        _ => Err(UsageError(format!("{}: more than one DWARF file in the dSYM bundle; pass \
+                                     the one in Contents/Resources/DWARF", path.display()))
+                 .into()),
+    }
+}
+
+fn uuid(mach: &MachO) -> Option<[u8; 16]> {
+    mach.load_commands.iter().find_map(|lc| match lc.command {
+        CommandVariant::Uuid(ref cmd) => Some(cmd.uuid),
+        _ => None,
+    })
+}
+
+/// The slice of the dSYM DWARF file `dsym` that belongs to the Mach-O binary `buf`: the one with
+/// the same `LC_UUID`, or for a binary without one, the same CPU type. Universal binaries have
+/// a dSYM slice for each of their own slices, so only thin binaries are accepted.
+pub fn slice<'a>(buf: &[u8], dsym: &'a [u8]) -> Result<&'a [u8], Error> {
+    let binary = match Mach::parse(buf) {
+        Ok(Mach::Binary(binary)) => binary,
+        Ok(Mach::Fat(_)) => {
+            return Err(UsageError("--dsym needs a thin Mach-O binary; extract a slice with \
+                                   `lipo -thin`".to_string()).into());
+        }
+        Err(_) => return Err(UsageError("--dsym needs a Mach-O binary".to_string()).into()),
+    };
+    let wanted = uuid(&binary);
+    let mut found = Vec::new();
+    let mut slices = Vec::new();
+    match Mach::parse(dsym)? {
+        Mach::Binary(_) => slices.push(dsym),
+        Mach::Fat(fat) => {
+            for arch in fat.arches()? {
+                slices.push(fat_slice(dsym, &arch)?);
+            }
+        }
+    }
+    for slice in slices {
+        let mach = MachO::parse(slice, 0)?;
+        let matches = match wanted {
+            Some(wanted) => uuid(&mach) == Some(wanted),
+            None => mach.header.cputype == binary.header.cputype,
+        };
+        if matches {
+            return Ok(slice);
+        }
+        found.extend(uuid(&mach).map(|uuid| metadata::uuid(&uuid)));
+    }
+    match wanted {
+        Some(wanted) => bail!("The dSYM doesn't match the binary: it has UUID {}, the binary {}",
+                              if found.is_empty() { "none".to_string() } else { found.join(", ") },
+                              metadata::uuid(&wanted)),
+        None => bail!("The dSYM has no slice for the binary's architecture"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::mach::constants::S_ATTR_PURE_INSTRUCTIONS;
+    use std::{env, process};
+    use testelf::Elf;
+    use testmacho;
+
+    fn binary(uuid: Option<[u8; 16]>) -> Vec<u8> {
+        let macho = testmacho::MachO::executable()
+            .section("__TEXT", "__text", S_ATTR_PURE_INSTRUCTIONS, &[0xc3; 16]);
+        match uuid {
+            Some(uuid) => macho.uuid(uuid).build(),
+            None => macho.build(),
+        }
+    }
+
+    /// A universal binary of the thin binaries `slices`, all of them for x86_64.
+    fn universal(slices: &[Vec<u8>]) -> Vec<u8> {
+        let mut out = vec![0xca, 0xfe, 0xba, 0xbe];
+        out.extend_from_slice(&(slices.len() as u32).to_be_bytes());
+        let mut offset = 8 + 20 * slices.len();
+        for slice in slices {
+            for &field in &[0x0100_0007, 3, offset as u32, slice.len() as u32, 0] {
+                out.extend_from_slice(&u32::to_be_bytes(field));
+            }
+            offset += slice.len();
+        }
+        for slice in slices {
+            out.extend_from_slice(slice);
+        }
+        out
+    }
+
+    #[test]
+    fn slices_by_uuid_or_cpu_type() {
+        let (a, b) = ([0xaa; 16], [0xbb; 16]);
+        let dsym = universal(&[binary(Some(b)), binary(Some(a))]);
+        assert_eq!(slice(&binary(Some(a)), &dsym).unwrap(), &binary(Some(a))[..]);
+        assert_eq!(slice(&binary(None), &dsym).unwrap(), &binary(Some(b))[..]);
+        let thin = binary(Some(b));
+        assert_eq!(slice(&binary(Some(b)), &thin).unwrap(), &thin[..]);
+
+        let err = slice(&binary(Some([0xcc; 16])), &dsym).unwrap_err().to_string();
+        assert!(err.contains(&format!("it has UUID {}, {}, the binary {}", metadata::uuid(&b),
+                                      metadata::uuid(&a), metadata::uuid(&[0xcc; 16]))),
+                "{}", err);
+        for binary in &[universal(&[binary(None)]), Elf::executable().build()] {
+            let err = slice(binary, &dsym).unwrap_err();
+            assert!(err.downcast_ref::<UsageError>().is_some(), "{}", err);
+        }
+    }
+
+    #[test]
+    fn dwarf_files_of_bundles() {
+        let dir = env::temp_dir().join(format!("rust-size-{}-dsym", process::id()));
+        let bundle = dir.join("app.dSYM");
+        let dwarf = bundle.join("Contents").join("Resources").join("DWARF");
+        fs::create_dir_all(&dwarf).unwrap();
+        let err = dwarf_file(&bundle).unwrap_err();
+        assert!(err.downcast_ref::<UsageError>().is_some(), "{}", err);
+        // A binary renamed after the bundle was made.
+        fs::write(dwarf.join("old"), b"").unwrap();
+        assert_eq!(dwarf_file(&bundle).unwrap(), dwarf.join("old"));
+        fs::write(dwarf.join("other"), b"").unwrap();
+        assert!(dwarf_file(&bundle).is_err());
+        fs::write(dwarf.join("app"), b"").unwrap();
+        assert_eq!(dwarf_file(&bundle).unwrap(), dwarf.join("app"));
+        assert_eq!(dwarf_file(&dwarf.join("app")).unwrap(), dwarf.join("app"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
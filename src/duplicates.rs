@@ -0,0 +1,252 @@
+use std::collections::{BTreeMap, HashMap};
+use symbols::Symbol;
+use units::SizeFormat;
+
+/// A set of symbols with byte-identical contents.
+#[derive(Clone, Debug, Serialize)]
+pub struct DuplicateGroup {
+    /// The size of each copy.
+    pub size: u64,
+    /// Whether the copies live in writable memory, in which case they can't be merged, since
+    /// writing to one copy must not change the others.
+    pub writable: bool,
+    /// The names of the symbols with these contents.
+    pub symbols: Vec<String>,
+}
+
+impl DuplicateGroup {
+    /// The number of bytes that keeping only one copy would save.
+    pub fn savings(&self) -> u64 {
+        self.size * (self.symbols.len() as u64 - 1)
+    }
+}
+
+/// Find groups of symbols in `symbols` with byte-identical contents in `buf`, largest savings
+/// first. Only functions are considered when `code` is set, and only data objects otherwise.
+/// Symbols smaller than `min_size` are ignored.
+///
+/// Symbols that are already aliases of each other (e.g. were folded by the linker) share an
+/// offset, and are only counted once.
+pub fn duplicates(buf: &[u8], symbols: &[Symbol], code: bool, min_size: u64)
+                  -> Vec<DuplicateGroup> {
+    let mut candidates: Vec<&Symbol> = symbols.iter()
+        .filter(|sym| sym.code == code && sym.size >= min_size && sym.offset.is_some())
+        .collect();
+    candidates.sort_by_key(|sym| sym.offset);
+    candidates.dedup_by_key(|sym| sym.offset);
+
+    let mut by_contents: HashMap<(&[u8], bool), Vec<&Symbol>> = HashMap::new();
+    for sym in candidates {
+        if let Some(data) = sym.data(buf) {
+            by_contents.entry((data, sym.writable)).or_default().push(sym);
+        }
+    }
+    let mut groups: Vec<DuplicateGroup> = by_contents.into_iter()
+        .filter(|(_, syms)| syms.len() > 1)
+        .map(|((data, writable), syms)| DuplicateGroup {
+            size: data.len() as u64,
+            writable,
+            symbols: syms.iter().map(|sym| sym.name.clone()).collect(),
+        })
+        .collect();
+    groups.sort_by(|a, b| {
+        b.savings().cmp(&a.savings()).then_with(|| a.symbols.cmp(&b.symbols))
+    });
+    groups
+}
+
+/// The summary of a duplicate search, as emitted by `duplicates --format json`.
+#[derive(Clone, Debug, Serialize)]
+pub struct DuplicateReport {
+    pub groups: Vec<DuplicateGroup>,
+    /// The bytes that merging every read-only group down to one copy would save.
+    pub mergeable_savings: u64,
+}
+
+impl DuplicateReport {
+    pub fn new(groups: Vec<DuplicateGroup>) -> DuplicateReport {
+        let mergeable_savings = groups.iter()
+            .filter(|g| !g.writable)
+            .map(DuplicateGroup::savings)
+            .sum();
+        DuplicateReport { groups, mergeable_savings }
+    }
+
+    /// Print the report in human-readable form, passing symbol names through `display` and
+    /// writing sizes in `format`.
+    pub fn print<F: Fn(&str) -> String>(&self, display: F, format: &SizeFormat) {
+        println!("{:>10} {:>10} {:>6}  SYMBOLS", "SAVINGS", "SIZE", "COPIES");
+        for g in &self.groups {
+            let names: Vec<String> = g.symbols.iter().map(|n| display(n)).collect();
+            let names = names.join(", ");
+            println!("{:>10} {:>10} {:>6}  {}{}",
+                     format.size(g.savings()), format.size(g.size), g.symbols.len(),
+                     if g.writable { "(writable) " } else { "" }, names);
+        }
+        println!("{:>10}                     (mergeable total)",
+                 format.size(self.mergeable_savings));
+    }
+}
+
+/// Code or data that is byte-identical in several binaries.
+#[derive(Clone, Debug, Serialize)]
+pub struct SharedGroup {
+    /// The size of each copy.
+    pub size: u64,
+    pub writable: bool,
+    /// The names of the symbols with these contents, by binary.
+    pub copies: BTreeMap<String, Vec<String>>,
+}
+
+impl SharedGroup {
+    /// The number of bytes that moving this into a library shared by the binaries would save.
+    pub fn savings(&self) -> u64 {
+        self.size * (self.copies.len() as u64 - 1)
+    }
+}
+
+/// The names of the symbols with some contents, by file.
+type Copies = BTreeMap<String, Vec<String>>;
+
+/// Find symbols whose contents appear in more than one of `files`, given as (name, contents,
+/// symbols) triples, largest savings first. Only functions are considered when `code` is set,
+/// and only data objects otherwise. Symbols smaller than `min_size` are ignored.
+pub fn shared_duplicates(files: &[(String, &[u8], Vec<Symbol>)], code: bool, min_size: u64)
+                         -> Vec<SharedGroup> {
+    let mut by_contents: HashMap<(&[u8], bool), Copies> = HashMap::new();
+    for &(ref file, buf, ref symbols) in files {
+        for sym in symbols.iter().filter(|sym| sym.code == code && sym.size >= min_size) {
+            if let Some(data) = sym.data(buf) {
+                by_contents.entry((data, sym.writable)).or_default()
+                    .entry(file.clone()).or_default()
+                    .push(sym.name.clone());
+            }
+        }
+    }
+    let mut groups: Vec<SharedGroup> = by_contents.into_iter()
+        .filter(|(_, copies)| copies.len() > 1)
+        .map(|((data, writable), mut copies)| {
+            for names in copies.values_mut() {
+                names.sort();
+                names.dedup();
+            }
+            SharedGroup { size: data.len() as u64, writable, copies }
+        })
+        .collect();
+    groups.sort_by(|a, b| b.savings().cmp(&a.savings()).then_with(|| a.copies.cmp(&b.copies)));
+    groups
+}
+
+/// The summary of a search for duplicates across binaries, as emitted by
+/// `duplicates --format json` with several files.
+#[derive(Clone, Debug, Serialize)]
+pub struct SharedReport {
+    pub groups: Vec<SharedGroup>,
+    /// The bytes that sharing every group would save across all the binaries.
+    pub savings: u64,
+}
+
+impl SharedReport {
+    pub fn new(groups: Vec<SharedGroup>) -> SharedReport {
+        let savings = groups.iter().map(SharedGroup::savings).sum();
+        SharedReport { groups, savings }
+    }
+
+    /// Print the report in human-readable form, passing symbol names through `display` and
+    /// writing sizes in `format`.
+    pub fn print<F: Fn(&str) -> String>(&self, display: F, format: &SizeFormat) {
+        println!("{:>10} {:>10} {:>6}  SYMBOLS", "SAVINGS", "SIZE", "FILES");
+        for g in &self.groups {
+            let copies: Vec<String> = g.copies.iter().map(|(file, names)| {
+                let mut names: Vec<String> = names.iter().map(|n| display(n)).collect();
+                // Local copies of a function in one binary often demangle to the same name.
+                names.dedup();
+                format!("{}: {}", file, names.join(", "))
+            }).collect();
+            println!("{:>10} {:>10} {:>6}  {}{}",
+                     format.size(g.savings()), format.size(g.size), g.copies.len(),
+                     if g.writable { "(writable) " } else { "" }, copies.join("; "));
+        }
+        println!("{:>10}                     (total)", format.size(self.savings));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::elf::section_header::{SHF_ALLOC, SHF_EXECINSTR, SHF_WRITE, SHT_PROGBITS};
+    use goblin::elf::sym::{STT_FUNC, STT_OBJECT};
+    use symbols::symbols;
+    use testelf::Elf;
+
+    #[test]
+    fn groups_identical_data_once_per_address() {
+        let table = [7u8; 32];
+        let mut rodata = table.to_vec();
+        rodata.extend_from_slice(&table);
+        rodata.extend_from_slice(&[1; 32]);
+        let buf = Elf::executable()
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &[0x90; 32])
+            .section(".rodata", SHT_PROGBITS, SHF_ALLOC, &rodata)
+            .section(".data", SHT_PROGBITS, SHF_ALLOC | SHF_WRITE, &[0; 64])
+            .symbol("TABLE", STT_OBJECT, ".rodata", 0, 32)
+            .symbol("TABLE_ALIAS", STT_OBJECT, ".rodata", 0, 32)
+            .symbol("TABLE_COPY", STT_OBJECT, ".rodata", 32, 32)
+            .symbol("OTHER", STT_OBJECT, ".rodata", 64, 32)
+            .symbol("state_a", STT_OBJECT, ".data", 0, 32)
+            .symbol("state_b", STT_OBJECT, ".data", 32, 32)
+            .symbol("f", STT_FUNC, ".text", 0, 16)
+            .symbol("g", STT_FUNC, ".text", 16, 16)
+            .build();
+        let symbols = symbols(&buf).unwrap();
+
+        let groups = duplicates(&buf, &symbols, false, 16);
+        assert_eq!(groups.len(), 2);
+        assert!(groups.iter().all(|g| g.size == 32 && g.symbols.len() == 2 && g.savings() == 32));
+        let mut names: Vec<_> = groups.iter().map(|g| (g.writable, g.symbols.clone())).collect();
+        for (_, symbols) in &mut names {
+            symbols.sort();
+        }
+        names.sort();
+        assert!(names[0] == (false, vec!["TABLE".to_string(), "TABLE_COPY".to_string()]) ||
+                names[0] == (false, vec!["TABLE_ALIAS".to_string(), "TABLE_COPY".to_string()]));
+        assert_eq!(names[1], (true, vec!["state_a".to_string(), "state_b".to_string()]));
+        let report = DuplicateReport::new(groups);
+        assert_eq!(report.mergeable_savings, 32);
+
+        let code = duplicates(&buf, &symbols, true, 16);
+        assert_eq!(code.len(), 1);
+        assert_eq!(code[0].symbols.len(), 2);
+        assert!(duplicates(&buf, &symbols, true, 17).is_empty());
+    }
+
+    #[test]
+    fn shared_across_binaries() {
+        let binary = |crc: &[u8], own: u8| {
+            let mut rodata = crc.to_vec();
+            rodata.extend_from_slice(&[own; 32]);
+            Elf::executable()
+                .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &[0x90; 16])
+                .section(".rodata", SHT_PROGBITS, SHF_ALLOC, &rodata)
+                .symbol("CRC_TABLE", STT_OBJECT, ".rodata", 0, crc.len() as u64)
+                .symbol("CRC_TABLE_ALIAS", STT_OBJECT, ".rodata", 0, crc.len() as u64)
+                .symbol("OWN", STT_OBJECT, ".rodata", crc.len() as u64, 32)
+                .symbol("main", STT_FUNC, ".text", 0, 16)
+                .build()
+        };
+        let (a, b, c) = (binary(&[3; 64], 1), binary(&[3; 64], 2), binary(&[3; 64], 1));
+        let files: Vec<(String, &[u8], Vec<Symbol>)> = [("a", &a), ("b", &b), ("c", &c)].iter()
+            .map(|&(name, buf)| (name.to_string(), &buf[..], symbols(buf).unwrap()))
+            .collect();
+        let groups = shared_duplicates(&files, false, 16);
+        let summary: Vec<_> = groups.iter()
+            .map(|g| (g.size, g.savings(), g.copies.keys().cloned().collect::<Vec<_>>()))
+            .collect();
+        assert_eq!(summary, vec![(64, 128, vec!["a".to_string(), "b".to_string(), "c".to_string()]),
+                                 (32, 32, vec!["a".to_string(), "c".to_string()])]);
+        assert_eq!(groups[0].copies["b"], vec!["CRC_TABLE", "CRC_TABLE_ALIAS"]);
+        assert_eq!(SharedReport::new(groups).savings, 160);
+        assert_eq!(shared_duplicates(&files, true, 16)[0].savings(), 32);
+        assert!(shared_duplicates(&files, false, 65).is_empty());
+    }
+}
@@ -0,0 +1,139 @@
+use failure::Error;
+use gimli::{self, EndianSlice, RunTimeEndian, SectionId};
+use goblin::Object;
+use section_records;
+use std::collections::BTreeMap;
+use SectionRecord;
+
+/// The contents of the section named `name` in `buf`, or an empty slice if there is none.
+/// Mach-O spells DWARF section names `__debug_info` rather than `.debug_info`.
+fn section_data<'a>(buf: &'a [u8], records: &[SectionRecord], name: &str) -> &'a [u8] {
+    let mach_name = format!("__{}", &name[1..]);
+    records.iter()
+        .find(|r| r.name == name || r.name == mach_name)
+        .and_then(|r| r.offset.map(|offset| (offset as usize, r.size as usize)))
+        .and_then(|(offset, size)| buf.get(offset..offset + size))
+        .unwrap_or(&[])
+}
+
+/// The source file a compile unit is named after. rustc names its codegen units
+/// `src/lib.rs/@/<hash>`, where the hash changes between builds, so the suffix is dropped.
+fn source_file(name: &str) -> &str {
+    name.find("/@/").map_or(name, |i| &name[..i])
+}
+
+/// The DWARF debug info of `buf`, which is empty if `buf` has none.
+pub fn load(buf: &[u8]) -> Result<gimli::Dwarf<EndianSlice<'_, RunTimeEndian>>, Error> {
+    let endian = match Object::parse(buf) {
+        Ok(Object::Elf(ref elf)) if !elf.little_endian => RunTimeEndian::Big,
+        Ok(Object::Mach(goblin::mach::Mach::Binary(ref mach))) if !mach.little_endian => {
+            RunTimeEndian::Big
+        }
+        _ => RunTimeEndian::Little,
+    };
+    let records = section_records(buf)?;
+    Ok(gimli::Dwarf::load(|id: SectionId| -> Result<_, gimli::Error> {
+        Ok(EndianSlice::new(section_data(buf, &records, id.name()), endian))
+    })?)
+}
+
+/// The address range covered by part of a compile unit.
+#[derive(Clone, Debug)]
+pub struct UnitRange {
+    pub begin: u64,
+    pub end: u64,
+    /// The source file the compile unit is named after (see `source_file`).
+    pub name: String,
+}
+
+/// Return the address ranges of the compile units in the DWARF debug info of `buf`, in address
+/// order. Units without address ranges (e.g. type units) have none. The list is empty if `buf`
+/// has no debug info.
+pub fn compile_unit_ranges(buf: &[u8]) -> Result<Vec<UnitRange>, Error> {
+    let dwarf = load(buf)?;
+    let mut vec = Vec::new();
+    let mut headers = dwarf.units();
+    while let Some(header) = headers.next()? {
+        let unit = dwarf.unit(header)?;
+        let name = match unit.name {
+            Some(name) => source_file(&name.to_string_lossy()).to_string(),
+            None => format!("<unit at {:#x}>", unit.header.offset().as_debug_info_offset()
+                            .map_or(0, |o| o.0)),
+        };
+        let mut ranges = dwarf.unit_ranges(&unit)?;
+        while let Some(range) = ranges.next()? {
+            if range.end > range.begin {
+                vec.push(UnitRange { begin: range.begin, end: range.end, name: name.clone() });
+            }
+        }
+    }
+    vec.sort_by_key(|r| r.begin);
+    Ok(vec)
+}
+
+/// Return the compile unit whose ranges in `ranges` (as returned by `compile_unit_ranges`)
+/// contain `address`.
+pub fn unit_at(ranges: &[UnitRange], address: u64) -> Option<&UnitRange> {
+    let i = ranges.partition_point(|r| r.begin <= address);
+    ranges[..i].last().filter(|r| address < r.end)
+}
+
+/// Return the amount of code and data covered by each compile unit in the DWARF debug info of
+/// `buf`, keyed by the unit's source file name. Units without address ranges (e.g. type units)
+/// are skipped, and units for the same source file are summed. The map is empty if `buf` has no
+/// debug info.
+pub fn compile_unit_sizes(buf: &[u8]) -> Result<BTreeMap<String, u64>, Error> {
+    let mut sizes = BTreeMap::new();
+    for range in compile_unit_ranges(buf)? {
+        *sizes.entry(range.name).or_insert(0) += range.end - range.begin;
+    }
+    Ok(sizes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::elf::section_header::SHT_PROGBITS;
+    use testelf::Elf;
+
+    /// A DWARF 4 compile unit named `name` covering `len` bytes at `low_pc`, with the
+    /// abbreviations of `ABBREV`.
+    fn unit(name: &str, low_pc: u64, len: u32) -> Vec<u8> {
+        let mut die = vec![1];
+        die.extend_from_slice(name.as_bytes());
+        die.push(0);
+        die.extend_from_slice(&low_pc.to_le_bytes());
+        die.extend_from_slice(&len.to_le_bytes());
+        let mut unit = ((7 + die.len()) as u32).to_le_bytes().to_vec();
+        unit.extend_from_slice(&[4, 0, 0, 0, 0, 0, 8]);
+        unit.extend_from_slice(&die);
+        unit
+    }
+
+    /// A compile unit without children, with a `DW_FORM_string` name, a `DW_FORM_addr` low PC
+    /// and a `DW_FORM_data4` high PC.
+    const ABBREV: &[u8] = &[1, 0x11, 0, 0x03, 0x08, 0x11, 0x01, 0x12, 0x06, 0, 0, 0];
+
+    #[test]
+    fn sizes_by_source_file() {
+        let mut info = unit("src/lib.rs/@/3fx9orzyx3r6r2k4", 0x1000, 0x100);
+        info.extend(unit("src/main.rs", 0x2000, 0x10));
+        info.extend(unit("src/lib.rs/@/1a2b3c", 0x3000, 0x20));
+        info.extend(unit("empty.c", 0x4000, 0));
+        let buf = Elf::object()
+            .section(".debug_abbrev", SHT_PROGBITS, 0, ABBREV)
+            .section(".debug_info", SHT_PROGBITS, 0, &info)
+            .build();
+        let sizes = compile_unit_sizes(&buf).unwrap();
+        assert_eq!(sizes.into_iter().collect::<Vec<_>>(),
+                   vec![("src/lib.rs".to_string(), 0x120), ("src/main.rs".to_string(), 0x10)]);
+
+        let ranges = compile_unit_ranges(&buf).unwrap();
+        assert_eq!(unit_at(&ranges, 0x2008).map(|r| &*r.name), Some("src/main.rs"));
+        assert_eq!(unit_at(&ranges, 0x1000).map(|r| &*r.name), Some("src/lib.rs"));
+        assert!(unit_at(&ranges, 0x2010).is_none());
+        assert!(unit_at(&ranges, 0xfff).is_none());
+
+        assert!(compile_unit_sizes(&Elf::object().build()).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,837 @@
+use exports;
+use failure::Error;
+use goblin::elf::dyn::DT_FLAGS_1;
+use goblin::elf::header::{EM_386, EM_AARCH64, EM_ARM, EM_X86_64, ET_DYN};
+use goblin::elf::reloc::{r_to_str, R_386_RELATIVE, R_AARCH64_RELATIVE, R_ARM_RELATIVE};
+use goblin::elf::reloc::R_X86_64_RELATIVE;
+use goblin::elf::section_header::{SHF_EXECINSTR, SHN_UNDEF, SHT_GNU_HASH, SHT_GNU_VERSYM};
+use goblin::elf::section_header::SHT_HASH;
+use goblin::elf::sym::{STT_FUNC, STT_GNU_IFUNC};
+use goblin::elf::Elf;
+use goblin::Object;
+use std::collections::HashMap;
+use version_script::VersionScript;
+
+/// The statistics of a symbol hash table, `.hash` or `.gnu.hash`.
+#[derive(Clone, Debug, Serialize)]
+pub struct HashTable {
+    pub section: String,
+    pub size: u64,
+    pub buckets: u64,
+    /// The number of symbols in the chains.
+    pub symbols: u64,
+    pub empty_buckets: u64,
+    /// The length of the longest chain.
+    pub max_chain: u64,
+    /// The average length of the chains of non-empty buckets, which is the number of symbols a
+    /// successful lookup compares against at most.
+    pub average_chain: f64,
+    /// The number of words in the Bloom filter of `.gnu.hash`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bloom_words: Option<u64>,
+}
+
+/// The dynamic relocations of one type.
+#[derive(Clone, Debug, Serialize)]
+pub struct RelocationType {
+    /// The name of the type, or `RELR` for the packed relative relocations of `.relr.dyn`.
+    pub name: String,
+    pub count: u64,
+    /// The size of the relocation table entries.
+    pub size: u64,
+}
+
+/// The dynamic linking structures of an ELF binary or shared library, as emitted by
+/// `dynamic --format json`.
+#[derive(Clone, Debug, Serialize)]
+pub struct DynamicReport {
+    pub hash_tables: Vec<HashTable>,
+    /// The bytes that linking with `--hash-style=gnu` would save: all of `.hash` if there is
+    /// also a `.gnu.hash`, or otherwise the difference from an estimated `.gnu.hash`.
+    pub hash_style_savings: i64,
+    /// The dynamic relocations by type, largest first. The dynamic linker applies each one at
+    /// load time, so they cost startup time as well as space.
+    pub relocations: Vec<RelocationType>,
+    /// The procedure linkage table, if there is one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plt: Option<PltReport>,
+    /// What position independence costs, for position-independent executables.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pie: Option<PieReport>,
+    /// The effect of a proposed version script, with `--version-script`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version_script: Option<VersionScriptImpact>,
+}
+
+/// What making the exports that a version script doesn't list local would save, before
+/// relinking.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct VersionScriptImpact {
+    pub exported: u64,
+    /// The exports that would become local.
+    pub hidden: u64,
+    pub dynsym_savings: u64,
+    /// The names of the hidden exports, ignoring any sharing of string storage.
+    pub dynstr_savings: u64,
+    /// The `.gnu.version` entries of the hidden exports.
+    pub versym_savings: u64,
+    pub hash_savings: u64,
+    /// The size of the functions that would become local, which the linker can then drop if
+    /// nothing uses them, and the compiler could have inlined with `-fvisibility=hidden`.
+    pub hidden_code: u64,
+    /// The size of the data objects that would become local.
+    pub hidden_data: u64,
+}
+
+impl VersionScriptImpact {
+    /// The bytes the dynamic symbol tables would shrink by.
+    pub fn table_savings(&self) -> u64 {
+        self.dynsym_savings + self.dynstr_savings + self.versym_savings + self.hash_savings
+    }
+}
+
+/// The procedure linkage table of an ELF file, and what avoiding it would save.
+#[derive(Clone, Debug, Serialize)]
+pub struct PltReport {
+    /// The number of PLT entries, from the `JUMP_SLOT` relocations.
+    pub entries: u64,
+    /// The size of `.plt`, and of `.plt.sec` when there is one.
+    pub plt_size: u64,
+    pub got_plt_size: u64,
+    pub rela_plt_size: u64,
+    /// The direct calls and jumps to PLT entries, for architectures whose calls can be found
+    /// without disassembling.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub call_sites: Option<u64>,
+    /// The estimated change in size from compiling with `-fno-plt`, which calls through the GOT
+    /// instead, with longer call sequences and without lazy binding.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub no_plt_delta: Option<i64>,
+    /// The PLT entries for functions the file defines itself, which a shared library only
+    /// calls through the PLT so that they can be interposed.
+    pub local_entries: u64,
+    /// The estimated bytes that linking with `-Bsymbolic-functions` would save, by binding
+    /// calls to `local_entries` directly.
+    pub symbolic_savings: u64,
+}
+
+/// The sizes of the PLT header and of the bytes a call through the GOT takes over a direct
+/// call, for `machine`.
+fn plt_layout(machine: u16) -> (u64, Option<u64>) {
+    match machine {
+        // `call *foo@GOTPCREL(%rip)` is 6 bytes to `call foo@plt`'s 5.
+        EM_X86_64 | EM_386 => (16, Some(1)),
+        // `adrp`, `ldr` and `blr` to `bl`.
+        EM_AARCH64 => (32, Some(8)),
+        EM_ARM => (20, None),
+        _ => (0, None),
+    }
+}
+
+/// Count the direct calls and jumps in the executable sections of `elf` that land on an entry
+/// of the PLT at `plt`, whose entries are `entry_size` bytes from `first`.
+fn plt_call_sites(buf: &[u8], elf: &Elf, plt: (u64, u64), first: u64, entry_size: u64)
+                  -> Option<u64> {
+    let is_entry = |target: u64| {
+        target >= first && target < plt.1 && (target - first).is_multiple_of(entry_size)
+    };
+    let mut count = 0;
+    for sh in &elf.section_headers {
+        let name = elf.shdr_strtab.get(sh.sh_name).and_then(|res| res.ok()).unwrap_or("");
+        if sh.sh_flags & SHF_EXECINSTR as u64 == 0 || name.starts_with(".plt") {
+            continue;
+        }
+        let start = sh.sh_offset as usize;
+        let code = match buf.get(start..start + sh.sh_size as usize) {
+            Some(code) => code,
+            None => continue,
+        };
+        match elf.header.e_machine {
+            // `call rel32` and `jmp rel32`. Scanning every byte finds some false positives, but
+            // few that land exactly on an entry.
+            EM_X86_64 | EM_386 => {
+                for (i, w) in code.windows(5).enumerate() {
+                    if w[0] == 0xe8 || w[0] == 0xe9 {
+                        let rel = i32::from_le_bytes([w[1], w[2], w[3], w[4]]) as i64;
+                        let target = (sh.sh_addr + i as u64 + 5) as i64 + rel;
+                        if is_entry(target as u64) {
+                            count += 1;
+                        }
+                    }
+                }
+            }
+            // `bl imm26` and `b imm26`.
+            EM_AARCH64 if elf.little_endian => {
+                for (i, w) in code.chunks_exact(4).enumerate() {
+                    let insn = u32::from_le_bytes([w[0], w[1], w[2], w[3]]);
+                    if insn & 0x7c00_0000 == 0x1400_0000 {
+                        let rel = (((insn & 0x03ff_ffff) << 6) as i32 >> 4) as i64;
+                        let target = (sh.sh_addr + 4 * i as u64) as i64 + rel;
+                        if is_entry(target as u64) {
+                            count += 1;
+                        }
+                    }
+                }
+            }
+            _ => return None,
+        }
+    }
+    Some(count)
+}
+
+/// Analyze the PLT of `elf`, read from `buf`, if it has one.
+fn plt(buf: &[u8], elf: &Elf) -> Option<PltReport> {
+    let entries = elf.pltrelocs.len() as u64;
+    let section = |name: &str| elf.section_headers.iter().find(|sh| {
+        elf.shdr_strtab.get(sh.sh_name).and_then(|res| res.ok()) == Some(name)
+    });
+    let size = |name: &str| section(name).map_or(0, |sh| sh.sh_size);
+    let plt = section(".plt")?;
+    if entries == 0 {
+        return None;
+    }
+    let plt_size = plt.sh_size + size(".plt.sec");
+    let (header, call_cost) = plt_layout(elf.header.e_machine);
+    let entry_size = plt_size.saturating_sub(header) / entries;
+    // With IBT, calls go to the second PLT in `.plt.sec`.
+    let (calls_to, first, stride) = match section(".plt.sec") {
+        Some(sec) => ((sec.sh_addr, sec.sh_addr + sec.sh_size), sec.sh_addr,
+                      sec.sh_size / entries),
+        None => ((plt.sh_addr, plt.sh_addr + plt.sh_size), plt.sh_addr + header, entry_size),
+    };
+    let call_sites = match call_cost {
+        Some(_) if stride > 0 => plt_call_sites(buf, elf, calls_to, first, stride),
+        _ => None,
+    };
+    let pointer_size = if elf.is_64 { 8 } else { 4 };
+    let got_plt_size = size(".got.plt");
+    let rela_plt_size = size(".rela.plt") + size(".rel.plt");
+    let local_entries = elf.pltrelocs.iter().filter(|reloc| {
+        elf.dynsyms.get(reloc.r_sym).is_some_and(|sym| {
+            reloc.r_sym != 0 && sym.st_shndx != SHN_UNDEF as usize &&
+                sym.st_type() != STT_GNU_IFUNC
+        })
+    }).count() as u64;
+    // Each entry also has a GOT slot and a relocation.
+    let per_entry = entry_size + pointer_size + rela_plt_size / entries;
+    Some(PltReport {
+        entries,
+        plt_size,
+        got_plt_size,
+        rela_plt_size,
+        call_sites,
+        // The PLT goes, and so do the three GOT slots reserved for lazy binding; each entry's
+        // GOT slot and relocation moves to `.got` and `.rela.dyn`.
+        no_plt_delta: match (call_sites, call_cost) {
+            (Some(sites), Some(cost)) => {
+                Some((sites * cost) as i64 - (plt_size + 3 * pointer_size) as i64)
+            }
+            _ => None,
+        },
+        local_entries,
+        symbolic_savings: local_entries * per_entry,
+    })
+}
+
+/// `DF_1_PIE`, which goblin doesn't define: the `DT_FLAGS_1` flag marking a position
+/// independent executable.
+const DF_1_PIE: u64 = 0x0800_0000;
+
+/// `SHT_RELR`, a section of packed relative relocations.
+const SHT_RELR: u32 = 19;
+
+/// What a position-independent executable carries that a non-PIE build of it wouldn't need.
+#[derive(Clone, Debug, Serialize)]
+pub struct PieReport {
+    /// Whether the executable is statically linked, and so relocates itself.
+    pub static_pie: bool,
+    /// The relocations that add the load address, which a non-PIE executable resolves at link
+    /// time.
+    pub relative_relocations: u64,
+    /// The size of those relocations (or of `.relr.dyn`, where they are packed).
+    pub relocation_size: u64,
+    /// The GOT entries holding addresses in the executable itself, which a non-PIE link can
+    /// turn into direct references.
+    pub got_entries: u64,
+    pub got_size: u64,
+    /// The code that only position independence needs: static-pie self-relocation, and the
+    /// x86 thunks that load the program counter.
+    pub startup_code: u64,
+    /// For static-pie, the dynamic section and symbol tables that only self-relocation needs.
+    pub dynamic_tables: u64,
+}
+
+impl PieReport {
+    /// The bytes a non-PIE build would save.
+    pub fn total(&self) -> u64 {
+        self.relocation_size + self.got_size + self.startup_code + self.dynamic_tables
+    }
+}
+
+/// Analyze what position independence costs `elf`, if it is a position-independent executable.
+fn pie(elf: &Elf) -> Option<PieReport> {
+    let flags_1 = elf.dynamic.as_ref().map_or(0, |dynamic| {
+        dynamic.dyns.iter().find(|d| d.d_tag == DT_FLAGS_1).map_or(0, |d| d.d_val)
+    });
+    let is_pie = elf.header.e_type == ET_DYN &&
+        (flags_1 & DF_1_PIE != 0 || (elf.interpreter.is_some() && elf.soname.is_none()));
+    if !is_pie {
+        return None;
+    }
+    let section = |name: &str| elf.section_headers.iter().find(|sh| {
+        elf.shdr_strtab.get(sh.sh_name).and_then(|res| res.ok()) == Some(name)
+    });
+    let size = |name: &str| section(name).map_or(0, |sh| sh.sh_size);
+
+    let relative = match elf.header.e_machine {
+        EM_X86_64 => R_X86_64_RELATIVE,
+        EM_386 => R_386_RELATIVE,
+        EM_AARCH64 => R_AARCH64_RELATIVE,
+        EM_ARM => R_ARM_RELATIVE,
+        _ => return None,
+    };
+    let pointer_size = if elf.is_64 { 8 } else { 4 };
+    let got = section(".got").map(|sh| (sh.sh_addr, sh.sh_addr + sh.sh_size));
+    let (mut relative_relocations, mut relocation_size, mut got_entries) = (0, 0, 0);
+    let tables = [(&elf.dynrelas, 3 * pointer_size), (&elf.dynrels, 2 * pointer_size)];
+    for (relocs, entry_size) in &tables {
+        for reloc in relocs.iter().filter(|reloc| reloc.r_type == relative) {
+            relative_relocations += 1;
+            relocation_size += entry_size;
+            if got.is_some_and(|(start, end)| reloc.r_offset >= start && reloc.r_offset < end) {
+                got_entries += 1;
+            }
+        }
+    }
+    relocation_size += elf.section_headers.iter()
+        .filter(|sh| sh.sh_type == SHT_RELR)
+        .map(|sh| sh.sh_size)
+        .sum::<u64>();
+
+    let startup_code = elf.syms.iter()
+        .filter(|sym| {
+            elf.strtab.get(sym.st_name).and_then(|res| res.ok()).is_some_and(|name| {
+                name == "_dl_relocate_static_pie" || name.starts_with("__x86.get_pc_thunk.")
+            })
+        })
+        .map(|sym| sym.st_size)
+        .sum();
+    let static_pie = elf.interpreter.is_none();
+    Some(PieReport {
+        static_pie,
+        relative_relocations,
+        relocation_size,
+        got_entries,
+        got_size: got_entries * pointer_size,
+        startup_code,
+        dynamic_tables: if static_pie {
+            [".dynamic", ".dynsym", ".dynstr", ".gnu.hash", ".hash"].iter().map(|s| size(s)).sum()
+        } else {
+            0
+        },
+    })
+}
+
+/// Count the relocations packed into the `SHT_RELR` section `data`: each address entry (with
+/// the low bit clear) is one relocation, and each bitmap entry one per bit besides the marker.
+fn relr_count(elf: &Elf, data: &[u8]) -> u64 {
+    let pointer_size = if elf.is_64 { 8 } else { 4 };
+    data.chunks_exact(pointer_size).map(|w| {
+        let mut bytes = [0; 8];
+        if elf.little_endian {
+            bytes[..pointer_size].copy_from_slice(w);
+            u64::from_le_bytes(bytes)
+        } else {
+            bytes[8 - pointer_size..].copy_from_slice(w);
+            u64::from_be_bytes(bytes)
+        }
+    }).map(|word| if word & 1 == 0 { 1 } else { u64::from(word.count_ones()) - 1 }).sum()
+}
+
+/// Group the dynamic relocations of `elf` by type.
+fn relocations(buf: &[u8], elf: &Elf) -> Vec<RelocationType> {
+    let pointer_size = if elf.is_64 { 8 } else { 4 };
+    let mut types: HashMap<u32, (u64, u64)> = HashMap::new();
+    for reloc in elf.dynrelas.iter().chain(&elf.dynrels).chain(&elf.pltrelocs) {
+        let entry = types.entry(reloc.r_type).or_insert((0, 0));
+        entry.0 += 1;
+        entry.1 += if reloc.r_addend.is_some() { 3 } else { 2 } * pointer_size;
+    }
+    let mut relocations: Vec<RelocationType> = types.into_iter().map(|(r_type, (count, size))| {
+        let name = r_to_str(r_type, elf.header.e_machine);
+        RelocationType {
+            name: if name.starts_with("R_UNKNOWN") {
+                format!("type {}", r_type)
+            } else {
+                format!("R_{}", name)
+            },
+            count,
+            size,
+        }
+    }).collect();
+    for sh in elf.section_headers.iter().filter(|sh| sh.sh_type == SHT_RELR) {
+        let start = sh.sh_offset as usize;
+        if let Some(data) = buf.get(start..start + sh.sh_size as usize) {
+            relocations.push(RelocationType {
+                name: "RELR".to_string(),
+                count: relr_count(elf, data),
+                size: sh.sh_size,
+            });
+        }
+    }
+    relocations.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+    relocations
+}
+
+/// Read the 32-bit words of `data`.
+fn words(elf: &Elf, data: &[u8]) -> Vec<u32> {
+    data.chunks_exact(4).map(|w| {
+        let bytes = [w[0], w[1], w[2], w[3]];
+        if elf.little_endian { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) }
+    }).collect()
+}
+
+/// Fill in the chain statistics of `table` from the chain lengths of its buckets.
+fn chain_stats(table: &mut HashTable, lengths: &[u64]) {
+    let used: Vec<u64> = lengths.iter().cloned().filter(|&n| n > 0).collect();
+    table.empty_buckets = (lengths.len() - used.len()) as u64;
+    table.max_chain = used.iter().cloned().max().unwrap_or(0);
+    table.average_chain = if used.is_empty() {
+        0.0
+    } else {
+        used.iter().sum::<u64>() as f64 / used.len() as f64
+    };
+}
+
+/// Analyze a SysV `.hash` section: nbucket, nchain, the buckets, then the chains, all 32-bit.
+fn sysv_hash(elf: &Elf, name: &str, data: &[u8]) -> Option<HashTable> {
+    let words = words(elf, data);
+    let (nbucket, nchain) = (*words.first()? as usize, *words.get(1)? as usize);
+    let buckets = words.get(2..2 + nbucket)?;
+    let chains = words.get(2 + nbucket..2 + nbucket + nchain)?;
+    let lengths: Vec<u64> = buckets.iter().map(|&first| {
+        let (mut n, mut i) = (0, first as usize);
+        // Symbol 0 ends the chain; guard against cycles in a malformed table.
+        while i != 0 && i < nchain && n <= nchain as u64 {
+            n += 1;
+            i = chains[i] as usize;
+        }
+        n
+    }).collect();
+    let mut table = HashTable {
+        section: name.to_string(),
+        size: data.len() as u64,
+        buckets: nbucket as u64,
+        symbols: lengths.iter().sum(),
+        empty_buckets: 0,
+        max_chain: 0,
+        average_chain: 0.0,
+        bloom_words: None,
+    };
+    chain_stats(&mut table, &lengths);
+    Some(table)
+}
+
+/// Analyze a `.gnu.hash` section: nbuckets, symoffset, bloom_size and bloom_shift, the Bloom
+/// filter words (of the ELF class size), the buckets, then one hash value per hashed symbol,
+/// whose low bit marks the end of a chain.
+fn gnu_hash(elf: &Elf, name: &str, data: &[u8]) -> Option<HashTable> {
+    let header = words(elf, data.get(..16)?);
+    let (nbuckets, symoffset, bloom_words) = (header[0] as usize, header[1] as usize,
+                                              header[2] as usize);
+    let bloom_size = bloom_words * if elf.is_64 { 8 } else { 4 };
+    let rest = words(elf, data.get(16 + bloom_size..)?);
+    let buckets = rest.get(..nbuckets)?;
+    let chains = &rest[nbuckets..];
+    let lengths: Vec<u64> = buckets.iter().map(|&first| {
+        if (first as usize) < symoffset {
+            return 0;
+        }
+        let mut i = first as usize - symoffset;
+        let mut n = 0;
+        while let Some(&hash) = chains.get(i) {
+            n += 1;
+            i += 1;
+            if hash & 1 != 0 {
+                break;
+            }
+        }
+        n
+    }).collect();
+    let mut table = HashTable {
+        section: name.to_string(),
+        size: data.len() as u64,
+        buckets: nbuckets as u64,
+        symbols: lengths.iter().sum(),
+        empty_buckets: 0,
+        max_chain: 0,
+        average_chain: 0.0,
+        bloom_words: Some(bloom_words as u64),
+    };
+    chain_stats(&mut table, &lengths);
+    Some(table)
+}
+
+/// The size of the `.gnu.hash` that lld would build for `symbols` defined dynamic symbols: a
+/// bucket per four symbols, and 12 Bloom filter bits per symbol, rounded up to a power of two
+/// words.
+fn estimated_gnu_hash_size(symbols: u64, is_64: bool) -> u64 {
+    let word_size = if is_64 { 8 } else { 4 };
+    let bloom_words = (symbols * 12).div_ceil(word_size * 8).max(1).next_power_of_two();
+    16 + bloom_words * word_size + (symbols / 4).max(1) * 4 + symbols * 4
+}
+
+/// Estimate what `script` would save in the ELF shared library `elf`, read from `buf`.
+fn version_script_impact(buf: &[u8], elf: &Elf, hash_tables: &[HashTable],
+                         script: &VersionScript) -> Result<VersionScriptImpact, Error> {
+    let functions: HashMap<&str, bool> = elf.dynsyms.iter()
+        .filter_map(|sym| {
+            let name = elf.dynstrtab.get(sym.st_name).and_then(|res| res.ok())?;
+            Some((name, sym.st_type() == STT_FUNC))
+        })
+        .collect();
+    let exports = exports::exports(buf)?;
+    let mut impact = VersionScriptImpact { exported: exports.len() as u64, ..Default::default() };
+    for export in exports.iter().filter(|export| !script.exports(&export.name)) {
+        impact.hidden += 1;
+        impact.dynstr_savings += export.name.len() as u64 + 1;
+        if functions.get(&*export.name) == Some(&true) {
+            impact.hidden_code += export.size;
+        } else {
+            impact.hidden_data += export.size;
+        }
+    }
+    impact.dynsym_savings = impact.hidden * if elf.is_64 { 24 } else { 16 };
+    if elf.section_headers.iter().any(|sh| sh.sh_type == SHT_GNU_VERSYM) {
+        impact.versym_savings = impact.hidden * 2;
+    }
+    let defined = elf.dynsyms.iter().filter(|sym| sym.st_shndx != SHN_UNDEF as usize).count()
+        as u64;
+    for table in hash_tables {
+        impact.hash_savings += if table.bloom_words.is_some() {
+            estimated_gnu_hash_size(defined, elf.is_64)
+                .saturating_sub(estimated_gnu_hash_size(defined - impact.hidden, elf.is_64))
+        } else {
+            // A chain entry per symbol, and linkers size the buckets by the symbol count.
+            impact.hidden * 4 + table.buckets * impact.hidden / table.symbols.max(1) * 4
+        };
+    }
+    Ok(impact)
+}
+
+/// Analyze the hash tables of the ELF binary or shared library `buf`, and the effect of the
+/// version script `script`, if any.
+pub fn dynamic(buf: &[u8], script: Option<&VersionScript>) -> Result<DynamicReport, Error> {
+    let elf = match Object::parse(buf)? {
+        Object::Elf(elf) => elf,
+        _ => bail!("Not an ELF file"),
+    };
+    let mut hash_tables = Vec::new();
+    for sh in &elf.section_headers {
+        if sh.sh_type != SHT_HASH && sh.sh_type != SHT_GNU_HASH {
+            continue;
+        }
+        let name = elf.shdr_strtab.get(sh.sh_name).and_then(|res| res.ok()).unwrap_or("");
+        let start = sh.sh_offset as usize;
+        let data = match buf.get(start..start + sh.sh_size as usize) {
+            Some(data) => data,
+            None => continue,
+        };
+        let table = if sh.sh_type == SHT_HASH {
+            sysv_hash(&elf, name, data)
+        } else {
+            gnu_hash(&elf, name, data)
+        };
+        hash_tables.extend(table);
+    }
+
+    let sysv = hash_tables.iter().find(|t| t.bloom_words.is_none()).map(|t| t.size);
+    let has_gnu = hash_tables.iter().any(|t| t.bloom_words.is_some());
+    let hash_style_savings = match sysv {
+        Some(size) if has_gnu => size as i64,
+        Some(size) => {
+            let defined = elf.dynsyms.iter()
+                .filter(|sym| sym.st_shndx != SHN_UNDEF as usize)
+                .count() as u64;
+            size as i64 - estimated_gnu_hash_size(defined, elf.is_64) as i64
+        }
+        None => 0,
+    };
+    let version_script = match script {
+        Some(script) => Some(version_script_impact(buf, &elf, &hash_tables, script)?),
+        None => None,
+    };
+    Ok(DynamicReport {
+        hash_tables,
+        hash_style_savings,
+        relocations: relocations(buf, &elf),
+        plt: plt(buf, &elf),
+        pie: pie(&elf),
+        version_script,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::elf::section_header::{SHF_ALLOC, SHF_WRITE, SHT_PROGBITS};
+    use goblin::elf::program_header::{PF_R, PT_INTERP};
+    use goblin::elf::reloc::{R_X86_64_GLOB_DAT, R_X86_64_JUMP_SLOT};
+    use goblin::elf::sym::STT_OBJECT;
+    use std::{env, fs, process};
+    use testelf::Elf as Builder;
+
+    /// The bytes of 32-bit little-endian `words`.
+    fn table(words: &[u32]) -> Vec<u8> {
+        words.iter().flat_map(|w| w.to_le_bytes().to_vec()).collect()
+    }
+
+    /// A shared library exporting three functions, with the hash tables `tables`.
+    fn library(tables: &[(&str, u32, &[u8])]) -> Vec<u8> {
+        let mut elf = Builder::shared()
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &[0; 48]);
+        for &(name, kind, data) in tables {
+            elf = elf.section(name, kind, SHF_ALLOC, data);
+        }
+        elf.export("a", STT_FUNC, ".text", 0, 16)
+            .export("b", STT_FUNC, ".text", 16, 16)
+            .export("c", STT_FUNC, ".text", 32, 16)
+            .build()
+    }
+
+    type Stats = (String, u64, u64, u64, u64, u64, f64, Option<u64>);
+
+    fn stats(report: &DynamicReport) -> Vec<Stats> {
+        report.hash_tables.iter().map(|t| {
+            (t.section.clone(), t.size, t.buckets, t.symbols, t.empty_buckets, t.max_chain,
+             t.average_chain, t.bloom_words)
+        }).collect()
+    }
+
+    #[test]
+    fn hash_table_chains() {
+        // Two buckets: symbols 1, 2 and 3 in the first, none in the second.
+        let sysv = table(&[2, 4, 1, 0, 0, 2, 3, 0]);
+        // Two buckets from symbol 1, with one 64-bit Bloom filter word, and the chains 1-2 and
+        // 3, whose last hashes have the low bit set.
+        let gnu = table(&[2, 1, 1, 6, 0, 0, 1, 3, 0x10, 0x21, 0x31]);
+        let report = dynamic(&library(&[(".hash", SHT_HASH, &sysv),
+                                        (".gnu.hash", SHT_GNU_HASH, &gnu)]), None).unwrap();
+        assert_eq!(stats(&report), vec![
+            (".hash".to_string(), 32, 2, 3, 1, 3, 3.0, None),
+            (".gnu.hash".to_string(), 44, 2, 3, 0, 2, 1.5, Some(1)),
+        ]);
+        // With `.gnu.hash` there, `.hash` isn't needed at all.
+        assert_eq!(report.hash_style_savings, 32);
+
+        // Otherwise, switching saves the difference from the `.gnu.hash` lld would build.
+        let report = dynamic(&library(&[(".hash", SHT_HASH, &sysv)]), None).unwrap();
+        assert_eq!(report.hash_style_savings, 32 - 40);
+        assert_eq!(dynamic(&library(&[]), None).unwrap().hash_style_savings, 0);
+    }
+
+    #[test]
+    fn estimated_gnu_hash_sizes() {
+        // The header, one Bloom word, one bucket and a hash per symbol.
+        assert_eq!(estimated_gnu_hash_size(3, true), 16 + 8 + 4 + 12);
+        // 36 Bloom bits take two 32-bit words.
+        assert_eq!(estimated_gnu_hash_size(3, false), 16 + 2 * 4 + 4 + 12);
+        // 12000 Bloom bits round up to 256 words.
+        assert_eq!(estimated_gnu_hash_size(1000, true), 16 + 256 * 8 + 250 * 4 + 1000 * 4);
+    }
+
+    #[test]
+    fn malformed_hash_tables() {
+        // More buckets than the table holds, and a chain that loops.
+        let truncated = table(&[100, 4, 1]);
+        let looping = table(&[1, 3, 1, 0, 2, 1]);
+        let report = dynamic(&library(&[(".hash", SHT_HASH, &truncated),
+                                        (".hash", SHT_HASH, &looping)]), None).unwrap();
+        assert_eq!(report.hash_tables.len(), 1);
+        // The loop is cut off after more links than the table has symbols.
+        assert_eq!(report.hash_tables[0].max_chain, 4);
+        assert!(dynamic(b"\0asm\x01\0\0\0", None).is_err());
+    }
+
+    #[test]
+    fn version_script_savings() {
+        let path = env::temp_dir().join(format!("rust-size-{}-exports.map", process::id()));
+        fs::write(&path, "{ global: a; local: *; };").unwrap();
+        let script = VersionScript::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        // `b` and `c` become local: two symbols, their names, and their chain entries and a
+        // share of the buckets, or a smaller Bloom filter and hash array.
+        let sysv = table(&[2, 4, 1, 0, 0, 2, 3, 0]);
+        let gnu = table(&[2, 1, 1, 6, 0, 0, 1, 3, 0x10, 0x21, 0x31]);
+        let buf = library(&[(".hash", SHT_HASH, &sysv), (".gnu.hash", SHT_GNU_HASH, &gnu)]);
+        let impact = dynamic(&buf, Some(&script)).unwrap().version_script.unwrap();
+        assert_eq!((impact.exported, impact.hidden), (3, 2));
+        assert_eq!((impact.dynsym_savings, impact.dynstr_savings, impact.versym_savings),
+                   (48, 4, 0));
+        // Two buckets for three symbols: a bucket, rounded down, for the two.
+        assert_eq!(impact.hash_savings, (2 * 4 + 4) + (40 - 32));
+        assert_eq!((impact.hidden_code, impact.hidden_data), (32, 0));
+        assert_eq!(impact.table_savings(), 48 + 4 + 20);
+
+        let buf = Builder::shared()
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &[0; 16])
+            .section(".data", SHT_PROGBITS, SHF_ALLOC | SHF_WRITE, &[0; 8])
+            .export("a", STT_FUNC, ".text", 0, 16)
+            .export("table", STT_OBJECT, ".data", 0, 8)
+            .build();
+        let impact = dynamic(&buf, Some(&script)).unwrap().version_script.unwrap();
+        assert_eq!((impact.hidden, impact.hidden_code, impact.hidden_data), (1, 0, 8));
+        assert!(dynamic(&buf, None).unwrap().version_script.is_none());
+    }
+
+    /// A shared library calling `puts` and its own `run` through the PLT, with a second PLT
+    /// in `.plt.sec` if `ibt`.
+    fn plt_library(ibt: bool, text: &[u8]) -> Vec<u8> {
+        let code = SHF_ALLOC | SHF_EXECINSTR;
+        let mut elf = Builder::shared()
+            .section(".text", SHT_PROGBITS, code, text)
+            .section(".plt", SHT_PROGBITS, code, &[0; 48]);
+        if ibt {
+            elf = elf.section(".plt.sec", SHT_PROGBITS, code, &[0; 32]);
+        }
+        elf.section(".got.plt", SHT_PROGBITS, SHF_ALLOC | SHF_WRITE, &[0; 40])
+            .import("puts")
+            .export("run", STT_FUNC, ".text", 0, 16)
+            .dynamic_reloc(true, R_X86_64_JUMP_SLOT, ".got.plt", 24, Some("puts"))
+            .dynamic_reloc(true, R_X86_64_JUMP_SLOT, ".got.plt", 32, Some("run"))
+            .build()
+    }
+
+    fn address(buf: &[u8], name: &str) -> u64 {
+        let elf = Elf::parse(buf).unwrap();
+        elf.section_headers.iter()
+            .find(|sh| elf.shdr_strtab.get(sh.sh_name).and_then(|res| res.ok()) == Some(name))
+            .unwrap()
+            .sh_addr
+    }
+
+    /// Code at `from` making the `call` or `jmp` (`op`) to each of `targets`.
+    fn branches(from: u64, targets: &[(u8, u64)]) -> Vec<u8> {
+        let mut code = Vec::new();
+        for &(op, target) in targets {
+            let rel = target as i64 - (from + code.len() as u64 + 5) as i64;
+            code.push(op);
+            code.extend_from_slice(&(rel as i32).to_le_bytes());
+        }
+        code.resize(32, 0);
+        code
+    }
+
+    type Plt = (u64, u64, u64, u64, Option<u64>, Option<i64>, u64, u64);
+
+    fn plt_stats(buf: &[u8]) -> Option<Plt> {
+        dynamic(buf, None).unwrap().plt.map(|p| {
+            (p.entries, p.plt_size, p.got_plt_size, p.rela_plt_size, p.call_sites,
+             p.no_plt_delta, p.local_entries, p.symbolic_savings)
+        })
+    }
+
+    #[test]
+    fn plt_entries_and_their_calls() {
+        // Calls and a jump to the two entries after the 16-byte header, and calls to the
+        // header and the middle of an entry, which don't count.
+        let layout = plt_library(false, &[0; 32]);
+        let (text, plt) = (address(&layout, ".text"), address(&layout, ".plt"));
+        let code = branches(text, &[(0xe8, plt + 16), (0xe8, plt + 32), (0xe9, plt + 16),
+                                    (0xe8, plt), (0xe8, plt + 24)]);
+        // `-fno-plt` makes three calls a byte longer, but drops the PLT and three GOT slots;
+        // `-Bsymbolic-functions` drops `run`'s entry, GOT slot and relocation.
+        assert_eq!(plt_stats(&plt_library(false, &code)),
+                   Some((2, 48, 40, 48, Some(3), Some(3 - 48 - 24), 1, 16 + 8 + 24)));
+
+        // With IBT, the calls go to `.plt.sec`, and the entries take both PLTs.
+        let layout = plt_library(true, &[0; 32]);
+        let (text, plt) = (address(&layout, ".text"), address(&layout, ".plt"));
+        let sec = address(&layout, ".plt.sec");
+        let code = branches(text, &[(0xe8, sec), (0xe8, sec + 16), (0xe9, sec),
+                                    (0xe8, sec + 8), (0xe8, plt + 16)]);
+        assert_eq!(plt_stats(&plt_library(true, &code)),
+                   Some((2, 80, 40, 48, Some(3), Some(3 - 80 - 24), 1, 32 + 8 + 24)));
+
+        assert_eq!(plt_stats(&library(&[])), None);
+        assert_eq!(plt_layout(EM_AARCH64), (32, Some(8)));
+        assert_eq!(plt_layout(EM_ARM), (20, None));
+    }
+
+    #[test]
+    fn position_independent_executables() {
+        let data = SHF_ALLOC | SHF_WRITE;
+        let buf = Builder::shared()
+            .section(".interp", SHT_PROGBITS, SHF_ALLOC, b"/lib64/ld-linux-x86-64.so.2\0")
+            .segment(PT_INTERP, PF_R, &[".interp"], 1)
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &[0; 16])
+            .section(".got", SHT_PROGBITS, data, &[0; 24])
+            .section(".data", SHT_PROGBITS, data, &[0; 16])
+            .section(".relr.dyn", SHT_RELR, SHF_ALLOC, &[0; 16])
+            .local("__x86.get_pc_thunk.bx", STT_FUNC, ".text", 0, 4)
+            .import("puts")
+            .dynamic_reloc(false, R_X86_64_RELATIVE, ".got", 0, None)
+            .dynamic_reloc(false, R_X86_64_RELATIVE, ".got", 8, None)
+            .dynamic_reloc(false, R_X86_64_GLOB_DAT, ".got", 16, Some("puts"))
+            .dynamic_reloc(false, R_X86_64_RELATIVE, ".data", 0, None)
+            .build();
+        let pie = dynamic(&buf, None).unwrap().pie.unwrap();
+        assert!(!pie.static_pie);
+        // Three relative relocations and the packed ones, two of them for GOT entries.
+        assert_eq!((pie.relative_relocations, pie.relocation_size), (3, 3 * 24 + 16));
+        assert_eq!((pie.got_entries, pie.got_size), (2, 16));
+        assert_eq!((pie.startup_code, pie.dynamic_tables), (4, 0));
+        assert_eq!(pie.total(), 88 + 16 + 4);
+
+        // A static-pie has no interpreter, but says what it is in `DT_FLAGS_1`.
+        let buf = Builder::shared()
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &[0; 16])
+            .section(".data", SHT_PROGBITS, data, &[0; 8])
+            .symbol("_dl_relocate_static_pie", STT_FUNC, ".text", 0, 12)
+            .dynamic_reloc(false, R_X86_64_RELATIVE, ".data", 0, None)
+            .dynamic_entry(DT_FLAGS_1, DF_1_PIE)
+            .build();
+        let pie = dynamic(&buf, None).unwrap().pie.unwrap();
+        assert!(pie.static_pie);
+        assert_eq!((pie.relative_relocations, pie.relocation_size, pie.got_entries), (1, 24, 0));
+        // The null symbol, the empty string table, and the symbol table, relocation,
+        // `DT_FLAGS_1` and terminating entries of `.dynamic`.
+        assert_eq!((pie.startup_code, pie.dynamic_tables), (12, 24 + 1 + 9 * 16));
+
+        // Shared libraries and fixed-address executables aren't PIEs.
+        assert!(dynamic(&library(&[]), None).unwrap().pie.is_none());
+        let buf = Builder::executable()
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &[0; 16])
+            .dynamic_entry(DT_FLAGS_1, DF_1_PIE)
+            .build();
+        assert!(dynamic(&buf, None).unwrap().pie.is_none());
+    }
+
+    #[test]
+    fn relocations_by_type() {
+        // An address, and a bitmap relocating the two words after it and a third.
+        let mut relr = 0x1000u64.to_le_bytes().to_vec();
+        relr.extend_from_slice(&0b1011u64.to_le_bytes());
+        let buf = Builder::shared()
+            .section(".got", SHT_PROGBITS, SHF_ALLOC | SHF_WRITE, &[0; 32])
+            .section(".relr.dyn", SHT_RELR, SHF_ALLOC, &relr)
+            .import("puts")
+            .dynamic_reloc(false, R_X86_64_RELATIVE, ".got", 0, None)
+            .dynamic_reloc(false, R_X86_64_GLOB_DAT, ".got", 8, Some("puts"))
+            .dynamic_reloc(false, R_X86_64_RELATIVE, ".got", 16, None)
+            .dynamic_reloc(false, 200, ".got", 16, None)
+            .dynamic_reloc(true, R_X86_64_JUMP_SLOT, ".got", 24, Some("puts"))
+            .build();
+        let relocations: Vec<_> = dynamic(&buf, None).unwrap().relocations.into_iter()
+            .map(|r| (r.name, r.count, r.size))
+            .collect();
+        assert_eq!(relocations, vec![
+            ("R_X86_64_RELATIVE".to_string(), 2, 48),
+            ("R_X86_64_GLOB_DAT".to_string(), 1, 24),
+            ("R_X86_64_JUMP_SLOT".to_string(), 1, 24),
+            ("type 200".to_string(), 1, 24),
+            ("RELR".to_string(), 3, 16),
+        ]);
+    }
+}
@@ -0,0 +1,131 @@
+use failure::{Error, Fail};
+use std::fmt;
+
+/// The exit status of rust-size. These values are a stable contract, so that scripts and CI
+/// steps can tell outcomes apart without parsing the output.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ExitCode {
+    Success = 0,
+    BudgetExceeded = 1,
+    Usage = 2,
+    ParseFailure = 3,
+    PartialFailure = 4,
+}
+
+/// Every exit code, in numeric order.
+pub const EXIT_CODES: &[ExitCode] = &[
+    ExitCode::Success,
+    ExitCode::BudgetExceeded,
+    ExitCode::Usage,
+    ExitCode::ParseFailure,
+    ExitCode::PartialFailure,
+];
+
+impl ExitCode {
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            ExitCode::Success => "the report was produced",
+            ExitCode::BudgetExceeded => "the report was produced, but a size budget was exceeded",
+            ExitCode::Usage => "the command line was invalid",
+            ExitCode::ParseFailure => "an input could not be read or parsed; nothing was reported",
+            ExitCode::PartialFailure => "some inputs could not be read or parsed, and were left \
+                                         out of the report",
+        }
+    }
+
+    /// The exit code for a run that failed with `err`.
+    pub fn for_error(err: &Error) -> ExitCode {
+        if err.downcast_ref::<UsageError>().is_some() {
+            ExitCode::Usage
+        } else if err.downcast_ref::<BudgetExceeded>().is_some() {
+            ExitCode::BudgetExceeded
+        } else if err.downcast_ref::<PartialFailure>().is_some() {
+            ExitCode::PartialFailure
+        } else {
+            ExitCode::ParseFailure
+        }
+    }
+}
+
+/// The exit codes as a table, for `--help` and `--exit-codes`.
+pub fn exit_code_table() -> String {
+    let mut table = String::from("EXIT CODES:\n");
+    for &code in EXIT_CODES {
+        table.push_str(&format!("    {}    {}\n", code.code(), code.description()));
+    }
+    table
+}
+
+/// An invalid option value, which exits with `ExitCode::Usage`.
+#[derive(Debug)]
+pub struct UsageError(pub String);
+
+impl fmt::Display for UsageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Fail for UsageError {}
+
+/// A report that was produced without some of its inputs, which exits with
+/// `ExitCode::PartialFailure`. The inputs' own errors have already been reported.
+#[derive(Debug)]
+pub struct PartialFailure {
+    /// The number of inputs that were left out.
+    pub failed: usize,
+}
+
+impl fmt::Display for PartialFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} inputs could not be read or parsed", self.failed)
+    }
+}
+
+impl Fail for PartialFailure {}
+
+/// A report that was produced, but showed sizes over their budgets, which exits with
+/// `ExitCode::BudgetExceeded`. The report lists the budgets that were exceeded.
+#[derive(Debug)]
+pub struct BudgetExceeded {
+    /// The number of budgets that were exceeded.
+    pub violations: usize,
+}
+
+impl fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} size budgets were exceeded", self.violations)
+    }
+}
+
+impl Fail for BudgetExceeded {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_for_errors() {
+        let code = |err: Error| ExitCode::for_error(&err);
+        assert_eq!(code(UsageError("Invalid units: KiB".to_string()).into()), ExitCode::Usage);
+        assert_eq!(code(BudgetExceeded { violations: 2 }.into()), ExitCode::BudgetExceeded);
+        assert_eq!(code(PartialFailure { failed: 1 }.into()), ExitCode::PartialFailure);
+        assert_eq!(code(format_err!("Unhandled file type!")), ExitCode::ParseFailure);
+    }
+
+    #[test]
+    fn table_in_numeric_order() {
+        let table = exit_code_table();
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), EXIT_CODES.len() + 1);
+        assert_eq!(lines[0], "EXIT CODES:");
+        assert_eq!(lines[3], "    2    the command line was invalid");
+        for (i, &code) in EXIT_CODES.iter().enumerate() {
+            assert_eq!(code.code(), i as i32);
+        }
+    }
+}
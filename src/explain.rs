@@ -0,0 +1,190 @@
+/// Descriptions of well-known sections, looked up by exact name.
+///
+/// Mach-O sections are listed under the names that `map_mach_name` produces for them, so that
+/// only sections without an ELF counterpart appear with their `__`-prefixed names.
+const SECTIONS: &[(&str, &str)] = &[
+    // ELF
+    (".text", "executable code"),
+    (".init", "code run before main"),
+    (".fini", "code run at exit"),
+    (".plt", "procedure linkage table: stubs for calls into shared libraries"),
+    (".plt.got", "PLT stubs that jump through the GOT"),
+    (".plt.sec", "PLT stubs with control-flow enforcement (IBT) landing pads"),
+    (".iplt", "PLT stubs for ifunc-resolved functions in static binaries"),
+    (".rodata", "read-only data: constants and literals"),
+    (".rodata1", "read-only data: constants and literals"),
+    (".cstring", "C string literals"),
+    (".data", "initialized writable data"),
+    (".data1", "initialized writable data"),
+    (".data.rel.ro", "data that is only written by relocation processing at load time"),
+    (".bss", "zero-initialized writable data, not stored in the file"),
+    (".tdata", "initialized thread-local data"),
+    (".tbss", "zero-initialized thread-local data"),
+    (".eh_frame", "stack unwinding tables"),
+    (".eh_frame_hdr", "binary search index for .eh_frame"),
+    (".gcc_except_table", "exception handling landing pad tables (LSDA)"),
+    (".ARM.exidx", "ARM stack unwinding index"),
+    (".ARM.extab", "ARM stack unwinding tables"),
+    (".ARM.attributes", "ARM build attributes"),
+    (".got", "global offset table: addresses resolved at load time"),
+    (".got.plt", "global offset table entries used by PLT stubs"),
+    (".dynamic", "dynamic linking information"),
+    (".dynsym", "dynamic symbol table"),
+    (".dynstr", "string table for the dynamic symbol table"),
+    (".symtab", "symbol table"),
+    (".strtab", "string table for the symbol table"),
+    (".shstrtab", "section name string table"),
+    (".hash", "SysV symbol hash table for the dynamic linker"),
+    (".gnu.hash", "GNU symbol hash table for the dynamic linker"),
+    (".gnu.version", "symbol version indices"),
+    (".gnu.version_r", "symbol versions required from other libraries"),
+    (".gnu.version_d", "symbol versions defined by this object"),
+    (".interp", "path to the dynamic linker"),
+    (".init_array", "pointers to static constructors"),
+    (".fini_array", "pointers to static destructors"),
+    (".preinit_array", "pointers to functions run before static constructors"),
+    (".ctors", "pointers to static constructors (legacy)"),
+    (".dtors", "pointers to static destructors (legacy)"),
+    (".comment", "compiler and linker version strings"),
+    (".note.gnu.build-id", "unique build identifier"),
+    (".note.ABI-tag", "minimum kernel ABI version"),
+    (".note.gnu.property", "program properties such as CET support"),
+    (".note.stapsdt", "SystemTap static probe points"),
+    (".gnu_debuglink", "name and checksum of the separate debug info file"),
+    (".gnu_debugdata", "compressed mini debug info (MiniDebugInfo)"),
+    (".stab", "legacy stabs debug info"),
+    (".stabstr", "legacy stabs debug strings"),
+    (".debug_info", "DWARF debug info: types, variables and functions"),
+    (".debug_abbrev", "DWARF abbreviation tables for .debug_info"),
+    (".debug_line", "DWARF line number tables"),
+    (".debug_line_str", "DWARF strings for line number tables"),
+    (".debug_str", "DWARF debug strings"),
+    (".debug_str_offsets", "DWARF string offset tables"),
+    (".debug_aranges", "DWARF address range index"),
+    (".debug_ranges", "DWARF address ranges"),
+    (".debug_rnglists", "DWARF address range lists"),
+    (".debug_loc", "DWARF variable location lists"),
+    (".debug_loclists", "DWARF variable location lists"),
+    (".debug_frame", "DWARF stack unwinding tables (not loaded at runtime)"),
+    (".debug_pubnames", "DWARF index of global names"),
+    (".debug_pubtypes", "DWARF index of global types"),
+    (".debug_gnu_pubnames", "GNU index of global names"),
+    (".debug_gnu_pubtypes", "GNU index of global types"),
+    (".debug_names", "DWARF name index"),
+    (".debug_addr", "DWARF address table"),
+    (".debug_types", "DWARF type units"),
+    (".debug_macinfo", "DWARF macro information"),
+    (".debug_macro", "DWARF macro information"),
+    (".gdb_index", "GDB symbol index"),
+    (".rustc", "Rust crate metadata"),
+    // Mach-O
+    ("__stubs", "stubs for calls into shared libraries"),
+    ("__stub_helper", "lazy binding helpers for stubs"),
+    ("__auth_stubs", "pointer-authenticated stubs for calls into shared libraries"),
+    ("__unwind_info", "compact stack unwinding tables"),
+    ("__eh_frame", "DWARF stack unwinding tables"),
+    ("__gcc_except_tab", "exception handling landing pad tables (LSDA)"),
+    ("__literal4", "4-byte literal constants"),
+    ("__literal8", "8-byte literal constants"),
+    ("__literal16", "16-byte literal constants"),
+    ("__ustring", "UTF-16 string literals"),
+    ("__got", "non-lazy symbol pointers"),
+    ("__auth_got", "pointer-authenticated non-lazy symbol pointers"),
+    ("__la_symbol_ptr", "lazy symbol pointers"),
+    ("__nl_symbol_ptr", "non-lazy symbol pointers"),
+    ("__mod_init_func", "pointers to static constructors"),
+    ("__mod_term_func", "pointers to static destructors"),
+    ("__init_offsets", "offsets of static constructors"),
+    ("__thread_vars", "thread-local variable descriptors"),
+    ("__thread_data", "initialized thread-local data"),
+    ("__thread_bss", "zero-initialized thread-local data"),
+    ("__common", "zero-initialized common symbols"),
+    ("__cfstring", "CFString/NSString literal objects"),
+    ("__objc_methname", "Objective-C method names"),
+    ("__objc_classname", "Objective-C class names"),
+    ("__objc_methtype", "Objective-C method type encodings"),
+    ("__objc_selrefs", "Objective-C selector references"),
+    ("__objc_classrefs", "Objective-C class references"),
+    ("__objc_superrefs", "Objective-C superclass references"),
+    ("__objc_classlist", "Objective-C class list"),
+    ("__objc_catlist", "Objective-C category list"),
+    ("__objc_protolist", "Objective-C protocol list"),
+    ("__objc_protorefs", "Objective-C protocol references"),
+    ("__objc_const", "Objective-C class metadata"),
+    ("__objc_data", "Objective-C class objects"),
+    ("__objc_ivar", "Objective-C instance variable offsets"),
+    ("__objc_imageinfo", "Objective-C image information"),
+    ("__swift5_typeref", "Swift type references"),
+    ("__swift5_reflstr", "Swift reflection strings"),
+    ("__swift5_fieldmd", "Swift field metadata"),
+    ("__swift5_types", "Swift type metadata records"),
+    ("__swift5_protos", "Swift protocol descriptors"),
+    ("__swift5_proto", "Swift protocol conformance records"),
+    ("__const", "read-only data: constants and literals"),
+    ("export_table", "the table of exported symbols (the export trie, for Mach-O)"),
+    ("symtab_local", "symbol table entries for local symbols, removed by `strip -x`"),
+    ("symtab_external", "symbol table entries for exported symbols"),
+    ("symtab_undefined", "symbol table entries for imported symbols"),
+    ("symtab_strings", "the names in the symbol table"),
+    ("indirect_symtab", "the symbols that stubs and pointers to imports refer to"),
+    // PE
+    (".rdata", "read-only data: constants, import and debug directories"),
+    (".pdata", "function tables for stack unwinding"),
+    (".xdata", "stack unwinding information"),
+    (".idata", "import tables"),
+    (".edata", "export tables"),
+    (".reloc", "base relocations applied when the image is rebased"),
+    (".rsrc", "resources: icons, manifests, version info"),
+    (".tls", "thread-local data"),
+    (".CRT", "C runtime initializer tables"),
+    (".gfids", "control flow guard function tables"),
+    ("_RDATA", "read-only data"),
+];
+
+/// Descriptions of families of sections, looked up by name prefix when there is no exact match.
+const PREFIXES: &[(&str, &str)] = &[
+    (".text.", "executable code"),
+    (".rodata.", "read-only data: constants and literals"),
+    (".data.rel.ro.", "data that is only written by relocation processing at load time"),
+    (".data.", "initialized writable data"),
+    (".bss.", "zero-initialized writable data, not stored in the file"),
+    (".tdata.", "initialized thread-local data"),
+    (".tbss.", "zero-initialized thread-local data"),
+    (".rela.", "relocations with addends"),
+    (".rel.", "relocations"),
+    (".note.", "vendor notes"),
+    (".debug_", "DWARF debug info"),
+    (".zdebug_", "compressed DWARF debug info"),
+    (".gnu.linkonce.", "sections deduplicated by the linker (legacy COMDAT)"),
+    ("__objc_", "Objective-C runtime metadata"),
+    ("__swift5_", "Swift runtime metadata"),
+    ("__llvm_prf_", "profile-guided optimization instrumentation"),
+];
+
+/// Return a short human-readable description of the section called `name`, if it is a
+/// well-known one.
+pub fn explain(name: &str) -> Option<&'static str> {
+    SECTIONS.iter()
+        .find(|&&(n, _)| n == name)
+        .or_else(|| PREFIXES.iter().find(|&&(prefix, _)| name.starts_with(prefix)))
+        .map(|&(_, description)| description)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_and_prefixes() {
+        assert_eq!(explain(".text"), Some("executable code"));
+        assert_eq!(explain(".text.unlikely"), Some("executable code"));
+        // The longer of two matching prefixes comes first.
+        assert_eq!(explain(".data.rel.ro.local"), explain(".data.rel.ro"));
+        assert_eq!(explain(".data.counter"), Some("initialized writable data"));
+        assert_eq!(explain(".debug_info"),
+                   Some("DWARF debug info: types, variables and functions"));
+        assert_eq!(explain(".debug_cu_index"), Some("DWARF debug info"));
+        assert_eq!(explain(".textual"), None);
+        assert_eq!(explain("custom"), None);
+    }
+}
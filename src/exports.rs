@@ -0,0 +1,171 @@
+use failure::Error;
+use goblin::elf::section_header::SHN_UNDEF;
+use goblin::elf::sym::{STB_GLOBAL, STB_GNU_UNIQUE, STB_WEAK, STT_FILE, STT_SECTION};
+use goblin::mach::Mach;
+use goblin::Object;
+use std::collections::{BTreeSet, HashMap};
+use symbols;
+
+/// The ELF symbol visibilities (`st_other & 3`) under which a symbol can't be bound to from
+/// another module.
+const STV_INTERNAL: u8 = 1;
+const STV_HIDDEN: u8 = 2;
+
+/// A symbol exported by a shared library.
+#[derive(Clone, Debug, Serialize)]
+pub struct Export {
+    pub name: String,
+    /// The size of the symbol, if known. PE export tables don't record sizes.
+    pub size: u64,
+}
+
+/// Return the symbols that the shared library `buf` exports.
+pub fn exports(buf: &[u8]) -> Result<Vec<Export>, Error> {
+    Ok(match Object::parse(buf)? {
+        Object::Elf(elf) => elf.dynsyms.iter()
+            .filter(|sym| {
+                let visibility = sym.st_other & 3;
+                sym.st_shndx != SHN_UNDEF as usize &&
+                    [STB_GLOBAL, STB_WEAK, STB_GNU_UNIQUE].contains(&sym.st_bind()) &&
+                    ![STT_SECTION, STT_FILE].contains(&sym.st_type()) &&
+                    visibility != STV_HIDDEN && visibility != STV_INTERNAL
+            })
+            .filter_map(|sym| {
+                elf.dynstrtab.get(sym.st_name).and_then(|res| res.ok()).map(|name| Export {
+                    name: name.to_string(),
+                    size: sym.st_size,
+                })
+            })
+            .collect(),
+        Object::Mach(Mach::Binary(mach)) => {
+            // The export trie doesn't record sizes, so take them from the symbol table.
+            let sizes: HashMap<String, u64> = symbols::symbols(buf)?.into_iter()
+                .map(|sym| (sym.name, sym.size))
+                .collect();
+            mach.exports()?.into_iter().map(|export| Export {
+                size: sizes.get(&export.name).cloned().unwrap_or(0),
+                name: export.name,
+            }).collect()
+        },
+        Object::PE(pe) => pe.exports.iter()
+            .filter(|export| export.reexport.is_none())
+            .map(|export| Export { name: export.name.to_string(), size: 0 })
+            .collect(),
+        _ => bail!("Unhandled file type!"),
+    })
+}
+
+/// Return the names of the symbols that the binary or shared library `buf` imports.
+pub fn imports(buf: &[u8]) -> Result<BTreeSet<String>, Error> {
+    Ok(match Object::parse(buf)? {
+        Object::Elf(elf) => elf.dynsyms.iter()
+            .filter(|sym| sym.st_shndx == SHN_UNDEF as usize && sym.st_name != 0)
+            .filter_map(|sym| elf.dynstrtab.get(sym.st_name).and_then(|res| res.ok()))
+            .map(str::to_string)
+            .collect(),
+        Object::Mach(Mach::Binary(mach)) => {
+            mach.imports()?.into_iter().map(|import| import.name.to_string()).collect()
+        },
+        Object::PE(pe) => pe.imports.iter().map(|import| import.name.to_string()).collect(),
+        _ => bail!("Unhandled file type!"),
+    })
+}
+
+/// The exports of a shared library that none of a set of consumers import, as emitted by
+/// `dead-exports --format json`.
+#[derive(Clone, Debug, Serialize)]
+pub struct DeadExports {
+    /// The number of symbols the library exports.
+    pub exported: usize,
+    /// The exports that at least one consumer imports, by name.
+    pub used: Vec<String>,
+    /// The exports that no consumer imports, largest first.
+    pub dead: Vec<Export>,
+    /// The total size of the dead exports.
+    pub dead_size: u64,
+}
+
+impl DeadExports {
+    /// Cross-reference the exports of `library` against the imports of `consumers`.
+    ///
+    /// Symbols are matched by name only, regardless of which library the consumers expect to
+    /// find them in, so a symbol that another library also provides counts as used.
+    pub fn new(library: &[u8], consumers: &[&[u8]]) -> Result<DeadExports, Error> {
+        let mut imported = BTreeSet::new();
+        for consumer in consumers {
+            imported.extend(imports(consumer)?);
+        }
+        let exports = exports(library)?;
+        let exported = exports.len();
+        let (used, mut dead): (Vec<Export>, Vec<Export>) = exports.into_iter()
+            .partition(|export| imported.contains(&export.name));
+        dead.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+        let mut used: Vec<String> = used.into_iter().map(|export| export.name).collect();
+        used.sort();
+        used.dedup();
+        Ok(DeadExports {
+            exported,
+            used,
+            dead_size: dead.iter().map(|export| export.size).sum(),
+            dead,
+        })
+    }
+
+    /// A GNU linker version script that keeps only the used exports, and makes the rest local.
+    pub fn version_script(&self) -> String {
+        let mut script = String::from("{\n  global:\n");
+        for name in &self.used {
+            script.push_str(&format!("    \"{}\";\n", name.replace('\\', "\\\\")
+                                                         .replace('"', "\\\"")));
+        }
+        script.push_str("  local:\n    *;\n};\n");
+        script
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::elf::section_header::{SHF_ALLOC, SHF_EXECINSTR, SHT_PROGBITS};
+    use goblin::elf::sym::{STT_FUNC, STT_OBJECT};
+    use testelf::Elf;
+
+    fn library() -> Vec<u8> {
+        Elf::shared()
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &[0; 64])
+            .section(".rodata", SHT_PROGBITS, SHF_ALLOC, &[0; 16])
+            .export("used", STT_FUNC, ".text", 0, 16)
+            .export("unused_big", STT_FUNC, ".text", 16, 40)
+            .export("unused_table", STT_OBJECT, ".rodata", 0, 16)
+            .export("internal", STT_FUNC, ".text", 56, 8)
+            .visibility(STV_HIDDEN)
+            .import("malloc")
+            .build()
+    }
+
+    #[test]
+    fn exports_and_imports() {
+        let buf = library();
+        let exported: Vec<_> = exports(&buf).unwrap().into_iter()
+            .map(|export| (export.name, export.size))
+            .collect();
+        assert_eq!(exported, vec![("used".to_string(), 16), ("unused_big".to_string(), 40),
+                                  ("unused_table".to_string(), 16)]);
+        assert_eq!(imports(&buf).unwrap().into_iter().collect::<Vec<_>>(), vec!["malloc"]);
+    }
+
+    #[test]
+    fn dead_exports_and_their_version_script() {
+        let consumer = Elf::executable()
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &[0; 8])
+            .import("used")
+            .import("printf")
+            .build();
+        let dead = DeadExports::new(&library(), &[&consumer, &consumer]).unwrap();
+        assert_eq!((dead.exported, dead.dead_size), (3, 56));
+        assert_eq!(dead.used, vec!["used"]);
+        let names: Vec<_> = dead.dead.iter().map(|export| export.name.as_str()).collect();
+        assert_eq!(names, vec!["unused_big", "unused_table"]);
+        assert_eq!(dead.version_script(), "{\n  global:\n    \"used\";\n  local:\n    *;\n};\n");
+    }
+}
@@ -0,0 +1,270 @@
+use failure::Error;
+use std::str;
+
+/// The magic number at the start of a flattened device tree.
+const MAGIC: u32 = 0xd00d_feed;
+
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_NOP: u32 = 4;
+const FDT_END: u32 = 9;
+
+/// Whether `buf` starts with a flattened device tree.
+pub fn is_fdt(buf: &[u8]) -> bool {
+    be32(buf, 0) == Some(MAGIC)
+}
+
+fn be32(buf: &[u8], offset: usize) -> Option<u32> {
+    let bytes = buf.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// The header of a flattened device tree, with the offsets of its blocks.
+#[derive(Clone, Debug)]
+pub struct Header {
+    pub total_size: u32,
+    pub struct_offset: u32,
+    pub strings_offset: u32,
+    pub memory_reserve_offset: u32,
+    pub version: u32,
+    /// The sizes of the strings and structure blocks, which trees older than versions 3 and 17
+    /// don't record.
+    pub strings_size: Option<u32>,
+    pub struct_size: Option<u32>,
+}
+
+impl Header {
+    pub fn parse(buf: &[u8]) -> Result<Header, Error> {
+        if !is_fdt(buf) {
+            bail!("Not a flattened device tree");
+        }
+        let field = |i: usize| be32(buf, 4 * i).ok_or_else(|| format_err!("Truncated FDT header"));
+        let version = field(5)?;
+        Ok(Header {
+            total_size: field(1)?,
+            struct_offset: field(2)?,
+            strings_offset: field(3)?,
+            memory_reserve_offset: field(4)?,
+            version,
+            strings_size: if version >= 3 { Some(field(8)?) } else { None },
+            struct_size: if version >= 17 { Some(field(9)?) } else { None },
+        })
+    }
+}
+
+/// A property of a device tree node.
+#[derive(Clone, Debug)]
+pub struct Property<'a> {
+    pub name: String,
+    pub value: &'a [u8],
+}
+
+/// A device tree node, with its properties and child nodes.
+#[derive(Clone, Debug)]
+pub struct Node<'a> {
+    pub name: String,
+    pub properties: Vec<Property<'a>>,
+    pub children: Vec<Node<'a>>,
+}
+
+impl<'a> Node<'a> {
+    pub fn property(&self, name: &str) -> Option<&'a [u8]> {
+        self.properties.iter().find(|p| p.name == name).map(|p| p.value)
+    }
+
+    /// The value of the string property `name`, without its terminating NUL.
+    pub fn string(&self, name: &str) -> Option<&'a str> {
+        let value = self.property(name)?;
+        str::from_utf8(value.strip_suffix(&[0]).unwrap_or(value)).ok()
+    }
+
+    /// The value of the 32-bit cell property `name`.
+    pub fn u32(&self, name: &str) -> Option<u32> {
+        self.property(name).filter(|value| value.len() == 4).and_then(|value| be32(value, 0))
+    }
+
+    pub fn child(&self, name: &str) -> Option<&Node<'a>> {
+        self.children.iter().find(|node| node.name == name)
+    }
+}
+
+/// Parse the structure block of the flattened device tree `buf` into its root node.
+pub fn parse(buf: &[u8]) -> Result<Node<'_>, Error> {
+    parse_tree(buf, &Header::parse(buf)?).map(|(root, _)| root)
+}
+
+/// Parse the structure block described by `header`, returning the root node and the offset of
+/// the end of the block.
+fn parse_tree<'a>(buf: &'a [u8], header: &Header) -> Result<(Node<'a>, usize), Error> {
+    let strings = buf.get(header.strings_offset as usize..)
+        .ok_or_else(|| format_err!("FDT strings block out of bounds"))?;
+    let mut offset = header.struct_offset as usize;
+    let token = |offset: usize| be32(buf, offset).ok_or_else(|| format_err!("Truncated FDT"));
+    let c_string = |data: &'_ [u8]| -> Result<String, Error> {
+        let end = data.iter().position(|&b| b == 0)
+            .ok_or_else(|| format_err!("Unterminated FDT string"))?;
+        Ok(String::from_utf8_lossy(&data[..end]).into_owned())
+    };
+
+    // The nodes being parsed, from the root down.
+    let mut stack: Vec<Node> = Vec::new();
+    loop {
+        match token(offset)? {
+            FDT_BEGIN_NODE => {
+                let name = c_string(&buf[offset + 4..])?;
+                offset += 4 + (name.len() + 1).div_ceil(4) * 4;
+                stack.push(Node { name, properties: Vec::new(), children: Vec::new() });
+            }
+            FDT_END_NODE => {
+                offset += 4;
+                let node = stack.pop().ok_or_else(|| format_err!("Unbalanced FDT nodes"))?;
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(node),
+                    // The root node is followed by `FDT_END`.
+                    None => return Ok((node, offset + 4)),
+                }
+            }
+            FDT_PROP => {
+                let len = token(offset + 4)? as usize;
+                let name_offset = token(offset + 8)? as usize;
+                let value = buf.get(offset + 12..offset + 12 + len)
+                    .ok_or_else(|| format_err!("FDT property out of bounds"))?;
+                let name = c_string(strings.get(name_offset..).unwrap_or(&[]))?;
+                stack.last_mut()
+                    .ok_or_else(|| format_err!("FDT property outside a node"))?
+                    .properties.push(Property { name, value });
+                offset += 12 + len.div_ceil(4) * 4;
+            }
+            FDT_NOP => offset += 4,
+            FDT_END => bail!("FDT ended inside a node"),
+            token => bail!("Invalid FDT token {:#x}", token),
+        }
+    }
+}
+
+/// The layout of a device tree blob, as emitted in `firmware` and `analyze` reports.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Breakdown {
+    pub header: u64,
+    /// The memory reservation block, including its terminating entry.
+    pub memory_reserve: u64,
+    pub reservations: u64,
+    /// The structure block, with the nodes and property values.
+    pub structure: u64,
+    /// The strings block, with the property names.
+    pub strings: u64,
+    /// The space between and after the blocks, left for bootloaders to add to the tree.
+    pub padding: u64,
+    pub total: u64,
+    pub nodes: u64,
+    pub properties: u64,
+}
+
+/// The size of the header of a version `version` tree, which grew a field in versions 2, 3
+/// and 17.
+fn header_size(version: u32) -> u64 {
+    match version {
+        0..=1 => 28,
+        2 => 32,
+        3..=16 => 36,
+        _ => 40,
+    }
+}
+
+/// Measure the blocks of the flattened device tree `buf`.
+pub fn breakdown(buf: &[u8]) -> Result<Breakdown, Error> {
+    let header = Header::parse(buf)?;
+    let (root, struct_end) = parse_tree(buf, &header)?;
+    let mut reservations = 0;
+    let mut offset = header.memory_reserve_offset as usize;
+    loop {
+        let entry = buf.get(offset..offset + 16)
+            .ok_or_else(|| format_err!("FDT memory reservation block out of bounds"))?;
+        offset += 16;
+        if entry.iter().all(|&b| b == 0) {
+            break;
+        }
+        reservations += 1;
+    }
+    let memory_reserve = 16 * (reservations + 1);
+    let structure = header.struct_size
+        .map_or((struct_end - header.struct_offset as usize) as u64, u64::from);
+    let strings = header.strings_size
+        .map_or(header.total_size.saturating_sub(header.strings_offset), |size| size) as u64;
+    fn count(node: &Node) -> (u64, u64) {
+        node.children.iter().map(count).fold((1, node.properties.len() as u64), |a, b| {
+            (a.0 + b.0, a.1 + b.1)
+        })
+    }
+    let (nodes, properties) = count(&root);
+    let total = u64::from(header.total_size);
+    let header = header_size(header.version);
+    Ok(Breakdown {
+        header,
+        memory_reserve,
+        reservations,
+        structure,
+        strings,
+        padding: total.saturating_sub(header + memory_reserve + structure + strings),
+        total,
+        nodes,
+        properties,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use testfdt::Tree;
+
+    fn tree() -> Vec<u8> {
+        Tree::root()
+            .reserve(0x8000_0000, 0x1000)
+            .string("model", "x")
+            .begin("cpus")
+            .cell("#address-cells", 1)
+            .end()
+            .padding(64)
+            .build()
+    }
+
+    #[test]
+    fn nodes_and_properties() {
+        let buf = tree();
+        let root = parse(&buf).unwrap();
+        assert_eq!((&*root.name, root.string("model")), ("", Some("x")));
+        let cpus = root.child("cpus").unwrap();
+        assert_eq!(cpus.u32("#address-cells"), Some(1));
+        assert_eq!((cpus.u32("model"), root.u32("model"), root.child("memory").is_none()),
+                   (None, None, true));
+
+        assert!(parse(b"\xd0\x0d\xfe\xed\0\0\0\x28").is_err());
+        // Before the padding, the strings, and the root node's `FDT_END_NODE` and `FDT_END`.
+        let end = buf.len() - 64 - 21 - 8;
+        assert!(parse(&buf[..end]).is_err());
+        let mut unbalanced = buf.clone();
+        unbalanced[end + 3] = FDT_END as u8;
+        assert!(parse(&unbalanced).is_err());
+        assert!(!is_fdt(b"\x7fELF"));
+    }
+
+    #[test]
+    fn blocks_of_a_tree() {
+        // Nodes open with a token and their padded name, and properties take a token, a length
+        // and a name offset before their padded value.
+        let structure = (4 + 4) + (12 + 4) + (4 + 8) + (12 + 4) + 4 + 4 + 4;
+        let strings = "model\0#address-cells\0".len() as u64;
+        let mut buf = tree();
+        let b = breakdown(&buf).unwrap();
+        assert_eq!((b.header, b.memory_reserve, b.reservations), (40, 32, 1));
+        assert_eq!((b.structure, b.strings, b.padding), (structure, strings, 64));
+        assert_eq!((b.total, b.nodes, b.properties), (40 + 32 + 64 + strings + 64, 2, 2));
+
+        // Version 2 trees don't record the block sizes, so the padding counts as strings.
+        buf[23] = 2;
+        let b = breakdown(&buf).unwrap();
+        assert_eq!((b.header, b.structure, b.strings), (32, structure, strings + 64));
+        assert_eq!(b.padding, 40 - 32);
+    }
+}
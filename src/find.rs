@@ -0,0 +1,81 @@
+use failure::Error;
+use regex::Regex;
+use rustc_demangle;
+use symbols;
+
+/// A symbol whose name matches a `find` pattern, as emitted by `find --format json`.
+#[derive(Clone, Debug, Serialize)]
+pub struct Match {
+    /// The demangled name, without the hash.
+    pub name: String,
+    /// The name as it appears in the symbol table.
+    pub symbol: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub section: Option<String>,
+    pub address: u64,
+    pub size: u64,
+    pub code: bool,
+    /// Whether the size is the distance to the next symbol rather than recorded in the symbol
+    /// table.
+    pub inferred: bool,
+}
+
+/// Find the symbols of `buf` whose demangled or mangled names match `pattern`, largest first,
+/// inferring the sizes of unsized symbols with `infer_sizes`.
+pub fn find(buf: &[u8], pattern: &Regex, infer_sizes: bool) -> Result<Vec<Match>, Error> {
+    let symbols = symbols::symbols_inferring(buf, infer_sizes)?;
+    let mut matches: Vec<Match> = symbols.into_iter().filter_map(|sym| {
+        let name = format!("{:#}", rustc_demangle::demangle(&sym.name));
+        if !pattern.is_match(&name) && !pattern.is_match(&sym.name) {
+            return None;
+        }
+        Some(Match {
+            name,
+            symbol: sym.name,
+            section: sym.section,
+            address: sym.address,
+            size: sym.size,
+            code: sym.code,
+            inferred: sym.inferred,
+        })
+    }).collect();
+    matches.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+    Ok(matches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::elf::section_header::{SHF_ALLOC, SHF_EXECINSTR, SHT_PROGBITS};
+    use goblin::elf::sym::{STT_FUNC, STT_OBJECT};
+    use testelf::{Elf, BASE};
+
+    #[test]
+    fn demangled_or_mangled_names() {
+        let buf = Elf::executable()
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &[0; 64])
+            .section(".rodata", SHT_PROGBITS, SHF_ALLOC, &[0; 16])
+            .symbol("_ZN4core3fmt5write17h0123456789abcdefE", STT_FUNC, ".text", 0, 16)
+            .symbol("_ZN3app3fmt4Args17hfedcba9876543210E", STT_OBJECT, ".rodata", 0, 16)
+            .symbol("fmt_helper", STT_FUNC, ".text", 16, 48)
+            .symbol("main", STT_FUNC, ".text", 48, 16)
+            .build();
+        let matches: Vec<_> = find(&buf, &Regex::new("fmt").unwrap(), false).unwrap()
+            .into_iter()
+            .map(|m| (m.name, m.section, m.size, m.code))
+            .collect();
+        let text = Some(".text".to_string());
+        assert_eq!(matches, vec![
+            ("fmt_helper".to_string(), text.clone(), 48, true),
+            ("app::fmt::Args".to_string(), Some(".rodata".to_string()), 16, false),
+            ("core::fmt::write".to_string(), text, 16, true),
+        ]);
+
+        // The hash is only in the mangled name.
+        let matches = find(&buf, &Regex::new("^_ZN4core.*17h0123").unwrap(), false).unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].symbol, "_ZN4core3fmt5write17h0123456789abcdefE");
+        assert!(matches[0].address > BASE && !matches[0].inferred);
+        assert!(find(&buf, &Regex::new("^write$").unwrap(), false).unwrap().is_empty());
+    }
+}
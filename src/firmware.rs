@@ -0,0 +1,345 @@
+use archive;
+use compare::Column;
+use failure::Error;
+use fdt::{self, Node};
+
+/// The magic number of a legacy U-Boot image header.
+const UIMAGE_MAGIC: u32 = 0x2705_1956;
+
+/// The size of a legacy U-Boot image header.
+const UIMAGE_HEADER_SIZE: usize = 64;
+
+/// The image type that holds several images, with a table of their sizes.
+const UIMAGE_MULTI: u8 = 4;
+
+/// One of the images in a firmware image: a kernel, device tree, ramdisk and so on.
+#[derive(Clone, Debug, Serialize)]
+pub struct Payload {
+    pub name: String,
+    /// The type of the image, as `mkimage` spells it (`kernel`, `flat_dt`, `ramdisk`, ...).
+    pub kind: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub arch: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub os: Option<String>,
+    pub compression: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub size: u64,
+    /// The offset of the image in the file.
+    pub offset: u64,
+    /// The sizes of the image, if it is an uncompressed object file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub analysis: Option<Column>,
+    /// The layout of the image, if it is an uncompressed device tree blob.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dtb: Option<fdt::Breakdown>,
+}
+
+/// The images that a U-Boot FIT or legacy uImage contains, as emitted by
+/// `firmware --format json`.
+#[derive(Clone, Debug, Serialize)]
+pub struct FirmwareReport {
+    /// `FIT` or `uImage`.
+    pub format: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub file_size: u64,
+    pub payloads: Vec<Payload>,
+    /// The boot configurations of a FIT, which pick the images to boot together.
+    pub configurations: Vec<String>,
+}
+
+/// Analyze the image `data` if it is an uncompressed object file.
+fn analysis(name: &str, compression: &str, data: &[u8]) -> Result<Option<Column>, Error> {
+    if compression != "none" || !archive::is_object(data) {
+        return Ok(None);
+    }
+    Column::new(name.to_string(), data, false, false).map(Some)
+}
+
+/// Measure the image `data` if it is an uncompressed device tree blob.
+fn dtb(compression: &str, data: &[u8]) -> Result<Option<fdt::Breakdown>, Error> {
+    if compression != "none" || !fdt::is_fdt(data) {
+        return Ok(None);
+    }
+    fdt::breakdown(data).map(Some)
+}
+
+/// The name of a legacy uImage code from the table `names`, which is indexed by code.
+fn code_name(names: &[&str], code: u8) -> String {
+    match names.get(code as usize) {
+        Some(name) if !name.is_empty() => name.to_string(),
+        _ => format!("{}", code),
+    }
+}
+
+const UIMAGE_OS: &[&str] = &["invalid", "openbsd", "netbsd", "freebsd", "4_4bsd", "linux",
+                             "svr4", "esix", "solaris", "irix", "sco", "dell", "ncr", "lynxos",
+                             "vxworks", "psos", "qnx", "u-boot", "rtems", "artos", "unity",
+                             "integrity", "ose", "plan9", "openrtos", "arm-trusted-firmware",
+                             "tee", "opensbi", "efi"];
+const UIMAGE_ARCH: &[&str] = &["invalid", "alpha", "arm", "x86", "ia64", "mips", "mips64",
+                               "powerpc", "s390", "sh", "sparc", "sparc64", "m68k", "",
+                               "microblaze", "nios2", "blackfin", "avr32", "st200", "sandbox",
+                               "nds32", "or1k", "arm64", "arc", "x86_64", "xtensa", "riscv"];
+const UIMAGE_TYPE: &[&str] = &["invalid", "standalone", "kernel", "ramdisk", "multi", "firmware",
+                               "script", "filesystem", "flat_dt"];
+const UIMAGE_COMPRESSION: &[&str] = &["none", "gzip", "bzip2", "lzma", "lzo", "lz4", "zstd"];
+
+/// Parse a legacy uImage: a 64-byte header, followed by the image, or for multi-file images by
+/// a zero-terminated table of sizes and the images, each padded to 4 bytes.
+fn uimage(buf: &[u8]) -> Result<FirmwareReport, Error> {
+    let header = &buf[..UIMAGE_HEADER_SIZE];
+    let size = u32::from_be_bytes([header[12], header[13], header[14], header[15]]) as usize;
+    let (os, arch, kind, compression) = (header[28], header[29], header[30], header[31]);
+    let name_end = header[32..].iter().position(|&b| b == 0).unwrap_or(32);
+    let name = String::from_utf8_lossy(&header[32..32 + name_end]).into_owned();
+    let data = buf.get(UIMAGE_HEADER_SIZE..UIMAGE_HEADER_SIZE + size)
+        .ok_or_else(|| format_err!("uImage data out of bounds"))?;
+    let compression = code_name(UIMAGE_COMPRESSION, compression);
+
+    let mut images = Vec::new();
+    if kind == UIMAGE_MULTI {
+        let mut sizes = Vec::new();
+        for word in data.chunks_exact(4) {
+            match u32::from_be_bytes([word[0], word[1], word[2], word[3]]) {
+                0 => break,
+                size => sizes.push(size as usize),
+            }
+        }
+        let mut offset = 4 * (sizes.len() + 1);
+        for (i, size) in sizes.into_iter().enumerate() {
+            let image = data.get(offset..offset + size)
+                .ok_or_else(|| format_err!("uImage part {} out of bounds", i))?;
+            images.push((format!("{} [{}]", name, i), UIMAGE_HEADER_SIZE + offset, image));
+            offset += size.div_ceil(4) * 4;
+        }
+    } else {
+        images.push((name.clone(), UIMAGE_HEADER_SIZE, data));
+    }
+
+    let mut payloads = Vec::new();
+    for (name, offset, data) in images {
+        payloads.push(Payload {
+            analysis: analysis(&name, &compression, data)?,
+            dtb: dtb(&compression, data)?,
+            name,
+            kind: code_name(UIMAGE_TYPE, kind),
+            arch: Some(code_name(UIMAGE_ARCH, arch)),
+            os: Some(code_name(UIMAGE_OS, os)),
+            compression: compression.clone(),
+            description: None,
+            size: data.len() as u64,
+            offset: offset as u64,
+        });
+    }
+    Ok(FirmwareReport {
+        format: "uImage",
+        description: Some(name).filter(|name| !name.is_empty()),
+        file_size: buf.len() as u64,
+        payloads,
+        configurations: Vec::new(),
+    })
+}
+
+/// The contents of a FIT image node, which are either embedded in its `data` property, or
+/// stored after the tree, at `data-position` in the file or `data-offset` past the end of the
+/// tree (rounded up to 4 bytes).
+fn fit_data<'a>(buf: &'a [u8], tree_size: usize, node: &Node<'a>)
+                -> Result<(usize, &'a [u8]), Error> {
+    if let Some(data) = node.property("data") {
+        return Ok((data.as_ptr() as usize - buf.as_ptr() as usize, data));
+    }
+    let size = node.u32("data-size")
+        .ok_or_else(|| format_err!("FIT image {} has no data", node.name))? as usize;
+    let offset = match (node.u32("data-position"), node.u32("data-offset")) {
+        (Some(position), _) => position as usize,
+        (None, Some(offset)) => tree_size.div_ceil(4) * 4 + offset as usize,
+        (None, None) => bail!("FIT image {} has no data", node.name),
+    };
+    let data = buf.get(offset..offset + size)
+        .ok_or_else(|| format_err!("FIT image {} out of bounds", node.name))?;
+    Ok((offset, data))
+}
+
+/// Parse a FIT image: a device tree with an `images` node describing each image.
+fn fit(buf: &[u8]) -> Result<FirmwareReport, Error> {
+    let header = fdt::Header::parse(buf)?;
+    let root = fdt::parse(buf)?;
+    let images = root.child("images").ok_or_else(|| format_err!("Not a FIT image"))?;
+    let mut payloads = Vec::new();
+    for node in &images.children {
+        let (offset, data) = fit_data(buf, header.total_size as usize, node)?;
+        let compression = node.string("compression").unwrap_or("none").to_string();
+        payloads.push(Payload {
+            name: node.name.clone(),
+            kind: node.string("type").unwrap_or("unknown").to_string(),
+            arch: node.string("arch").map(str::to_string),
+            os: node.string("os").map(str::to_string),
+            analysis: analysis(&node.name, &compression, data)?,
+            dtb: dtb(&compression, data)?,
+            compression,
+            description: node.string("description").map(str::to_string),
+            size: data.len() as u64,
+            offset: offset as u64,
+        });
+    }
+    Ok(FirmwareReport {
+        format: "FIT",
+        description: root.string("description").map(str::to_string),
+        file_size: buf.len() as u64,
+        payloads,
+        configurations: root.child("configurations")
+            .map_or_else(Vec::new, |node| node.children.iter().map(|c| c.name.clone()).collect()),
+    })
+}
+
+/// List the images in the U-Boot FIT or legacy uImage `buf`, analyzing those that are
+/// uncompressed object files.
+pub fn firmware(buf: &[u8]) -> Result<FirmwareReport, Error> {
+    if buf.len() >= UIMAGE_HEADER_SIZE && buf[..4] == UIMAGE_MAGIC.to_be_bytes() {
+        uimage(buf)
+    } else if fdt::is_fdt(buf) {
+        fit(buf)
+    } else {
+        bail!("Not a FIT image or uImage")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::elf::section_header::{SHF_ALLOC, SHF_EXECINSTR, SHT_PROGBITS};
+    use testelf::Elf;
+    use testfdt::Tree;
+
+    /// A legacy uImage header of the type `kind`, for `size` bytes of an arm64 Linux image.
+    fn uimage_header(kind: u8, compression: u8, size: u32, name: &str) -> Vec<u8> {
+        let mut header = UIMAGE_MAGIC.to_be_bytes().to_vec();
+        header.extend_from_slice(&[0; 8]);
+        header.extend_from_slice(&size.to_be_bytes());
+        header.extend_from_slice(&[0; 12]);
+        header.extend_from_slice(&[5, 22, kind, compression]);
+        let mut field = [0; 32];
+        field[..name.len()].copy_from_slice(name.as_bytes());
+        header.extend_from_slice(&field);
+        header
+    }
+
+    type Summary = (String, String, Option<String>, Option<String>, String, u64, u64, bool);
+
+    fn summary(report: &FirmwareReport) -> Vec<Summary> {
+        report.payloads.iter().map(|p| {
+            (p.name.clone(), p.kind.clone(), p.arch.clone(), p.os.clone(), p.compression.clone(),
+             p.size, p.offset, p.analysis.is_some())
+        }).collect()
+    }
+
+    fn payload(name: &str, kind: &str, compression: &str, size: u64, offset: u64,
+               analyzed: bool) -> Summary {
+        let (arch, os) = (Some("arm64".to_string()), Some("linux".to_string()));
+        (name.to_string(), kind.to_string(), arch, os, compression.to_string(), size, offset,
+         analyzed)
+    }
+
+    #[test]
+    fn legacy_uimages() {
+        let mut buf = uimage_header(2, 1, 10, "Linux-6.1");
+        buf.extend_from_slice(&[0; 10]);
+        let report = firmware(&buf).unwrap();
+        assert_eq!((report.format, report.description.as_deref()), ("uImage", Some("Linux-6.1")));
+        assert_eq!(summary(&report), vec![payload("Linux-6.1", "kernel", "gzip", 10, 64, false)]);
+
+        // A table of the sizes 3 and 5, then the images, padded to 4 bytes.
+        let elf = Elf::executable()
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &[0; 16])
+            .build();
+        let mut data = vec![0, 0, 0, 3];
+        data.extend_from_slice(&(elf.len() as u32).to_be_bytes());
+        data.extend_from_slice(&[0, 0, 0, 0, 1, 2, 3, 0]);
+        data.extend_from_slice(&elf);
+        let mut buf = uimage_header(UIMAGE_MULTI, 0, data.len() as u32, "multi");
+        buf.extend_from_slice(&data);
+        let report = firmware(&buf).unwrap();
+        assert_eq!(summary(&report), vec![
+            payload("multi [0]", "multi", "none", 3, 64 + 12, false),
+            payload("multi [1]", "multi", "none", elf.len() as u64, 64 + 16, true),
+        ]);
+
+        // Codes past the end of the tables are kept as numbers.
+        let mut buf = uimage_header(200, 9, 0, "");
+        buf[29] = 13;
+        let report = firmware(&buf).unwrap();
+        assert_eq!(report.description, None);
+        let p = &report.payloads[0];
+        assert_eq!((&*p.kind, p.arch.as_deref(), &*p.compression), ("200", Some("13"), "9"));
+        let mut buf = uimage_header(2, 0, 100, "short");
+        buf.extend_from_slice(&[0; 10]);
+        assert!(firmware(&buf).is_err());
+    }
+
+    #[test]
+    fn fit_images_with_embedded_and_external_data() {
+        let elf = Elf::executable()
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &[0; 16])
+            .build();
+        let fit = |position: Option<u32>| {
+            let tree = Tree::root()
+                .string("description", "Test FIT")
+                .begin("images")
+                .begin("kernel-1")
+                .string("description", "Linux")
+                .string("type", "kernel")
+                .string("arch", "arm64")
+                .string("os", "linux")
+                .string("compression", "gzip")
+                .property("data", &[0; 10])
+                .end()
+                .begin("firmware-1")
+                .string("type", "firmware")
+                .cell("data-size", elf.len() as u32);
+            let tree = match position {
+                Some(position) => tree.cell("data-position", position),
+                None => tree.cell("data-offset", 2),
+            };
+            tree.end().end()
+                .begin("configurations")
+                .begin("conf-1").end()
+                .begin("conf-2").end()
+                .end()
+                .build()
+        };
+
+        // The external data goes 2 bytes after the end of the tree, rounded up to 4 bytes.
+        let mut buf = fit(None);
+        let start = buf.len().div_ceil(4) * 4 + 2;
+        buf.resize(start, 0);
+        buf.extend_from_slice(&elf);
+        let report = firmware(&buf).unwrap();
+        assert_eq!((report.format, report.description.as_deref()), ("FIT", Some("Test FIT")));
+        assert_eq!(report.configurations, vec!["conf-1", "conf-2"]);
+        let kernel = &report.payloads[0];
+        assert_eq!(&buf[kernel.offset as usize..kernel.offset as usize + 10], &[0; 10]);
+        assert_eq!(kernel.description.as_deref(), Some("Linux"));
+        let mut payloads = summary(&report);
+        payloads[0].6 = 0;
+        assert_eq!(payloads, vec![
+            payload("kernel-1", "kernel", "gzip", 10, 0, false),
+            ("firmware-1".to_string(), "firmware".to_string(), None, None, "none".to_string(),
+             elf.len() as u64, start as u64, true),
+        ]);
+
+        // Or at an absolute position.
+        let position = fit(Some(0)).len() + 8;
+        let mut buf = fit(Some(position as u32));
+        buf.resize(position, 0);
+        buf.extend_from_slice(&elf);
+        assert_eq!(firmware(&buf).unwrap().payloads[1].offset, position as u64);
+        buf.truncate(position + 8);
+        assert!(firmware(&buf).is_err());
+
+        assert!(firmware(&Tree::root().build()).is_err());
+        assert!(firmware(b"\x7fELF").is_err());
+    }
+}
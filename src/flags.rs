@@ -0,0 +1,135 @@
+use goblin::elf::section_header::{shf_to_str, SHF_FLAGS};
+use goblin::mach::constants::*;
+use goblin::pe::section_table::*;
+
+/// `SHF_EXCLUDE`, which goblin leaves commented out.
+const SHF_EXCLUDE: u64 = 0x8000_0000;
+
+/// Names of the ELF `sh_flags` bits set in `flags`.
+pub fn elf_flag_names(flags: u64) -> Vec<&'static str> {
+    let mut names: Vec<&'static str> = SHF_FLAGS.iter()
+        .filter(|&&flag| flags & u64::from(flag) != 0)
+        .map(|&flag| shf_to_str(flag))
+        .collect();
+    if flags & SHF_EXCLUDE != 0 {
+        names.push("SHF_EXCLUDE");
+    }
+    names
+}
+
+/// Mach-O section attributes, which share the `flags` field with the section type.
+const MACH_ATTRIBUTES: &[(u32, &str)] = &[
+    (S_ATTR_PURE_INSTRUCTIONS, "S_ATTR_PURE_INSTRUCTIONS"),
+    (S_ATTR_NO_TOC, "S_ATTR_NO_TOC"),
+    (S_ATTR_STRIP_STATIC_SYMS, "S_ATTR_STRIP_STATIC_SYMS"),
+    (S_ATTR_NO_DEAD_STRIP, "S_ATTR_NO_DEAD_STRIP"),
+    (S_ATTR_LIVE_SUPPORT, "S_ATTR_LIVE_SUPPORT"),
+    (S_ATTR_SELF_MODIFYING_CODE, "S_ATTR_SELF_MODIFYING_CODE"),
+    (S_ATTR_DEBUG, "S_ATTR_DEBUG"),
+    (S_ATTR_SOME_INSTRUCTIONS, "S_ATTR_SOME_INSTRUCTIONS"),
+    (S_ATTR_EXT_RELOC, "S_ATTR_EXT_RELOC"),
+    (S_ATTR_LOC_RELOC, "S_ATTR_LOC_RELOC"),
+];
+
+/// The name of a Mach-O section type.
+fn mach_type_name(section_type: u32) -> &'static str {
+    match section_type {
+        S_REGULAR => "S_REGULAR",
+        S_ZEROFILL => "S_ZEROFILL",
+        S_CSTRING_LITERALS => "S_CSTRING_LITERALS",
+        S_4BYTE_LITERALS => "S_4BYTE_LITERALS",
+        S_8BYTE_LITERALS => "S_8BYTE_LITERALS",
+        S_LITERAL_POINTERS => "S_LITERAL_POINTERS",
+        S_NON_LAZY_SYMBOL_POINTERS => "S_NON_LAZY_SYMBOL_POINTERS",
+        S_LAZY_SYMBOL_POINTERS => "S_LAZY_SYMBOL_POINTERS",
+        S_SYMBOL_STUBS => "S_SYMBOL_STUBS",
+        S_MOD_INIT_FUNC_POINTERS => "S_MOD_INIT_FUNC_POINTERS",
+        S_MOD_TERM_FUNC_POINTERS => "S_MOD_TERM_FUNC_POINTERS",
+        S_COALESCED => "S_COALESCED",
+        S_GB_ZEROFILL => "S_GB_ZEROFILL",
+        S_INTERPOSING => "S_INTERPOSING",
+        S_16BYTE_LITERALS => "S_16BYTE_LITERALS",
+        S_DTRACE_DOF => "S_DTRACE_DOF",
+        S_LAZY_DYLIB_SYMBOL_POINTERS => "S_LAZY_DYLIB_SYMBOL_POINTERS",
+        S_THREAD_LOCAL_REGULAR => "S_THREAD_LOCAL_REGULAR",
+        S_THREAD_LOCAL_ZEROFILL => "S_THREAD_LOCAL_ZEROFILL",
+        S_THREAD_LOCAL_VARIABLES => "S_THREAD_LOCAL_VARIABLES",
+        S_THREAD_LOCAL_VARIABLE_POINTERS => "S_THREAD_LOCAL_VARIABLE_POINTERS",
+        S_THREAD_LOCAL_INIT_FUNCTION_POINTERS => "S_THREAD_LOCAL_INIT_FUNCTION_POINTERS",
+        _ => "S_UNKNOWN",
+    }
+}
+
+/// Names of the Mach-O section type and attributes in `flags`.
+pub fn mach_flag_names(flags: u32) -> Vec<&'static str> {
+    let mut names = vec![mach_type_name(flags & SECTION_TYPE)];
+    names.extend(MACH_ATTRIBUTES.iter()
+        .filter(|&&(attr, _)| flags & attr != 0)
+        .map(|&(_, name)| name));
+    names
+}
+
+/// PE section characteristics, apart from the alignment, which is a number rather than a bit.
+const PE_CHARACTERISTICS: &[(u32, &str)] = &[
+    (IMAGE_SCN_TYPE_NO_PAD, "IMAGE_SCN_TYPE_NO_PAD"),
+    (IMAGE_SCN_CNT_CODE, "IMAGE_SCN_CNT_CODE"),
+    (IMAGE_SCN_CNT_INITIALIZED_DATA, "IMAGE_SCN_CNT_INITIALIZED_DATA"),
+    (IMAGE_SCN_CNT_UNINITIALIZED_DATA, "IMAGE_SCN_CNT_UNINITIALIZED_DATA"),
+    (IMAGE_SCN_LNK_OTHER, "IMAGE_SCN_LNK_OTHER"),
+    (IMAGE_SCN_LNK_INFO, "IMAGE_SCN_LNK_INFO"),
+    (IMAGE_SCN_LNK_REMOVE, "IMAGE_SCN_LNK_REMOVE"),
+    (IMAGE_SCN_LNK_COMDAT, "IMAGE_SCN_LNK_COMDAT"),
+    (IMAGE_SCN_GPREL, "IMAGE_SCN_GPREL"),
+    (IMAGE_SCN_MEM_PURGEABLE, "IMAGE_SCN_MEM_PURGEABLE"),
+    (IMAGE_SCN_MEM_LOCKED, "IMAGE_SCN_MEM_LOCKED"),
+    (IMAGE_SCN_MEM_PRELOAD, "IMAGE_SCN_MEM_PRELOAD"),
+    (IMAGE_SCN_LNK_NRELOC_OVFL, "IMAGE_SCN_LNK_NRELOC_OVFL"),
+    (IMAGE_SCN_MEM_DISCARDABLE, "IMAGE_SCN_MEM_DISCARDABLE"),
+    (IMAGE_SCN_MEM_NOT_CACHED, "IMAGE_SCN_MEM_NOT_CACHED"),
+    (IMAGE_SCN_MEM_NOT_PAGED, "IMAGE_SCN_MEM_NOT_PAGED"),
+    (IMAGE_SCN_MEM_SHARED, "IMAGE_SCN_MEM_SHARED"),
+    (IMAGE_SCN_MEM_EXECUTE, "IMAGE_SCN_MEM_EXECUTE"),
+    (IMAGE_SCN_MEM_READ, "IMAGE_SCN_MEM_READ"),
+    (IMAGE_SCN_MEM_WRITE, "IMAGE_SCN_MEM_WRITE"),
+];
+
+/// Names of the PE section characteristics set in `characteristics`.
+pub fn pe_flag_names(characteristics: u32) -> Vec<&'static str> {
+    PE_CHARACTERISTICS.iter()
+        .filter(|&&(flag, _)| characteristics & flag != 0)
+        .map(|&(_, name)| name)
+        .collect()
+}
+
+/// The alignment encoded in PE section characteristics, if any.
+pub fn pe_alignment(characteristics: u32) -> Option<u64> {
+    match (characteristics & IMAGE_SCN_ALIGN_MASK) >> 20 {
+        0 => None,
+        n => Some(1 << (n - 1)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flag_names() {
+        assert_eq!(elf_flag_names(0x8000_0006), vec!["SHF_ALLOC", "SHF_EXECINSTR", "SHF_EXCLUDE"]);
+        assert!(elf_flag_names(0).is_empty());
+        assert_eq!(mach_flag_names(S_CSTRING_LITERALS), vec!["S_CSTRING_LITERALS"]);
+        assert_eq!(mach_flag_names(S_ATTR_PURE_INSTRUCTIONS | S_ATTR_SOME_INSTRUCTIONS),
+                   vec!["S_REGULAR", "S_ATTR_PURE_INSTRUCTIONS", "S_ATTR_SOME_INSTRUCTIONS"]);
+        assert_eq!(mach_flag_names(0xff), vec!["S_UNKNOWN"]);
+        assert_eq!(pe_flag_names(0x6000_0020),
+                   vec!["IMAGE_SCN_CNT_CODE", "IMAGE_SCN_MEM_EXECUTE", "IMAGE_SCN_MEM_READ"]);
+    }
+
+    #[test]
+    fn pe_alignments() {
+        assert_eq!(pe_alignment(0x6000_0020), None);
+        assert_eq!(pe_alignment(IMAGE_SCN_ALIGN_1BYTES), Some(1));
+        assert_eq!(pe_alignment(IMAGE_SCN_ALIGN_16BYTES | IMAGE_SCN_CNT_CODE), Some(16));
+        assert_eq!(pe_alignment(IMAGE_SCN_ALIGN_8192BYTES), Some(8192));
+    }
+}
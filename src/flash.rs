@@ -0,0 +1,353 @@
+use exit::UsageError;
+use failure::Error;
+use goblin::elf::program_header::PT_LOAD;
+use goblin::elf::section_header::{SHF_ALLOC, SHT_NOBITS};
+use goblin::elf::Elf;
+use std::cmp;
+use std::collections::BTreeMap;
+
+/// The Intel HEX record types.
+const IHEX_DATA: u8 = 0;
+const IHEX_END_OF_FILE: u8 = 1;
+const IHEX_EXTENDED_SEGMENT_ADDRESS: u8 = 2;
+const IHEX_START_SEGMENT_ADDRESS: u8 = 3;
+const IHEX_EXTENDED_LINEAR_ADDRESS: u8 = 4;
+const IHEX_START_LINEAR_ADDRESS: u8 = 5;
+
+/// The bytes that an image programs, keyed by the address of each contiguous run.
+type Runs = BTreeMap<u64, Vec<u8>>;
+
+/// The format of a firmware image, which has no header to tell.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// The bytes to program from a base address, as `objcopy -O binary` writes them.
+    Raw,
+    /// Intel HEX, as `objcopy -O ihex` writes it.
+    Ihex,
+}
+
+/// A run of contiguous bytes that an image programs into flash.
+#[derive(Clone, Debug, Serialize)]
+pub struct Region {
+    pub address: u64,
+    pub size: u64,
+}
+
+/// A loaded section of the ELF file that an image was made from, and where it is in the image.
+#[derive(Clone, Debug, Serialize)]
+pub struct ImageSection {
+    pub name: String,
+    /// The address that the section is loaded from, which for initialized data isn't the
+    /// address that it runs at.
+    pub load_address: u64,
+    pub size: u64,
+    /// The bytes of the section that the image holds.
+    pub present: u64,
+    /// Whether the bytes that the image holds are those of the section.
+    pub matches: bool,
+}
+
+/// How the sections of an ELF file map to the image made from it.
+#[derive(Clone, Debug, Serialize)]
+pub struct CrossReference {
+    pub sections: Vec<ImageSection>,
+    /// The bytes of the image that no section accounts for, like the fill between sections.
+    pub unattributed: u64,
+    /// The bytes of sections that the image doesn't hold.
+    pub missing: u64,
+}
+
+/// The flash footprint of a firmware image, as emitted for `--format raw` and `--format ihex`.
+#[derive(Clone, Debug, Serialize)]
+pub struct FlashReport {
+    /// `raw` or `ihex`.
+    pub format: &'static str,
+    pub file_size: u64,
+    /// The bytes that the image programs.
+    pub flash_size: u64,
+    /// The bytes from the lowest address that the image programs to the highest, counting the
+    /// gaps between regions.
+    pub span: u64,
+    /// The start address of an Intel HEX image, if it has one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entry: Option<u64>,
+    pub regions: Vec<Region>,
+    /// The sections of the ELF file given with `--elf`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub elf: Option<CrossReference>,
+}
+
+/// Parse the address `s`, in hex with a `0x` prefix or in decimal.
+pub fn parse_address(s: &str) -> Result<u64, Error> {
+    let address = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(&hex.replace('_', ""), 16),
+        None => s.replace('_', "").parse(),
+    };
+    address.map_err(|_| UsageError(format!("Invalid address: {}", s)).into())
+}
+
+/// The bytes that the Intel HEX file `text` programs, and its start address.
+fn ihex(text: &str) -> Result<(Runs, Option<u64>), Error> {
+    let mut runs = Runs::new();
+    let mut base = 0;
+    let mut entry = None;
+    // The start of the run being added to, and the address that it ends at.
+    let mut run = (0, None);
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let digits = match line.strip_prefix(':') {
+            Some(digits) if digits.len() >= 10 && digits.len().is_multiple_of(2) => digits,
+            _ => bail!("Malformed Intel HEX record on line {}", i + 1),
+        };
+        let bytes = (0..digits.len()).step_by(2)
+            .map(|j| u8::from_str_radix(&digits[j..j + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| format_err!("Malformed Intel HEX record on line {}", i + 1))?;
+        let length = bytes[0] as usize;
+        if bytes.len() != length + 5 {
+            bail!("Intel HEX record on line {} has the wrong length", i + 1);
+        }
+        if bytes.iter().fold(0u8, |sum, &b| sum.wrapping_add(b)) != 0 {
+            bail!("Intel HEX record on line {} has a bad checksum", i + 1);
+        }
+        let offset = u64::from(u16::from_be_bytes([bytes[1], bytes[2]]));
+        let data = &bytes[4..4 + length];
+        let value = data.iter().fold(0u64, |value, &b| value << 8 | u64::from(b));
+        match bytes[3] {
+            IHEX_DATA => {
+                let address = base + offset;
+                if run.1 != Some(address) {
+                    run.0 = address;
+                }
+                runs.entry(run.0).or_default().extend_from_slice(data);
+                run.1 = Some(address + length as u64);
+            }
+            IHEX_END_OF_FILE => break,
+            IHEX_EXTENDED_SEGMENT_ADDRESS => base = value << 4,
+            IHEX_EXTENDED_LINEAR_ADDRESS => base = value << 16,
+            // `CS:IP`, or an address.
+            IHEX_START_SEGMENT_ADDRESS => entry = Some((value >> 16 << 4) + (value & 0xffff)),
+            IHEX_START_LINEAR_ADDRESS => entry = Some(value),
+            kind => bail!("Unknown Intel HEX record type {} on line {}", kind, i + 1),
+        }
+    }
+    // Records are usually in address order, but needn't be; join the runs that touch.
+    let mut joined = Runs::new();
+    for (address, run) in runs {
+        match joined.iter_mut().next_back() {
+            Some((&start, last)) if start + last.len() as u64 >= address => {
+                let overlap = (start + last.len() as u64 - address) as usize;
+                last.extend_from_slice(&run[cmp::min(overlap, run.len())..]);
+            }
+            _ => {
+                joined.insert(address, run);
+            }
+        }
+    }
+    Ok((joined, entry))
+}
+
+/// The loaded sections of the ELF file `elf`, with the addresses that they are loaded from: a
+/// section in a segment is loaded from the segment's physical address, where `objcopy` puts it
+/// in images.
+fn load_sections<'a>(elf: &'a Elf) -> Vec<(&'a str, u64, u64, u64)> {
+    let mut sections = Vec::new();
+    for sh in &elf.section_headers {
+        if sh.sh_flags & u64::from(SHF_ALLOC) == 0 || sh.sh_type == SHT_NOBITS || sh.sh_size == 0 {
+            continue;
+        }
+        let name = elf.shdr_strtab.get(sh.sh_name).and_then(|res| res.ok()).unwrap_or("");
+        let load_address = elf.program_headers.iter()
+            .find(|ph| {
+                ph.p_type == PT_LOAD && sh.sh_offset >= ph.p_offset &&
+                    sh.sh_offset + sh.sh_size <= ph.p_offset + ph.p_filesz
+            })
+            .map_or(sh.sh_addr, |ph| ph.p_paddr + sh.sh_offset - ph.p_offset);
+        sections.push((name, load_address, sh.sh_offset, sh.sh_size));
+    }
+    sections
+}
+
+/// Map the sections of the ELF file `elf` to the runs of the image `runs`.
+fn cross_reference(runs: &Runs, elf: &[u8]) -> Result<CrossReference, Error> {
+    let parsed = match Elf::parse(elf) {
+        Ok(parsed) => parsed,
+        Err(_) => return Err(UsageError("--elf needs an ELF file".to_string()).into()),
+    };
+    let mut report = CrossReference { sections: Vec::new(), unattributed: 0, missing: 0 };
+    let mut ranges = Vec::new();
+    for (name, load_address, offset, size) in load_sections(&parsed) {
+        let contents = elf.get(offset as usize..(offset + size) as usize).unwrap_or(&[]);
+        let mut section = ImageSection {
+            name: name.to_string(),
+            load_address,
+            size,
+            present: 0,
+            matches: true,
+        };
+        let end = load_address + size;
+        for (&start, run) in runs.range(..end) {
+            let from = cmp::max(start, load_address);
+            let to = cmp::min(start + run.len() as u64, end);
+            if from >= to {
+                continue;
+            }
+            section.present += to - from;
+            let image = &run[(from - start) as usize..(to - start) as usize];
+            let own = contents.get((from - load_address) as usize..(to - load_address) as usize);
+            section.matches &= own == Some(image);
+        }
+        section.matches &= section.present > 0;
+        report.missing += size - section.present;
+        ranges.push((load_address, end));
+        report.sections.push(section);
+    }
+    // The bytes of each run that the sections cover, counting overlapping sections once.
+    ranges.sort();
+    for (&start, run) in runs {
+        let end = start + run.len() as u64;
+        let (mut covered, mut last) = (0, start);
+        for &(from, to) in &ranges {
+            let (from, to) = (cmp::max(from, last), cmp::min(to, end));
+            if from < to {
+                covered += to - from;
+                last = to;
+            }
+        }
+        report.unattributed += run.len() as u64 - covered;
+    }
+    Ok(report)
+}
+
+/// Report the flash footprint of the firmware image `buf`, in the format `format`: the regions
+/// that it programs, from `base` for raw images, and with the ELF file `elf` that it was made
+/// from, the sections in each region.
+pub fn flash(buf: &[u8], format: Format, base: u64, elf: Option<&[u8]>)
+             -> Result<FlashReport, Error> {
+    let (runs, entry) = match format {
+        Format::Raw => {
+            let mut runs = Runs::new();
+            if !buf.is_empty() {
+                runs.insert(base, buf.to_vec());
+            }
+            (runs, None)
+        }
+        Format::Ihex => ihex(&String::from_utf8_lossy(buf))?,
+    };
+    let regions: Vec<Region> = runs.iter()
+        .map(|(&address, run)| Region { address, size: run.len() as u64 })
+        .collect();
+    let span = match (regions.first(), regions.last()) {
+        (Some(first), Some(last)) => last.address + last.size - first.address,
+        _ => 0,
+    };
+    Ok(FlashReport {
+        format: match format {
+            Format::Raw => "raw",
+            Format::Ihex => "ihex",
+        },
+        file_size: buf.len() as u64,
+        flash_size: regions.iter().map(|region| region.size).sum(),
+        span,
+        entry,
+        regions,
+        elf: match elf {
+            Some(elf) => Some(cross_reference(&runs, elf)?),
+            None => None,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::elf::section_header::{SHF_EXECINSTR, SHT_PROGBITS};
+    use testelf;
+
+    /// An Intel HEX record of type `kind` with `data` at `offset`.
+    fn record(kind: u8, offset: u16, data: &[u8]) -> String {
+        let mut bytes = vec![data.len() as u8];
+        bytes.extend_from_slice(&offset.to_be_bytes());
+        bytes.push(kind);
+        bytes.extend_from_slice(data);
+        let sum = bytes.iter().fold(0u8, |sum, &b| sum.wrapping_add(b));
+        bytes.push(sum.wrapping_neg());
+        let digits: String = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+        format!(":{}\n", digits)
+    }
+
+    #[test]
+    fn addresses() {
+        assert_eq!(parse_address("0x0800_0000").unwrap(), 0x800_0000);
+        assert_eq!(parse_address("0X10").unwrap(), 16);
+        assert_eq!(parse_address("4_096").unwrap(), 4096);
+        let err = parse_address("0xg").unwrap_err();
+        assert!(err.downcast_ref::<UsageError>().is_some(), "{}", err);
+    }
+
+    #[test]
+    fn intel_hex_records() {
+        let text = [
+            record(IHEX_EXTENDED_LINEAR_ADDRESS, 0, &[0x08, 0x00]),
+            record(IHEX_DATA, 0x10, &[3; 16]),
+            // Out of order, and touching the run after it.
+            record(IHEX_DATA, 0, &[1; 16]),
+            record(IHEX_DATA, 0x100, &[4; 4]),
+            record(IHEX_EXTENDED_SEGMENT_ADDRESS, 0, &[0x10, 0x00]),
+            record(IHEX_DATA, 0, &[5; 2]),
+            record(IHEX_START_LINEAR_ADDRESS, 0, &[0x08, 0x00, 0x01, 0x01]),
+            record(IHEX_END_OF_FILE, 0, &[]),
+            record(IHEX_DATA, 0x200, &[6; 2]),
+        ].concat();
+        let report = flash(text.as_bytes(), Format::Ihex, 0, None).unwrap();
+        let regions: Vec<_> = report.regions.iter().map(|r| (r.address, r.size)).collect();
+        assert_eq!(regions, vec![(0x1_0000, 2), (0x800_0000, 32), (0x800_0100, 4)]);
+        assert_eq!((report.flash_size, report.span), (38, 0x800_0104 - 0x1_0000));
+        assert_eq!(report.entry, Some(0x800_0101));
+        assert_eq!(report.format, "ihex");
+
+        let (runs, entry) = ihex(&record(IHEX_START_SEGMENT_ADDRESS, 0, &[0x10, 0, 0, 4]))
+            .unwrap();
+        assert!(runs.is_empty());
+        assert_eq!(entry, Some(0x1_0004));
+
+        let mut bad = record(IHEX_DATA, 0, &[1, 2]);
+        bad.replace_range(9..11, "03");
+        assert!(ihex(&bad).unwrap_err().to_string().contains("bad checksum"));
+        assert!(ihex("not hex\n").unwrap_err().to_string().contains("line 1"));
+        assert!(ihex(&record(7, 0, &[])).is_err());
+    }
+
+    #[test]
+    fn raw_images_and_their_elf_sections() {
+        let elf = testelf::Elf::executable()
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &[1; 16])
+            .section(".rodata", SHT_PROGBITS, SHF_ALLOC, &[2; 8])
+            .nobits(".bss", SHF_ALLOC, 64)
+            .build();
+        let parsed = Elf::parse(&elf).unwrap();
+        let sections = load_sections(&parsed);
+        let (text, rodata) = (sections[0].1, sections[1].1);
+        // The text, fill up to the read-only data, and the first half of it, wrong.
+        let mut image = vec![1; 16];
+        image.resize((rodata - text) as usize, 0xff);
+        image.extend_from_slice(&[9; 4]);
+
+        let report = flash(&image, Format::Raw, text, Some(&elf)).unwrap();
+        assert_eq!((report.flash_size, report.span), (image.len() as u64, image.len() as u64));
+        let elf = report.elf.unwrap();
+        let sections: Vec<_> = elf.sections.iter()
+            .map(|s| (&*s.name, s.load_address, s.size, s.present, s.matches))
+            .collect();
+        assert_eq!(sections, vec![(".text", text, 16, 16, true),
+                                  (".rodata", rodata, 8, 4, false)]);
+        assert_eq!((elf.unattributed, elf.missing), (rodata - text - 16, 4));
+
+        assert!(flash(&[], Format::Raw, 0, None).unwrap().regions.is_empty());
+        let err = flash(&image, Format::Raw, 0, Some(b"not an elf")).unwrap_err();
+        assert!(err.downcast_ref::<UsageError>().is_some(), "{}", err);
+    }
+}
@@ -0,0 +1,189 @@
+use failure::Error;
+use rustc_demangle;
+use std::collections::BTreeMap;
+use symbols;
+use thunks::KindSize;
+
+/// The primitive types that `core::fmt` formats itself.
+const INTEGERS: &[&str] = &["i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64",
+                            "u128", "usize"];
+const FLOATS: &[&str] = &["f16", "f32", "f64", "f128"];
+
+/// A function or table of the formatting machinery.
+#[derive(Clone, Debug, Serialize)]
+pub struct FmtSymbol {
+    /// The demangled name, without the hash.
+    pub name: String,
+    /// See `kind`.
+    pub kind: &'static str,
+    pub size: u64,
+}
+
+/// What the formatting machinery of a binary costs, as emitted by `fmt --format json`.
+#[derive(Clone, Debug, Serialize)]
+pub struct FmtReport {
+    pub kinds: BTreeMap<&'static str, KindSize>,
+    /// The size of the formatting code and tables.
+    pub size: u64,
+    /// The size of all functions and data symbols, counting aliases once.
+    pub symbol_size: u64,
+    /// What building with `-Zfmt-debug=none`, or not deriving `Debug`, would save: the size
+    /// of the `Debug` impls.
+    pub debug_savings: u64,
+    /// What formatting with `ufmt` instead would save, roughly: the size of the machinery,
+    /// float formatting and tables, which it doesn't have. Its integer formatting and the
+    /// impls, which would be ported to `uDisplay` and `uDebug`, are taken to cost the same.
+    pub ufmt_savings: u64,
+    /// The symbols, largest first. The pieces of format strings are anonymous constants, so
+    /// they aren't included.
+    pub symbols: Vec<FmtSymbol>,
+}
+
+/// The type that the demangled trait method `name` is implemented for, if it is a method of a
+/// `core::fmt` trait: `T` in `<T as core::fmt::Debug>::fmt` or, with legacy mangling,
+/// `core::fmt::num::<impl core::fmt::Debug for T>::fmt`, with the trait.
+fn fmt_impl(name: &str) -> Option<(&str, &str)> {
+    if let Some(i) = name.rfind(" as core::fmt::") {
+        let rest = &name[i + " as core::fmt::".len()..];
+        let trait_name = &rest[..rest.find(['>', ':', '<']).unwrap_or(rest.len())];
+        return Some((name[..i].strip_prefix('<').unwrap_or(&name[..i]), trait_name));
+    }
+    let i = name.find("<impl core::fmt::")?;
+    let rest = &name[i + "<impl core::fmt::".len()..];
+    let (trait_name, rest) = rest.split_once(" for ")?;
+    Some((&rest[..rest.rfind('>')?], trait_name))
+}
+
+/// The part of the formatting machinery that the demangled symbol `name` is: `float` and
+/// `integer` for the formatting of numbers, `debug-impls` and `display-impls` for the impls of
+/// `Debug` and of the other formatting traits, `machinery` for the `Formatter`, the builders
+/// and the `Write` adapters that they write through, and `tables` for their data.
+fn kind(name: &str, code: bool) -> Option<&'static str> {
+    if !code {
+        let tables = name.starts_with("core::fmt::") || name.contains("::flt2dec::");
+        return if tables { Some("tables") } else { None };
+    }
+    if name.contains("core::fmt::float") || name.contains("::flt2dec::") {
+        return Some("float");
+    }
+    if name.contains("core::fmt::num::") {
+        return Some("integer");
+    }
+    match fmt_impl(name) {
+        Some((ty, _)) if FLOATS.contains(&ty) => Some("float"),
+        Some((ty, _)) if INTEGERS.contains(&ty) => Some("integer"),
+        Some((ty, _)) if ty.starts_with("core::fmt::") => Some("machinery"),
+        Some((_, "Write")) => Some("machinery"),
+        Some((_, "Debug")) => Some("debug-impls"),
+        Some(_) => Some("display-impls"),
+        None if name.starts_with("core::fmt::") || name.starts_with("<core::fmt::") ||
+            name.starts_with("alloc::fmt::format") || name.contains("::write_fmt") => {
+            Some("machinery")
+        }
+        None => None,
+    }
+}
+
+/// Sum the sizes of the formatting machinery of the Rust binary `buf`, by what each part of it
+/// does, and estimate what doing without `Debug` and formatting with `ufmt` would save.
+pub fn fmt(buf: &[u8]) -> Result<FmtReport, Error> {
+    let mut syms = symbols::symbols(buf)?;
+    // Aliases share their symbol's address; keep the largest of each.
+    syms.sort_by(|a, b| {
+        (&a.section, a.address, b.size, &a.name).cmp(&(&b.section, b.address, a.size, &b.name))
+    });
+    syms.dedup_by(|a, b| a.section == b.section && a.address == b.address);
+
+    let mut report = FmtReport {
+        kinds: BTreeMap::new(),
+        size: 0,
+        symbol_size: syms.iter().map(|sym| sym.size).sum(),
+        debug_savings: 0,
+        ufmt_savings: 0,
+        symbols: Vec::new(),
+    };
+    for sym in syms {
+        let name = format!("{:#}", rustc_demangle::demangle(&sym.name));
+        let kind = match kind(&name, sym.code) {
+            Some(kind) => kind,
+            None => continue,
+        };
+        let totals = report.kinds.entry(kind).or_default();
+        totals.count += 1;
+        totals.size += sym.size;
+        report.size += sym.size;
+        match kind {
+            "debug-impls" => report.debug_savings += sym.size,
+            "machinery" | "float" | "tables" => report.ufmt_savings += sym.size,
+            _ => {}
+        }
+        report.symbols.push(FmtSymbol { name, kind, size: sym.size });
+    }
+    report.symbols.sort_by(|a, b| (b.size, &a.name).cmp(&(a.size, &b.name)));
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::elf::section_header::{SHF_ALLOC, SHF_EXECINSTR, SHT_PROGBITS};
+    use goblin::elf::sym::{STT_FUNC, STT_OBJECT};
+    use testelf::Elf;
+
+    #[test]
+    fn fmt_impls_with_either_mangling() {
+        assert_eq!(fmt_impl("<app::Point as core::fmt::Debug>::fmt"),
+                   Some(("app::Point", "Debug")));
+        assert_eq!(fmt_impl("<&T as core::fmt::Display>::fmt"), Some(("&T", "Display")));
+        assert_eq!(fmt_impl("core::fmt::num::<impl core::fmt::LowerHex for u32>::fmt"),
+                   Some(("u32", "LowerHex")));
+        assert_eq!(fmt_impl("core::fmt::Formatter::pad"), None);
+    }
+
+    #[test]
+    fn kinds_by_name() {
+        assert_eq!(kind("core::fmt::float::float_to_decimal_common_exact", true), Some("float"));
+        assert_eq!(kind("core::num::flt2dec::strategy::grisu::format_shortest", true),
+                   Some("float"));
+        assert_eq!(kind("<f64 as core::fmt::Display>::fmt", true), Some("float"));
+        assert_eq!(kind("core::fmt::num::imp::fmt_u64", true), Some("integer"));
+        assert_eq!(kind("<usize as core::fmt::Debug>::fmt", true), Some("integer"));
+        assert_eq!(kind("<core::fmt::Arguments as core::fmt::Display>::fmt", true),
+                   Some("machinery"));
+        assert_eq!(kind("<alloc::string::String as core::fmt::Write>::write_str", true),
+                   Some("machinery"));
+        assert_eq!(kind("core::fmt::write", true), Some("machinery"));
+        assert_eq!(kind("std::io::Write::write_fmt", true), Some("machinery"));
+        assert_eq!(kind("<app::Point as core::fmt::Debug>::fmt", true), Some("debug-impls"));
+        assert_eq!(kind("<app::Point as core::fmt::Display>::fmt", true), Some("display-impls"));
+        assert_eq!(kind("app::main", true), None);
+        assert_eq!(kind("core::fmt::num::DEC_DIGITS_LUT", false), Some("tables"));
+        assert_eq!(kind("app::TABLE", false), None);
+    }
+
+    #[test]
+    fn savings_of_a_binary() {
+        let buf = Elf::object()
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &[0; 64])
+            .section(".rodata", SHT_PROGBITS, SHF_ALLOC, &[0; 16])
+            .symbol("main", STT_FUNC, ".text", 0, 8)
+            .symbol("core::fmt::write", STT_FUNC, ".text", 8, 16)
+            // An alias counts once.
+            .symbol("core::fmt::write_alias", STT_FUNC, ".text", 8, 16)
+            .symbol("core::fmt::num::imp::fmt_u64", STT_FUNC, ".text", 24, 8)
+            .symbol("<app::Point as core::fmt::Debug>::fmt", STT_FUNC, ".text", 32, 12)
+            .symbol("<app::Point as core::fmt::Display>::fmt", STT_FUNC, ".text", 44, 4)
+            .symbol("core::fmt::float::float_to_exponential", STT_FUNC, ".text", 48, 16)
+            .symbol("core::fmt::num::DEC_DIGITS_LUT", STT_OBJECT, ".rodata", 0, 10)
+            .build();
+        let report = fmt(&buf).unwrap();
+        assert_eq!((report.size, report.symbol_size), (66, 74));
+        assert_eq!(report.debug_savings, 12);
+        assert_eq!(report.ufmt_savings, 16 + 16 + 10);
+        let kinds: Vec<_> = report.kinds.iter().map(|(&k, s)| (k, s.count, s.size)).collect();
+        assert_eq!(kinds, vec![("debug-impls", 1, 12), ("display-impls", 1, 4), ("float", 1, 16),
+                               ("integer", 1, 8), ("machinery", 1, 16), ("tables", 1, 10)]);
+        assert_eq!(report.symbols[0].kind, "float");
+        assert_eq!(report.symbols.len(), 6);
+    }
+}
@@ -0,0 +1,326 @@
+use failure::Error;
+use rustc_demangle;
+use std::collections::BTreeMap;
+use symbols::{self, Symbol};
+use thunks::{self, KindSize};
+
+/// The memory functions that compilers call for copies, moves and fills instead of emitting
+/// the loops, and that they expand small ones of inline.
+const MEMORY_INTRINSICS: &[&str] = &["memcpy", "memmove", "memset", "memcmp", "bcmp", "strlen",
+                                     "__memcpy_chk", "__memmove_chk", "__memset_chk"];
+
+/// The prefixes of the compiler-rt and libgcc functions that compilers call for arithmetic the
+/// target has no instructions for. Their names end in the mode of their operands and their
+/// number, like `__addsf3`, which tells them from libc functions like `__add_to_environ`.
+const BUILTIN_PREFIXES: &[&str] = &["__udiv", "__div", "__umod", "__mod", "__mul", "__ashl",
+                                    "__ashr", "__lshr", "__float", "__fix", "__extend",
+                                    "__trunc", "__add", "__sub", "__neg", "__cmp", "__ucmp",
+                                    "__clz", "__ctz", "__popcount", "__bswap", "__pow"];
+
+/// The prefixes of the ARM EABI helpers, and the probes that compilers call to grow the stack.
+const HELPER_PREFIXES: &[&str] = &["__aeabi_", "__rust_probestack", "__chkstk", "___chkstk",
+                                   "__probestack"];
+
+/// The functions of the allocator shim that rustc generates to forward the global allocator.
+const ALLOCATOR_SHIMS: &[&str] = &["__rust_alloc", "__rust_dealloc", "__rust_realloc",
+                                   "__rust_alloc_zeroed", "__rust_alloc_error_handler",
+                                   "__rg_alloc", "__rg_dealloc", "__rg_realloc",
+                                   "__rg_alloc_zeroed", "__rg_oom"];
+
+/// A function that the compiler generated, rather than one written in the source.
+#[derive(Clone, Debug, Serialize)]
+pub struct GeneratedFunction {
+    /// The demangled name, without the hash.
+    pub name: String,
+    /// See `kind`.
+    pub kind: &'static str,
+    pub size: u64,
+}
+
+/// The compiler-generated functions of a binary, as emitted by `generated --format json`.
+#[derive(Clone, Debug, Serialize)]
+pub struct GeneratedReport {
+    pub kinds: BTreeMap<&'static str, KindSize>,
+    /// The size of the compiler-generated functions.
+    pub size: u64,
+    /// The size of all functions, counting aliases once.
+    pub code_size: u64,
+    /// The size of the functions written in the source: `code_size` less `size`.
+    pub user_size: u64,
+    /// The functions, largest first.
+    pub functions: Vec<GeneratedFunction>,
+}
+
+/// The kind of the function `name`, if the compiler generated it: `drop-glue` for the
+/// instantiations of `core::ptr::drop_in_place`, `shim` for Rust's vtable and reify shims,
+/// the `Fn*::call*` adapters of functions and closures and the allocator shim, `intrinsic` for
+/// memory functions and compiler-rt builtins, and `outlined`, `thunk` or `trampoline` as
+/// `thunks` classifies them.
+pub fn kind(name: &str) -> Option<&'static str> {
+    let unprefixed = name.strip_prefix('_').unwrap_or(name);
+    for name in &[name, unprefixed] {
+        if MEMORY_INTRINSICS.contains(name) ||
+            BUILTIN_PREFIXES.iter().any(|prefix| {
+                name.starts_with(prefix) && name.ends_with(|c: char| c.is_ascii_digit())
+            }) ||
+            HELPER_PREFIXES.iter().any(|prefix| name.starts_with(prefix)) {
+            return Some("intrinsic");
+        }
+        if ALLOCATOR_SHIMS.contains(name) {
+            return Some("shim");
+        }
+    }
+    let demangled = format!("{:#}", rustc_demangle::demangle(name));
+    if demangled.starts_with("core::ptr::drop_in_place") {
+        return Some("drop-glue");
+    }
+    if demangled.starts_with("compiler_builtins::") {
+        return Some("intrinsic");
+    }
+    let adapters = ["core::ops::function::FnOnce::call_once",
+                    "core::ops::function::FnMut::call_mut", "core::ops::function::Fn::call"];
+    if demangled.contains(".shim}") || demangled.contains("{shim:") ||
+        adapters.iter().any(|adapter| demangled.starts_with(adapter)) {
+        return Some("shim");
+    }
+    thunks::kind(name)
+}
+
+/// The functions of `buf` with their kinds, counting aliases once.
+fn functions(buf: &[u8]) -> Result<Vec<(Option<&'static str>, Symbol)>, Error> {
+    let mut functions: Vec<_> = symbols::symbols(buf)?.into_iter()
+        .filter(|sym| sym.code)
+        .map(|sym| (kind(&sym.name), sym))
+        .collect();
+    // Aliases share their function's address; keep the largest of each, preferring a name that
+    // says what the function is.
+    functions.sort_by(|&(a_kind, ref a), &(b_kind, ref b)| {
+        (&a.section, a.address, b.size, b_kind.is_some(), &a.name)
+            .cmp(&(&b.section, b.address, a.size, a_kind.is_some(), &b.name))
+    });
+    functions.dedup_by(|a, b| a.1.section == b.1.section && a.1.address == b.1.address);
+    Ok(functions)
+}
+
+/// Find the functions of `buf` that the compiler generated, by their names, and sum their
+/// sizes by kind.
+pub fn generated(buf: &[u8]) -> Result<GeneratedReport, Error> {
+    let functions = functions(buf)?;
+    let code_size = functions.iter().map(|(_, sym)| sym.size).sum();
+    let mut report = GeneratedReport {
+        kinds: BTreeMap::new(),
+        size: 0,
+        code_size,
+        user_size: code_size,
+        functions: Vec::new(),
+    };
+    for (kind, sym) in functions {
+        let kind = match kind {
+            Some(kind) => kind,
+            None => continue,
+        };
+        let totals = report.kinds.entry(kind).or_default();
+        totals.count += 1;
+        totals.size += sym.size;
+        report.size += sym.size;
+        report.user_size -= sym.size;
+        report.functions.push(GeneratedFunction {
+            name: format!("{:#}", rustc_demangle::demangle(&sym.name)),
+            kind,
+            size: sym.size,
+        });
+    }
+    report.functions.sort_by(|a, b| (b.size, &a.name).cmp(&(a.size, &b.name)));
+    Ok(report)
+}
+
+/// The drop glue of one type.
+#[derive(Clone, Debug, Serialize)]
+pub struct DropGlue {
+    /// The type, or with `--group-by base`, the type without its generic arguments.
+    pub name: String,
+    /// The instantiations of `drop_in_place`, which can be more than one per type when
+    /// several codegen units each have their own.
+    pub count: u64,
+    pub size: u64,
+    /// How deeply generic arguments nest in the type, or the deepest of the types of a base.
+    pub depth: u64,
+}
+
+/// The drop glue of a binary by type, as emitted by `drop-glue --format json`.
+#[derive(Clone, Debug, Serialize)]
+pub struct DropGlueReport {
+    pub count: u64,
+    pub size: u64,
+    /// The size of all functions, counting aliases once.
+    pub code_size: u64,
+    /// The types, largest first.
+    pub types: Vec<DropGlue>,
+}
+
+/// The type that the demangled `core::ptr::drop_in_place` instantiation `name` drops: legacy
+/// names are `core::ptr::drop_in_place<T>`, and v0 names `core::ptr::drop_in_place::<T>`.
+fn dropped_type(name: &str) -> Option<&str> {
+    let rest = name.strip_prefix("core::ptr::drop_in_place")?;
+    let rest = rest.strip_prefix("::").unwrap_or(rest);
+    rest.strip_prefix('<')?.strip_suffix('>')
+}
+
+/// The type `ty` without its generic arguments: `alloc::vec::Vec` for
+/// `alloc::vec::Vec<alloc::string::String>`. Tuples, references and the like are kept whole.
+fn base_type(ty: &str) -> &str {
+    match ty.find('<') {
+        Some(i) if i > 0 && !ty.starts_with(['(', '[', '&', '*', '<']) => &ty[..i],
+        _ => ty,
+    }
+}
+
+/// How deeply the generic arguments of `ty` nest.
+fn depth(ty: &str) -> u64 {
+    let (mut depth, mut deepest) = (0u64, 0);
+    for c in ty.chars() {
+        match c {
+            '<' => {
+                depth += 1;
+                deepest = deepest.max(depth);
+            }
+            '>' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    deepest
+}
+
+/// Sum the instantiations of `core::ptr::drop_in_place` in `buf` by the type that they drop,
+/// or with `base`, by that type without its generic arguments.
+pub fn drop_glue(buf: &[u8], base: bool) -> Result<DropGlueReport, Error> {
+    let functions = functions(buf)?;
+    let mut report = DropGlueReport {
+        count: 0,
+        size: 0,
+        code_size: functions.iter().map(|(_, sym)| sym.size).sum(),
+        types: Vec::new(),
+    };
+    let mut types: BTreeMap<String, DropGlue> = BTreeMap::new();
+    for (kind, sym) in functions {
+        if kind != Some("drop-glue") {
+            continue;
+        }
+        let name = format!("{:#}", rustc_demangle::demangle(&sym.name));
+        let ty = dropped_type(&name).unwrap_or(&name);
+        let key = if base { base_type(ty) } else { ty };
+        let glue = types.entry(key.to_string()).or_insert_with(|| DropGlue {
+            name: key.to_string(),
+            count: 0,
+            size: 0,
+            depth: 0,
+        });
+        glue.count += 1;
+        glue.size += sym.size;
+        glue.depth = glue.depth.max(depth(ty));
+        report.count += 1;
+        report.size += sym.size;
+    }
+    report.types = types.into_values().collect();
+    report.types.sort_by(|a, b| (b.size, &a.name).cmp(&(a.size, &b.name)));
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::elf::section_header::{SHF_ALLOC, SHF_EXECINSTR, SHT_PROGBITS};
+    use goblin::elf::sym::STT_FUNC;
+    use testelf::Elf;
+
+    /// The legacy Rust mangling of `path`, whose components are already escaped.
+    fn mangled(path: &[&str]) -> String {
+        let components: String = path.iter().map(|c| format!("{}{}", c.len(), c)).collect();
+        format!("_ZN{}17h0123456789abcdefE", components)
+    }
+
+    fn drop_in_place(ty: &str) -> String {
+        mangled(&["core", "ptr", &format!("drop_in_place$LT${}$GT$", ty)])
+    }
+
+    #[test]
+    fn kinds_by_name() {
+        assert_eq!(kind("memcpy"), Some("intrinsic"));
+        assert_eq!(kind("_memset"), Some("intrinsic"));
+        assert_eq!(kind("__udivti3"), Some("intrinsic"));
+        assert_eq!(kind("__aeabi_uidiv"), Some("intrinsic"));
+        assert_eq!(kind("__rust_probestack"), Some("intrinsic"));
+        assert_eq!(kind(&mangled(&["compiler_builtins", "int", "udiv", "__udivti3"])),
+                   Some("intrinsic"));
+        // Not a builtin: libc's, without the number.
+        assert_eq!(kind("__add_to_environ"), None);
+        assert_eq!(kind("__rust_alloc"), Some("shim"));
+        assert_eq!(kind(&mangled(&["core", "ops", "function", "FnOnce", "call_once"])),
+                   Some("shim"));
+        assert_eq!(kind(&drop_in_place("u8")), Some("drop-glue"));
+        assert_eq!(kind("OUTLINED_FUNCTION_3"), Some("outlined"));
+        assert_eq!(kind(&mangled(&["app", "main"])), None);
+    }
+
+    #[test]
+    fn generated_functions_by_kind() {
+        let buf = Elf::object()
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &[0; 64])
+            .symbol("main", STT_FUNC, ".text", 0, 32)
+            .symbol(&drop_in_place("alloc..string..String"), STT_FUNC, ".text", 32, 16)
+            // An alias whose name says what the function is wins over one that doesn't.
+            .symbol("copy", STT_FUNC, ".text", 48, 8)
+            .symbol("memcpy", STT_FUNC, ".text", 48, 8)
+            .symbol("__muldi3", STT_FUNC, ".text", 56, 8)
+            .build();
+        let report = generated(&buf).unwrap();
+        assert_eq!((report.code_size, report.size, report.user_size), (64, 32, 32));
+        let kinds: Vec<_> = report.kinds.iter().map(|(&k, s)| (k, s.count, s.size)).collect();
+        assert_eq!(kinds, vec![("drop-glue", 1, 16), ("intrinsic", 2, 16)]);
+        let functions: Vec<_> = report.functions.iter().map(|f| (&*f.name, f.kind)).collect();
+        assert_eq!(functions, vec![("core::ptr::drop_in_place<alloc::string::String>", "drop-glue"),
+                                   ("__muldi3", "intrinsic"), ("memcpy", "intrinsic")]);
+    }
+
+    #[test]
+    fn dropped_types_their_bases_and_depths() {
+        assert_eq!(dropped_type("core::ptr::drop_in_place<alloc::string::String>"),
+                   Some("alloc::string::String"));
+        assert_eq!(dropped_type("core::ptr::drop_in_place::<(u8, alloc::vec::Vec<u8>)>"),
+                   Some("(u8, alloc::vec::Vec<u8>)"));
+        assert_eq!(dropped_type("core::ptr::read<u8>"), None);
+        assert_eq!(base_type("alloc::vec::Vec<alloc::string::String>"), "alloc::vec::Vec");
+        assert_eq!(base_type("(u8, alloc::vec::Vec<u8>)"), "(u8, alloc::vec::Vec<u8>)");
+        assert_eq!(base_type("&mut alloc::vec::Vec<u8>"), "&mut alloc::vec::Vec<u8>");
+        assert_eq!(base_type("<T as Trait>::Output"), "<T as Trait>::Output");
+        assert_eq!(depth("u8"), 0);
+        assert_eq!(depth("Option<Vec<u8>>"), 2);
+        assert_eq!(depth("HashMap<Vec<u8>, Box<Option<Rc<u8>>>>"), 4);
+    }
+
+    #[test]
+    fn drop_glue_by_type_or_base() {
+        let buf = Elf::object()
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &[0; 64])
+            .symbol("main", STT_FUNC, ".text", 0, 8)
+            .symbol(&drop_in_place("alloc..vec..Vec$LT$u8$GT$"), STT_FUNC, ".text", 8, 8)
+            .symbol(&drop_in_place("alloc..vec..Vec$LT$alloc..vec..Vec$LT$u8$GT$$GT$"),
+                    STT_FUNC, ".text", 16, 24)
+            .symbol(&drop_in_place("alloc..string..String"), STT_FUNC, ".text", 40, 16)
+            .build();
+        let report = drop_glue(&buf, false).unwrap();
+        assert_eq!((report.count, report.size, report.code_size), (3, 48, 56));
+        let types: Vec<_> = report.types.iter()
+            .map(|t| (&*t.name, t.count, t.size, t.depth))
+            .collect();
+        assert_eq!(types, vec![("alloc::vec::Vec<alloc::vec::Vec<u8>>", 1, 24, 2),
+                               ("alloc::string::String", 1, 16, 0),
+                               ("alloc::vec::Vec<u8>", 1, 8, 1)]);
+
+        let report = drop_glue(&buf, true).unwrap();
+        let types: Vec<_> = report.types.iter()
+            .map(|t| (&*t.name, t.count, t.size, t.depth))
+            .collect();
+        assert_eq!(types, vec![("alloc::vec::Vec", 2, 32, 2), ("alloc::string::String", 1, 16, 0)]);
+    }
+}
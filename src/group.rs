@@ -0,0 +1,314 @@
+use components::{self, Components};
+use dwarf::{self, UnitRange};
+use exit::UsageError;
+use failure::Error;
+use generated;
+use labels::Labels;
+use owners::Owners;
+use rustc_demangle;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use symbols::{self, Symbol};
+
+/// The crate of symbols that aren't Rust or C++, or are outside any namespace.
+pub const NO_CRATE: &str = "(no crate)";
+
+/// The dimension symbol sizes are rolled up by, with `--group-by`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GroupBy {
+    /// The Rust crate or C++ top-level namespace the symbol belongs to.
+    Crate,
+    /// The section the symbol is in.
+    Section,
+    /// The source file of the compile unit containing the symbol, from DWARF debug info.
+    SourceFile,
+    /// The language the symbol was compiled from, going by its mangling.
+    Language,
+    /// The team or component label given by a labels file (see `labels::Labels`).
+    Label,
+    /// The owners of the symbol's source file, from a CODEOWNERS file (see `owners::Owners`).
+    Owner,
+    /// The project component that claims the symbol, from a components file (see
+    /// `components::Components`).
+    Component,
+    /// Whether the compiler generated the symbol, like drop glue and shims (see
+    /// `generated::kind`), or it was written in the source.
+    Origin,
+    /// No grouping: every symbol on its own.
+    None,
+}
+
+impl FromStr for GroupBy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<GroupBy, Error> {
+        match s {
+            "crate" => Ok(GroupBy::Crate),
+            "section" => Ok(GroupBy::Section),
+            "source-file" => Ok(GroupBy::SourceFile),
+            "language" => Ok(GroupBy::Language),
+            "label" => Ok(GroupBy::Label),
+            "owner" => Ok(GroupBy::Owner),
+            "component" => Ok(GroupBy::Component),
+            "origin" => Ok(GroupBy::Origin),
+            "none" => Ok(GroupBy::None),
+            _ => Err(UsageError(format!("Invalid grouping: {}", s)).into()),
+        }
+    }
+}
+
+/// Mach-O prepends an underscore to every symbol name; return `name` without it.
+fn strip_mach_underscore(name: &str) -> &str {
+    match name.strip_prefix('_') {
+        Some(rest) if rest.starts_with("_Z") || rest.starts_with("_R") ||
+            rest.starts_with("$s") => rest,
+        _ => name,
+    }
+}
+
+/// Whether `name` is mangled the way rustc mangles symbols, either as `_R...` (v0) or as a legacy
+/// `_ZN...17h<hash>E`, which would otherwise look like C++.
+fn is_rust(name: &str) -> bool {
+    if name.starts_with("_R") {
+        return true;
+    }
+    match name.strip_suffix('E').and_then(|n| n.get(n.len().saturating_sub(19)..)) {
+        Some(hash) => {
+            name.starts_with("_ZN") && hash.starts_with("17h") &&
+                hash[3..].bytes().all(|b| b.is_ascii_hexdigit())
+        }
+        None => false,
+    }
+}
+
+/// The language `name` was compiled from, going by its mangling. Unmangled names are taken to be
+/// C (or assembly).
+pub fn language(name: &str) -> &'static str {
+    let name = strip_mach_underscore(name);
+    if is_rust(name) {
+        "Rust"
+    } else if name.starts_with("_Z") {
+        "C++"
+    } else if name.starts_with("$s") {
+        "Swift"
+    } else if name.starts_with("-[") || name.starts_with("+[") {
+        "Objective-C"
+    } else {
+        "C"
+    }
+}
+
+/// The crate of the demangled Rust path `path`. For trait impls like `<a::B as c::D>::f`, this
+/// is the crate of the implementing type.
+pub fn rust_crate(path: &str) -> Option<&str> {
+    let mut path = path.trim_start_matches(['<', '&', '*', '[', '(']);
+    for prefix in &["mut ", "const ", "dyn "] {
+        path = path.strip_prefix(prefix).unwrap_or(path);
+    }
+    let end = path.find(|c: char| !(c.is_alphanumeric() || c == '_'))?;
+    if path[end..].starts_with("::") {
+        Some(&path[..end])
+    } else {
+        None
+    }
+}
+
+/// The first component of the Itanium-mangled nested name `name` (`_ZN7mozilla3FooE`), which is
+/// its top-level namespace or class.
+fn cpp_namespace(name: &str) -> Option<&str> {
+    let rest = name.strip_prefix("_ZN")?.trim_start_matches(['K', 'V', 'r']);
+    if rest.starts_with("St") {
+        return Some("std");
+    }
+    let digits = rest.find(|c: char| !c.is_ascii_digit())?;
+    let len: usize = rest[..digits].parse().ok()?;
+    rest.get(digits..digits + len)
+}
+
+/// The Rust crate or C++ top-level namespace of `sym`, going by its mangled name.
+fn symbol_crate(sym: &Symbol) -> Option<String> {
+    let name = strip_mach_underscore(&sym.name);
+    if is_rust(name) {
+        rustc_demangle::try_demangle(name).ok()
+            .and_then(|d| rust_crate(&format!("{:#}", d)).map(str::to_string))
+    } else {
+        cpp_namespace(name).map(str::to_string)
+    }
+}
+
+/// The rules files that some groupings need.
+#[derive(Default)]
+pub struct Rules {
+    /// The labels, for `GroupBy::Label`.
+    pub labels: Option<Labels>,
+    /// The ownership rules, for `GroupBy::Owner`.
+    pub owners: Option<Owners>,
+    /// The project's components, for `GroupBy::Component`.
+    pub components: Option<Components>,
+}
+
+/// Assigns symbols to groups.
+pub struct Grouper<'a> {
+    by: GroupBy,
+    /// The compile unit ranges, for `GroupBy::SourceFile`, `GroupBy::Owner` and components with
+    /// paths.
+    units: Vec<UnitRange>,
+    rules: &'a Rules,
+}
+
+impl<'a> Grouper<'a> {
+    /// A grouper for the symbols of `buf`.
+    pub fn new(buf: &[u8], by: GroupBy, rules: &'a Rules) -> Result<Grouper<'a>, Error> {
+        if by == GroupBy::Label && rules.labels.is_none() {
+            return Err(UsageError("--group-by label needs --labels".to_string()).into());
+        }
+        if by == GroupBy::Owner && rules.owners.is_none() {
+            return Err(UsageError("--group-by owner needs --owners".to_string()).into());
+        }
+        if by == GroupBy::Component && rules.components.is_none() {
+            return Err(UsageError(format!("--group-by component needs --components or a {}",
+                                          components::FILE_NAME)).into());
+        }
+        let units = if by == GroupBy::SourceFile || by == GroupBy::Owner {
+            let units = dwarf::compile_unit_ranges(buf)?;
+            if units.is_empty() {
+                bail!("Grouping by source file or owner needs DWARF debug info");
+            }
+            units
+        } else if by == GroupBy::Component && rules.components.as_ref().unwrap().has_paths() {
+            // Components can claim symbols by crate and name too, so they don't need debug info.
+            dwarf::compile_unit_ranges(buf)?
+        } else {
+            Vec::new()
+        };
+        Ok(Grouper { by, units, rules })
+    }
+
+    /// The name of the group `sym` belongs to.
+    pub fn group(&self, sym: &Symbol) -> String {
+        match self.by {
+            GroupBy::Crate => symbol_crate(sym).unwrap_or_else(|| NO_CRATE.to_string()),
+            GroupBy::Section => sym.section.clone().unwrap_or_else(|| "(unknown)".to_string()),
+            GroupBy::SourceFile => match dwarf::unit_at(&self.units, sym.address) {
+                Some(unit) => unit.name.clone(),
+                None => "(no debug info)".to_string(),
+            },
+            GroupBy::Language => language(&sym.name).to_string(),
+            GroupBy::Label => self.rules.labels.as_ref().unwrap().label(sym).to_string(),
+            GroupBy::Owner => match dwarf::unit_at(&self.units, sym.address) {
+                Some(unit) => self.rules.owners.as_ref().unwrap().owner(&unit.name).to_string(),
+                None => "(no debug info)".to_string(),
+            },
+            GroupBy::Component => {
+                let name = format!("{:#}", rustc_demangle::demangle(&sym.name));
+                let unit = dwarf::unit_at(&self.units, sym.address);
+                self.rules.components.as_ref().unwrap()
+                    .component(&name, symbol_crate(sym).as_deref(),
+                               unit.map(|unit| unit.name.as_str()))
+                    .to_string()
+            }
+            GroupBy::Origin if sym.code && generated::kind(&sym.name).is_some() => {
+                "compiler-generated".to_string()
+            }
+            GroupBy::Origin => "user code".to_string(),
+            GroupBy::None => sym.name.clone(),
+        }
+    }
+}
+
+/// Sum the sizes of the symbols in `buf` by the group `by` assigns them to, inferring the sizes
+/// of unsized symbols with `infer_sizes` (see `symbols::symbols_inferring`).
+pub fn group_sizes(buf: &[u8], by: GroupBy, rules: &Rules, infer_sizes: bool)
+                   -> Result<BTreeMap<String, u64>, Error> {
+    let grouper = Grouper::new(buf, by, rules)?;
+    let mut sizes = BTreeMap::new();
+    symbols::for_each_symbol(buf, infer_sizes, |sym| {
+        *sizes.entry(grouper.group(&sym)).or_insert(0) += sym.size;
+    })?;
+    Ok(sizes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::elf::section_header::{SHF_ALLOC, SHF_EXECINSTR, SHT_PROGBITS};
+    use goblin::elf::sym::STT_FUNC;
+    use testelf::Elf;
+
+    const RUST_FMT: &str = "_ZN4core3fmt5write17h0123456789abcdefE";
+
+    #[test]
+    fn rust_manglings() {
+        assert!(is_rust(RUST_FMT));
+        assert!(is_rust("_RNvCs1234_7mycrate3foo"));
+        assert!(!is_rust("_ZN7mozilla3FooE"));
+        // Not a hash: too short, or not hex.
+        assert!(!is_rust("_ZN3foo17h0123E"));
+        assert!(!is_rust("_ZN3foo17h0123456789abcdegE"));
+        assert!(!is_rust("E"));
+        assert_eq!(strip_mach_underscore("__ZN3fooE"), "_ZN3fooE");
+        assert_eq!(strip_mach_underscore("__RNvC3foo3bar"), "_RNvC3foo3bar");
+        assert_eq!(strip_mach_underscore("_main"), "_main");
+    }
+
+    #[test]
+    fn crates_of_rust_paths() {
+        assert_eq!(rust_crate("core::fmt::write"), Some("core"));
+        assert_eq!(rust_crate("<alloc::vec::Vec<T> as core::ops::Drop>::drop"), Some("alloc"));
+        assert_eq!(rust_crate("<&mut serde_json::Serializer as serde::Serializer>::f"),
+                   Some("serde_json"));
+        assert_eq!(rust_crate("<*const [u8] as core::fmt::Debug>::fmt"), None);
+        assert_eq!(rust_crate("<dyn std::any::Any>::is"), Some("std"));
+        assert_eq!(rust_crate("main"), None);
+    }
+
+    #[test]
+    fn cpp_namespaces() {
+        assert_eq!(cpp_namespace("_ZN7mozilla3dom4NodeC2Ev"), Some("mozilla"));
+        assert_eq!(cpp_namespace("_ZNK7mozilla3Foo3getEv"), Some("mozilla"));
+        assert_eq!(cpp_namespace("_ZNSt6vectorIiSaIiEE9push_backEOi"), Some("std"));
+        assert_eq!(cpp_namespace("_ZN99shortE"), None);
+        assert_eq!(cpp_namespace("_Z3foov"), None);
+        assert_eq!(cpp_namespace("malloc"), None);
+    }
+
+    #[test]
+    fn languages_by_mangling() {
+        assert_eq!(language(RUST_FMT), "Rust");
+        assert_eq!(language("__ZN7mozilla3FooE"), "C++");
+        assert_eq!(language("_$s4main3FooVMa"), "Swift");
+        assert_eq!(language("-[NSObject init]"), "Objective-C");
+        assert_eq!(language("memcpy"), "C");
+    }
+
+    #[test]
+    fn sizes_by_crate() {
+        let buf = Elf::object()
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &[0; 64])
+            .symbol(RUST_FMT, STT_FUNC, ".text", 0, 16)
+            .symbol("_ZN4core3ptr13drop_in_place17h00000000000000ffE", STT_FUNC, ".text", 16, 8)
+            .symbol("_ZN7mozilla3FooEv", STT_FUNC, ".text", 24, 4)
+            .symbol("main", STT_FUNC, ".text", 28, 2)
+            .build();
+        let rules = Rules::default();
+        let sizes = group_sizes(&buf, GroupBy::Crate, &rules, false).unwrap();
+        let expected: BTreeMap<String, u64> = vec![
+            ("core".to_string(), 24), ("mozilla".to_string(), 4), (NO_CRATE.to_string(), 2),
+        ].into_iter().collect();
+        assert_eq!(sizes, expected);
+        let sizes = group_sizes(&buf, GroupBy::Language, &rules, false).unwrap();
+        assert_eq!((sizes["Rust"], sizes["C++"], sizes["C"]), (24, 4, 2));
+        let sizes = group_sizes(&buf, GroupBy::Origin, &rules, false).unwrap();
+        assert_eq!((sizes["compiler-generated"], sizes["user code"]), (8, 22));
+
+        assert!(matches!("source-file".parse(), Ok(GroupBy::SourceFile)));
+        let err = "crates".parse::<GroupBy>().unwrap_err();
+        assert!(err.downcast_ref::<UsageError>().is_some());
+        for by in &[GroupBy::Label, GroupBy::Owner, GroupBy::Component] {
+            let err = Grouper::new(&buf, *by, &rules).err().unwrap();
+            assert!(err.downcast_ref::<UsageError>().is_some());
+        }
+        // Without debug info, there are no source files to group by.
+        assert!(Grouper::new(&buf, GroupBy::SourceFile, &rules).is_err());
+    }
+}
@@ -0,0 +1,308 @@
+use arch::fat_slice;
+use failure::Error;
+use duplicates::{duplicates, DuplicateGroup};
+use dynamic;
+use goblin::mach::constants::cputype::get_arch_name_from_types;
+use goblin::mach::load_command::CommandVariant;
+use goblin::mach::Mach;
+use goblin::Object;
+use loaded_data;
+use merge::{merge_stats, MergeStats};
+use paths;
+use regex::bytes::Regex;
+use sections;
+use std::cmp;
+use symbols;
+use Section;
+
+/// Sections that are only needed when something unwinds the stack.
+const UNWIND_SECTIONS: &[&str] = &[".eh_frame", ".eh_frame_hdr", "__eh_frame", "__unwind_info"];
+
+/// Sections that hold the landing pads that unwinding actually runs.
+const EXCEPTION_SECTIONS: &[&str] = &[".gcc_except_table", "__gcc_except_tab"];
+
+/// The smallest symbol considered when looking for identical code or data, since tiny functions
+/// and constants are often legitimately identical and folding them is not where the savings are.
+const MIN_FOLDABLE_SIZE: u64 = 16;
+
+/// An actionable suggestion for reducing the size of an object file.
+#[derive(Clone, Debug, Serialize)]
+pub struct Hint {
+    /// A stable identifier for the kind of hint.
+    pub id: &'static str,
+    /// What was found and what to do about it.
+    pub message: String,
+    /// An estimate of the number of bytes that following the hint would save.
+    pub savings: u64,
+}
+
+/// Examine the object file in `buf` and return the hints that apply to it, largest savings first.
+pub fn hints(buf: &[u8]) -> Result<Vec<Hint>, Error> {
+    let mut hints = Vec::new();
+    if let Object::Mach(Mach::Fat(fat)) = Object::parse(buf)? {
+        let arches = fat.arches()?;
+        let largest = arches.iter().map(|arch| arch.size as u64).max().unwrap_or(0);
+        if arches.len() > 1 {
+            let names: Vec<&str> = arches.iter().map(|arch| {
+                get_arch_name_from_types(arch.cputype, arch.cpusubtype & 0x00ff_ffff)
+                    .unwrap_or("unknown")
+            }).collect();
+            hints.push(Hint {
+                id: "single-arch",
+                message: format!("universal binary contains {} architectures ({}); shipping \
+                                  a single-architecture build per platform would avoid carrying \
+                                  the others", arches.len(), names.join(", ")),
+                savings: buf.len() as u64 - largest,
+            });
+        }
+        for arch in &arches {
+            hints.extend(hints_for_binary(fat_slice(buf, arch)?)?);
+        }
+    } else {
+        hints.extend(hints_for_binary(buf)?);
+    }
+    if buf.starts_with(b"\x7fELF") {
+        let report = dynamic::dynamic(buf, None)?;
+        if report.hash_tables.len() > 1 && report.hash_style_savings > 0 {
+            hints.push(Hint {
+                id: "legacy-hash-table",
+                message: format!("both .hash and .gnu.hash are present; only very old dynamic \
+                                  loaders need .hash, so linking with `-Wl,--hash-style=gnu` \
+                                  would drop its {} bytes", report.hash_style_savings),
+                savings: report.hash_style_savings as u64,
+            });
+        }
+    }
+    hints.sort_by_key(|hint| cmp::Reverse(hint.savings));
+    Ok(hints)
+}
+
+/// Return the hints for a single (non-fat) object file.
+fn hints_for_binary(buf: &[u8]) -> Result<Vec<Hint>, Error> {
+    let sections = sections(buf)?;
+    let size_of = |names: &[&str]| -> u64 {
+        sections.iter().filter(|s| names.contains(&&*s.0)).map(|s| s.1).sum()
+    };
+    let mut hints = Vec::new();
+
+    let loaded: u64 = sections.iter()
+        .filter(|s| s.2 == Section::Text || s.2 == Section::Data)
+        .map(|s| s.1)
+        .sum();
+    let unwind = size_of(UNWIND_SECTIONS);
+    if unwind > 4096 && unwind * 20 >= loaded && size_of(EXCEPTION_SECTIONS) == 0 {
+        hints.push(Hint {
+            id: "unwind-tables-without-exceptions",
+            message: format!("{} bytes of unwind tables ({:.1}% of loaded size) but no exception \
+                              landing pads; if nothing needs to unwind, building with \
+                              `-C panic=abort` or `-fno-asynchronous-unwind-tables` would drop \
+                              most of them", unwind, unwind as f64 * 100.0 / loaded as f64),
+            savings: unwind,
+        });
+    }
+
+    let symtab = symbol_table_size(buf)?;
+    if symtab > 0 {
+        hints.push(Hint {
+            id: "unstripped-symbols",
+            message: format!("{} bytes of symbol tables that aren't needed at runtime; strip \
+                              them (`strip`, `-C strip=symbols`, `-Wl,-s`)", symtab),
+            savings: symtab,
+        });
+    }
+
+    let paths = paths::absolute_paths(buf)?;
+    if !paths.is_empty() {
+        let bytes = paths.iter().map(|p| p.path.len() as u64).sum();
+        hints.push(Hint {
+            id: "absolute-source-paths",
+            message: format!("{} absolute source paths ({} bytes) are embedded, mostly by panic \
+                              locations, and expose the build machine's directories; \
+                              `--remap-path-prefix` shortens them (`paths` lists the prefixes), \
+                              and `-Z location-detail=none` drops panic locations", paths.len(),
+                             bytes),
+            savings: bytes,
+        });
+    }
+
+    let symbols = symbols::symbols(buf)?;
+    let checks: Vec<_> = symbols.iter()
+        .filter(|sym| sym.code && sym.name.contains("precondition_check"))
+        .collect();
+    if !checks.is_empty() {
+        let bytes = checks.iter().map(|sym| sym.size).sum();
+        hints.push(Hint {
+            id: "debug-assertions",
+            message: format!("{} unsafe precondition checks ({} bytes) are only compiled in with \
+                              debug assertions; build in release mode, or with \
+                              `-C debug-assertions=off`", checks.len(), bytes),
+            savings: bytes,
+        });
+    }
+
+    let logging = Regex::new(concat!(r"(?i)(?:\[(?:debug|trace)\]|<(?:debug|trace)>|",
+                                     r"\b(?:debug|trace):)[^\x00\n]*")).unwrap();
+    let log_strings: Vec<u64> = loaded_data(buf)?.iter()
+        .flat_map(|(_, _, data)| logging.find_iter(data).map(|m| m.len() as u64))
+        .collect();
+    if !log_strings.is_empty() {
+        let bytes = log_strings.iter().sum();
+        hints.push(Hint {
+            id: "debug-logging",
+            message: format!("{} strings ({} bytes) look like debug or trace log messages; \
+                              compile out verbose logging in release builds (e.g. the `log` \
+                              crate's `release_max_level_info` feature)", log_strings.len(),
+                             bytes),
+            savings: bytes,
+        });
+    }
+
+    let debug: u64 = sections.iter()
+        .filter(|s| s.0.starts_with(".debug_") || s.0.starts_with("__debug_"))
+        .map(|s| s.1)
+        .sum();
+    if debug > 0 && !has_compressed_debug_info(buf)? {
+        hints.push(Hint {
+            id: "uncompressed-debug-info",
+            message: format!("{} bytes of uncompressed debug info; ship it separately \
+                              (`objcopy --only-keep-debug`, `-C split-debuginfo=packed`) or at \
+                              least compress it (`-Wl,--compress-debug-sections=zlib`)", debug),
+            savings: debug,
+        });
+    }
+
+    let groups = duplicates(buf, &symbols, true, MIN_FOLDABLE_SIZE);
+    if !groups.is_empty() {
+        hints.push(Hint {
+            id: "identical-code",
+            message: format!("{} functions in {} groups have byte-identical code; linking with \
+                              identical code folding (`-Wl,--icf=all` with lld or gold, \
+                              `/OPT:ICF` with link.exe) would keep one copy of each",
+                             groups.iter().map(|g| g.symbols.len()).sum::<usize>(), groups.len()),
+            savings: groups.iter().map(DuplicateGroup::savings).sum(),
+        });
+    }
+    let groups: Vec<_> = duplicates(buf, &symbols, false, MIN_FOLDABLE_SIZE).into_iter()
+        .filter(|g| !g.writable)
+        .collect();
+    if !groups.is_empty() {
+        hints.push(Hint {
+            id: "identical-data",
+            message: format!("{} read-only data objects in {} groups have byte-identical \
+                              contents; see `rust-size duplicates` for the list",
+                             groups.iter().map(|g| g.symbols.len()).sum::<usize>(), groups.len()),
+            savings: groups.iter().map(DuplicateGroup::savings).sum(),
+        });
+    }
+
+    let unmerged: Vec<_> = merge_stats(buf)?.into_iter().filter(|s| s.savings() > 0).collect();
+    if !unmerged.is_empty() {
+        let savings = unmerged.iter().map(MergeStats::savings).sum();
+        let sections: Vec<&str> = unmerged.iter().map(|s| &*s.section).collect();
+        hints.push(Hint {
+            id: "unmerged-constants",
+            message: format!("{} bytes of duplicate or tail-mergeable entries remain in mergeable \
+                              sections ({}); check that the linker merges them (lld merges \
+                              string tails at `-O2`)", savings, sections.join(", ")),
+            savings,
+        });
+    }
+
+    Ok(hints)
+}
+
+/// Return the number of bytes taken up by symbol table entries that aren't needed at runtime.
+fn symbol_table_size(buf: &[u8]) -> Result<u64, Error> {
+    Ok(match Object::parse(buf)? {
+        Object::Elf(elf) => {
+            elf.section_headers.iter().filter(|sh| {
+                matches!(elf.shdr_strtab.get(sh.sh_name).and_then(|res| res.ok()),
+                         Some(".symtab") | Some(".strtab"))
+            }).map(|sh| sh.sh_size).sum()
+        },
+        Object::Mach(Mach::Binary(mach)) => {
+            let nlist_size = if mach.is_64 { 16 } else { 12 };
+            let mut symtab = None;
+            let mut nlocal = 0;
+            for lc in &mach.load_commands {
+                match lc.command {
+                    CommandVariant::Symtab(ref cmd) => symtab = Some((cmd.nsyms, cmd.strsize)),
+                    CommandVariant::Dysymtab(ref cmd) => nlocal = cmd.nlocalsym,
+                    _ => {},
+                }
+            }
+            match symtab {
+                // Assume local symbols use their share of the string table.
+                Some((nsyms, strsize)) if nsyms > 0 => {
+                    nlocal as u64 * nlist_size + strsize as u64 * nlocal as u64 / nsyms as u64
+                },
+                _ => 0,
+            }
+        },
+        Object::PE(pe) => {
+            // COFF symbol table entries are 18 bytes, followed by a string table that starts
+            // with its own size.
+            let coff = &pe.header.coff_header;
+            if coff.pointer_to_symbol_table == 0 {
+                0
+            } else {
+                let strtab = coff.pointer_to_symbol_table as usize +
+                    coff.number_of_symbol_table as usize * 18;
+                let strsize = buf.get(strtab..strtab + 4)
+                    .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                    .unwrap_or(0);
+                coff.number_of_symbol_table as u64 * 18 + strsize as u64
+            }
+        },
+        _ => 0,
+    })
+}
+
+/// Whether any ELF debug sections are already compressed.
+fn has_compressed_debug_info(buf: &[u8]) -> Result<bool, Error> {
+    const SHF_COMPRESSED: u32 = 0x800;
+    Ok(match Object::parse(buf)? {
+        Object::Elf(elf) => elf.section_headers.iter().any(|sh| {
+            let name = elf.shdr_strtab.get(sh.sh_name).and_then(|res| res.ok()).unwrap_or("");
+            name.starts_with(".zdebug_") ||
+                (name.starts_with(".debug_") && (sh.sh_flags as u32 & SHF_COMPRESSED) != 0)
+        }),
+        _ => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::elf::section_header::{SHF_ALLOC, SHF_EXECINSTR, SHT_PROGBITS};
+    use goblin::elf::sym::STT_FUNC;
+    use testelf::Elf;
+
+    #[test]
+    fn hints_for_an_unstripped_debug_build() {
+        let buf = Elf::executable()
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &[0xc3; 64])
+            .section(".rodata", SHT_PROGBITS, SHF_ALLOC, b"[debug] starting up\0ok\0")
+            .section(".eh_frame", SHT_PROGBITS, SHF_ALLOC, &[0; 5000])
+            .symbol("main", STT_FUNC, ".text", 0, 32)
+            .symbol("precondition_check", STT_FUNC, ".text", 32, 8)
+            .build();
+        let hints = hints(&buf).unwrap();
+        let ids: Vec<_> = hints.iter().map(|hint| (hint.id, hint.savings)).collect();
+        assert_eq!(ids[0], ("unwind-tables-without-exceptions", 5000));
+        assert!(ids.contains(&("debug-logging", 19)), "{:?}", ids);
+        assert!(ids.contains(&("debug-assertions", 8)), "{:?}", ids);
+        // The symbol table and its strings: three symbols, with the null one.
+        assert!(ids.contains(&("unstripped-symbols", 3 * 24 + 25)), "{:?}", ids);
+    }
+
+    #[test]
+    fn unwind_tables_with_landing_pads_are_needed() {
+        let buf = Elf::executable()
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &[0xc3; 64])
+            .section(".eh_frame", SHT_PROGBITS, SHF_ALLOC, &[0; 5000])
+            .section(".gcc_except_table", SHT_PROGBITS, SHF_ALLOC, &[0; 16])
+            .build();
+        assert!(hints(&buf).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,265 @@
+use cold;
+use failure::Error;
+use rustc_demangle;
+use std::collections::HashMap;
+use symbols;
+
+/// Functions this many times hotter than average for their size, taking at least
+/// `SMALL_HOT_SHARE` of the samples, are small and hot.
+const SMALL_HOT_HEAT: f64 = 10.0;
+const SMALL_HOT_SHARE: f64 = 0.01;
+
+/// Functions at most this fraction as hot as average for their size are big and cold.
+const BIG_COLD_HEAT: f64 = 0.1;
+
+/// A function, with its share of the code and of the samples.
+#[derive(Clone, Debug, Serialize)]
+pub struct HotFunction {
+    /// The demangled name, without the hash.
+    pub name: String,
+    pub size: u64,
+    pub samples: u64,
+    /// The function's share of the code, from 0 to 1.
+    pub size_share: f64,
+    /// The function's share of the samples that are in the binary, from 0 to 1.
+    pub sample_share: f64,
+    /// `sample_share` over `size_share`: 1 for functions as hot as average for their size,
+    /// more for hotter ones.
+    pub heat: f64,
+    /// `big-cold` for functions of at least `--big` bytes that are at most a tenth as hot as
+    /// average, or `small-hot` for smaller functions that are at least ten times as hot and
+    /// take at least 1% of the samples.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub class: Option<&'static str>,
+}
+
+/// The sizes and samples of the functions of a binary, as emitted by
+/// `hotness --format json`.
+#[derive(Clone, Debug, Serialize)]
+pub struct HotnessReport {
+    /// What the samples were read from: `perf-script`, `perf-report`, or `counts` for a file
+    /// of symbols and counts.
+    pub source: &'static str,
+    pub code_size: u64,
+    /// The samples of the functions in the binary.
+    pub samples: u64,
+    /// The samples of functions that aren't in the binary, like those of shared libraries or
+    /// the kernel.
+    pub unmatched_samples: u64,
+    pub big_cold: u64,
+    pub big_cold_size: u64,
+    pub small_hot: u64,
+    /// The share of the samples that the small and hot functions take, from 0 to 1.
+    pub small_hot_share: f64,
+    /// The functions, largest first.
+    pub functions: Vec<HotFunction>,
+}
+
+/// The samples of each symbol in the output of `perf report --stdio` `text`. With `-n`, the
+/// sample counts are used; without, the (self) overhead, in hundredths of a percent.
+fn perf_report_counts(text: &str) -> HashMap<String, u64> {
+    let mut counts = HashMap::new();
+    for line in text.lines() {
+        if line.trim_start().starts_with('#') {
+            continue;
+        }
+        let (columns, symbol) = match line.find(" [.] ").or_else(|| line.find(" [k] ")) {
+            Some(i) => (&line[..i], line[i + 5..].trim()),
+            None => continue,
+        };
+        let columns: Vec<&str> = columns.split_whitespace().collect();
+        // `Children` and `Self` overheads with `-g`, or just the overhead, then the samples
+        // with `-n`, the command and the shared object.
+        let overheads = columns.iter().take_while(|column| column.ends_with('%')).count();
+        let samples = columns.get(overheads).and_then(|column| column.parse::<u64>().ok());
+        let count = match samples {
+            Some(samples) if columns.len() > overheads + 2 => samples,
+            _ => match overheads.checked_sub(1).map(|i| columns[i].trim_end_matches('%')) {
+                Some(overhead) => overhead.parse::<f64>().map_or(0, |o| (o * 100.0).round() as u64),
+                None => continue,
+            },
+        };
+        *counts.entry(cold::key(symbol)).or_insert(0) += count;
+    }
+    counts
+}
+
+/// The counts of a file with a symbol and a count on each line, in either order, separated by
+/// whitespace or a comma, as a histogram exported from ETW or another profiler can be written.
+/// Lines without a count, like headers and `#` comments, are ignored.
+fn symbol_counts(text: &str) -> HashMap<String, u64> {
+    let mut counts = HashMap::new();
+    for line in text.lines().map(str::trim) {
+        if line.starts_with('#') {
+            continue;
+        }
+        let split = |line: &str| -> Option<(String, u64)> {
+            let separator = |c: char| c.is_whitespace() || c == ',';
+            let last = line.rsplit_once(separator)
+                .and_then(|(symbol, count)| Some((symbol, count.trim().parse().ok()?)));
+            let first = || line.split_once(separator)
+                .and_then(|(count, symbol)| Some((symbol, count.trim().parse().ok()?)));
+            let (symbol, count) = last.or_else(first)?;
+            let symbol = symbol.trim().trim_matches('"');
+            if symbol.is_empty() { None } else { Some((cold::key(symbol), count)) }
+        };
+        if let Some((symbol, count)) = split(line) {
+            *counts.entry(symbol).or_insert(0) += count;
+        }
+    }
+    counts
+}
+
+/// Whether `text` is the output of `perf script`, which has frames like
+/// `401025 main+0x5 (/usr/bin/sw)`.
+fn is_perf_script(text: &str) -> bool {
+    text.lines().take(1000).any(|line| {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        words.len() >= 3 && words[0].bytes().all(|b| b.is_ascii_hexdigit()) &&
+            words[words.len() - 1].starts_with('(') && words[words.len() - 1].ends_with(')')
+    })
+}
+
+/// Combine the sizes of the functions of `buf` with the samples of `samples`, which is the
+/// output of `perf script` or `perf report --stdio`, or a file of symbols and counts, and
+/// classify the functions of at least `big` bytes that are cold for their size and the smaller
+/// ones that are hot.
+pub fn hotness(buf: &[u8], samples: &[u8], big: u64) -> Result<HotnessReport, Error> {
+    let text = String::from_utf8_lossy(samples);
+    let (source, mut counts) = if text.lines().any(|l| l.contains(" [.] ") || l.contains(" [k] ")) {
+        ("perf-report", perf_report_counts(&text))
+    } else if is_perf_script(&text) {
+        ("perf-script", cold::perf_counts(&text))
+    } else {
+        ("counts", symbol_counts(&text))
+    };
+
+    let mut functions: Vec<_> = symbols::symbols(buf)?.into_iter()
+        .filter(|sym| sym.code)
+        .collect();
+    // Aliases share their function's address; keep the largest of each.
+    functions.sort_by(|a, b| {
+        (&a.section, a.address, b.size, &a.name).cmp(&(&b.section, b.address, a.size, &b.name))
+    });
+    functions.dedup_by(|a, b| a.section == b.section && a.address == b.address);
+
+    let total: u64 = counts.values().sum();
+    let functions: Vec<(String, u64, u64)> = functions.into_iter()
+        .map(|sym| {
+            let samples = counts.remove(&cold::key(&sym.name)).unwrap_or(0);
+            (format!("{:#}", rustc_demangle::demangle(&sym.name)), sym.size, samples)
+        })
+        .collect();
+    let code_size: u64 = functions.iter().map(|&(_, size, _)| size).sum();
+    let sampled: u64 = functions.iter().map(|&(_, _, samples)| samples).sum();
+    let mut report = HotnessReport {
+        source,
+        code_size,
+        samples: sampled,
+        unmatched_samples: total - sampled,
+        big_cold: 0,
+        big_cold_size: 0,
+        small_hot: 0,
+        small_hot_share: 0.0,
+        functions: Vec::new(),
+    };
+    for (name, size, samples) in functions {
+        let size_share = size as f64 / code_size.max(1) as f64;
+        let sample_share = samples as f64 / sampled.max(1) as f64;
+        let heat = if size_share > 0.0 { sample_share / size_share } else { 0.0 };
+        let class = if sampled == 0 {
+            None
+        } else if size >= big && heat <= BIG_COLD_HEAT {
+            report.big_cold += 1;
+            report.big_cold_size += size;
+            Some("big-cold")
+        } else if size < big && heat >= SMALL_HOT_HEAT && sample_share >= SMALL_HOT_SHARE {
+            report.small_hot += 1;
+            report.small_hot_share += sample_share;
+            Some("small-hot")
+        } else {
+            None
+        };
+        report.functions.push(HotFunction {
+            name,
+            size,
+            samples,
+            size_share,
+            sample_share,
+            heat,
+            class,
+        });
+    }
+    report.functions.sort_by(|a, b| (b.size, &a.name).cmp(&(a.size, &b.name)));
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::elf::section_header::{SHF_ALLOC, SHF_EXECINSTR, SHT_PROGBITS};
+    use goblin::elf::sym::STT_FUNC;
+    use testelf::Elf;
+
+    fn sorted(counts: HashMap<String, u64>) -> Vec<(String, u64)> {
+        let mut counts: Vec<_> = counts.into_iter().collect();
+        counts.sort();
+        counts
+    }
+
+    fn pairs(pairs: &[(&str, u64)]) -> Vec<(String, u64)> {
+        pairs.iter().map(|&(name, count)| (name.to_string(), count)).collect()
+    }
+
+    #[test]
+    fn perf_report_samples_or_overheads() {
+        let report = "\
+# Overhead       Samples  Command  Shared Object      Symbol
+# ........  ............  .......  .................  ......
+    60.00%           600  app      app                [.] main
+    30.00%           300  app      libc.so.6          [.] memcpy
+     0.10%             1  app      [kernel.kallsyms]  [k] do_syscall
+";
+        assert_eq!(sorted(perf_report_counts(report)),
+                   pairs(&[("do_syscall", 1), ("main", 600), ("memcpy", 300)]));
+        // Without `-n`, and with `-g`, which adds the overhead with children first.
+        let report = "    60.00%  app  app  [.] main\n    70.00%  0.25%  app  app  [.] f\n";
+        assert_eq!(sorted(perf_report_counts(report)), pairs(&[("f", 25), ("main", 6000)]));
+    }
+
+    #[test]
+    fn symbols_and_counts() {
+        let counts = "Symbol,Samples\n\"hot\",900\n99 mid\n# big 5\nbig 1\nbig\t2\n";
+        assert_eq!(sorted(symbol_counts(counts)), pairs(&[("big", 3), ("hot", 900), ("mid", 99)]));
+        assert!(is_perf_script("app 1234 cycles:\n\t401025 main+0x5 (/usr/bin/app)\n"));
+        assert!(!is_perf_script(counts));
+    }
+
+    #[test]
+    fn big_cold_and_small_hot_functions() {
+        let buf = Elf::object()
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &[0; 1296])
+            .symbol("big", STT_FUNC, ".text", 0, 1024)
+            .symbol("mid", STT_FUNC, ".text", 1024, 256)
+            .symbol("hot", STT_FUNC, ".text", 1280, 16)
+            .build();
+        let report = hotness(&buf, b"hot,900\nmid,99\nbig,1\nmemcpy,50\n", 512).unwrap();
+        assert_eq!((report.source, report.code_size), ("counts", 1296));
+        assert_eq!((report.samples, report.unmatched_samples), (1000, 50));
+        assert_eq!((report.big_cold, report.big_cold_size, report.small_hot), (1, 1024, 1));
+        assert_eq!(report.small_hot_share, 0.9);
+        let functions: Vec<_> = report.functions.iter()
+            .map(|f| (&*f.name, f.samples, f.class))
+            .collect();
+        assert_eq!(functions, vec![("big", 1, Some("big-cold")), ("mid", 99, None),
+                                   ("hot", 900, Some("small-hot"))]);
+        let hot = &report.functions[2];
+        assert_eq!((hot.size_share, hot.sample_share), (16.0 / 1296.0, 0.9));
+        assert_eq!(hot.heat, 0.9 / (16.0 / 1296.0));
+
+        // Without samples in the binary, nothing is hot or cold.
+        let report = hotness(&buf, b"memcpy 50\n", 512).unwrap();
+        assert!(report.functions.iter().all(|f| f.class.is_none()));
+        assert_eq!(report.unmatched_samples, 50);
+    }
+}
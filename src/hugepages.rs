@@ -0,0 +1,108 @@
+use failure::Error;
+use goblin::elf::program_header::{PF_X, PT_LOAD};
+use goblin::Object;
+
+/// How an executable LOAD segment lines up with huge pages, as emitted by
+/// `hugepages --format json`.
+#[derive(Clone, Debug, Serialize)]
+pub struct TextSegment {
+    pub address: u64,
+    pub offset: u64,
+    pub size: u64,
+    pub alignment: u64,
+    /// Whether the segment starts on a huge page boundary both in memory and in the file, as
+    /// mapping file-backed text with huge pages needs.
+    pub aligned: bool,
+    /// The number of whole huge pages the segment covers.
+    pub huge_pages: u64,
+    /// The bytes of the segment outside whole huge pages, which are mapped with small pages.
+    pub unaligned_bytes: u64,
+    /// The gap between the previous segment and this one, in the file and in memory, which is
+    /// where linking for huge pages puts its padding.
+    pub file_padding: u64,
+    pub memory_padding: u64,
+    /// The bytes it would take to move the segment to the next huge page boundary, if it isn't
+    /// aligned.
+    pub alignment_cost: u64,
+}
+
+fn align_up(value: u64, alignment: u64) -> u64 {
+    value.div_ceil(alignment) * alignment
+}
+
+/// Check the executable LOAD segments of the ELF file `buf` against huge pages of `page_size`
+/// bytes, which must be a power of two.
+pub fn text_segments(buf: &[u8], page_size: u64) -> Result<Vec<TextSegment>, Error> {
+    let elf = match Object::parse(buf)? {
+        Object::Elf(elf) => elf,
+        _ => bail!("Not an ELF file"),
+    };
+    let mut loads: Vec<_> = elf.program_headers.iter().filter(|ph| ph.p_type == PT_LOAD)
+        .collect();
+    loads.sort_by_key(|ph| ph.p_vaddr);
+    let mut segments = Vec::new();
+    for (i, ph) in loads.iter().enumerate() {
+        if ph.p_flags & PF_X == 0 {
+            continue;
+        }
+        let (start, end) = (ph.p_vaddr, ph.p_vaddr + ph.p_memsz);
+        let first_page = align_up(start, page_size);
+        let huge_pages = end.saturating_sub(first_page) / page_size;
+        let aligned = start.is_multiple_of(page_size) && ph.p_offset.is_multiple_of(page_size);
+        let (file_padding, memory_padding) = match i.checked_sub(1).map(|j| loads[j]) {
+            Some(prev) => (ph.p_offset.saturating_sub(prev.p_offset + prev.p_filesz),
+                           start.saturating_sub(prev.p_vaddr + prev.p_memsz)),
+            None => (0, 0),
+        };
+        segments.push(TextSegment {
+            address: start,
+            offset: ph.p_offset,
+            size: ph.p_memsz,
+            alignment: ph.p_align,
+            aligned,
+            huge_pages,
+            unaligned_bytes: ph.p_memsz - huge_pages * page_size,
+            file_padding,
+            memory_padding,
+            alignment_cost: if aligned {
+                0
+            } else {
+                align_up(ph.p_offset, page_size) - ph.p_offset
+            },
+        });
+    }
+    Ok(segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::elf::program_header::PF_R;
+    use goblin::elf::section_header::{SHF_ALLOC, SHF_EXECINSTR, SHT_PROGBITS};
+    use testelf::{Elf, BASE};
+
+    #[test]
+    fn text_against_huge_pages() {
+        // `.rodata` ends 0x100 bytes into the file, and padding moves `.text` to 0x200.
+        let buf = Elf::executable()
+            .section(".rodata", SHT_PROGBITS, SHF_ALLOC, &[0; 0x50])
+            .section(".pad", SHT_PROGBITS, 0, &[0; 0x100])
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &[0; 0x250])
+            .segment(PT_LOAD, PF_R, &[".rodata"], 0x100)
+            .segment(PT_LOAD, PF_R | PF_X, &[".text"], 0x100)
+            .build();
+        let segments = text_segments(&buf, 0x100).unwrap();
+        assert_eq!(segments.len(), 1);
+        let text = &segments[0];
+        assert_eq!((text.address, text.offset, text.size), (BASE + 0x200, 0x200, 0x250));
+        assert_eq!((text.aligned, text.huge_pages, text.unaligned_bytes), (true, 2, 0x50));
+        assert_eq!((text.file_padding, text.memory_padding, text.alignment_cost),
+                   (0x100, 0x100, 0));
+
+        // Against 4KB pages, the segment doesn't cover a whole page.
+        let text = &text_segments(&buf, 0x1000).unwrap()[0];
+        assert_eq!((text.aligned, text.huge_pages, text.unaligned_bytes), (false, 0, 0x250));
+        assert_eq!(text.alignment_cost, 0x1000 - 0x200);
+        assert!(text_segments(b"\0asm\x01\0\0\0", 0x100).is_err());
+    }
+}
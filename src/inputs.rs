@@ -0,0 +1,235 @@
+use archive;
+use counts_toward_total;
+use failure::Error;
+use normalize::normalize_name;
+use section_records;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use units::SizeFormat;
+use Section;
+use SectionRecord;
+
+/// Linker options whose value is a separate argument, which must not be taken for an input.
+const OPTIONS_WITH_VALUES: &[&str] = &["-o", "-L", "-T", "-e", "-soname", "-Map", "-rpath"];
+
+/// Split the contents of a response file into arguments, the way GNU tools do: arguments are
+/// separated by whitespace, and can be quoted with `'` or `"`, or escaped with `\`.
+fn split_arguments(s: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut arg = None;
+    let mut quote = None;
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => if let Some(next) = chars.next() {
+                arg.get_or_insert_with(String::new).push(next);
+            },
+            '\'' | '"' if quote.is_none() => {
+                quote = Some(c);
+                arg.get_or_insert_with(String::new);
+            },
+            c if quote == Some(c) => quote = None,
+            c if c.is_whitespace() && quote.is_none() => if let Some(a) = arg.take() {
+                args.push(a);
+            },
+            c => arg.get_or_insert_with(String::new).push(c),
+        }
+    }
+    args.extend(arg);
+    args
+}
+
+/// Read the input files listed in the response file or MRI script at `path`.
+///
+/// MRI scripts (as accepted by `ar -M`) are recognized by a leading `CREATE` or `CREATETHIN`
+/// command, and contribute the files of their `ADDMOD` and `ADDLIB` commands. Otherwise, the file
+/// is taken to be a linker response file: options are skipped, and other arguments are inputs,
+/// with `@file` arguments expanded recursively.
+pub fn read_input_list(path: &Path) -> Result<Vec<String>, Error> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format_err!("{}: {}", path.display(), e))?;
+    let first = contents.lines().map(str::trim).find(|l| !l.is_empty() && !l.starts_with(';'));
+    let is_mri = first.is_some_and(|l| {
+        let command = l.split_whitespace().next().unwrap_or("").to_ascii_uppercase();
+        command == "CREATE" || command == "CREATETHIN"
+    });
+
+    let mut inputs = Vec::new();
+    if is_mri {
+        for line in contents.lines() {
+            let mut words = line.split_whitespace();
+            let command = words.next().unwrap_or("").to_ascii_uppercase();
+            if command == "ADDMOD" || command == "ADDLIB" {
+                // Both commands take comma- or space-separated file names.
+                inputs.extend(words.flat_map(|w| w.split(','))
+                              .filter(|w| !w.is_empty())
+                              .map(str::to_string));
+            }
+        }
+        return Ok(inputs);
+    }
+
+    let mut args = split_arguments(&contents).into_iter();
+    while let Some(arg) = args.next() {
+        if let Some(nested) = arg.strip_prefix('@') {
+            inputs.extend(read_input_list(Path::new(nested))?);
+        } else if OPTIONS_WITH_VALUES.contains(&arg.as_str()) {
+            args.next();
+        } else if !arg.starts_with('-') {
+            inputs.push(arg);
+        }
+    }
+    Ok(inputs)
+}
+
+/// Call `f` with the name and contents of each object file at `paths`, or in the archives at
+/// `paths`, and return the results in order. Archive members are named `path(member)`, and are
+/// processed in parallel (see `archive::par_map`).
+pub fn map_objects<T, F>(paths: &[String], f: F) -> Result<Vec<T>, Error>
+    where T: Send,
+          F: Fn(String, &[u8]) -> Result<T, Error> + Sync,
+{
+    let mut results = Vec::new();
+    for path in paths {
+        let file = fs::File::open(path).map_err(|e| format_err!("{}: {}", path, e))?;
+        let buf = unsafe { memmap::Mmap::map(&file)? };
+        if archive::is_archive(&buf) {
+            let members = archive::members(&buf, Path::new(path))?;
+            for result in archive::par_map(&members, |member| {
+                f(format!("{}({})", path, member.name), &member.data)
+            }) {
+                results.push(result?);
+            }
+        } else {
+            results.push(f(path.clone(), &buf)?);
+        }
+    }
+    Ok(results)
+}
+
+/// The sizes contributed by one input of a link: an object file, or a member of an archive.
+#[derive(Clone, Debug, Serialize)]
+pub struct InputSizes {
+    /// The path of the input, followed by the archive member name in parentheses for archives.
+    pub name: String,
+    pub categories: BTreeMap<Section, u64>,
+    pub total: u64,
+}
+
+/// The aggregate size of a set of link inputs, as emitted by `link-inputs --format json`.
+#[derive(Clone, Debug, Serialize)]
+pub struct InputReport {
+    /// The inputs, largest total first.
+    pub inputs: Vec<InputSizes>,
+    /// The total size of each section across all inputs.
+    pub sections: BTreeMap<Section, BTreeMap<String, u64>>,
+    pub total: u64,
+}
+
+impl InputReport {
+    /// Measure the object files and archives at `paths`. Section names are normalized if
+    /// `normalize` is set, which collapses e.g. the `.text.*` sections of `-ffunction-sections`
+    /// into one. Non-allocated sections only count toward totals if `include_non_alloc` is set.
+    pub fn new(paths: &[String], normalize: bool, include_non_alloc: bool)
+               -> Result<InputReport, Error> {
+        let mut report = InputReport {
+            inputs: Vec::new(),
+            sections: BTreeMap::new(),
+            total: 0,
+        };
+        let measured = map_objects(paths, |name, buf| {
+            let records = section_records(buf).map_err(|e| format_err!("{}: {}", name, e))?;
+            Ok((name, records))
+        })?;
+        for (name, records) in measured {
+            report.add(name, records, normalize, include_non_alloc);
+        }
+        report.inputs.sort_by(|a, b| b.total.cmp(&a.total).then_with(|| a.name.cmp(&b.name)));
+        Ok(report)
+    }
+
+    fn add(&mut self, name: String, records: Vec<SectionRecord>, normalize: bool,
+           include_non_alloc: bool) {
+        let mut input = InputSizes {
+            name,
+            categories: BTreeMap::new(),
+            total: 0,
+        };
+        for record in records {
+            let name = if normalize {
+                normalize_name(&record.name).to_string()
+            } else {
+                record.name
+            };
+            *input.categories.entry(record.category).or_insert(0) += record.size;
+            *self.sections.entry(record.category).or_default().entry(name).or_insert(0) +=
+                record.size;
+            if counts_toward_total(record.category, include_non_alloc) {
+                input.total += record.size;
+            }
+        }
+        self.total += input.total;
+        self.inputs.push(input);
+    }
+
+    /// Print the report in human-readable form: one line per input, then the totals. Sizes are
+    /// written in `format`.
+    pub fn print(&self, format: &SizeFormat) {
+        let category = |input: &InputSizes, section| {
+            format.size(input.categories.get(&section).cloned().unwrap_or(0))
+        };
+        println!("{:>10} {:>10} {:>10} {:>10} {:>10}  INPUT",
+                 "TEXT", "DATA", "BSS", "OTHER", "TOTAL");
+        for input in &self.inputs {
+            println!("{:>10} {:>10} {:>10} {:>10} {:>10}  {}",
+                     category(input, Section::Text), category(input, Section::Data),
+                     category(input, Section::Bss), category(input, Section::Other),
+                     format.size(input.total), input.name);
+        }
+        let sum = |section| {
+            format.size(self.sections.get(&section).map(|names| names.values().sum())
+                        .unwrap_or(0))
+        };
+        println!("{:>10} {:>10} {:>10} {:>10} {:>10}  ({} inputs)",
+                 sum(Section::Text), sum(Section::Data), sum(Section::Bss), sum(Section::Other),
+                 format.size(self.total), self.inputs.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    #[test]
+    fn splits_like_gnu_tools() {
+        assert_eq!(split_arguments("  a.o 'b c.o'\n\"d\\\"e.o\" f\\ g.o '' -o out "),
+                   vec!["a.o", "b c.o", "d\"e.o", "f g.o", "", "-o", "out"]);
+        assert!(split_arguments(" \n\t").is_empty());
+    }
+
+    #[test]
+    fn response_files_and_mri_scripts() {
+        let dir = env::temp_dir();
+        let nested = dir.join(format!("rust-size-{}-nested.rsp", process::id()));
+        let rsp = dir.join(format!("rust-size-{}.rsp", process::id()));
+        let mri = dir.join(format!("rust-size-{}.mri", process::id()));
+        fs::write(&nested, "c.o -lm").unwrap();
+        fs::write(&rsp, format!("-o out -L /lib a.o --gc-sections b.a @{}", nested.display()))
+            .unwrap();
+        fs::write(&mri, "; comment\ncreate libx.a\nADDMOD a.o,b.o\naddlib libc.a\nSAVE\nEND\n")
+            .unwrap();
+        let inputs = read_input_list(&rsp);
+        let script = read_input_list(&mri);
+        for path in &[&nested, &rsp, &mri] {
+            fs::remove_file(path).unwrap();
+        }
+        assert_eq!(inputs.unwrap(), vec!["a.o", "b.a", "c.o"]);
+        assert_eq!(script.unwrap(), vec!["a.o", "b.o", "libc.a"]);
+
+        let err = read_input_list(&nested).unwrap_err().to_string();
+        assert!(err.starts_with(&nested.display().to_string()), "{}", err);
+    }
+}
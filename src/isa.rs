@@ -0,0 +1,232 @@
+use arch::{arch, fat_slice, mach_arch};
+use failure::Error;
+use goblin::mach::Mach;
+use goblin::Object;
+use group::{GroupBy, Grouper, Rules};
+use std::collections::BTreeMap;
+use symbols::{self, canonical_name};
+
+/// A function that both builds have, with its size in each.
+#[derive(Clone, Debug, Serialize)]
+pub struct IsaFunction {
+    pub name: String,
+    #[serde(rename = "crate")]
+    pub krate: String,
+    pub baseline_size: u64,
+    pub other_size: u64,
+    /// `other_size` over `baseline_size`.
+    pub ratio: f64,
+    /// How much larger `other_size` is than `baseline_size` scaled by the overall factor: what
+    /// the function costs on the other architecture beyond what the instruction set explains.
+    pub excess: i64,
+}
+
+/// The functions of one crate, or C++ namespace, in both builds.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct IsaCrate {
+    pub name: String,
+    /// The functions that both builds have, and their sizes in each.
+    pub functions: u64,
+    pub baseline_size: u64,
+    pub other_size: u64,
+    /// `other_size` over `baseline_size`: the crate's expansion factor.
+    pub factor: f64,
+    /// How much larger `other_size` is than `baseline_size` scaled by the overall factor.
+    pub excess: i64,
+    /// The size of the functions that only one of the builds has, like code that is
+    /// conditionally compiled for one architecture or inlined differently.
+    pub baseline_only_size: u64,
+    pub other_only_size: u64,
+}
+
+/// The function sizes of two builds of the same program for different architectures, as
+/// emitted by `isa --format json`.
+#[derive(Clone, Debug, Serialize)]
+pub struct IsaReport {
+    pub baseline_arch: String,
+    pub other_arch: String,
+    /// The functions that both builds have, and their sizes in each.
+    pub functions: u64,
+    pub baseline_size: u64,
+    pub other_size: u64,
+    /// `other_size` over `baseline_size`: how much larger the same functions are on the other
+    /// architecture, overall.
+    pub factor: f64,
+    pub baseline_only_size: u64,
+    pub other_only_size: u64,
+    /// The crates, largest in the baseline first.
+    pub crates: Vec<IsaCrate>,
+    /// The functions whose `excess` is largest, either way.
+    pub outliers: Vec<IsaFunction>,
+}
+
+/// What `a` is to `b`, or 0 if `b` is 0.
+fn ratio(a: u64, b: u64) -> f64 {
+    if b == 0 { 0.0 } else { a as f64 / b as f64 }
+}
+
+/// How much `other` exceeds `baseline` scaled by `factor`.
+fn excess(other: u64, baseline: u64, factor: f64) -> i64 {
+    (other as f64 - baseline as f64 * factor).round() as i64
+}
+
+/// The slices of the universal binary `buf`, by architecture, or `buf` alone if it isn't one.
+pub fn slices(buf: &[u8]) -> Result<Vec<(String, &[u8])>, Error> {
+    Ok(match Object::parse(buf)? {
+        Object::Mach(Mach::Fat(fat)) => {
+            fat.arches()?.iter()
+                .map(|slice| Ok((mach_arch(slice.cputype).to_string(), fat_slice(buf, slice)?)))
+                .collect::<Result<_, Error>>()?
+        }
+        _ => vec![(arch(buf)?, buf)],
+    })
+}
+
+/// The sizes of the functions of `buf` by canonical name, with their crates. Aliases count
+/// once, and Mach-O's leading underscores are dropped, so that names match across formats.
+fn functions(buf: &[u8]) -> Result<BTreeMap<String, (u64, String)>, Error> {
+    let mach = matches!(Object::parse(buf)?, Object::Mach(_));
+    let mut syms: Vec<_> = symbols::symbols(buf)?.into_iter().filter(|sym| sym.code).collect();
+    syms.sort_by(|a, b| {
+        (&a.section, a.address, b.size, &a.name).cmp(&(&b.section, b.address, a.size, &b.name))
+    });
+    syms.dedup_by(|a, b| a.section == b.section && a.address == b.address);
+
+    let rules = Rules::default();
+    let grouper = Grouper::new(buf, GroupBy::Crate, &rules)?;
+    let mut functions = BTreeMap::new();
+    for sym in syms {
+        let name = if mach { sym.name.strip_prefix('_').unwrap_or(&sym.name) } else { &sym.name };
+        let entry = functions.entry(canonical_name(name))
+            .or_insert_with(|| (0, grouper.group(&sym)));
+        entry.0 += sym.size;
+    }
+    Ok(functions)
+}
+
+/// Pair up the functions of `baseline` and `other`, two builds of the same program for
+/// architectures `baseline_arch` and `other_arch`, and work out how much larger they are on
+/// `other`, overall and by crate, listing the `top` functions that stray furthest from the
+/// overall factor.
+pub fn isa(baseline: &[u8], baseline_arch: String, other: &[u8], other_arch: String, top: usize)
+           -> Result<IsaReport, Error> {
+    let baseline = functions(baseline)?;
+    let mut other = functions(other)?;
+    let mut crates: BTreeMap<String, IsaCrate> = BTreeMap::new();
+    let mut matched = Vec::new();
+    for (name, (baseline_size, krate)) in baseline {
+        let entry = crates.entry(krate.clone()).or_default();
+        match other.remove(&name) {
+            Some((other_size, _)) => {
+                entry.functions += 1;
+                entry.baseline_size += baseline_size;
+                entry.other_size += other_size;
+                matched.push((name, krate, baseline_size, other_size));
+            }
+            None => entry.baseline_only_size += baseline_size,
+        }
+    }
+    for (_, (other_size, krate)) in other {
+        crates.entry(krate).or_default().other_only_size += other_size;
+    }
+
+    let baseline_size = crates.values().map(|krate| krate.baseline_size).sum();
+    let other_size = crates.values().map(|krate| krate.other_size).sum();
+    let factor = ratio(other_size, baseline_size);
+    let mut outliers: Vec<IsaFunction> = matched.into_iter()
+        .map(|(name, krate, baseline_size, other_size)| IsaFunction {
+            name,
+            krate,
+            baseline_size,
+            other_size,
+            ratio: ratio(other_size, baseline_size),
+            excess: excess(other_size, baseline_size, factor),
+        })
+        .collect();
+    outliers.sort_by(|a, b| {
+        (b.excess.abs(), &a.name).cmp(&(a.excess.abs(), &b.name))
+    });
+    outliers.truncate(top);
+
+    let mut crates: Vec<IsaCrate> = crates.into_iter().map(|(name, mut krate)| {
+        krate.name = name;
+        krate.factor = ratio(krate.other_size, krate.baseline_size);
+        krate.excess = excess(krate.other_size, krate.baseline_size, factor);
+        krate
+    }).collect();
+    crates.sort_by(|a, b| {
+        (b.baseline_size + b.baseline_only_size, &a.name)
+            .cmp(&(a.baseline_size + a.baseline_only_size, &b.name))
+    });
+    Ok(IsaReport {
+        baseline_arch,
+        other_arch,
+        functions: crates.iter().map(|krate| krate.functions).sum(),
+        baseline_size,
+        other_size,
+        factor,
+        baseline_only_size: crates.iter().map(|krate| krate.baseline_only_size).sum(),
+        other_only_size: crates.iter().map(|krate| krate.other_only_size).sum(),
+        crates,
+        outliers,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::elf::header::EM_AARCH64;
+    use goblin::elf::section_header::{SHF_ALLOC, SHF_EXECINSTR, SHT_PROGBITS};
+    use goblin::elf::sym::STT_FUNC;
+    use testelf::Elf;
+
+    /// A build whose functions are `(crate, name, size)`, named with the legacy mangling and
+    /// the hash `hash`, which differs between builds.
+    fn build(elf: Elf, hash: &str, functions: &[(&str, &str, u64)]) -> Vec<u8> {
+        let total = functions.iter().map(|&(_, _, size)| size).sum::<u64>();
+        let mut elf = elf.section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR,
+                                  &vec![0; total as usize]);
+        let mut offset = 0;
+        for &(krate, name, size) in functions {
+            let mangled = format!("_ZN{}{}{}{}17h{}E", krate.len(), krate, name.len(), name, hash);
+            elf = elf.symbol(&mangled, STT_FUNC, ".text", offset, size);
+            offset += size;
+        }
+        elf.build()
+    }
+
+    #[test]
+    fn expansion_by_crate_and_outliers() {
+        let baseline = build(Elf::object(), "0123456789abcdef",
+                             &[("app", "main", 100), ("app", "parse", 50), ("core", "write", 40),
+                               ("app", "cpuid", 10)]);
+        let other = build(Elf::object().machine(EM_AARCH64), "fedcba9876543210",
+                          &[("app", "main", 120), ("app", "parse", 80), ("core", "write", 40),
+                            ("app", "hwcap", 6)]);
+        let report = isa(&baseline, "x86_64".to_string(), &other, "aarch64".to_string(), 2)
+            .unwrap();
+        assert_eq!((report.functions, report.baseline_size, report.other_size), (3, 190, 240));
+        assert!((report.factor - 240.0 / 190.0).abs() < 1e-9);
+        assert_eq!((report.baseline_only_size, report.other_only_size), (10, 6));
+        let crates: Vec<_> = report.crates.iter()
+            .map(|c| (&*c.name, c.functions, c.baseline_size, c.other_size, c.excess,
+                      c.baseline_only_size, c.other_only_size))
+            .collect();
+        assert_eq!(crates, vec![("app", 2, 150, 200, 11, 10, 6), ("core", 1, 40, 40, -11, 0, 0)]);
+        assert!((report.crates[0].factor - 200.0 / 150.0).abs() < 1e-9);
+        let outliers: Vec<_> = report.outliers.iter()
+            .map(|f| (&*f.name, &*f.krate, f.excess))
+            .collect();
+        assert_eq!(outliers, vec![("app::parse", "app", 17), ("core::write", "core", -11)]);
+    }
+
+    #[test]
+    fn ratios_and_excess() {
+        assert_eq!(ratio(3, 2), 1.5);
+        assert_eq!(ratio(3, 0), 0.0);
+        assert_eq!(excess(130, 100, 1.2), 10);
+        assert_eq!(excess(100, 100, 1.2), -20);
+        let buf = Elf::object().machine(EM_AARCH64).build();
+        assert_eq!(slices(&buf).unwrap(), vec![(arch(&buf).unwrap(), &buf[..])]);
+    }
+}
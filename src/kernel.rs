@@ -0,0 +1,234 @@
+use exit::UsageError;
+use failure::Error;
+use goblin::elf::header::ET_REL;
+use goblin::elf::Elf;
+use normalize::normalize_name;
+use section_records;
+use std::collections::BTreeMap;
+use Section;
+
+/// The groups of the kernel's own sections, by name prefix. Sections in none of them are
+/// grouped by their normalized names.
+const GROUPS: &[(&str, &str)] = &[
+    (".init.", "init"),
+    (".initcall", "init"),
+    (".con_initcall.init", "init"),
+    (".x86_cpu_dev.init", "init"),
+    (".exit.", "exit"),
+    (".exitcall.exit", "exit"),
+    (".data..percpu", "percpu"),
+    ("__ksymtab", "exports"),
+    ("___ksymtab", "exports"),
+    ("__kcrctab", "exports"),
+    ("___kcrctab", "exports"),
+    (".export_symbol", "exports"),
+    (".modinfo", "modinfo"),
+    ("__versions", "modinfo"),
+    (".gnu.linkonce.this_module", "modinfo"),
+    ("__param", "params"),
+    ("__modver", "params"),
+    ("__ex_table", "exception-tables"),
+    ("__bug_table", "bug-table"),
+    (".altinstr", "alternatives"),
+    (".parainstructions", "alternatives"),
+    (".smp_locks", "alternatives"),
+    (".retpoline_sites", "alternatives"),
+    (".return_sites", "alternatives"),
+    (".call_sites", "alternatives"),
+    (".ibt_endbr_seal", "alternatives"),
+    ("__jump_table", "static-keys"),
+    (".static_call", "static-keys"),
+    ("__tracepoint", "tracing"),
+    ("_ftrace", "tracing"),
+    ("__mcount_loc", "tracing"),
+    ("__patchable_function_entries", "tracing"),
+    ("__dyndbg", "tracing"),
+    ("__trace_printk_fmt", "tracing"),
+    ("__bpf_raw_tp_map", "tracing"),
+    (".orc_", "unwind"),
+];
+
+/// The sections of one group, in one category.
+#[derive(Clone, Debug, Serialize)]
+pub struct KernelGroup {
+    /// `init`, `exit`, `percpu`, `exports` and the like for the kernel's own sections, and the
+    /// normalized section name for the others.
+    pub name: String,
+    pub category: Section,
+    pub sections: u64,
+    pub size: u64,
+    /// The part of `size` that the kernel frees once initialization is done.
+    pub discarded: u64,
+}
+
+/// The sections of a kernel or kernel module, as emitted by `kernel --format json`.
+#[derive(Clone, Debug, Serialize)]
+pub struct KernelReport {
+    /// `module` for `.ko` files, or `vmlinux`.
+    pub kind: &'static str,
+    /// The groups, largest first.
+    pub groups: Vec<KernelGroup>,
+    /// The size of the loaded sections.
+    pub loaded_size: u64,
+    /// The size of the loaded sections that are freed after initialization.
+    pub init_size: u64,
+    /// The size that stays in memory: `loaded_size` less `init_size`.
+    pub resident_size: u64,
+    pub file_size: u64,
+}
+
+/// The kernel group of the section `name`, if it is one of the kernel's own.
+pub fn group(name: &str) -> Option<&'static str> {
+    GROUPS.iter()
+        .find(|&&(prefix, _)| name.starts_with(prefix))
+        .map(|&(_, group)| group)
+}
+
+/// The range of addresses that vmlinux frees after boot, from `__init_begin` to `__init_end`,
+/// if it has the symbols: it holds more than the `.init` sections, e.g. the alternatives and,
+/// on x86, the initial copy of the per-CPU data.
+fn init_range(elf: &Elf) -> Option<(u64, u64)> {
+    let address = |wanted: &str| {
+        elf.syms.iter()
+            .find(|sym| elf.strtab.get(sym.st_name).and_then(|name| name.ok()) == Some(wanted))
+            .map(|sym| sym.st_value)
+    };
+    Some((address("__init_begin")?, address("__init_end")?))
+}
+
+/// Group the sections of the kernel or kernel module `buf` by what the kernel uses them for,
+/// and sum the loaded sections that it frees after initialization: those in vmlinux's init
+/// range, or without the symbols that bound it, the `init` group, and the `.init` sections of
+/// modules, which the module loader frees once the module's init function returns.
+pub fn kernel(buf: &[u8]) -> Result<KernelReport, Error> {
+    let elf = match Elf::parse(buf) {
+        Ok(elf) => elf,
+        Err(_) => return Err(UsageError("kernel needs an ELF vmlinux or .ko".to_string()).into()),
+    };
+    let module = elf.header.e_type == ET_REL;
+    let range = if module { None } else { init_range(&elf) };
+
+    let mut groups: BTreeMap<(String, Section), KernelGroup> = BTreeMap::new();
+    let (mut loaded_size, mut init_size) = (0, 0);
+    for record in section_records(buf)? {
+        if record.size == 0 {
+            continue;
+        }
+        let kernel_group = group(&record.name);
+        let loaded = record.category != Section::Other;
+        let discarded = loaded && match (module, range, record.address) {
+            (true, _, _) => record.name.starts_with(".init"),
+            (false, Some((begin, end)), Some(address)) => address >= begin && address < end,
+            _ => kernel_group == Some("init"),
+        };
+        let name = kernel_group.unwrap_or_else(|| normalize_name(&record.name)).to_string();
+        let entry = groups.entry((name.clone(), record.category)).or_insert(KernelGroup {
+            name,
+            category: record.category,
+            sections: 0,
+            size: 0,
+            discarded: 0,
+        });
+        entry.sections += 1;
+        entry.size += record.size;
+        if loaded {
+            loaded_size += record.size;
+        }
+        if discarded {
+            entry.discarded += record.size;
+            init_size += record.size;
+        }
+    }
+    let mut groups: Vec<KernelGroup> = groups.into_values().collect();
+    groups.sort_by(|a, b| (b.size, &a.name).cmp(&(a.size, &b.name)));
+    Ok(KernelReport {
+        kind: if module { "module" } else { "vmlinux" },
+        groups,
+        loaded_size,
+        init_size,
+        resident_size: loaded_size - init_size,
+        file_size: buf.len() as u64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::elf::section_header::{SHF_ALLOC, SHF_EXECINSTR, SHF_WRITE, SHT_PROGBITS};
+    use goblin::elf::sym::STT_NOTYPE;
+    use testelf;
+
+    /// The groups of `report`, but for the string and symbol tables.
+    fn groups(report: &KernelReport) -> Vec<(&str, Section, u64, u64)> {
+        report.groups.iter()
+            .filter(|g| g.name != "metadata")
+            .map(|g| (&*g.name, g.category, g.size, g.discarded))
+            .collect()
+    }
+
+    #[test]
+    fn groups_by_prefix() {
+        assert_eq!(group(".init.text"), Some("init"));
+        assert_eq!(group(".initcall6.init"), Some("init"));
+        assert_eq!(group(".exit.data"), Some("exit"));
+        assert_eq!(group(".data..percpu..shared_aligned"), Some("percpu"));
+        assert_eq!(group("___ksymtab_gpl+kmalloc"), Some("exports"));
+        assert_eq!(group("__ex_table"), Some("exception-tables"));
+        assert_eq!(group(".orc_unwind_ip"), Some("unwind"));
+        assert_eq!(group(".text"), None);
+        assert_eq!(group(".initial"), None);
+    }
+
+    #[test]
+    fn modules_free_their_init_sections() {
+        let code = SHF_ALLOC | SHF_EXECINSTR;
+        let buf = testelf::Elf::object()
+            .section(".text", SHT_PROGBITS, code, &[0; 32])
+            .section(".init.text", SHT_PROGBITS, code, &[0; 16])
+            .section(".exit.text", SHT_PROGBITS, code, &[0; 8])
+            .section("__ksymtab_gpl", SHT_PROGBITS, SHF_ALLOC, &[0; 12])
+            .section(".data..percpu", SHT_PROGBITS, SHF_ALLOC | SHF_WRITE, &[0; 24])
+            .section(".modinfo", SHT_PROGBITS, 0, &[0; 10])
+            .build();
+        let report = kernel(&buf).unwrap();
+        assert_eq!(report.kind, "module");
+        assert_eq!(groups(&report), vec![("text", Section::Text, 32, 0),
+                                         ("percpu", Section::Data, 24, 0),
+                                         ("init", Section::Text, 16, 16),
+                                         ("exports", Section::Text, 12, 0),
+                                         ("modinfo", Section::Other, 10, 0),
+                                         ("exit", Section::Text, 8, 0)]);
+        assert_eq!((report.loaded_size, report.init_size, report.resident_size), (92, 16, 76));
+        assert_eq!(report.file_size, buf.len() as u64);
+
+        match kernel(b"not an ELF file") {
+            Err(err) => assert!(err.downcast_ref::<UsageError>().is_some()),
+            Ok(_) => panic!("not an ELF file"),
+        }
+    }
+
+    #[test]
+    fn vmlinux_frees_its_init_range() {
+        let vmlinux = || {
+            testelf::Elf::executable()
+                .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &[0; 32])
+                .section(".init.text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &[0; 16])
+                .section(".altinstructions", SHT_PROGBITS, SHF_ALLOC, &[0; 8])
+                .section(".init.data", SHT_PROGBITS, SHF_ALLOC | SHF_WRITE, &[0; 8])
+                .section(".data", SHT_PROGBITS, SHF_ALLOC | SHF_WRITE, &[0; 8])
+        };
+        let buf = vmlinux()
+            .symbol("__init_begin", STT_NOTYPE, ".init.text", 0, 0)
+            .symbol("__init_end", STT_NOTYPE, ".data", 0, 0)
+            .build();
+        let report = kernel(&buf).unwrap();
+        assert_eq!(report.kind, "vmlinux");
+        assert_eq!((report.loaded_size, report.init_size), (72, 32));
+        let alternatives = report.groups.iter().find(|g| g.name == "alternatives").unwrap();
+        assert_eq!(alternatives.discarded, 8);
+
+        // Without the symbols, only the init group is freed.
+        let report = kernel(&vmlinux().build()).unwrap();
+        assert_eq!((report.loaded_size, report.init_size, report.resident_size), (72, 24, 48));
+    }
+}
@@ -0,0 +1,149 @@
+use failure::Error;
+use regex::Regex;
+use rustc_demangle;
+use std::fs;
+use std::path::Path;
+use symbols::Symbol;
+use toml;
+
+/// The label of symbols that no rule matches.
+pub const UNLABELED: &str = "(unlabeled)";
+
+/// One rule of a labels file, as written in TOML.
+#[derive(Deserialize)]
+struct RawRule {
+    label: String,
+    symbol: Option<String>,
+    section: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RawLabels {
+    #[serde(default)]
+    rule: Vec<RawRule>,
+}
+
+/// A rule assigning `label` to the symbols whose demangled name matches `symbol` and whose
+/// section matches `section`. A rule without either pattern matches everything.
+struct Rule {
+    label: String,
+    symbol: Option<Regex>,
+    section: Option<Regex>,
+}
+
+/// Team or component labels for symbols, read from a TOML file of rules like:
+///
+/// ```toml
+/// [[rule]]
+/// label = "media"
+/// symbol = "^mozilla::(dom::)?media::"
+///
+/// [[rule]]
+/// label = "networking"
+/// section = "^\\.text\\.necko"
+/// ```
+///
+/// The first rule that matches a symbol gives its label.
+pub struct Labels {
+    rules: Vec<Rule>,
+}
+
+impl Labels {
+    /// Read the labels file at `path`.
+    pub fn load(path: &Path) -> Result<Labels, Error> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format_err!("{}: {}", path.display(), e))?;
+        let raw: RawLabels = toml::from_str(&contents)
+            .map_err(|e| format_err!("{}: {}", path.display(), e))?;
+        let compile = |pattern: Option<String>| -> Result<Option<Regex>, Error> {
+            match pattern {
+                Some(p) => Ok(Some(Regex::new(&p)
+                    .map_err(|e| format_err!("{}: {}", path.display(), e))?)),
+                None => Ok(None),
+            }
+        };
+        let mut rules = Vec::new();
+        for rule in raw.rule {
+            rules.push(Rule {
+                label: rule.label,
+                symbol: compile(rule.symbol)?,
+                section: compile(rule.section)?,
+            });
+        }
+        Ok(Labels { rules })
+    }
+
+    /// The label of `sym`, or `UNLABELED`.
+    pub fn label(&self, sym: &Symbol) -> &str {
+        let name = format!("{:#}", rustc_demangle::demangle(&sym.name));
+        let section = sym.section.as_deref().unwrap_or("");
+        self.rules.iter()
+            .find(|rule| {
+                rule.symbol.as_ref().is_none_or(|re| re.is_match(&name)) &&
+                    rule.section.as_ref().is_none_or(|re| re.is_match(section))
+            })
+            .map_or(UNLABELED, |rule| &rule.label)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    fn symbol(name: &str, section: &str) -> Symbol {
+        Symbol {
+            name: name.to_string(),
+            size: 1,
+            address: 0,
+            section: Some(section.to_string()),
+            offset: None,
+            code: true,
+            writable: false,
+            inferred: false,
+        }
+    }
+
+    fn load(name: &str, contents: &str) -> Result<Labels, Error> {
+        let path = env::temp_dir().join(format!("rust-size-{}-{}.toml", process::id(), name));
+        fs::write(&path, contents).unwrap();
+        let labels = Labels::load(&path);
+        fs::remove_file(&path).unwrap();
+        labels
+    }
+
+    #[test]
+    fn first_matching_rule_labels() {
+        let labels = load("labels", r#"
+            [[rule]]
+            label = "media"
+            symbol = "^mozilla::(dom::)?media::"
+
+            [[rule]]
+            label = "networking"
+            section = "^\\.text\\.necko"
+
+            [[rule]]
+            label = "fmt"
+            symbol = "^core::fmt::"
+            section = "^\\.text"
+        "#).unwrap();
+        let media = "_ZN7mozilla3dom5media6Decode17h0123456789abcdefE";
+        assert_eq!(labels.label(&symbol(media, ".text.necko")), "media");
+        assert_eq!(labels.label(&symbol("http_get", ".text.necko.http")), "networking");
+        let fmt = "_ZN4core3fmt5write17h0123456789abcdefE";
+        assert_eq!(labels.label(&symbol(fmt, ".text")), "fmt");
+        assert_eq!(labels.label(&symbol(fmt, ".rodata")), UNLABELED);
+        assert_eq!(load("empty", "").unwrap().label(&symbol("f", ".text")), UNLABELED);
+    }
+
+    #[test]
+    fn bad_rules_name_the_file() {
+        for (name, contents) in &[("regex", "[[rule]]\nlabel = \"x\"\nsymbol = \"(\"\n"),
+                                  ("toml", "[[rule]]\nsymbol = \"x\"\n")] {
+            let err = load(name, contents).err().unwrap().to_string();
+            assert!(err.contains(&format!("-{}.toml: ", name)), "{}", err);
+        }
+    }
+}
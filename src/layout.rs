@@ -0,0 +1,207 @@
+use failure::Error;
+use std::collections::HashMap;
+use symbols::{self, canonical_name};
+
+/// A function that isn't where it was relative to the others.
+#[derive(Clone, Debug, Serialize)]
+pub struct MovedFunction {
+    pub name: String,
+    pub size: u64,
+    pub old_address: u64,
+    pub new_address: u64,
+}
+
+/// How much of the code of a build was laid out differently from the build before, as emitted
+/// by `layout --format json`, and in `diff --symbols`.
+#[derive(Clone, Debug, Serialize)]
+pub struct LayoutChurn {
+    /// The size of the functions of the new build, counting aliases once.
+    pub code_size: u64,
+    /// The functions that both builds have, and their size in the new one.
+    pub functions: u64,
+    pub size: u64,
+    /// The functions that both builds have that are out of the order of the rest, the fewest
+    /// bytes of them that can be: with them taken out, the others are in the same order.
+    pub moved: u64,
+    pub moved_size: u64,
+    /// The size of the functions that both builds have that are at a different address, like
+    /// everything after a function that grew.
+    pub shifted_size: u64,
+    /// The size of the new build's functions that the old one doesn't have, and of the old
+    /// build's that the new one doesn't.
+    pub added_size: u64,
+    pub removed_size: u64,
+    /// `moved_size` and `added_size` as a percentage of `code_size`: how much of the new code
+    /// isn't laid out as before, which makes for larger differential updates and cold caches.
+    pub churn: f64,
+    /// The moved functions, largest first.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub moved_functions: Vec<MovedFunction>,
+}
+
+/// A function of a build, by canonical name and which of the functions with that name it is
+/// in address order, with its size and address.
+type Function = ((String, usize), u64, u64);
+
+/// The functions of `buf` in address order, counting aliases once.
+fn functions(buf: &[u8]) -> Result<Vec<Function>, Error> {
+    let mut syms: Vec<_> = symbols::symbols(buf)?.into_iter().filter(|sym| sym.code).collect();
+    syms.sort_by(|a, b| {
+        (&a.section, a.address, b.size, &a.name).cmp(&(&b.section, b.address, a.size, &b.name))
+    });
+    syms.dedup_by(|a, b| a.section == b.section && a.address == b.address);
+    syms.sort_by(|a, b| (a.address, &a.section).cmp(&(b.address, &b.section)));
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    Ok(syms.into_iter().map(|sym| {
+        let name = canonical_name(&sym.name);
+        let count = seen.entry(name.clone()).or_insert(0);
+        *count += 1;
+        ((name, *count - 1), sym.size, sym.address)
+    }).collect())
+}
+
+/// The indices into `sequence` of its increasing subsequence of the largest total weight,
+/// where each element is a rank below `ranks` and a weight.
+fn heaviest_increasing(sequence: &[(usize, u64)], ranks: usize) -> Vec<usize> {
+    // A Fenwick tree of the heaviest subsequence ending at each rank, with where it ends.
+    let mut tree: Vec<(u64, Option<usize>)> = vec![(0, None); ranks + 1];
+    let mut previous = vec![None; sequence.len()];
+    let mut best = (0, None);
+    for (i, &(rank, weight)) in sequence.iter().enumerate() {
+        let mut before = (0, None);
+        let mut j = rank;
+        while j > 0 {
+            before = heavier(before, tree[j]);
+            j &= j - 1;
+        }
+        previous[i] = before.1;
+        let ending = (before.0 + weight, Some(i));
+        best = heavier(best, ending);
+        let mut j = rank + 1;
+        while j < tree.len() {
+            tree[j] = heavier(tree[j], ending);
+            j += j & j.wrapping_neg();
+        }
+    }
+    let mut indices = Vec::new();
+    let mut end = best.1;
+    while let Some(i) = end {
+        indices.push(i);
+        end = previous[i];
+    }
+    indices.reverse();
+    indices
+}
+
+/// The heavier of `a` and `b`, or `a` if they weigh the same.
+fn heavier(a: (u64, Option<usize>), b: (u64, Option<usize>)) -> (u64, Option<usize>) {
+    if b.0 > a.0 { b } else { a }
+}
+
+/// Compare the order of the functions of `old` and `new`, two builds of the same program,
+/// listing the `top` largest of those that moved.
+pub fn churn(old: &[u8], new: &[u8], top: usize) -> Result<LayoutChurn, Error> {
+    let old = functions(old)?;
+    let new = functions(new)?;
+    let mut old_by_name: HashMap<&(String, usize), (usize, u64, u64)> = old.iter()
+        .enumerate()
+        .map(|(rank, (key, size, address))| (key, (rank, *size, *address)))
+        .collect();
+
+    let code_size = new.iter().map(|f| f.1).sum();
+    let mut matched = Vec::new();
+    let mut added_size = 0;
+    for (key, size, address) in &new {
+        match old_by_name.remove(key) {
+            Some((rank, _, old_address)) => {
+                matched.push((rank, *size, old_address, key, *address));
+            }
+            None => added_size += size,
+        }
+    }
+    let removed_size = old_by_name.values().map(|f| f.1).sum();
+
+    let sequence: Vec<(usize, u64)> = matched.iter().map(|f| (f.0, f.1)).collect();
+    let mut in_order = vec![false; matched.len()];
+    for i in heaviest_increasing(&sequence, old.len()) {
+        in_order[i] = true;
+    }
+    let mut moved_functions: Vec<MovedFunction> = matched.iter().zip(&in_order)
+        .filter(|&(_, &in_order)| !in_order)
+        .map(|(&(_, size, old_address, key, new_address), _)| MovedFunction {
+            name: key.0.clone(),
+            size,
+            old_address,
+            new_address,
+        })
+        .collect();
+    let moved = moved_functions.len() as u64;
+    let moved_size = moved_functions.iter().map(|f| f.size).sum();
+    moved_functions.sort_by(|a, b| (b.size, &a.name).cmp(&(a.size, &b.name)));
+    moved_functions.truncate(top);
+    Ok(LayoutChurn {
+        code_size,
+        functions: matched.len() as u64,
+        size: matched.iter().map(|f| f.1).sum(),
+        moved,
+        moved_size,
+        shifted_size: matched.iter().filter(|f| f.2 != f.4).map(|f| f.1).sum(),
+        added_size,
+        removed_size,
+        churn: if code_size == 0 {
+            0.0
+        } else {
+            (moved_size + added_size) as f64 * 100.0 / code_size as f64
+        },
+        moved_functions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::elf::section_header::{SHF_ALLOC, SHF_EXECINSTR, SHT_PROGBITS};
+    use goblin::elf::sym::STT_FUNC;
+    use testelf::Elf;
+
+    /// An object whose `.text` holds `functions`, as (name, size), in order.
+    fn build(functions: &[(&str, u64)]) -> Vec<u8> {
+        let total = functions.iter().map(|&(_, size)| size).sum::<u64>();
+        let mut elf = Elf::object()
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &vec![0; total as usize]);
+        let mut offset = 0;
+        for &(name, size) in functions {
+            elf = elf.symbol(name, STT_FUNC, ".text", offset, size);
+            offset += size;
+        }
+        elf.build()
+    }
+
+    #[test]
+    fn heaviest_increasing_subsequences() {
+        assert_eq!(heaviest_increasing(&[(2, 1), (0, 5), (1, 5), (3, 1)], 4), vec![1, 2, 3]);
+        assert_eq!(heaviest_increasing(&[(1, 10), (0, 1), (2, 1)], 3), vec![0, 2]);
+        assert_eq!(heaviest_increasing(&[(1, 1), (1, 2)], 2), vec![1]);
+        assert!(heaviest_increasing(&[], 0).is_empty());
+    }
+
+    #[test]
+    fn moved_shifted_added_and_removed() {
+        let old = build(&[("a", 16), ("b", 32), ("c", 8), ("d", 8)]);
+        let new = build(&[("b", 32), ("a", 16), ("c", 12), ("e", 4)]);
+        let churn = churn(&old, &new, 10).unwrap();
+        assert_eq!((churn.code_size, churn.functions, churn.size), (64, 3, 60));
+        // Moving `a` keeps the larger `b` in order.
+        assert_eq!((churn.moved, churn.moved_size), (1, 16));
+        let moved: Vec<_> = churn.moved_functions.iter()
+            .map(|f| (&*f.name, f.size, f.old_address, f.new_address))
+            .collect();
+        assert_eq!(moved, vec![("a", 16, 0, 32)]);
+        assert_eq!(churn.shifted_size, 32 + 16);
+        assert_eq!((churn.added_size, churn.removed_size), (4, 8));
+        assert_eq!(churn.churn, 31.25);
+
+        let same = super::churn(&old, &old, 10).unwrap();
+        assert_eq!((same.moved, same.shifted_size, same.churn), (0, 0, 0.0));
+    }
+}
@@ -0,0 +1,141 @@
+use goblin::mach::load_command::CommandVariant;
+use goblin::mach::MachO;
+use Section;
+use SectionRecord;
+
+/// `LC_DYLD_EXPORTS_TRIE`, which newer linkers use for the export trie instead of
+/// `LC_DYLD_INFO_ONLY`. goblin doesn't know it, so it is parsed by hand.
+const LC_DYLD_EXPORTS_TRIE: u32 = 0x8000_0033;
+
+fn record(name: &str, offset: u32, size: u64, category: Section) -> SectionRecord {
+    let mut record = SectionRecord::synthetic(name, size, category);
+    record.segment = Some("__LINKEDIT".to_string());
+    record.offset = Some(offset as u64);
+    record
+}
+
+/// Return records for the tables in the `__LINKEDIT` segment of `mach` (read from `buf`) that
+/// stripping affects: the export trie, which dyld needs and so counts toward totals, and the
+/// symbol table split as `LC_DYSYMTAB` partitions it into local, external and undefined
+/// symbols, with the string table and the indirect symbol table.
+pub fn records(mach: &MachO, buf: &[u8]) -> Vec<SectionRecord> {
+    let u32_at = |offset: usize| buf.get(offset..offset + 4).map(|b| {
+        let bytes = [b[0], b[1], b[2], b[3]];
+        if mach.little_endian { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) }
+    });
+    let nlist_size: u32 = if mach.is_64 { 16 } else { 12 };
+    let mut records = Vec::new();
+    let (mut symtab, mut dysymtab) = (None, None);
+    for lc in &mach.load_commands {
+        match lc.command {
+            CommandVariant::DyldInfo(ref cmd) | CommandVariant::DyldInfoOnly(ref cmd)
+                if cmd.export_size > 0 => {
+                records.push(record("export_table", cmd.export_off, cmd.export_size as u64,
+                                    Section::Data));
+            }
+            CommandVariant::Unimplemented(ref header) if header.cmd == LC_DYLD_EXPORTS_TRIE => {
+                // A linkedit_data_command: cmd, cmdsize, dataoff, datasize.
+                if let (Some(off), Some(size)) = (u32_at(lc.offset + 8), u32_at(lc.offset + 12)) {
+                    records.push(record("export_table", off, size as u64, Section::Data));
+                }
+            }
+            CommandVariant::Symtab(cmd) => symtab = Some(cmd),
+            CommandVariant::Dysymtab(cmd) => dysymtab = Some(cmd),
+            _ => {},
+        }
+    }
+    if let Some(symtab) = symtab {
+        match dysymtab {
+            Some(cmd) => {
+                let partitions = [("symtab_local", cmd.ilocalsym, cmd.nlocalsym),
+                                  ("symtab_external", cmd.iextdefsym, cmd.nextdefsym),
+                                  ("symtab_undefined", cmd.iundefsym, cmd.nundefsym)];
+                for &(name, first, count) in &partitions {
+                    if count > 0 {
+                        records.push(record(name, symtab.symoff + first * nlist_size,
+                                            (count * nlist_size) as u64, Section::Other));
+                    }
+                }
+                if cmd.nindirectsyms > 0 {
+                    records.push(record("indirect_symtab", cmd.indirectsymoff,
+                                        cmd.nindirectsyms as u64 * 4, Section::Other));
+                }
+            }
+            // Object files may not have an LC_DYSYMTAB.
+            None if symtab.nsyms > 0 => {
+                records.push(record("symtab", symtab.symoff, (symtab.nsyms * nlist_size) as u64,
+                                    Section::Other));
+            }
+            None => {},
+        }
+        if symtab.strsize > 0 {
+            records.push(record("symtab_strings", symtab.stroff, symtab.strsize as u64,
+                                Section::Other));
+        }
+    }
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::mach::Mach;
+    use testmacho::MachO as Builder;
+
+    fn linkedit_records(buf: &[u8]) -> Vec<(String, u64, u64, Section)> {
+        let mach = match Mach::parse(buf).unwrap() {
+            Mach::Binary(mach) => mach,
+            Mach::Fat(_) => unreachable!(),
+        };
+        records(&mach, buf).into_iter()
+            .map(|r| (r.name, r.offset.unwrap(), r.size, r.category))
+            .collect()
+    }
+
+    #[test]
+    fn symbol_table_partitions_and_exports() {
+        let buf = Builder::executable()
+            .section("__TEXT", "__text", 0, &[0; 32])
+            .local("_local", "__text", 0)
+            .symbol("_a", "__text", 8)
+            .symbol("_b", "__text", 16)
+            .undefined("_undef")
+            .dysymtab(3)
+            .dyld_info(&[0; 8])
+            .build();
+        let records = linkedit_records(&buf);
+        let layout: Vec<_> = records.iter().map(|r| (r.0.as_str(), r.2, r.3)).collect();
+        assert_eq!(layout, vec![("export_table", 8, Section::Data),
+                                ("symtab_local", 16, Section::Other),
+                                ("symtab_external", 32, Section::Other),
+                                ("symtab_undefined", 16, Section::Other),
+                                ("indirect_symtab", 12, Section::Other),
+                                // " \0", then the four names and their terminators.
+                                ("symtab_strings", 22, Section::Other)]);
+        // The symbol table follows the export trie, and the three partitions cover it in order.
+        assert_eq!(records[1].1, records[0].1 + 8);
+        assert_eq!(records[2].1, records[1].1 + 16);
+        assert_eq!(records[3].1, records[2].1 + 32);
+        assert!(records.iter().all(|r| r.1 + r.2 <= buf.len() as u64));
+    }
+
+    #[test]
+    fn exports_trie_command_and_objects() {
+        let buf = Builder::executable()
+            .section("__TEXT", "__text", 0, &[0; 16])
+            .linkedit_data(LC_DYLD_EXPORTS_TRIE, &[1; 24])
+            .build();
+        let records = linkedit_records(&buf);
+        assert_eq!(records.len(), 1);
+        assert_eq!((records[0].0.as_str(), records[0].2), ("export_table", 24));
+        assert_eq!(&buf[records[0].1 as usize..][..24], &[1; 24][..]);
+
+        // Without an LC_DYSYMTAB, the symbol table is one record.
+        let buf = Builder::object()
+            .section("__TEXT", "__text", 0, &[0; 16])
+            .symbol("_f", "__text", 0)
+            .build();
+        let names: Vec<_> = linkedit_records(&buf).into_iter().map(|r| (r.0, r.2)).collect();
+        assert_eq!(names, vec![("symtab".to_string(), 16), ("symtab_strings".to_string(), 5)]);
+    }
+}
@@ -0,0 +1,430 @@
+use failure::Error;
+use map_mach_name;
+use normalize::normalize_name;
+use std::borrow::Cow;
+use Section;
+use SectionRecord;
+
+/// The headings that GNU ld starts its map files with, depending on what it has to say.
+const GNU_HEADINGS: &[&str] = &[
+    "Archive member included to satisfy reference by file (symbol)",
+    "As-needed library included to satisfy reference by file (symbol)",
+    "Allocating common symbols",
+    "Discarded input sections",
+    "Memory Configuration",
+    "Merging program properties",
+    "Linker script and memory map",
+];
+
+/// The sections of ELF and PE files that aren't loaded into memory, other than debug info.
+const NON_ALLOC: &[&str] = &[".comment", ".symtab", ".strtab", ".shstrtab", ".gnu_debuglink",
+                             ".gnu.build.attributes", ".gnu.attributes", ".ARM.attributes",
+                             ".riscv.attributes", ".GCC.command.line", ".llvm_addrsig",
+                             ".note.GNU-stack", ".line"];
+
+/// The linker that wrote a map file.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Flavor {
+    /// GNU ld's `-Map`, as gold also writes.
+    Gnu,
+    /// lld's `-Map` for ELF and `/lldmap` for PE.
+    Lld,
+    /// link.exe's and lld-link's `/MAP`.
+    Msvc,
+    /// ld64's and lld's `-map` for Mach-O.
+    Ld64,
+}
+
+/// The start of `buf` as text, enough to recognize a map file by its header.
+fn head(buf: &[u8]) -> Cow<'_, str> {
+    String::from_utf8_lossy(&buf[..buf.len().min(4096)])
+}
+
+fn flavor(buf: &[u8]) -> Option<Flavor> {
+    if buf.starts_with(b"# Path: ") {
+        return Some(Flavor::Ld64);
+    }
+    let head = head(buf);
+    let first = head.lines().map(str::trim).find(|line| !line.is_empty())?;
+    let columns: Vec<&str> = first.split_whitespace().collect();
+    if columns == ["VMA", "LMA", "Size", "Align", "Out", "In", "Symbol"] ||
+        columns == ["Address", "Size", "Align", "Out", "In", "Symbol"] {
+        return Some(Flavor::Lld);
+    }
+    if GNU_HEADINGS.iter().any(|heading| first.starts_with(heading)) {
+        return Some(Flavor::Gnu);
+    }
+    // The first line is the name of the image.
+    if head.starts_with(' ') && head.contains("\n Timestamp is ") &&
+        head.contains("\n Preferred load address is ") {
+        return Some(Flavor::Msvc);
+    }
+    None
+}
+
+/// Whether `buf` looks like a linker map file.
+pub fn is_map(buf: &[u8]) -> bool {
+    flavor(buf).is_some()
+}
+
+/// The name of the linker that wrote the map file `buf`.
+pub fn linker(buf: &[u8]) -> Option<&'static str> {
+    flavor(buf).map(|flavor| match flavor {
+        Flavor::Gnu => "GNU ld",
+        Flavor::Lld => "lld",
+        Flavor::Msvc => "link.exe",
+        Flavor::Ld64 => "ld64",
+    })
+}
+
+/// The architecture of the output that the map file `buf` describes, as `arch::arch` names
+/// it, if the map records it: ld64 maps do, and GNU ld maps name the BFD target.
+pub fn arch(buf: &[u8]) -> String {
+    let text = String::from_utf8_lossy(buf);
+    let arch = match flavor(buf) {
+        Some(Flavor::Ld64) => text.lines().find_map(|line| line.strip_prefix("# Arch: "))
+            .map(|arch| match arch.trim() {
+                arch if arch.starts_with("arm64") => "aarch64",
+                arch if arch.starts_with("arm") => "arm",
+                "i386" => "x86",
+                "x86_64" | "x86_64h" => "x86_64",
+                _ => "unknown",
+            }),
+        Some(Flavor::Gnu) => text.lines().rev().find_map(|line| line.strip_prefix("OUTPUT("))
+            .and_then(|output| output.trim_end_matches(')').rsplit(' ').next())
+            .map(|target| match target {
+                target if target.contains("x86-64") => "x86_64",
+                target if target.contains("i386") => "x86",
+                target if target.contains("aarch64") => "aarch64",
+                target if target.contains("arm") => "arm",
+                target if target.contains("riscv") && target.starts_with("elf64") => "riscv64",
+                target if target.contains("riscv") => "riscv32",
+                _ => "unknown",
+            }),
+        _ => None,
+    };
+    arch.unwrap_or("unknown").to_string()
+}
+
+/// The category of the ELF or PE output section `name`, which maps don't give flags for, by
+/// what the section of that name usually holds.
+fn category(name: &str) -> Section {
+    if name.starts_with(".tbss") {
+        return Section::Bss;
+    }
+    if NON_ALLOC.contains(&name) || name.starts_with(".debug") || name.starts_with(".stab") {
+        return Section::Other;
+    }
+    match normalize_name(name) {
+        "bss" => Section::Bss,
+        "data" | "relro" | "tls" | "got" | "init-array" | "imports" => Section::Data,
+        "dynamic" if name == ".dynamic" => Section::Data,
+        "debug" => Section::Other,
+        _ => Section::Text,
+    }
+}
+
+fn hex(s: &str) -> Option<u64> {
+    u64::from_str_radix(s.trim_start_matches("0x").trim_end_matches('H'), 16).ok()
+}
+
+/// The output sections of a GNU ld map, from its memory map. Output sections start at the
+/// start of a line, with their address and size after them, or on the next line if the name
+/// is long; input sections and assignments are indented.
+fn gnu_sections(text: &str) -> Vec<SectionRecord> {
+    let mut vec = Vec::new();
+    let mut lines = text.lines()
+        .skip_while(|line| !line.starts_with("Linker script and memory map"))
+        .peekable();
+    while let Some(line) = lines.next() {
+        if line.starts_with(char::is_whitespace) {
+            continue;
+        }
+        let mut words = line.split_whitespace();
+        let name = match words.next() {
+            Some(name) if name != "/DISCARD/" => name,
+            _ => continue,
+        };
+        let mut words: Vec<&str> = words.collect();
+        if words.is_empty() {
+            match lines.peek() {
+                Some(next) if next.starts_with(char::is_whitespace) => {
+                    words = next.split_whitespace().collect();
+                }
+                _ => continue,
+            }
+        }
+        let (address, size) = match words.get(..2) {
+            Some(&[address, size]) if address.starts_with("0x") && size.starts_with("0x") => {
+                (hex(address), hex(size))
+            }
+            _ => continue,
+        };
+        if let (Some(address), Some(size)) = (address, size) {
+            if size > 0 {
+                let mut record = SectionRecord::synthetic(name, size, category(name));
+                record.address = Some(address);
+                vec.push(record);
+            }
+        }
+    }
+    vec
+}
+
+/// The output sections of an lld map, which are the lines with a name in the `Out` column.
+fn lld_sections(text: &str) -> Vec<SectionRecord> {
+    let mut lines = text.lines().skip_while(|line| line.trim().is_empty());
+    let header = lines.next().unwrap_or("");
+    let column = match header.find(" Out ") {
+        Some(i) => i + 1,
+        None => return Vec::new(),
+    };
+    let mut vec = Vec::new();
+    for line in lines {
+        if line.len() <= column || !line.is_char_boundary(column) ||
+            line[column..].starts_with(' ') {
+            continue;
+        }
+        let name = line[column..].trim();
+        // `VMA LMA Size Align` for ELF, `Address Size Align` for PE; assignments have names
+        // like `. = ALIGN(16)`.
+        let numbers: Vec<&str> = line[..column].split_whitespace().collect();
+        let (address, size, align) = match numbers[..] {
+            [address, _, size, align] | [address, size, align] => (address, size, align),
+            _ => continue,
+        };
+        if name.contains('=') {
+            continue;
+        }
+        if let (Some(address), Some(size)) = (hex(address), hex(size)) {
+            if size > 0 {
+                let mut record = SectionRecord::synthetic(name, size, category(name));
+                record.address = Some(address);
+                record.alignment = align.parse().ok();
+                vec.push(record);
+            }
+        }
+    }
+    vec
+}
+
+/// The sections of a link.exe map, summed from the contributions to each of them (like
+/// `.text$mn`) that it lists under `Start Length Name Class`.
+fn msvc_sections(text: &str) -> Vec<SectionRecord> {
+    let lines = text.lines()
+        .skip_while(|line| !line.trim_start().starts_with("Start"))
+        .skip(1)
+        .take_while(|line| !line.trim().is_empty());
+    let mut vec: Vec<SectionRecord> = Vec::new();
+    for line in lines {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (size, name) = match words[..] {
+            [_, size, name, _] => (size, name),
+            _ => continue,
+        };
+        let name = name.split('$').next().unwrap_or(name);
+        let size = match hex(size) {
+            Some(size) => size,
+            None => continue,
+        };
+        match vec.iter_mut().find(|record| record.name == name) {
+            Some(record) => record.size += size,
+            None => vec.push(SectionRecord::synthetic(name, size, category(name))),
+        }
+    }
+    vec
+}
+
+/// The sections of an ld64 map, from its `# Sections:` table, classified as in Mach-O files.
+fn ld64_sections(text: &str) -> Vec<SectionRecord> {
+    let lines = text.lines()
+        .skip_while(|line| !line.starts_with("# Sections:"))
+        .skip(1)
+        .take_while(|line| !line.starts_with("# Symbols:"));
+    let mut vec = Vec::new();
+    for line in lines {
+        if line.starts_with('#') {
+            continue;
+        }
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (address, size, segment, section) = match words[..] {
+            [address, size, segment, section] => (address, size, segment, section),
+            _ => continue,
+        };
+        if let (Some(address), Some(size)) = (hex(address), hex(size)) {
+            let category = if section == "__bss" {
+                Section::Bss
+            } else if segment == "__DATA" {
+                Section::Data
+            } else if segment == "__TEXT" {
+                Section::Text
+            } else {
+                Section::Other
+            };
+            let mut record = SectionRecord::synthetic(&map_mach_name(segment, section), size,
+                                                      category);
+            record.segment = Some(segment.to_string());
+            record.address = Some(address);
+            vec.push(record);
+        }
+    }
+    vec
+}
+
+/// Parse `buf` as a linker map file, from GNU ld, lld, link.exe or ld64, and return a record
+/// for each output section, so that size can be tracked from the map alone.
+///
+/// Maps don't record section flags, so ELF and PE sections are classified by their names,
+/// which works for the sections that linkers create by default; Mach-O sections are classified
+/// by their segments, as in Mach-O files. link.exe maps only list the contributions to each
+/// section, which are summed, and don't include the sections that the linker generates, like
+/// `.reloc`.
+///
+/// Sections have addresses, except in link.exe maps, but no file offsets.
+pub fn sections(buf: &[u8]) -> Result<Vec<SectionRecord>, Error> {
+    let text = String::from_utf8_lossy(buf);
+    Ok(match flavor(buf) {
+        Some(Flavor::Gnu) => gnu_sections(&text),
+        Some(Flavor::Lld) => lld_sections(&text),
+        Some(Flavor::Msvc) => msvc_sections(&text),
+        Some(Flavor::Ld64) => ld64_sections(&text),
+        None => bail!("Not a linker map file"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records(buf: &str) -> Vec<(String, Section, u64, Option<u64>)> {
+        sections(buf.as_bytes()).unwrap().into_iter()
+            .map(|r| (r.name, r.category, r.size, r.address))
+            .collect()
+    }
+
+    #[test]
+    fn gnu_ld_maps() {
+        let map = "\
+Archive member included to satisfy reference by file (symbol)
+
+libc.a(printf.o)              main.o (printf)
+
+Linker script and memory map
+
+LOAD main.o
+                0x0000000000400000                . = 0x400000
+
+.text           0x0000000000401000      0x120
+ *(.text)
+ .text          0x0000000000401000      0x120 main.o
+
+.rodata.a_rather_long_name
+                0x0000000000402000       0x30
+ .rodata        0x0000000000402000       0x30 main.o
+
+.bss            0x0000000000404000       0x40
+.comment        0x0000000000000000       0x2b
+.note.empty     0x0000000000405000        0x0
+
+/DISCARD/
+ *(.eh_frame)
+OUTPUT(a.out elf64-x86-64)
+";
+        assert_eq!(linker(map.as_bytes()), Some("GNU ld"));
+        assert_eq!(arch(map.as_bytes()), "x86_64");
+        assert_eq!(records(map), vec![
+            (".text".to_string(), Section::Text, 0x120, Some(0x401000)),
+            (".rodata.a_rather_long_name".to_string(), Section::Text, 0x30, Some(0x402000)),
+            (".bss".to_string(), Section::Bss, 0x40, Some(0x404000)),
+            (".comment".to_string(), Section::Other, 0x2b, Some(0)),
+        ]);
+    }
+
+    #[test]
+    fn lld_maps() {
+        let line = |vma: &str, size: &str, align: &str, rest: &str| {
+            format!("{:>16} {:>16} {:>8} {:>5} {}\n", vma, vma, size, align, rest)
+        };
+        let map = [
+            format!("{:>16} {:>16} {:>8} {:>5} Out     In      Symbol\n", "VMA", "LMA", "Size",
+                    "Align"),
+            line("2001c8", "40", "8", ".rodata"),
+            line("2001c8", "40", "1", "        main.o:(.rodata)"),
+            line("201210", "100", "16", ".text"),
+            line("201210", "0", "1", "                main"),
+            line("201310", "0", "16", ". = ALIGN(16)"),
+            line("203000", "10", "8", ".bss"),
+            line("0", "30", "1", ".comment"),
+        ].concat();
+        assert_eq!(linker(map.as_bytes()), Some("lld"));
+        assert_eq!(arch(map.as_bytes()), "unknown");
+        assert_eq!(records(&map), vec![
+            (".rodata".to_string(), Section::Text, 0x40, Some(0x2001c8)),
+            (".text".to_string(), Section::Text, 0x100, Some(0x201210)),
+            (".bss".to_string(), Section::Bss, 0x10, Some(0x203000)),
+            (".comment".to_string(), Section::Other, 0x30, Some(0)),
+        ]);
+        let alignments: Vec<_> = sections(map.as_bytes()).unwrap().into_iter()
+            .map(|r| r.alignment)
+            .collect();
+        assert_eq!(alignments, vec![Some(8), Some(16), Some(8), Some(1)]);
+    }
+
+    #[test]
+    fn link_exe_maps_sum_contributions() {
+        let map = " app
+
+ Timestamp is 5f000000 (Sat Jul  4 00:00:00 2020)
+
+ Preferred load address is 0000000140000000
+
+ Start         Length     Name                   Class
+ 0001:00000000 00001000H .text$mn                CODE
+ 0001:00001000 00000200H .text$x                 CODE
+ 0002:00000000 00000100H .rdata                  DATA
+ 0003:00000000 00000080H .data                   DATA
+ 0003:00000080 00000040H .bss                    DATA
+
+  Address         Publics by Value              Rva+Base               Lib:Object
+";
+        assert_eq!(linker(map.as_bytes()), Some("link.exe"));
+        assert_eq!(records(map), vec![
+            (".text".to_string(), Section::Text, 0x1200, None),
+            (".rdata".to_string(), Section::Text, 0x100, None),
+            (".data".to_string(), Section::Data, 0x80, None),
+            (".bss".to_string(), Section::Bss, 0x40, None),
+        ]);
+    }
+
+    #[test]
+    fn ld64_maps() {
+        let map = "\
+# Path: /tmp/a.out
+# Arch: arm64
+# Object files:
+[  0] linker synthesized
+# Sections:
+# Address\tSize    \tSegment\tSection
+0x100003F90\t0x00000020\t__TEXT\t__text
+0x100004000\t0x00000008\t__DATA\t__data
+0x100004008\t0x00000010\t__DATA\t__bss
+# Symbols:
+# Address\tSize    \tFile  Name
+0x100003F90\t0x00000020\t[  1] _main
+";
+        assert_eq!(linker(map.as_bytes()), Some("ld64"));
+        assert_eq!(arch(map.as_bytes()), "aarch64");
+        assert_eq!(records(map), vec![
+            (".text".to_string(), Section::Text, 0x20, Some(0x1_0000_3f90)),
+            (".data".to_string(), Section::Data, 8, Some(0x1_0000_4000)),
+            (".bss".to_string(), Section::Bss, 0x10, Some(0x1_0000_4008)),
+        ]);
+    }
+
+    #[test]
+    fn other_text_is_not_a_map() {
+        assert!(!is_map(b"hello\nworld\n"));
+        assert!(!is_map(b""));
+        assert!(sections(b"VMA LMA Size\n").is_err());
+    }
+}
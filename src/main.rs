@@ -1,28 +1,133 @@
+extern crate clap;
 #[macro_use]
 extern crate failure;
+extern crate gimli;
 extern crate goblin;
+#[cfg(feature = "disasm")]
+extern crate iced_x86;
 extern crate memmap;
+extern crate miniz_oxide;
+extern crate pdb;
+extern crate regex;
+extern crate rustc_demangle;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 extern crate serde_json;
+extern crate toml;
 
+mod analyze;
+mod android;
+mod arch;
+mod assets;
+mod archive;
+mod budget;
+mod codeview;
+mod coff;
+mod cold;
+mod compare;
+mod components;
+mod compression;
+mod constructors;
+mod coredump;
+mod decompress;
+mod delimited;
+mod delta;
+mod der;
+mod diff;
+mod digest;
+#[cfg(feature = "disasm")]
+mod disasm;
+mod download;
+mod dsym;
+mod duplicates;
+mod dwarf;
+mod dynamic;
+mod exit;
+mod explain;
+mod fdt;
+mod find;
+mod firmware;
+mod exports;
+mod flags;
+mod flash;
+mod formatting;
+mod generated;
+mod group;
+mod hints;
+mod hotness;
+mod hugepages;
+mod inputs;
+mod isa;
+mod kernel;
+mod labels;
+mod layout;
+mod linkedit;
+mod linkmap;
+mod mapping;
+mod merge;
+mod metadata;
+mod normalize;
+mod notify;
+mod objc;
+mod owners;
+mod paths;
+mod pgo;
+mod postlink;
+mod predict;
+mod rlib;
+mod rsa;
+mod runtime;
+mod saved;
+mod signature;
+mod spill;
+mod stdlib;
+mod supersize;
+mod switches;
+mod symbols;
+mod te;
+#[cfg(test)]
+mod testarchive;
+#[cfg(test)]
+mod testelf;
+#[cfg(test)]
+mod testfdt;
+#[cfg(test)]
+mod testmacho;
+#[cfg(test)]
+mod testzip;
+mod thinning;
+mod thunks;
+mod treemap;
+mod units;
+mod version_script;
+mod wasm;
+mod xz;
+mod yaml;
+mod zip;
+
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
 use failure::Error;
 use goblin::elf::section_header::SHT_NOBITS;
 use goblin::mach::constants::SECT_BSS;
 use goblin::mach::constants::SEG_DATA;
 use goblin::mach::constants::SEG_TEXT;
+use goblin::mach::constants::{SECTION_TYPE, S_GB_ZEROFILL, S_THREAD_LOCAL_ZEROFILL, S_ZEROFILL};
 use goblin::mach::Mach;
 use goblin::pe::section_table::IMAGE_SCN_MEM_READ;
 use goblin::pe::section_table::IMAGE_SCN_MEM_WRITE;
 use goblin::Object;
+use regex::Regex;
+use std::cmp;
 use std::collections::BTreeMap;
-use std::env;
-use std::fs::File;
-use std::io;
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
 
 /// Possible types of object file sections.
-#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 enum Section {
     /// Executable code.
     Text,
@@ -65,28 +170,108 @@ fn map_mach_name(seg_name: &str, sec_name: &str) -> String {
     mapped.to_string()
 }
 
-/// Parse `buf` as an object file, iterate over the sections contained within it, and
-/// return a `Vec` containing a (name, size, Section) tuple for each section.
-fn sections(buf: &[u8]) -> Result<Vec<(String, u64, Section)>, Error> {
+/// A section of an object file, with where it lives in the file and in memory.
+#[derive(Clone, Debug, Serialize)]
+struct SectionRecord {
+    name: String,
+    category: Section,
+    size: u64,
+    /// The Mach-O segment that contains the section.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    segment: Option<String>,
+    /// The virtual address of the section (relative to the image base for PE).
+    address: Option<u64>,
+    /// The offset of the section's contents in the file, if it has any.
+    offset: Option<u64>,
+    alignment: Option<u64>,
+    /// The raw flags (ELF `sh_flags`, Mach-O `flags`, PE characteristics).
+    flags: Option<u64>,
+    flag_names: Vec<&'static str>,
+    /// The algorithm and uncompressed size of a compressed ELF debug section.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    compression: Option<compression::Compression>,
+    /// A short description of the section, with `--explain`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'static str>,
+    /// The first bytes of the section's contents, with `--preview`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    preview: Option<String>,
+    /// The architecture of the slice of a universal binary that holds the section.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    arch: Option<String>,
+}
+
+impl SectionRecord {
+    /// A record for a section that isn't backed by a section header, e.g. `export_table`.
+    fn synthetic(name: &str, size: u64, category: Section) -> SectionRecord {
+        SectionRecord {
+            name: name.to_string(),
+            category,
+            size,
+            segment: None,
+            address: None,
+            offset: None,
+            alignment: None,
+            flags: None,
+            flag_names: vec![],
+            compression: None,
+            description: None,
+            preview: None,
+            arch: None,
+        }
+    }
+}
+
+/// Parse `buf` as an object file and return a record for each section contained within it.
+fn section_records(buf: &[u8]) -> Result<Vec<SectionRecord>, Error> {
+    if wasm::is_wasm(buf) {
+        return wasm::sections(buf);
+    }
+    if coff::is_coff(buf) {
+        return coff::sections(buf);
+    }
+    if te::is_te(buf) {
+        return te::sections(buf);
+    }
+    if linkmap::is_map(buf) {
+        return linkmap::sections(buf);
+    }
+    if saved::is_report(buf) {
+        return saved::sections(buf);
+    }
     Ok(match Object::parse(buf)? {
         Object::Elf(elf) => {
             elf.section_headers.iter().filter_map(|sec| {
                 elf.shdr_strtab.get(sec.sh_name)
                     .and_then(|res| res.ok())
-                    .map(|name| (name.to_string(), sec.sh_size, if !sec.is_alloc() {
-                        Section::Other
-                    } else if sec.is_executable() || !sec.is_writable() {
-                        Section::Text
-                    } else if sec.sh_type != SHT_NOBITS {
-                        Section::Data
-                    } else {
-                        Section::Bss
-                    }))
+                    .map(|name| SectionRecord {
+                        name: name.to_string(),
+                        category: if !sec.is_alloc() {
+                            Section::Other
+                        } else if sec.is_executable() || !sec.is_writable() {
+                            Section::Text
+                        } else if sec.sh_type != SHT_NOBITS {
+                            Section::Data
+                        } else {
+                            Section::Bss
+                        },
+                        size: sec.sh_size,
+                        segment: None,
+                        address: if sec.is_alloc() { Some(sec.sh_addr) } else { None },
+                        offset: if sec.sh_type != SHT_NOBITS { Some(sec.sh_offset) } else { None },
+                        alignment: Some(sec.sh_addralign),
+                        flags: Some(sec.sh_flags),
+                        flag_names: flags::elf_flag_names(sec.sh_flags),
+                        compression: compression::elf_compression(buf, &elf, sec, name),
+                        description: None,
+                        preview: None,
+                        arch: None,
+                    })
             }).collect()
         },
         Object::PE(pe) => {
             let mut bss: u64 = 0;
-            let mut vec: Vec<(String, u64, Section)> = pe.sections.iter().map(|sec| {
+            let mut vec: Vec<SectionRecord> = pe.sections.iter().map(|sec| {
                 let mut size = sec.virtual_size as u64;
                 let sec_type = if (sec.characteristics & IMAGE_SCN_MEM_WRITE) == 0 {
                     Section::Text
@@ -94,11 +279,7 @@ fn sections(buf: &[u8]) -> Result<Vec<(String, u64, Section)>, Error> {
                     // My understanding is that bss is "hidden" in the portion
                     // of the data section that is allocated in memory but does
                     // not correspond to the on disk size.
-                    let delta = if sec.virtual_size > sec.size_of_raw_data {
-                        sec.virtual_size - sec.size_of_raw_data
-                    } else {
-                        0
-                    };
+                    let delta = sec.virtual_size.saturating_sub(sec.size_of_raw_data);
 
                     bss += delta as u64;
 
@@ -111,24 +292,44 @@ fn sections(buf: &[u8]) -> Result<Vec<(String, u64, Section)>, Error> {
                     Section::Other
                 };
 
-               (sec.name().unwrap().to_string(), size, sec_type)
+                SectionRecord {
+                    name: sec.name().unwrap().to_string(),
+                    category: sec_type,
+                    size,
+                    segment: None,
+                    address: Some(sec.virtual_address as u64),
+                    offset: if sec.pointer_to_raw_data != 0 {
+                        Some(sec.pointer_to_raw_data as u64)
+                    } else {
+                        None
+                    },
+                    alignment: flags::pe_alignment(sec.characteristics),
+                    flags: Some(sec.characteristics as u64),
+                    flag_names: flags::pe_flag_names(sec.characteristics),
+                    compression: None,
+                    description: None,
+                    preview: None,
+                    arch: None,
+                }
             }).collect();
 
-            if pe.header.optional_header.is_some() {
-                let hdr = pe.header.optional_header.unwrap();
+            if let Some(hdr) = pe.header.optional_header {
                 let size = hdr.standard_fields.size_of_uninitialized_data;
 
                 // In theory the optional header can hold ths size of BSS aka
                 // uninitialized data. In practice this seems to be zero.
                 if size != 0 {
-                    vec.push((".bss".to_string(), size, Section::Bss));
+                    vec.push(SectionRecord::synthetic(".bss", size, Section::Bss));
                 } else {
-                    vec.push((".bss".to_string(), bss, Section::Bss));
+                    vec.push(SectionRecord::synthetic(".bss", bss, Section::Bss));
                 }
 
                 // Include the export table size. We'll put this in `Data` I guess.
                 if let Some(table) = hdr.data_directories.get_export_table() {
-                    vec.push(("export_table".to_string(), table.size as u64, Section::Data));
+                    let mut record =
+                        SectionRecord::synthetic("export_table", table.size as u64, Section::Data);
+                    record.address = Some(table.virtual_address as u64);
+                    vec.push(record);
                 }
             }
 
@@ -136,35 +337,54 @@ fn sections(buf: &[u8]) -> Result<Vec<(String, u64, Section)>, Error> {
         },
         Object::Mach(m) => {
             match m {
-                Mach::Fat(_fat) => {
-                    unimplemented!()
+                Mach::Fat(fat) => {
+                    // Report the sections of each slice, with its offset in the universal
+                    // binary.
+                    let mut vec = Vec::new();
+                    for arch in fat.arches()? {
+                        for mut record in section_records(arch::fat_slice(buf, &arch)?)? {
+                            record.offset = record.offset.map(|o| o + arch.offset as u64);
+                            record.arch = Some(arch::mach_arch(arch.cputype).to_string());
+                            vec.push(record);
+                        }
+                    }
+                    vec
                 },
                 Mach::Binary(mach) => {
                     // `sections` is actually an iterator of iterators.
                     let sections_itr = mach.segments.sections();
-                    let mut vec: Vec<(String, u64, Section)> =
-                        sections_itr.flat_map(|i| i).filter_map(|s| s.ok()).map(|(sec, _data)| {
+                    let mut vec: Vec<SectionRecord> =
+                        sections_itr.flatten().filter_map(|s| s.ok()).map(|(sec, _data)| {
                             let name = sec.name().unwrap();
                             let seg = sec.segname().unwrap();
-                            (map_mach_name(seg, name), sec.size, if name == SECT_BSS {
-                                Section::Bss
-                            } else if seg == SEG_DATA {
-                                Section::Data
-                            } else if seg == SEG_TEXT {
-                                Section::Text
-                            } else {
-                                Section::Other
-                            })
+                            let zerofill = matches!(sec.flags & SECTION_TYPE,
+                                S_ZEROFILL | S_GB_ZEROFILL | S_THREAD_LOCAL_ZEROFILL);
+                            SectionRecord {
+                                name: map_mach_name(seg, name),
+                                category: if name == SECT_BSS {
+                                    Section::Bss
+                                } else if seg == SEG_DATA {
+                                    Section::Data
+                                } else if seg == SEG_TEXT {
+                                    Section::Text
+                                } else {
+                                    Section::Other
+                                },
+                                size: sec.size,
+                                segment: Some(seg.to_string()),
+                                address: Some(sec.addr),
+                                offset: if zerofill { None } else { Some(sec.offset as u64) },
+                                alignment: Some(1 << sec.align),
+                                flags: Some(sec.flags as u64),
+                                flag_names: flags::mach_flag_names(sec.flags),
+                                compression: None,
+                                description: None,
+                                preview: None,
+                                arch: None,
+                            }
                         }).collect();
 
-                    // The size field of goblin::mach::exports::Export just returns 0, so the best
-                    // we can do is count.
-                    let export_count = match mach.exports() {
-                        Err(_) => 0,
-                        Ok(exports) => exports.len() as u64,
-                    };
-
-                    vec.push(("export_table".to_string(), export_count, Section::Data));
+                    vec.extend(linkedit::records(&mach, buf));
 
                     vec
                 }
@@ -174,23 +394,4080 @@ fn sections(buf: &[u8]) -> Result<Vec<(String, u64, Section)>, Error> {
     })
 }
 
-fn real_main() -> Result<(), Error> {
-    let path = env::args_os().nth(1).unwrap();
-    let f = File::open(&path)?;
-    let buf = unsafe { memmap::Mmap::map(&f)? };
-    let mut map: BTreeMap<Section, BTreeMap<String, u64>> = BTreeMap::new();
-    for (name, size, section) in sections(&buf)? {
-        map.entry(section)
-            .or_insert_with(|| BTreeMap::<String, u64>::new()).insert(name, size);
+/// A section's name, size, category and address, if it is loaded at one.
+type NamedSection = (String, u64, Section, Option<u64>);
+
+/// Parse `buf` as an object file, iterate over the sections contained within it, and
+/// return a `Vec` containing a `NamedSection` tuple for each section.
+fn sections(buf: &[u8]) -> Result<Vec<NamedSection>, Error> {
+    Ok(section_records(buf)?.into_iter()
+        .map(|record| (record.name, record.size, record.category, record.address))
+        .collect())
+}
+
+/// The flags that mark sections holding code.
+const CODE_FLAGS: &[&str] = &["SHF_EXECINSTR", "S_ATTR_PURE_INSTRUCTIONS", "IMAGE_SCN_CNT_CODE"];
+
+/// A section's name, file offset and contents.
+type SectionData<'a> = (String, u64, &'a [u8]);
+
+/// The contents of the loaded sections of `buf` that hold data rather than code, for searching
+/// for strings.
+fn loaded_data(buf: &[u8]) -> Result<Vec<SectionData<'_>>, Error> {
+    let mut sections = Vec::new();
+    for record in section_records(buf)? {
+        if record.category != Section::Text && record.category != Section::Data ||
+            record.flag_names.iter().any(|flag| CODE_FLAGS.contains(flag)) {
+            continue;
+        }
+        let offset = match record.offset {
+            Some(offset) => offset,
+            None => continue,
+        };
+        let end = match offset.checked_add(record.size) {
+            Some(end) => end,
+            None => continue,
+        };
+        if let Some(data) = buf.get(offset as usize..end as usize) {
+            sections.push((record.name, offset, data));
+        }
+    }
+    Ok(sections)
+}
+
+/// Return the section records of the input file `buf`, read from `path`. For archives, these are
+/// the sections of all of the object files in the archive.
+fn input_records(path: &Path, buf: &[u8]) -> Result<Vec<SectionRecord>, Error> {
+    previewed_records(path, buf, 0)
+}
+
+/// Like `input_records`, but with a preview of the first `preview` bytes of each section that
+/// has contents in the file.
+fn previewed_records(path: &Path, buf: &[u8], preview: usize)
+                     -> Result<Vec<SectionRecord>, Error> {
+    if !archive::is_archive(buf) {
+        return object_records(buf, preview);
+    }
+    let members = archive::members(buf, path)?;
+    let mut vec = Vec::new();
+    for member_records in archive::par_map(&members, |member| {
+        object_records(&member.data, preview).map_err(|e| format_err!("{}: {}", member.name, e))
+    }) {
+        vec.extend(member_records?);
+    }
+    Ok(vec)
+}
+
+/// The section records of the object file `buf`, with a preview of the first `preview` bytes of
+/// each section that has contents in the file.
+fn object_records(buf: &[u8], preview: usize) -> Result<Vec<SectionRecord>, Error> {
+    let mut records = section_records(buf)?;
+    if preview > 0 {
+        for record in &mut records {
+            record.preview = record.offset.and_then(|offset| {
+                let len = cmp::min(record.size, preview as u64);
+                buf.get(offset as usize..offset.checked_add(len)? as usize)
+            }).filter(|data| !data.is_empty()).map(preview_bytes);
+        }
+    }
+    Ok(records)
+}
+
+/// The records of the debug info sections of `dsym`, the slice of a dSYM DWARF file for a
+/// Mach-O binary, with previews as in `object_records`. The other sections of a dSYM only
+/// repeat the binary's, without their contents.
+fn dsym_records(dsym: &[u8], preview: usize) -> Result<Vec<SectionRecord>, Error> {
+    Ok(object_records(dsym, preview)?.into_iter()
+       .filter(|record| record.segment.as_ref().is_some_and(|segment| segment == "__DWARF"))
+       .collect())
+}
+
+/// Render `data` as an escaped string if it is text, possibly NUL-separated, and otherwise as
+/// hex bytes.
+fn preview_bytes(data: &[u8]) -> String {
+    let text = data.iter().all(|&b| b == 0 || b == b'\t' || b == b'\n' || b == b'\r' ||
+                                    (0x20..0x7f).contains(&b)) &&
+        data.iter().any(|&b| (0x20..0x7f).contains(&b));
+    if text {
+        data.iter().map(|&b| match b {
+            0 => "\\0".to_string(),
+            _ => (b as char).escape_default().to_string(),
+        }).collect()
+    } else {
+        data.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(" ")
+    }
+}
+
+/// Like `sections`, but for the input file `buf` read from `path` (see `input_records`), and
+/// with the section names normalized by `normalize::normalize_name` if `normalize` is set.
+fn named_sections(path: &Path, buf: &[u8], normalize: bool)
+                  -> Result<Vec<NamedSection>, Error> {
+    let mut vec: Vec<NamedSection> = input_records(path, buf)?.into_iter()
+        .map(|record| (record.name, record.size, record.category, record.address))
+        .collect();
+    if normalize {
+        for (name, _, _, _) in &mut vec {
+            *name = normalize::normalize_name(name).to_string();
+        }
+    }
+    Ok(vec)
+}
+
+/// Whether sections in `category` count towards totals. Non-allocated sections (debug info,
+/// notes, symbol tables) take up space in the file but are not shipped to memory, and only
+/// count with `--include-non-alloc`.
+fn counts_toward_total(category: Section, include_non_alloc: bool) -> bool {
+    include_non_alloc || category != Section::Other
+}
+
+/// Map the file at `path` into memory, or if it is compressed with gzip or xz, read it
+/// decompressed. A `path` of `-` reads standard input, which can't be mapped, to the end.
+fn map_file(path: &OsStr) -> Result<decompress::Input, Error> {
+    if path == "-" {
+        let mut data = Vec::new();
+        io::stdin().read_to_end(&mut data)?;
+        return decompress::Input::from_vec(data).map_err(|e| format_err!("<stdin>: {}", e));
+    }
+    let f = File::open(path)?;
+    let mmap = unsafe { memmap::Mmap::map(&f)? };
+    decompress::Input::new(mmap).map_err(|e| format_err!("{}: {}", path.to_string_lossy(), e))
+}
+
+/// Return the total size of each named section in `buf` that counts toward totals.
+fn section_sizes(path: &Path, buf: &[u8], normalize: bool, include_non_alloc: bool)
+                 -> Result<BTreeMap<String, u64>, Error> {
+    let mut map = BTreeMap::new();
+    for (name, size, section, _) in named_sections(path, buf, normalize)? {
+        if counts_toward_total(section, include_non_alloc) {
+            *map.entry(name).or_insert(0) += size;
+        }
+    }
+    Ok(map)
+}
+
+/// A section size annotated with a description of the section, for `--explain`.
+#[derive(Serialize)]
+struct Explained {
+    size: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'static str>,
+}
+
+/// The report printed with `--details`.
+#[derive(Serialize)]
+struct DetailedReport {
+    metadata: metadata::Metadata,
+    sections: Vec<SectionRecord>,
+}
+
+/// The sizes of the sections of each category, by section name.
+type SectionSizes = BTreeMap<Section, BTreeMap<String, u64>>;
+
+/// The sizes of one object file in a static archive, for `--members`.
+#[derive(Serialize)]
+struct MemberReport {
+    name: String,
+    sections: SectionSizes,
+    /// The size of the member's loaded sections.
+    total: u64,
+}
+
+/// The report printed with `--members`: each object file in an archive, and their sum.
+#[derive(Serialize)]
+struct ArchiveReport {
+    members: Vec<MemberReport>,
+    sections: SectionSizes,
+    total: u64,
+}
+
+/// Sum `sections` by category and name.
+fn section_sizes_by_category(sections: Vec<NamedSection>, normalize: bool) -> SectionSizes {
+    let mut map: SectionSizes = BTreeMap::new();
+    for (name, size, section, _) in sections {
+        let name = if normalize { normalize::normalize_name(&name).to_string() } else { name };
+        *map.entry(section).or_default().entry(name).or_insert(0) += size;
+    }
+    map
+}
+
+/// The loaded size of the sections in `sizes`.
+fn loaded_total(sizes: &SectionSizes) -> u64 {
+    sizes.iter()
+        .filter(|&(&section, _)| counts_toward_total(section, false))
+        .flat_map(|(_, names)| names.values())
+        .sum()
+}
+
+/// The sections of each object file in the archive `buf`, read from `path`, and their sum.
+fn archive_report(path: &Path, buf: &[u8], normalize: bool) -> Result<ArchiveReport, Error> {
+    if !archive::is_archive(buf) {
+        return Err(exit::UsageError("--members needs a static archive".to_string()).into());
+    }
+    let members = archive::members(buf, path)?;
+    let mut report = ArchiveReport { members: Vec::new(), sections: BTreeMap::new(), total: 0 };
+    for (member, member_sections) in members.iter().zip(archive::par_map(&members, |member| {
+        sections(&member.data).map_err(|e| format_err!("{}: {}", member.name, e))
+    })) {
+        let sections = section_sizes_by_category(member_sections?, normalize);
+        for (&section, names) in &sections {
+            for (name, size) in names {
+                *report.sections.entry(section).or_default().entry(name.clone()).or_insert(0) +=
+                    size;
+            }
+        }
+        report.members.push(MemberReport {
+            name: member.name.clone(),
+            total: loaded_total(&sections),
+            sections,
+        });
+    }
+    report.total = loaded_total(&report.sections);
+    Ok(report)
+}
+
+/// Report the sections of each object file in the archive `buf`, read from `path`, and their
+/// sum, for `--members`.
+fn members_main(path: &Path, buf: &[u8], normalize: bool) -> Result<(), Error> {
+    let report = archive_report(path, buf, normalize)?;
+    serde_json::to_writer_pretty(&mut io::stdout(), &saved::versioned(saved::Schema::Members,
+                                                                      report))?;
+    Ok(())
+}
+
+/// The sizes of one file in a ZIP archive, for `--member`.
+#[derive(Serialize)]
+struct ZipMemberReport {
+    name: String,
+    /// Whether the file is stored uncompressed, as Android needs native libraries to be to
+    /// load them from the APK in place.
+    stored: bool,
+    /// The size of the file in the archive.
+    compressed_size: u64,
+    /// The size of the file once extracted.
+    size: u64,
+    sections: SectionSizes,
+    /// The size of the file's loaded sections.
+    total: u64,
+}
+
+/// The report printed with `--member`: each matching file in a ZIP archive, like the native
+/// libraries of an APK, and the sum of their loaded sections.
+#[derive(Serialize)]
+struct ZipReport {
+    members: Vec<ZipMemberReport>,
+    total: u64,
+}
+
+/// The sections of each file in the ZIP archive `buf` whose path matches one of the globs
+/// `patterns`, and their sum.
+fn zip_report(buf: &[u8], patterns: Vec<&str>, normalize: bool) -> Result<ZipReport, Error> {
+    if !zip::is_zip(buf) {
+        return Err(exit::UsageError("--member needs a ZIP archive, like an APK".to_string())
+                   .into());
+    }
+    let patterns = patterns.into_iter().map(version_script::glob_regex)
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut report = ZipReport { members: Vec::new(), total: 0 };
+    for entry in zip::entries(buf)? {
+        if !patterns.iter().any(|pattern| pattern.is_match(&entry.name)) {
+            continue;
+        }
+        let data = entry.read(buf)?;
+        let sections = section_sizes_by_category(
+            sections(&data).map_err(|e| format_err!("{}: {}", entry.name, e))?, normalize);
+        let total = loaded_total(&sections);
+        report.total += total;
+        report.members.push(ZipMemberReport {
+            stored: entry.is_stored(),
+            compressed_size: entry.compressed_size as u64,
+            size: entry.size as u64,
+            name: entry.name,
+            sections,
+            total,
+        });
+    }
+    if report.members.is_empty() {
+        return Err(exit::UsageError("No file in the archive matches --member".to_string())
+                   .into());
+    }
+    Ok(report)
+}
+
+/// Report the sections of each file in the ZIP archive `buf` whose path matches one of the
+/// globs `patterns`, for `--member`.
+fn zip_members_main(buf: &[u8], patterns: Vec<&str>, normalize: bool) -> Result<(), Error> {
+    let report = zip_report(buf, patterns, normalize)?;
+    serde_json::to_writer_pretty(&mut io::stdout(), &report)?;
+    Ok(())
+}
+
+/// A symbol and its size, for `--symbols`.
+#[derive(Serialize)]
+struct SymbolSize {
+    /// The demangled name, without the hash.
+    name: String,
+    size: u64,
+    address: u64,
+    section: Option<String>,
+    /// `function` or `data`.
+    kind: &'static str,
+}
+
+/// How much of a section its symbols cover, for `--symbols`.
+#[derive(Serialize)]
+struct SectionSymbols {
+    name: String,
+    size: u64,
+    symbols: u64,
+    /// The bytes that symbols cover, counting overlapping symbols once.
+    attributed: u64,
+    unattributed: u64,
+}
+
+/// The report printed with `--symbols`: each loaded section, how much of it symbols cover, and
+/// the symbols, largest first.
+#[derive(Serialize)]
+struct SymbolReport {
+    /// The PDB that the symbols of a PE image came from.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pdb: Option<String>,
+    /// The dSYM that the symbols of a Mach-O binary came from.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dsym: Option<String>,
+    sections: Vec<SectionSymbols>,
+    symbols: Vec<SymbolSize>,
+}
+
+/// The symbols of the file `buf` at `path`, from the DWARF file of its dSYM `dsym`, or if it is
+/// a PE image, from the PDB `pdb` or the one it names. The PDB read is returned with them.
+fn file_symbols(path: &Path, buf: &[u8], pdb: Option<&OsStr>, dsym: Option<(&Path, &[u8])>)
+                -> Result<(Vec<symbols::Symbol>, Option<PathBuf>), Error> {
+    Ok(match (Object::parse(buf)?, dsym) {
+        (_, Some((_, dsym))) => (symbols::symbols(dsym)?, None),
+        (Object::PE(pe), None) => {
+            let pdb = match pdb {
+                Some(pdb) => PathBuf::from(pdb),
+                None => codeview::find_pdb(path, &pe).ok_or_else(|| exit::UsageError(format!(
+                    "{}: no PDB found next to the image; pass --pdb", path.display())))?,
+            };
+            (codeview::symbols(buf, &map_file(pdb.as_os_str())?)?, Some(pdb))
+        }
+        (_, None) if pdb.is_some() => {
+            return Err(exit::UsageError("--pdb needs a PE image".to_string()).into());
+        }
+        (Object::Mach(Mach::Fat(_)), None) => {
+            return Err(exit::UsageError(format!(
+                "{}: a universal binary has symbols for each architecture; thin it with lipo",
+                path.display())).into());
+        }
+        _ => (symbols::symbols(buf)?, None),
+    })
+}
+
+/// How much of each loaded section of `buf` the symbols `symbols` cover, for `--symbols`.
+fn section_symbols(buf: &[u8], symbols: &[symbols::Symbol])
+                   -> Result<Vec<SectionSymbols>, Error> {
+    // The address ranges of the symbols in each section.
+    let mut ranges: BTreeMap<&str, Vec<(u64, u64)>> = BTreeMap::new();
+    for sym in symbols {
+        if let Some(ref section) = sym.section {
+            ranges.entry(section).or_default()
+                .push((sym.address, sym.address.saturating_add(sym.size)));
+        }
+    }
+    let mut sections: Vec<SectionSymbols> = Vec::new();
+    for record in section_records(buf)? {
+        if !counts_toward_total(record.category, false) {
+            continue;
+        }
+        match sections.iter_mut().find(|s| s.name == record.name) {
+            Some(section) => section.size += record.size,
+            None => sections.push(SectionSymbols {
+                name: record.name,
+                size: record.size,
+                symbols: 0,
+                attributed: 0,
+                unattributed: 0,
+            }),
+        }
+    }
+    for section in &mut sections {
+        let mut ranges = ranges.remove(section.name.as_str()).unwrap_or_default();
+        section.symbols = ranges.len() as u64;
+        ranges.sort();
+        let mut end = 0;
+        for (start, range_end) in ranges {
+            section.attributed += range_end.saturating_sub(cmp::max(start, end));
+            end = cmp::max(end, range_end);
+        }
+        section.attributed = cmp::min(section.attributed, section.size);
+        section.unattributed = section.size - section.attributed;
+    }
+    Ok(sections)
+}
+
+fn symbols_main(path: &Path, buf: &[u8], pdb: Option<&OsStr>, dsym: Option<(&Path, &[u8])>)
+                -> Result<(), Error> {
+    let (symbols, pdb_path) = file_symbols(path, buf, pdb, dsym)?;
+
+    let sections = section_symbols(buf, &symbols)?;
+    let mut symbols: Vec<SymbolSize> = symbols.into_iter().map(|sym| SymbolSize {
+        name: format!("{:#}", rustc_demangle::demangle(&sym.name)),
+        size: sym.size,
+        address: sym.address,
+        section: sym.section,
+        kind: if sym.code { "function" } else { "data" },
+    }).collect();
+    symbols.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+    serde_json::to_writer_pretty(&mut io::stdout(), &SymbolReport {
+        pdb: pdb_path.map(|pdb| pdb.display().to_string()),
+        dsym: dsym.map(|(path, _)| path.display().to_string()),
+        sections,
+        symbols,
+    })?;
+    Ok(())
+}
+
+/// Print an `address size name` line for each symbol of the file `buf` at `path`, in address
+/// order, for `--format symbol-map`: the address and size in hex and the name as in the symbol
+/// table, mangled, as `nm --print-size` prints them for tools like SuperSize to read.
+/// Addresses are those of the symbol table, which in relocatable objects are section offsets.
+fn symbol_map_main(path: &Path, buf: &[u8]) -> Result<(), Error> {
+    let (symbols, _) = file_symbols(path, buf, None, None)?;
+    for line in symbol_map_lines(symbols) {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+/// The `--format symbol-map` lines of `symbols`, in address order.
+fn symbol_map_lines(mut symbols: Vec<symbols::Symbol>) -> Vec<String> {
+    symbols.sort_by(|a, b| (a.address, &a.name).cmp(&(b.address, &b.name)));
+    symbols.into_iter()
+        .map(|sym| format!("{:016x} {:016x} {}", sym.address, sym.size, sym.name))
+        .collect()
+}
+
+/// Write the treemap of the file `buf` at `path` as a web page, for `--format html`, to the
+/// `--output` file or standard output.
+fn html_main(args: &ArgMatches, path: &Path, buf: &[u8]) -> Result<(), Error> {
+    let page = treemap::html(&treemap::tree(path, buf, args.is_present("normalize-names"))?)?;
+    match args.value_of_os("output") {
+        Some(output) => {
+            fs::write(output, page)
+                .map_err(|e| format_err!("{}: {}", Path::new(output).display(), e))?;
+        }
+        None => print!("{}", page),
+    }
+    Ok(())
+}
+
+/// The `--summary` line of the file `buf`, read from `path`.
+fn summary_line(path: &str, buf: &[u8], format: &units::SizeFormat) -> Result<String, Error> {
+    let c = compare::Column::new(path.to_string(), buf, false, false)?;
+    Ok(format!("{:<8} {:<10} {:>10} {:>10} {:>10} {:>10}  {}", metadata::metadata(buf)?.format,
+               c.arch, format.size(c.categories[&Section::Text]),
+               format.size(c.categories[&Section::Data]),
+               format.size(c.categories[&Section::Bss]), format.size(c.file_size), c.path))
+}
+
+/// Print one line per file in `args`, for `--summary`. Files that can't be read or parsed are
+/// reported on stderr and skipped.
+fn summary_main(args: &ArgMatches) -> Result<(), Error> {
+    let format = size_format(args)?;
+    println!("{:<8} {:<10} {:>10} {:>10} {:>10} {:>10}  FILE",
+             "FORMAT", "ARCH", "TEXT", "DATA", "BSS", "FILE SIZE");
+    let mut failed = 0;
+    for file in args.values_of_os("FILE").unwrap() {
+        let path = file.to_string_lossy();
+        match map_file(file).and_then(|buf| summary_line(&path, &buf, &format)) {
+            Ok(line) => println!("{}", line),
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(exit::PartialFailure { failed }.into());
+    }
+    Ok(())
+}
+
+/// The `--format berkeley` lines of the file at `path`, whose sections are `records`: one per
+/// slice of a universal binary, or one for any other file.
+fn berkeley_lines(path: &str, records: Vec<SectionRecord>) -> Vec<String> {
+    let mut arches: BTreeMap<Option<String>, BTreeMap<Section, u64>> = BTreeMap::new();
+    for record in records {
+        *arches.entry(record.arch).or_default().entry(record.category).or_insert(0) +=
+            record.size;
+    }
+    if arches.is_empty() {
+        arches.insert(None, BTreeMap::new());
+    }
+    arches.into_iter().map(|(arch, sizes)| {
+        let size = |section| sizes.get(&section).copied().unwrap_or(0);
+        let (text, data, bss) = (size(Section::Text), size(Section::Data), size(Section::Bss));
+        let dec = text + data + bss;
+        let filename = match arch {
+            Some(arch) => format!("{} (for architecture {})", path, arch),
+            None => path.to_string(),
+        };
+        format!("{:>7}\t{:>7}\t{:>7}\t{:>7}\t{:>7x}\t{}", text, data, bss, dec, dec, filename)
+    }).collect()
+}
+
+/// Print the `text data bss dec hex filename` line of each FILE that GNU `size` prints by
+/// default, for `--format berkeley`. Each slice of a universal binary gets a line of its own.
+fn berkeley_main(args: &ArgMatches) -> Result<(), Error> {
+    println!("{:>7}\t{:>7}\t{:>7}\t{:>7}\t{:>7}\tfilename", "text", "data", "bss", "dec", "hex");
+    let mut failed = 0;
+    for path in args.values_of_os("FILE").unwrap() {
+        let records = map_file(path).and_then(|buf| input_records(Path::new(path), &buf));
+        let records = match records {
+            Ok(records) => records,
+            Err(e) => {
+                eprintln!("{}: {}", path.to_string_lossy(), e);
+                failed += 1;
+                continue;
+            }
+        };
+        for line in berkeley_lines(&path.to_string_lossy(), records) {
+            println!("{}", line);
+        }
+    }
+    if failed > 0 {
+        return Err(exit::PartialFailure { failed }.into());
+    }
+    Ok(())
+}
+
+/// The `--format sysv` lines of the file at `path`, whose sections are `records`: a table for
+/// each slice of a universal binary, or one for any other file, each followed by two blank
+/// lines.
+fn sysv_lines(path: &str, records: Vec<SectionRecord>, normalize: bool) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut arches: BTreeMap<Option<String>, Vec<NamedSection>> = BTreeMap::new();
+    for record in records.into_iter().filter(|record| !record.name.is_empty()) {
+        let name = if normalize {
+            normalize::normalize_name(&record.name).to_string()
+        } else {
+            record.name
+        };
+        arches.entry(record.arch).or_default()
+            .push((name, record.size, record.category, record.address));
+    }
+    if arches.is_empty() {
+        arches.insert(None, Vec::new());
+    }
+    for (arch, sections) in arches {
+        let total: u64 = sections.iter().map(|s| s.1).sum();
+        let name_width = sections.iter().map(|s| s.0.len()).chain(Some("section".len()))
+            .max().unwrap();
+        let size_width = total.to_string().len().max("size".len());
+        let address_width = sections.iter()
+            .map(|s| s.3.unwrap_or(0).to_string().len())
+            .chain(Some("addr".len()))
+            .max().unwrap();
+        lines.push(match arch {
+            Some(arch) => format!("{} (for architecture {}):", path, arch),
+            None => format!("{}  :", path),
+        });
+        lines.push(format!("{:<nw$}   {:>sw$}   {:>aw$}", "section", "size", "addr",
+                           nw = name_width, sw = size_width, aw = address_width));
+        for (name, size, _, address) in &sections {
+            lines.push(format!("{:<nw$}   {:>sw$}   {:>aw$}", name, size, address.unwrap_or(0),
+                               nw = name_width, sw = size_width, aw = address_width));
+        }
+        lines.push(format!("{:<nw$}   {:>sw$}", "Total", total, nw = name_width,
+                           sw = size_width));
+        lines.push(String::new());
+        lines.push(String::new());
+    }
+    lines
+}
+
+/// Print the table of sections of each FILE, with their sizes and addresses and a total, that
+/// `size -A` prints, for `--format sysv`. Each slice of a universal binary gets a table of its
+/// own. Unlike `size -A`, the tables list the ELF symbol and string tables, which BFD hides.
+fn sysv_main(args: &ArgMatches) -> Result<(), Error> {
+    let normalize = args.is_present("normalize-names");
+    let mut failed = 0;
+    for path in args.values_of_os("FILE").unwrap() {
+        let records = map_file(path).and_then(|buf| input_records(Path::new(path), &buf));
+        let records = match records {
+            Ok(records) => records,
+            Err(e) => {
+                eprintln!("{}: {}", path.to_string_lossy(), e);
+                failed += 1;
+                continue;
+            }
+        };
+        for line in sysv_lines(&path.to_string_lossy(), records, normalize) {
+            println!("{}", line);
+        }
+    }
+    if failed > 0 {
+        return Err(exit::PartialFailure { failed }.into());
+    }
+    Ok(())
+}
+
+/// Print a `file,category,section,size` row for each section of each FILE, after a header, for
+/// `--format csv` and `--format tsv`. The sections of each slice of a universal binary are
+/// listed with the architecture after the file, as in `--format berkeley`.
+fn delimited_main(args: &ArgMatches, format: delimited::Delimited) -> Result<(), Error> {
+    let normalize = args.is_present("normalize-names");
+    println!("{}", format.row(&["file", "category", "section", "size"]));
+    let mut failed = 0;
+    for path in args.values_of_os("FILE").unwrap() {
+        let records = map_file(path).and_then(|buf| input_records(Path::new(path), &buf));
+        let records = match records {
+            Ok(records) => records,
+            Err(e) => {
+                eprintln!("{}: {}", path.to_string_lossy(), e);
+                failed += 1;
+                continue;
+            }
+        };
+        for record in records.into_iter().filter(|record| !record.name.is_empty()) {
+            let file = match record.arch {
+                Some(ref arch) => {
+                    format!("{} (for architecture {})", path.to_string_lossy(), arch)
+                }
+                None => path.to_string_lossy().into_owned(),
+            };
+            let name = if normalize {
+                normalize::normalize_name(&record.name)
+            } else {
+                &record.name
+            };
+            let row = format.row(&[&file, &format!("{:?}", record.category), name,
+                                   &record.size.to_string()]);
+            println!("{}", row);
+        }
+    }
+    if failed > 0 {
+        return Err(exit::PartialFailure { failed }.into());
+    }
+    Ok(())
+}
+
+/// Print the sections of FILE as `bloaty --csv` does, for `--format bloaty-csv`: a
+/// `sections,vmsize,filesize` header, then a row for each section name, largest first by the
+/// larger of its two sizes, leaving out empty ones. The sections of every FILE and of every
+/// slice of a universal binary are added up, as bloaty adds up the files it is given, and the
+/// bytes of a file outside its sections are `[Unmapped]`.
+fn bloaty_main(args: &ArgMatches) -> Result<(), Error> {
+    let mut files = Vec::new();
+    let mut failed = 0;
+    for path in args.values_of_os("FILE").unwrap() {
+        let input = map_file(path).and_then(|buf| {
+            let records = input_records(Path::new(path), &buf)?;
+            Ok((buf.len() as u64, records))
+        });
+        match input {
+            Ok(input) => files.push(input),
+            Err(e) => {
+                eprintln!("{}: {}", path.to_string_lossy(), e);
+                failed += 1;
+            }
+        }
+    }
+    for line in bloaty_lines(files, args.is_present("normalize-names")) {
+        println!("{}", line);
+    }
+    if failed > 0 {
+        return Err(exit::PartialFailure { failed }.into());
+    }
+    Ok(())
+}
+
+/// The `--format bloaty-csv` lines of `files`, the lengths of some files and their sections.
+fn bloaty_lines(files: Vec<(u64, Vec<SectionRecord>)>, normalize: bool) -> Vec<String> {
+    let mut sections: BTreeMap<String, (u64, u64)> = BTreeMap::new();
+    for (len, records) in files {
+        let mut mapped = 0;
+        for record in records.into_iter().filter(|record| !record.name.is_empty()) {
+            // Sections that aren't loaded only take up the file, and bss only memory.
+            let vmsize = if record.category == Section::Other { 0 } else { record.size };
+            let filesize = if record.category == Section::Bss { 0 } else { record.size };
+            let name = if normalize {
+                normalize::normalize_name(&record.name).to_string()
+            } else {
+                record.name
+            };
+            let sizes = sections.entry(name).or_insert((0, 0));
+            sizes.0 += vmsize;
+            sizes.1 += filesize;
+            mapped += filesize;
+        }
+        let unmapped = len.saturating_sub(mapped);
+        if unmapped > 0 {
+            sections.entry("[Unmapped]".to_string()).or_insert((0, 0)).1 += unmapped;
+        }
+    }
+    let mut sections: Vec<_> = sections.into_iter().filter(|section| section.1 != (0, 0))
+        .collect();
+    sections.sort_by(|a, b| {
+        let (a_max, b_max) = ((a.1).0.max((a.1).1), (b.1).0.max((b.1).1));
+        (b_max, &a.0).cmp(&(a_max, &b.0))
+    });
+    let csv = delimited::Delimited::Csv;
+    let mut lines = vec![csv.row(&["sections", "vmsize", "filesize"])];
+    for (name, (vmsize, filesize)) in sections {
+        lines.push(csv.row(&[&name, &vmsize.to_string(), &filesize.to_string()]));
+    }
+    lines
+}
+
+/// One line of `--format ndjson`: the sections of a file, or of one slice of a universal binary.
+#[derive(Serialize)]
+struct FileSections {
+    file: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    arch: Option<String>,
+    sections: SectionSizes,
+    /// The size of the loaded sections.
+    total: u64,
+}
+
+/// Print the sections of each FILE as a JSON object on a line of its own, for `--format ndjson`.
+/// Each line is written out as soon as its file is read, so that the lines of a long run can be
+/// consumed as they come. Each slice of a universal binary gets a line of its own.
+fn ndjson_main(args: &ArgMatches) -> Result<(), Error> {
+    let normalize = args.is_present("normalize-names");
+    let stdout = io::stdout();
+    let mut failed = 0;
+    for path in args.values_of_os("FILE").unwrap() {
+        let records = map_file(path).and_then(|buf| input_records(Path::new(path), &buf));
+        let records = match records {
+            Ok(records) => records,
+            Err(e) => {
+                eprintln!("{}: {}", path.to_string_lossy(), e);
+                failed += 1;
+                continue;
+            }
+        };
+        let mut out = stdout.lock();
+        for line in ndjson_lines(&path.to_string_lossy(), records, normalize)? {
+            writeln!(out, "{}", line)?;
+        }
+        out.flush()?;
+    }
+    if failed > 0 {
+        return Err(exit::PartialFailure { failed }.into());
+    }
+    Ok(())
+}
+
+/// The `--format ndjson` lines of the file at `path`, whose sections are `records`: one per
+/// slice of a universal binary, or one for any other file.
+fn ndjson_lines(path: &str, records: Vec<SectionRecord>, normalize: bool)
+                -> Result<Vec<String>, Error> {
+    let mut arches: BTreeMap<Option<String>, Vec<NamedSection>> = BTreeMap::new();
+    for record in records {
+        arches.entry(record.arch)
+            .or_default().push((record.name, record.size, record.category, record.address));
+    }
+    let mut lines = Vec::new();
+    for (arch, sections) in arches {
+        let sections = section_sizes_by_category(sections, normalize);
+        lines.push(serde_json::to_string(&FileSections {
+            file: path.to_string(),
+            arch,
+            total: loaded_total(&sections),
+            sections,
+        })?);
+    }
+    Ok(lines)
+}
+
+/// Report the flash footprint of the firmware image `buf`, for `--format raw` and
+/// `--format ihex`.
+fn flash_main(args: &ArgMatches, buf: &[u8], format: &str) -> Result<(), Error> {
+    let format = if format == "ihex" { flash::Format::Ihex } else { flash::Format::Raw };
+    let base = match args.value_of("base") {
+        Some(_) if format == flash::Format::Ihex => {
+            return Err(exit::UsageError("--base needs --format raw".to_string()).into());
+        }
+        Some(base) => flash::parse_address(base)?,
+        None => 0,
+    };
+    let elf = match args.value_of_os("elf") {
+        Some(elf) => Some(map_file(elf)?),
+        None => None,
+    };
+    let report = flash::flash(buf, format, base, elf.as_ref().map(|elf| &elf[..]))?;
+    serde_json::to_writer_pretty(&mut io::stdout(), &report)?;
+    Ok(())
+}
+
+fn report_main(args: &ArgMatches) -> Result<(), Error> {
+    if args.is_present("summary") {
+        return summary_main(args);
+    }
+    let format = args.value_of("format");
+    if (args.is_present("base") || args.is_present("elf")) &&
+        format != Some("raw") && format != Some("ihex")
+    {
+        return Err(exit::UsageError("--base and --elf need --format raw or ihex".to_string())
+                   .into());
+    }
+    if args.is_present("output") && format != Some("html") {
+        return Err(exit::UsageError("--output needs --format html".to_string()).into());
+    }
+    match format {
+        Some("berkeley") => return berkeley_main(args),
+        Some("sysv") => return sysv_main(args),
+        Some("csv") => return delimited_main(args, delimited::Delimited::Csv),
+        Some("tsv") => return delimited_main(args, delimited::Delimited::Tsv),
+        Some("bloaty-csv") => return bloaty_main(args),
+        Some("ndjson") => return ndjson_main(args),
+        _ => {}
+    }
+    if args.occurrences_of("FILE") > 1 {
+        return Err(exit::UsageError("Only --summary accepts more than one file".to_string())
+                   .into());
+    }
+    let path = Path::new(args.value_of_os("FILE").unwrap());
+    let buf = map_file(path.as_os_str())?;
+    let normalize = args.is_present("normalize-names");
+    if args.is_present("members") {
+        return members_main(path, &buf, normalize);
+    }
+    if let Some(patterns) = args.values_of("member") {
+        return zip_members_main(&buf, patterns.collect(), normalize);
+    }
+    match format {
+        Some(format @ "raw") | Some(format @ "ihex") => return flash_main(args, &buf, format),
+        Some("symbol-map") => return symbol_map_main(path, &buf),
+        Some("html") => return html_main(args, path, &buf),
+        _ => {}
+    }
+    // The table is for people, so it is the default on a terminal, and JSON the default
+    // elsewhere.
+    let table = match format {
+        Some("table") if args.is_present("explain") => {
+            return Err(exit::UsageError("--explain needs --format json or yaml".to_string())
+                       .into());
+        }
+        Some("table") => true,
+        Some(_) => false,
+        None => !args.is_present("explain") && io::stdout().is_terminal(),
+    };
+    let dsym = match args.value_of_os("dsym") {
+        Some(dsym) => {
+            let file = dsym::dwarf_file(Path::new(dsym))?;
+            let data = map_file(file.as_os_str())?;
+            Some((file, data))
+        }
+        None => None,
+    };
+    let dsym = match dsym {
+        Some((ref file, ref data)) => Some((file.as_path(), dsym::slice(&buf, data)?)),
+        None => None,
+    };
+    if args.is_present("symbols") {
+        return symbols_main(path, &buf, args.value_of_os("pdb"), dsym);
     }
     let mut stdout = io::stdout();
-    serde_json::to_writer_pretty(&mut stdout, &map)?;
+    let preview = match args.value_of("preview").map(str::parse) {
+        Some(Ok(preview)) => preview,
+        Some(Err(_)) => return Err(exit::UsageError("Invalid --preview".to_string()).into()),
+        None => 0,
+    };
+    if args.is_present("details") || preview > 0 {
+        let mut sections = previewed_records(path, &buf, preview)?;
+        if let Some((_, dsym)) = dsym {
+            sections.extend(dsym_records(dsym, preview)?);
+        }
+        for record in &mut sections {
+            if normalize {
+                record.name = normalize::normalize_name(&record.name).to_string();
+            }
+            if args.is_present("explain") {
+                record.description = explain::explain(&record.name);
+            }
+        }
+        let mut metadata = metadata::metadata(&buf)?;
+        if args.is_present("signature") {
+            metadata.signature = Some(signature::signature(&buf)?);
+        }
+        let report = DetailedReport { metadata, sections };
+        serde_json::to_writer_pretty(&mut stdout,
+                                     &saved::versioned(saved::Schema::Details, report))?;
+    } else {
+        // The slices of a universal binary are reported separately, keyed by architecture.
+        let mut arches: BTreeMap<Option<String>, Vec<NamedSection>> = BTreeMap::new();
+        let mut records = input_records(path, &buf)?;
+        if let Some((_, dsym)) = dsym {
+            records.extend(dsym_records(dsym, 0)?);
+        }
+        for record in records {
+            let name = if normalize {
+                normalize::normalize_name(&record.name).to_string()
+            } else {
+                record.name
+            };
+            arches.entry(record.arch).or_default()
+                .push((name, record.size, record.category, record.address));
+        }
+        if args.is_present("explain") {
+            let reports = arches.into_iter().map(|(arch, sections)| {
+                let mut map: BTreeMap<Section, BTreeMap<String, Explained>> = BTreeMap::new();
+                for (name, size, section, _) in sections {
+                    let description = explain::explain(&name);
+                    map.entry(section)
+                        .or_default().entry(name).or_insert(Explained { size: 0, description })
+                        .size += size;
+                }
+                (arch, map)
+            }).collect();
+            write_section_report(args, reports)?;
+        } else if table {
+            let format = size_format(args)?;
+            let fat = !arches.contains_key(&None);
+            for (i, (arch, sections)) in arches.into_iter().enumerate() {
+                if i > 0 {
+                    println!();
+                }
+                if let (true, Some(arch)) = (fat, arch) {
+                    println!("{}:", arch);
+                }
+                for line in table_lines(&section_sizes_by_category(sections, false), &format) {
+                    println!("{}", line);
+                }
+            }
+        } else {
+            let reports = arches.into_iter().map(|(arch, sections)| {
+                (arch, section_sizes_by_category(sections, false))
+            }).collect();
+            write_section_report(args, reports)?;
+        }
+    }
     Ok(())
 }
 
-fn main() {
-    match real_main() {
-        Ok(_) => {},
-        Err(err) => println!("Error: {:?}", err),
+/// The lines of `sizes` as a table for `--format table`: the sections of each category largest
+/// first, with their share of the loaded size and a subtotal for the category, then the loaded
+/// total. Non-allocated sections have no share, as they aren't loaded, and nameless ones aren't
+/// listed, though they count in the subtotals.
+fn table_lines(sizes: &SectionSizes, format: &units::SizeFormat) -> Vec<String> {
+    let total = loaded_total(sizes);
+    let mut rows = vec![("SIZE".to_string(), "%".to_string(), "SECTION".to_string())];
+    for (&section, names) in sizes {
+        let loaded = counts_toward_total(section, false);
+        let percent = |size: u64| if !loaded {
+            String::new()
+        } else if total == 0 {
+            "-".to_string()
+        } else {
+            format!("{:.1}%", size as f64 * 100.0 / total as f64)
+        };
+        let subtotal = names.values().sum();
+        let mut names: Vec<(&String, u64)> =
+            names.iter().filter(|(name, _)| !name.is_empty())
+            .map(|(name, &size)| (name, size))
+            .collect();
+        names.sort_by(|a, b| (b.1, a.0).cmp(&(a.1, b.0)));
+        for &(name, size) in &names {
+            rows.push((format.size(size), percent(size), name.clone()));
+        }
+        let category = format!("{:?}", section).to_lowercase();
+        rows.push((format.size(subtotal), percent(subtotal), format!("({} total)", category)));
+    }
+    rows.push((format.size(total), if total == 0 { "-" } else { "100.0%" }.to_string(),
+               "(loaded total)".to_string()));
+    let size_width = rows.iter().map(|row| row.0.chars().count()).max().unwrap_or(0);
+    let percent_width = rows.iter().map(|row| row.1.len()).max().unwrap_or(0);
+    rows.into_iter().map(|(size, percent, name)| {
+        let line = format!("{:>size_width$}  {:>percent_width$}  {}", size, percent, name);
+        line.trim_end().to_string()
+    }).collect()
+}
+
+/// Write the report of each architecture in `reports` keyed by architecture, or the report
+/// alone if the file isn't a universal binary.
+fn write_by_arch<T: serde::Serialize>(args: &ArgMatches, mut reports: BTreeMap<Option<String>, T>)
+                                      -> Result<(), Error> {
+    match reports.remove(&None) {
+        Some(report) => write_structured(args, &report)?,
+        None => {
+            let reports: BTreeMap<String, T> = reports.into_iter()
+                .filter_map(|(arch, report)| Some((arch?, report)))
+                .collect();
+            write_structured(args, &reports)?;
+        }
+    }
+    Ok(())
+}
+
+/// Write the section report of each architecture in `reports` like `write_by_arch`, with the
+/// schema version that `saved` reads them back by.
+fn write_section_report<T: serde::Serialize>(args: &ArgMatches,
+                                             mut reports: BTreeMap<Option<String>, T>)
+                                             -> Result<(), Error> {
+    match reports.remove(&None) {
+        Some(report) => write_structured(args, &saved::versioned(saved::Schema::Categories,
+                                                                 report)),
+        None => {
+            let reports: BTreeMap<String, T> = reports.into_iter()
+                .filter_map(|(arch, report)| Some((arch?, report)))
+                .collect();
+            write_structured(args, &saved::versioned(saved::Schema::Arches, reports))
+        }
+    }
+}
+
+/// Print the report of each architecture in `reports` with `print`, under the name of the
+/// architecture if the file is a universal binary.
+fn print_by_arch<T, F>(reports: BTreeMap<Option<String>, T>, mut print: F) -> Result<(), Error>
+    where F: FnMut(&T) -> Result<(), Error>
+{
+    for (i, (arch, report)) in reports.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        if let Some(ref arch) = *arch {
+            println!("{}:", arch);
+        }
+        print(report)?;
+    }
+    Ok(())
+}
+
+/// Whether `--format` asks for a report that programs read, JSON or YAML.
+fn structured(args: &ArgMatches) -> bool {
+    args.value_of("format") == Some("json") || args.value_of("format") == Some("yaml")
+}
+
+/// Write `report` to standard output as YAML with `--format yaml`, and as JSON otherwise.
+fn write_structured<T: serde::Serialize>(args: &ArgMatches, report: &T) -> Result<(), Error> {
+    if args.value_of("format") == Some("yaml") {
+        yaml::to_writer(io::stdout(), report)
+    } else {
+        serde_json::to_writer_pretty(&mut io::stdout(), report)?;
+        Ok(())
+    }
+}
+
+/// The format for sizes in human-readable output, from the global `--units`, `--human` and
+/// `--group-digits` options.
+fn size_format(args: &ArgMatches) -> Result<units::SizeFormat, Error> {
+    Ok(units::SizeFormat {
+        units: match args.value_of("units") {
+            Some(units) => units.parse()?,
+            None if args.is_present("human") => units::Units::Iec,
+            None => units::Units::Bytes,
+        },
+        separators: if args.is_present("group-digits") {
+            Some(units::locale_separators())
+        } else {
+            None
+        },
+    })
+}
+
+/// Sum the sizes of the symbols in `buf` by name, in at most about `limit` bytes of memory.
+fn spilled_symbol_sizes(buf: &[u8], limit: usize, infer_sizes: bool)
+                        -> Result<spill::SortedSizes, Error> {
+    let mut sizes = spill::SpillingMap::new(limit);
+    let mut result = Ok(());
+    symbols::for_each_symbol(buf, infer_sizes, |sym| if result.is_ok() {
+        result = sizes.add(&sym.name, sym.size);
+    })?;
+    result?;
+    sizes.finish()
+}
+
+fn core_main(args: &ArgMatches) -> Result<(), Error> {
+    let buf = map_file(args.value_of_os("FILE").unwrap())?;
+    let report = coredump::core(&buf)?;
+    if structured(args) {
+        write_structured(args, &report)?;
+        return Ok(());
+    }
+    let format = size_format(args)?;
+    if args.is_present("segments") {
+        println!("{:<18}  {:<4} {:>10} {:>10} {:>10}  FILE", "ADDRESS", "PERM", "MEMORY",
+                 "DUMPED", "RESIDENT");
+        for segment in &report.segments {
+            println!("{:#018x}  {:<4} {:>10} {:>10} {:>10}  {}", segment.address,
+                     segment.permissions, format.size(segment.memory_size),
+                     format.size(segment.dumped_size), format.size(segment.resident_size),
+                     segment.file.as_deref().unwrap_or(""));
+        }
+    } else {
+        println!("{:>10} {:>10} {:>10} {:>8}  FILE", "MEMORY", "DUMPED", "RESIDENT", "SEGMENTS");
+        for mapping in &report.mappings {
+            println!("{:>10} {:>10} {:>10} {:>8}  {}", format.size(mapping.memory_size),
+                     format.size(mapping.dumped_size), format.size(mapping.resident_size),
+                     mapping.segments, mapping.name);
+        }
+    }
+    let permissions: Vec<String> = report.permissions.iter()
+        .map(|(permissions, size)| format!("{} {}", permissions, format.size(*size)))
+        .collect();
+    println!("{}mapped: {}, dumped: {}, resident: {} ({})",
+             report.process.as_ref().map(|name| format!("{}: ", name)).unwrap_or_default(),
+             format.size(report.memory_size), format.size(report.dumped_size),
+             format.size(report.resident_size), permissions.join(", "));
+    Ok(())
+}
+
+fn data_in_code_main(args: &ArgMatches) -> Result<(), Error> {
+    let buf = map_file(args.value_of_os("FILE").unwrap())?;
+    let report = mapping::data_in_code(&buf)?;
+    if structured(args) {
+        write_structured(args, &report)?;
+        return Ok(());
+    }
+    let format = size_format(args)?;
+    println!("{:>10} {:>12} {:>11} {:>10} {:>7}  SECTION", "SIZE", "INSTRUCTIONS",
+             "JUMP TABLES", "LITERALS", "PURITY");
+    for s in &report.sections {
+        println!("{:>10} {:>12} {:>11} {:>10} {:>6.1}%  {}", format.size(s.size),
+                 format.size(s.instructions), format.size(s.jump_tables),
+                 format.size(s.literals), 100.0 * s.purity, s.name);
+    }
+    let total = report.instructions + report.data;
+    match report.source {
+        "none" => println!("No data is marked in the code, so all {} count as instructions",
+                           format.size(total)),
+        source => println!("{} of {} of code ({:.1}%) is data, {} of jump tables and {} of \
+                            literals, as marked by {}",
+                           format.size(report.data), format.size(total),
+                           100.0 * report.data as f64 / cmp::max(total, 1) as f64,
+                           format.size(report.jump_tables), format.size(report.literals),
+                           source.replace('-', " ")),
+    }
+    Ok(())
+}
+
+fn dead_exports_main(args: &ArgMatches) -> Result<(), Error> {
+    let library = map_file(args.value_of_os("LIBRARY").unwrap())?;
+    let mut consumers = Vec::new();
+    for path in args.values_of_os("CONSUMERS").unwrap() {
+        consumers.push(map_file(path)?);
+    }
+    let consumers: Vec<&[u8]> = consumers.iter().map(|c| &c[..]).collect();
+    let report = exports::DeadExports::new(&library, &consumers)?;
+    match args.value_of("format") {
+        Some("json") | Some("yaml") => write_structured(args, &report)?,
+        Some("version-script") => print!("{}", report.version_script()),
+        _ => {
+            let format = size_format(args)?;
+            println!("{:>10}  SYMBOL", "SIZE");
+            for export in &report.dead {
+                println!("{:>10}  {:#}", format.size(export.size),
+                         rustc_demangle::demangle(&export.name));
+            }
+            println!();
+            println!("{} of {} exports are not imported by any consumer ({})", report.dead.len(),
+                     report.exported, savings(&format, report.dead_size));
+        }
+    }
+    Ok(())
+}
+
+fn debug_sections_main(args: &ArgMatches) -> Result<(), Error> {
+    let buf = map_file(args.value_of_os("FILE").unwrap())?;
+    let report = compression::debug_sections(&buf)?;
+    if structured(args) {
+        write_structured(args, &report)?;
+        return Ok(());
+    }
+    let format = size_format(args)?;
+    println!("{:>10} {:>12}  {:<5}  SECTION", "SIZE", "UNCOMPRESSED", "COMP");
+    for s in &report.sections {
+        println!("{:>10} {:>12}  {:<5}  {}", format.size(s.size), format.size(s.uncompressed_size),
+                 s.algorithm.as_deref().unwrap_or("-"), s.name);
+    }
+    println!();
+    println!("Debug info: {} in the file ({:.1}%), {} uncompressed", format.size(report.size),
+             100.0 * report.size as f64 / cmp::max(report.file_size, 1) as f64,
+             format.size(report.uncompressed_size));
+    if report.compression_saved > 0 {
+        println!("Compression saves {}", savings(&format, report.compression_saved));
+    }
+    Ok(())
+}
+
+fn delta_main(args: &ArgMatches) -> Result<(), Error> {
+    let old = map_file(args.value_of_os("OLD").unwrap())?;
+    let new = map_file(args.value_of_os("NEW").unwrap())?;
+    let report = delta::delta(&old, &new);
+    if structured(args) {
+        write_structured(args, &report)?;
+        return Ok(());
+    }
+    let format = size_format(args)?;
+    let change = format.size(report.size_change.unsigned_abs());
+    println!("{} -> {} ({}{})", format.size(report.old_size), format.size(report.new_size),
+             if report.size_change < 0 { "-" } else { "+" }, change);
+    println!("full download: {}", format.size(report.full_download));
+    println!("patch: {} ({} of control, {} of differences, {} inserted), {:.1}% of the full \
+              download", format.size(report.patch), format.size(report.control),
+             format.size(report.diff), format.size(report.extra),
+             100.0 * report.patch as f64 / cmp::max(report.full_download, 1) as f64);
+    println!("{} copied from the old file, {} of them unchanged; {} new",
+             format.size(report.copied), format.size(report.unchanged),
+             format.size(report.inserted));
+    Ok(())
+}
+
+fn dynamic_main(args: &ArgMatches) -> Result<(), Error> {
+    let buf = map_file(args.value_of_os("FILE").unwrap())?;
+    let script = match args.value_of_os("version-script") {
+        Some(path) => Some(version_script::VersionScript::load(Path::new(path))?),
+        None => None,
+    };
+    let report = dynamic::dynamic(&buf, script.as_ref())?;
+    if structured(args) {
+        write_structured(args, &report)?;
+        return Ok(());
+    }
+    let format = size_format(args)?;
+    println!("{:>10} {:>8} {:>8} {:>6} {:>9} {:>9}  SECTION",
+             "SIZE", "BUCKETS", "SYMBOLS", "EMPTY", "MAX CHAIN", "AVG CHAIN");
+    for table in &report.hash_tables {
+        println!("{:>10} {:>8} {:>8} {:>6} {:>9} {:>9.2}  {}", format.size(table.size),
+                 table.buckets, table.symbols, table.empty_buckets, table.max_chain,
+                 table.average_chain, table.section);
+    }
+    if report.hash_style_savings > 0 {
+        println!("Linking with --hash-style=gnu would save {}",
+                 format.size(report.hash_style_savings as u64));
+    }
+    if !report.relocations.is_empty() {
+        println!();
+        println!("{:>10} {:>8}  RELOCATION TYPE", "SIZE", "COUNT");
+        for r in &report.relocations {
+            println!("{:>10} {:>8}  {}", format.size(r.size), r.count, r.name);
+        }
+    }
+    if let Some(ref plt) = report.plt {
+        println!();
+        println!("{} PLT entries: {} in the PLT, {} in .got.plt, {} in relocations", plt.entries,
+                 format.size(plt.plt_size), format.size(plt.got_plt_size),
+                 format.size(plt.rela_plt_size));
+        if let (Some(sites), Some(delta)) = (plt.call_sites, plt.no_plt_delta) {
+            println!("Building with -fno-plt would change {} call sites, for a net {}", sites,
+                     format.delta(delta));
+        }
+        if plt.local_entries > 0 {
+            println!("{} entries are for functions defined here; linking with \
+                      -Bsymbolic-functions would save about {}", plt.local_entries,
+                     savings(&format, plt.symbolic_savings));
+        }
+    }
+    if let Some(ref pie) = report.pie {
+        println!();
+        println!("Position independence costs about {}{}:", savings(&format, pie.total()),
+                 if pie.static_pie { " (static-pie)" } else { "" });
+        println!("{:>10}  {} relative relocations", format.size(pie.relocation_size),
+                 pie.relative_relocations);
+        println!("{:>10}  {} GOT entries for local addresses", format.size(pie.got_size),
+                 pie.got_entries);
+        println!("{:>10}  self-relocation and PC thunk code", format.size(pie.startup_code));
+        if pie.static_pie {
+            println!("{:>10}  dynamic section and symbols", format.size(pie.dynamic_tables));
+        }
+    }
+    if let Some(ref impact) = report.version_script {
+        println!();
+        println!("The version script would make {} of {} exports local, saving about:",
+                 impact.hidden, impact.exported);
+        println!("{:>10}  .dynsym", format.size(impact.dynsym_savings));
+        println!("{:>10}  .dynstr", format.size(impact.dynstr_savings));
+        println!("{:>10}  .gnu.version", format.size(impact.versym_savings));
+        println!("{:>10}  hash tables", format.size(impact.hash_savings));
+        println!("{:>10}  total", format.size(impact.table_savings()));
+        println!("It would also make {} of functions and {} of data local, which the linker \
+                  can drop if unused", savings(&format, impact.hidden_code),
+                 savings(&format, impact.hidden_data));
+    }
+    Ok(())
+}
+
+/// The rules files of `--labels` and `--owners`, and for `--group-by component`, the components
+/// of `--components` or of the `components.toml` in the current directory or above it.
+fn group_rules(args: &ArgMatches, group_by: group::GroupBy) -> Result<group::Rules, Error> {
+    let mut rules = group::Rules::default();
+    if let Some(path) = args.value_of_os("labels") {
+        rules.labels = Some(labels::Labels::load(Path::new(path))?);
+    }
+    if let Some(path) = args.value_of_os("owners") {
+        rules.owners = Some(owners::Owners::load(Path::new(path), args.value_of("source-root"))?);
+    }
+    if group_by == group::GroupBy::Component {
+        let path = args.value_of_os("components").map(PathBuf::from)
+            .or_else(components::Components::find);
+        if let Some(path) = path {
+            rules.components = Some(components::Components::load(&path)?);
+        }
+    }
+    Ok(rules)
+}
+
+/// The rules of `group_rules` for the subcommand `subcommand`, which checks sizes against the
+/// components file and so can't do without one.
+fn component_rules(args: &ArgMatches, subcommand: &str) -> Result<group::Rules, Error> {
+    let rules = group_rules(args, group::GroupBy::Component)?;
+    if rules.components.is_none() {
+        return Err(exit::UsageError(format!(
+            "{} needs --components or a {} in the current directory or one above it",
+            subcommand, components::FILE_NAME)).into());
+    }
+    Ok(rules)
+}
+
+fn diff_main(args: &ArgMatches) -> Result<(), Error> {
+    let group_by = args.value_of("group-by").unwrap().parse::<group::GroupBy>()?;
+    // Grouped sizes aren't spilled to disk, so they would take no notice of the limit.
+    if group_by != group::GroupBy::None && args.is_present("max-memory") {
+        return Err(exit::UsageError("--group-by can't be combined with --max-memory".to_string())
+            .into());
+    }
+    let (old_path, new_path) = (args.value_of_os("OLD").unwrap(), args.value_of_os("NEW").unwrap());
+    let old = map_file(old_path)?;
+    let new = map_file(new_path)?;
+    // Saved reports only have the sizes of sections.
+    if (saved::is_report(&old) || saved::is_report(&new)) &&
+        (args.is_present("symbols") || args.is_present("compile-units")) {
+        return Err(exit::UsageError("--symbols and --compile-units need object files, not saved \
+                                     reports".to_string()).into());
+    }
+    let threshold = match args.value_of("ignore-delta-below") {
+        Some(s) => Some(s.parse::<diff::Threshold>()?),
+        None => None,
+    };
+    let normalize = args.is_present("normalize-names");
+    let include_non_alloc = args.is_present("include-non-alloc");
+    let infer_sizes = args.is_present("infer-sizes");
+    let rules = group_rules(args, group_by)?;
+    let entries = diff::diff_sizes(
+        &section_sizes(Path::new(old_path), &old, normalize, include_non_alloc)?,
+        &section_sizes(Path::new(new_path), &new, normalize, include_non_alloc)?);
+    let report = diff::DiffReport {
+        sections: diff::DiffTable::new(&entries, threshold, |name| name.to_string()),
+        symbols: if args.is_present("symbols") {
+            let display = |name: &str| format!("{:#}", rustc_demangle::demangle(name));
+            Some(match args.value_of("max-memory") {
+                _ if group_by != group::GroupBy::None => {
+                    let entries = diff::diff_sizes(
+                        &group::group_sizes(&old, group_by, &rules, infer_sizes)?,
+                        &group::group_sizes(&new, group_by, &rules, infer_sizes)?);
+                    diff::DiffTable::new(&entries, threshold, |name| name.to_string())
+                }
+                Some(limit) => {
+                    // Give each side half of the limit.
+                    let limit = spill::parse_limit(limit)? / 2;
+                    let (entries, unchanged) = diff::diff_sorted_sizes(
+                        spilled_symbol_sizes(&old, limit, infer_sizes)?,
+                        spilled_symbol_sizes(&new, limit, infer_sizes)?)?;
+                    diff::DiffTable::new(&entries, threshold, display).with_unchanged(unchanged)
+                }
+                None => {
+                    let entries = diff::diff_symbols(
+                        &symbols::symbols_inferring(&old, infer_sizes)?,
+                        &symbols::symbols_inferring(&new, infer_sizes)?);
+                    diff::DiffTable::new(&entries, threshold, display)
+                }
+            })
+        } else {
+            None
+        },
+        compile_units: if args.is_present("compile-units") {
+            let (old_units, new_units) = (dwarf::compile_unit_sizes(&old)?,
+                                          dwarf::compile_unit_sizes(&new)?);
+            if old_units.is_empty() || new_units.is_empty() {
+                bail!("--compile-units needs DWARF debug info in both files");
+            }
+            let entries = diff::diff_sizes(&old_units, &new_units);
+            Some(diff::DiffTable::new(&entries, threshold, |name| name.to_string()))
+        } else {
+            None
+        },
+        layout: if args.is_present("symbols") {
+            Some(layout::churn(&old, &new, 0)?)
+        } else {
+            None
+        },
+    };
+
+    let mut stdout = io::stdout();
+    if structured(args) {
+        write_structured(args, &report)?;
+    } else {
+        let format = size_format(args)?;
+        report.sections.print(&mut stdout, "Sections", &format)?;
+        if let Some(ref symbols) = report.symbols {
+            println!();
+            let title = match group_by {
+                group::GroupBy::None => "Symbols".to_string(),
+                _ => format!("Symbols by {}", args.value_of("group-by").unwrap()),
+            };
+            symbols.print(&mut stdout, &title, &format)?;
+        }
+        if let Some(ref layout) = report.layout {
+            println!();
+            println!("Layout: {} of {} of code moved out of order and {} added, {:.1}% churn",
+                     format.size(layout.moved_size), format.size(layout.code_size),
+                     format.size(layout.added_size), layout.churn);
+        }
+        if let Some(ref compile_units) = report.compile_units {
+            println!();
+            compile_units.print(&mut stdout, "Compile units", &format)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "disasm")]
+fn disasm_main(args: &ArgMatches) -> Result<(), Error> {
+    let buf = map_file(args.value_of_os("FILE").unwrap())?;
+    let report = disasm::disasm(&buf)?;
+    if structured(args) {
+        write_structured(args, &report)?;
+        return Ok(());
+    }
+    let format = size_format(args)?;
+    println!("{:>10} {:>10} {:>10} {:>10} {:>12}  SECTION", "SIZE", "FUNCTIONS", "ALIGNMENT",
+             "FILLER", "UNATTRIBUTED");
+    for s in &report.sections {
+        println!("{:>10} {:>10} {:>10} {:>10} {:>12}  {}", format.size(s.size),
+                 format.size(s.functions), format.size(s.alignment), format.size(s.filler),
+                 format.size(s.unattributed), s.name);
+    }
+    let total: u64 = report.sections.iter().map(|s| s.size).sum();
+    let functions: u64 = report.sections.iter().map(|s| s.functions).sum();
+    println!("Alignment: {} of {} of functions ({:.1}%) aligns branch targets, in {} runs",
+             format.size(report.alignment), format.size(functions),
+             100.0 * report.alignment as f64 / cmp::max(functions, 1) as f64,
+             report.alignment_runs);
+    println!("Filler: {} of {} of code ({:.1}%) pads between functions",
+             format.size(report.filler), format.size(total),
+             100.0 * report.filler as f64 / cmp::max(total, 1) as f64);
+    println!("Verified the bounds of {} of {} functions", report.verified, report.functions);
+    if !report.issues.is_empty() {
+        println!();
+        println!("{:>18} {:>10}  {:<13}  FUNCTION", "ADDRESS", "SIZE", "ISSUE");
+        for issue in &report.issues {
+            println!("{:>#18x} {:>10}  {:<13}  {}", issue.address, format.size(issue.size),
+                     issue.issue, issue.name);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "disasm"))]
+fn disasm_main(_args: &ArgMatches) -> Result<(), Error> {
+    Err(exit::UsageError("rust-size was built without the `disasm` feature".to_string()).into())
+}
+
+/// Format `size` for a sentence: `123 bytes` with plain byte counts, `1.2 KiB` otherwise.
+fn savings(format: &units::SizeFormat, size: u64) -> String {
+    match format.units {
+        units::Units::Bytes => format!("{} bytes", format.size(size)),
+        _ => format.size(size),
+    }
+}
+
+fn hints_main(args: &ArgMatches) -> Result<(), Error> {
+    let buf = map_file(args.value_of_os("FILE").unwrap())?;
+    let hints = hints::hints(&buf)?;
+    if structured(args) {
+        write_structured(args, &hints)?;
+    } else {
+        let format = size_format(args)?;
+        for hint in &hints {
+            println!("{}: {} (estimated savings: {})", hint.id, hint.message,
+                     savings(&format, hint.savings));
+        }
+    }
+    Ok(())
+}
+
+fn duplicates_main(args: &ArgMatches) -> Result<(), Error> {
+    let min_size = match args.value_of("min-size").unwrap().parse() {
+        Ok(size) => size,
+        Err(_) => return Err(exit::UsageError("Invalid --min-size".to_string()).into()),
+    };
+    if args.occurrences_of("FILE") > 1 {
+        return shared_duplicates_main(args, min_size);
+    }
+    let buf = map_file(args.value_of_os("FILE").unwrap())?;
+    let symbols = symbols::symbols(&buf)?;
+    let report = duplicates::DuplicateReport::new(
+        duplicates::duplicates(&buf, &symbols, args.is_present("code"), min_size));
+    if structured(args) {
+        write_structured(args, &report)?;
+    } else {
+        report.print(|name| format!("{:#}", rustc_demangle::demangle(name)), &size_format(args)?);
+    }
+    Ok(())
+}
+
+/// Report code or data duplicated across the files of a `duplicates` run.
+fn shared_duplicates_main(args: &ArgMatches, min_size: u64) -> Result<(), Error> {
+    let mut maps = Vec::new();
+    for path in args.values_of_os("FILE").unwrap() {
+        maps.push((path.to_string_lossy().into_owned(), map_file(path)?));
+    }
+    let mut files = Vec::new();
+    for (path, buf) in &maps {
+        let symbols = symbols::symbols(buf).map_err(|e| format_err!("{}: {}", path, e))?;
+        files.push((path.clone(), &buf[..], symbols));
+    }
+    let report = duplicates::SharedReport::new(
+        duplicates::shared_duplicates(&files, args.is_present("code"), min_size));
+    if structured(args) {
+        write_structured(args, &report)?;
+    } else {
+        report.print(|name| format!("{:#}", rustc_demangle::demangle(name)), &size_format(args)?);
+    }
+    Ok(())
+}
+
+/// Describe the layout of a device tree blob in one line.
+fn dtb_summary(dtb: &fdt::Breakdown, format: &units::SizeFormat) -> String {
+    format!("device tree: {} structure ({} nodes, {} properties), {} strings, {} reserved \
+             memory ({} entries), {} padding", format.size(dtb.structure), dtb.nodes,
+            dtb.properties, format.size(dtb.strings), format.size(dtb.memory_reserve),
+            dtb.reservations, format.size(dtb.padding))
+}
+
+fn firmware_main(args: &ArgMatches) -> Result<(), Error> {
+    let buf = map_file(args.value_of_os("FILE").unwrap())?;
+    let report = firmware::firmware(&buf)?;
+    if structured(args) {
+        write_structured(args, &report)?;
+        return Ok(());
+    }
+    let format = size_format(args)?;
+    println!("{} of {} in {} images{}", report.format, format.size(report.file_size),
+             report.payloads.len(),
+             report.description.as_ref().map_or_else(String::new, |d| format!(": {}", d)));
+    println!("{:>10} {:>10}  {:<10} {:<8} {:<8} {:<6} NAME",
+             "SIZE", "OFFSET", "TYPE", "ARCH", "OS", "COMP");
+    for p in &report.payloads {
+        println!("{:>10} {:>#10x}  {:<10} {:<8} {:<8} {:<6} {}{}", format.size(p.size), p.offset,
+                 p.kind, p.arch.as_deref().unwrap_or("-"), p.os.as_deref().unwrap_or("-"),
+                 p.compression, p.name,
+                 p.description.as_ref().map_or_else(String::new, |d| format!(" ({})", d)));
+        if let Some(ref column) = p.analysis {
+            let category = |section| format.size(column.categories[&section]);
+            println!("{:>10} {:>10}  {} object: {} text, {} data, {} bss", "", "", column.arch,
+                     category(Section::Text), category(Section::Data), category(Section::Bss));
+        }
+        if let Some(ref dtb) = p.dtb {
+            println!("{:>10} {:>10}  {}", "", "", dtb_summary(dtb, &format));
+        }
+    }
+    if !report.configurations.is_empty() {
+        println!("Configurations: {}", report.configurations.join(", "));
+    }
+    Ok(())
+}
+
+fn download_main(args: &ArgMatches) -> Result<(), Error> {
+    let report = download::DownloadReport::new(Path::new(args.value_of_os("PATH").unwrap()))?;
+    if structured(args) {
+        write_structured(args, &report)?;
+        return Ok(());
+    }
+    let format = size_format(args)?;
+    println!("{:<12} {:>10} {:>10}", "DEVICE", "INSTALL", "DOWNLOAD");
+    for configuration in &report.configurations {
+        println!("{:<12} {:>10} {:>10}", configuration.name, format.size(configuration.install),
+                 format.size(configuration.download));
+    }
+    println!("{} as built, as delivered by {}", format.size(report.size),
+             if report.store == "play" { "Google Play" } else { "the App Store" });
+    Ok(())
+}
+
+fn drop_glue_main(args: &ArgMatches) -> Result<(), Error> {
+    let buf = map_file(args.value_of_os("FILE").unwrap())?;
+    let top = args.value_of("top").unwrap().parse::<usize>()
+        .map_err(|_| exit::UsageError("Invalid --top".to_string()))?;
+    let report = generated::drop_glue(&buf, args.value_of("group-by") == Some("base"))?;
+    if structured(args) {
+        write_structured(args, &report)?;
+        return Ok(());
+    }
+    let format = size_format(args)?;
+    if !report.types.is_empty() && top > 0 {
+        println!("{:>10} {:>6} {:>6}  TYPE", "SIZE", "COUNT", "DEPTH");
+        for glue in report.types.iter().take(top) {
+            println!("{:>10} {:>6} {:>6}  {}", format.size(glue.size), glue.count, glue.depth,
+                     glue.name);
+        }
+        println!();
+    }
+    println!("{} drop_in_place instantiations for {} types: {} ({:.1}% of functions)",
+             report.count, report.types.len(), format.size(report.size),
+             100.0 * report.size as f64 / cmp::max(report.code_size, 1) as f64);
+    Ok(())
+}
+
+fn fmt_main(args: &ArgMatches) -> Result<(), Error> {
+    let buf = map_file(args.value_of_os("FILE").unwrap())?;
+    let top = args.value_of("top").unwrap().parse::<usize>()
+        .map_err(|_| exit::UsageError("Invalid --top".to_string()))?;
+    let report = formatting::fmt(&buf)?;
+    if structured(args) {
+        write_structured(args, &report)?;
+        return Ok(());
+    }
+    let format = size_format(args)?;
+    println!("{:>10} {:>10}  KIND", "COUNT", "SIZE");
+    for (kind, totals) in &report.kinds {
+        println!("{:>10} {:>10}  {}", totals.count, format.size(totals.size), kind);
+    }
+    println!("Formatting: {} of {} of symbols ({:.1}%)", format.size(report.size),
+             format.size(report.symbol_size),
+             100.0 * report.size as f64 / cmp::max(report.symbol_size, 1) as f64);
+    println!("-Zfmt-debug=none would save about {}", savings(&format, report.debug_savings));
+    println!("ufmt would save about {}", savings(&format, report.ufmt_savings));
+    if !report.symbols.is_empty() && top > 0 {
+        println!();
+        println!("{:>10}  {:<13}  SYMBOL", "SIZE", "KIND");
+        for sym in report.symbols.iter().take(top) {
+            println!("{:>10}  {:<13}  {}", format.size(sym.size), sym.kind, sym.name);
+        }
+    }
+    Ok(())
+}
+
+fn generated_main(args: &ArgMatches) -> Result<(), Error> {
+    let buf = map_file(args.value_of_os("FILE").unwrap())?;
+    let top = args.value_of("top").unwrap().parse::<usize>()
+        .map_err(|_| exit::UsageError("Invalid --top".to_string()))?;
+    let report = generated::generated(&buf)?;
+    if structured(args) {
+        write_structured(args, &report)?;
+        return Ok(());
+    }
+    let format = size_format(args)?;
+    let share = |size: u64| 100.0 * size as f64 / cmp::max(report.code_size, 1) as f64;
+    println!("{:>10} {:>10} {:>6}  KIND", "COUNT", "SIZE", "CODE%");
+    for (kind, totals) in &report.kinds {
+        println!("{:>10} {:>10} {:>5.1}%  {}", totals.count, format.size(totals.size),
+                 share(totals.size), kind);
+    }
+    println!("Compiler-generated: {} ({:.1}%), user code: {} ({:.1}%)", format.size(report.size),
+             share(report.size), format.size(report.user_size), share(report.user_size));
+    if !report.functions.is_empty() && top > 0 {
+        println!();
+        println!("{:>10}  {:<10}  FUNCTION", "SIZE", "KIND");
+        for f in report.functions.iter().take(top) {
+            println!("{:>10}  {:<10}  {}", format.size(f.size), f.kind, f.name);
+        }
+    }
+    Ok(())
+}
+
+fn hotness_main(args: &ArgMatches) -> Result<(), Error> {
+    let number = |name: &str| {
+        args.value_of(name).unwrap().parse::<u64>()
+            .map_err(|_| exit::UsageError(format!("Invalid --{}", name)))
+    };
+    let (big, top) = (number("big")?, number("top")? as usize);
+    let buf = map_file(args.value_of_os("FILE").unwrap())?;
+    let samples = map_file(args.value_of_os("samples").unwrap())?;
+    let report = hotness::hotness(&buf, &samples, big)?;
+    if structured(args) {
+        write_structured(args, &report)?;
+        return Ok(());
+    }
+    let format = size_format(args)?;
+    let table = |heading: &str, functions: &[&hotness::HotFunction]| {
+        println!("{}", heading);
+        println!("{:>10} {:>6} {:>10} {:>6} {:>8}  {:<9}  FUNCTION",
+                 "SIZE", "SIZE%", "SAMPLES", "SAMP%", "HEAT", "CLASS");
+        for f in functions {
+            println!("{:>10} {:>5.1}% {:>10} {:>5.1}% {:>8.2}  {:<9}  {}", format.size(f.size),
+                     100.0 * f.size_share, f.samples, 100.0 * f.sample_share, f.heat,
+                     f.class.unwrap_or("-"), f.name);
+        }
+        println!();
+    };
+    let largest: Vec<_> = report.functions.iter().take(top).collect();
+    let mut hottest: Vec<_> = report.functions.iter().filter(|f| f.samples > 0).collect();
+    hottest.sort_by(|a, b| (b.samples, a.size, &a.name).cmp(&(a.samples, b.size, &b.name)));
+    hottest.truncate(top);
+    if top > 0 {
+        table("Largest functions:", &largest);
+        table("Hottest functions:", &hottest);
+    }
+    println!("{} samples in {} of functions, {} samples elsewhere", report.samples,
+             format.size(report.code_size), report.unmatched_samples);
+    println!("{} big and cold functions: {} ({:.1}% of functions)", report.big_cold,
+             format.size(report.big_cold_size),
+             100.0 * report.big_cold_size as f64 / cmp::max(report.code_size, 1) as f64);
+    println!("{} small and hot functions: {:.1}% of samples", report.small_hot,
+             100.0 * report.small_hot_share);
+    Ok(())
+}
+
+fn hugepages_main(args: &ArgMatches) -> Result<(), Error> {
+    let page_size = spill::parse_limit(args.value_of("page-size").unwrap())? as u64;
+    if !page_size.is_power_of_two() {
+        return Err(exit::UsageError("--page-size must be a power of two".to_string()).into());
+    }
+    let buf = map_file(args.value_of_os("FILE").unwrap())?;
+    let segments = hugepages::text_segments(&buf, page_size)?;
+    if structured(args) {
+        write_structured(args, &segments)?;
+        return Ok(());
+    }
+    let format = size_format(args)?;
+    println!("{:>18} {:>10} {:>9} {:>7} {:>6} {:>10} {:>10} {:>10} {:>10}",
+             "ADDRESS", "SIZE", "ALIGN", "ALIGNED", "PAGES", "SMALL", "FILE PAD", "MEM PAD",
+             "COST");
+    for s in &segments {
+        println!("{:>#18x} {:>10} {:>#9x} {:>7} {:>6} {:>10} {:>10} {:>10} {:>10}", s.address,
+                 format.size(s.size), s.alignment, if s.aligned { "yes" } else { "no" },
+                 s.huge_pages, format.size(s.unaligned_bytes), format.size(s.file_padding),
+                 format.size(s.memory_padding), format.size(s.alignment_cost));
+    }
+    Ok(())
+}
+
+fn isa_main(args: &ArgMatches) -> Result<(), Error> {
+    let top = args.value_of("top").unwrap().parse::<usize>()
+        .map_err(|_| exit::UsageError("Invalid --top".to_string()))?;
+    let bufs = args.values_of_os("FILES").unwrap().map(map_file)
+        .collect::<Result<Vec<_>, Error>>()?;
+    let mut builds = Vec::new();
+    for buf in &bufs {
+        builds.extend(isa::slices(buf)?);
+    }
+    if builds.len() != 2 {
+        return Err(exit::UsageError(format!(
+            "isa compares two builds, either two files or a universal binary with two slices, \
+             but got {}", builds.len())).into());
+    }
+    let (other_arch, other) = builds.pop().unwrap();
+    let (baseline_arch, baseline) = builds.pop().unwrap();
+    let report = isa::isa(baseline, baseline_arch, other, other_arch, top)?;
+    if structured(args) {
+        write_structured(args, &report)?;
+        return Ok(());
+    }
+    let format = size_format(args)?;
+    println!("{} functions: {} on {}, {} on {}, {:.2}x", report.functions,
+             format.size(report.baseline_size), report.baseline_arch,
+             format.size(report.other_size), report.other_arch, report.factor);
+    println!();
+    println!("{:>10} {:>10} {:>7} {:>10} {:>10} {:>10}  CRATE", "BASELINE", "OTHER", "FACTOR",
+             "EXCESS", "ONLY BASE", "ONLY OTHER");
+    for krate in &report.crates {
+        println!("{:>10} {:>10} {:>6.2}x {:>+10} {:>10} {:>10}  {}",
+                 format.size(krate.baseline_size), format.size(krate.other_size), krate.factor,
+                 krate.excess, format.size(krate.baseline_only_size),
+                 format.size(krate.other_only_size), krate.name);
+    }
+    if !report.outliers.is_empty() {
+        println!();
+        println!("{:>10} {:>10} {:>7} {:>10}  FUNCTION", "BASELINE", "OTHER", "RATIO", "EXCESS");
+        for function in &report.outliers {
+            println!("{:>10} {:>10} {:>6.2}x {:>+10}  {}", format.size(function.baseline_size),
+                     format.size(function.other_size), function.ratio, function.excess,
+                     function.name);
+        }
+    }
+    println!();
+    println!("{} only on {}, {} only on {}; EXCESS is the size beyond {:.2}x the baseline",
+             format.size(report.baseline_only_size), report.baseline_arch,
+             format.size(report.other_only_size), report.other_arch, report.factor);
+    Ok(())
+}
+
+fn jump_tables_main(args: &ArgMatches) -> Result<(), Error> {
+    let buf = map_file(args.value_of_os("FILE").unwrap())?;
+    let top = args.value_of("top").unwrap().parse::<usize>()
+        .map_err(|_| exit::UsageError("Invalid --top".to_string()))?;
+    let report = switches::jump_tables(&buf)?;
+    if structured(args) {
+        write_structured(args, &report)?;
+        return Ok(());
+    }
+    let format = size_format(args)?;
+    println!("{:>10} {:>7} {:>8} {:>13}  FUNCTION", "SIZE", "TABLES", "ENTRIES",
+             "FUNCTION SIZE");
+    for function in report.functions.iter().take(top) {
+        let entries: u64 = function.tables.iter().filter_map(|table| table.entries).sum();
+        println!("{:>10} {:>7} {:>8} {:>13}  {}{}", format.size(function.size),
+                 function.tables.len(), entries, format.size(function.function_size),
+                 function.name, if function.sparse { " (sparse)" } else { "" });
+    }
+    if report.functions.len() > top {
+        println!("... and {} more functions", report.functions.len() - top);
+    }
+    if !report.unattributed.is_empty() {
+        let size: u64 = report.unattributed.iter().map(|table| table.size).sum();
+        println!("{} in {} tables outside any function", format.size(size),
+                 report.unattributed.len());
+    }
+    println!("{} in {} jump tables of {} functions", format.size(report.size), report.tables,
+             report.functions.len());
+    let sparse = report.functions.iter().filter(|function| function.sparse).count();
+    if sparse > 0 {
+        println!("{} functions have sparse tables, which mostly jump to one arm; a binary \
+                  search or a lookup table may be smaller", sparse);
+    }
+    Ok(())
+}
+
+fn kernel_main(args: &ArgMatches) -> Result<(), Error> {
+    let buf = map_file(args.value_of_os("FILE").unwrap())?;
+    let report = kernel::kernel(&buf)?;
+    if structured(args) {
+        write_structured(args, &report)?;
+        return Ok(());
+    }
+    let format = size_format(args)?;
+    println!("{:>10} {:>10} {:>8}  {:<8}  GROUP", "SIZE", "DISCARDED", "SECTIONS", "CATEGORY");
+    for group in &report.groups {
+        println!("{:>10} {:>10} {:>8}  {:<8}  {}", format.size(group.size),
+                 format.size(group.discarded), group.sections, format!("{:?}", group.category),
+                 group.name);
+    }
+    let when = match report.kind {
+        "module" => "once the module is initialized",
+        _ => "after boot",
+    };
+    println!("Loaded: {}, freed {}: {}, resident: {}", format.size(report.loaded_size), when,
+             format.size(report.init_size), format.size(report.resident_size));
+    Ok(())
+}
+
+fn layout_main(args: &ArgMatches) -> Result<(), Error> {
+    let top = args.value_of("top").unwrap().parse::<usize>()
+        .map_err(|_| exit::UsageError("Invalid --top".to_string()))?;
+    let old = map_file(args.value_of_os("OLD").unwrap())?;
+    let new = map_file(args.value_of_os("NEW").unwrap())?;
+    let report = layout::churn(&old, &new, top)?;
+    if structured(args) {
+        write_structured(args, &report)?;
+        return Ok(());
+    }
+    let format = size_format(args)?;
+    if !report.moved_functions.is_empty() {
+        println!("{:>10} {:>18} {:>18}  FUNCTION", "SIZE", "OLD ADDRESS", "NEW ADDRESS");
+        for function in &report.moved_functions {
+            println!("{:>10} {:>#18x} {:>#18x}  {}", format.size(function.size),
+                     function.old_address, function.new_address, function.name);
+        }
+        let listed = report.moved_functions.len() as u64;
+        if report.moved > listed {
+            println!("... and {} more functions", report.moved - listed);
+        }
+        println!();
+    }
+    println!("{} functions ({}) in both builds: {} moved out of order ({}), {} at a new address",
+             report.functions, format.size(report.size), report.moved,
+             format.size(report.moved_size), format.size(report.shifted_size));
+    println!("{} added, {} removed; {:.1}% of the new build's {} of code isn't laid out as \
+              before", format.size(report.added_size), format.size(report.removed_size),
+             report.churn, format.size(report.code_size));
+    Ok(())
+}
+
+fn link_inputs_main(args: &ArgMatches) -> Result<(), Error> {
+    let list = args.value_of("INPUTS").unwrap();
+    let paths = inputs::read_input_list(Path::new(list.strip_prefix('@').unwrap_or(list)))?;
+    let report = inputs::InputReport::new(&paths, args.is_present("normalize-names"),
+                                          args.is_present("include-non-alloc"))?;
+    if structured(args) {
+        write_structured(args, &report)?;
+    } else {
+        report.print(&size_format(args)?);
+    }
+    Ok(())
+}
+
+fn merge_main(args: &ArgMatches) -> Result<(), Error> {
+    let buf = map_file(args.value_of_os("FILE").unwrap())?;
+    let mut reports = BTreeMap::new();
+    for (arch, slice) in arch::universal_slices(&buf)? {
+        reports.insert(arch, merge::merge_stats(slice)?);
+    }
+    if structured(args) {
+        return write_by_arch(args, reports);
+    }
+    let format = size_format(args)?;
+    print_by_arch(reports, |stats| {
+        println!("{:>10} {:>8} {:>8} {:>10} {:>10}  {:<6}  SECTION",
+                 "SIZE", "ENTRIES", "UNIQUE", "DUPLICATE", "TAIL", "MERGED");
+        for s in stats {
+            println!("{:>10} {:>8} {:>8} {:>10} {:>10}  {:<6}  {}", format.size(s.size),
+                     s.entries, s.unique, format.size(s.duplicate_bytes),
+                     format.size(s.tail_mergeable_bytes),
+                     if s.merged() { "yes" } else { "no" }, s.section);
+        }
+        println!("{} could be saved by merging",
+                 savings(&format, stats.iter().map(merge::MergeStats::savings).sum()));
+        Ok(())
+    })
+}
+
+fn analyze_main(args: &ArgMatches) -> Result<(), Error> {
+    let (entries, failure) = analyze::analyze(Path::new(args.value_of_os("DIR").unwrap()),
+                                              args.value_of_os("cache").map(Path::new),
+                                              args.is_present("include-non-alloc"))?;
+    if structured(args) {
+        write_structured(args, &entries)?;
+    } else {
+        let format = size_format(args)?;
+        let category = |entry: &analyze::Entry, section| {
+            format.size(entry.sizes.categories.get(&section).cloned().unwrap_or(0))
+        };
+        println!("{:>10} {:>10} {:>10} {:>10} {:>10}  FILE",
+                 "TEXT", "DATA", "BSS", "OTHER", "TOTAL");
+        for entry in &entries {
+            println!("{:>10} {:>10} {:>10} {:>10} {:>10}  {}{}",
+                     category(entry, Section::Text), category(entry, Section::Data),
+                     category(entry, Section::Bss), category(entry, Section::Other),
+                     format.size(entry.sizes.total), entry.path,
+                     if entry.cached { " (cached)" } else { "" });
+            if let Some(ref dtb) = entry.sizes.dtb {
+                println!("{:>54}  {}", "", dtb_summary(dtb, &format));
+            }
+        }
+        println!("{} files, {} from cache", entries.len(),
+                 entries.iter().filter(|e| e.cached).count());
+    }
+    match failure {
+        Some(failure) => Err(failure.into()),
+        None => Ok(()),
+    }
+}
+
+fn android_abis_main(args: &ArgMatches) -> Result<(), Error> {
+    let threshold = args.value_of("threshold").unwrap().trim_end_matches('%').parse::<f64>()
+        .map_err(|_| exit::UsageError("Invalid --threshold".to_string()))?;
+    let report = android::AbiReport::new(Path::new(args.value_of_os("PATH").unwrap()),
+                                         threshold)?;
+    if structured(args) {
+        write_structured(args, &report)?;
+        return Ok(());
+    }
+    let format = size_format(args)?;
+    let width = report.libraries.iter().map(|l| l.name.len()).max().unwrap_or(0).max(7);
+    print!("{:<width$}", "LIBRARY", width = width);
+    for abi in &report.abis {
+        print!(" {:>12}", abi);
+    }
+    println!();
+    for library in &report.libraries {
+        print!("{:<width$}", library.name, width = width);
+        for abi in &report.abis {
+            let cell = match library.sizes.get(abi) {
+                Some(&size) if library.outliers.contains_key(abi) => {
+                    format!("{}!", format.size(size))
+                }
+                Some(&size) => format.size(size),
+                None => "-".to_string(),
+            };
+            print!(" {:>12}", cell);
+        }
+        println!();
+    }
+    let outliers: Vec<_> = report.libraries.iter()
+        .flat_map(|l| l.outliers.iter().map(move |(abi, excess)| (&l.name, abi, excess)))
+        .collect();
+    if !outliers.is_empty() {
+        println!();
+        for (library, abi, excess) in outliers {
+            println!("{}: {} is {:.1}% larger than the median of the other ABIs",
+                     library, abi, excess);
+        }
+    }
+    Ok(())
+}
+
+fn assets_main(args: &ArgMatches) -> Result<(), Error> {
+    let min_size = match args.value_of("min-size").unwrap().parse() {
+        Ok(size) => size,
+        Err(_) => return Err(exit::UsageError("Invalid --min-size".to_string()).into()),
+    };
+    let buf = map_file(args.value_of_os("FILE").unwrap())?;
+    let assets = assets::assets(&buf, min_size)?;
+    if structured(args) {
+        write_structured(args, &assets)?;
+        return Ok(());
+    }
+    let format = size_format(args)?;
+    println!("{:>10} {:>18}  {:<9} {:<40} LOCATION", "SIZE", "ADDRESS", "KIND", "NAME");
+    for a in &assets {
+        println!("{:>10} {:>#18x}  {:<9} {:<40} {}", format.size(a.size), a.address, a.kind,
+                 a.name, a.location.as_deref().unwrap_or("?"));
+    }
+    // Identical assets are merged, so count the bytes at each address once.
+    let mut distinct = BTreeMap::new();
+    for a in &assets {
+        let size = distinct.entry(a.address).or_insert(0);
+        *size = cmp::max(*size, a.size);
+    }
+    println!("{} embedded assets, {}", assets.len(), format.size(distinct.values().sum()));
+    Ok(())
+}
+
+fn paths_main(args: &ArgMatches) -> Result<(), Error> {
+    let buf = map_file(args.value_of_os("FILE").unwrap())?;
+    let report = paths::path_report(&buf)?;
+    if structured(args) {
+        write_structured(args, &report)?;
+        return Ok(());
+    }
+    let format = size_format(args)?;
+    println!("Loaded data: {} absolute paths, {}", report.loaded_paths,
+             format.size(report.loaded_bytes));
+    println!("Debug info: {} absolute paths, {}", report.debug_paths,
+             format.size(report.debug_bytes));
+    if report.remapped_paths > 0 {
+        println!("({} paths of the standard library are already remapped by the toolchain)",
+                 report.remapped_paths);
+    }
+    if report.prefixes.is_empty() {
+        return Ok(());
+    }
+    println!();
+    println!("{:>10} {:>10} {:>7}  PREFIX", "LOADED", "DEBUG", "PATHS");
+    for p in &report.prefixes {
+        println!("{:>10} {:>10} {:>7}  {}", format.size(p.loaded_savings),
+                 format.size(p.debug_savings), p.loaded_paths + p.debug_paths, p.prefix);
+    }
+    let loaded: u64 = report.prefixes.iter().map(|p| p.loaded_savings).sum();
+    let debug: u64 = report.prefixes.iter().map(|p| p.debug_savings).sum();
+    println!("Remapping these prefixes would save {} of loaded data and {} of debug info, e.g. \
+              with RUSTFLAGS:", format.size(loaded), format.size(debug));
+    for p in &report.prefixes {
+        println!("  --remap-path-prefix={}=", p.prefix);
+    }
+    Ok(())
+}
+
+fn cold_main(args: &ArgMatches) -> Result<(), Error> {
+    let number = |name: &str| {
+        args.value_of(name).unwrap().parse::<u64>()
+            .map_err(|_| exit::UsageError(format!("Invalid --{}", name)))
+    };
+    let (max_count, min_size, top) = (number("max-count")?, number("min-size")?, number("top")?);
+    let buf = map_file(args.value_of_os("FILE").unwrap())?;
+    let profile = match (args.value_of_os("profdata"), args.value_of_os("perf")) {
+        (Some(path), _) => Some((cold::Profile::Profdata, map_file(path)?)),
+        (_, Some(path)) => Some((cold::Profile::Perf, map_file(path)?)),
+        _ => None,
+    };
+    let profile = profile.as_ref().map(|&(kind, ref data)| (kind, &data[..]));
+    let report = cold::cold(&buf, profile, max_count, min_size)?;
+    if structured(args) {
+        write_structured(args, &report)?;
+        return Ok(());
+    }
+    let format = size_format(args)?;
+    if !report.candidates.is_empty() && top > 0 {
+        println!("{:>10} {:>10}  {:<15}  FUNCTION", "SIZE", "COUNT", "REASON");
+        for f in report.candidates.iter().take(top as usize) {
+            let count = f.count.map_or_else(|| "-".to_string(), |count| count.to_string());
+            println!("{:>10} {:>10}  {:<15}  {}", format.size(f.size), count, f.reason, f.name);
+        }
+        println!();
+    }
+    println!("{} of {} of functions are cold ({:.1}%)", format.size(report.cold_size),
+             format.size(report.code_size),
+             100.0 * report.cold_size as f64 / cmp::max(report.code_size, 1) as f64);
+    if report.source != "names" {
+        println!("{} of {} functions are in the {} profile", report.profiled, report.functions,
+                 report.source);
+    }
+    if report.already_split > 0 {
+        println!("{} are already split out into .cold functions",
+                 format.size(report.already_split));
+    }
+    Ok(())
+}
+
+fn compare_main(args: &ArgMatches) -> Result<(), Error> {
+    let mut columns = Vec::new();
+    for path in args.values_of_os("FILES").unwrap() {
+        let buf = map_file(path)?;
+        columns.push(compare::Column::new(path.to_string_lossy().into_owned(), &buf,
+                                          args.is_present("normalize-names"),
+                                          args.is_present("include-non-alloc"))?);
+    }
+    if structured(args) {
+        write_structured(args, &columns)?;
+    } else {
+        compare::print_matrix(&columns, &size_format(args)?);
+    }
+    Ok(())
+}
+
+fn find_main(args: &ArgMatches) -> Result<(), Error> {
+    let pattern = Regex::new(args.value_of("PATTERN").unwrap())
+        .map_err(|e| exit::UsageError(format!("Invalid pattern: {}", e)))?;
+    let buf = map_file(args.value_of_os("FILE").unwrap())?;
+    let matches = find::find(&buf, &pattern, args.is_present("infer-sizes"))?;
+    if structured(args) {
+        write_structured(args, &matches)?;
+        return Ok(());
+    }
+    let format = size_format(args)?;
+    println!("{:>10} {:>18}  {:<20} SYMBOL", "SIZE", "ADDRESS", "SECTION");
+    for m in &matches {
+        // Mark the sizes that are only the distance to the next symbol.
+        let size = format.size(m.size);
+        println!("{:>10} {:>#18x}  {:<20} {}",
+                 if m.inferred { format!("~{}", size) } else { size }, m.address,
+                 m.section.as_deref().unwrap_or("?"), m.name);
+    }
+    println!("{} matching symbols, {}", matches.len(),
+             format.size(matches.iter().map(|m| m.size).sum()));
+    Ok(())
+}
+
+/// The size of the symbols of one component, for `components`.
+#[derive(Serialize)]
+struct ComponentSize {
+    name: String,
+    symbols: u64,
+    size: u64,
+}
+
+/// The report printed by `components`: the components of the project, largest first, and the
+/// size of all the symbols.
+#[derive(Serialize)]
+struct ComponentsReport {
+    /// The components file.
+    config: String,
+    components: Vec<ComponentSize>,
+    total: u64,
+}
+
+/// The symbols of the file `buf` at `path`, counting aliases once.
+fn unaliased_symbols(path: &Path, buf: &[u8]) -> Result<Vec<symbols::Symbol>, Error> {
+    let (mut symbols, _) = file_symbols(path, buf, None, None)?;
+    symbols.sort_by(|a, b| {
+        (&a.section, a.address, b.size, &a.name).cmp(&(&b.section, b.address, a.size, &b.name))
+    });
+    symbols.dedup_by(|a, b| a.section == b.section && a.address == b.address);
+    Ok(symbols)
+}
+
+/// Check the crates and components of FILE against the budgets of the components file, and
+/// list those over their budgets, failing with `exit::BudgetExceeded` if there are any.
+fn budget_main(args: &ArgMatches) -> Result<(), Error> {
+    let webhook = args.value_of("notify-webhook");
+    if webhook.is_some_and(|url| !url.starts_with("http://") && !url.starts_with("https://")) {
+        return Err(exit::UsageError("--notify-webhook needs an http or https URL".to_string())
+                   .into());
+    }
+    let rules = component_rules(args, "budget")?;
+    let path = Path::new(args.value_of_os("FILE").unwrap());
+    let buf = map_file(path.as_os_str())?;
+    let by_crate = group::Grouper::new(&buf, group::GroupBy::Crate, &rules)?;
+    let by_component = group::Grouper::new(&buf, group::GroupBy::Component, &rules)?;
+    let (mut crates, mut components) = (BTreeMap::new(), BTreeMap::new());
+    for sym in unaliased_symbols(path, &buf)? {
+        *crates.entry(by_crate.group(&sym)).or_insert(0) += sym.size;
+        *components.entry(by_component.group(&sym)).or_insert(0) += sym.size;
+    }
+    let report = budget::check(rules.components.as_ref().unwrap(), &crates, &components);
+    if structured(args) {
+        write_structured(args, &report)?;
+    } else {
+        let format = size_format(args)?;
+        println!("{} of {} budgets from {} exceeded", report.violations.len(), report.checked,
+                 report.config);
+        if !report.violations.is_empty() {
+            println!();
+            println!("{:>10} {:>10} {:>10}  {:<9}  NAME", "SIZE", "BUDGET", "EXCESS", "KIND");
+            for violation in &report.violations {
+                let source = match violation.source {
+                    components::BudgetSource::Default => " (default budget)",
+                    components::BudgetSource::Override => "",
+                };
+                println!("{:>10} {:>10} {:>10}  {:<9}  {}{}", format.size(violation.size),
+                         format.size(violation.budget), format.delta(violation.excess as i64),
+                         violation.kind, violation.name, source);
+            }
+        }
+    }
+    if !report.violations.is_empty() {
+        // The budgets being exceeded is what the exit status is about, so a notification that
+        // can't be sent only warns.
+        if let Some(url) = webhook {
+            let notification = report.notification(&path.to_string_lossy());
+            if let Err(e) = notify::post(url, &notification) {
+                eprintln!("--notify-webhook: {}", e);
+            }
+        }
+        return Err(exit::BudgetExceeded { violations: report.violations.len() }.into());
+    }
+    Ok(())
+}
+
+/// Sum the symbols of FILE by the project component that claims them, counting aliases once.
+fn components_main(args: &ArgMatches) -> Result<(), Error> {
+    let rules = component_rules(args, "components")?;
+    let path = Path::new(args.value_of_os("FILE").unwrap());
+    let buf = map_file(path.as_os_str())?;
+    let grouper = group::Grouper::new(&buf, group::GroupBy::Component, &rules)?;
+    let config = rules.components.as_ref().unwrap();
+    let symbols = unaliased_symbols(path, &buf)?;
+
+    let mut sizes: BTreeMap<String, (u64, u64)> =
+        config.names().map(|name| (name.to_string(), (0, 0))).collect();
+    for sym in &symbols {
+        let entry = sizes.entry(grouper.group(sym)).or_default();
+        entry.0 += 1;
+        entry.1 += sym.size;
+    }
+    let mut components: Vec<ComponentSize> = sizes.into_iter()
+        .map(|(name, (symbols, size))| ComponentSize { name, symbols, size })
+        .collect();
+    components.sort_by(|a, b| (b.size, &a.name).cmp(&(a.size, &b.name)));
+    let report = ComponentsReport {
+        config: config.path.display().to_string(),
+        total: components.iter().map(|component| component.size).sum(),
+        components,
+    };
+    if structured(args) {
+        write_structured(args, &report)?;
+        return Ok(());
+    }
+    let format = size_format(args)?;
+    let share = |size: u64| 100.0 * size as f64 / cmp::max(report.total, 1) as f64;
+    println!("Components from {}", report.config);
+    println!("{:>10} {:>6} {:>10}  COMPONENT", "SIZE", "SYMS%", "SYMBOLS");
+    for component in &report.components {
+        println!("{:>10} {:>5.1}% {:>10}  {}", format.size(component.size), share(component.size),
+                 component.symbols, component.name);
+    }
+    println!("{:>10} {:>5.1}% {:>10}  (total)", format.size(report.total), 100.0,
+             report.components.iter().map(|component| component.symbols).sum::<u64>());
+    Ok(())
+}
+
+fn constructors_main(args: &ArgMatches) -> Result<(), Error> {
+    let buf = map_file(args.value_of_os("FILE").unwrap())?;
+    let mut reports = BTreeMap::new();
+    for (arch, slice) in arch::universal_slices(&buf)? {
+        reports.insert(arch, constructors::constructors(slice)?);
+    }
+    if structured(args) {
+        return write_by_arch(args, reports);
+    }
+    let format = size_format(args)?;
+    print_by_arch(reports, |report| print_constructors(report, &format))
+}
+
+/// Print the `constructors` report `report` of a binary, or of one slice of a universal binary.
+fn print_constructors(report: &constructors::ConstructorReport, format: &units::SizeFormat)
+                      -> Result<(), Error> {
+    println!("{:>10}  TABLE", "SIZE");
+    for (name, size) in &report.tables {
+        println!("{:>10}  {}", format.size(*size), name);
+    }
+    println!();
+    println!("{:>10} {:>18}  {:<16} FUNCTION", "SIZE", "ADDRESS", "TABLE");
+    for c in &report.constructors {
+        let name = c.name.as_ref().map_or_else(|| "?".to_string(), |name| {
+            format!("{:#}", rustc_demangle::demangle(name))
+        });
+        println!("{:>10} {:>#18x}  {:<16} {}", format.size(c.size), c.address, c.table, name);
+    }
+    println!();
+    println!("{} constructors with {} of code{}", report.constructors.len(),
+             format.size(report.code_size), if report.unresolved > 0 {
+                 format!(" ({} not in the symbol table)", report.unresolved)
+             } else {
+                 String::new()
+             });
+    Ok(())
+}
+
+fn objc_main(args: &ArgMatches) -> Result<(), Error> {
+    let buf = map_file(args.value_of_os("FILE").unwrap())?;
+    let long = args.value_of("long").unwrap().parse::<usize>()
+        .map_err(|_| exit::UsageError("Invalid --long".to_string()))?;
+    let top = args.value_of("top").unwrap().parse::<usize>()
+        .map_err(|_| exit::UsageError("Invalid --top".to_string()))?;
+    let mut reports = BTreeMap::new();
+    for (arch, slice) in arch::universal_slices(&buf)? {
+        reports.insert(arch, objc::objc(slice, long, top)?);
+    }
+    if structured(args) {
+        return write_by_arch(args, reports);
+    }
+    let format = size_format(args)?;
+    print_by_arch(reports, |report| print_objc(report, long, &format))
+}
+
+/// Print the `objc` report `report` of a binary, or of one slice of a universal binary.
+fn print_objc(report: &objc::ObjcReport, long: usize, format: &units::SizeFormat)
+              -> Result<(), Error> {
+    let selectors = &report.selectors;
+    println!("__objc_methname: {} in {} names, {} unique ({} duplicated)",
+             format.size(selectors.methname_size), selectors.selectors, selectors.unique,
+             format.size(selectors.duplicate_bytes));
+    println!("__objc_selrefs: {} references, {} duplicates ({}), {} unresolved",
+             selectors.selrefs, selectors.duplicate_selrefs,
+             format.size(selectors.duplicate_selref_bytes), selectors.unresolved_selrefs);
+    if !selectors.long.is_empty() {
+        println!();
+        println!("{} names are longer than {} bytes ({}):", selectors.long.len(), long,
+                 format.size(selectors.long_bytes));
+        println!("{:>6} {:>5}  NAME", "LENGTH", "REFS");
+        for selector in &selectors.long {
+            println!("{:>6} {:>5}  {}", selector.name.len(), selector.references, selector.name);
+        }
+    }
+    println!();
+    println!("{} could be saved by coalescing duplicates", savings(format, selectors.savings()));
+
+    let cfstrings = &report.cfstrings;
+    println!();
+    println!("__cfstring: {} literals, {} in objects and {} in characters, {} unresolved",
+             cfstrings.count, format.size(cfstrings.objects_size),
+             format.size(cfstrings.storage_size), cfstrings.unresolved);
+    if !cfstrings.largest.is_empty() {
+        println!("{:>10}  LITERAL", "SIZE");
+        for literal in &cfstrings.largest {
+            println!("{:>10}  {:?}{}", format.size(literal.size), literal.value,
+                     if literal.utf16 { " (UTF-16)" } else { "" });
+        }
+    }
+    Ok(())
+}
+
+fn page_size_main(args: &ArgMatches) -> Result<(), Error> {
+    let page_size = spill::parse_limit(args.value_of("page-size").unwrap())? as u64;
+    if !page_size.is_power_of_two() {
+        return Err(exit::UsageError("--page-size must be a power of two".to_string()).into());
+    }
+    let path = Path::new(args.value_of_os("PATH").unwrap());
+    let mut libraries = Vec::new();
+    if path.is_dir() || zip::is_zip(&map_file(path.as_os_str())?) {
+        android::for_each_library(path, |library| {
+            libraries.push(android::PageAlignment::new(library.path, library.buf,
+                                                       library.stored_offset, page_size)?);
+            Ok(())
+        })?;
+    } else {
+        libraries.push(android::PageAlignment::new(&path.to_string_lossy(),
+                                                   &map_file(path.as_os_str())?, None,
+                                                   page_size)?);
+    }
+    if structured(args) {
+        write_structured(args, &libraries)?;
+        return Ok(());
+    }
+    let format = size_format(args)?;
+    println!("{:>8} {:>7} {:>11} {:>12} {:>14}  LIBRARY",
+             "ALIGN", "ALIGNED", "ZIP-ALIGNED", "FILE PADDING", "MEMORY PADDING");
+    for library in &libraries {
+        let yes_no = |b| if b { "yes" } else { "no" };
+        println!("{:>8} {:>7} {:>11} {:>12} {:>14}  {}", library.alignment,
+                 yes_no(library.aligned), library.zip_aligned.map_or("-", yes_no),
+                 format.size(library.file_padding), format.size(library.memory_padding),
+                 library.path);
+    }
+    let unaligned: Vec<_> = libraries.iter().filter(|l| !l.aligned).collect();
+    println!();
+    println!("{} of {} libraries are not aligned to {}-byte pages; re-aligning adds about {} to \
+              files and {} in memory", unaligned.len(), libraries.len(), page_size,
+             format.size(unaligned.iter().map(|l| l.file_padding).sum()),
+             format.size(unaligned.iter().map(|l| l.memory_padding).sum()));
+    Ok(())
+}
+
+fn pgo_main(args: &ArgMatches) -> Result<(), Error> {
+    let buf = map_file(args.value_of_os("FILE").unwrap())?;
+    let report = pgo::pgo(&buf, args.is_present("include-non-alloc"))?;
+    if structured(args) {
+        write_structured(args, &report)?;
+        return Ok(());
+    }
+    let format = size_format(args)?;
+    if !report.instrumented {
+        println!("No profile instrumentation found");
+        return Ok(());
+    }
+    println!("{:>10}  SECTION", "SIZE");
+    for (name, size) in &report.sections {
+        println!("{:>10}  {}", format.size(*size), name);
+    }
+    println!("{:>10}  profiling runtime", format.size(report.runtime));
+    let counter_code = report.counter_code
+        .map_or_else(|| "unknown".to_string(), |size| format.size(size));
+    println!("{:>10}  counter increments ({} counters, estimated)", counter_code,
+             report.counters);
+    println!();
+    println!("Instrumented total: {}; estimated final size: {} ({} of instrumentation)",
+             format.size(report.total), format.size(report.estimated_final),
+             format.size(report.total - report.estimated_final));
+    Ok(())
+}
+
+fn post_link_main(args: &ArgMatches) -> Result<(), Error> {
+    let buf = map_file(args.value_of_os("FILE").unwrap())?;
+    let report = postlink::post_link(&buf)?;
+    if structured(args) {
+        write_structured(args, &report)?;
+        return Ok(());
+    }
+    let format = size_format(args)?;
+    if report.detected.is_empty() {
+        println!("No post-link or profile-guided layout optimizations detected");
+    } else {
+        println!("Detected: {}", report.detected.join(", "));
+    }
+    let percent = |n: u64| n as f64 * 100.0 / report.text_size.max(1) as f64;
+    println!("{} split functions: {} hot, {} cold ({:.1}% of {} of code), {} in other \
+              fragments", report.functions.len(), format.size(report.hot_bytes),
+             format.size(report.cold_bytes), percent(report.cold_bytes),
+             format.size(report.text_size), format.size(report.other_fragment_bytes));
+    if report.original_text > 0 {
+        println!("{} of original code kept by BOLT (.bolt.org.text)",
+                 savings(&format, report.original_text));
+    }
+    if report.bb_address_map > 0 {
+        println!("{} of basic block address maps (.llvm_bb_addr_map)",
+                 savings(&format, report.bb_address_map));
+    }
+    if !report.functions.is_empty() {
+        println!();
+        println!("{:>10} {:>10} {:>10}  FUNCTION", "HOT", "COLD", "OTHER");
+        for f in &report.functions {
+            println!("{:>10} {:>10} {:>10}  {:#}", format.size(f.hot), format.size(f.cold),
+                     format.size(f.other), rustc_demangle::demangle(&f.name));
+        }
+    }
+    Ok(())
+}
+
+fn predict_main(args: &ArgMatches) -> Result<(), Error> {
+    let list = args.value_of("INPUTS").unwrap();
+    let paths = inputs::read_input_list(Path::new(list.strip_prefix('@').unwrap_or(list)))?;
+    let entry = if args.is_present("gc-sections") { args.value_of("entry") } else { None };
+    let prediction = predict::predict(&paths, entry)?;
+    if structured(args) {
+        write_structured(args, &prediction)?;
+    } else {
+        let format = size_format(args)?;
+        println!("{:>10} {:>8} {:>8}  {:<6} SECTION", "SIZE", "INPUTS", "PADDING", "KIND");
+        for s in &prediction.sections {
+            println!("{:>10} {:>8} {:>8}  {:<6} {}", format.size(s.size), s.inputs,
+                     format.size(s.padding), format!("{:?}", s.category), s.name);
+        }
+        println!();
+        let line = |size, what| println!("{:>16} {}", savings(&format, size), what);
+        line(prediction.input_size, "of allocated input sections");
+        line(prediction.comdat_folded, "of duplicate COMDAT groups discarded");
+        line(prediction.merged, "of duplicate strings and constants merged");
+        if let Some(removed) = prediction.gc_removed {
+            line(removed, "of unreferenced sections collected");
+        }
+        line(prediction.padding, "of alignment padding");
+        line(prediction.predicted_size, "predicted");
+    }
+    Ok(())
+}
+
+fn rlib_main(args: &ArgMatches) -> Result<(), Error> {
+    let path = Path::new(args.value_of_os("FILE").unwrap());
+    let buf = map_file(path.as_os_str())?;
+    let report = rlib::rlib(&buf, path)?;
+    if structured(args) {
+        write_structured(args, &report)?;
+        return Ok(());
+    }
+    let format = size_format(args)?;
+    let line = |size: u64, what: &str| {
+        println!("{:>10} {:>5.1}%  {}", format.size(size),
+                 100.0 * size as f64 / cmp::max(report.file_size, 1) as f64, what);
+    };
+    line(report.metadata, "crate metadata (lib.rmeta)");
+    line(report.sections[&Section::Text], "code and read-only data");
+    line(report.sections[&Section::Data], "data");
+    line(report.sections[&Section::Bss], "bss (no space in the file)");
+    line(report.sections[&Section::Other], "debug info, symbol tables and relocations");
+    if report.other_members > 0 {
+        line(report.other_members, "other members");
+    }
+    line(report.overhead, "archive and object file headers");
+    println!("{:>10}         file size, with {} object files{}", format.size(report.file_size),
+             report.objects, match report.crate_name {
+                 Some(ref name) => format!(" for crate {}", name),
+                 None => String::new(),
+             });
+    Ok(())
+}
+
+fn runtime_main(args: &ArgMatches) -> Result<(), Error> {
+    let buf = map_file(args.value_of_os("FILE").unwrap())?;
+    let top = args.value_of("top").unwrap().parse::<usize>()
+        .map_err(|_| exit::UsageError("Invalid --top".to_string()))?;
+    let report = runtime::runtime(&buf)?;
+    if structured(args) {
+        write_structured(args, &report)?;
+        return Ok(());
+    }
+    let format = size_format(args)?;
+    let share = |size: u64| 100.0 * size as f64 / cmp::max(report.symbol_size, 1) as f64;
+    println!("{:>10} {:>10} {:>6}  KIND", "COUNT", "SIZE", "SYMS%");
+    for (kind, totals) in &report.kinds {
+        println!("{:>10} {:>10} {:>5.1}%  {}", totals.count, format.size(totals.size),
+                 share(totals.size), kind);
+    }
+    println!("Runtime: {} ({:.1}%), application: {} ({:.1}%)", format.size(report.size),
+             share(report.size), format.size(report.application_size),
+             share(report.application_size));
+    if !report.symbols.is_empty() && top > 0 {
+        println!();
+        println!("{:>10}  {:<9}  SYMBOL", "SIZE", "KIND");
+        for sym in report.symbols.iter().take(top) {
+            println!("{:>10}  {:<9}  {}", format.size(sym.size), sym.kind, sym.name);
+        }
+    }
+    Ok(())
+}
+
+fn std_main(args: &ArgMatches) -> Result<(), Error> {
+    let buf = map_file(args.value_of_os("FILE").unwrap())?;
+    let report = stdlib::std_split(&buf)?;
+    if structured(args) {
+        write_structured(args, &report)?;
+        return Ok(());
+    }
+    let format = size_format(args)?;
+    let share = |size: u64| 100.0 * size as f64 / cmp::max(report.symbol_size, 1) as f64;
+    println!("{:>10} {:>6}  PART", "SIZE", "SYMS%");
+    for (size, part) in &[(report.std_size, "standard library"),
+                          (report.generic_size, "standard library generics for your types"),
+                          (report.runtime_size, "runtime (allocator, unwinder, builtins)"),
+                          (report.user_size, "your code"),
+                          (report.other_size, "other (C, C++ and unattributed symbols)")] {
+        println!("{:>10} {:>5.1}%  {}", format.size(*size), share(*size), part);
+    }
+    if !report.crates.is_empty() {
+        println!();
+        println!("{:>10} {:>10}  CRATE", "COUNT", "SIZE");
+        let mut crates: Vec<_> = report.crates.iter().collect();
+        crates.sort_by(|a, b| (b.1.size, a.0).cmp(&(a.1.size, b.0)));
+        for (name, totals) in crates {
+            println!("{:>10} {:>10}  {}", totals.count, format.size(totals.size), name);
+        }
+    }
+    println!();
+    println!("Built no_std, about {} of {} ({:.1}% less)", format.size(report.no_std_estimate),
+             format.size(report.symbol_size),
+             100.0 - share(report.no_std_estimate));
+    Ok(())
+}
+
+/// Write the `.size` archive of FILE for SuperSize's viewer, with the groups of `--group-by` as
+/// the components of the symbols.
+fn supersize_main(args: &ArgMatches) -> Result<(), Error> {
+    let path = Path::new(args.value_of_os("FILE").unwrap());
+    let buf = map_file(path.as_os_str())?;
+    let group_by = args.value_of("group-by").unwrap().parse::<group::GroupBy>()?;
+    let rules = group_rules(args, group_by)?;
+    let grouper = group::Grouper::new(&buf, group_by, &rules)?;
+    let (symbols, _) = file_symbols(path, &buf, None, None)?;
+    let mut metadata = BTreeMap::new();
+    metadata.insert("arch", arch::arch(&buf)?);
+    if let Some(name) = path.file_name() {
+        metadata.insert("file_name", name.to_string_lossy().into_owned());
+    }
+    let archive = supersize::archive(&buf, symbols, &grouper, metadata)?;
+    let output = args.value_of_os("output").unwrap();
+    fs::write(output, archive).map_err(|e| format_err!("{}: {}", Path::new(output).display(), e))?;
+    Ok(())
+}
+
+fn thinning_main(args: &ArgMatches) -> Result<(), Error> {
+    let arch = match args.value_of("arch").unwrap() {
+        "arm64" => "aarch64",
+        arch => arch,
+    };
+    let report = thinning::ThinningReport::new(Path::new(args.value_of_os("PATH").unwrap()),
+                                               arch)?;
+    if structured(args) {
+        write_structured(args, &report)?;
+        return Ok(());
+    }
+    let format = size_format(args)?;
+    println!("{:>10} {:>12} {:>10} {:>10} {:>10} {:>10}  FILE", "SIZE", "OTHER SLICES",
+             "BITCODE", "SHARED", "PRIVATE", "DOWNLOAD");
+    for binary in &report.binaries {
+        println!("{:>10} {:>12} {:>10} {:>10} {:>10} {:>10}  {} ({})", format.size(binary.size),
+                 format.size(binary.other_slices), format.size(binary.bitcode),
+                 format.size(binary.shared), format.size(binary.private),
+                 format.size(binary.download), binary.path, binary.arches.join(", "));
+    }
+    println!("{:>10} {:>12} {:>10} {:>10} {:>10} {:>10}  (resources)",
+             format.size(report.resources), "", "", "", format.size(report.resources),
+             format.size(report.resources_download));
+    println!("Thinned for {}, {} of {} is eliminated ({} of other slices, {} of bitcode, {} in \
+              the shared region); about {} to download",
+             report.arch, format.size(report.other_slices + report.bitcode + report.shared),
+             format.size(report.size), format.size(report.other_slices),
+             format.size(report.bitcode), format.size(report.shared),
+             format.size(report.download));
+    Ok(())
+}
+
+fn thunks_main(args: &ArgMatches) -> Result<(), Error> {
+    let buf = map_file(args.value_of_os("FILE").unwrap())?;
+    let top = args.value_of("top").unwrap().parse::<usize>()
+        .map_err(|_| exit::UsageError("Invalid --top".to_string()))?;
+    let report = thunks::thunks(&buf)?;
+    if structured(args) {
+        write_structured(args, &report)?;
+        return Ok(());
+    }
+    let format = size_format(args)?;
+    println!("{:>10} {:>10}  KIND", "COUNT", "SIZE");
+    for (kind, totals) in &report.kinds {
+        println!("{:>10} {:>10}  {}", totals.count, format.size(totals.size), kind);
+    }
+    println!("{} thunks, trampolines and outlined functions: {} of {} of functions ({:.1}%)",
+             report.count, format.size(report.size), format.size(report.code_size),
+             100.0 * report.size as f64 / cmp::max(report.code_size, 1) as f64);
+    if let Some(ref outlining) = report.outlining {
+        println!("Outlining: {} outlined functions, branched to {} times, {} about {}",
+                 outlining.functions, outlining.calls,
+                 if outlining.saved < 0 { "cost" } else { "save" },
+                 format.size(outlining.saved.unsigned_abs()));
+    }
+    if !report.thunks.is_empty() && top > 0 {
+        println!();
+        println!("{:>10} {:>6}  {:<10}  FUNCTION", "SIZE", "CALLS", "KIND");
+        for thunk in report.thunks.iter().take(top) {
+            let calls = thunk.calls.map_or_else(|| "-".to_string(), |calls| calls.to_string());
+            println!("{:>10} {:>6}  {:<10}  {}", format.size(thunk.size), calls, thunk.kind,
+                     thunk.name);
+        }
+    }
+    Ok(())
+}
+
+fn real_main() -> Result<(), Error> {
+    let exit_codes = exit::exit_code_table();
+    let matches = App::new("rust-size")
+        .about("Report the sizes of the sections in an object file")
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .after_help(exit_codes.as_str())
+        .arg(Arg::with_name("FILE")
+             .help("The object file to examine, the map file that the linker wrote for it, or \
+                    a report that an earlier run saved, or - to read it from standard input")
+             .multiple(true)
+             .required_unless("exit-codes"))
+        .arg(Arg::with_name("summary")
+             .long("summary")
+             .help("Print a one-line overview of each FILE: its format, architecture, text, \
+                    data and bss sizes, and file size"))
+        .arg(Arg::with_name("exit-codes")
+             .long("exit-codes")
+             .help("List the exit codes and what they mean, then exit"))
+        .arg(Arg::with_name("details")
+             .long("details")
+             .help("List every section with its address, file offset, alignment and flags, \
+                    along with the file's entry point, target OS and linker"))
+        .arg(Arg::with_name("signature")
+             .long("signature")
+             .requires("details")
+             .help("With --details, check the file's Authenticode or Mach-O code signature, \
+                    and record whether it is valid, who signed it and the sizes of its parts"))
+        .arg(Arg::with_name("explain")
+             .long("explain")
+             .help("Include a short description of each well-known section"))
+        .arg(Arg::with_name("members")
+             .long("members")
+             .help("For a static archive, report the sections of each object file in it, \
+                    along with their sum"))
+        .arg(Arg::with_name("member")
+             .long("member")
+             .value_name("GLOB")
+             .multiple(true)
+             .number_of_values(1)
+             .conflicts_with_all(&["summary", "members", "symbols", "dsym", "details", "format"])
+             .help("For a ZIP archive, like an APK, report the sections of each file in it whose \
+                    path matches GLOB (e.g. 'lib/arm64-v8a/*.so'), with whether it is stored \
+                    uncompressed; may be given more than once"))
+        .arg(Arg::with_name("symbols")
+             .long("symbols")
+             .help("Attribute the bytes of each section to the functions and globals in it, \
+                    using the symbol table, or for PE images the PDB, or with --dsym the \
+                    symbol table of the dSYM"))
+        .arg(Arg::with_name("pdb")
+             .long("pdb")
+             .value_name("PATH")
+             .requires("symbols")
+             .help("The PDB to read the symbols of a PE image from, instead of the one that the \
+                    image names or that is next to it"))
+        .arg(Arg::with_name("dsym")
+             .long("dsym")
+             .value_name("PATH")
+             .conflicts_with_all(&["summary", "members", "pdb"])
+             .help("The .dSYM bundle of a Mach-O binary, or the DWARF file in it: adds its \
+                    debug info sections to the report, and --symbols reads its symbols"))
+        .arg(Arg::with_name("format")
+             .long("format")
+             .takes_value(true)
+             .possible_values(&["table", "json", "yaml", "raw", "ihex", "berkeley", "sysv",
+                                "csv", "tsv", "bloaty-csv", "ndjson", "symbol-map", "html"])
+             .conflicts_with_all(&["summary", "members", "symbols", "dsym", "details", "preview"])
+             .help("Print the sections as an aligned table, largest first with their share of \
+                    the total and a subtotal per category (the default on a terminal), as JSON \
+                    (the default otherwise), or as YAML of the same structure; read FILE as a \
+                    firmware image, raw bytes (as `objcopy -O binary` writes) or Intel HEX, and \
+                    report the flash that it takes up; or with berkeley, print the text, data \
+                    and bss sizes of each FILE as GNU `size` does, with sysv, its sections with \
+                    their addresses as `size -A` does, with csv or tsv, a \
+                    file,category,section,size row for each section, with bloaty-csv, the \
+                    sections,vmsize,filesize rows of `bloaty --csv`, with ndjson, a line of JSON \
+                    with the sections of each FILE as soon as it is read, and with symbol-map, \
+                    an `address size name` line for each symbol, in hex as `nm --print-size` \
+                    prints them, for other tools to read; or with html, write a web page with a \
+                    treemap of the loaded sections by category and of their symbols, to zoom \
+                    into by clicking"))
+        .arg(Arg::with_name("output")
+             .short("o")
+             .long("output")
+             .value_name("PATH")
+             .requires("format")
+             .help("The file to write the --format html page to [default: standard output]"))
+        .arg(Arg::with_name("base")
+             .long("base")
+             .value_name("ADDR")
+             .requires("format")
+             .help("The address that a raw image is programmed at [default: 0]"))
+        .arg(Arg::with_name("elf")
+             .long("elf")
+             .value_name("PATH")
+             .requires("format")
+             .help("The ELF file that the firmware image was made from, to find its sections \
+                    in the image"))
+        .arg(Arg::with_name("preview")
+             .long("preview")
+             .value_name("N")
+             .help("Include the first N bytes of each section, as an escaped string or hex \
+                    bytes, in the --details report (implies --details)"))
+        .arg(Arg::with_name("normalize-names")
+             .long("normalize-names")
+             .help("Use the same section names (text, rodata, unwind, ...) for every file \
+                    format"))
+        .arg(Arg::with_name("units")
+             .long("units")
+             .global(true)
+             .takes_value(true)
+             .possible_values(&["bytes", "si", "iec"])
+             .help("Write sizes in human-readable output as byte counts, or in kB/MB (si) or \
+                    KiB/MiB (iec) [default: bytes, or iec with --human]"))
+        .arg(Arg::with_name("human")
+             .long("human")
+             .global(true)
+             .help("Write sizes in human-readable output like 12.3 KiB or 4.7 MiB, or with \
+                    --units si, like 12.6 kB; JSON output keeps byte counts"))
+        .arg(Arg::with_name("group-digits")
+             .long("group-digits")
+             .global(true)
+             .help("Group the digits of sizes in human-readable output, with the separators \
+                    of the locale in LC_ALL, LC_NUMERIC or LANG"))
+        .arg(Arg::with_name("components")
+             .long("components")
+             .global(true)
+             .value_name("FILE")
+             .help("The TOML file of the project's components and size budgets, for \
+                    --group-by component, `components` and `budget` [default: the \
+                    components.toml in the current directory or above it]"))
+        .subcommand(SubCommand::with_name("analyze")
+                    .about("Report the sizes of every object file and archive in a directory")
+                    .arg(Arg::with_name("cache")
+                         .long("cache")
+                         .value_name("FILE")
+                         .help("Remember the sizes in FILE, and only analyze files again if \
+                                they changed since the last run"))
+                    .arg(Arg::with_name("include-non-alloc")
+                         .long("include-non-alloc")
+                         .help("Count sections that aren't loaded into memory (debug info, \
+                                symbol tables) toward the total"))
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(&["text", "json", "yaml"])
+                         .default_value("text")
+                         .help("The output format"))
+                    .arg(Arg::with_name("DIR")
+                         .help("The directory to search, recursively")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("android-abis")
+                    .about("Compare the sizes of an Android app's native libraries across \
+                            ABIs")
+                    .arg(Arg::with_name("threshold")
+                         .long("threshold")
+                         .value_name("PERCENT")
+                         .default_value("25")
+                         .help("Flag builds of a library that are more than this much larger \
+                                than the median of its builds for the other ABIs"))
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(&["text", "json", "yaml"])
+                         .default_value("text")
+                         .help("The output format"))
+                    .arg(Arg::with_name("PATH")
+                         .help("An APK or AAB, or a directory with a subdirectory of libraries \
+                                per ABI (like `lib/` or `jniLibs/`)")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("assets")
+                    .about("List the static byte arrays and strings, like those include_bytes! \
+                            and include_str! embed, with where they are declared")
+                    .arg(Arg::with_name("min-size")
+                         .long("min-size")
+                         .value_name("BYTES")
+                         .default_value("256")
+                         .help("Ignore arrays and strings smaller than this"))
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(&["text", "json", "yaml"])
+                         .default_value("text")
+                         .help("The output format"))
+                    .arg(Arg::with_name("FILE")
+                         .help("The object file to examine, with debug info")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("budget")
+                    .about("Check the size of each crate and project component of a binary \
+                            against the budgets of --components or components.toml, a default \
+                            for every crate and component and overrides for some, and list \
+                            those over budget")
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(&["text", "json", "yaml"])
+                         .default_value("text")
+                         .help("The output format"))
+                    .arg(Arg::with_name("notify-webhook")
+                         .long("notify-webhook")
+                         .value_name("URL")
+                         .help("When budgets are exceeded, POST the violations as JSON to URL, \
+                                with a summary in a `text` field for Slack and other chat \
+                                webhooks (uses curl)"))
+                    .arg(Arg::with_name("FILE")
+                         .help("The object file to check")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("cold")
+                    .about("List the large functions that a profile shows are never or rarely \
+                            executed, as candidates for #[cold] or splitting out of the hot \
+                            code; without a profile, list the functions that only run when \
+                            something fails")
+                    .arg(Arg::with_name("profdata")
+                         .long("profdata")
+                         .value_name("FILE")
+                         .help("An LLVM profile of the binary, in the text format that \
+                                `llvm-profdata merge --text` writes"))
+                    .arg(Arg::with_name("perf")
+                         .long("perf")
+                         .value_name("FILE")
+                         .conflicts_with("profdata")
+                         .help("The output of `perf script` for a recording of the binary, \
+                                preferably with --no-demangle"))
+                    .arg(Arg::with_name("max-count")
+                         .long("max-count")
+                         .value_name("N")
+                         .default_value("0")
+                         .help("Count functions executed or sampled at most this many times \
+                                as cold"))
+                    .arg(Arg::with_name("min-size")
+                         .long("min-size")
+                         .value_name("BYTES")
+                         .default_value("256")
+                         .help("Ignore functions smaller than this"))
+                    .arg(Arg::with_name("top")
+                         .long("top")
+                         .value_name("N")
+                         .default_value("20")
+                         .help("List this many of the largest of the functions"))
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(&["text", "json", "yaml"])
+                         .default_value("text")
+                         .help("The output format"))
+                    .arg(Arg::with_name("FILE")
+                         .help("The object file to examine")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("compare")
+                    .about("Compare builds of the same program for different targets")
+                    .arg(Arg::with_name("include-non-alloc")
+                         .long("include-non-alloc")
+                         .help("Count sections that aren't loaded into memory (debug info, \
+                                symbol tables) toward the total"))
+                    .arg(Arg::with_name("normalize-names")
+                         .long("normalize-names")
+                         .help("List sizes by normalized section name rather than by \
+                                category"))
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(&["text", "json", "yaml"])
+                         .default_value("text")
+                         .help("The output format"))
+                    .arg(Arg::with_name("FILES")
+                         .help("The object files to compare; the first is the baseline")
+                         .multiple(true)
+                         .required(true)))
+        .subcommand(SubCommand::with_name("components")
+                    .about("Sum the symbols of a binary by the project component that claims \
+                            them, by crate, source path or symbol name, as given by \
+                            --components or components.toml")
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(&["text", "json", "yaml"])
+                         .default_value("text")
+                         .help("The output format"))
+                    .arg(Arg::with_name("FILE")
+                         .help("The object file to examine")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("constructors")
+                    .about("List the functions that static initialization runs, with their code \
+                            sizes")
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(&["text", "json", "yaml"])
+                         .default_value("text")
+                         .help("The output format"))
+                    .arg(Arg::with_name("FILE")
+                         .help("The object file to examine")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("core")
+                    .about("Report the memory of the process that dumped an ELF core file: its \
+                            mappings with their sizes, permissions and files, and how much of \
+                            them was resident")
+                    .arg(Arg::with_name("segments")
+                         .long("segments")
+                         .help("List every LOAD segment instead of summing them by file"))
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(&["text", "json", "yaml"])
+                         .default_value("text")
+                         .help("The output format"))
+                    .arg(Arg::with_name("FILE")
+                         .help("The core file to examine")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("data-in-code")
+                    .about("Split the code sections into instructions and the jump tables and \
+                            literals embedded in them, using ARM and RISC-V mapping symbols, \
+                            relocations, and Mach-O LC_DATA_IN_CODE")
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(&["text", "json", "yaml"])
+                         .default_value("text")
+                         .help("The output format"))
+                    .arg(Arg::with_name("FILE")
+                         .help("The object file to examine")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("dead-exports")
+                    .about("Find the exports of a shared library that none of its consumers \
+                            import")
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(&["text", "json", "yaml", "version-script"])
+                         .default_value("text")
+                         .help("The output format; `version-script` writes a linker version \
+                                script that keeps only the imported exports"))
+                    .arg(Arg::with_name("LIBRARY")
+                         .help("The shared library to examine")
+                         .required(true))
+                    .arg(Arg::with_name("CONSUMERS")
+                         .help("The binaries and libraries that link against LIBRARY")
+                         .multiple(true)
+                         .required(true)))
+        .subcommand(SubCommand::with_name("debug-sections")
+                    .about("List the debug sections with their sizes in the file and, for \
+                            compressed ones (-gz), uncompressed, to show what stripping and \
+                            compressing them save")
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(&["text", "json", "yaml"])
+                         .default_value("text")
+                         .help("The output format"))
+                    .arg(Arg::with_name("FILE")
+                         .help("The object file to examine")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("delta")
+                    .about("Estimate the size of a differential update from one version of a \
+                            file to the next, as a bsdiff patch, next to downloading the new \
+                            version whole")
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(&["text", "json", "yaml"])
+                         .default_value("text")
+                         .help("The output format"))
+                    .arg(Arg::with_name("OLD")
+                         .help("The version that is installed")
+                         .required(true))
+                    .arg(Arg::with_name("NEW")
+                         .help("The version to update to")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("dynamic")
+                    .about("Report on the dynamic linking structures of an ELF binary or shared \
+                            library")
+                    .arg(Arg::with_name("version-script")
+                         .long("version-script")
+                         .value_name("FILE")
+                         .help("Estimate the savings from linking with this version script, or \
+                                list of symbols to export, one per line"))
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(&["text", "json", "yaml"])
+                         .default_value("text")
+                         .help("The output format"))
+                    .arg(Arg::with_name("FILE")
+                         .help("The ELF file to examine")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("diff")
+                    .about("Compare the sizes of two object files")
+                    .arg(Arg::with_name("include-non-alloc")
+                         .long("include-non-alloc")
+                         .help("Count sections that aren't loaded into memory (debug info, \
+                                symbol tables) toward the total"))
+                    .arg(Arg::with_name("symbols")
+                         .long("symbols")
+                         .help("Also compare the sizes of individual symbols"))
+                    .arg(Arg::with_name("infer-sizes")
+                         .long("infer-sizes")
+                         .help("Size ELF symbols that have no size by the gap to the next \
+                                symbol in their section"))
+                    .arg(Arg::with_name("group-by")
+                         .long("group-by")
+                         .takes_value(true)
+                         .possible_values(&["crate", "section", "source-file", "language",
+                                            "label", "owner", "component", "origin", "none"])
+                         .default_value("none")
+                         .help("Roll the symbol comparison up by crate (or C++ namespace), \
+                                section, source file (from DWARF debug info), language, the \
+                                labels given by --labels, the source file owners given by \
+                                --owners, the project components given by --components, or \
+                                whether the compiler generated the symbols"))
+                    .arg(Arg::with_name("labels")
+                         .long("labels")
+                         .value_name("FILE")
+                         .help("A TOML file of `[[rule]]`s, each giving a `label` to the \
+                                symbols matching its `symbol` and `section` regexes"))
+                    .arg(Arg::with_name("owners")
+                         .long("owners")
+                         .value_name("FILE")
+                         .help("A CODEOWNERS file, mapping source file patterns to owners"))
+                    .arg(Arg::with_name("source-root")
+                         .long("source-root")
+                         .value_name("DIR")
+                         .requires("owners")
+                         .help("The directory the --owners patterns are relative to, if the \
+                                debug info has absolute source paths"))
+                    .arg(Arg::with_name("compile-units")
+                         .long("compile-units")
+                         .help("Also compare the code size of each compile unit, using the DWARF \
+                                debug info of both files"))
+                    .arg(Arg::with_name("max-memory")
+                         .long("max-memory")
+                         .value_name("BYTES")
+                         .help("Keep the symbol comparison within about this much memory (e.g. \
+                                `512M`), spilling to temporary files as needed. Compile units \
+                                are always processed one at a time"))
+                    .arg(Arg::with_name("normalize-names")
+                         .long("normalize-names")
+                         .help("Use the same section names (text, rodata, unwind, ...) for every \
+                                file format"))
+                    .arg(Arg::with_name("ignore-delta-below")
+                         .long("ignore-delta-below")
+                         .value_name("BYTES|PERCENT")
+                         .help("Group changes smaller than this many bytes (or percent of the \
+                                old size, e.g. `1%`) into a single row"))
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(&["text", "json", "yaml"])
+                         .default_value("text")
+                         .help("The output format"))
+                    .arg(Arg::with_name("OLD")
+                         .help("The baseline object file")
+                         .required(true))
+                    .arg(Arg::with_name("NEW")
+                         .help("The object file to compare against the baseline")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("disasm")
+                    .about("Disassemble x86 and x86-64 code to verify the bounds of function \
+                            symbols and measure the filler between functions (needs the \
+                            `disasm` feature)")
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(&["text", "json", "yaml"])
+                         .default_value("text")
+                         .help("The output format"))
+                    .arg(Arg::with_name("FILE")
+                         .help("The object file to examine")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("download")
+                    .about("Estimate the download and install size of an app for each kind of \
+                            device, modelling App Store thinning for iOS and macOS apps and \
+                            Play's split by ABI and compression for Android apps")
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(&["text", "json", "yaml"])
+                         .default_value("text")
+                         .help("The output format"))
+                    .arg(Arg::with_name("PATH")
+                         .help("An .ipa, an .app directory or a Mach-O file, or an APK, an AAB \
+                                or a directory with a subdirectory of libraries per ABI")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("drop-glue")
+                    .about("Sum the drop glue (core::ptr::drop_in_place instantiations) of a \
+                            Rust binary by the type that it drops")
+                    .arg(Arg::with_name("group-by")
+                         .long("group-by")
+                         .takes_value(true)
+                         .possible_values(&["type", "base"])
+                         .default_value("type")
+                         .help("Sum by the dropped type, or by the type without its generic \
+                                arguments, so that all the Vec<T>s add up"))
+                    .arg(Arg::with_name("top")
+                         .long("top")
+                         .value_name("N")
+                         .default_value("20")
+                         .help("List this many of the types with the most drop glue"))
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(&["text", "json", "yaml"])
+                         .default_value("text")
+                         .help("The output format"))
+                    .arg(Arg::with_name("FILE")
+                         .help("The object file to examine")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("duplicates")
+                    .about("Find symbols with byte-identical contents")
+                    .arg(Arg::with_name("code")
+                         .long("code")
+                         .help("Look for identical functions instead of data objects"))
+                    .arg(Arg::with_name("min-size")
+                         .long("min-size")
+                         .value_name("BYTES")
+                         .default_value("16")
+                         .help("Ignore symbols smaller than this"))
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(&["text", "json", "yaml"])
+                         .default_value("text")
+                         .help("The output format"))
+                    .arg(Arg::with_name("FILE")
+                         .help("The object file to examine; with several files, look for \
+                                symbols duplicated across them instead")
+                         .multiple(true)
+                         .required(true)))
+        .subcommand(SubCommand::with_name("find")
+                    .about("Find the symbols whose names match a pattern, with their sizes and \
+                            sections")
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(&["text", "json", "yaml"])
+                         .default_value("text")
+                         .help("The output format"))
+                    .arg(Arg::with_name("FILE")
+                         .help("The object file to examine")
+                         .required(true))
+                    .arg(Arg::with_name("PATTERN")
+                         .help("A regular expression matching demangled or mangled names")
+                         .required(true))
+                    .arg(Arg::with_name("infer-sizes")
+                         .long("infer-sizes")
+                         .help("Size ELF symbols that have no size by the gap to the next \
+                                symbol in their section")))
+        .subcommand(SubCommand::with_name("firmware")
+                    .about("List the kernels, device trees and other images in a U-Boot FIT image \
+                            or uImage")
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(&["text", "json", "yaml"])
+                         .default_value("text")
+                         .help("The output format"))
+                    .arg(Arg::with_name("FILE")
+                         .help("The FIT image or uImage to examine")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("fmt")
+                    .about("Sum the size of the core::fmt machinery of a Rust binary, with the \
+                            Debug and Display impls and the tables they use, and estimate \
+                            what -Zfmt-debug=none and ufmt would save")
+                    .arg(Arg::with_name("top")
+                         .long("top")
+                         .value_name("N")
+                         .default_value("20")
+                         .help("List this many of the largest of the symbols"))
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(&["text", "json", "yaml"])
+                         .default_value("text")
+                         .help("The output format"))
+                    .arg(Arg::with_name("FILE")
+                         .help("The object file to examine")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("generated")
+                    .about("Sum the functions that the compiler generated rather than the \
+                            source defines: drop glue, shims, thunks, outlined functions and \
+                            memory and arithmetic intrinsics")
+                    .arg(Arg::with_name("top")
+                         .long("top")
+                         .value_name("N")
+                         .default_value("20")
+                         .help("List this many of the largest of the functions"))
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(&["text", "json", "yaml"])
+                         .default_value("text")
+                         .help("The output format"))
+                    .arg(Arg::with_name("FILE")
+                         .help("The object file to examine")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("hints")
+                    .about("Suggest ways to make an object file smaller")
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(&["text", "json", "yaml"])
+                         .default_value("text")
+                         .help("The output format"))
+                    .arg(Arg::with_name("FILE")
+                         .help("The object file to examine")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("hotness")
+                    .about("Put the size of each function next to its share of profile \
+                            samples, flagging the big functions that are cold and the small \
+                            ones that are hot")
+                    .arg(Arg::with_name("samples")
+                         .long("samples")
+                         .value_name("FILE")
+                         .required(true)
+                         .help("The output of `perf script` or `perf report --stdio` for a \
+                                recording of the binary, or a file with a symbol and a sample \
+                                count on each line, like a histogram exported from ETW"))
+                    .arg(Arg::with_name("big")
+                         .long("big")
+                         .value_name("BYTES")
+                         .default_value("1024")
+                         .help("The size from which functions count as big"))
+                    .arg(Arg::with_name("top")
+                         .long("top")
+                         .value_name("N")
+                         .default_value("20")
+                         .help("List this many of the largest and of the hottest functions"))
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(&["text", "json", "yaml"])
+                         .default_value("text")
+                         .help("The output format"))
+                    .arg(Arg::with_name("FILE")
+                         .help("The object file to examine")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("hugepages")
+                    .about("Check whether the code of an ELF file is laid out for transparent \
+                            huge pages")
+                    .arg(Arg::with_name("page-size")
+                         .long("page-size")
+                         .value_name("BYTES")
+                         .default_value("2M")
+                         .help("The huge page size"))
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(&["text", "json", "yaml"])
+                         .default_value("text")
+                         .help("The output format"))
+                    .arg(Arg::with_name("FILE")
+                         .help("The ELF file to examine")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("isa")
+                    .about("Compare the function sizes of two builds of a program for different \
+                            architectures, giving the expansion factor of each crate and the \
+                            functions that grow more or less than the rest")
+                    .arg(Arg::with_name("top")
+                         .long("top")
+                         .value_name("N")
+                         .default_value("20")
+                         .help("List this many of the functions furthest from the overall \
+                                factor"))
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(&["text", "json", "yaml"])
+                         .default_value("text")
+                         .help("The output format"))
+                    .arg(Arg::with_name("FILES")
+                         .help("The two builds to compare, the baseline first, or a universal \
+                                binary with two slices")
+                         .multiple(true)
+                         .max_values(2)
+                         .required(true)))
+        .subcommand(SubCommand::with_name("jump-tables")
+                    .about("Report the jump tables that match and switch statements lower to, \
+                            by function")
+                    .arg(Arg::with_name("top")
+                         .long("top")
+                         .value_name("N")
+                         .default_value("20")
+                         .help("List this many of the functions with the largest tables"))
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(&["text", "json", "yaml"])
+                         .default_value("text")
+                         .help("The output format"))
+                    .arg(Arg::with_name("FILE")
+                         .help("The binary or object file to examine")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("kernel")
+                    .about("Group the sections of a vmlinux or kernel module by what the kernel \
+                            uses them for (init, exit, percpu, exports, ...) and sum those that \
+                            it frees after initialization")
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(&["text", "json", "yaml"])
+                         .default_value("text")
+                         .help("The output format"))
+                    .arg(Arg::with_name("FILE")
+                         .help("The vmlinux or .ko file to examine")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("layout")
+                    .about("Compare the order of the functions of two builds: how much of the \
+                            code moved out of order or to a new address, which predicts the \
+                            size of differential updates and how warm caches stay")
+                    .arg(Arg::with_name("top")
+                         .long("top")
+                         .value_name("N")
+                         .default_value("20")
+                         .help("List this many of the largest functions that moved"))
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(&["text", "json", "yaml"])
+                         .default_value("text")
+                         .help("The output format"))
+                    .arg(Arg::with_name("OLD")
+                         .help("The baseline build")
+                         .required(true))
+                    .arg(Arg::with_name("NEW")
+                         .help("The build to compare against the baseline")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("link-inputs")
+                    .about("Report the combined size of the inputs of a link, before linking")
+                    .arg(Arg::with_name("normalize-names")
+                         .long("normalize-names")
+                         .help("Use the same section names (text, rodata, unwind, ...) for every \
+                                file format"))
+                    .arg(Arg::with_name("include-non-alloc")
+                         .long("include-non-alloc")
+                         .help("Count sections that aren't loaded into memory (debug info, \
+                                symbol tables) toward the total"))
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(&["text", "json", "yaml"])
+                         .default_value("text")
+                         .help("The output format"))
+                    .arg(Arg::with_name("INPUTS")
+                         .help("A linker response file (`@file`) or MRI script listing the \
+                                object files and archives to link")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("merge")
+                    .about("Report how well mergeable string and constant sections were merged")
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(&["text", "json", "yaml"])
+                         .default_value("text")
+                         .help("The output format"))
+                    .arg(Arg::with_name("FILE")
+                         .help("The object file to examine")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("objc")
+                    .about("Report duplicated and unusually long Objective-C selectors, and \
+                            CFString literals")
+                    .arg(Arg::with_name("long")
+                         .long("long")
+                         .value_name("BYTES")
+                         .default_value("64")
+                         .help("List the selectors and method names longer than this"))
+                    .arg(Arg::with_name("top")
+                         .long("top")
+                         .value_name("N")
+                         .default_value("10")
+                         .help("List this many of the largest CFString literals"))
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(&["text", "json", "yaml"])
+                         .default_value("text")
+                         .help("The output format"))
+                    .arg(Arg::with_name("FILE")
+                         .help("The Mach-O file to examine")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("page-size")
+                    .about("Check whether shared libraries can be loaded on devices with 16KB \
+                            pages")
+                    .arg(Arg::with_name("page-size")
+                         .long("page-size")
+                         .value_name("BYTES")
+                         .default_value("16K")
+                         .help("The page size to check against"))
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(&["text", "json", "yaml"])
+                         .default_value("text")
+                         .help("The output format"))
+                    .arg(Arg::with_name("PATH")
+                         .help("An ELF shared library, an APK or AAB, or a directory with a \
+                                subdirectory of libraries per ABI")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("paths")
+                    .about("List the absolute paths embedded in loaded data and debug info, by \
+                            the prefix that --remap-path-prefix would remove")
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(&["text", "json", "yaml"])
+                         .default_value("text")
+                         .help("The output format"))
+                    .arg(Arg::with_name("FILE")
+                         .help("The object file to examine")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("pgo")
+                    .about("Report the size of profile instrumentation and estimate the size of \
+                            the binary without it")
+                    .arg(Arg::with_name("include-non-alloc")
+                         .long("include-non-alloc")
+                         .help("Count sections that aren't loaded into memory (debug info, \
+                                symbol tables) toward the total"))
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(&["text", "json", "yaml"])
+                         .default_value("text")
+                         .help("The output format"))
+                    .arg(Arg::with_name("FILE")
+                         .help("The instrumented object file to examine")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("post-link")
+                    .about("Report the hot/cold splitting and duplication left by BOLT, \
+                            Propeller and other layout optimizations")
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(&["text", "json", "yaml"])
+                         .default_value("text")
+                         .help("The output format"))
+                    .arg(Arg::with_name("FILE")
+                         .help("The object file to examine")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("predict")
+                    .about("Predict the size of a linked ELF binary from its object file inputs")
+                    .arg(Arg::with_name("gc-sections")
+                         .long("gc-sections")
+                         .help("Model the removal of unreferenced sections, as with the linker's \
+                                --gc-sections"))
+                    .arg(Arg::with_name("entry")
+                         .long("entry")
+                         .value_name("SYMBOL")
+                         .default_value("_start")
+                         .help("The symbol that --gc-sections starts from"))
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(&["text", "json", "yaml"])
+                         .default_value("text")
+                         .help("The output format"))
+                    .arg(Arg::with_name("INPUTS")
+                         .help("A linker response file (`@file`) or MRI script listing the \
+                                object files and archives to link")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("rlib")
+                    .about("Split a Rust library into its crate metadata, which only the \
+                            compiler reads, and the code and data of its object files")
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(&["text", "json", "yaml"])
+                         .default_value("text")
+                         .help("The output format"))
+                    .arg(Arg::with_name("FILE")
+                         .help("The rlib to examine")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("runtime")
+                    .about("Sum the runtime libraries linked into a binary: the memory \
+                            allocator, the unwinder and the compiler builtins, which are a \
+                            fixed cost apart from the application's code")
+                    .arg(Arg::with_name("top")
+                         .long("top")
+                         .value_name("N")
+                         .default_value("20")
+                         .help("List this many of the largest of the symbols"))
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(&["text", "json", "yaml"])
+                         .default_value("text")
+                         .help("The output format"))
+                    .arg(Arg::with_name("FILE")
+                         .help("The object file to examine")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("std")
+                    .about("Split the symbols of a Rust binary between the standard library \
+                            (std, core, alloc), the runtime libraries and your own code, and \
+                            estimate the size of the binary built no_std")
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(&["text", "json", "yaml"])
+                         .default_value("text")
+                         .help("The output format"))
+                    .arg(Arg::with_name("FILE")
+                         .help("The object file to examine")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("supersize")
+                    .about("Write a .size archive of the symbols of a binary, with their source \
+                            files and components, for Chromium's SuperSize viewer")
+                    .arg(Arg::with_name("output")
+                         .short("o")
+                         .long("output")
+                         .value_name("PATH")
+                         .required(true)
+                         .help("The .size file to write"))
+                    .arg(Arg::with_name("group-by")
+                         .long("group-by")
+                         .takes_value(true)
+                         .possible_values(&["crate", "section", "source-file", "language",
+                                            "label", "owner", "component", "origin"])
+                         .default_value("crate")
+                         .help("What the component of each symbol is: its crate (or C++ \
+                                namespace), section, source file (from DWARF debug info), \
+                                language, label given by --labels, owners given by --owners, \
+                                project component given by --components, or whether the \
+                                compiler generated it"))
+                    .arg(Arg::with_name("labels")
+                         .long("labels")
+                         .value_name("FILE")
+                         .help("A TOML file of `[[rule]]`s, each giving a `label` to the \
+                                symbols matching its `symbol` and `section` regexes"))
+                    .arg(Arg::with_name("owners")
+                         .long("owners")
+                         .value_name("FILE")
+                         .help("A CODEOWNERS file, mapping source file patterns to owners"))
+                    .arg(Arg::with_name("source-root")
+                         .long("source-root")
+                         .value_name("DIR")
+                         .requires("owners")
+                         .help("The directory the --owners patterns are relative to, if the \
+                                debug info has absolute source paths"))
+                    .arg(Arg::with_name("FILE")
+                         .help("The binary to archive")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("thinning")
+                    .about("Estimate what App Store thinning leaves of an iOS or macOS app for \
+                            one architecture: the slices for others, bitcode and libraries in \
+                            the OS's shared region are eliminated, and the rest is private to \
+                            the app")
+                    .arg(Arg::with_name("arch")
+                         .long("arch")
+                         .value_name("ARCH")
+                         .default_value("arm64")
+                         .help("The architecture of the device (arm64, x86_64, ...)"))
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(&["text", "json", "yaml"])
+                         .default_value("text")
+                         .help("The output format"))
+                    .arg(Arg::with_name("PATH")
+                         .help("An .ipa, an .app directory, or a Mach-O file")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("thunks")
+                    .about("Report the functions that the compiler outlined, and the thunks and \
+                            trampolines that the compiler and linker generated, as one \
+                            category")
+                    .arg(Arg::with_name("top")
+                         .long("top")
+                         .value_name("N")
+                         .default_value("10")
+                         .help("List this many of the largest of the functions"))
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(&["text", "json", "yaml"])
+                         .default_value("text")
+                         .help("The output format"))
+                    .arg(Arg::with_name("FILE")
+                         .help("The object file to examine")
+                         .required(true)))
+        .get_matches_safe();
+    let matches = match matches {
+        Ok(matches) => matches,
+        // --help and --version exit successfully, anything else is a usage error.
+        Err(err) if !err.use_stderr() => err.exit(),
+        Err(err) => {
+            eprintln!("{}", err.message);
+            process::exit(exit::ExitCode::Usage.code());
+        }
+    };
+    if matches.is_present("exit-codes") {
+        print!("{}", exit_codes);
+        return Ok(());
+    }
+    match matches.subcommand() {
+        ("analyze", Some(args)) => analyze_main(args),
+        ("android-abis", Some(args)) => android_abis_main(args),
+        ("assets", Some(args)) => assets_main(args),
+        ("budget", Some(args)) => budget_main(args),
+        ("cold", Some(args)) => cold_main(args),
+        ("compare", Some(args)) => compare_main(args),
+        ("components", Some(args)) => components_main(args),
+        ("constructors", Some(args)) => constructors_main(args),
+        ("core", Some(args)) => core_main(args),
+        ("data-in-code", Some(args)) => data_in_code_main(args),
+        ("dead-exports", Some(args)) => dead_exports_main(args),
+        ("debug-sections", Some(args)) => debug_sections_main(args),
+        ("diff", Some(args)) => diff_main(args),
+        ("disasm", Some(args)) => disasm_main(args),
+        ("download", Some(args)) => download_main(args),
+        ("drop-glue", Some(args)) => drop_glue_main(args),
+        ("delta", Some(args)) => delta_main(args),
+        ("dynamic", Some(args)) => dynamic_main(args),
+        ("duplicates", Some(args)) => duplicates_main(args),
+        ("find", Some(args)) => find_main(args),
+        ("firmware", Some(args)) => firmware_main(args),
+        ("fmt", Some(args)) => fmt_main(args),
+        ("generated", Some(args)) => generated_main(args),
+        ("hints", Some(args)) => hints_main(args),
+        ("hotness", Some(args)) => hotness_main(args),
+        ("hugepages", Some(args)) => hugepages_main(args),
+        ("isa", Some(args)) => isa_main(args),
+        ("jump-tables", Some(args)) => jump_tables_main(args),
+        ("kernel", Some(args)) => kernel_main(args),
+        ("layout", Some(args)) => layout_main(args),
+        ("link-inputs", Some(args)) => link_inputs_main(args),
+        ("merge", Some(args)) => merge_main(args),
+        ("objc", Some(args)) => objc_main(args),
+        ("page-size", Some(args)) => page_size_main(args),
+        ("paths", Some(args)) => paths_main(args),
+        ("pgo", Some(args)) => pgo_main(args),
+        ("post-link", Some(args)) => post_link_main(args),
+        ("predict", Some(args)) => predict_main(args),
+        ("rlib", Some(args)) => rlib_main(args),
+        ("runtime", Some(args)) => runtime_main(args),
+        ("std", Some(args)) => std_main(args),
+        ("supersize", Some(args)) => supersize_main(args),
+        ("thinning", Some(args)) => thinning_main(args),
+        ("thunks", Some(args)) => thunks_main(args),
+        _ => report_main(&matches),
+    }
+}
+
+fn main() {
+    match real_main() {
+        Ok(_) => {},
+        Err(err) => {
+            eprintln!("Error: {:?}", err);
+            process::exit(exit::ExitCode::for_error(&err).code());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::elf::section_header::{SHF_ALLOC, SHF_EXECINSTR, SHF_WRITE, SHT_PROGBITS};
+    use goblin::elf::sym::{STT_FUNC, STT_OBJECT};
+    use testelf::{self, Elf};
+
+    fn object() -> Vec<u8> {
+        Elf::object()
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &[0; 40])
+            .section(".rodata", SHT_PROGBITS, SHF_ALLOC, &[0; 16])
+            .section(".data", SHT_PROGBITS, SHF_ALLOC | SHF_WRITE, &[0; 8])
+            .nobits(".bss", SHF_ALLOC | SHF_WRITE, 100)
+            .section(".comment", SHT_PROGBITS, 0, &[0; 5])
+            .build()
+    }
+
+    #[test]
+    fn non_alloc_sections_count_on_request() {
+        let buf = object();
+        let categories: Vec<_> = sections(&buf).unwrap().into_iter()
+            .map(|(name, size, category, _)| (name, size, category))
+            .collect();
+        assert_eq!(&categories[1..6], &[
+            (".text".to_string(), 40, Section::Text),
+            (".rodata".to_string(), 16, Section::Text),
+            (".data".to_string(), 8, Section::Data),
+            (".bss".to_string(), 100, Section::Bss),
+            (".comment".to_string(), 5, Section::Other),
+        ]);
+        let path = Path::new("a.o");
+        let loaded = section_sizes(path, &buf, false, false).unwrap();
+        assert_eq!(loaded.keys().collect::<Vec<_>>(), vec![".bss", ".data", ".rodata", ".text"]);
+        let all = section_sizes(path, &buf, false, true).unwrap();
+        assert_eq!((all[".comment"], all.len()), (5, 7));
+    }
+
+    #[test]
+    fn summary_lines() {
+        let buf = object();
+        let format = units::SizeFormat::default();
+        let line = summary_line("a.o", &buf, &format).unwrap();
+        assert_eq!(line, format!("{:<8} {:<10} {:>10} {:>10} {:>10} {:>10}  a.o", "elf", "x86_64",
+                                 56, 8, 100, buf.len()));
+        assert!(summary_line("notes.txt", b"not an object", &format).is_err());
+    }
+
+    #[test]
+    fn section_previews() {
+        assert_eq!(preview_bytes(b"GCC: (GNU) 13\0clang\t\"x\"\n"),
+                   "GCC: (GNU) 13\\0clang\\t\\\"x\\\"\\n");
+        assert_eq!(preview_bytes(&[0x7f, b'E', 0, 0xff]), "7f 45 00 ff");
+        assert_eq!(preview_bytes(&[0, 0]), "00 00");
+
+        let buf = Elf::object()
+            .section(".comment", SHT_PROGBITS, 0, b"rustc version 1.80\0")
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &[0xc3, 0x90])
+            .nobits(".bss", SHF_ALLOC | SHF_WRITE, 100)
+            .build();
+        let previews: Vec<_> = object_records(&buf, 8).unwrap().into_iter()
+            .filter(|r| r.name.starts_with(".c") || r.name.starts_with(".t") || r.name == ".bss")
+            .map(|r| (r.name, r.preview))
+            .collect();
+        assert_eq!(previews, vec![(".comment".to_string(), Some("rustc ve".to_string())),
+                                  (".text".to_string(), Some("c3 90".to_string())),
+                                  (".bss".to_string(), None)]);
+        assert!(object_records(&buf, 0).unwrap().iter().all(|r| r.preview.is_none()));
+    }
+
+    #[test]
+    fn archive_members_and_their_sum() {
+        let other = Elf::object()
+            .section(".text.f", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &[0; 24])
+            .section(".text.g", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &[0; 8])
+            .build();
+        let buf = testarchive::archive(&[testarchive::member("a.o/", &object()),
+                                         testarchive::member("b.o/", &other)]);
+        let path = Path::new("libx.a");
+        let report = archive_report(path, &buf, false).unwrap();
+        let members: Vec<_> = report.members.iter().map(|m| (&*m.name, m.total)).collect();
+        assert_eq!(members, vec![("a.o", 40 + 16 + 8 + 100), ("b.o", 32)]);
+        assert_eq!(report.total, 164 + 32);
+        assert_eq!(report.sections[&Section::Text][".text"], 40);
+        assert_eq!(report.sections[&Section::Other][".comment"], 5);
+
+        // Normalized, the function sections add up with `.text`.
+        let report = archive_report(path, &buf, true).unwrap();
+        assert_eq!(report.sections[&Section::Text]["text"], 40 + 24 + 8);
+        assert_eq!(report.total, 164 + 32);
+        match archive_report(Path::new("a.o"), &object(), false) {
+            Err(err) => assert!(err.downcast_ref::<exit::UsageError>().is_some()),
+            Ok(_) => panic!("an object file isn't an archive"),
+        }
+    }
+
+    #[test]
+    fn sections_covered_by_symbols() {
+        let buf = Elf::object()
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &[0; 40])
+            .section(".data", SHT_PROGBITS, SHF_ALLOC | SHF_WRITE, &[0; 8])
+            .nobits(".bss", SHF_ALLOC | SHF_WRITE, 100)
+            .symbol("f", STT_FUNC, ".text", 0, 16)
+            .symbol("g", STT_FUNC, ".text", 8, 12)
+            .symbol("x", STT_OBJECT, ".data", 4, 4)
+            .build();
+        let sections: Vec<_> = section_symbols(&buf, &symbols::symbols(&buf).unwrap()).unwrap()
+            .into_iter()
+            .map(|s| (s.name, s.size, s.symbols, s.attributed, s.unattributed))
+            .collect();
+        // Overlapping symbols count once.
+        assert_eq!(sections, vec![(".text".to_string(), 40, 2, 20, 20),
+                                  (".data".to_string(), 8, 1, 4, 4),
+                                  (".bss".to_string(), 100, 0, 0, 100)]);
+
+        // A corrupt size covers no more than the whole section.
+        let mut symbols = symbols::symbols(&buf).unwrap();
+        symbols.iter_mut().find(|sym| sym.name == "x").unwrap().size = u64::MAX;
+        let data = section_symbols(&buf, &symbols).unwrap().into_iter()
+            .find(|s| s.name == ".data").unwrap();
+        assert_eq!((data.attributed, data.unattributed), (8, 0));
+    }
+
+    #[test]
+    fn zip_members_matching_globs() {
+        let small = Elf::shared()
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &[0; 24])
+            .build();
+        let buf = testzip::archive(&[("lib/arm64-v8a/libxul.so", zip::STORED, 0, &object()),
+                                     ("lib/x86_64/libsmall.so", zip::DEFLATED, 0, &small),
+                                     ("classes.dex", zip::DEFLATED, 0, b"dex\n035\0")]);
+        let report = zip_report(&buf, vec!["lib/*/*.so"], false).unwrap();
+        let members: Vec<_> = report.members.iter()
+            .map(|m| (&*m.name, m.stored, m.size == m.compressed_size, m.total))
+            .collect();
+        assert_eq!(members, vec![("lib/arm64-v8a/libxul.so", true, true, 164),
+                                 ("lib/x86_64/libsmall.so", false, false, 24)]);
+        assert_eq!(report.total, 164 + 24);
+        assert_eq!(report.members[1].size, small.len() as u64);
+        let report = zip_report(&buf, vec!["lib/x86_64/*", "*.none"], false).unwrap();
+        assert_eq!(report.members.len(), 1);
+
+        for (buf, patterns) in [(&buf, vec!["*.none"]), (&object(), vec!["*"])] {
+            match zip_report(buf, patterns, false) {
+                Err(err) => assert!(err.downcast_ref::<exit::UsageError>().is_some()),
+                Ok(_) => panic!("no member matches"),
+            }
+        }
+        assert!(zip_report(&buf, vec!["classes.dex"], false).is_err());
+    }
+
+    #[test]
+    fn berkeley_lines_per_slice() {
+        let record = |name, size, category, arch: Option<&str>| {
+            let mut record = SectionRecord::synthetic(name, size, category);
+            record.arch = arch.map(str::to_string);
+            record
+        };
+        let records = vec![record(".text", 100, Section::Text, None),
+                           record(".rodata", 20, Section::Data, None),
+                           record(".data", 8, Section::Data, None),
+                           record(".bss", 16, Section::Bss, None),
+                           record(".comment", 40, Section::Other, None)];
+        assert_eq!(berkeley_lines("a.out", records),
+                   vec!["    100\t     28\t     16\t    144\t     90\ta.out"]);
+        let records = vec![record("__text", 10, Section::Text, Some("x86_64")),
+                           record("__text", 12, Section::Text, Some("arm64"))];
+        assert_eq!(berkeley_lines("app", records),
+                   vec!["     12\t      0\t      0\t     12\t      c\t\
+                         app (for architecture arm64)",
+                        "     10\t      0\t      0\t     10\t      a\t\
+                         app (for architecture x86_64)"]);
+        assert_eq!(berkeley_lines("empty.o", Vec::new()),
+                   vec!["      0\t      0\t      0\t      0\t      0\tempty.o"]);
+    }
+
+    #[test]
+    fn sysv_tables_per_slice() {
+        let record = |name, size, address, arch: Option<&str>| {
+            let mut record = SectionRecord::synthetic(name, size, Section::Text);
+            record.address = address;
+            record.arch = arch.map(str::to_string);
+            record
+        };
+        let records = vec![record(".text", 1234, Some(0x401000), None),
+                           record(".comment", 16, None, None),
+                           record("", 8, None, None)];
+        assert_eq!(sysv_lines("a.out", records.clone(), false),
+                   vec!["a.out  :", "section    size      addr", ".text      1234   4198400",
+                        ".comment     16         0", "Total      1250", "", ""]);
+        assert_eq!(sysv_lines("a.out", records, true)[2], "text       1234   4198400");
+        let records = vec![record("__text", 10, Some(16), Some("x86_64")),
+                           record("__text", 12, Some(32), Some("aarch64"))];
+        let lines = sysv_lines("app", records, false);
+        assert_eq!(lines[..3], ["app (for architecture aarch64):", "section   size   addr",
+                                "__text      12     32"]);
+        assert_eq!(lines[6], "app (for architecture x86_64):");
+        assert_eq!(sysv_lines("empty.o", Vec::new(), false),
+                   vec!["empty.o  :", "section   size   addr", "Total        0", "", ""]);
+    }
+
+    #[test]
+    fn grouped_diffs_without_a_memory_limit() {
+        let diff = |args: &[&str]| {
+            let matches = App::new("diff")
+                .arg(Arg::with_name("OLD").required(true))
+                .arg(Arg::with_name("NEW").required(true))
+                .arg(Arg::with_name("symbols").long("symbols"))
+                .arg(Arg::with_name("group-by").long("group-by").default_value("none"))
+                .arg(Arg::with_name("max-memory").long("max-memory").takes_value(true))
+                .get_matches_from(args);
+            diff_main(&matches)
+        };
+        let args = ["diff", "/nonexistent/old", "/nonexistent/new", "--symbols", "--max-memory",
+                    "1M"];
+        let err = diff(&[&args[..], &["--group-by", "crate"]].concat()).unwrap_err();
+        assert!(err.downcast_ref::<exit::UsageError>().is_some());
+        // Without --group-by, the limit is taken, and the files are read.
+        let err = diff(&args).unwrap_err();
+        assert!(err.downcast_ref::<exit::UsageError>().is_none());
+    }
+
+    #[test]
+    fn subcommands_that_need_a_components_file() {
+        // The file is looked for above the current directory, where there may be one.
+        if components::Components::find().is_some() {
+            return;
+        }
+        let matches = App::new("budget")
+            .arg(Arg::with_name("components").long("components").takes_value(true))
+            .get_matches_from(["budget"]);
+        let err = match component_rules(&matches, "budget") {
+            Err(err) => err,
+            Ok(_) => panic!("there is no components file"),
+        };
+        assert!(err.downcast_ref::<exit::UsageError>().is_some());
+        assert!(err.to_string().starts_with("budget needs --components or a components.toml"),
+                "{}", err);
+    }
+
+    #[test]
+    fn human_sizes_default_to_iec() {
+        let units = |args: &[&str]| {
+            let matches = App::new("rust-size")
+                .arg(Arg::with_name("units").long("units").takes_value(true))
+                .arg(Arg::with_name("human").long("human"))
+                .get_matches_from(args);
+            size_format(&matches).map(|format| format.units)
+        };
+        assert_eq!(units(&["rust-size"]).unwrap(), units::Units::Bytes);
+        assert_eq!(units(&["rust-size", "--human"]).unwrap(), units::Units::Iec);
+        assert_eq!(units(&["rust-size", "--human", "--units", "si"]).unwrap(), units::Units::Si);
+        assert!(units(&["rust-size", "--units", "kib"]).is_err());
+    }
+
+    #[test]
+    fn tables_with_shares_and_subtotals() {
+        let mut sizes = SectionSizes::new();
+        for &(category, name, size) in &[(Section::Text, ".init", 100),
+                                         (Section::Text, ".text", 300),
+                                         (Section::Data, ".data", 100),
+                                         (Section::Data, "", 100),
+                                         (Section::Other, ".comment", 50)] {
+            sizes.entry(category).or_default().insert(name.to_string(), size);
+        }
+        assert_eq!(table_lines(&sizes, &units::SizeFormat::default()),
+                   vec!["SIZE       %  SECTION",
+                        " 300   50.0%  .text",
+                        " 100   16.7%  .init",
+                        " 400   66.7%  (text total)",
+                        " 100   16.7%  .data",
+                        " 200   33.3%  (data total)",
+                        "  50          .comment",
+                        "  50          (other total)",
+                        " 600  100.0%  (loaded total)"]);
+        assert_eq!(table_lines(&SectionSizes::new(), &units::SizeFormat::default()),
+                   vec!["SIZE  %  SECTION", "   0  -  (loaded total)"]);
+    }
+
+    #[test]
+    fn symbol_maps_in_address_order() {
+        let buf = Elf::object()
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &[0; 40])
+            .section(".data", SHT_PROGBITS, SHF_ALLOC | SHF_WRITE, &[0; 8])
+            .symbol("_ZN3foo3bar17h0123456789abcdefE", STT_FUNC, ".text", 8, 32)
+            .symbol("main", STT_FUNC, ".text", 0, 8)
+            .symbol("COUNT", STT_OBJECT, ".data", 4, 4)
+            .build();
+        // The addresses of an object's symbols are offsets in their sections, and names stay
+        // mangled.
+        assert_eq!(symbol_map_lines(symbols::symbols(&buf).unwrap()),
+                   vec!["0000000000000000 0000000000000008 main",
+                        "0000000000000004 0000000000000004 COUNT",
+                        "0000000000000008 0000000000000020 _ZN3foo3bar17h0123456789abcdefE"]);
+    }
+
+    #[test]
+    fn ndjson_lines_per_slice() {
+        let record = |name, size, category, arch: Option<&str>| {
+            let mut record = SectionRecord::synthetic(name, size, category);
+            record.arch = arch.map(str::to_string);
+            record
+        };
+        let records = vec![record(".text.a", 100, Section::Text, None),
+                           record(".text.b", 20, Section::Text, None),
+                           record(".bss", 16, Section::Bss, None),
+                           record(".comment", 40, Section::Other, None)];
+        // Non-allocated sections are listed, but don't count in the total.
+        assert_eq!(ndjson_lines("a.out", records.clone(), false).unwrap(),
+                   vec![r#"{"file":"a.out","sections":{"Text":{".text.a":100,".text.b":20},"#
+                        .to_string() +
+                        r#""Bss":{".bss":16},"Other":{".comment":40}},"total":136}"#]);
+        assert_eq!(ndjson_lines("a.out", records, true).unwrap(),
+                   vec![r#"{"file":"a.out","sections":{"Text":{"text":120},"Bss":{"bss":16},"#
+                        .to_string() + r#""Other":{"metadata":40}},"total":136}"#]);
+        let records = vec![record("__text", 10, Section::Text, Some("x86_64")),
+                           record("__text", 12, Section::Text, Some("arm64"))];
+        assert_eq!(ndjson_lines("app", records, false).unwrap(),
+                   vec![r#"{"file":"app","arch":"arm64","sections":{"Text":{"__text":12}},"#
+                        .to_string() + r#""total":12}"#,
+                        r#"{"file":"app","arch":"x86_64","sections":{"Text":{"__text":10}},"#
+                        .to_string() + r#""total":10}"#]);
+    }
+
+    #[test]
+    fn bloaty_rows_of_every_file() {
+        let records = || vec![SectionRecord::synthetic(".text", 100, Section::Text),
+                              SectionRecord::synthetic(".bss", 400, Section::Bss),
+                              SectionRecord::synthetic(".comment", 20, Section::Other),
+                              SectionRecord::synthetic(".empty", 0, Section::Data),
+                              SectionRecord::synthetic("", 8, Section::Data)];
+        // The files are added up, and what of them no section covers is unmapped.
+        assert_eq!(bloaty_lines(vec![(200, records()), (150, records())], false),
+                   vec!["sections,vmsize,filesize", ".bss,800,0", ".text,200,200",
+                        "[Unmapped],0,110", ".comment,0,40"]);
+        assert_eq!(bloaty_lines(vec![(120, records())], true),
+                   vec!["sections,vmsize,filesize", "bss,400,0", "text,100,100",
+                        "metadata,0,20"]);
+    }
+
+    #[test]
+    fn loaded_data_skips_sections_past_the_end() {
+        let mut buf = Elf::object()
+            .section(".rodata", SHT_PROGBITS, SHF_ALLOC, b"abcd")
+            .section(".rodata.bogus", SHT_PROGBITS, SHF_ALLOC, b"efgh")
+            .build();
+        testelf::corrupt_section(&mut buf, 1, u64::MAX - 2, 16);
+        let sections: Vec<_> = loaded_data(&buf).unwrap().into_iter()
+            .map(|(name, _, data)| (name, data.to_vec()))
+            .collect();
+        assert_eq!(sections, vec![(".rodata".to_string(), b"abcd".to_vec())]);
+    }
+
+    #[test]
+    fn no_preview_past_the_end() {
+        let mut buf = Elf::object()
+            .section(".rodata", SHT_PROGBITS, SHF_ALLOC, b"abcd")
+            .build();
+        testelf::corrupt_section(&mut buf, 0, u64::MAX - 2, 4);
+        let records = object_records(&buf, 16).unwrap();
+        let rodata = records.iter().find(|record| record.name == ".rodata").unwrap();
+        assert_eq!(rodata.preview, None);
     }
 }
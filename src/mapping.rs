@@ -0,0 +1,434 @@
+use failure::Error;
+use goblin::elf::header::ET_REL;
+use goblin::elf::section_header::SHT_NOBITS;
+use goblin::elf::sym::STT_OBJECT;
+use goblin::mach::constants::{S_ATTR_PURE_INSTRUCTIONS, S_ATTR_SOME_INSTRUCTIONS};
+use goblin::mach::load_command::CommandVariant;
+use goblin::mach::Mach;
+use goblin::Object;
+use map_mach_name;
+use section_records;
+use std::cmp;
+use std::collections::BTreeMap;
+use CODE_FLAGS;
+
+/// The size of an `LC_DATA_IN_CODE` entry.
+const DATA_IN_CODE_ENTRY_SIZE: usize = 8;
+
+/// The `LC_DATA_IN_CODE` kind of plain data; the others are kinds of jump tables.
+const DICE_KIND_DATA: u16 = 1;
+
+/// The size of the entries of each `LC_DATA_IN_CODE` kind of jump table, from
+/// `DICE_KIND_JUMP_TABLE8` to `DICE_KIND_ABS_JUMP_TABLE32`.
+fn dice_entry_size(kind: u16) -> Option<u64> {
+    match kind {
+        2 => Some(1),
+        3 => Some(2),
+        4 | 5 => Some(4),
+        _ => None,
+    }
+}
+
+/// A jump table embedded in a code section.
+#[derive(Clone, Debug)]
+pub struct InlineTable {
+    pub section: String,
+    /// The address of the table, relative to its section in relocatable ELF objects.
+    pub address: u64,
+    pub size: u64,
+    /// The size of the entries, if known.
+    pub entry_size: Option<u64>,
+}
+
+/// A code section, split into instructions and the data embedded between them.
+#[derive(Clone, Debug, Serialize)]
+pub struct CodeSection {
+    pub name: String,
+    pub size: u64,
+    pub instructions: u64,
+    /// The tables of code addresses or offsets that switch statements jump through.
+    pub jump_tables: u64,
+    /// Literal pools and other constants.
+    pub literals: u64,
+    /// The sum of `jump_tables` and `literals`.
+    pub data: u64,
+    /// The share of the section that is instructions, from 0 to 1.
+    pub purity: f64,
+}
+
+impl CodeSection {
+    fn new(name: String, size: u64, jump_tables: u64, literals: u64) -> CodeSection {
+        let data = cmp::min(jump_tables + literals, size);
+        CodeSection {
+            name,
+            size,
+            instructions: size - data,
+            jump_tables: cmp::min(jump_tables, data),
+            literals: data - cmp::min(jump_tables, data),
+            data,
+            purity: if size == 0 { 1.0 } else { (size - data) as f64 / size as f64 },
+        }
+    }
+}
+
+/// The data embedded in the code of a binary, as emitted by `data-in-code --format json`.
+#[derive(Clone, Debug, Serialize)]
+pub struct DataInCodeReport {
+    /// What marks the data: `mapping-symbols` (ARM, AArch64 and RISC-V ELF files),
+    /// `LC_DATA_IN_CODE` (Mach-O), `symbols` when only data symbols in code sections do, or
+    /// `none`, in which case all code counts as instructions.
+    pub source: &'static str,
+    pub sections: Vec<CodeSection>,
+    pub instructions: u64,
+    pub jump_tables: u64,
+    pub literals: u64,
+    pub data: u64,
+}
+
+/// Whether the mapping symbol `name` starts data (`$d`) or instructions (`$x` on AArch64 and
+/// RISC-V, where it may carry the ISA, and `$a` and `$t` on ARM), or `None` if `name` isn't a
+/// mapping symbol. Mapping symbols may have a `.<n>` suffix to keep them distinct.
+fn mapping_kind(name: &str) -> Option<bool> {
+    match name.split('.').next().unwrap_or(name) {
+        "$d" => Some(true),
+        "$a" | "$t" => Some(false),
+        base if base.starts_with("$x") => Some(false),
+        _ => None,
+    }
+}
+
+/// Whether the run of data `data` in the linked code section spanning the addresses `code`
+/// looks like a jump table: at least two pointers, all into the section.
+fn is_address_table(data: &[u8], pointer_size: usize, little_endian: bool,
+                    code: (u64, u64)) -> bool {
+    data.len() >= 2 * pointer_size && data.len().is_multiple_of(pointer_size) &&
+        data.chunks_exact(pointer_size).all(|word| {
+            let mut bytes = [0; 8];
+            let value = if little_endian {
+                bytes[..pointer_size].copy_from_slice(word);
+                u64::from_le_bytes(bytes)
+            } else {
+                bytes[8 - pointer_size..].copy_from_slice(word);
+                u64::from_be_bytes(bytes)
+            };
+            value >= code.0 && value < code.1
+        })
+}
+
+/// Split the code sections of `buf` into instructions, jump tables and literals, using ELF
+/// mapping symbols or Mach-O `LC_DATA_IN_CODE` entries. Mapping symbols only survive in object
+/// files and unstripped binaries. On ELF, data that relocations point at code, or that is a
+/// table of addresses in its section, counts as jump tables; without mapping symbols, the data
+/// symbols in code sections count as literals.
+pub fn data_in_code(buf: &[u8]) -> Result<DataInCodeReport, Error> {
+    scan(buf).map(|(report, _)| report)
+}
+
+/// Find the jump tables embedded in the code sections of `buf`, as `data_in_code` classifies
+/// them.
+pub fn inline_jump_tables(buf: &[u8]) -> Result<Vec<InlineTable>, Error> {
+    scan(buf).map(|(_, tables)| tables)
+}
+
+/// Split the code sections of `buf` as `data_in_code` does, also returning the jump tables.
+fn scan(buf: &[u8]) -> Result<(DataInCodeReport, Vec<InlineTable>), Error> {
+    let mut source = "none";
+    let mut sections = Vec::new();
+    let mut tables = Vec::new();
+    match Object::parse(buf)? {
+        Object::Elf(elf) => {
+            let relocatable = elf.header.e_type == ET_REL;
+            // The start addresses of the runs of data and instructions in each section, and the
+            // data symbols in each section.
+            let mut runs: BTreeMap<usize, Vec<(u64, bool)>> = BTreeMap::new();
+            let mut objects: BTreeMap<usize, u64> = BTreeMap::new();
+            for sym in elf.syms.iter() {
+                if let Some(Ok(name)) = elf.strtab.get(sym.st_name) {
+                    if let Some(is_data) = mapping_kind(name) {
+                        runs.entry(sym.st_shndx).or_default().push((sym.st_value, is_data));
+                    } else if sym.st_type() == STT_OBJECT {
+                        *objects.entry(sym.st_shndx).or_insert(0) += sym.st_size;
+                    }
+                }
+            }
+            if !runs.is_empty() {
+                source = "mapping-symbols";
+            }
+            // The offsets in each section of the relocations that point into code.
+            let is_code = |shndx: usize| {
+                elf.section_headers.get(shndx).is_some_and(|sh| sh.is_executable())
+            };
+            let mut code_pointers: BTreeMap<usize, Vec<u64>> = BTreeMap::new();
+            for &(idx, ref relocs) in &elf.shdr_relocs {
+                let target = elf.section_headers[idx].sh_info as usize;
+                for reloc in relocs {
+                    if elf.syms.get(reloc.r_sym).is_some_and(|sym| is_code(sym.st_shndx)) {
+                        code_pointers.entry(target).or_default().push(reloc.r_offset);
+                    }
+                }
+            }
+            let pointer_size = if elf.is_64 { 8 } else { 4 };
+
+            for (i, sh) in elf.section_headers.iter().enumerate() {
+                if !sh.is_executable() || sh.sh_type == SHT_NOBITS {
+                    continue;
+                }
+                let name = elf.shdr_strtab.get(sh.sh_name).and_then(|res| res.ok())
+                    .unwrap_or("").to_string();
+                let start = if relocatable { 0 } else { sh.sh_addr };
+                let end = start + sh.sh_size;
+                let (mut jump_tables, mut literals) = (0, 0);
+                match runs.get_mut(&i) {
+                    Some(runs) => {
+                        runs.sort();
+                        for (j, &(address, is_data)) in runs.iter().enumerate() {
+                            let next = runs.get(j + 1).map_or(end, |&(next, _)| next.min(end));
+                            if !is_data || address < start || next <= address {
+                                continue;
+                            }
+                            let offset = (sh.sh_offset + address - start) as usize;
+                            let contents = buf.get(offset..offset + (next - address) as usize)
+                                .unwrap_or(&[]);
+                            let jump_table = if relocatable {
+                                code_pointers.get(&i).is_some_and(|offsets| {
+                                    offsets.iter().any(|&o| o >= address && o < next)
+                                })
+                            } else {
+                                is_address_table(contents, pointer_size, elf.little_endian,
+                                                 (start, end))
+                            };
+                            if jump_table {
+                                jump_tables += next - address;
+                                tables.push(InlineTable {
+                                    section: name.clone(),
+                                    address,
+                                    size: next - address,
+                                    entry_size: if relocatable {
+                                        None
+                                    } else {
+                                        Some(pointer_size as u64)
+                                    },
+                                });
+                            } else {
+                                literals += next - address;
+                            }
+                        }
+                    }
+                    None => {
+                        literals = objects.get(&i).cloned().unwrap_or(0);
+                        if literals > 0 && source == "none" {
+                            source = "symbols";
+                        }
+                    }
+                }
+                sections.push(CodeSection::new(name, sh.sh_size, jump_tables, literals));
+            }
+        }
+        Object::Mach(Mach::Binary(mach)) => {
+            let mut entries: &[u8] = &[];
+            for lc in &mach.load_commands {
+                if let CommandVariant::DataInCode(ref command) = lc.command {
+                    let start = command.dataoff as usize;
+                    entries = buf.get(start..start + command.datasize as usize).unwrap_or(&[]);
+                    source = "LC_DATA_IN_CODE";
+                }
+            }
+            // Each entry is the offset of a run of data from the Mach-O header, as loaded at
+            // the start of `__TEXT` (or at 0 in object files), and its length.
+            let image_base = mach.segments.iter()
+                .find(|seg| seg.name().ok() == Some("__TEXT"))
+                .map_or(0, |seg| seg.vmaddr);
+            let read = |entry: &[u8], i: usize, size: usize| {
+                let mut bytes = [0; 4];
+                bytes[..size].copy_from_slice(&entry[i..i + size]);
+                if mach.little_endian {
+                    u32::from_le_bytes(bytes)
+                } else {
+                    u32::from_be_bytes(bytes)
+                }
+            };
+            let runs: Vec<(u64, u64, u16)> = entries.chunks_exact(DATA_IN_CODE_ENTRY_SIZE)
+                .map(|entry| {
+                    (u64::from(read(entry, 0, 4)), u64::from(read(entry, 4, 2)),
+                     read(entry, 6, 2) as u16)
+                })
+                .collect();
+            let code_flags = S_ATTR_PURE_INSTRUCTIONS | S_ATTR_SOME_INSTRUCTIONS;
+            let code = mach.segments.sections().flatten().filter_map(|s| s.ok())
+                .map(|(sec, _data)| sec)
+                .filter(|sec| sec.flags & code_flags != 0);
+            for sec in code {
+                let name = match (sec.segname(), sec.name()) {
+                    (Ok(seg), Ok(name)) => map_mach_name(seg, name),
+                    _ => String::new(),
+                };
+                let start = sec.addr - image_base;
+                let (mut jump_tables, mut literals) = (0, 0);
+                for &(offset, length, kind) in &runs {
+                    if offset < start || offset >= start + sec.size {
+                        continue;
+                    }
+                    if kind == DICE_KIND_DATA {
+                        literals += length;
+                    } else {
+                        jump_tables += length;
+                        tables.push(InlineTable {
+                            section: name.clone(),
+                            address: image_base + offset,
+                            size: length,
+                            entry_size: dice_entry_size(kind),
+                        });
+                    }
+                }
+                sections.push(CodeSection::new(name, sec.size, jump_tables, literals));
+            }
+        }
+        _ => {
+            for record in section_records(buf)? {
+                if record.flag_names.iter().any(|flag| CODE_FLAGS.contains(flag)) {
+                    sections.push(CodeSection::new(record.name, record.size, 0, 0));
+                }
+            }
+        }
+    }
+    let report = DataInCodeReport {
+        source,
+        instructions: sections.iter().map(|s| s.instructions).sum(),
+        jump_tables: sections.iter().map(|s| s.jump_tables).sum(),
+        literals: sections.iter().map(|s| s.literals).sum(),
+        data: sections.iter().map(|s| s.data).sum(),
+        sections,
+    };
+    Ok((report, tables))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::elf::section_header::{SHF_ALLOC, SHF_EXECINSTR, SHT_PROGBITS};
+    use goblin::elf::sym::{STT_FUNC, STT_NOTYPE};
+    use goblin::elf::Elf as ElfFile;
+    use goblin::mach::constants::S_ATTR_PURE_INSTRUCTIONS;
+    use goblin::mach::load_command::LC_DATA_IN_CODE;
+    use goblin::mach::MachO as MachOFile;
+    use testelf::Elf;
+    use testmacho::MachO;
+
+    const CODE: u32 = SHF_ALLOC | SHF_EXECINSTR;
+
+    fn summary(report: &DataInCodeReport) -> (&'static str, u64, u64, u64) {
+        (report.source, report.instructions, report.jump_tables, report.literals)
+    }
+
+    #[test]
+    fn mapping_symbols() {
+        assert_eq!(mapping_kind("$d"), Some(true));
+        assert_eq!(mapping_kind("$d.12"), Some(true));
+        assert_eq!(mapping_kind("$a"), Some(false));
+        assert_eq!(mapping_kind("$t.1"), Some(false));
+        assert_eq!(mapping_kind("$xrv64i2p1_m2p0"), Some(false));
+        assert_eq!(mapping_kind("$dx"), None);
+        assert_eq!(mapping_kind("main"), None);
+    }
+
+    #[test]
+    fn data_runs_of_an_object() {
+        // Instructions, a literal pool, instructions, then a jump table that a relocation
+        // points at the function.
+        let buf = Elf::object()
+            .section(".text", SHT_PROGBITS, CODE, &[0; 32])
+            .symbol("f", STT_FUNC, ".text", 0, 32)
+            .local("$x", STT_NOTYPE, ".text", 0, 0)
+            .local("$d", STT_NOTYPE, ".text", 8, 0)
+            .local("$x.1", STT_NOTYPE, ".text", 16, 0)
+            .local("$d.2", STT_NOTYPE, ".text", 24, 0)
+            .rela(".text", &[(24, "f")])
+            .build();
+        let report = data_in_code(&buf).unwrap();
+        assert_eq!(summary(&report), ("mapping-symbols", 16, 8, 8));
+        assert_eq!(report.data, 16);
+        assert_eq!(report.sections[0].purity, 0.5);
+        let tables: Vec<_> = inline_jump_tables(&buf).unwrap().into_iter()
+            .map(|t| (t.section, t.address, t.size, t.entry_size))
+            .collect();
+        assert_eq!(tables, vec![(".text".to_string(), 24, 8, None)]);
+    }
+
+    #[test]
+    fn address_tables_of_a_linked_binary() {
+        let executable = |text: &[u8]| {
+            Elf::executable()
+                .section(".text", SHT_PROGBITS, CODE, text)
+                .local("$x", STT_NOTYPE, ".text", 0, 0)
+                .local("$d", STT_NOTYPE, ".text", 8, 0)
+                .local("$x.1", STT_NOTYPE, ".text", 24, 0)
+                .local("$d.2", STT_NOTYPE, ".text", 32, 0)
+                .build()
+        };
+        // Section addresses don't depend on their contents.
+        let buf = executable(&[0; 48]);
+        let elf = ElfFile::parse(&buf).unwrap();
+        let start = elf.section_headers.iter()
+            .find(|sh| elf.shdr_strtab.get(sh.sh_name).and_then(|res| res.ok()) == Some(".text"))
+            .unwrap().sh_addr;
+        // Two pointers into the section, then instructions, then a null pointer and one past
+        // the end of the section.
+        let mut text = vec![0; 8];
+        for &address in &[start, start + 16, 0, 0, start + 48] {
+            text.extend_from_slice(&address.to_le_bytes());
+        }
+        let buf = executable(&text);
+        let report = data_in_code(&buf).unwrap();
+        assert_eq!(summary(&report), ("mapping-symbols", 16, 16, 16));
+        let tables: Vec<_> = inline_jump_tables(&buf).unwrap().into_iter()
+            .map(|t| (t.address, t.size, t.entry_size))
+            .collect();
+        assert_eq!(tables, vec![(start + 8, 16, Some(8))]);
+    }
+
+    #[test]
+    fn data_symbols_without_mapping_symbols() {
+        let buf = Elf::object()
+            .section(".text", SHT_PROGBITS, CODE, &[0; 32])
+            .symbol("f", STT_FUNC, ".text", 0, 24)
+            .symbol("pool", STT_OBJECT, ".text", 24, 8)
+            .build();
+        assert_eq!(summary(&data_in_code(&buf).unwrap()), ("symbols", 24, 0, 8));
+
+        let buf = Elf::object().section(".text", SHT_PROGBITS, CODE, &[0; 32]).build();
+        let report = data_in_code(&buf).unwrap();
+        assert_eq!(summary(&report), ("none", 32, 0, 0));
+        assert_eq!(report.sections[0].purity, 1.0);
+    }
+
+    #[test]
+    fn mach_o_data_in_code_entries() {
+        // Data at 8, and a table of 32-bit offsets at 16.
+        let mut entries = Vec::new();
+        for &(offset, length, kind) in &[(8u32, 4u16, DICE_KIND_DATA), (16, 8, 4)] {
+            entries.extend_from_slice(&offset.to_le_bytes());
+            entries.extend_from_slice(&length.to_le_bytes());
+            entries.extend_from_slice(&kind.to_le_bytes());
+        }
+        let buf = MachO::executable()
+            .section("__TEXT", "__text", S_ATTR_PURE_INSTRUCTIONS, &[0; 32])
+            .linkedit_data(LC_DATA_IN_CODE, &entries)
+            .build();
+        let report = data_in_code(&buf).unwrap();
+        assert_eq!(summary(&report), ("LC_DATA_IN_CODE", 20, 8, 4));
+        let mach = MachOFile::parse(&buf, 0).unwrap();
+        let text = mach.segments[0].vmaddr;
+        let tables: Vec<_> = inline_jump_tables(&buf).unwrap().into_iter()
+            .map(|t| (t.address - text, t.size, t.entry_size))
+            .collect();
+        assert_eq!(tables, vec![(16, 8, Some(4))]);
+    }
+
+    #[test]
+    fn data_is_capped_at_the_section_size() {
+        let section = CodeSection::new(".text".to_string(), 16, 12, 8);
+        assert_eq!((section.instructions, section.jump_tables, section.literals, section.data),
+                   (0, 12, 4, 16));
+        assert_eq!(section.purity, 0.0);
+    }
+}
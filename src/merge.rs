@@ -0,0 +1,202 @@
+use failure::Error;
+use goblin::elf::section_header::{SHF_MERGE, SHF_STRINGS};
+use goblin::mach::constants::{S_16BYTE_LITERALS, S_4BYTE_LITERALS, S_8BYTE_LITERALS};
+use goblin::mach::constants::{SECTION_TYPE, S_CSTRING_LITERALS};
+use goblin::mach::Mach;
+use goblin::Object;
+use std::collections::HashMap;
+use map_mach_name;
+
+/// How well the linker merged the entries of a mergeable string or constant section.
+#[derive(Clone, Debug, Serialize)]
+pub struct MergeStats {
+    pub section: String,
+    pub size: u64,
+    /// The size of each constant, or of each character for string sections.
+    pub entry_size: u64,
+    /// Whether the section holds NUL-terminated strings rather than fixed-size constants.
+    pub strings: bool,
+    pub entries: u64,
+    pub unique: u64,
+    /// The bytes taken up by entries that are copies of an earlier entry.
+    pub duplicate_bytes: u64,
+    /// The bytes taken up by unique strings that are a suffix of another string, and so could
+    /// share its storage (tail merging, e.g. lld's `-O2`).
+    pub tail_mergeable_bytes: u64,
+}
+
+impl MergeStats {
+    /// Whether the section looks like it has been merged: no entry appears more than once.
+    pub fn merged(&self) -> bool {
+        self.duplicate_bytes == 0
+    }
+
+    /// The bytes that fully merging the section would save.
+    pub fn savings(&self) -> u64 {
+        self.duplicate_bytes + self.tail_mergeable_bytes
+    }
+}
+
+/// Split a string section into its strings (without terminators), skipping empty strings, which
+/// are usually alignment padding.
+pub fn split_strings(data: &[u8], char_size: usize) -> Vec<&[u8]> {
+    let mut strings = Vec::new();
+    let mut start = 0;
+    let mut pos = 0;
+    while pos + char_size <= data.len() {
+        if data[pos..pos + char_size].iter().all(|&b| b == 0) {
+            if pos > start {
+                strings.push(&data[start..pos]);
+            }
+            start = pos + char_size;
+        }
+        pos += char_size;
+    }
+    if start < data.len() {
+        strings.push(&data[start..]);
+    }
+    strings
+}
+
+/// Compute merge statistics for one section's contents.
+fn stats(section: String, data: &[u8], entry_size: u64, strings: bool) -> MergeStats {
+    let entries: Vec<&[u8]> = if strings {
+        split_strings(data, entry_size as usize)
+    } else {
+        data.chunks(entry_size as usize).collect()
+    };
+    let terminator = if strings { entry_size } else { 0 };
+
+    let mut counts: HashMap<&[u8], u64> = HashMap::new();
+    for e in &entries {
+        *counts.entry(e).or_insert(0) += 1;
+    }
+    let duplicate_bytes = counts.iter()
+        .map(|(e, &n)| (e.len() as u64 + terminator) * (n - 1))
+        .sum();
+
+    // A string is a suffix of another exactly when its reverse is a prefix of the other's
+    // reverse, and after sorting the reversed strings, that other string sorts right after it.
+    let mut tail_mergeable_bytes = 0;
+    if strings {
+        let mut reversed: Vec<Vec<u8>> = counts.keys().map(|e| {
+            let mut chars: Vec<&[u8]> = e.chunks(entry_size as usize).collect();
+            chars.reverse();
+            chars.concat()
+        }).collect();
+        reversed.sort();
+        for pair in reversed.windows(2) {
+            if pair[1].starts_with(&pair[0]) {
+                tail_mergeable_bytes += pair[0].len() as u64 + terminator;
+            }
+        }
+    }
+
+    MergeStats {
+        section,
+        size: data.len() as u64,
+        entry_size,
+        strings,
+        entries: entries.len() as u64,
+        unique: counts.len() as u64,
+        duplicate_bytes,
+        tail_mergeable_bytes,
+    }
+}
+
+/// Parse `buf` as an object file and compute merge statistics for each of its mergeable string
+/// and constant sections: ELF sections flagged `SHF_MERGE`, and Mach-O literal sections.
+pub fn merge_stats(buf: &[u8]) -> Result<Vec<MergeStats>, Error> {
+    let mut vec = Vec::new();
+    match Object::parse(buf)? {
+        Object::Elf(elf) => {
+            for sh in &elf.section_headers {
+                // Linkers sometimes keep the merge flags on output sections that combine
+                // mergeable and ordinary input sections, but then clear the entry size.
+                if (sh.sh_flags & SHF_MERGE as u64) == 0 || sh.sh_entsize == 0 {
+                    continue;
+                }
+                let name = match elf.shdr_strtab.get(sh.sh_name).and_then(|res| res.ok()) {
+                    Some(name) => name.to_string(),
+                    None => continue,
+                };
+                let start = sh.sh_offset as usize;
+                if let Some(data) = buf.get(start..start + sh.sh_size as usize) {
+                    let strings = (sh.sh_flags & SHF_STRINGS as u64) != 0;
+                    vec.push(stats(name, data, sh.sh_entsize, strings));
+                }
+            }
+        },
+        Object::Mach(Mach::Binary(mach)) => {
+            for (sec, data) in mach.segments.sections().flatten().filter_map(|s| s.ok()) {
+                let (entry_size, strings) = match sec.flags & SECTION_TYPE {
+                    S_CSTRING_LITERALS => (1, true),
+                    S_4BYTE_LITERALS => (4, false),
+                    S_8BYTE_LITERALS => (8, false),
+                    S_16BYTE_LITERALS => (16, false),
+                    _ => continue,
+                };
+                let name = map_mach_name(sec.segname()?, sec.name()?);
+                vec.push(stats(name, data, entry_size, strings));
+            }
+        },
+        Object::Mach(Mach::Fat(_)) => bail!("A universal binary is analyzed a slice at a time"),
+        Object::PE(_) => {},
+        _ => bail!("Unhandled file type!"),
+    }
+    Ok(vec)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::elf::section_header::{SHF_ALLOC, SHT_PROGBITS};
+    use testelf::Elf;
+
+    #[test]
+    fn split_strings_skips_padding() {
+        assert_eq!(split_strings(b"ab\0\0c\0d", 1), vec![&b"ab"[..], b"c", b"d"]);
+        assert_eq!(split_strings(b"a\0b\0\0\0c\0", 2), vec![&b"a\0b\0"[..], b"c\0"]);
+    }
+
+    #[test]
+    fn stats_counts_duplicates_and_tails() {
+        let s = stats(".rodata.str".to_string(), b"hello\0lo\0hello\0jello\0", 1, true);
+        assert_eq!((s.entries, s.unique), (4, 3));
+        assert_eq!(s.duplicate_bytes, 6);
+        // "lo" can share the storage of "hello" or "jello".
+        assert_eq!(s.tail_mergeable_bytes, 3);
+        assert!(!s.merged());
+        assert_eq!(s.savings(), 9);
+
+        let s = stats(".rodata.cst4".to_string(), &[1, 0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0], 4,
+                      false);
+        assert_eq!((s.entries, s.unique, s.duplicate_bytes, s.tail_mergeable_bytes),
+                   (3, 2, 4, 0));
+    }
+
+    #[test]
+    fn universal_binary_is_an_error() {
+        let fat = [0xca, 0xfe, 0xba, 0xbe, 0, 0, 0, 1, 1, 0, 0, 7, 0, 0, 0, 3,
+                   0, 0, 0, 28, 0, 0, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0];
+        assert!(merge_stats(&fat).is_err());
+    }
+
+    #[test]
+    fn stats_of_mergeable_elf_sections() {
+        let merge = SHF_ALLOC | SHF_MERGE;
+        let buf = Elf::object()
+            .merge_section(".rodata.str1.1", SHT_PROGBITS, merge | SHF_STRINGS, 1, b"a\0ba\0a\0")
+            .merge_section(".rodata.cst8", SHT_PROGBITS, merge, 8, &[0; 16])
+            // Left unmerged: the linker cleared the entry size.
+            .merge_section(".rodata", SHT_PROGBITS, merge, 0, &[0; 16])
+            .section(".text", SHT_PROGBITS, SHF_ALLOC, &[0; 16])
+            .build();
+        let stats: Vec<_> = merge_stats(&buf).unwrap().into_iter()
+            .map(|s| (s.section, s.entries, s.unique, s.duplicate_bytes, s.tail_mergeable_bytes))
+            .collect();
+        assert_eq!(stats, vec![(".rodata.str1.1".to_string(), 3, 2, 2, 2),
+                               (".rodata.cst8".to_string(), 2, 1, 8, 0)]);
+    }
+}
+
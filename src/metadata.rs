@@ -0,0 +1,467 @@
+use arch::arch;
+use archive;
+use coff;
+use failure::Error;
+use goblin::elf::Elf;
+use goblin::mach::load_command::CommandVariant;
+use goblin::mach::{Mach, MachO};
+use goblin::Object;
+use linkmap;
+use saved;
+use signature::Signature;
+use te;
+use wasm;
+
+/// `LC_BUILD_VERSION`, which goblin doesn't parse yet.
+const LC_BUILD_VERSION: u32 = 0x32;
+
+/// The smallest PE timestamp that's flagged as a likely link time (2010-01-01).
+const PLAUSIBLE_PE_TIMESTAMP: u32 = 1_262_304_000;
+
+/// Facts about an object file that are worth recording alongside its sizes.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Metadata {
+    pub format: &'static str,
+    pub arch: String,
+    /// The entry point address (relative to the image base for PE).
+    pub entry: Option<u64>,
+    /// The ELF `EI_OSABI` header field.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub os_abi: Option<&'static str>,
+    pub os: Option<String>,
+    pub min_os_version: Option<String>,
+    pub linker: Option<String>,
+    /// The subsystem of PE and TE images, like `windows-gui` or `efi-application`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subsystem: Option<&'static str>,
+    /// The address that PE and TE images are linked to load at.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image_base: Option<u64>,
+    /// The size of the headers of PE and TE images, which are loaded with the sections.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub headers_size: Option<u64>,
+    pub reproducibility: Reproducibility,
+    /// The code signature, with `--signature`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<Signature>,
+}
+
+/// Fields that identify a particular build, for checking whether builds are reproducible.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Reproducibility {
+    /// The PE link timestamp; zero, or a content hash, for reproducible builds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<u32>,
+    /// The ELF `.note.gnu.build-id`, in hex.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub build_id: Option<String>,
+    /// The Mach-O `LC_UUID`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uuid: Option<String>,
+    /// Strings that look like the expansion of C's `__DATE__` and `__TIME__` macros.
+    pub build_dates: Vec<String>,
+    /// Fields that likely differ between two otherwise identical builds.
+    pub non_reproducible: Vec<String>,
+}
+
+/// The name of an ELF `EI_OSABI` value.
+fn os_abi_name(os_abi: u8) -> &'static str {
+    match os_abi {
+        0 => "none",
+        1 => "hpux",
+        2 => "netbsd",
+        3 => "gnu",
+        6 => "solaris",
+        7 => "aix",
+        8 => "irix",
+        9 => "freebsd",
+        12 => "openbsd",
+        97 => "arm",
+        255 => "standalone",
+        _ => "unknown",
+    }
+}
+
+/// The name of a PE subsystem, as TE images also record it.
+fn subsystem_name(subsystem: u16) -> &'static str {
+    match subsystem {
+        1 => "native",
+        2 => "windows-gui",
+        3 => "windows-cui",
+        5 => "os2-cui",
+        7 => "posix-cui",
+        8 => "native-windows",
+        9 => "windows-ce-gui",
+        10 => "efi-application",
+        11 => "efi-boot-service-driver",
+        12 => "efi-runtime-driver",
+        13 => "efi-rom",
+        14 => "xbox",
+        16 => "windows-boot-application",
+        _ => "unknown",
+    }
+}
+
+/// The OS that images of the PE subsystem `subsystem` run on.
+fn subsystem_os(subsystem: u16) -> &'static str {
+    match subsystem {
+        10..=13 => "uefi",
+        _ => "windows",
+    }
+}
+
+/// Read a 32-bit number at `offset` in `buf`.
+fn read_u32(buf: &[u8], offset: usize, little_endian: bool) -> Option<u32> {
+    let bytes = buf.get(offset..offset + 4)?;
+    let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+    Some(if little_endian { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) })
+}
+
+/// Format `bytes` as lowercase hex.
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Format a Mach-O UUID in the usual 8-4-4-4-12 form.
+pub fn uuid(bytes: &[u8; 16]) -> String {
+    format!("{}-{}-{}-{}-{}", hex(&bytes[..4]), hex(&bytes[4..6]), hex(&bytes[6..8]),
+            hex(&bytes[8..10]), hex(&bytes[10..])).to_uppercase()
+}
+
+const MONTHS: &[&[u8]] = &[
+    b"Jan", b"Feb", b"Mar", b"Apr", b"May", b"Jun", b"Jul", b"Aug", b"Sep", b"Oct", b"Nov", b"Dec",
+];
+
+/// Whether `s` matches `pattern`, where `d` stands for a digit and `_` for a digit or space.
+fn matches_pattern(s: &[u8], pattern: &[u8]) -> bool {
+    s.len() == pattern.len() && s.iter().zip(pattern).all(|(&c, &p)| match p {
+        b'd' => c.is_ascii_digit(),
+        b'_' => c.is_ascii_digit() || c == b' ',
+        _ => c == p,
+    })
+}
+
+/// Find strings in `buf` that look like `__DATE__` ("Jan  1 2024"), optionally followed by
+/// `__TIME__` ("12:34:56"), which change with every build. At most `limit` distinct strings
+/// are returned.
+fn build_dates(buf: &[u8], limit: usize) -> Vec<String> {
+    let mut dates: Vec<String> = Vec::new();
+    for (i, window) in buf.windows(11).enumerate() {
+        if !MONTHS.contains(&&window[..3]) || !matches_pattern(&window[3..], b" _d dddd") {
+            continue;
+        }
+        // The year must start with 19 or 20, and the date must not be part of a longer word.
+        if !(window[7..9] == *b"19" || window[7..9] == *b"20") ||
+            (i > 0 && buf[i - 1].is_ascii_alphanumeric()) {
+            continue;
+        }
+        let mut len = 11;
+        if let Some(time) = buf.get(i + 12..i + 20) {
+            if matches_pattern(time, b"dd:dd:dd") {
+                len = 20;
+            }
+        }
+        let date = String::from_utf8_lossy(&buf[i..i + len]).into_owned();
+        if !dates.contains(&date) {
+            dates.push(date);
+            if dates.len() == limit {
+                break;
+            }
+        }
+    }
+    dates
+}
+
+/// The contents of the ELF section called `name`.
+fn elf_section<'a>(elf: &Elf, buf: &'a [u8], name: &str) -> Option<&'a [u8]> {
+    let sh = elf.section_headers.iter()
+        .find(|sh| elf.shdr_strtab.get(sh.sh_name).and_then(|res| res.ok()) == Some(name))?;
+    buf.get(sh.sh_offset as usize..(sh.sh_offset + sh.sh_size) as usize)
+}
+
+/// The descriptor of the first note in a note section, if it has owner `owner`.
+fn note_desc<'a>(data: &'a [u8], owner: &[u8], little_endian: bool) -> Option<&'a [u8]> {
+    let namesz = read_u32(data, 0, little_endian)? as usize;
+    let descsz = read_u32(data, 4, little_endian)? as usize;
+    let name = data.get(12..12 + namesz)?;
+    if name.split(|&b| b == 0).next() != Some(owner) {
+        return None;
+    }
+    let desc = 12 + namesz.div_ceil(4) * 4;
+    data.get(desc..desc + descsz)
+}
+
+fn elf_metadata(elf: &Elf, buf: &[u8], metadata: &mut Metadata) {
+    let le = elf.little_endian;
+    metadata.format = "elf";
+    metadata.entry = Some(elf.header.e_entry);
+    metadata.os_abi = Some(os_abi_name(elf.header.e_ident[7]));
+
+    if let Some(desc) = elf_section(elf, buf, ".note.ABI-tag")
+        .and_then(|data| note_desc(data, b"GNU", le)) {
+        metadata.os = read_u32(desc, 0, le).map(|os| match os {
+            0 => "linux",
+            1 => "hurd",
+            2 => "solaris",
+            3 => "freebsd",
+            _ => "unknown",
+        }.to_string());
+        let version: Option<Vec<String>> = (1..4)
+            .map(|i| read_u32(desc, i * 4, le).map(|v| v.to_string()))
+            .collect();
+        metadata.min_os_version = version.map(|v| v.join("."));
+    } else if let Some(desc) = elf_section(elf, buf, ".note.android.ident")
+        .and_then(|data| note_desc(data, b"Android", le)) {
+        metadata.os = Some("android".to_string());
+        metadata.min_os_version = read_u32(desc, 0, le).map(|api| format!("API {}", api));
+    }
+
+    // Linkers that identify themselves do so in `.comment`, next to the compilers.
+    if let Some(comment) = elf_section(elf, buf, ".comment") {
+        metadata.linker = comment.split(|&b| b == 0)
+            .map(String::from_utf8_lossy)
+            .find_map(|s| if let Some(i) = s.find("Linker: ") {
+                Some(s[i + "Linker: ".len()..].trim().to_string())
+            } else if s.starts_with("mold ") || s.starts_with("GNU gold") {
+                Some(s.trim().to_string())
+            } else {
+                None
+            });
+    }
+
+    metadata.reproducibility.build_id = elf_section(elf, buf, ".note.gnu.build-id")
+        .and_then(|data| note_desc(data, b"GNU", le))
+        .map(hex);
+}
+
+/// Format a Mach-O version number, encoded in nibbles as xxxx.yy.zz.
+fn mach_version(version: u32) -> String {
+    format!("{}.{}.{}", version >> 16, (version >> 8) & 0xff, version & 0xff)
+}
+
+/// The name of an `LC_BUILD_VERSION` platform.
+fn mach_platform(platform: u32) -> &'static str {
+    match platform {
+        1 => "macos",
+        2 => "ios",
+        3 => "tvos",
+        4 => "watchos",
+        5 => "bridgeos",
+        6 => "maccatalyst",
+        7 => "ios-simulator",
+        8 => "tvos-simulator",
+        9 => "watchos-simulator",
+        10 => "driverkit",
+        11 => "visionos",
+        12 => "visionos-simulator",
+        _ => "unknown",
+    }
+}
+
+fn mach_metadata(mach: &MachO, buf: &[u8], metadata: &mut Metadata) {
+    let le = mach.little_endian;
+    metadata.format = "mach-o";
+    metadata.entry = if mach.entry != 0 { Some(mach.entry) } else { None };
+    for lc in &mach.load_commands {
+        match lc.command {
+            CommandVariant::Uuid(ref cmd) => {
+                metadata.reproducibility.uuid = Some(uuid(&cmd.uuid));
+            },
+            CommandVariant::IdDylib(ref cmd) => {
+                // ld64 writes 1 here unless told otherwise.
+                let timestamp = cmd.dylib.timestamp;
+                if timestamp > 1 {
+                    metadata.reproducibility.non_reproducible.push(format!(
+                        "LC_ID_DYLIB timestamp {}", timestamp));
+                }
+            },
+            CommandVariant::VersionMinMacosx(ref cmd) => {
+                metadata.os = Some("macos".to_string());
+                metadata.min_os_version = Some(mach_version(cmd.version));
+            },
+            CommandVariant::VersionMinIphoneos(ref cmd) => {
+                metadata.os = Some("ios".to_string());
+                metadata.min_os_version = Some(mach_version(cmd.version));
+            },
+            CommandVariant::Unimplemented(ref header) if header.cmd == LC_BUILD_VERSION => {
+                let field = |i: usize| read_u32(buf, lc.offset + 8 + i * 4, le);
+                metadata.os = field(0).map(|p| mach_platform(p).to_string());
+                metadata.min_os_version = field(1).map(mach_version);
+                // The tools that built the binary follow the fixed fields as (tool, version)
+                // pairs; ld64 is tool 3 and lld is tool 4.
+                for i in 0..field(3).unwrap_or(0) as usize {
+                    let name = match field(4 + i * 2) {
+                        Some(3) => "ld64",
+                        Some(4) => "lld",
+                        _ => continue,
+                    };
+                    if let Some(version) = field(5 + i * 2) {
+                        metadata.linker = Some(format!("{} {}", name, mach_version(version)));
+                    }
+                }
+            },
+            _ => {},
+        }
+    }
+}
+
+/// Parse `buf` as an object file and collect its header metadata: format, architecture, entry
+/// point, target OS and minimum OS version, the linker that produced it, where recorded, and
+/// the fields that identify the build.
+pub fn metadata(buf: &[u8]) -> Result<Metadata, Error> {
+    let mut metadata = Metadata {
+        arch: arch(buf)?,
+        ..Default::default()
+    };
+    metadata.reproducibility.build_dates = build_dates(buf, 10);
+    for date in &metadata.reproducibility.build_dates {
+        metadata.reproducibility.non_reproducible.push(format!("embedded build date {:?}", date));
+    }
+    if wasm::is_wasm(buf) {
+        metadata.format = "wasm";
+        return Ok(metadata);
+    }
+    if archive::is_archive(buf) {
+        metadata.format = "archive";
+        return Ok(metadata);
+    }
+    if coff::is_coff(buf) {
+        metadata.format = "coff";
+        metadata.os = Some("windows".to_string());
+        return Ok(metadata);
+    }
+    if let Some(header) = te::Header::parse(buf) {
+        metadata.format = "te";
+        metadata.entry = Some(header.entry);
+        metadata.os = Some(subsystem_os(header.subsystem).to_string());
+        metadata.subsystem = Some(subsystem_name(header.subsystem));
+        metadata.image_base = Some(header.image_base);
+        metadata.headers_size = Some(header.headers_size());
+        return Ok(metadata);
+    }
+    if linkmap::is_map(buf) {
+        metadata.format = "map";
+        metadata.linker = linkmap::linker(buf).map(str::to_string);
+        return Ok(metadata);
+    }
+    if saved::is_report(buf) {
+        metadata.format = "report";
+        return Ok(metadata);
+    }
+    match Object::parse(buf)? {
+        Object::Elf(elf) => elf_metadata(&elf, buf, &mut metadata),
+        Object::PE(pe) => {
+            metadata.format = "pe";
+            metadata.entry = Some(pe.entry as u64);
+            metadata.os = Some("windows".to_string());
+            let timestamp = pe.header.coff_header.time_date_stamp;
+            metadata.reproducibility.timestamp = Some(timestamp);
+            // With `/Brepro`, link.exe and lld-link store a hash of the contents instead, which
+            // there's no telling apart from a time, so this only flags plausible recent times.
+            if timestamp >= PLAUSIBLE_PE_TIMESTAMP {
+                metadata.reproducibility.non_reproducible.push(format!(
+                    "PE timestamp {}", timestamp));
+            }
+            if let Some(hdr) = pe.header.optional_header {
+                let (standard, windows) = (hdr.standard_fields, hdr.windows_fields);
+                metadata.min_os_version = Some(format!("{}.{}",
+                    windows.major_operating_system_version,
+                    windows.minor_operating_system_version));
+                metadata.linker = Some(format!("{}.{}", standard.major_linker_version,
+                                               standard.minor_linker_version));
+                metadata.os = Some(subsystem_os(windows.subsystem).to_string());
+                metadata.subsystem = Some(subsystem_name(windows.subsystem));
+                metadata.image_base = Some(windows.image_base);
+                metadata.headers_size = Some(u64::from(windows.size_of_headers));
+            }
+        },
+        Object::Mach(Mach::Binary(mach)) => mach_metadata(&mach, buf, &mut metadata),
+        Object::Mach(Mach::Fat(_)) => metadata.format = "mach-o-fat",
+        Object::Archive(_) => metadata.format = "archive",
+        Object::Unknown(magic) => bail!("Unknown file magic: {:#x}", magic),
+    }
+    Ok(metadata)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::elf::section_header::{SHF_ALLOC, SHF_EXECINSTR, SHT_NOTE, SHT_PROGBITS};
+    use testelf::{Elf, BASE};
+
+    fn note(owner: &[u8], kind: u32, desc: &[u8]) -> Vec<u8> {
+        let mut note = Vec::new();
+        for field in &[owner.len() as u32 + 1, desc.len() as u32, kind] {
+            note.extend_from_slice(&field.to_le_bytes());
+        }
+        note.extend_from_slice(owner);
+        note.push(0);
+        while note.len() % 4 != 0 {
+            note.push(0);
+        }
+        note.extend_from_slice(desc);
+        note
+    }
+
+    #[test]
+    fn elf_target_and_linker() {
+        let abi: Vec<u8> = [0u32, 3, 2, 0].iter().flat_map(|v| v.to_le_bytes()).collect();
+        let buf = Elf::executable()
+            .section(".note.ABI-tag", SHT_NOTE, SHF_ALLOC, &note(b"GNU", 1, &abi))
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &[0xc3])
+            .section(".comment", SHT_PROGBITS, 0, b"GCC: (GNU) 13.2.0\0Linker: LLD 17.0.6\0")
+            .build();
+        let elf = metadata(&buf).unwrap();
+        assert_eq!((elf.format, elf.arch.as_str()), ("elf", "x86_64"));
+        assert_eq!(elf.os_abi, Some("none"));
+        assert_eq!(elf.os.as_deref(), Some("linux"));
+        assert_eq!(elf.min_os_version.as_deref(), Some("3.2.0"));
+        assert_eq!(elf.linker.as_deref(), Some("LLD 17.0.6"));
+        assert!(elf.entry.unwrap() > BASE);
+
+        let android = note(b"Android", 1, &34u32.to_le_bytes());
+        let buf = Elf::object()
+            .section(".note.android.ident", SHT_NOTE, SHF_ALLOC, &android)
+            .section(".comment", SHT_PROGBITS, 0, b"mold 2.4.0 (compatible with GNU ld)\0")
+            .build();
+        let elf = metadata(&buf).unwrap();
+        assert_eq!(elf.os.as_deref(), Some("android"));
+        assert_eq!(elf.min_os_version.as_deref(), Some("API 34"));
+        assert_eq!(elf.linker.as_deref(), Some("mold 2.4.0 (compatible with GNU ld)"));
+    }
+
+    #[test]
+    fn names_of_numbers() {
+        assert_eq!(mach_version(0x000e_0201), "14.2.1");
+        assert_eq!(mach_platform(2), "ios");
+        assert_eq!((os_abi_name(3), os_abi_name(200)), ("gnu", "unknown"));
+        assert_eq!((subsystem_name(10), subsystem_os(10)), ("efi-application", "uefi"));
+        assert_eq!((subsystem_name(3), subsystem_os(3)), ("windows-cui", "windows"));
+        // Notes whose owner doesn't match, or that run past the section, have no descriptor.
+        let gnu = note(b"GNU", 3, &[1, 2, 3, 4]);
+        assert_eq!(note_desc(&gnu, b"GNU", true), Some(&[1, 2, 3, 4][..]));
+        assert_eq!(note_desc(&gnu, b"Go", true), None);
+        assert_eq!(note_desc(&gnu[..gnu.len() - 1], b"GNU", true), None);
+    }
+
+    #[test]
+    fn build_ids_and_dates() {
+        let buf = Elf::object()
+            .section(".note.gnu.build-id", SHT_NOTE, SHF_ALLOC, &note(b"GNU", 3, &[0xab, 0xcd, 1]))
+            .section(".rodata", SHT_PROGBITS, SHF_ALLOC,
+                     b"built Mar  7 2024 09:15:02\0Jan 12 1999\0xOct 10 2020\0Feb 10 1850\0")
+            .build();
+        let reproducibility = metadata(&buf).unwrap().reproducibility;
+        assert_eq!(reproducibility.build_id.as_deref(), Some("abcd01"));
+        assert_eq!(reproducibility.build_dates, vec!["Mar  7 2024 09:15:02", "Jan 12 1999"]);
+        assert_eq!(reproducibility.non_reproducible,
+                   vec!["embedded build date \"Mar  7 2024 09:15:02\"",
+                        "embedded build date \"Jan 12 1999\""]);
+
+        let dates = build_dates(b"Jan  1 2000 Jan  1 2000 Feb  2 2001 Mar  3 2002", 2);
+        assert_eq!(dates, vec!["Jan  1 2000", "Feb  2 2001"]);
+        assert_eq!(uuid(&[0x12, 0x34, 0x56, 0x78, 0x9a, 0xbc, 0xde, 0xf0, 0, 1, 2, 3, 4, 5, 6, 7]),
+                   "12345678-9ABC-DEF0-0001-020304050607");
+    }
+}
+
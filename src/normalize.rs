@@ -0,0 +1,151 @@
+/// Normalized names for sections with exactly these names, across ELF, Mach-O (after
+/// `map_mach_name`), PE and wasm.
+const NAMES: &[(&str, &str)] = &[
+    (".text", "text"),
+    (".init", "text"),
+    (".fini", "text"),
+    ("code", "text"),
+    (".rodata", "rodata"),
+    (".rodata1", "rodata"),
+    (".rdata", "rodata"),
+    ("_RDATA", "rodata"),
+    (".cstring", "rodata"),
+    ("__literal4", "rodata"),
+    ("__literal8", "rodata"),
+    ("__literal16", "rodata"),
+    ("__ustring", "rodata"),
+    ("__const", "relro"),
+    (".data", "data"),
+    (".data1", "data"),
+    ("data", "data"),
+    (".data.rel.ro", "relro"),
+    (".bss", "bss"),
+    ("__common", "bss"),
+    (".tdata", "tls"),
+    (".tbss", "tls"),
+    (".tls", "tls"),
+    ("__thread_vars", "tls"),
+    ("__thread_data", "tls"),
+    ("__thread_bss", "tls"),
+    (".eh_frame", "unwind"),
+    (".eh_frame_hdr", "unwind"),
+    (".ARM.exidx", "unwind"),
+    (".ARM.extab", "unwind"),
+    ("__unwind_info", "unwind"),
+    ("__eh_frame", "unwind"),
+    (".pdata", "unwind"),
+    (".xdata", "unwind"),
+    (".gcc_except_table", "exception-tables"),
+    ("__gcc_except_tab", "exception-tables"),
+    (".got", "got"),
+    (".got.plt", "got"),
+    ("__got", "got"),
+    ("__auth_got", "got"),
+    ("__la_symbol_ptr", "got"),
+    ("__nl_symbol_ptr", "got"),
+    (".plt", "plt"),
+    (".plt.got", "plt"),
+    (".plt.sec", "plt"),
+    (".iplt", "plt"),
+    ("__stubs", "plt"),
+    ("__stub_helper", "plt"),
+    ("__auth_stubs", "plt"),
+    (".reloc", "relocations"),
+    (".dynamic", "dynamic"),
+    (".interp", "dynamic"),
+    (".dynsym", "dynamic-symbols"),
+    (".dynstr", "dynamic-symbols"),
+    (".hash", "dynamic-symbols"),
+    (".gnu.hash", "dynamic-symbols"),
+    (".gnu.version", "dynamic-symbols"),
+    (".gnu.version_r", "dynamic-symbols"),
+    (".gnu.version_d", "dynamic-symbols"),
+    (".idata", "imports"),
+    ("import", "imports"),
+    (".edata", "exports"),
+    ("export", "exports"),
+    ("export_table", "exports"),
+    (".init_array", "init-array"),
+    (".fini_array", "init-array"),
+    (".preinit_array", "init-array"),
+    (".ctors", "init-array"),
+    (".dtors", "init-array"),
+    (".CRT", "init-array"),
+    ("__mod_init_func", "init-array"),
+    ("__mod_term_func", "init-array"),
+    ("__init_offsets", "init-array"),
+    (".symtab", "symbols"),
+    ("symtab", "symbols"),
+    ("symtab_local", "symbols"),
+    ("symtab_external", "symbols"),
+    ("symtab_undefined", "symbols"),
+    ("symtab_strings", "symbols"),
+    ("indirect_symtab", "dynamic-symbols"),
+    (".strtab", "symbols"),
+    ("name", "symbols"),
+    (".comment", "metadata"),
+    (".shstrtab", "metadata"),
+    ("producers", "metadata"),
+    ("target_features", "metadata"),
+    ("type", "metadata"),
+    ("function", "metadata"),
+    ("table", "metadata"),
+    ("memory", "metadata"),
+    ("global", "metadata"),
+    ("element", "metadata"),
+    ("start", "metadata"),
+    ("datacount", "metadata"),
+    ("tag", "metadata"),
+    (".rsrc", "resources"),
+];
+
+/// Normalized names for families of sections, by name prefix, when there is no exact match.
+const PREFIXES: &[(&str, &str)] = &[
+    (".text.", "text"),
+    (".rodata.", "rodata"),
+    (".data.rel.ro.", "relro"),
+    (".data.", "data"),
+    (".bss.", "bss"),
+    (".tdata.", "tls"),
+    (".tbss.", "tls"),
+    (".rela.", "relocations"),
+    (".rel.", "relocations"),
+    (".note.", "metadata"),
+    (".debug_", "debug"),
+    (".zdebug_", "debug"),
+    ("__debug_", "debug"),
+    ("__objc_", "objc-metadata"),
+    ("__swift5_", "swift-metadata"),
+];
+
+/// Map a section name, as returned by `sections`, to a format-independent name (`text`,
+/// `rodata`, `unwind`, ...), so that reports for different platforms line up row by row.
+/// Sections without a known counterpart keep their own names.
+///
+/// Several sections can map to the same normalized name, e.g. `.eh_frame` and `.eh_frame_hdr`
+/// both become `unwind`; callers are expected to sum their sizes.
+pub fn normalize_name(name: &str) -> &str {
+    NAMES.iter()
+        .find(|&&(n, _)| n == name)
+        .or_else(|| PREFIXES.iter().find(|&&(prefix, _)| name.starts_with(prefix)))
+        .map(|&(_, normalized)| normalized)
+        .unwrap_or(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_line_up_across_formats() {
+        for &(name, normalized) in &[(".text", "text"), ("code", "text"), (".rdata", "rodata"),
+                                     ("__const", "relro"), (".data.rel.ro.local", "relro"),
+                                     (".data.rel.local", "data"), ("__stubs", "plt"),
+                                     (".debug_line", "debug"),
+                                     ("__objc_methname", "objc-metadata")] {
+            assert_eq!(normalize_name(name), normalized, "{}", name);
+        }
+        assert_eq!(normalize_name(".custom"), ".custom");
+        assert_eq!(normalize_name(".textual"), ".textual");
+    }
+}
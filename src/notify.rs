@@ -0,0 +1,106 @@
+use failure::Error;
+use serde::Serialize;
+use serde_json;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// How long to wait for a webhook to answer, in seconds.
+const TIMEOUT: &str = "30";
+
+/// `value` as a quoted string of a curl config file.
+fn quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// POST `payload` as JSON to the webhook at `url`, an http or https URL. This runs `curl`,
+/// which does TLS and honors the proxy settings of the environment, as CI runners need.
+///
+/// The URL and the body go to curl in a config on its standard input rather than on its
+/// command line, which other users can read, and errors leave out `url`, as webhook URLs
+/// often have their secret in them.
+pub fn post<T: Serialize>(url: &str, payload: &T) -> Result<(), Error> {
+    let body = String::from_utf8(serde_json::to_vec(payload)?)?;
+    let config = format!("url = {}\ndata-raw = {}\n", quote(url), quote(&body));
+    let mut curl = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--max-time", TIMEOUT])
+        .args(["--header", "Content-Type: application/json", "--config", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format_err!("running curl to post to the webhook: {}", e))?;
+    curl.stdin.take().unwrap().write_all(config.as_bytes())?;
+    let output = curl.wait_with_output()?;
+    if !output.status.success() {
+        bail!("posting to the webhook: {}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use std::process;
+
+    #[test]
+    fn config_strings() {
+        assert_eq!(quote(r#"{"a": "b\"c"}"#), r#""{\"a\": \"b\\\"c\"}""#);
+        assert_eq!(quote("x\ny"), r#""x\ny""#);
+    }
+
+    #[test]
+    fn posts_with_curl() {
+        // A curl that records its arguments and its config, and fails as `--fail` makes it for
+        // the URLs ending in `/fail`.
+        let dir = env::temp_dir().join(format!("rust-size-{}-curl", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let curl = dir.join("curl");
+        fs::write(&curl, format!("#!/bin/sh
+echo \"$@\" > {0}/args
+cat > {0}/config
+if grep -q '/fail\"' {0}/config; then
+    echo 'curl: (22) The requested URL returned error: 500' >&2
+    exit 22
+fi
+", dir.display())).unwrap();
+        fs::set_permissions(&curl, fs::Permissions::from_mode(0o755)).unwrap();
+        let path = env::var_os("PATH").unwrap_or_default();
+        let paths = Some(dir.clone()).into_iter().chain(env::split_paths(&path));
+        env::set_var("PATH", env::join_paths(paths).unwrap());
+        let run = |url: &str, payload: &[&str]| {
+            let result = post(url, &payload);
+            (result.map_err(|e| e.to_string()), fs::read_to_string(dir.join("args")).unwrap(),
+             fs::read_to_string(dir.join("config")).unwrap())
+        };
+        let sent = run("https://hooks.example.com/T000/secret", &["a\"b"]);
+        let failed = run("https://hooks.example.com/T000/fail", &[]);
+        env::set_var("PATH", path);
+        fs::remove_dir_all(&dir).unwrap();
+
+        let (result, args, config) = sent;
+        assert_eq!(result, Ok(()));
+        assert!(!args.contains("hooks.example.com"), "{}", args);
+        assert!(args.ends_with("--config -\n"), "{}", args);
+        assert_eq!(config, "url = \"https://hooks.example.com/T000/secret\"\n\
+                            data-raw = \"[\\\"a\\\\\\\"b\\\"]\"\n");
+        assert_eq!(failed.0,
+                   Err("posting to the webhook: curl: (22) The requested URL returned error: 500"
+                       .to_string()));
+    }
+}
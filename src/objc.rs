@@ -0,0 +1,311 @@
+use failure::Error;
+use goblin::mach::Mach;
+use goblin::Object;
+use std::collections::HashMap;
+
+/// A selector, with how many selector references point at it.
+#[derive(Clone, Debug, Serialize)]
+pub struct Selector {
+    pub name: String,
+    pub references: u64,
+}
+
+/// The Objective-C selectors of a Mach-O file, as emitted by `objc --format json`.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct SelectorReport {
+    /// The size of `__objc_methname`, which holds the selector (and method) names.
+    pub methname_size: u64,
+    pub selectors: u64,
+    pub unique: u64,
+    /// The bytes taken up by selector names that are copies of an earlier one.
+    pub duplicate_bytes: u64,
+    /// The number of entries in `__objc_selrefs`.
+    pub selrefs: u64,
+    /// The selector references to the same name as an earlier reference, which the linker
+    /// normally coalesces.
+    pub duplicate_selrefs: u64,
+    pub duplicate_selref_bytes: u64,
+    /// The selector references that don't point into `__objc_methname`, e.g. because they are
+    /// bound through relocations or chained fixups that aren't applied.
+    pub unresolved_selrefs: u64,
+    /// The names longer than the `--long` threshold, longest first.
+    pub long: Vec<Selector>,
+    pub long_bytes: u64,
+}
+
+impl SelectorReport {
+    /// The bytes that coalescing the duplicate names and references would save.
+    pub fn savings(&self) -> u64 {
+        self.duplicate_bytes + self.duplicate_selref_bytes
+    }
+}
+
+/// A CFString (or `@"..."` NSString) literal.
+#[derive(Clone, Debug, Serialize)]
+pub struct CFString {
+    pub value: String,
+    /// Whether the characters are stored as UTF-16 (in `__ustring`) rather than as bytes.
+    pub utf16: bool,
+    /// The size of the object and of the characters it points at.
+    pub size: u64,
+}
+
+/// The CFString literals of a Mach-O file.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct CFStringReport {
+    pub count: u64,
+    /// The size of `__cfstring`, which holds the string objects.
+    pub objects_size: u64,
+    /// The size of the characters the objects point at, in `__cstring` or `__ustring`. These
+    /// may be shared with identical C string literals.
+    pub storage_size: u64,
+    /// The objects whose characters couldn't be found, e.g. because they are bound through
+    /// relocations or chained fixups that aren't applied.
+    pub unresolved: u64,
+    /// The largest literals, largest first.
+    pub largest: Vec<CFString>,
+}
+
+/// The Objective-C and Foundation structures of a Mach-O file, as emitted by
+/// `objc --format json`.
+#[derive(Clone, Debug, Serialize)]
+pub struct ObjcReport {
+    pub selectors: SelectorReport,
+    pub cfstrings: CFStringReport,
+}
+
+/// The contents of the sections of a Mach-O file, to read what its pointers point at.
+struct Sections<'a> {
+    sections: Vec<(String, u64, &'a [u8])>,
+    pointer_size: usize,
+    little_endian: bool,
+}
+
+impl<'a> Sections<'a> {
+    /// The sections named `name`, with their addresses.
+    fn named(&self, name: &str) -> impl Iterator<Item = (u64, &'a [u8])> + '_ {
+        let name = name.to_string();
+        self.sections.iter().filter(move |s| s.0 == name).map(|s| (s.1, s.2))
+    }
+
+    /// The name of the section containing `addr`, and the contents from there on.
+    fn at(&self, addr: u64) -> Option<(&str, &'a [u8])> {
+        self.sections.iter()
+            .find(|s| addr >= s.1 && addr < s.1 + s.2.len() as u64)
+            .map(|s| (s.0.as_str(), &s.2[(addr - s.1) as usize..]))
+    }
+
+    /// Read the pointer-sized integer at the start of `data`.
+    fn pointer(&self, data: &[u8]) -> Option<u64> {
+        let chunk = data.get(..self.pointer_size)?;
+        let mut bytes = [0; 8];
+        Some(if self.little_endian {
+            bytes[..self.pointer_size].copy_from_slice(chunk);
+            u64::from_le_bytes(bytes)
+        } else {
+            bytes[8 - self.pointer_size..].copy_from_slice(chunk);
+            u64::from_be_bytes(bytes)
+        })
+    }
+}
+
+/// Analyze the Objective-C selectors and the CFString literals of the Mach-O file `buf`,
+/// listing the selectors longer than `long` bytes and the `top` largest literals.
+pub fn objc(buf: &[u8], long: usize, top: usize) -> Result<ObjcReport, Error> {
+    let mach = match Object::parse(buf)? {
+        Object::Mach(Mach::Binary(mach)) => mach,
+        Object::Mach(Mach::Fat(_)) => bail!("A universal binary is analyzed a slice at a time"),
+        _ => bail!("Not a Mach-O file"),
+    };
+    let mut sections = Vec::new();
+    for (sec, data) in mach.segments.sections().flatten().filter_map(|s| s.ok()) {
+        sections.push((sec.name()?.to_string(), sec.addr, data));
+    }
+    let sections = Sections {
+        sections,
+        pointer_size: if mach.is_64 { 8 } else { 4 },
+        little_endian: mach.little_endian,
+    };
+    Ok(ObjcReport {
+        selectors: selectors(&sections, long),
+        cfstrings: cfstrings(&sections, top),
+    })
+}
+
+/// Analyze `__objc_methname` and `__objc_selrefs`, listing the names longer than `long` bytes.
+fn selectors(sections: &Sections, long: usize) -> SelectorReport {
+    let (base, names) = match sections.named("__objc_methname").next() {
+        Some(methname) => methname,
+        None => return SelectorReport::default(),
+    };
+
+    // The names by their offset in the section, and the number of copies of each.
+    let mut offsets = HashMap::new();
+    let mut copies: HashMap<&[u8], u64> = HashMap::new();
+    let mut start = 0;
+    for (i, &b) in names.iter().enumerate() {
+        if b == 0 {
+            if i > start {
+                offsets.insert(start as u64, &names[start..i]);
+                *copies.entry(&names[start..i]).or_insert(0) += 1;
+            }
+            start = i + 1;
+        }
+    }
+    let mut report = SelectorReport {
+        methname_size: names.len() as u64,
+        selectors: offsets.len() as u64,
+        unique: copies.len() as u64,
+        duplicate_bytes: copies.iter().map(|(name, &n)| (name.len() as u64 + 1) * (n - 1)).sum(),
+        ..SelectorReport::default()
+    };
+
+    let pointer_size = sections.pointer_size;
+    let mut references: HashMap<&[u8], u64> = HashMap::new();
+    for (_, data) in sections.named("__objc_selrefs") {
+        for chunk in data.chunks_exact(pointer_size) {
+            report.selrefs += 1;
+            let pointer = sections.pointer(chunk).unwrap();
+            match pointer.checked_sub(base).and_then(|offset| offsets.get(&offset)) {
+                Some(name) => *references.entry(name).or_insert(0) += 1,
+                None => report.unresolved_selrefs += 1,
+            }
+        }
+    }
+    report.duplicate_selrefs = references.values().map(|n| n - 1).sum();
+    report.duplicate_selref_bytes = report.duplicate_selrefs * pointer_size as u64;
+
+    let mut long_names: Vec<&[u8]> = copies.keys().filter(|name| name.len() > long).cloned()
+        .collect();
+    long_names.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+    report.long_bytes = long_names.iter().map(|name| name.len() as u64 + 1).sum();
+    report.long = long_names.into_iter().map(|name| Selector {
+        name: String::from_utf8_lossy(name).into_owned(),
+        references: references.get(name).cloned().unwrap_or(0),
+    }).collect();
+    report
+}
+
+/// Analyze the objects in `__cfstring`, listing the `top` largest.
+fn cfstrings(sections: &Sections, top: usize) -> CFStringReport {
+    // Each object is an isa pointer, an int32 of flags (padded to a pointer), a pointer to the
+    // characters and a length.
+    let pointer_size = sections.pointer_size;
+    let object_size = 4 * pointer_size;
+    let mut report = CFStringReport::default();
+    for (_, data) in sections.named("__cfstring") {
+        report.objects_size += data.len() as u64;
+        for object in data.chunks_exact(object_size) {
+            report.count += 1;
+            let chars = sections.pointer(&object[2 * pointer_size..]).unwrap();
+            let length = sections.pointer(&object[3 * pointer_size..]).unwrap() as usize;
+            let (utf16, value) = match sections.at(chars) {
+                Some(("__ustring", data)) if data.len() >= 2 * length => {
+                    let units: Vec<u16> = data[..2 * length].chunks(2).map(|c| {
+                        if sections.little_endian {
+                            u16::from_le_bytes([c[0], c[1]])
+                        } else {
+                            u16::from_be_bytes([c[0], c[1]])
+                        }
+                    }).collect();
+                    (true, String::from_utf16_lossy(&units))
+                }
+                Some((_, data)) if data.len() >= length => {
+                    (false, String::from_utf8_lossy(&data[..length]).into_owned())
+                }
+                _ => {
+                    report.unresolved += 1;
+                    continue;
+                }
+            };
+            // The characters are NUL-terminated.
+            let storage = if utf16 { 2 * (length + 1) } else { length + 1 } as u64;
+            report.storage_size += storage;
+            report.largest.push(CFString { value, utf16, size: object_size as u64 + storage });
+        }
+    }
+    report.largest.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.value.cmp(&b.value)));
+    report.largest.truncate(top);
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use testelf::Elf;
+    use testmacho::MachO;
+
+    /// The addresses of the sections of the Mach-O file `buf`, by name.
+    fn addresses(buf: &[u8]) -> HashMap<String, u64> {
+        let mach = match Mach::parse(buf).unwrap() {
+            Mach::Binary(mach) => mach,
+            Mach::Fat(_) => unreachable!(),
+        };
+        mach.segments.sections().flatten().map(|s| s.unwrap().0)
+            .map(|sec| (sec.name().unwrap().to_string(), sec.addr))
+            .collect()
+    }
+
+    fn pointers(pointers: &[u64]) -> Vec<u8> {
+        pointers.iter().flat_map(|p| p.to_le_bytes().to_vec()).collect()
+    }
+
+    #[test]
+    fn duplicated_and_long_selectors() {
+        let methname = b"init\0init\0alloc\0aVeryLongSelectorName:withArgument:\0";
+        let build = |selrefs: &[u64]| {
+            MachO::executable()
+                .section("__TEXT", "__text", 0, &[0; 16])
+                .section("__TEXT", "__objc_methname", 0, methname)
+                .section("__DATA", "__objc_selrefs", 0, &pointers(selrefs))
+                .build()
+        };
+        // The addresses don't depend on the contents of the sections.
+        let base = addresses(&build(&[0; 5]))["__objc_methname"];
+        let buf = build(&[base, base, base + 5, base + 10, 0]);
+        let report = objc(&buf, 20, 10).unwrap().selectors;
+        assert_eq!((report.methname_size, report.selectors, report.unique, report.duplicate_bytes),
+                   (52, 4, 3, 5));
+        // Both copies of `init` count as references to it.
+        assert_eq!((report.selrefs, report.duplicate_selrefs, report.duplicate_selref_bytes,
+                    report.unresolved_selrefs), (5, 2, 16, 1));
+        assert_eq!(report.long.len(), 1);
+        assert_eq!((report.long[0].name.as_str(), report.long[0].references, report.long_bytes),
+                   ("aVeryLongSelectorName:withArgument:", 0, 36));
+        assert_eq!(report.savings(), 21);
+
+        let empty = objc(&MachO::executable().section("__TEXT", "__text", 0, &[0; 4]).build(),
+                         20, 10).unwrap();
+        assert_eq!((empty.selectors.selrefs, empty.selectors.methname_size), (0, 0));
+        assert!(objc(&Elf::object().build(), 20, 10).is_err());
+        let fat = [0xca, 0xfe, 0xba, 0xbe, 0, 0, 0, 1, 1, 0, 0, 7, 0, 0, 0, 3,
+                   0, 0, 0, 28, 0, 0, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0];
+        assert!(objc(&fat, 20, 10).is_err());
+    }
+
+    #[test]
+    fn cfstring_literals_and_their_characters() {
+        let ustring: Vec<u8> = "hé\0".encode_utf16().flat_map(|u| u.to_le_bytes().to_vec())
+            .collect();
+        let build = |objects: &[u64]| {
+            MachO::executable()
+                .section("__TEXT", "__text", 0, &[0; 16])
+                .section("__TEXT", "__cstring", 0, b"hello\0")
+                .section("__TEXT", "__ustring", 0, &ustring)
+                .section("__DATA", "__cfstring", 0, &pointers(objects))
+                .build()
+        };
+        let sections = addresses(&build(&[0; 12]));
+        let (cstring, ustring) = (sections["__cstring"], sections["__ustring"]);
+        let buf = build(&[0, 0x7c8, cstring, 5, 0, 0x7d0, ustring, 2, 0, 0x7c8, 0, 3]);
+        let report = objc(&buf, 20, 1).unwrap().cfstrings;
+        assert_eq!((report.count, report.objects_size, report.unresolved), (3, 96, 1));
+        // "hello" and its terminator, and two UTF-16 characters and theirs.
+        assert_eq!(report.storage_size, 6 + 6);
+        assert_eq!(report.largest.len(), 1);
+        let largest = &report.largest[0];
+        assert_eq!((largest.value.as_str(), largest.utf16, largest.size), ("hello", false, 38));
+        let all = objc(&buf, 20, 10).unwrap().cfstrings.largest;
+        assert_eq!((all[1].value.as_str(), all[1].utf16, all[1].size), ("hé", true, 38));
+    }
+}
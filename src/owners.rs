@@ -0,0 +1,130 @@
+use failure::Error;
+use regex::{self, Regex};
+use std::fs;
+use std::path::Path;
+
+/// The owner of source files that no rule matches.
+pub const UNOWNED: &str = "(unowned)";
+
+/// Translate a CODEOWNERS pattern into a regex over `/`-separated paths, following gitignore
+/// rules: `*` and `?` don't cross directories, `**` does, a pattern with a leading or inner `/`
+/// is anchored at the root while others match at any depth, and a pattern matching a directory
+/// matches everything in it.
+pub fn pattern_regex(pattern: &str) -> Result<Regex, Error> {
+    let trimmed = pattern.trim_end_matches('/');
+    let anchored = trimmed.contains('/');
+    let glob = trimmed.trim_start_matches('/');
+    let mut re = String::from(if anchored { "^" } else { "(^|/)" });
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                // `**/` also matches no directory at all.
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    re.push_str("(.*/)?");
+                } else {
+                    re.push_str(".*");
+                }
+            }
+            '*' => re.push_str("[^/]*"),
+            '?' => re.push_str("[^/]"),
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    re.push_str("(/.*)?$");
+    Regex::new(&re).map_err(|e| format_err!("Invalid pattern {}: {}", pattern, e))
+}
+
+/// Ownership rules in the format of GitHub's and GitLab's CODEOWNERS files: each line is a
+/// path pattern followed by its owners, and the last matching line gives a file's owners.
+pub struct Owners {
+    rules: Vec<(Regex, String)>,
+    /// A prefix to remove from source paths, to make them relative to the root the patterns
+    /// are anchored at.
+    root: Option<String>,
+}
+
+impl Owners {
+    /// Read the ownership rules at `path`. Source paths under `root` are matched relative to it.
+    pub fn load(path: &Path, root: Option<&str>) -> Result<Owners, Error> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format_err!("{}: {}", path.display(), e))?;
+        let mut rules = Vec::new();
+        for line in contents.lines() {
+            let line = line.trim();
+            // GitLab's `[Section]` headers group rules, but don't change how they match.
+            if line.is_empty() || line.starts_with('#') || line.starts_with('[') {
+                continue;
+            }
+            let mut words = line.split_whitespace();
+            let pattern = words.next().unwrap();
+            let owners: Vec<&str> = words.take_while(|w| !w.starts_with('#')).collect();
+            rules.push((pattern_regex(pattern)
+                            .map_err(|e| format_err!("{}: {}", path.display(), e))?,
+                        owners.join(" ")));
+        }
+        Ok(Owners {
+            rules,
+            root: root.map(|r| format!("{}/", r.trim_end_matches('/'))),
+        })
+    }
+
+    /// The owners of the source file `path`, or `UNOWNED`. A matching rule without owners
+    /// explicitly leaves files unowned.
+    pub fn owner(&self, path: &str) -> &str {
+        let path = self.root.as_ref()
+            .and_then(|root| path.strip_prefix(root.as_str()))
+            .unwrap_or(path);
+        match self.rules.iter().rev().find(|(re, _)| re.is_match(path)) {
+            Some((_, owners)) if !owners.is_empty() => owners,
+            _ => UNOWNED,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    #[test]
+    fn patterns_follow_gitignore_rules() {
+        let matches = |pattern: &str, path: &str| pattern_regex(pattern).unwrap().is_match(path);
+        assert!(matches("*.rs", "src/main.rs"));
+        assert!(matches("*.rs", "main.rs"));
+        assert!(!matches("*.rs", "main.rsx"));
+        assert!(matches("/src/*.rs", "src/main.rs"));
+        assert!(!matches("/src/*.rs", "src/a/main.rs"));
+        assert!(!matches("src/*.rs", "lib/src/main.rs"));
+        assert!(matches("src/**/*.rs", "src/main.rs"));
+        assert!(matches("src/**/*.rs", "src/a/b/main.rs"));
+        assert!(matches("docs/", "docs/a/b.md"));
+        assert!(matches("media", "dom/media/Decoder.cpp"));
+        assert!(!matches("media", "dom/multimedia/Decoder.cpp"));
+        assert!(matches("?.c", "lib/a.c"));
+        assert!(!matches("?.c", "lib/ab.c"));
+        assert!(matches("a+b.c", "a+b.c"));
+    }
+
+    #[test]
+    fn last_matching_line_owns() {
+        let path = env::temp_dir().join(format!("rust-size-{}-CODEOWNERS", process::id()));
+        fs::write(&path, "\
+# Everything
+*       @org/all
+[Media]
+/dom/media/   @media-team @alice  # reviewers
+/dom/media/vendored/
+").unwrap();
+        let owners = Owners::load(&path, Some("/builds/worker/checkouts/gecko/")).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(owners.owner("/builds/worker/checkouts/gecko/dom/media/A.cpp"),
+                   "@media-team @alice");
+        assert_eq!(owners.owner("dom/base/Node.cpp"), "@org/all");
+        assert_eq!(owners.owner("dom/media/vendored/b.c"), UNOWNED);
+        assert_eq!(owners.owner("/usr/include/stdio.h"), "@org/all");
+    }
+}
@@ -0,0 +1,284 @@
+use dwarf;
+use failure::Error;
+use loaded_data;
+use regex;
+use regex::bytes::Regex;
+use section_records;
+use std::cmp;
+use std::collections::BTreeMap;
+
+/// The prefixes that the Rust toolchain remaps the sources of the standard library and its
+/// dependencies to.
+const TOOLCHAIN_PREFIXES: &[&str] = &["/rustc/", "/rust/deps/"];
+
+/// Whether `path` is one the toolchain has already remapped.
+fn is_toolchain(path: &str) -> bool {
+    TOOLCHAIN_PREFIXES.iter().any(|prefix| path.starts_with(prefix))
+}
+
+/// The sections holding the strings of DWARF debug info, including the directory and file
+/// tables of line programs older than DWARF 5.
+const DEBUG_STRING_SECTIONS: &[&str] = &[".debug_str", ".debug_line_str", ".debug_line",
+                                         "__debug_str", "__debug_line_str", "__debug_line"];
+
+/// An absolute source file path embedded in the loaded data of a binary, typically by a panic
+/// location or an assertion message.
+#[derive(Clone, Debug, Serialize)]
+pub struct PathString {
+    pub path: String,
+    pub section: String,
+    pub offset: u64,
+}
+
+/// The pattern of an absolute Unix or Windows path to a source file.
+fn path_regex() -> Regex {
+    Regex::new(concat!(r"(?:/|[A-Za-z]:\\)(?:[A-Za-z0-9_.+@~-]+[/\\])+[A-Za-z0-9_.+@~-]+",
+                       r"\.(?:rs|c|cc|cpp|cxx|h|hpp|m|mm|swift|go|zig)")).unwrap()
+}
+
+/// Find the absolute source paths in the loaded, non-code sections of `buf`, including the
+/// toolchain's.
+fn loaded_paths(buf: &[u8]) -> Result<Vec<PathString>, Error> {
+    let regex = path_regex();
+    let mut paths = Vec::new();
+    for (section, offset, data) in loaded_data(buf)? {
+        for m in regex.find_iter(data) {
+            paths.push(PathString {
+                path: String::from_utf8_lossy(m.as_bytes()).into_owned(),
+                section: section.clone(),
+                offset: offset + m.start() as u64,
+            });
+        }
+    }
+    Ok(paths)
+}
+
+/// Find the absolute source paths in the loaded, non-code sections of `buf`, except those that
+/// the toolchain has already remapped.
+pub fn absolute_paths(buf: &[u8]) -> Result<Vec<PathString>, Error> {
+    let mut paths = loaded_paths(buf)?;
+    paths.retain(|p| !is_toolchain(&p.path));
+    Ok(paths)
+}
+
+/// Whether `s` is an absolute Unix or Windows path.
+fn is_absolute(s: &[u8]) -> bool {
+    (s.len() > 1 && s[0] == b'/' && s[1] != b'/') ||
+        (s.len() > 3 && s[0].is_ascii_alphabetic() && &s[1..3] == b":\\")
+}
+
+/// Find the absolute paths among the NUL-terminated strings of the debug info of `buf`: the
+/// compilation directories, and the directories and files of the line programs.
+fn debug_paths(buf: &[u8]) -> Result<Vec<PathString>, Error> {
+    let mut paths = Vec::new();
+    for record in section_records(buf)? {
+        if !DEBUG_STRING_SECTIONS.contains(&&*record.name) {
+            continue;
+        }
+        let data = match record.offset {
+            Some(offset) => buf.get(offset as usize..(offset + record.size) as usize),
+            None => None,
+        };
+        let (data, mut offset) = match (data, record.offset) {
+            (Some(data), Some(offset)) => (data, offset),
+            _ => continue,
+        };
+        for s in data.split(|&b| b == 0) {
+            if is_absolute(s) && s.iter().all(|&b| (0x20..0x7f).contains(&b)) {
+                paths.push(PathString {
+                    path: String::from_utf8_lossy(s).into_owned(),
+                    section: record.name.clone(),
+                    offset,
+                });
+            }
+            offset += s.len() as u64 + 1;
+        }
+    }
+    Ok(paths)
+}
+
+/// A directory prefix of embedded paths, and what remapping it away would save.
+#[derive(Clone, Debug, Serialize)]
+pub struct Prefix {
+    pub prefix: String,
+    pub loaded_paths: u64,
+    pub debug_paths: u64,
+    /// The bytes that `--remap-path-prefix=<prefix>=` would save in loaded data.
+    pub loaded_savings: u64,
+    /// The bytes it would save in debug info, which `strip` would also save.
+    pub debug_savings: u64,
+}
+
+/// The absolute paths embedded in a binary, as emitted by `paths --format json`.
+#[derive(Clone, Debug, Serialize)]
+pub struct PathReport {
+    pub loaded_paths: u64,
+    pub loaded_bytes: u64,
+    pub debug_paths: u64,
+    pub debug_bytes: u64,
+    /// The paths under `/rustc/` and `/rust/deps/`, which the toolchain has already remapped,
+    /// and which aren't counted above.
+    pub remapped_paths: u64,
+    /// The prefixes to remap, largest savings first.
+    pub prefixes: Vec<Prefix>,
+}
+
+/// The prefix of `path` to remap: the Cargo or rustup source directory that contains it, or
+/// else the longest of the compilation directories `comp_dirs` that contains it, or else the
+/// directory of the crate with a `src` directory that contains it, or else its parent.
+fn prefix_of(path: &str, comp_dirs: &[String], source_dirs: &[regex::Regex]) -> String {
+    // Cargo builds dependencies in their own directories, but one prefix remaps them all.
+    if let Some(caps) = source_dirs.iter().find_map(|re| re.captures(path)) {
+        return caps[1].to_string();
+    }
+    let separator = if path.starts_with('/') { '/' } else { '\\' };
+    let dir = format!("{}{}", path.trim_end_matches(separator), separator);
+    let longest = comp_dirs.iter()
+        .map(|comp_dir| format!("{}{}", comp_dir.trim_end_matches(separator), separator))
+        .filter(|comp_dir| dir.starts_with(comp_dir.as_str()))
+        .max_by_key(String::len);
+    if let Some(prefix) = longest {
+        return prefix;
+    }
+    if let Some(i) = path.find(&format!("{}src{}", separator, separator)) {
+        return path[..i + 1].to_string();
+    }
+    match path.trim_end_matches(separator).rfind(separator) {
+        Some(i) => path[..i + 1].to_string(),
+        None => dir,
+    }
+}
+
+/// Find the absolute paths in the loaded data and debug info of `buf`, and what remapping
+/// each of their prefixes to nothing would save.
+pub fn path_report(buf: &[u8]) -> Result<PathReport, Error> {
+    let dwarf = dwarf::load(buf)?;
+    let mut comp_dirs = Vec::new();
+    let mut headers = dwarf.units();
+    while let Some(header) = headers.next()? {
+        if let Some(comp_dir) = dwarf.unit(header)?.comp_dir {
+            let comp_dir = comp_dir.to_string_lossy().into_owned();
+            if is_absolute(comp_dir.as_bytes()) && !is_toolchain(&comp_dir) &&
+                !comp_dirs.contains(&comp_dir) {
+                comp_dirs.push(comp_dir);
+            }
+        }
+    }
+    let source_dirs = [
+        regex::Regex::new(r"^(.*?[/\\]registry[/\\]src[/\\][^/\\]+[/\\])").unwrap(),
+        regex::Regex::new(r"^(.*?[/\\]git[/\\]checkouts[/\\][^/\\]+[/\\])").unwrap(),
+        regex::Regex::new(r"^(.*?[/\\]lib[/\\]rustlib[/\\]src[/\\]rust[/\\])").unwrap(),
+    ];
+
+    let mut report = PathReport {
+        loaded_paths: 0,
+        loaded_bytes: 0,
+        debug_paths: 0,
+        debug_bytes: 0,
+        remapped_paths: 0,
+        prefixes: Vec::new(),
+    };
+    let mut prefixes = BTreeMap::new();
+    for (paths, debug) in [(loaded_paths(buf)?, false), (debug_paths(buf)?, true)] {
+        for p in paths {
+            if is_toolchain(&p.path) {
+                report.remapped_paths += 1;
+                continue;
+            }
+            let prefix = prefix_of(&p.path, &comp_dirs, &source_dirs);
+            let len = p.path.len() as u64;
+            let savings = cmp::min(prefix.len() as u64, len);
+            let entry = prefixes.entry(prefix.clone()).or_insert_with(|| Prefix {
+                prefix,
+                loaded_paths: 0,
+                debug_paths: 0,
+                loaded_savings: 0,
+                debug_savings: 0,
+            });
+            if debug {
+                report.debug_paths += 1;
+                report.debug_bytes += len;
+                entry.debug_paths += 1;
+                entry.debug_savings += savings;
+            } else {
+                report.loaded_paths += 1;
+                report.loaded_bytes += len;
+                entry.loaded_paths += 1;
+                entry.loaded_savings += savings;
+            }
+        }
+    }
+    report.prefixes = prefixes.into_values().collect();
+    report.prefixes.sort_by(|a, b| {
+        (b.loaded_savings, b.debug_savings).cmp(&(a.loaded_savings, a.debug_savings))
+            .then_with(|| a.prefix.cmp(&b.prefix))
+    });
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::elf::section_header::{SHF_ALLOC, SHF_EXECINSTR, SHT_PROGBITS};
+    use testelf::Elf;
+
+    const RODATA: &[u8] = b"called `Option::unwrap()`\0/home/ci/app/src/main.rs\0\
+C:\\Users\\ci\\app\\src\\lib.rs\0/rustc/0123abcd/library/core/src/option.rs\0\
+src/relative.rs\0/etc/passwd\0";
+
+    #[test]
+    fn absolute_paths_in_loaded_data() {
+        let buf = Elf::executable()
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, b"/home/ci/x.rs\0")
+            .section(".rodata", SHT_PROGBITS, SHF_ALLOC, RODATA)
+            .build();
+        let rodata = section_records(&buf).unwrap().into_iter()
+            .find(|r| r.name == ".rodata").unwrap().offset.unwrap();
+        let paths: Vec<_> = absolute_paths(&buf).unwrap().into_iter()
+            .map(|p| (p.path, p.section, p.offset - rodata))
+            .collect();
+        assert_eq!(paths, vec![
+            ("/home/ci/app/src/main.rs".to_string(), ".rodata".to_string(), 26),
+            ("C:\\Users\\ci\\app\\src\\lib.rs".to_string(), ".rodata".to_string(), 51),
+        ]);
+        assert_eq!(loaded_paths(&buf).unwrap().len(), 3);
+        assert!(is_absolute(b"/a") && is_absolute(b"D:\\a") && !is_absolute(b"//a"));
+    }
+
+    const REGISTRY: &str = "/home/ci/.cargo/registry/src/index.crates.io-6f17d22bba15001f/";
+
+    #[test]
+    fn prefixes_to_remap() {
+        let mut rodata = String::from("/home/ci/app/src/main.rs\0/opt/other/src/lib.rs\0");
+        rodata.push_str(&format!("{}serde-1.0.0/src/de.rs\0", REGISTRY));
+        rodata.push_str("/rustc/0123abcd/library/core/src/option.rs\0/tmp/gen.rs\0");
+        // Compile units with a `DW_FORM_string` compilation directory; the toolchain's is
+        // already remapped.
+        let mut info = Vec::new();
+        for comp_dir in &["/home/ci/app/", "/rustc/0123abcd"] {
+            info.extend_from_slice(&(9 + comp_dir.len() as u32).to_le_bytes());
+            info.extend_from_slice(&[4, 0, 0, 0, 0, 0, 8, 1]);
+            info.extend_from_slice(comp_dir.as_bytes());
+            info.push(0);
+        }
+        let buf = Elf::executable()
+            .section(".rodata", SHT_PROGBITS, SHF_ALLOC, rodata.as_bytes())
+            .section(".debug_abbrev", SHT_PROGBITS, 0, &[1, 0x11, 0, 0x1b, 0x08, 0, 0, 0])
+            .section(".debug_info", SHT_PROGBITS, 0, &info)
+            .section(".debug_str", SHT_PROGBITS, 0, b"/home/ci/app\0/home/ci/app/src/util.rs\0")
+            .build();
+        let report = path_report(&buf).unwrap();
+        let loaded = [24, 21, REGISTRY.len() + 21, 11].iter().sum::<usize>() as u64;
+        assert_eq!((report.loaded_paths, report.loaded_bytes), (4, loaded));
+        assert_eq!((report.debug_paths, report.debug_bytes, report.remapped_paths), (2, 36, 1));
+        let prefixes: Vec<_> = report.prefixes.iter()
+            .map(|p| (&*p.prefix, p.loaded_paths, p.debug_paths, p.loaded_savings,
+                      p.debug_savings))
+            .collect();
+        let registry = REGISTRY.len() as u64;
+        // The compilation directory itself only saves its length.
+        assert_eq!(prefixes, vec![(REGISTRY, 1, 0, registry, 0),
+                                  ("/home/ci/app/", 1, 2, 13, 12 + 13),
+                                  ("/opt/other/", 1, 0, 11, 0), ("/tmp/", 1, 0, 5, 0)]);
+    }
+}
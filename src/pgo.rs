@@ -0,0 +1,142 @@
+use arch::arch;
+use counts_toward_total;
+use failure::Error;
+use sections;
+use std::collections::BTreeMap;
+use symbols;
+
+/// The sections that LLVM's profile instrumentation (`-fprofile-generate`,
+/// `-C instrument-coverage`) adds, under their ELF, Mach-O and COFF names.
+const PROFILE_SECTIONS: &[&str] = &[
+    "__llvm_prf_cnts", "__llvm_prf_data", "__llvm_prf_names", "__llvm_prf_vnds",
+    "__llvm_prf_vns", "__llvm_prf_bits", "__llvm_covmap", "__llvm_covfun", "__llvm_orderfile",
+    ".lprfc", ".lprfd", ".lprfn", ".lprfv", ".lprfb", ".lcovmap", ".lcovfun",
+];
+
+/// The prefixes of the symbols of the profiling runtimes, and of GCC's per-function counters
+/// (`-fprofile-arcs`).
+const RUNTIME_PREFIXES: &[&str] = &["__llvm_profile_", "lprof", "__gcov", "gcov_"];
+
+/// The LLVM section holding the counters, one per instrumented edge or block.
+const COUNTERS: &[&str] = &["__llvm_prf_cnts", ".lprfc"];
+
+/// The prefix of GCC's arc counters, one array per function.
+const GCOV_COUNTERS: &str = "__gcov0.";
+
+/// The size of the profile instrumentation in a binary, as emitted by `pgo --format json`.
+#[derive(Clone, Debug, Serialize)]
+pub struct PgoReport {
+    /// Whether the binary is instrumented at all.
+    pub instrumented: bool,
+    /// The size of each profile data section.
+    pub sections: BTreeMap<String, u64>,
+    /// The number of 8-byte counters.
+    pub counters: u64,
+    /// The size of the profiling runtime's functions and data, and of GCC's counters.
+    pub runtime: u64,
+    /// An estimate of the code that increments the counters, from a typical instruction
+    /// sequence per counter for the architecture.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub counter_code: Option<u64>,
+    /// The total size of the binary, counting sections as in size reports.
+    pub total: u64,
+    /// The estimated total without the instrumentation, to compare with an optimized build.
+    pub estimated_final: u64,
+}
+
+/// The bytes it takes to increment a counter on `arch`.
+fn increment_size(arch: &str) -> Option<u64> {
+    match arch {
+        // `incq counter(%rip)`, or `addq $1` when the flags are live (8 bytes).
+        "x86_64" | "x86" => Some(7),
+        // `adrp`, `ldr`, `add` and `str`; the `adrp` is often shared.
+        "aarch64" => Some(12),
+        _ => None,
+    }
+}
+
+/// Measure the profile instrumentation of `buf`. Non-allocated sections only count toward the
+/// totals if `include_non_alloc` is set.
+pub fn pgo(buf: &[u8], include_non_alloc: bool) -> Result<PgoReport, Error> {
+    let mut profile_sections = BTreeMap::new();
+    let mut total = 0;
+    for (name, size, category, _) in sections(buf)? {
+        if !counts_toward_total(category, include_non_alloc) {
+            continue;
+        }
+        total += size;
+        if PROFILE_SECTIONS.contains(&&*name) {
+            *profile_sections.entry(name).or_insert(0) += size;
+        }
+    }
+    let symbols = symbols::symbols(buf)?;
+    let counters = profile_sections.iter()
+        .filter(|(name, _)| COUNTERS.contains(&name.as_str()))
+        .map(|(_, size)| size / 8)
+        .chain(symbols.iter().filter(|sym| sym.name.starts_with(GCOV_COUNTERS))
+               .map(|sym| sym.size / 8))
+        .sum::<u64>();
+    let runtime = symbols.iter()
+        .filter(|sym| {
+            let name = sym.name.trim_start_matches('_');
+            RUNTIME_PREFIXES.iter().any(|p| name.starts_with(p.trim_start_matches('_')))
+        })
+        .map(|sym| sym.size)
+        .sum();
+    let counter_code = increment_size(&arch(buf)?).map(|size| counters * size);
+    let instrumented = !profile_sections.is_empty() || runtime > 0;
+    let overhead = profile_sections.values().sum::<u64>() + runtime + counter_code.unwrap_or(0);
+    Ok(PgoReport {
+        instrumented,
+        sections: profile_sections,
+        counters,
+        runtime,
+        counter_code,
+        total,
+        estimated_final: total.saturating_sub(overhead),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::elf::section_header::{SHF_ALLOC, SHF_EXECINSTR, SHF_WRITE, SHT_PROGBITS};
+    use goblin::elf::sym::{STT_FUNC, STT_OBJECT};
+    use testelf::Elf;
+
+    #[test]
+    fn instrumentation_overhead() {
+        let data = SHF_ALLOC | SHF_WRITE;
+        let buf = Elf::executable()
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &[0; 256])
+            .section("__llvm_prf_cnts", SHT_PROGBITS, data, &[0; 64])
+            .section("__llvm_prf_data", SHT_PROGBITS, data, &[0; 48])
+            .section("__llvm_prf_names", SHT_PROGBITS, SHF_ALLOC, &[0; 16])
+            .section(".data", SHT_PROGBITS, data, &[0; 16])
+            .symbol("__llvm_profile_write_file", STT_FUNC, ".text", 0, 32)
+            .symbol("lprofGetHostName", STT_FUNC, ".text", 32, 16)
+            .symbol("main", STT_FUNC, ".text", 48, 64)
+            .symbol("__gcov0.main", STT_OBJECT, ".data", 0, 16)
+            .build();
+        let report = pgo(&buf, false).unwrap();
+        assert!(report.instrumented);
+        let sections: Vec<_> = report.sections.iter().map(|(name, &size)| (&**name, size))
+            .collect();
+        assert_eq!(sections, vec![("__llvm_prf_cnts", 64), ("__llvm_prf_data", 48),
+                                  ("__llvm_prf_names", 16)]);
+        // Eight LLVM counters and two GCC ones, each incremented by a 7-byte instruction.
+        assert_eq!((report.counters, report.runtime, report.counter_code), (10, 64, Some(70)));
+        assert_eq!(report.total, 256 + 64 + 48 + 16 + 16);
+        assert_eq!(report.estimated_final, 400 - 128 - 64 - 70);
+
+        let buf = Elf::executable()
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &[0; 256])
+            .symbol("main", STT_FUNC, ".text", 0, 64)
+            .build();
+        let report = pgo(&buf, false).unwrap();
+        assert!(!report.instrumented);
+        assert_eq!((report.counters, report.total, report.estimated_final), (0, 256, 256));
+        assert_eq!(increment_size("aarch64"), Some(12));
+        assert_eq!(increment_size("riscv64"), None);
+    }
+}
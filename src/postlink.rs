@@ -0,0 +1,153 @@
+use failure::Error;
+use regex::Regex;
+use sections;
+use std::collections::BTreeMap;
+use symbols;
+use Section;
+
+/// A function split into several fragments by the compiler or a post-link optimizer.
+#[derive(Clone, Debug, Serialize)]
+pub struct SplitFunction {
+    pub name: String,
+    /// The size of the main fragment, which holds the entry point.
+    pub hot: u64,
+    /// The size of the fragments moved away as cold (`.cold`, `.cold.N`).
+    pub cold: u64,
+    /// The size of the other fragments (basic block section clusters, `.__part.N`).
+    pub other: u64,
+}
+
+/// What post-link and profile-guided layout optimizers did to a binary, as emitted by
+/// `post-link --format json`.
+#[derive(Clone, Debug, Serialize)]
+pub struct PostLinkReport {
+    /// The optimizations whose artifacts are present.
+    pub detected: Vec<&'static str>,
+    /// The size of the code sections.
+    pub text_size: u64,
+    pub hot_bytes: u64,
+    pub cold_bytes: u64,
+    pub other_fragment_bytes: u64,
+    /// The size of the original code that BOLT keeps alongside the rewritten code
+    /// (`.bolt.org.text`), which is pure duplication.
+    pub original_text: u64,
+    /// The size of the basic block address maps (`.llvm_bb_addr_map`) that Propeller profiling
+    /// needs, but a release binary doesn't.
+    pub bb_address_map: u64,
+    /// The split functions, largest cold part first.
+    pub functions: Vec<SplitFunction>,
+}
+
+/// Analyze the code layout of `buf`.
+pub fn post_link(buf: &[u8]) -> Result<PostLinkReport, Error> {
+    let sections = sections(buf)?;
+    let size_of = |pred: &dyn Fn(&str) -> bool| -> u64 {
+        sections.iter().filter(|s| pred(&s.0)).map(|s| s.1).sum()
+    };
+    let mut detected = Vec::new();
+    let original_text = size_of(&|name| name == ".bolt.org.text");
+    if original_text > 0 || sections.iter().any(|s| s.0 == ".note.bolt_info") {
+        detected.push("BOLT");
+    }
+    let bb_address_map = size_of(&|name| name == ".llvm_bb_addr_map");
+    if bb_address_map > 0 {
+        detected.push("basic block address maps (Propeller profiling)");
+    }
+    if sections.iter().any(|s| s.0.starts_with(".text.split.")) {
+        detected.push("machine function splitting");
+    }
+
+    // The fragment suffixes go after the mangled name, so they can be stripped directly.
+    let fragment = Regex::new(r"^(.+?)\.(cold(\.\d+)?|__part\.\d+)$").unwrap();
+    let symbols = symbols::symbols(buf)?;
+    let mut functions: BTreeMap<&str, SplitFunction> = BTreeMap::new();
+    let mut has_parts = false;
+    for sym in symbols.iter().filter(|sym| sym.code) {
+        if let Some(caps) = fragment.captures(&sym.name) {
+            let base = caps.get(1).unwrap().as_str();
+            let entry = functions.entry(base).or_insert_with(|| SplitFunction {
+                name: base.to_string(),
+                hot: 0,
+                cold: 0,
+                other: 0,
+            });
+            if caps[2].starts_with("cold") {
+                entry.cold += sym.size;
+            } else {
+                entry.other += sym.size;
+                has_parts = true;
+            }
+        }
+    }
+    for sym in symbols.iter().filter(|sym| sym.code) {
+        if let Some(entry) = functions.get_mut(&*sym.name) {
+            entry.hot += sym.size;
+        }
+    }
+    if has_parts {
+        detected.push("basic block sections (Propeller)");
+    }
+    if functions.values().any(|f| f.cold > 0) {
+        detected.push("hot/cold function splitting");
+    }
+
+    let mut functions: Vec<SplitFunction> = functions.into_values().collect();
+    functions.sort_by(|a, b| b.cold.cmp(&a.cold).then_with(|| a.name.cmp(&b.name)));
+    Ok(PostLinkReport {
+        detected,
+        text_size: sections.iter().filter(|s| s.2 == Section::Text).map(|s| s.1).sum(),
+        hot_bytes: functions.iter().map(|f| f.hot).sum(),
+        cold_bytes: functions.iter().map(|f| f.cold).sum(),
+        other_fragment_bytes: functions.iter().map(|f| f.other).sum(),
+        original_text,
+        bb_address_map,
+        functions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::elf::section_header::{SHF_ALLOC, SHF_EXECINSTR, SHT_NOTE, SHT_PROGBITS};
+    use goblin::elf::sym::STT_FUNC;
+    use testelf::Elf;
+
+    #[test]
+    fn split_functions_and_optimizer_sections() {
+        let code = SHF_ALLOC | SHF_EXECINSTR;
+        let buf = Elf::executable()
+            .section(".text", SHT_PROGBITS, code, &[0; 64])
+            .section(".text.split.foo", SHT_PROGBITS, code, &[0; 16])
+            .section(".bolt.org.text", SHT_PROGBITS, code, &[0; 32])
+            .section(".llvm_bb_addr_map", SHT_PROGBITS, 0, &[0; 8])
+            .symbol("foo", STT_FUNC, ".text", 0, 16)
+            .symbol("foo.cold", STT_FUNC, ".text.split.foo", 0, 8)
+            .symbol("foo.cold.1", STT_FUNC, ".text.split.foo", 8, 4)
+            .symbol("bar", STT_FUNC, ".text", 16, 16)
+            .symbol("bar.__part.1", STT_FUNC, ".text", 32, 8)
+            .symbol("baz", STT_FUNC, ".text", 40, 8)
+            .build();
+        let report = post_link(&buf).unwrap();
+        assert_eq!(report.detected, vec!["BOLT", "basic block address maps (Propeller profiling)",
+                                         "machine function splitting",
+                                         "basic block sections (Propeller)",
+                                         "hot/cold function splitting"]);
+        assert_eq!(report.text_size, 64 + 16 + 32);
+        assert_eq!((report.hot_bytes, report.cold_bytes, report.other_fragment_bytes), (32, 12, 8));
+        assert_eq!((report.original_text, report.bb_address_map), (32, 8));
+        let functions: Vec<_> = report.functions.iter()
+            .map(|f| (&*f.name, f.hot, f.cold, f.other))
+            .collect();
+        assert_eq!(functions, vec![("foo", 16, 12, 0), ("bar", 16, 0, 8)]);
+
+        // Only a BOLT note, and code the compiler didn't split.
+        let buf = Elf::executable()
+            .section(".text", SHT_PROGBITS, code, &[0; 16])
+            .section(".note.bolt_info", SHT_NOTE, 0, &[0; 16])
+            .symbol("main", STT_FUNC, ".text", 0, 16)
+            .build();
+        let report = post_link(&buf).unwrap();
+        assert_eq!(report.detected, vec!["BOLT"]);
+        assert!(report.functions.is_empty());
+    }
+}
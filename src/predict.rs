@@ -0,0 +1,461 @@
+use failure::Error;
+use goblin::elf::header::ET_REL;
+use goblin::elf::section_header::{SHF_ALLOC, SHF_EXECINSTR, SHF_MERGE, SHF_STRINGS, SHF_WRITE};
+use goblin::elf::section_header::SHT_NOBITS;
+use goblin::elf::sym::STB_LOCAL;
+use goblin::elf::Elf;
+use goblin::Object;
+use inputs::map_objects;
+use merge::split_strings;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use Section;
+
+const SHT_GROUP: u32 = 17;
+const GRP_COMDAT: u32 = 1;
+const SHN_UNDEF: usize = 0;
+const SHN_LORESERVE: usize = 0xff00;
+const SHN_COMMON: usize = 0xfff2;
+
+/// Output sections that collect input sections by name prefix, as in the default GNU linker
+/// scripts. More specific prefixes come first.
+const OUTPUT_SECTIONS: &[(&str, &str)] = &[
+    (".text", ".text"),
+    (".rodata", ".rodata"),
+    (".data.rel.ro", ".data.rel.ro"),
+    (".data", ".data"),
+    (".bss", ".bss"),
+    (".tdata", ".tdata"),
+    (".tbss", ".tbss"),
+    (".gcc_except_table", ".gcc_except_table"),
+    (".init_array", ".init_array"),
+    (".fini_array", ".fini_array"),
+    (".preinit_array", ".preinit_array"),
+    (".ctors", ".ctors"),
+    (".dtors", ".dtors"),
+];
+
+/// Sections that the linker keeps even when collecting garbage, since they are used without
+/// being referenced.
+const KEPT_SECTIONS: &[&str] = &[
+    ".init", ".fini", ".init_array", ".fini_array", ".preinit_array", ".ctors", ".dtors",
+    ".note", ".eh_frame",
+];
+
+/// Kept sections whose references don't keep anything else alive: the linker drops the
+/// unwind entries of functions that it removes.
+const WEAK_ROOTS: &[&str] = &[".eh_frame"];
+
+/// The output section that the input section `name` ends up in.
+fn output_section(name: &str) -> &str {
+    OUTPUT_SECTIONS.iter()
+        .find(|&&(prefix, _)| name == prefix || name.starts_with(&format!("{}.", prefix)))
+        .map(|&(_, output)| output)
+        .unwrap_or(name)
+}
+
+/// Whether the input section `name` is kept by `--gc-sections` without being referenced.
+fn is_kept(name: &str) -> bool {
+    KEPT_SECTIONS.iter().any(|&kept| name == kept || name.starts_with(&format!("{}.", kept)))
+}
+
+/// An allocated section of one of the input object files.
+struct InputSection<'a> {
+    output: String,
+    category: Section,
+    data: Option<&'a [u8]>,
+    size: u64,
+    alignment: u64,
+    /// The entry size for sections whose entries the linker merges.
+    merge: Option<(u64, bool)>,
+    /// The COMDAT group that the section belongs to.
+    group: Option<String>,
+    /// The sections of the same object that this one refers to.
+    refs: Vec<usize>,
+    /// The global symbols that this section refers to.
+    imports: Vec<String>,
+}
+
+/// One of the input object files.
+struct InputObject<'a> {
+    sections: BTreeMap<usize, InputSection<'a>>,
+    /// Global symbols defined in this object, and the sections that define them.
+    exports: Vec<(String, usize)>,
+}
+
+/// An input section, identified by the index of its object and its section index.
+type Key = (usize, usize);
+
+/// Read the word at `index` of an `SHT_GROUP` section.
+fn group_word(data: &[u8], index: usize, little_endian: bool) -> Option<u32> {
+    let bytes = data.get(index * 4..index * 4 + 4)?;
+    let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+    Some(if little_endian { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) })
+}
+
+fn read_object<'a>(elf: &Elf, buf: &'a [u8]) -> InputObject<'a> {
+    let mut object = InputObject {
+        sections: BTreeMap::new(),
+        exports: Vec::new(),
+    };
+    for (index, sh) in elf.section_headers.iter().enumerate() {
+        if sh.sh_flags & u64::from(SHF_ALLOC) == 0 {
+            continue;
+        }
+        let name = elf.shdr_strtab.get(sh.sh_name).and_then(|res| res.ok()).unwrap_or("");
+        let flags = sh.sh_flags;
+        let category = if flags & u64::from(SHF_EXECINSTR) != 0 ||
+            flags & u64::from(SHF_WRITE) == 0 {
+            Section::Text
+        } else if sh.sh_type != SHT_NOBITS {
+            Section::Data
+        } else {
+            Section::Bss
+        };
+        let data = if sh.sh_type == SHT_NOBITS {
+            None
+        } else {
+            buf.get(sh.sh_offset as usize..(sh.sh_offset + sh.sh_size) as usize)
+        };
+        let merge = if flags & u64::from(SHF_MERGE) != 0 && sh.sh_entsize != 0 {
+            Some((sh.sh_entsize, flags & u64::from(SHF_STRINGS) != 0))
+        } else {
+            None
+        };
+        object.sections.insert(index, InputSection {
+            output: output_section(name).to_string(),
+            category,
+            data,
+            size: sh.sh_size,
+            alignment: sh.sh_addralign.max(1),
+            merge,
+            group: None,
+            refs: Vec::new(),
+            imports: Vec::new(),
+        });
+    }
+
+    let sym_name = |index: usize| -> Option<String> {
+        let sym = elf.syms.get(index)?;
+        elf.strtab.get(sym.st_name).and_then(|res| res.ok()).map(str::to_string)
+    };
+
+    // COMDAT groups, identified by their signature symbol.
+    for sh in elf.section_headers.iter().filter(|sh| sh.sh_type == SHT_GROUP) {
+        let data = match buf.get(sh.sh_offset as usize..(sh.sh_offset + sh.sh_size) as usize) {
+            Some(data) => data,
+            None => continue,
+        };
+        if group_word(data, 0, elf.little_endian).unwrap_or(0) & GRP_COMDAT == 0 {
+            continue;
+        }
+        let signature = match sym_name(sh.sh_info as usize) {
+            Some(signature) => signature,
+            None => continue,
+        };
+        for i in 1..data.len() / 4 {
+            if let Some(member) = group_word(data, i, elf.little_endian) {
+                if let Some(section) = object.sections.get_mut(&(member as usize)) {
+                    section.group = Some(signature.clone());
+                }
+            }
+        }
+    }
+
+    for (index, sym) in elf.syms.iter().enumerate() {
+        let shndx = sym.st_shndx;
+        if sym.st_bind() == STB_LOCAL || shndx == SHN_UNDEF || shndx >= SHN_LORESERVE {
+            if shndx == SHN_COMMON {
+                // Common symbols get their storage in `.bss` at link time.
+                if let Some(name) = sym_name(index) {
+                    let key = usize::MAX - index;
+                    object.sections.insert(key, InputSection {
+                        output: ".bss".to_string(),
+                        category: Section::Bss,
+                        data: None,
+                        size: sym.st_size,
+                        alignment: sym.st_value.max(1),
+                        merge: None,
+                        group: Some(name.clone()),
+                        refs: Vec::new(),
+                        imports: Vec::new(),
+                    });
+                    object.exports.push((name, key));
+                }
+            }
+            continue;
+        }
+        if let Some(name) = sym_name(index) {
+            object.exports.push((name, shndx));
+        }
+    }
+
+    for &(reloc_index, ref relocs) in &elf.shdr_relocs {
+        let target = elf.section_headers[reloc_index].sh_info as usize;
+        if !object.sections.contains_key(&target) {
+            continue;
+        }
+        let mut refs = Vec::new();
+        let mut imports = Vec::new();
+        for reloc in relocs {
+            let sym = match elf.syms.get(reloc.r_sym) {
+                Some(sym) => sym,
+                None => continue,
+            };
+            let shndx = sym.st_shndx;
+            if sym.st_bind() == STB_LOCAL && shndx != SHN_UNDEF && shndx < SHN_LORESERVE {
+                refs.push(shndx);
+            } else if let Some(name) = sym_name(reloc.r_sym) {
+                imports.push(name);
+            }
+        }
+        let section = object.sections.get_mut(&target).unwrap();
+        section.refs.extend(refs);
+        section.imports.extend(imports);
+    }
+    object
+}
+
+/// A predicted section of the linked output.
+#[derive(Clone, Debug, Serialize)]
+pub struct PredictedSection {
+    pub name: String,
+    pub category: Section,
+    pub size: u64,
+    /// The number of input sections it is made of.
+    pub inputs: usize,
+    /// The bytes of padding inserted to align the input sections.
+    pub padding: u64,
+}
+
+/// A prediction of the size of the output of a link, as emitted by `predict --format json`.
+#[derive(Clone, Debug, Serialize)]
+pub struct Prediction {
+    pub sections: Vec<PredictedSection>,
+    /// The total size of the allocated sections of the inputs.
+    pub input_size: u64,
+    /// The bytes of duplicate COMDAT groups that the linker discards.
+    pub comdat_folded: u64,
+    /// The bytes of duplicate strings and constants that the linker merges.
+    pub merged: u64,
+    /// The bytes of sections that `--gc-sections` removes, if enabled.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gc_removed: Option<u64>,
+    pub padding: u64,
+    pub predicted_size: u64,
+}
+
+/// Predict the allocated size of the linked output of the ELF object files and archives at
+/// `paths`, modelling what the linker does to its inputs:
+///
+/// - input sections are collected into output sections the way the default linker scripts do,
+///   each aligned as it requires;
+/// - only the first copy of each COMDAT group is kept;
+/// - identical entries of mergeable string and constant sections are merged;
+/// - with `gc_entry`, sections that aren't reachable through relocations from the entry symbol
+///   (or from sections that are always kept) are removed, as with `--gc-sections`.
+///
+/// Synthesized sections (headers, PLT, GOT, dynamic linking tables) are not predicted.
+pub fn predict(paths: &[String], gc_entry: Option<&str>) -> Result<Prediction, Error> {
+    // The object files need to live until the end, since they borrow their contents.
+    let buffers = map_objects(paths, |name, buf| Ok((name, buf.to_vec())))?;
+    let mut objects = Vec::new();
+    for (name, buf) in &buffers {
+        match Object::parse(buf)? {
+            Object::Elf(ref elf) if elf.header.e_type == ET_REL => {
+                objects.push(read_object(elf, buf));
+            },
+            _ => bail!("{}: only ELF relocatable objects can be used to predict a link", name),
+        }
+    }
+
+    let input_size: u64 = objects.iter()
+        .flat_map(|o| o.sections.values())
+        .map(|s| s.size)
+        .sum();
+
+    // Keep only the first copy of each COMDAT group, and the first definition of each symbol.
+    let mut live: HashSet<Key> = HashSet::new();
+    let mut seen_groups = HashSet::new();
+    let mut comdat_folded = 0;
+    for (o, object) in objects.iter().enumerate() {
+        let mut first_in_group = HashMap::new();
+        for (&index, section) in &object.sections {
+            let keep = match section.group {
+                Some(ref group) => *first_in_group.entry(group.clone())
+                    .or_insert_with(|| seen_groups.insert(group.clone())),
+                None => true,
+            };
+            if keep {
+                live.insert((o, index));
+            } else {
+                comdat_folded += section.size;
+            }
+        }
+    }
+    let mut definitions: HashMap<&str, Key> = HashMap::new();
+    for (o, object) in objects.iter().enumerate() {
+        for &(ref name, index) in &object.exports {
+            if live.contains(&(o, index)) {
+                definitions.entry(name.as_str()).or_insert((o, index));
+            }
+        }
+    }
+
+    let mut gc_removed = None;
+    if let Some(entry) = gc_entry {
+        let (weak, mut stack): (Vec<Key>, Vec<Key>) = live.iter().cloned()
+            .filter(|&(o, index)| {
+                is_kept(&objects[o].sections[&index].output) ||
+                    definitions.get(entry) == Some(&(o, index))
+            })
+            .partition(|&(o, index)| {
+                WEAK_ROOTS.contains(&objects[o].sections[&index].output.as_str())
+            });
+        let mut reached: HashSet<Key> = weak.into_iter().chain(stack.clone()).collect();
+        while let Some((o, index)) = stack.pop() {
+            let section = &objects[o].sections[&index];
+            let targets = section.refs.iter().map(|&r| (o, r))
+                .chain(section.imports.iter().filter_map(|name| {
+                    definitions.get(name.as_str()).cloned()
+                }));
+            for target in targets {
+                if live.contains(&target) && reached.insert(target) {
+                    stack.push(target);
+                }
+            }
+        }
+        gc_removed = Some(live.iter()
+            .filter(|key| !reached.contains(key))
+            .map(|&(o, index)| objects[o].sections[&index].size)
+            .sum());
+        live = reached;
+    }
+
+    // Lay out the output sections, merging mergeable entries across inputs.
+    let mut outputs: BTreeMap<&str, PredictedSection> = BTreeMap::new();
+    let mut merged_entries: HashMap<(&str, u64, bool), HashSet<&[u8]>> = HashMap::new();
+    let mut merged = 0;
+    let mut keys: Vec<&Key> = live.iter().collect();
+    keys.sort();
+    for &(o, index) in keys {
+        let section = &objects[o].sections[&index];
+        let out = outputs.entry(&section.output).or_insert_with(|| PredictedSection {
+            name: section.output.clone(),
+            category: section.category,
+            size: 0,
+            inputs: 0,
+            padding: 0,
+        });
+        let mut size = section.size;
+        if let (Some((entry_size, strings)), Some(data)) = (section.merge, section.data) {
+            let entries: Vec<&[u8]> = if strings {
+                split_strings(data, entry_size as usize)
+            } else {
+                data.chunks(entry_size as usize).collect()
+            };
+            let terminator = if strings { entry_size } else { 0 };
+            let seen = merged_entries.entry((&section.output, entry_size, strings)).or_default();
+            size = 0;
+            for e in entries {
+                if seen.insert(e) {
+                    size += e.len() as u64 + terminator;
+                }
+            }
+            merged += section.size.saturating_sub(size);
+        }
+        let aligned = out.size.div_ceil(section.alignment) * section.alignment;
+        out.padding += aligned - out.size;
+        out.size = aligned + size;
+        out.inputs += 1;
+    }
+
+    let mut sections: Vec<PredictedSection> = outputs.into_values().collect();
+    sections.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+    Ok(Prediction {
+        input_size,
+        comdat_folded,
+        merged,
+        gc_removed,
+        padding: sections.iter().map(|s| s.padding).sum(),
+        predicted_size: sections.iter().map(|s| s.size).sum(),
+        sections,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::elf::section_header::SHT_PROGBITS;
+    use goblin::elf::sym::{STT_FUNC, STT_OBJECT};
+    use std::env;
+    use std::fs;
+    use std::process;
+    use testelf::Elf;
+
+    #[test]
+    fn output_sections_by_prefix() {
+        assert_eq!(output_section(".text.main"), ".text");
+        assert_eq!(output_section(".data.rel.ro.local"), ".data.rel.ro");
+        assert_eq!(output_section(".data.rel.local"), ".data");
+        assert_eq!(output_section(".textual"), ".textual");
+        assert!(is_kept(".init_array.00100") && is_kept(".eh_frame"));
+        assert!(!is_kept(".text.startup"));
+    }
+
+    #[test]
+    fn folds_merges_and_collects_garbage() {
+        let code = SHF_ALLOC | SHF_EXECINSTR;
+        let strings = SHF_ALLOC | SHF_MERGE | SHF_STRINGS;
+        let a = Elf::object()
+            .section(".text.main", SHT_PROGBITS, code, &[0; 10])
+            .section(".text.unused", SHT_PROGBITS, code, &[0; 10])
+            .section(".text.inline", SHT_PROGBITS, code, &[0; 6])
+            .merge_section(".rodata.str1.1", SHT_PROGBITS, strings, 1, b"hi\0x\0")
+            .section(".eh_frame", SHT_PROGBITS, SHF_ALLOC, &[0; 8])
+            .symbol("main", STT_FUNC, ".text.main", 0, 10)
+            .symbol("unused", STT_FUNC, ".text.unused", 0, 10)
+            .symbol("inline_fn", STT_FUNC, ".text.inline", 0, 6)
+            .undefined("helper")
+            .group("inline_fn", &[".text.inline"])
+            .rela(".text.main", &[(2, "helper")])
+            // Unwind entries don't keep the functions they describe alive.
+            .rela(".eh_frame", &[(0, "main"), (4, "unused")])
+            .build();
+        let b = Elf::object()
+            .section(".text.helper", SHT_PROGBITS, code, &[0; 10])
+            .section(".text.inline", SHT_PROGBITS, code, &[0; 6])
+            .merge_section(".rodata.str1.1", SHT_PROGBITS, strings, 1, b"hi\0y\0")
+            .symbol("helper", STT_FUNC, ".text.helper", 0, 10)
+            .symbol("inline_fn", STT_FUNC, ".text.inline", 0, 6)
+            .local("table", STT_OBJECT, ".rodata.str1.1", 0, 5)
+            .group("inline_fn", &[".text.inline"])
+            .build();
+        let dir = env::temp_dir();
+        let paths: Vec<String> = [("a", &a), ("b", &b)].iter().map(|&(name, buf)| {
+            let path = dir.join(format!("rust-size-{}-{}.o", process::id(), name));
+            fs::write(&path, buf).unwrap();
+            path.display().to_string()
+        }).collect();
+        let all = predict(&paths, None);
+        let gc = predict(&paths, Some("main"));
+        for path in &paths {
+            fs::remove_file(path).unwrap();
+        }
+
+        let all = all.unwrap();
+        assert_eq!((all.input_size, all.comdat_folded, all.merged, all.gc_removed),
+                   (60, 6, 3, None));
+        let sections: Vec<_> = all.sections.iter()
+            .map(|s| (s.name.as_str(), s.size, s.inputs, s.padding))
+            .collect();
+        // Input sections are aligned to 8 bytes.
+        assert_eq!(sections, vec![(".text", 50, 4, 14), (".rodata", 10, 2, 3),
+                                  (".eh_frame", 8, 1, 0)]);
+        assert_eq!((all.padding, all.predicted_size), (17, 68));
+
+        let gc = gc.unwrap();
+        assert_eq!(gc.gc_removed, Some(26));
+        let sections: Vec<_> = gc.sections.iter().map(|s| (s.name.as_str(), s.size)).collect();
+        assert_eq!(sections, vec![(".text", 26), (".eh_frame", 8)]);
+    }
+}
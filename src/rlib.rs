@@ -0,0 +1,136 @@
+use archive;
+use failure::Error;
+use sections;
+use std::collections::BTreeMap;
+use std::path::Path;
+use Section;
+
+/// The member of an rlib holding the crate's metadata, which rustc reads to compile dependent
+/// crates, and which isn't linked into binaries.
+const METADATA: &str = "lib.rmeta";
+
+/// What an rlib is made of, as emitted by `rlib --format json`.
+#[derive(Clone, Debug, Serialize)]
+pub struct RlibReport {
+    /// The crate name, from the names of the codegen unit objects.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crate_name: Option<String>,
+    /// The size of `lib.rmeta`.
+    pub metadata: u64,
+    /// The number of object files, one per codegen unit.
+    pub objects: u64,
+    /// The sizes of the sections of the object files by category. `Other` is their debug info,
+    /// symbol tables and relocations.
+    pub sections: BTreeMap<Section, u64>,
+    /// The size of the members that are neither, like bitcode.
+    pub other_members: u64,
+    /// The rest of the file: the archive's headers and symbol index, and the headers of the
+    /// object files.
+    pub overhead: u64,
+    pub file_size: u64,
+}
+
+/// Split the rlib `buf`, read from `path`, into its crate metadata and the sections of its
+/// object files.
+pub fn rlib(buf: &[u8], path: &Path) -> Result<RlibReport, Error> {
+    if !archive::is_archive(buf) {
+        bail!("Not an rlib");
+    }
+    let members = archive::all_members(buf, path)?;
+    let mut report = RlibReport {
+        crate_name: None,
+        metadata: 0,
+        objects: 0,
+        sections: [Section::Text, Section::Data, Section::Bss, Section::Other].iter()
+            .map(|&section| (section, 0))
+            .collect(),
+        other_members: 0,
+        overhead: 0,
+        file_size: buf.len() as u64,
+    };
+    let mut found_metadata = false;
+    for (member, member_sections) in members.iter().zip(archive::par_map(&members, |member| {
+        if member.name == METADATA || !archive::is_object(&member.data) {
+            return None;
+        }
+        Some(sections(&member.data).map_err(|e| format_err!("{}: {}", member.name, e)))
+    })) {
+        if member.name == METADATA {
+            found_metadata = true;
+            report.metadata += member.data.len() as u64;
+            continue;
+        }
+        let member_sections = match member_sections {
+            Some(member_sections) => member_sections?,
+            None => {
+                report.other_members += member.data.len() as u64;
+                continue;
+            }
+        };
+        report.objects += 1;
+        // Codegen units are named `<crate>-<hash>.<crate>.<hash>-cgu.<n>.rcgu.o`.
+        if report.crate_name.is_none() && member.name.ends_with(".rcgu.o") {
+            report.crate_name = member.name.split('-').next().map(str::to_string);
+        }
+        for (_, size, section, _) in member_sections {
+            *report.sections.entry(section).or_insert(0) += size;
+        }
+    }
+    if !found_metadata {
+        bail!("Not an rlib: there is no {} member", METADATA);
+    }
+    let stored: u64 = report.sections.iter()
+        .filter(|&(&section, _)| section != Section::Bss)
+        .map(|(_, size)| size)
+        .sum();
+    report.overhead = report.file_size
+        .saturating_sub(report.metadata + stored + report.other_members);
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::elf::section_header::{SHF_ALLOC, SHF_EXECINSTR, SHF_WRITE, SHT_PROGBITS};
+    use testarchive::{archive, member};
+    use testelf::Elf;
+
+    fn object() -> Vec<u8> {
+        Elf::object()
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &[0; 32])
+            .section(".data", SHT_PROGBITS, SHF_ALLOC | SHF_WRITE, &[0; 16])
+            .nobits(".bss", SHF_ALLOC | SHF_WRITE, 8)
+            .build()
+    }
+
+    #[test]
+    fn metadata_objects_and_other_members() {
+        let object = object();
+        let buf = archive(&[
+            member("//", b"foo-1a2b.foo.3c4d-cgu.0.rcgu.o/\n"),
+            member("/0", &object),
+            member("lib.rmeta/", &[0; 40]),
+            member("foo.bc/", &[0; 10]),
+        ]);
+        let report = rlib(&buf, Path::new("libfoo.rlib")).unwrap();
+        assert_eq!(report.crate_name.as_deref(), Some("foo"));
+        assert_eq!((report.metadata, report.objects, report.other_members), (40, 1, 10));
+        assert_eq!(report.sections[&Section::Text], 32);
+        assert_eq!(report.sections[&Section::Data], 16);
+        assert_eq!(report.sections[&Section::Bss], 8);
+        // The object's symbol tables and section names count as `Other`, and its headers as
+        // overhead, like those of the archive.
+        let other = report.sections[&Section::Other];
+        assert!(other > 0);
+        assert_eq!(report.overhead, buf.len() as u64 - 40 - 10 - 32 - 16 - other);
+        assert_eq!(report.file_size, buf.len() as u64);
+    }
+
+    #[test]
+    fn archives_without_metadata() {
+        let path = Path::new("libfoo.a");
+        let buf = archive(&[member("foo.o/", &object())]);
+        assert!(rlib(&buf, path).unwrap_err().to_string().contains("no lib.rmeta member"));
+        assert!(rlib(&object(), path).is_err());
+    }
+}
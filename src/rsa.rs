@@ -0,0 +1,190 @@
+use der;
+use digest::Algorithm;
+use std::cmp::Ordering;
+
+/// A number as 32-bit limbs, from the lowest.
+type Limbs = Vec<u32>;
+
+fn from_be_bytes(bytes: &[u8]) -> Limbs {
+    bytes.rchunks(4)
+        .map(|chunk| chunk.iter().fold(0, |limb, &b| limb << 8 | u32::from(b)))
+        .collect()
+}
+
+/// The lowest `len` bytes of `limbs`, from the highest.
+fn to_be_bytes(limbs: &[u32], len: usize) -> Vec<u8> {
+    let mut bytes: Vec<u8> = limbs.iter().flat_map(|limb| limb.to_le_bytes()).collect();
+    bytes.resize(len, 0);
+    bytes.reverse();
+    bytes
+}
+
+fn cmp(a: &[u32], b: &[u32]) -> Ordering {
+    let len = a.len().max(b.len());
+    for i in (0..len).rev() {
+        let (x, y) = (a.get(i).copied().unwrap_or(0), b.get(i).copied().unwrap_or(0));
+        if x != y {
+            return x.cmp(&y);
+        }
+    }
+    Ordering::Equal
+}
+
+fn mul(a: &[u32], b: &[u32]) -> Limbs {
+    let mut product = vec![0u32; a.len() + b.len()];
+    for (i, &x) in a.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, &y) in b.iter().enumerate() {
+            let t = u64::from(x) * u64::from(y) + u64::from(product[i + j]) + carry;
+            product[i + j] = t as u32;
+            carry = t >> 32;
+        }
+        product[i + b.len()] = carry as u32;
+    }
+    product
+}
+
+/// `a` modulo `m`, by long division a bit at a time: slow, but there are only a few hundred
+/// bits of exponent to go through.
+fn rem(a: &[u32], m: &[u32]) -> Limbs {
+    let mut r = vec![0u32; m.len() + 1];
+    for i in (0..a.len() * 32).rev() {
+        let mut carry = a[i / 32] >> (i % 32) & 1;
+        for limb in &mut r {
+            let next = *limb >> 31;
+            *limb = *limb << 1 | carry;
+            carry = next;
+        }
+        if cmp(&r, m) != Ordering::Less {
+            let mut borrow = 0i64;
+            for (j, limb) in r.iter_mut().enumerate() {
+                let t = i64::from(*limb) - i64::from(m.get(j).copied().unwrap_or(0)) - borrow;
+                *limb = t as u32;
+                borrow = if t < 0 { 1 } else { 0 };
+            }
+        }
+    }
+    r
+}
+
+/// `base` to the power of `exponent`, modulo `m`.
+fn pow_mod(base: &[u32], exponent: &[u32], m: &[u32]) -> Limbs {
+    let mut result = vec![1];
+    for i in (0..exponent.len() * 32).rev() {
+        result = rem(&mul(&result, &result), m);
+        if exponent[i / 32] >> (i % 32) & 1 == 1 {
+            result = rem(&mul(&result, base), m);
+        }
+    }
+    result
+}
+
+/// Whether `signature` is the PKCS #1 v1.5 signature of the hash `hash`, made with `algorithm`,
+/// by the RSA key with `modulus` and `exponent`.
+pub fn verify(modulus: &[u8], exponent: &[u8], signature: &[u8], algorithm: Algorithm,
+              hash: &[u8]) -> bool {
+    let n = from_be_bytes(modulus);
+    let s = from_be_bytes(signature);
+    if signature.len() > modulus.len() || cmp(&s, &n) != Ordering::Less {
+        return false;
+    }
+    let encoded = to_be_bytes(&pow_mod(&s, &from_be_bytes(exponent), &n), modulus.len());
+    // 00 01, at least eight bytes of ff, 00, then the DigestInfo: the algorithm and the hash.
+    if !encoded.starts_with(&[0, 1]) {
+        return false;
+    }
+    let padding = encoded[2..].iter().take_while(|&&b| b == 0xff).count();
+    if padding < 8 || encoded.get(2 + padding) != Some(&0) {
+        return false;
+    }
+    let digest_info = match der::read(&encoded[3 + padding..]) {
+        Some((value, [])) => value,
+        _ => return false,
+    };
+    match digest_info.children_of(der::SEQUENCE).as_deref() {
+        Some([algorithm_id, digest]) => {
+            let oid = algorithm_id.children().first().and_then(der::Value::oid);
+            oid.as_deref().and_then(Algorithm::from_oid) == Some(algorithm) &&
+                digest.tag == der::OCTET_STRING && digest.contents == hash
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unhex(s: &str) -> Vec<u8> {
+        (0..s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap()).collect()
+    }
+
+    /// The modulus of a 1024-bit key made with `openssl genrsa`, whose exponent is 65537.
+    const MODULUS: &str = concat!(
+        "b09e938b6c60badda4b7338d0f9618a3ceda011a051cf25a8a49265551de788ed723651dac5ab3c8",
+        "44414fe3236a3623e74c654cb6e18a15e4f2be85e80e3a1282d9e3870544498662969384112a9ca3",
+        "d2bd5c8a42b44920c94c38e1c1f52f70cc4b98c587273058559f5d5a42ebfd3e94ab0e245a7a0f3e",
+        "b229a9441395bb4d",
+    );
+
+    /// `openssl dgst -sha256 -sign` of `abc` with the key.
+    const SHA256_SIGNATURE: &str = concat!(
+        "3c019e5fa418e695cd11f4ef388f582821d20b9ca3446eb38432147e1d480994b3d9dcef5e546030",
+        "e2ccbdd7f480908126fe6416884691a219f5375e90752149f9a37aa1cbd63dddd2061548e430b3ab",
+        "f19a0b08f038b8abf9fb7b07c45ee43c41ed0db9934d197c498b5766cb48aec25d4e71917772d19f",
+        "622535e9e713bd4f",
+    );
+
+    /// `openssl dgst -sha1 -sign` of `abc` with the key.
+    const SHA1_SIGNATURE: &str = concat!(
+        "02caea58ea9351e9ece4110d36c7c04d9d79ef0649e871711ae056ced81aefcca0cac8d1a257740b",
+        "c8d2923ccbd78e681a3008038df78169c33d016521c57a14975a9738923da66bfdb23bfd2eea8e56",
+        "f2615aaeed00658fdce67d23e3fba8a4cc81a57747ea7931dfc36c3434c2a84f5b48f8dd0d0e0373",
+        "4b2293dc159524b3",
+    );
+
+    const EXPONENT: &[u8] = &[1, 0, 1];
+
+    #[test]
+    fn verifies_pkcs1_v1_5() {
+        let n = unhex(MODULUS);
+        let sha256 = Algorithm::Sha256.digest(&[b"abc"]);
+        let sha1 = Algorithm::Sha1.digest(&[b"abc"]);
+        assert!(verify(&n, EXPONENT, &unhex(SHA256_SIGNATURE), Algorithm::Sha256, &sha256));
+        assert!(verify(&n, EXPONENT, &unhex(SHA1_SIGNATURE), Algorithm::Sha1, &sha1));
+    }
+
+    #[test]
+    fn rejects_bad_signatures() {
+        let n = unhex(MODULUS);
+        let signature = unhex(SHA256_SIGNATURE);
+        let sha256 = Algorithm::Sha256.digest(&[b"abc"]);
+        // Another message, another algorithm than the signature's, and another key.
+        assert!(!verify(&n, EXPONENT, &signature, Algorithm::Sha256,
+                        &Algorithm::Sha256.digest(&[b"abd"])));
+        assert!(!verify(&n, EXPONENT, &signature, Algorithm::Sha1,
+                        &Algorithm::Sha1.digest(&[b"abc"])));
+        assert!(!verify(&n, &[3], &signature, Algorithm::Sha256, &sha256));
+        let mut flipped = signature.clone();
+        flipped[100] ^= 1;
+        assert!(!verify(&n, EXPONENT, &flipped, Algorithm::Sha256, &sha256));
+        // Signatures that aren't below the modulus, or are longer than it.
+        assert!(!verify(&n, EXPONENT, &n, Algorithm::Sha256, &sha256));
+        let mut longer = vec![0];
+        longer.extend_from_slice(&signature);
+        assert!(!verify(&n, EXPONENT, &longer, Algorithm::Sha256, &sha256));
+        assert!(!verify(&n, EXPONENT, &[], Algorithm::Sha256, &sha256));
+    }
+
+    #[test]
+    fn bignum_arithmetic() {
+        assert_eq!(from_be_bytes(&[1, 2, 3, 4, 5]), vec![0x0203_0405, 1]);
+        assert_eq!(to_be_bytes(&[0x0203_0405, 1], 6), vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(mul(&[0xffff_ffff], &[0xffff_ffff]), vec![1, 0xffff_fffe]);
+        assert_eq!(cmp(&rem(&[10, 7], &[3]), &[(((7u64 << 32) + 10) % 3) as u32]),
+                   Ordering::Equal);
+        // 4^13 mod 497 = 445, and Fermat: 2^(p - 1) mod p = 1 for p = 2^31 - 1.
+        assert_eq!(cmp(&pow_mod(&[4], &[13], &[497]), &[445]), Ordering::Equal);
+        assert_eq!(cmp(&pow_mod(&[2], &[0x7fff_fffe], &[0x7fff_ffff]), &[1]), Ordering::Equal);
+    }
+}
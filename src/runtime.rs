@@ -0,0 +1,198 @@
+use failure::Error;
+use generated;
+use rustc_demangle;
+use std::collections::BTreeMap;
+use symbols;
+use thunks::KindSize;
+
+/// The symbols of the C allocators that binaries link statically: dlmalloc, jemalloc (with its
+/// `je_` and, as `tikv-jemalloc-sys` builds it, `_rjem_` prefixes), mimalloc, glibc's and
+/// musl's.
+const ALLOCATOR_PREFIXES: &[&str] = &["dlmalloc", "dlfree", "dlrealloc", "dlcalloc",
+                                      "dlmemalign", "dlposix_memalign", "je_", "_rjem_",
+                                      "rjem_", "mi_", "_mi_", "__libc_malloc", "__libc_free",
+                                      "__libc_calloc", "__libc_realloc", "__libc_memalign",
+                                      "__malloc_", "_int_malloc", "_int_free", "_int_realloc",
+                                      "_int_memalign", "malloc_", "sysmalloc", "ptmalloc_",
+                                      "tcache_", "arena_get", "__simple_malloc",
+                                      "__expand_heap"];
+const ALLOCATOR_FUNCTIONS: &[&str] = &["malloc", "free", "calloc", "realloc", "reallocarray",
+                                       "memalign", "posix_memalign", "aligned_alloc", "valloc",
+                                       "pvalloc", "malloc_usable_size"];
+
+/// The shims that rustc generates to forward to the global allocator, and the default
+/// `__rdl_` ones that forward to the system allocator.
+const ALLOCATOR_SHIM_PREFIXES: &[&str] = &["__rust_alloc", "__rust_dealloc", "__rust_realloc",
+                                           "__rust_no_alloc_shim_is_unstable", "__rg_", "__rdl_",
+                                           "__rust_oom"];
+
+/// The crates of Rust allocators, and the parts of `std` and `alloc` that implement or wrap
+/// the system allocator.
+const ALLOCATOR_PATHS: &[&str] = &["dlmalloc::", "jemallocator::", "tikv_jemallocator::",
+                                   "tikv_jemalloc_sys::", "jemalloc_sys::", "mimalloc::",
+                                   "libmimalloc_sys::", "wee_alloc::", "linked_list_allocator::",
+                                   "talc::", "std::sys::alloc::", "std::alloc::",
+                                   "alloc::alloc::"];
+
+/// The symbols of the unwinders, LLVM's libunwind and libgcc's, and of the personality
+/// routines and libraries that run landing pads.
+const UNWINDER_PREFIXES: &[&str] = &["_Unwind_", "__unw_", "unw_", "__gcc_personality_",
+                                     "__gxx_personality_", "rust_eh_personality",
+                                     "__register_frame", "__deregister_frame", "_US_",
+                                     "__rust_start_panic", "__rust_panic_cleanup",
+                                     "__frame_state_for", "_ZN9libunwind"];
+const UNWINDER_PATHS: &[&str] = &["libunwind::", "unwind::", "panic_unwind::",
+                                  "std::sys::personality::", "std::sys::pal::unix::personality::"];
+
+/// A symbol of a runtime library.
+#[derive(Clone, Debug, Serialize)]
+pub struct RuntimeSymbol {
+    /// The demangled name, without the hash.
+    pub name: String,
+    /// See `kind`.
+    pub kind: &'static str,
+    pub size: u64,
+}
+
+/// What the runtime libraries linked into a binary cost, as emitted by
+/// `runtime --format json`.
+#[derive(Clone, Debug, Serialize)]
+pub struct RuntimeReport {
+    pub kinds: BTreeMap<&'static str, KindSize>,
+    /// The size of the runtime libraries, which a binary pays for however little code it has.
+    pub size: u64,
+    /// The size of all functions and data symbols, counting aliases once.
+    pub symbol_size: u64,
+    /// The size of the other symbols: `symbol_size` less `size`.
+    pub application_size: u64,
+    /// The symbols, largest first.
+    pub symbols: Vec<RuntimeSymbol>,
+}
+
+/// Whether the demangled `name`, or the type of the trait impl that it is a method of, is in
+/// one of the modules `paths`.
+fn in_paths(name: &str, paths: &[&str]) -> bool {
+    let name = name.strip_prefix('<').unwrap_or(name);
+    paths.iter().any(|path| name.starts_with(path))
+}
+
+/// The runtime library that the symbol `name` is part of: `allocator` for the memory
+/// allocator and the shims that forward to it, `unwinder` for the unwinder and personality
+/// routines, and `builtins` for the compiler-rt or libgcc builtins and the memory functions
+/// that compilers call. The unwind tables themselves are sections, which the section report
+/// counts.
+pub fn kind(name: &str) -> Option<&'static str> {
+    let unprefixed = name.strip_prefix('_').unwrap_or(name);
+    let demangled = format!("{:#}", rustc_demangle::demangle(name));
+    if ALLOCATOR_PREFIXES.iter().any(|prefix| name.starts_with(prefix)) ||
+        ALLOCATOR_FUNCTIONS.contains(&name) || ALLOCATOR_FUNCTIONS.contains(&unprefixed) ||
+        ALLOCATOR_SHIM_PREFIXES.iter().any(|prefix| name.starts_with(prefix)) ||
+        in_paths(&demangled, ALLOCATOR_PATHS) {
+        return Some("allocator");
+    }
+    if UNWINDER_PREFIXES.iter().any(|prefix| name.starts_with(prefix) ||
+                                              unprefixed.starts_with(prefix)) ||
+        in_paths(&demangled, UNWINDER_PATHS) {
+        return Some("unwinder");
+    }
+    match generated::kind(name) {
+        Some("intrinsic") => Some("builtins"),
+        _ => None,
+    }
+}
+
+/// Sum the sizes of the allocator, the unwinder and the compiler builtins linked into `buf`,
+/// which are a fixed cost, apart from the application's own code and data.
+pub fn runtime(buf: &[u8]) -> Result<RuntimeReport, Error> {
+    let mut syms = symbols::symbols(buf)?;
+    // Aliases share their symbol's address; keep the largest of each.
+    syms.sort_by(|a, b| {
+        (&a.section, a.address, b.size, &a.name).cmp(&(&b.section, b.address, a.size, &b.name))
+    });
+    syms.dedup_by(|a, b| a.section == b.section && a.address == b.address);
+
+    let symbol_size = syms.iter().map(|sym| sym.size).sum();
+    let mut report = RuntimeReport {
+        kinds: BTreeMap::new(),
+        size: 0,
+        symbol_size,
+        application_size: symbol_size,
+        symbols: Vec::new(),
+    };
+    for sym in syms {
+        let kind = match kind(&sym.name) {
+            Some(kind) => kind,
+            None => continue,
+        };
+        let totals = report.kinds.entry(kind).or_default();
+        totals.count += 1;
+        totals.size += sym.size;
+        report.size += sym.size;
+        report.application_size -= sym.size;
+        report.symbols.push(RuntimeSymbol {
+            name: format!("{:#}", rustc_demangle::demangle(&sym.name)),
+            kind,
+            size: sym.size,
+        });
+    }
+    report.symbols.sort_by(|a, b| (b.size, &a.name).cmp(&(a.size, &b.name)));
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::elf::section_header::{SHF_ALLOC, SHF_EXECINSTR, SHT_PROGBITS};
+    use goblin::elf::sym::STT_FUNC;
+    use testelf::Elf;
+
+    /// The legacy Rust mangling of `path`.
+    fn mangled(path: &[&str]) -> String {
+        let components: String = path.iter().map(|c| format!("{}{}", c.len(), c)).collect();
+        format!("_ZN{}17h0123456789abcdefE", components)
+    }
+
+    #[test]
+    fn kinds_by_name() {
+        for name in &["malloc", "_free", "je_mallocx", "_rjem_sdallocx", "dlmalloc",
+                      "_int_malloc", "__rust_alloc", "__rdl_alloc", "__rg_oom"] {
+            assert_eq!(kind(name), Some("allocator"), "{}", name);
+        }
+        assert_eq!(kind(&mangled(&["dlmalloc", "dlmalloc", "Dlmalloc", "malloc"])),
+                   Some("allocator"));
+        assert_eq!(kind(&mangled(&["std", "sys", "alloc", "unix", "realloc_fallback"])),
+                   Some("allocator"));
+        for name in &["_Unwind_RaiseException", "__Unwind_Resume", "__gxx_personality_v0",
+                      "rust_eh_personality", "__register_frame_info",
+                      "_ZN9libunwind13UnwindCursorE"] {
+            assert_eq!(kind(name), Some("unwinder"), "{}", name);
+        }
+        assert_eq!(kind(&mangled(&["panic_unwind", "imp", "panic"])), Some("unwinder"));
+        assert_eq!(kind("__udivti3"), Some("builtins"));
+        assert_eq!(kind("memcpy"), Some("builtins"));
+        // Not the allocator, for all that they start like it.
+        assert_eq!(kind("freeze"), None);
+        assert_eq!(kind(&mangled(&["app", "main"])), None);
+    }
+
+    #[test]
+    fn runtime_and_application_sizes() {
+        let buf = Elf::object()
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &[0; 64])
+            .symbol("main", STT_FUNC, ".text", 0, 16)
+            .symbol("je_malloc", STT_FUNC, ".text", 16, 16)
+            // An alias counts once.
+            .symbol("malloc", STT_FUNC, ".text", 16, 16)
+            .symbol("_Unwind_Resume", STT_FUNC, ".text", 32, 12)
+            .symbol("__rust_alloc", STT_FUNC, ".text", 44, 4)
+            .symbol("memset", STT_FUNC, ".text", 48, 16)
+            .build();
+        let report = runtime(&buf).unwrap();
+        assert_eq!((report.size, report.symbol_size, report.application_size), (48, 64, 16));
+        let kinds: Vec<_> = report.kinds.iter().map(|(&k, s)| (k, s.count, s.size)).collect();
+        assert_eq!(kinds, vec![("allocator", 2, 20), ("builtins", 1, 16), ("unwinder", 1, 12)]);
+        let symbols: Vec<_> = report.symbols.iter().map(|s| (s.kind, s.size)).collect();
+        assert_eq!(symbols, vec![("allocator", 16), ("builtins", 16), ("unwinder", 12),
+                                 ("allocator", 4)]);
+    }
+}
@@ -0,0 +1,279 @@
+use failure::Error;
+use serde_json::{self, Map, Value};
+use Section;
+use SectionRecord;
+
+/// The largest report that is worth parsing to check whether a file is one.
+const MAX_REPORT_SIZE: usize = 256 << 20;
+
+/// The version of the schema of the section reports written now, in their `schema` field.
+/// Version 1 reports, written before the field was added, are told apart by their shape.
+pub const SCHEMA_VERSION: u64 = 2;
+
+/// The layouts of section reports, which version 2 reports name in their `layout` field.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Schema {
+    /// The sizes of the sections of each category, by name, as every version writes by
+    /// default: `{"Text": {".text": 1234}}`. With `--explain`, each size is an object with a
+    /// `size` and a `description`.
+    Categories,
+    /// A `Categories` report for each slice of a universal binary, keyed by architecture.
+    Arches,
+    /// The section records of `--details`, with the file's metadata: `{"metadata": {...},
+    /// "sections": [...]}`. Records written before a field was added lack it.
+    Details,
+    /// The members of an archive with their sum in `sections`, as `--members` writes.
+    Members,
+}
+
+/// A section report as it is written now: `report`, with the version of the schema and its
+/// layout alongside its own fields.
+#[derive(Serialize)]
+pub struct Versioned<T> {
+    schema: u64,
+    layout: Schema,
+    #[serde(flatten)]
+    report: T,
+}
+
+/// `report`, a section report of layout `layout`, with the current schema version.
+pub fn versioned<T>(layout: Schema, report: T) -> Versioned<T> {
+    Versioned { schema: SCHEMA_VERSION, layout, report }
+}
+
+/// Parse `buf` as JSON, if it looks like it could be a report.
+fn parse(buf: &[u8]) -> Option<Map<String, Value>> {
+    let start = buf.iter().position(|b| !b.is_ascii_whitespace())?;
+    if buf[start] != b'{' || buf.len() > MAX_REPORT_SIZE {
+        return None;
+    }
+    match serde_json::from_slice(buf) {
+        Ok(Value::Object(map)) => Some(map),
+        _ => None,
+    }
+}
+
+/// The category named `name`, as reports write them.
+fn category(name: &str) -> Option<Section> {
+    serde_json::from_value(Value::String(name.to_string())).ok()
+}
+
+/// Whether `value` is the sizes of sections by name, plain or explained.
+fn is_sizes(value: &Value) -> bool {
+    value.as_object().is_some_and(|sizes| sizes.values().all(|size| {
+        size.is_u64() || size.get("size").is_some_and(Value::is_u64)
+    }))
+}
+
+/// Whether `map` is the sections of each category by name.
+fn is_categories(map: &Map<String, Value>) -> bool {
+    map.iter().all(|(name, sizes)| category(name).is_some() && is_sizes(sizes))
+}
+
+/// Whether the report `map` has the layout `layout`.
+fn has_layout(map: &Map<String, Value>, layout: Schema) -> bool {
+    match layout {
+        Schema::Details => {
+            map.contains_key("metadata") && map.get("sections").is_some_and(Value::is_array)
+        }
+        Schema::Members => {
+            map.contains_key("members") &&
+                map.get("sections").and_then(Value::as_object).is_some_and(is_categories)
+        }
+        Schema::Categories => is_categories(map),
+        Schema::Arches => map.values().all(|slice| slice.as_object().is_some_and(is_categories)),
+    }
+}
+
+/// The layout of the report `map`, without its `schema` and `layout` fields. Version 1
+/// reports are told apart by their shape; an empty object could be anything, so it isn't
+/// taken for one.
+fn schema(map: &mut Map<String, Value>) -> Result<Schema, Error> {
+    let version = match map.remove("schema") {
+        Some(version) => version,
+        None => {
+            let layouts = [Schema::Details, Schema::Members, Schema::Categories, Schema::Arches];
+            return match layouts.iter().find(|&&layout| has_layout(map, layout)) {
+                Some(&layout) if !map.is_empty() => Ok(layout),
+                _ => bail!("Unknown section report layout"),
+            };
+        }
+    };
+    if version.as_u64() != Some(SCHEMA_VERSION) {
+        bail!("Unsupported section report schema: {}", version);
+    }
+    match map.remove("layout").and_then(|layout| serde_json::from_value(layout).ok()) {
+        Some(layout) if has_layout(map, layout) => Ok(layout),
+        _ => bail!("Invalid section report"),
+    }
+}
+
+/// Whether `buf` is a section report that an earlier run wrote, of any layout, which can be
+/// read in place of the object file that it describes. Reports of schema versions that this
+/// version doesn't know count, so that reading them fails with the version.
+pub fn is_report(buf: &[u8]) -> bool {
+    parse(buf).is_some_and(|mut map| {
+        map.get("schema").is_some_and(Value::is_u64) || schema(&mut map).is_ok()
+    })
+}
+
+/// The records of the sections of each category in `categories`, in slice `arch`.
+fn category_records(categories: &Map<String, Value>, arch: Option<&str>) -> Vec<SectionRecord> {
+    let mut vec = Vec::new();
+    for (name, sizes) in categories {
+        let category = category(name).unwrap();
+        for (name, size) in sizes.as_object().unwrap() {
+            let size = size.as_u64().or_else(|| size["size"].as_u64()).unwrap();
+            let mut record = SectionRecord::synthetic(name, size, category);
+            record.arch = arch.map(str::to_string);
+            vec.push(record);
+        }
+    }
+    vec
+}
+
+/// The record written as `value` by `--details`, taking the fields that its version lacks to
+/// be unknown. Flag names and descriptions are left out; they are derived again as needed.
+fn detailed_record(value: &Value) -> Result<SectionRecord, Error> {
+    let name = value["name"].as_str();
+    let category = value["category"].as_str().and_then(category);
+    let (name, category, size) = match (name, category, value["size"].as_u64()) {
+        (Some(name), Some(category), Some(size)) => (name, category, size),
+        _ => bail!("Invalid section record in report: {}", value),
+    };
+    let mut record = SectionRecord::synthetic(name, size, category);
+    record.segment = value["segment"].as_str().map(str::to_string);
+    record.address = value["address"].as_u64();
+    record.offset = value["offset"].as_u64();
+    record.alignment = value["alignment"].as_u64();
+    record.flags = value["flags"].as_u64();
+    record.preview = value["preview"].as_str().map(str::to_string);
+    record.arch = value["arch"].as_str().map(str::to_string);
+    Ok(record)
+}
+
+/// Read the section report `buf`, as any version wrote it, and return a record for each
+/// section, migrating older layouts to the current one. Records only have the fields that the
+/// report has: reports without `--details` have sizes and categories, but no addresses.
+pub fn sections(buf: &[u8]) -> Result<Vec<SectionRecord>, Error> {
+    let mut map = match parse(buf) {
+        Some(map) => map,
+        None => bail!("Not a section report"),
+    };
+    Ok(match schema(&mut map)? {
+        Schema::Categories => category_records(&map, None),
+        Schema::Arches => {
+            let mut vec = Vec::new();
+            for (arch, slice) in &map {
+                vec.extend(category_records(slice.as_object().unwrap(), Some(arch)));
+            }
+            vec
+        }
+        Schema::Details => {
+            map["sections"].as_array().unwrap().iter()
+                .map(detailed_record)
+                .collect::<Result<_, _>>()?
+        }
+        Schema::Members => category_records(map["sections"].as_object().unwrap(), None),
+    })
+}
+
+/// The architecture that the report `buf` recorded, if it did: the architectures of a
+/// universal binary joined with `+`, or that of `--details` metadata.
+pub fn arch(buf: &[u8]) -> String {
+    let mut map = parse(buf).unwrap_or_default();
+    match schema(&mut map).ok() {
+        Some(Schema::Arches) => map.keys().cloned().collect::<Vec<_>>().join("+"),
+        Some(Schema::Details) => {
+            map["metadata"]["arch"].as_str().unwrap_or("unknown").to_string()
+        }
+        _ => "unknown".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn records(buf: &[u8]) -> Vec<(String, Section, u64, Option<String>)> {
+        sections(buf).unwrap().into_iter().map(|r| (r.name, r.category, r.size, r.arch)).collect()
+    }
+
+    #[test]
+    fn layouts_of_every_version() {
+        let categories = br#" {"Text": {".text": 100},
+                                "Bss": {".bss": {"size": 8, "description": "zeros"}}}"#;
+        assert!(is_report(categories));
+        assert_eq!(records(categories), vec![(".bss".to_string(), Section::Bss, 8, None),
+                                             (".text".to_string(), Section::Text, 100, None)]);
+        assert_eq!(arch(categories), "unknown");
+
+        let arches = br#"{"arm64": {"Text": {".text": 10}}, "x86_64": {"Text": {".text": 12}}}"#;
+        assert_eq!(records(arches),
+                   vec![(".text".to_string(), Section::Text, 10, Some("arm64".to_string())),
+                        (".text".to_string(), Section::Text, 12, Some("x86_64".to_string()))]);
+        assert_eq!(arch(arches), "arm64+x86_64");
+
+        let members = br#"{"members": [], "sections": {"Data": {".rodata": 4}}}"#;
+        assert_eq!(records(members), vec![(".rodata".to_string(), Section::Data, 4, None)]);
+    }
+
+    #[test]
+    fn detailed_records_with_missing_fields() {
+        // The first record was written before `alignment` and `arch` were added.
+        let details = br#"{"metadata": {"arch": "x86_64"}, "sections": [
+            {"name": ".text", "category": "Text", "size": 16, "address": 4096},
+            {"name": ".data", "category": "Data", "size": 8, "alignment": 8, "arch": "x86_64"}
+        ]}"#;
+        let found = sections(details).unwrap();
+        assert_eq!((found[0].address, found[0].alignment), (Some(4096), None));
+        assert_eq!((found[1].alignment, found[1].arch.as_deref()), (Some(8), Some("x86_64")));
+        assert_eq!(arch(details), "x86_64");
+
+        let invalid = br#"{"metadata": {}, "sections": [{"name": ".text", "size": 16}]}"#;
+        assert!(sections(invalid).is_err());
+    }
+
+    #[test]
+    fn other_json_is_not_a_report() {
+        for buf in &[&b"[1, 2]"[..], b"{}", b"{\"Code\": {\".text\": 1}}",
+                     b"{\"Text\": {\".text\": -1}}", b"{\"Text\": ", b"\x7fELF"] {
+            assert!(!is_report(buf), "{}", String::from_utf8_lossy(buf));
+        }
+        assert!(sections(b"\x7fELF").is_err());
+    }
+
+    #[test]
+    fn versioned_reports() {
+        let mut report = BTreeMap::new();
+        report.insert(Section::Text, vec![(".text", 100)].into_iter().collect::<BTreeMap<_, _>>());
+        let buf = serde_json::to_vec(&versioned(Schema::Categories, &report)).unwrap();
+        assert!(is_report(&buf));
+        assert_eq!(records(&buf), vec![(".text".to_string(), Section::Text, 100, None)]);
+
+        let mut arches = BTreeMap::new();
+        arches.insert("arm64", &report);
+        let buf = serde_json::to_vec(&versioned(Schema::Arches, &arches)).unwrap();
+        assert_eq!(records(&buf),
+                   vec![(".text".to_string(), Section::Text, 100, Some("arm64".to_string()))]);
+        assert_eq!(arch(&buf), "arm64");
+
+        // A version 2 report of nothing is still one, unlike an empty version 1 report.
+        assert!(is_report(br#"{"schema": 2, "layout": "categories"}"#));
+        assert_eq!(records(br#"{"schema": 2, "layout": "categories"}"#), vec![]);
+    }
+
+    #[test]
+    fn reports_of_other_versions() {
+        let future = br#"{"schema": 3, "layout": "categories", "Text": {".text": 1}}"#;
+        assert!(is_report(future));
+        assert_eq!(sections(future).unwrap_err().to_string(),
+                   "Unsupported section report schema: 3");
+        for buf in &[&br#"{"schema": 2, "Text": {".text": 1}}"#[..],
+                     br#"{"schema": 2, "layout": "details", "Text": {".text": 1}}"#] {
+            assert_eq!(sections(buf).unwrap_err().to_string(), "Invalid section report");
+        }
+    }
+}
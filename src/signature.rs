@@ -0,0 +1,647 @@
+use der::{self, Value};
+use digest::Algorithm;
+use failure::Error;
+use goblin::mach::load_command::CommandVariant;
+use goblin::mach::{Mach, MachO};
+use goblin::Object;
+use rsa;
+
+/// The object identifiers that signatures are recognized by.
+const SIGNED_DATA: &str = "1.2.840.113549.1.7.2";
+const SPC_INDIRECT_DATA: &str = "1.3.6.1.4.1.311.2.1.4";
+const MESSAGE_DIGEST: &str = "1.2.840.113549.1.9.4";
+const RSA_ENCRYPTION: &str = "1.2.840.113549.1.1.1";
+const EC_PUBLIC_KEY: &str = "1.2.840.10045.2.1";
+const COMMON_NAME: &str = "2.5.4.3";
+const ORGANIZATION: &str = "2.5.4.10";
+
+/// The `WIN_CERTIFICATE` type of Authenticode signatures.
+const WIN_CERT_TYPE_PKCS_SIGNED_DATA: u16 = 2;
+
+/// The magic numbers of the Mach-O code signature blobs that are looked into.
+const CSMAGIC_EMBEDDED_SIGNATURE: u32 = 0xfade_0cc0;
+const CSMAGIC_CODEDIRECTORY: u32 = 0xfade_0c02;
+const CSMAGIC_BLOBWRAPPER: u32 = 0xfade_0b01;
+
+/// The slots of the code directory, the first alternate one and the CMS signature.
+const CSSLOT_CODEDIRECTORY: u32 = 0;
+const CSSLOT_ALTERNATE_CODEDIRECTORIES: u32 = 0x1000;
+const CSSLOT_SIGNATURESLOT: u32 = 0x10000;
+
+/// The code directory flag of signatures without a signer.
+const CS_ADHOC: u32 = 0x2;
+
+/// A part of a signature, and its size in the file.
+#[derive(Clone, Debug, Serialize)]
+pub struct SignatureBlob {
+    pub name: String,
+    pub size: u64,
+}
+
+/// The code signature of a PE image or a Mach-O file, as `--signature` records it.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Signature {
+    /// `valid` if the hashes that the signature covers match the file and the signer's RSA key
+    /// signed them, `invalid` if any doesn't match, `unverified` if the hashes match but some
+    /// algorithm isn't supported, or `unsigned`. The signer's certificate isn't checked
+    /// against any trusted root, so this doesn't say whether to trust the signer.
+    pub status: &'static str,
+    /// `authenticode` or `codesign`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<&'static str>,
+    /// The size of the signature in the file.
+    pub size: u64,
+    /// The parts of the signature, which add up to `size`.
+    pub blobs: Vec<SignatureBlob>,
+    /// The hash algorithm of the file's contents.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub digest: Option<&'static str>,
+    /// Whether the signature has no signer, as the linker signs arm64 macOS binaries: its
+    /// hashes only protect against corruption.
+    pub ad_hoc: bool,
+    /// The common name, or else the organization, of the signer's certificate and its issuer.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signer: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub issuer: Option<String>,
+    /// The identifier and team ID of a Mach-O code directory.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub identifier: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub team_id: Option<String>,
+    /// What doesn't match, or couldn't be checked.
+    pub problems: Vec<String>,
+}
+
+impl Signature {
+    fn new(kind: &'static str, size: u64) -> Signature {
+        Signature { status: "valid", kind: Some(kind), size, ..Default::default() }
+    }
+
+    fn unsigned() -> Signature {
+        Signature { status: "unsigned", ..Default::default() }
+    }
+
+    fn blob(&mut self, name: &str, size: u64) {
+        if size > 0 {
+            self.blobs.push(SignatureBlob { name: name.to_string(), size });
+        }
+    }
+
+    /// Record that something doesn't match.
+    fn fail(&mut self, problem: String) {
+        self.status = "invalid";
+        self.problems.push(problem);
+    }
+
+    /// Record that something couldn't be checked.
+    fn skip(&mut self, problem: String) {
+        if self.status == "valid" {
+            self.status = "unverified";
+        }
+        self.problems.push(problem);
+    }
+}
+
+fn u16_le(buf: &[u8], offset: usize) -> Option<u16> {
+    buf.get(offset..offset + 2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn u32_le(buf: &[u8], offset: usize) -> Option<u32> {
+    buf.get(offset..offset + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn u32_be(buf: &[u8], offset: usize) -> Option<u32> {
+    buf.get(offset..offset + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/// The parts of a PKCS #7 SignedData structure that are checked.
+struct SignedData<'a> {
+    content_type: Option<String>,
+    /// The signed content, if it isn't detached.
+    content: Option<Value<'a>>,
+    certificates: Vec<Value<'a>>,
+    certificates_size: u64,
+    signers: Vec<Value<'a>>,
+    signers_size: u64,
+}
+
+/// Parse the PKCS #7 ContentInfo `buf`, if it is SignedData.
+fn signed_data(buf: &[u8]) -> Option<SignedData<'_>> {
+    let content_info = der::read(buf)?.0.children_of(der::SEQUENCE)?;
+    if content_info.first()?.oid().as_deref() != Some(SIGNED_DATA) {
+        return None;
+    }
+    let fields = content_info.get(1)?.children().first()?.children_of(der::SEQUENCE)?;
+    // version, digestAlgorithms, encapContentInfo, [0] certificates, [1] crls, signerInfos.
+    let encap = fields.get(2)?.children();
+    let mut signed = SignedData {
+        content_type: encap.first().and_then(Value::oid),
+        content: encap.get(1).and_then(|content| content.children().first().cloned()),
+        certificates: Vec::new(),
+        certificates_size: 0,
+        signers: Vec::new(),
+        signers_size: 0,
+    };
+    for field in &fields[3..] {
+        if field.tag == der::context(0) {
+            signed.certificates = field.children();
+            signed.certificates_size = field.raw.len() as u64;
+        } else if field.tag == der::SET {
+            signed.signers = field.children();
+            signed.signers_size = field.raw.len() as u64;
+        }
+    }
+    Some(signed)
+}
+
+/// The common name in the X.509 name `name`, or else its organization.
+fn display_name(name: &Value) -> Option<String> {
+    let attributes: Vec<(String, String)> = name.children().iter()
+        .flat_map(|rdn| rdn.children())
+        .filter_map(|attribute| {
+            let parts = attribute.children();
+            Some((parts.first()?.oid()?, parts.get(1)?.string()?))
+        })
+        .collect();
+    [COMMON_NAME, ORGANIZATION].iter()
+        .find_map(|&oid| attributes.iter().find(|&(a, _)| a == oid))
+        .map(|(_, value)| value.clone())
+}
+
+/// Check that the first signer of `signed` signed `content`, and record who it is.
+fn check_signer(signed: &SignedData, content: &[u8], signature: &mut Signature) {
+    let signer = match signed.signers.first() {
+        Some(signer) => signer.children(),
+        None => return signature.fail("The signature has no signer".to_string()),
+    };
+    // version, sid, digestAlgorithm, [0] signedAttrs, signatureAlgorithm, signature.
+    let oid = signer.get(2).and_then(|id| id.children().first().and_then(Value::oid));
+    let algorithm = match oid.as_deref().and_then(Algorithm::from_oid) {
+        Some(algorithm) => algorithm,
+        None => {
+            return signature.skip(format!("The signer's digest algorithm {} isn't supported",
+                                          oid.unwrap_or_default()));
+        }
+    };
+    let attributes = signer.get(3).filter(|field| field.tag == der::context(0));
+    let rest = &signer[if attributes.is_some() { 4 } else { 3 }..];
+    let signed_hash = match attributes {
+        Some(attributes) => {
+            let message_digest = attributes.children().iter().find_map(|attribute| {
+                let parts = attribute.children();
+                if parts.first()?.oid().as_deref() != Some(MESSAGE_DIGEST) {
+                    return None;
+                }
+                Some(parts.get(1)?.children().first()?.contents)
+            });
+            if message_digest != Some(&algorithm.digest(&[content])[..]) {
+                signature.fail("The signed message digest doesn't match the content".to_string());
+            }
+            // The attributes are signed as a SET, not as the [0] field that holds them.
+            algorithm.digest(&[&[der::SET], &attributes.raw[1..]])
+        }
+        None => algorithm.digest(&[content]),
+    };
+
+    // Find the signer's certificate by issuer and serial number.
+    let sid = signer.get(1).map(Value::children).unwrap_or_default();
+    let certificate = signed.certificates.iter().find_map(|certificate| {
+        let tbs = certificate.children().first()?.children();
+        let fields = &tbs[if tbs.first()?.tag == der::context(0) { 1 } else { 0 }..];
+        // serialNumber, signature, issuer, validity, subject, subjectPublicKeyInfo.
+        if sid.len() == 2 && sid[0].raw == fields.get(2)?.raw && sid[1].raw == fields[0].raw {
+            Some(fields.to_vec())
+        } else {
+            None
+        }
+    });
+    let fields = match certificate {
+        Some(fields) => fields,
+        None => {
+            return signature.skip("The signer's certificate isn't in the signature".to_string());
+        }
+    };
+    signature.issuer = fields.get(2).and_then(display_name);
+    signature.signer = fields.get(4).and_then(display_name);
+
+    let key = fields.get(5).map(Value::children).unwrap_or_default();
+    let key_type = key.first().and_then(|id| id.children().first().and_then(Value::oid));
+    match key_type.as_deref() {
+        Some(RSA_ENCRYPTION) => {}
+        Some(EC_PUBLIC_KEY) => {
+            return signature.skip("The signer's EC key isn't supported".to_string());
+        }
+        key_type => {
+            return signature.skip(format!("The signer's {} key isn't supported",
+                                          key_type.unwrap_or("unknown")));
+        }
+    }
+    let rsa_key = key.get(1)
+        .filter(|bits| bits.tag == der::BIT_STRING && bits.contents.len() > 1)
+        .and_then(|bits| der::read(&bits.contents[1..]))
+        .and_then(|(key, _)| key.children_of(der::SEQUENCE));
+    let (modulus, exponent) = match rsa_key.as_deref() {
+        Some([modulus, exponent]) => (modulus.unsigned(), exponent.unsigned()),
+        _ => (None, None),
+    };
+    let value = rest.get(1).filter(|value| value.tag == der::OCTET_STRING);
+    match (modulus, exponent, value) {
+        (Some(modulus), Some(exponent), Some(value)) => {
+            if !rsa::verify(modulus, exponent, value.contents, algorithm, &signed_hash) {
+                signature.fail("The signer's signature doesn't match".to_string());
+            }
+        }
+        _ => signature.fail("The signer's key or signature is malformed".to_string()),
+    }
+}
+
+/// Check the Authenticode signature of the PE image `buf`: the hash of the image, leaving out
+/// the checksum, the certificate table and its directory entry, against the signed one.
+fn authenticode(buf: &[u8]) -> Result<Signature, Error> {
+    let pe = u32_le(buf, 0x3c).unwrap_or(0) as usize;
+    let optional = pe + 24;
+    let directories = match u16_le(buf, optional) {
+        Some(0x10b) => optional + 96,
+        Some(0x20b) => optional + 112,
+        _ => bail!("Invalid PE optional header"),
+    };
+    let entry = directories + 4 * 8;
+    if u32_le(buf, directories - 4).unwrap_or(0) <= 4 {
+        return Ok(Signature::unsigned());
+    }
+    let (offset, size) = match (u32_le(buf, entry), u32_le(buf, entry + 4)) {
+        (Some(offset), Some(size)) => (offset as usize, size as usize),
+        _ => bail!("Truncated PE data directories"),
+    };
+    if size == 0 {
+        return Ok(Signature::unsigned());
+    }
+    let table = match offset.checked_add(size).and_then(|end| buf.get(offset..end)) {
+        Some(table) => table,
+        None => bail!("The certificate table is outside the file"),
+    };
+
+    let mut signature = Signature::new("authenticode", size as u64);
+    let mut checked = false;
+    let (mut pos, mut used) = (0, 0);
+    while pos + 8 <= table.len() {
+        let len = u32_le(table, pos).unwrap() as usize;
+        let kind = u16_le(table, pos + 6).unwrap();
+        let data = match table.get(pos + 8..pos + len.max(8)) {
+            Some(data) => data,
+            None => {
+                signature.fail("A certificate table entry is truncated".to_string());
+                break;
+            }
+        };
+        match (kind, signed_data(data)) {
+            (WIN_CERT_TYPE_PKCS_SIGNED_DATA, Some(ref signed)) if !checked => {
+                checked = true;
+                let other = (len as u64).saturating_sub(signed.certificates_size +
+                                                        signed.signers_size);
+                signature.blob("certificates", signed.certificates_size);
+                signature.blob("signers", signed.signers_size);
+                signature.blob("signed-content", other);
+                check_image_hash(buf, signed, (optional + 64, entry, offset, size),
+                                 &mut signature);
+            }
+            _ => signature.blob(&format!("win-certificate-type-{}", kind), len as u64),
+        }
+        used += len.max(8);
+        // Entries are aligned to 8 bytes.
+        pos = (pos + len.max(8)).div_ceil(8) * 8;
+    }
+    signature.blob("padding", table.len().saturating_sub(used) as u64);
+    if !checked {
+        signature.fail("The certificate table has no PKCS #7 signature".to_string());
+    }
+    Ok(signature)
+}
+
+/// Check the image hash that `signed` holds against `buf`'s, leaving out the checksum at
+/// `checksum`, the certificate table directory entry at `entry`, and the table at `offset`,
+/// `size` bytes long, and check that the signer signed it.
+fn check_image_hash(buf: &[u8], signed: &SignedData,
+                    (checksum, entry, offset, size): (usize, usize, usize, usize),
+                    signature: &mut Signature) {
+    let content = match signed.content {
+        Some(content) if signed.content_type.as_deref() == Some(SPC_INDIRECT_DATA) => content,
+        _ => return signature.fail("The signed content isn't an Authenticode hash".to_string()),
+    };
+    // SpcIndirectDataContent: the type of file, then the DigestInfo.
+    let digest_info = content.children().get(1).map(Value::children).unwrap_or_default();
+    let oid = digest_info.first().and_then(|id| id.children().first().and_then(Value::oid));
+    match (oid.as_deref().and_then(Algorithm::from_oid), digest_info.get(1)) {
+        (Some(algorithm), Some(hash)) if entry + 8 <= offset => {
+            signature.digest = Some(algorithm.name());
+            let parts = [&buf[..checksum], &buf[checksum + 4..entry], &buf[entry + 8..offset],
+                         &buf[offset + size..]];
+            if algorithm.digest(&parts) != hash.contents {
+                signature.fail("The image hash doesn't match the signed hash".to_string());
+            }
+        }
+        (Some(_), Some(_)) => {
+            signature.fail("The certificate table overlaps the headers".to_string());
+        }
+        _ => signature.skip(format!("The image hash algorithm {} isn't supported",
+                                    oid.unwrap_or_default())),
+    }
+    // The signer signs the contents of the SpcIndirectDataContent, without its tag and length.
+    check_signer(signed, content.contents, signature);
+}
+
+/// The name of the blob in slot `slot` of a Mach-O code signature.
+fn blob_name(slot: u32) -> String {
+    match slot {
+        CSSLOT_CODEDIRECTORY => "code-directory".to_string(),
+        2 => "requirements".to_string(),
+        5 => "entitlements".to_string(),
+        7 => "der-entitlements".to_string(),
+        8..=11 => "launch-constraints".to_string(),
+        0x1000..=0x1004 => "alternate-code-directory".to_string(),
+        CSSLOT_SIGNATURESLOT => "cms-signature".to_string(),
+        _ => format!("blob-{:#x}", slot),
+    }
+}
+
+/// Check the code directory `cd` against the pages of `buf` and the blobs in `blobs` that it
+/// hashes, and record what it says about the code. Returns whether the hashes were checked.
+fn check_code_directory(buf: &[u8], cd: &[u8], blobs: &[(u32, &[u8])],
+                        signature: &mut Signature) -> bool {
+    let field = |offset| u32_be(cd, offset).unwrap_or(0);
+    let (version, flags, hash_offset) = (field(8), field(12), field(16) as usize);
+    let (special_slots, code_slots) = (field(24) as usize, field(28) as usize);
+    let mut code_limit = u64::from(field(32));
+    let (hash_size, hash_type, page_bits) = match cd.get(36..40) {
+        Some(bytes) => (usize::from(bytes[0]), bytes[1], bytes[3]),
+        None => {
+            signature.fail("The code directory is truncated".to_string());
+            return false;
+        }
+    };
+    if version >= 0x20300 {
+        if let Some(limit) = cd.get(56..64) {
+            let mut bytes = [0; 8];
+            bytes.copy_from_slice(limit);
+            if u64::from_be_bytes(bytes) != 0 {
+                code_limit = u64::from_be_bytes(bytes);
+            }
+        }
+    }
+    let string = |offset: u32| {
+        let start = offset as usize;
+        if offset == 0 || start >= cd.len() {
+            return None;
+        }
+        let len = cd[start..].iter().position(|&b| b == 0).unwrap_or(cd.len() - start);
+        Some(String::from_utf8_lossy(&cd[start..start + len]).into_owned())
+    };
+    if signature.identifier.is_none() {
+        signature.identifier = string(field(20));
+        signature.team_id = if version >= 0x20200 { string(field(48)) } else { None };
+        signature.ad_hoc = flags & CS_ADHOC != 0;
+    }
+
+    // SHA-256 truncated to 20 bytes is type 3; the hash size says how much to compare.
+    let algorithm = match hash_type {
+        1 => Algorithm::Sha1,
+        2 | 3 => Algorithm::Sha256,
+        _ => {
+            signature.skip(format!("Code directory hash type {} isn't supported", hash_type));
+            return false;
+        }
+    };
+    signature.digest.get_or_insert(algorithm.name());
+    let slot = |i: isize| {
+        let start = hash_offset as isize + i * hash_size as isize;
+        if start < 0 { None } else { cd.get(start as usize..start as usize + hash_size) }
+    };
+    let page = if page_bits == 0 { code_limit.max(1) } else { 1 << page_bits };
+    let mut mismatched = 0;
+    for i in 0..code_slots {
+        let start = (i as u64 * page).min(code_limit) as usize;
+        let end = ((i as u64 + 1) * page).min(code_limit) as usize;
+        let hash = buf.get(start..end).map(|data| algorithm.digest(&[data]));
+        if hash.as_ref().map(|hash| &hash[..hash_size.min(hash.len())]) != slot(i as isize) {
+            mismatched += 1;
+        }
+    }
+    if mismatched > 0 {
+        signature.fail(format!("{} of {} pages don't match the {} code directory", mismatched,
+                               code_slots, algorithm.name()));
+    }
+    // The blobs that the signature embeds are hashed in the special slots before the pages.
+    for &(kind, blob) in blobs {
+        if kind == 0 || kind as usize > special_slots {
+            continue;
+        }
+        let hash = algorithm.digest(&[blob]);
+        if Some(&hash[..hash_size.min(hash.len())]) != slot(-(kind as isize)) {
+            signature.fail(format!("The {} blob doesn't match the {} code directory",
+                                   blob_name(kind), algorithm.name()));
+        }
+    }
+    true
+}
+
+/// Check the code signature of the Mach-O file `mach`, read from `buf`: the hashes of its pages
+/// and blobs in each of its code directories, and the signer of the first.
+fn codesign(mach: &MachO, buf: &[u8]) -> Result<Signature, Error> {
+    let command = mach.load_commands.iter().find_map(|lc| match lc.command {
+        CommandVariant::CodeSignature(ref cmd) => Some((cmd.dataoff as usize, cmd.datasize)),
+        _ => None,
+    });
+    let (offset, size) = match command {
+        Some(command) => command,
+        None => return Ok(Signature::unsigned()),
+    };
+    let superblob = match buf.get(offset..offset + size as usize) {
+        Some(superblob) if u32_be(superblob, 0) == Some(CSMAGIC_EMBEDDED_SIGNATURE) => superblob,
+        _ => bail!("Invalid code signature"),
+    };
+    let mut signature = Signature::new("codesign", u64::from(size));
+    let count = u32_be(superblob, 8).unwrap_or(0) as usize;
+    let header = 12 + count * 8;
+    signature.blob("header", header.min(superblob.len()) as u64);
+    let mut blobs = Vec::new();
+    for i in 0..count {
+        let (kind, blob_offset) = match (u32_be(superblob, 12 + i * 8),
+                                         u32_be(superblob, 16 + i * 8)) {
+            (Some(kind), Some(offset)) => (kind, offset as usize),
+            _ => bail!("Truncated code signature"),
+        };
+        let len = u32_be(superblob, blob_offset + 4).unwrap_or(0) as usize;
+        match superblob.get(blob_offset..blob_offset + len) {
+            Some(blob) if len >= 8 => blobs.push((kind, blob)),
+            _ => bail!("Truncated code signature blob {}", blob_name(kind)),
+        }
+    }
+    let mut used = header;
+    for &(kind, blob) in &blobs {
+        signature.blob(&blob_name(kind), blob.len() as u64);
+        used += blob.len();
+    }
+    signature.blob("padding", superblob.len().saturating_sub(used) as u64);
+
+    let directories: Vec<&[u8]> = blobs.iter()
+        .filter(|&&(kind, blob)| {
+            (kind == CSSLOT_CODEDIRECTORY || kind & !0xf == CSSLOT_ALTERNATE_CODEDIRECTORIES) &&
+                u32_be(blob, 0) == Some(CSMAGIC_CODEDIRECTORY)
+        })
+        .map(|&(_, blob)| blob)
+        .collect();
+    if directories.is_empty() {
+        signature.fail("The code signature has no code directory".to_string());
+        return Ok(signature);
+    }
+    let mut checked = false;
+    for cd in &directories {
+        checked |= check_code_directory(buf, cd, &blobs, &mut signature);
+    }
+    if !checked {
+        return Ok(signature);
+    }
+
+    // The CMS signature signs the first code directory; ad hoc signatures leave it empty.
+    let cms = blobs.iter()
+        .find(|&&(kind, blob)| {
+            kind == CSSLOT_SIGNATURESLOT && u32_be(blob, 0) == Some(CSMAGIC_BLOBWRAPPER)
+        })
+        .map(|&(_, blob)| &blob[8..])
+        .filter(|cms| !cms.is_empty());
+    match (cms, signature.ad_hoc) {
+        (Some(cms), _) => match signed_data(cms) {
+            Some(ref signed) => check_signer(signed, directories[0], &mut signature),
+            None => signature.fail("The CMS signature is malformed".to_string()),
+        },
+        (None, false) => signature.fail("The code signature isn't ad hoc but has no \
+                                         signer".to_string()),
+        (None, true) => {}
+    }
+    Ok(signature)
+}
+
+/// Check the code signature of `buf`, an Authenticode-signed PE image or a signed Mach-O
+/// file, and size its parts.
+pub fn signature(buf: &[u8]) -> Result<Signature, Error> {
+    Ok(match Object::parse(buf)? {
+        Object::PE(_) => authenticode(buf)?,
+        Object::Mach(Mach::Binary(mach)) => codesign(&mach, buf)?,
+        Object::Mach(Mach::Fat(_)) => Signature {
+            status: "unverified",
+            kind: Some("codesign"),
+            problems: vec!["The slices of a universal binary are signed separately; extract \
+                            one with lipo -thin to check it".to_string()],
+            ..Default::default()
+        },
+        _ => Signature::unsigned(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::mach::constants::S_ATTR_PURE_INSTRUCTIONS;
+    use goblin::mach::load_command::LC_CODE_SIGNATURE;
+    use testmacho;
+
+    /// The requirements blob of a signature: its magic, length and no requirements.
+    const REQUIREMENTS: &[u8] = &[0xfa, 0xde, 0x0c, 0x01, 0, 0, 0, 12, 0, 0, 0, 0];
+
+    fn be32(out: &mut Vec<u8>, words: &[u32]) {
+        for word in words {
+            out.extend_from_slice(&word.to_be_bytes());
+        }
+    }
+
+    /// The superblob of a SHA-256 code signature, with a code directory of `flags` hashing
+    /// `code`, in one page, and `REQUIREMENTS`, then 8 bytes of padding.
+    fn superblob(code: &[u8], flags: u32) -> Vec<u8> {
+        let hash_offset = 48 + 4 + 2 * 32;
+        let mut cd = Vec::new();
+        be32(&mut cd, &[CSMAGIC_CODEDIRECTORY, hash_offset + 32, 0x20100, flags, hash_offset,
+                        48, 2, 1, code.len() as u32]);
+        // The hash size and type, the platform and the log2 of the page size.
+        cd.extend_from_slice(&[32, 2, 0, 12, 0, 0, 0, 0, 0, 0, 0, 0]);
+        cd.extend_from_slice(b"app\0");
+        cd.extend_from_slice(&Algorithm::Sha256.digest(&[REQUIREMENTS]));
+        cd.extend_from_slice(&[0; 32]);
+        cd.extend_from_slice(&Algorithm::Sha256.digest(&[code]));
+
+        let mut out = Vec::new();
+        be32(&mut out, &[CSMAGIC_EMBEDDED_SIGNATURE, 28 + cd.len() as u32 + 12 + 8, 2,
+                         CSSLOT_CODEDIRECTORY, 28, 2, 28 + cd.len() as u32]);
+        out.extend_from_slice(&cd);
+        out.extend_from_slice(REQUIREMENTS);
+        out.extend_from_slice(&[0; 8]);
+        out
+    }
+
+    /// A Mach-O executable signed with `superblob(_, flags)`, and the offset of the signature.
+    fn signed(flags: u32) -> (Vec<u8>, usize) {
+        let size = superblob(&[], flags).len();
+        let mut buf = testmacho::MachO::executable()
+            .section("__TEXT", "__text", S_ATTR_PURE_INSTRUCTIONS, &[0xc3; 64])
+            .linkedit_data(LC_CODE_SIGNATURE, &vec![0; size])
+            .build();
+        let offset = MachO::parse(&buf, 0).unwrap().load_commands.iter()
+            .find_map(|lc| match lc.command {
+                CommandVariant::CodeSignature(ref cmd) => Some(cmd.dataoff as usize),
+                _ => None,
+            })
+            .unwrap();
+        let blob = superblob(&buf[..offset], flags);
+        buf[offset..offset + size].copy_from_slice(&blob);
+        (buf, offset)
+    }
+
+    fn blobs(signature: &Signature) -> Vec<(&str, u64)> {
+        signature.blobs.iter().map(|b| (&*b.name, b.size)).collect()
+    }
+
+    #[test]
+    fn ad_hoc_code_signatures() {
+        let (buf, offset) = signed(CS_ADHOC);
+        let signature = signature(&buf).unwrap();
+        assert_eq!((signature.status, signature.kind), ("valid", Some("codesign")));
+        assert!(signature.problems.is_empty(), "{:?}", signature.problems);
+        assert!(signature.ad_hoc);
+        assert_eq!(signature.identifier.as_deref(), Some("app"));
+        assert_eq!(signature.digest, Some("sha256"));
+        assert_eq!(blobs(&signature), vec![("header", 28), ("code-directory", 148),
+                                           ("requirements", 12), ("padding", 8)]);
+        assert_eq!(signature.size, 28 + 148 + 12 + 8);
+
+        // A changed page, and a changed blob.
+        let mut corrupt = buf.clone();
+        corrupt[offset - 1] ^= 1;
+        let signature = super::signature(&corrupt).unwrap();
+        assert_eq!(signature.status, "invalid");
+        assert_eq!(signature.problems,
+                   vec!["1 of 1 pages don't match the sha256 code directory".to_string()]);
+        let mut corrupt = buf;
+        corrupt[offset + 28 + 148 + 11] = 1;
+        let signature = super::signature(&corrupt).unwrap();
+        assert_eq!(signature.problems,
+                   vec!["The requirements blob doesn't match the sha256 code directory"
+                        .to_string()]);
+    }
+
+    #[test]
+    fn unsigned_and_signerless_binaries() {
+        let (buf, _) = signed(0);
+        let signature = signature(&buf).unwrap();
+        assert_eq!(signature.status, "invalid");
+        assert_eq!(signature.problems,
+                   vec!["The code signature isn't ad hoc but has no signer".to_string()]);
+
+        let buf = testmacho::MachO::executable()
+            .section("__TEXT", "__text", S_ATTR_PURE_INSTRUCTIONS, &[0xc3; 64])
+            .build();
+        let signature = super::signature(&buf).unwrap();
+        assert_eq!((signature.status, signature.kind, signature.size), ("unsigned", None, 0));
+        assert_eq!(blob_name(0x10000), "cms-signature");
+        assert_eq!(blob_name(0x1001), "alternate-code-directory");
+    }
+}
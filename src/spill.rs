@@ -0,0 +1,233 @@
+use exit::UsageError;
+use failure::Error;
+use std::cmp::Reverse;
+use std::collections::{btree_map, BTreeMap, BinaryHeap};
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::iter::Peekable;
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The approximate memory a map entry takes on top of its name: the size, the `String` header
+/// and a share of the B-tree node.
+const ENTRY_OVERHEAD: usize = 48;
+
+/// Parse a memory limit given in bytes, optionally with a `K`, `M` or `G` suffix (powers of
+/// 1024), as in `512M`.
+pub fn parse_limit(s: &str) -> Result<usize, Error> {
+    let s = s.trim();
+    let (digits, multiplier) = match s.char_indices().last() {
+        Some((i, 'K')) | Some((i, 'k')) => (&s[..i], 1 << 10),
+        Some((i, 'M')) | Some((i, 'm')) => (&s[..i], 1 << 20),
+        Some((i, 'G')) | Some((i, 'g')) => (&s[..i], 1 << 30),
+        _ => (s, 1),
+    };
+    match digits.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n.saturating_mul(multiplier)),
+        _ => Err(UsageError(format!("Invalid memory limit: {}", s)).into()),
+    }
+}
+
+/// A temporary file holding a sorted run of (name, size) pairs, which is deleted when dropped.
+struct Run {
+    path: PathBuf,
+}
+
+impl Run {
+    fn write(entries: &BTreeMap<String, u64>) -> Result<Run, Error> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let path = env::temp_dir().join(format!("rust-size-{}-{}.run", process::id(),
+                                                COUNTER.fetch_add(1, Ordering::Relaxed)));
+        let run = Run { path };
+        let mut out = BufWriter::new(File::create(&run.path)?);
+        for (name, &size) in entries {
+            out.write_all(&size.to_le_bytes())?;
+            out.write_all(&(name.len() as u64).to_le_bytes())?;
+            out.write_all(name.as_bytes())?;
+        }
+        out.flush()?;
+        Ok(run)
+    }
+}
+
+impl Drop for Run {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Reads back the entries of a `Run`, in name order.
+struct RunReader {
+    input: BufReader<File>,
+    _run: Run,
+}
+
+impl RunReader {
+    fn read_u64(&mut self) -> io::Result<Option<u64>> {
+        let mut bytes = [0; 8];
+        match self.input.read_exact(&mut bytes) {
+            Ok(()) => Ok(Some(u64::from_le_bytes(bytes))),
+            Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn next_entry(&mut self) -> Result<Option<(String, u64)>, Error> {
+        let size = match self.read_u64()? {
+            Some(size) => size,
+            None => return Ok(None),
+        };
+        let len = match self.read_u64()? {
+            Some(len) => len as usize,
+            None => bail!("Truncated spill file"),
+        };
+        let mut name = vec![0; len];
+        self.input.read_exact(&mut name)?;
+        Ok(Some((String::from_utf8(name)?, size)))
+    }
+}
+
+/// Sums sizes by name, like building a `BTreeMap<String, u64>`, but keeps its memory use under
+/// a limit by spilling sorted runs of the map to temporary files whenever it grows too big.
+/// `finish` then merges the runs back into one sorted stream.
+pub struct SpillingMap {
+    limit: usize,
+    map: BTreeMap<String, u64>,
+    used: usize,
+    runs: Vec<Run>,
+}
+
+impl SpillingMap {
+    pub fn new(limit: usize) -> SpillingMap {
+        SpillingMap { limit, map: BTreeMap::new(), used: 0, runs: Vec::new() }
+    }
+
+    /// Add `size` to the total for `name`.
+    pub fn add(&mut self, name: &str, size: u64) -> Result<(), Error> {
+        if let Some(total) = self.map.get_mut(name) {
+            *total += size;
+            return Ok(());
+        }
+        self.map.insert(name.to_string(), size);
+        self.used += name.len() + ENTRY_OVERHEAD;
+        if self.used > self.limit {
+            self.runs.push(Run::write(&self.map)?);
+            self.map.clear();
+            self.used = 0;
+        }
+        Ok(())
+    }
+
+    /// Return the totals in name order.
+    pub fn finish(self) -> Result<SortedSizes, Error> {
+        let mut readers = Vec::new();
+        for run in self.runs {
+            readers.push(RunReader { input: BufReader::new(File::open(&run.path)?), _run: run });
+        }
+        let mut sorted = SortedSizes {
+            heap: BinaryHeap::new(),
+            readers,
+            memory: self.map.into_iter().peekable(),
+        };
+        for i in 0..sorted.readers.len() {
+            sorted.refill(i)?;
+        }
+        Ok(sorted)
+    }
+}
+
+/// The merged totals of a `SpillingMap`, as an iterator of (name, size) pairs in name order.
+pub struct SortedSizes {
+    /// The next entry of each run, with the index of its reader.
+    heap: BinaryHeap<Reverse<(String, u64, usize)>>,
+    readers: Vec<RunReader>,
+    /// The entries that were never spilled.
+    memory: Peekable<btree_map::IntoIter<String, u64>>,
+}
+
+impl SortedSizes {
+    fn refill(&mut self, reader: usize) -> Result<(), Error> {
+        if let Some((name, size)) = self.readers[reader].next_entry()? {
+            self.heap.push(Reverse((name, size, reader)));
+        }
+        Ok(())
+    }
+
+    /// The smallest next name among the runs and the in-memory entries.
+    fn peek_name(&mut self) -> Option<&str> {
+        let run = self.heap.peek().map(|Reverse((name, _, _))| name.as_str());
+        let memory = self.memory.peek().map(|(name, _)| name.as_str());
+        match (run, memory) {
+            (Some(a), Some(b)) => Some(if a <= b { a } else { b }),
+            (a, b) => a.or(b),
+        }
+    }
+
+    fn next_entry(&mut self) -> Result<Option<(String, u64)>, Error> {
+        let name = match self.peek_name() {
+            Some(name) => name.to_string(),
+            None => return Ok(None),
+        };
+        // The same name may have been spilled in several runs; sum all of them.
+        let mut total = 0;
+        while self.heap.peek().is_some_and(|Reverse((n, _, _))| *n == name) {
+            let Reverse((_, size, reader)) = self.heap.pop().unwrap();
+            total += size;
+            self.refill(reader)?;
+        }
+        if self.memory.peek().is_some_and(|(n, _)| *n == name) {
+            total += self.memory.next().unwrap().1;
+        }
+        Ok(Some((name, total)))
+    }
+}
+
+impl Iterator for SortedSizes {
+    type Item = Result<(String, u64), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_entry().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_limits() {
+        assert_eq!(parse_limit("4096").unwrap(), 4096);
+        assert_eq!(parse_limit("64k").unwrap(), 64 << 10);
+        assert_eq!(parse_limit(" 512M").unwrap(), 512 << 20);
+        assert_eq!(parse_limit("2G").unwrap(), 2 << 30);
+        for s in &["0", "0M", "", "M", "1.5G", "-1", "12T"] {
+            let err = parse_limit(s).unwrap_err();
+            assert!(err.downcast_ref::<UsageError>().is_some(), "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn spilled_runs_merge_back_in_order() {
+        let names = ["b", "a", "c", "b", "d", "a", "e", "b", "a", "f"];
+        let mut expected = BTreeMap::new();
+        // Room for about two entries, so most of them are spilled, in several runs each.
+        let mut map = SpillingMap::new(2 * (1 + ENTRY_OVERHEAD));
+        for (i, name) in names.iter().enumerate() {
+            map.add(name, i as u64).unwrap();
+            *expected.entry(name.to_string()).or_insert(0) += i as u64;
+        }
+        assert!(map.runs.len() >= 3);
+        let runs: Vec<PathBuf> = map.runs.iter().map(|run| run.path.clone()).collect();
+        let sorted: Vec<(String, u64)> = map.finish().unwrap().map(Result::unwrap).collect();
+        assert_eq!(sorted, expected.into_iter().collect::<Vec<_>>());
+        assert!(runs.iter().all(|path| !path.exists()));
+
+        let mut map = SpillingMap::new(1 << 20);
+        map.add("x", 1).unwrap();
+        map.add("x", 2).unwrap();
+        assert_eq!(map.finish().unwrap().map(Result::unwrap).collect::<Vec<_>>(),
+                   vec![("x".to_string(), 3)]);
+    }
+}
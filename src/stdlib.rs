@@ -0,0 +1,165 @@
+use failure::Error;
+use group;
+use runtime;
+use rustc_demangle;
+use std::collections::BTreeMap;
+use symbols;
+use thunks::KindSize;
+
+/// The crates of the standard library that a `no_std` binary still links.
+const CORE_CRATES: &[&str] = &["core", "alloc", "compiler_builtins"];
+
+/// The crates of the standard library that only `std` brings in: `std` itself, its panic and
+/// unwind runtimes and the crates it symbolizes backtraces with. Binaries can depend on the
+/// latter themselves, which their symbols' names don't tell apart.
+const STD_CRATES: &[&str] = &["std", "std_detect", "panic_unwind", "panic_abort", "unwind",
+                              "addr2line", "gimli", "object", "miniz_oxide", "adler", "adler2",
+                              "rustc_demangle"];
+
+/// The split of a binary's symbols between the standard library and the binary's own code, as
+/// emitted by `std --format json`.
+#[derive(Clone, Debug, Serialize)]
+pub struct StdReport {
+    /// The crates of the standard library, by name.
+    pub crates: BTreeMap<String, KindSize>,
+    /// The size of the standard library's own symbols.
+    pub std_size: u64,
+    /// The size of the standard library's generic functions and types instantiated for the
+    /// binary's own types, like `Vec<MyType>::push`, which the binary would have in some form
+    /// without `std` too. Legacy mangling leaves the generic arguments out of most names, so
+    /// this is only complete for binaries built with `-C symbol-mangling-version=v0`.
+    pub generic_size: u64,
+    /// The size of the runtime libraries that aren't Rust: the C allocator, the unwinder and
+    /// the compiler builtins (see `runtime::kind`).
+    pub runtime_size: u64,
+    /// The size of the binary's own Rust code and data.
+    pub user_size: u64,
+    /// The size of the other symbols, like those of C and C++ code.
+    pub other_size: u64,
+    /// The size of all functions and data symbols, counting aliases once.
+    pub symbol_size: u64,
+    /// `symbol_size` without the crates that only `std` brings in and the unwinder, as a rough
+    /// estimate of the size of the same binary built `no_std`.
+    pub no_std_estimate: u64,
+}
+
+/// Whether `krate` is a crate of the standard library.
+fn is_std_crate(krate: &str) -> bool {
+    CORE_CRATES.contains(&krate) || STD_CRATES.contains(&krate)
+}
+
+/// The crates that the paths in the demangled `name` start with, like `alloc` and `app` in
+/// `alloc::vec::Vec<app::Item>::push`.
+fn crates_in(name: &str) -> impl Iterator<Item = &str> {
+    name.match_indices("::").filter_map(move |(i, _)| {
+        let start = name[..i].rfind(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .map_or(0, |j| j + 1);
+        let before = name[..start].chars().next_back();
+        if start == i || before == Some(':') { None } else { Some(&name[start..i]) }
+    })
+}
+
+/// Attribute the symbols of the Rust binary `buf` to the crates of the standard library, the
+/// runtime libraries or the binary's own code, and estimate what the binary would weigh without
+/// `std`.
+pub fn std_split(buf: &[u8]) -> Result<StdReport, Error> {
+    let mut syms = symbols::symbols(buf)?;
+    // Aliases share their symbol's address; keep the largest of each.
+    syms.sort_by(|a, b| {
+        (&a.section, a.address, b.size, &a.name).cmp(&(&b.section, b.address, a.size, &b.name))
+    });
+    syms.dedup_by(|a, b| a.section == b.section && a.address == b.address);
+
+    let symbol_size = syms.iter().map(|sym| sym.size).sum();
+    let mut report = StdReport {
+        crates: BTreeMap::new(),
+        std_size: 0,
+        generic_size: 0,
+        runtime_size: 0,
+        user_size: 0,
+        other_size: 0,
+        symbol_size,
+        no_std_estimate: symbol_size,
+    };
+    for sym in syms {
+        let demangled = match group::language(&sym.name) {
+            "Rust" => Some(format!("{:#}", rustc_demangle::demangle(&sym.name))),
+            _ => None,
+        };
+        let krate = demangled.as_ref().and_then(|name| group::rust_crate(name));
+        match (krate, &demangled) {
+            (Some(krate), Some(name)) if is_std_crate(krate) => {
+                if crates_in(name).any(|other| !is_std_crate(other)) {
+                    report.generic_size += sym.size;
+                    continue;
+                }
+                let totals = report.crates.entry(krate.to_string()).or_default();
+                totals.count += 1;
+                totals.size += sym.size;
+                report.std_size += sym.size;
+                if STD_CRATES.contains(&krate) {
+                    report.no_std_estimate -= sym.size;
+                }
+            }
+            (Some(_), _) => report.user_size += sym.size,
+            _ => match runtime::kind(&sym.name) {
+                Some(kind) => {
+                    report.runtime_size += sym.size;
+                    if kind == "unwinder" {
+                        report.no_std_estimate -= sym.size;
+                    }
+                }
+                None => report.other_size += sym.size,
+            },
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::elf::section_header::{SHF_ALLOC, SHF_EXECINSTR, SHT_PROGBITS};
+    use goblin::elf::sym::STT_FUNC;
+    use testelf::Elf;
+
+    /// The legacy Rust mangling of `path`, whose components are already escaped.
+    fn mangled(path: &[&str]) -> String {
+        let components: String = path.iter().map(|c| format!("{}{}", c.len(), c)).collect();
+        format!("_ZN{}17h0123456789abcdefE", components)
+    }
+
+    #[test]
+    fn crates_in_paths() {
+        let crates: Vec<_> = crates_in("alloc::vec::Vec<app::Item>::push").collect();
+        assert_eq!(crates, vec!["alloc", "app"]);
+        let crates: Vec<_> = crates_in("<&mut std::io::Stdout as core::fmt::Write>::write_str")
+            .collect();
+        assert_eq!(crates, vec!["std", "core"]);
+        assert_eq!(crates_in("main").count(), 0);
+        assert!(is_std_crate("compiler_builtins") && is_std_crate("gimli"));
+        assert!(!is_std_crate("app"));
+    }
+
+    #[test]
+    fn std_and_user_code() {
+        let drop = "$LT$alloc..vec..Vec$LT$app..Item$GT$$u20$as$u20$core..ops..drop..Drop$GT$";
+        let buf = Elf::object()
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &[0; 96])
+            .symbol(&mangled(&["app", "main"]), STT_FUNC, ".text", 0, 16)
+            .symbol(&mangled(&["core", "fmt", "write"]), STT_FUNC, ".text", 16, 8)
+            .symbol(&mangled(&["std", "io", "stdio", "_print"]), STT_FUNC, ".text", 24, 12)
+            .symbol(&mangled(&[drop, "drop"]), STT_FUNC, ".text", 36, 4)
+            .symbol("malloc", STT_FUNC, ".text", 40, 10)
+            .symbol("_Unwind_Resume", STT_FUNC, ".text", 50, 6)
+            .symbol("sqlite3_open", STT_FUNC, ".text", 56, 20)
+            .build();
+        let report = std_split(&buf).unwrap();
+        let crates: Vec<_> = report.crates.iter().map(|(k, s)| (&**k, s.count, s.size)).collect();
+        assert_eq!(crates, vec![("core", 1, 8), ("std", 1, 12)]);
+        assert_eq!((report.std_size, report.generic_size, report.runtime_size), (20, 4, 16));
+        assert_eq!((report.user_size, report.other_size, report.symbol_size), (16, 20, 76));
+        // Without `std` and the unwinder.
+        assert_eq!(report.no_std_estimate, 76 - 12 - 6);
+    }
+}
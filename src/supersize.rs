@@ -0,0 +1,199 @@
+use digest::crc32;
+use dwarf;
+use failure::Error;
+use group::Grouper;
+use miniz_oxide::deflate::compress_to_vec;
+use rustc_demangle;
+use serde_json;
+use std::collections::BTreeMap;
+use symbols::Symbol;
+use {section_records, Section, CODE_FLAGS};
+
+/// The version line of the `.size` files that SuperSize writes for a single binary.
+const VERSION: &str = "Size File Format v1";
+
+/// The deflate level to compress the archive with.
+const LEVEL: u8 = 9;
+
+/// The header of a `.size` file, which SuperSize reads before the symbols.
+#[derive(Serialize)]
+struct Headers {
+    metadata: BTreeMap<&'static str, String>,
+    section_sizes: BTreeMap<&'static str, u64>,
+    has_components: bool,
+    has_padding: bool,
+}
+
+/// The SuperSize section that the section `name` of `category` goes in, with `code` if it holds
+/// code. SuperSize only knows the sections of ELF binaries, so the others go in the ones like
+/// them.
+fn supersize_section(category: Section, name: &str, code: bool) -> &'static str {
+    match category {
+        Section::Text if code => ".text",
+        Section::Text => ".rodata",
+        Section::Data if name.starts_with(".data.rel.ro") => ".data.rel.ro",
+        Section::Data => ".data",
+        Section::Bss => ".bss",
+        Section::Other => ".other",
+    }
+}
+
+/// `data` compressed as a gzip file.
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 2, 0xff];
+    out.extend_from_slice(&compress_to_vec(data, LEVEL));
+    out.extend_from_slice(&crc32(data).to_le_bytes());
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out
+}
+
+/// A symbol of a `.size` file: its address, size, name, path and component.
+type ArchivedSymbol = (u64, u64, String, String, String);
+
+/// `values` as a line of the `.size` file, each as the difference from the one before with
+/// `delta`.
+fn numbers<I: Iterator<Item = u64>>(values: I, delta: bool) -> String {
+    let mut previous = 0;
+    let values: Vec<String> = values.map(|value| {
+        let number = if delta { value as i64 - previous as i64 } else { value as i64 };
+        previous = value;
+        number.to_string()
+    }).collect();
+    values.join(" ")
+}
+
+/// The `.size` archive of the binary `buf`, with its symbols `symbols`, for SuperSize's viewer:
+/// the symbols, demangled, by section, with the source files of their compile units as their
+/// paths, where there is debug info, and the groups `grouper` puts them in as their components.
+/// Aliases count once. `metadata` is shown with the archive.
+pub fn archive(buf: &[u8], mut symbols: Vec<Symbol>, grouper: &Grouper,
+               metadata: BTreeMap<&'static str, String>) -> Result<Vec<u8>, Error> {
+    let mut supersize_sections = BTreeMap::new();
+    let mut section_sizes = BTreeMap::new();
+    for record in section_records(buf)? {
+        if record.category == Section::Other {
+            continue;
+        }
+        let code = record.flag_names.iter().any(|flag| CODE_FLAGS.contains(flag));
+        let section = supersize_section(record.category, &record.name, code);
+        *section_sizes.entry(section).or_insert(0) += record.size;
+        supersize_sections.insert(record.name, section);
+    }
+    let units = dwarf::compile_unit_ranges(buf).unwrap_or_default();
+
+    symbols.sort_by(|a, b| {
+        (&a.section, a.address, b.size, &a.name).cmp(&(&b.section, b.address, a.size, &b.name))
+    });
+    symbols.dedup_by(|a, b| a.section == b.section && a.address == b.address);
+    let mut sections: BTreeMap<&str, Vec<ArchivedSymbol>> = BTreeMap::new();
+    for sym in &symbols {
+        let section = match sym.section.as_ref().and_then(|s| supersize_sections.get(s)) {
+            Some(&section) => section,
+            None => continue,
+        };
+        let name = format!("{:#}", rustc_demangle::demangle(&sym.name)).replace(['\t', '\n'], " ");
+        let path = dwarf::unit_at(&units, sym.address).map_or("", |unit| &unit.name).to_string();
+        sections.entry(section).or_default()
+            .push((sym.address, sym.size, name, path, grouper.group(sym)));
+    }
+    for symbols in sections.values_mut() {
+        symbols.sort_by(|a, b| (a.0, &a.2).cmp(&(b.0, &b.2)));
+    }
+
+    let paths: Vec<&String> = {
+        let mut paths: Vec<&String> = sections.values().flatten().map(|sym| &sym.3).collect();
+        paths.sort();
+        paths.dedup();
+        paths
+    };
+    let components: Vec<&String> = {
+        let mut components: Vec<&String> =
+            sections.values().flatten().map(|sym| &sym.4).collect();
+        components.sort();
+        components.dedup();
+        components
+    };
+    let index = |list: &[&String], value: &String| {
+        list.binary_search(&value).unwrap_or_default() as u64
+    };
+
+    let headers = serde_json::to_string_pretty(&Headers {
+        metadata,
+        section_sizes,
+        has_components: true,
+        has_padding: false,
+    })?;
+    let mut lines = vec![
+        "# Created by rust-size".to_string(),
+        VERSION.to_string(),
+        headers.len().to_string(),
+        headers,
+        paths.len().to_string(),
+    ];
+    // There are no object files in a linked binary to name, only source files.
+    lines.extend(paths.iter().map(|path| format!("\t{}", path)));
+    lines.push(components.len().to_string());
+    lines.extend(components.iter().map(|component| component.to_string()));
+    lines.push(sections.keys().cloned().collect::<Vec<_>>().join("\t"));
+    lines.push(sections.values().map(|symbols| symbols.len().to_string())
+               .collect::<Vec<_>>().join("\t"));
+    lines.extend(sections.values().map(|symbols| numbers(symbols.iter().map(|s| s.0), true)));
+    lines.extend(sections.values().map(|symbols| numbers(symbols.iter().map(|s| s.1), false)));
+    lines.extend(sections.values().map(|symbols| {
+        numbers(symbols.iter().map(|s| index(&paths, &s.3)), true)
+    }));
+    lines.extend(sections.values().map(|symbols| {
+        numbers(symbols.iter().map(|s| index(&components, &s.4)), true)
+    }));
+    lines.extend(sections.values().flatten().map(|sym| sym.2.clone()));
+    let mut text = lines.join("\n");
+    text.push('\n');
+    Ok(gzip(text.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use decompress::Input;
+    use goblin::elf::section_header::{SHF_ALLOC, SHF_EXECINSTR, SHF_WRITE, SHT_PROGBITS};
+    use goblin::elf::sym::{STT_FUNC, STT_OBJECT};
+    use group::{GroupBy, Rules};
+    use symbols::symbols;
+    use testelf::Elf;
+
+    #[test]
+    fn numbers_and_their_deltas() {
+        assert_eq!(numbers(vec![16, 24, 24, 64].into_iter(), true), "16 8 0 40");
+        assert_eq!(numbers(vec![16, 8, 0].into_iter(), true), "16 -8 -8");
+        assert_eq!(numbers(vec![16, 8, 0].into_iter(), false), "16 8 0");
+        assert_eq!(numbers(Vec::new().into_iter(), true), "");
+    }
+
+    #[test]
+    fn archives_symbols_by_section() {
+        let buf = Elf::object()
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &[0; 64])
+            .section(".data", SHT_PROGBITS, SHF_ALLOC | SHF_WRITE, &[0; 8])
+            .section(".comment", SHT_PROGBITS, 0, &[0; 5])
+            .symbol("_ZN3foo3bar17h0123456789abcdefE", STT_FUNC, ".text", 16, 32)
+            .symbol("main", STT_FUNC, ".text", 0, 16)
+            .symbol("main_alias", STT_FUNC, ".text", 0, 16)
+            .symbol("COUNT", STT_OBJECT, ".data", 4, 4)
+            .build();
+        let rules = Rules::default();
+        let grouper = Grouper::new(&buf, GroupBy::Crate, &rules).unwrap();
+        let metadata = vec![("filename", "a.o".to_string())].into_iter().collect();
+        let archive = archive(&buf, symbols(&buf).unwrap(), &grouper, metadata).unwrap();
+        let text = String::from_utf8(Input::from_vec(archive).unwrap().to_vec()).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[..3], ["# Created by rust-size", VERSION, "153"]);
+        let headers: serde_json::Value = serde_json::from_str(&lines[3..14].join("\n")).unwrap();
+        assert_eq!(headers["section_sizes"], serde_json::json!({".data": 8, ".text": 64}));
+        // One path, the empty one, as there is no debug info; the components; the symbol
+        // counts of each section; their addresses, as deltas; their sizes; the deltas of their
+        // path and component indexes, and their names. The alias counts once.
+        assert_eq!(lines[14..], ["1", "\t", "2", "(no crate)", "foo", ".data\t.text", "1\t2",
+                                 "4", "0 16", "4", "16 32", "0", "0 0", "0", "0 1",
+                                 "COUNT", "main", "foo::bar"]);
+    }
+}
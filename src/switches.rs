@@ -0,0 +1,506 @@
+use failure::Error;
+use goblin::elf::header::{EM_386, EM_AARCH64, EM_RISCV, EM_X86_64, ET_REL};
+use goblin::elf::section_header::SHT_PROGBITS;
+use goblin::Object;
+use mapping;
+use rustc_demangle;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use symbols;
+
+/// The fewest entries that compilers lower a `match` or `switch` to a jump table for.
+const MIN_ENTRIES: u64 = 4;
+
+/// The entries from which a table that mostly jumps to one target is worth a second look.
+const SPARSE_MIN_ENTRIES: u64 = 32;
+
+/// A jump table that a `match` or `switch` was lowered to.
+#[derive(Clone, Debug, Serialize)]
+pub struct JumpTable {
+    pub section: String,
+    /// The address of the table, relative to its section in relocatable ELF objects.
+    pub address: u64,
+    pub size: u64,
+    /// The number of entries, if known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entries: Option<u64>,
+    /// The number of distinct targets of the entries, if known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub targets: Option<u64>,
+    /// The number of entries that jump to the most common target, typically the default arm.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_entries: Option<u64>,
+    /// What found the table: `relocations` (in objects), `offsets` and `addresses` (tables of
+    /// offsets from the table or addresses into one function, in linked files), or
+    /// `data-in-code` (tables embedded in code, as `data-in-code` finds them).
+    pub source: &'static str,
+}
+
+impl JumpTable {
+    /// Whether the table is large but mostly jumps to one target, so that a binary search or a
+    /// lookup table would likely be smaller.
+    pub fn is_sparse(&self) -> bool {
+        match (self.entries, self.default_entries) {
+            (Some(entries), Some(default)) => {
+                entries >= SPARSE_MIN_ENTRIES && 2 * default >= entries
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The jump tables of a function.
+#[derive(Clone, Debug, Serialize)]
+pub struct FunctionTables {
+    /// The demangled name, without the hash.
+    pub name: String,
+    pub function_size: u64,
+    /// The sum of the sizes of the tables.
+    pub size: u64,
+    /// Whether any of the tables is sparse (see `JumpTable::is_sparse`).
+    pub sparse: bool,
+    pub tables: Vec<JumpTable>,
+}
+
+/// The jump tables of a binary, as emitted by `jump-tables --format json`.
+#[derive(Clone, Debug, Serialize)]
+pub struct JumpTableReport {
+    /// The functions with jump tables, largest tables first.
+    pub functions: Vec<FunctionTables>,
+    /// The tables whose function no symbol covers.
+    pub unattributed: Vec<JumpTable>,
+    pub tables: u64,
+    pub size: u64,
+}
+
+/// The code symbols of a file, for finding the function that contains an address.
+struct Functions {
+    /// The address, size and name of the functions in each section, by address.
+    sections: HashMap<String, Vec<(u64, u64, String)>>,
+}
+
+impl Functions {
+    fn new(buf: &[u8]) -> Result<Functions, Error> {
+        let mut sections: HashMap<String, Vec<(u64, u64, String)>> = HashMap::new();
+        for sym in symbols::symbols(buf)? {
+            if let (true, Some(section)) = (sym.code, sym.section) {
+                sections.entry(section).or_default().push((sym.address, sym.size, sym.name));
+            }
+        }
+        for functions in sections.values_mut() {
+            functions.sort();
+        }
+        Ok(Functions { sections })
+    }
+
+    /// The index of the function in `section` that contains `address`.
+    fn at(&self, section: &str, address: u64) -> Option<usize> {
+        let functions = self.sections.get(section)?;
+        let i = functions.partition_point(|&(start, _, _)| start <= address).checked_sub(1)?;
+        let (start, size, _) = functions[i];
+        if address < start + size { Some(i) } else { None }
+    }
+}
+
+/// The size of the data relocations of `machine` that jump table entries use, and whether they
+/// are PC-relative.
+fn table_relocation(machine: u16, r_type: u32) -> Option<(u64, bool)> {
+    match (machine, r_type) {
+        (EM_X86_64, 1) => Some((8, false)),
+        (EM_X86_64, 2) => Some((4, true)),
+        (EM_X86_64, 10) | (EM_X86_64, 11) => Some((4, false)),
+        (EM_386, 1) => Some((4, false)),
+        (EM_386, 2) => Some((4, true)),
+        (EM_AARCH64, 257) => Some((8, false)),
+        (EM_AARCH64, 258) => Some((4, false)),
+        (EM_AARCH64, 261) => Some((4, true)),
+        (EM_RISCV, 1) => Some((4, false)),
+        (EM_RISCV, 2) => Some((8, false)),
+        (EM_RISCV, 57) => Some((4, true)),
+        _ => None,
+    }
+}
+
+/// A table being collected: its section, bounds, entry size, function and the targets of its
+/// entries.
+struct Run {
+    section: String,
+    start: u64,
+    end: u64,
+    entry_size: u64,
+    function: (String, usize),
+    targets: Vec<u64>,
+}
+
+impl Run {
+    fn table(&self, source: &'static str) -> JumpTable {
+        let mut counts: BTreeMap<u64, u64> = BTreeMap::new();
+        for &target in &self.targets {
+            *counts.entry(target).or_insert(0) += 1;
+        }
+        JumpTable {
+            section: self.section.clone(),
+            address: self.start,
+            size: self.end - self.start,
+            entries: Some(self.targets.len() as u64),
+            targets: Some(counts.len() as u64),
+            default_entries: counts.values().max().cloned(),
+            source,
+        }
+    }
+}
+
+/// Collect `runs` of at least `MIN_ENTRIES` entries into `tables`, with their functions.
+fn finish(runs: Vec<Run>, source: &'static str, tables: &mut Vec<((String, usize), JumpTable)>) {
+    for run in runs {
+        if run.targets.len() as u64 >= MIN_ENTRIES {
+            tables.push((run.function.clone(), run.table(source)));
+        }
+    }
+}
+
+/// Find the jump tables that the relocations of the ELF object `elf` describe: runs of
+/// relocations in data sections that point into a single function. Code that loads the address
+/// of a table starts a new one, so that adjacent tables of one function stay apart.
+fn relocated_tables(elf: &goblin::elf::Elf, buf: &[u8], functions: &Functions,
+                    tables: &mut Vec<((String, usize), JumpTable)>) {
+    let machine = elf.header.e_machine;
+    let section_name = |shndx: usize| {
+        elf.section_headers.get(shndx)
+            .and_then(|sh| elf.shdr_strtab.get(sh.sh_name))
+            .and_then(|res| res.ok())
+    };
+    // REL relocations keep their addends in the bytes they apply to.
+    let addend = |target: usize, reloc: &goblin::elf::Reloc, size: u64| {
+        reloc.r_addend.unwrap_or_else(|| {
+            let sh = &elf.section_headers[target];
+            let offset = (sh.sh_offset + reloc.r_offset) as usize;
+            match buf.get(offset..offset + size as usize) {
+                Some(&[a, b, c, d]) => i64::from(i32::from_le_bytes([a, b, c, d])),
+                Some(&[a, b, c, d, e, f, g, h]) => i64::from_le_bytes([a, b, c, d, e, f, g, h]),
+                _ => 0,
+            }
+        })
+    };
+
+    // The addresses in each data section that code refers to.
+    let mut starts: HashMap<usize, BTreeSet<u64>> = HashMap::new();
+    for &(idx, ref relocs) in &elf.shdr_relocs {
+        let target = elf.section_headers[idx].sh_info as usize;
+        if !elf.section_headers.get(target).is_some_and(|sh| sh.is_executable()) {
+            continue;
+        }
+        for reloc in relocs {
+            let sym = match elf.syms.get(reloc.r_sym) {
+                Some(sym) => sym,
+                None => continue,
+            };
+            // PC-relative references are relative to the end of the 4-byte immediate.
+            let (size, pc_relative) = table_relocation(machine, reloc.r_type).unwrap_or((4, false));
+            let address = sym.st_value as i64 + addend(target, reloc, size) +
+                if pc_relative { 4 } else { 0 };
+            starts.entry(sym.st_shndx).or_default().insert(address as u64);
+        }
+    }
+
+    for &(idx, ref relocs) in &elf.shdr_relocs {
+        let target = elf.section_headers[idx].sh_info as usize;
+        let section = match elf.section_headers.get(target) {
+            Some(sh) if sh.is_alloc() && !sh.is_executable() => section_name(target).unwrap_or(""),
+            _ => continue,
+        };
+        let mut relocs: Vec<_> = relocs.iter().collect();
+        relocs.sort_by_key(|reloc| reloc.r_offset);
+        let mut runs: Vec<Run> = Vec::new();
+        for reloc in relocs {
+            let (size, pc_relative) = match table_relocation(machine, reloc.r_type) {
+                Some(entry) => entry,
+                None => continue,
+            };
+            let sym = match elf.syms.get(reloc.r_sym) {
+                Some(sym) => sym,
+                None => continue,
+            };
+            let code = match section_name(sym.st_shndx) {
+                Some(name) if elf.section_headers[sym.st_shndx].is_executable() => name,
+                _ => continue,
+            };
+            let value = sym.st_value as i64 + addend(target, reloc, size);
+            // PC-relative entries are offsets from the start of the table, which the addend
+            // makes up for by the distance from there to the entry.
+            let target_of = |start: u64| {
+                if pc_relative {
+                    (value - (reloc.r_offset - start) as i64) as u64
+                } else {
+                    value as u64
+                }
+            };
+            let starts_table = starts.get(&target).is_some_and(|s| s.contains(&reloc.r_offset));
+            if let Some(run) = runs.last_mut() {
+                let address = target_of(run.start);
+                if !starts_table && run.end == reloc.r_offset && run.entry_size == size &&
+                    functions.at(code, address).map(|i| (code, i)) ==
+                        Some((&run.function.0, run.function.1)) {
+                    run.end += size;
+                    run.targets.push(address);
+                    continue;
+                }
+            }
+            let address = target_of(reloc.r_offset);
+            if let Some(function) = functions.at(code, address) {
+                runs.push(Run {
+                    section: section.to_string(),
+                    start: reloc.r_offset,
+                    end: reloc.r_offset + size,
+                    entry_size: size,
+                    function: (code.to_string(), function),
+                    targets: vec![address],
+                });
+            }
+        }
+        finish(runs, "relocations", tables);
+    }
+}
+
+/// Find the jump tables in the read-only data of the linked ELF file `elf`: runs of 32-bit
+/// offsets from the start of the run, as position-independent code uses, or of addresses, that
+/// all land in one function.
+fn scanned_tables(elf: &goblin::elf::Elf, buf: &[u8], functions: &Functions,
+                  tables: &mut Vec<((String, usize), JumpTable)>) {
+    let code: Vec<(u64, u64, &str)> = elf.section_headers.iter()
+        .filter(|sh| sh.is_executable())
+        .filter_map(|sh| {
+            let name = elf.shdr_strtab.get(sh.sh_name)?.ok()?;
+            Some((sh.sh_addr, sh.sh_addr + sh.sh_size, name))
+        })
+        .collect();
+    let function_at = |address: u64| {
+        let &(_, _, name) = code.iter()
+            .find(|&&(start, end, _)| start <= address && address < end)?;
+        functions.at(name, address).map(|i| (name, i))
+    };
+    let pointer_size = if elf.is_64 { 8 } else { 4 };
+    let read = |data: &[u8], size: usize| {
+        let mut bytes = [0; 8];
+        if elf.little_endian {
+            bytes[..size].copy_from_slice(&data[..size]);
+            u64::from_le_bytes(bytes)
+        } else {
+            bytes[8 - size..].copy_from_slice(&data[..size]);
+            u64::from_be_bytes(bytes)
+        }
+    };
+
+    for sh in &elf.section_headers {
+        if sh.sh_type != SHT_PROGBITS || !sh.is_alloc() || sh.is_executable() || sh.is_writable() {
+            continue;
+        }
+        let section = elf.shdr_strtab.get(sh.sh_name).and_then(|res| res.ok()).unwrap_or("");
+        let data = match buf.get(sh.sh_offset as usize..(sh.sh_offset + sh.sh_size) as usize) {
+            Some(data) => data,
+            None => continue,
+        };
+        let mut i = 0;
+        while i + 4 <= data.len() {
+            let start = sh.sh_addr + i as u64;
+            let mut found = None;
+            for &(size, relative) in &[(4, true), (pointer_size, false)] {
+                let entry = |j: usize| -> Option<u64> {
+                    let word = data.get(j..j + size)?;
+                    Some(if relative {
+                        (start as i64 + i64::from(read(word, 4) as u32 as i32)) as u64
+                    } else {
+                        read(word, size)
+                    })
+                };
+                let (first, function) = match entry(i).and_then(|a| Some((a, function_at(a)?))) {
+                    Some(first) => first,
+                    None => continue,
+                };
+                let mut targets = vec![first];
+                let mut j = i + size;
+                while let Some(address) = entry(j) {
+                    if function_at(address) != Some(function) {
+                        break;
+                    }
+                    targets.push(address);
+                    j += size;
+                }
+                if targets.len() as u64 >= MIN_ENTRIES {
+                    let run = Run {
+                        section: section.to_string(),
+                        start,
+                        end: start + (j - i) as u64,
+                        entry_size: size as u64,
+                        function: (function.0.to_string(), function.1),
+                        targets,
+                    };
+                    found = Some((run, if relative { "offsets" } else { "addresses" }));
+                    break;
+                }
+            }
+            match found {
+                Some((run, source)) => {
+                    i += (run.end - run.start) as usize;
+                    finish(vec![run], source, tables);
+                }
+                None => i += 4,
+            }
+        }
+    }
+}
+
+/// Find the jump tables of `buf` and the functions that they belong to: in ELF objects, from
+/// the relocations of their entries; in other ELF files, by scanning read-only data for tables
+/// of offsets or addresses into one function; and in code sections, as `data-in-code` finds
+/// them, from mapping symbols or `LC_DATA_IN_CODE`.
+pub fn jump_tables(buf: &[u8]) -> Result<JumpTableReport, Error> {
+    let functions = Functions::new(buf)?;
+    let mut tables = Vec::new();
+    if let Object::Elf(elf) = Object::parse(buf)? {
+        if elf.header.e_type == ET_REL {
+            relocated_tables(&elf, buf, &functions, &mut tables);
+        } else {
+            scanned_tables(&elf, buf, &functions, &mut tables);
+        }
+    }
+
+    let mut unattributed = Vec::new();
+    for table in mapping::inline_jump_tables(buf)? {
+        let size = table.size;
+        let inline = JumpTable {
+            section: table.section,
+            address: table.address,
+            size,
+            entries: table.entry_size.map(|entry_size| size / entry_size),
+            targets: None,
+            default_entries: None,
+            source: "data-in-code",
+        };
+        match functions.at(&inline.section, inline.address) {
+            Some(i) => tables.push(((inline.section.clone(), i), inline)),
+            None => unattributed.push(inline),
+        }
+    }
+
+    let mut by_function: BTreeMap<(String, usize), Vec<JumpTable>> = BTreeMap::new();
+    for (function, table) in tables {
+        by_function.entry(function).or_default().push(table);
+    }
+    let mut report = JumpTableReport {
+        functions: Vec::new(),
+        tables: unattributed.len() as u64,
+        size: unattributed.iter().map(|table| table.size).sum(),
+        unattributed,
+    };
+    for ((section, i), mut tables) in by_function {
+        let (_, function_size, ref name) = functions.sections[&section][i];
+        tables.sort_by_key(|table| table.address);
+        let size = tables.iter().map(|table| table.size).sum();
+        report.tables += tables.len() as u64;
+        report.size += size;
+        report.functions.push(FunctionTables {
+            name: format!("{:#}", rustc_demangle::demangle(name)),
+            function_size,
+            size,
+            sparse: tables.iter().any(JumpTable::is_sparse),
+            tables,
+        });
+    }
+    report.functions.sort_by(|a, b| (b.size, &a.name).cmp(&(a.size, &b.name)));
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::elf::section_header::{SHF_ALLOC, SHF_EXECINSTR};
+    use goblin::elf::sym::{STT_FUNC, STT_OBJECT};
+    use goblin::elf::Elf as ElfFile;
+    use testelf::Elf;
+
+    const CODE: u32 = SHF_ALLOC | SHF_EXECINSTR;
+
+    fn tables(report: &JumpTableReport) -> Vec<(&str, u64, u64, &'static str)> {
+        report.functions.iter()
+            .flat_map(|f| f.tables.iter().map(move |t| (&*f.name, t.address, t.size, t.source)))
+            .collect()
+    }
+
+    #[test]
+    fn sparse_tables() {
+        let table = |entries, default_entries| JumpTable {
+            section: ".rodata".to_string(),
+            address: 0,
+            size: 4 * entries,
+            entries: Some(entries),
+            targets: None,
+            default_entries: Some(default_entries),
+            source: "offsets",
+        };
+        assert!(table(32, 16).is_sparse());
+        assert!(!table(32, 15).is_sparse());
+        assert!(!table(31, 31).is_sparse());
+    }
+
+    #[test]
+    fn relocated_entries_of_an_object() {
+        // Eight entries into `f`, which its code loads as two tables, then two into `g`, too
+        // few for a table.
+        let entries: Vec<(u64, &str)> = (0..8).map(|i| (8 * i, "f"))
+            .chain(vec![(64, "g"), (72, "g")])
+            .collect();
+        let buf = Elf::object()
+            .section(".text", SHT_PROGBITS, CODE, &[0; 64])
+            .section(".rodata", SHT_PROGBITS, SHF_ALLOC, &[0; 80])
+            .symbol("f", STT_FUNC, ".text", 0, 48)
+            .symbol("g", STT_FUNC, ".text", 48, 16)
+            .local("table", STT_OBJECT, ".rodata", 32, 32)
+            .rela(".text", &[(4, "table")])
+            .rela(".rodata", &entries)
+            .build();
+        let report = jump_tables(&buf).unwrap();
+        assert_eq!(tables(&report), vec![("f", 0, 32, "relocations"),
+                                         ("f", 32, 32, "relocations")]);
+        let table = &report.functions[0].tables[0];
+        assert_eq!((table.entries, table.targets, table.default_entries),
+                   (Some(4), Some(1), Some(4)));
+        assert_eq!((report.tables, report.size, report.functions[0].size), (2, 64, 64));
+        assert!(report.unattributed.is_empty());
+    }
+
+    #[test]
+    fn offsets_and_addresses_in_linked_data() {
+        let executable = |rodata: &[u8]| {
+            Elf::executable()
+                .section(".text", SHT_PROGBITS, CODE, &[0; 64])
+                .section(".rodata", SHT_PROGBITS, SHF_ALLOC, rodata)
+                .symbol("f", STT_FUNC, ".text", 0, 32)
+                .symbol("g", STT_FUNC, ".text", 32, 32)
+                .build()
+        };
+        // Section addresses don't depend on their contents.
+        let buf = executable(&[0; 52]);
+        let elf = ElfFile::parse(&buf).unwrap();
+        let address = |name| {
+            elf.section_headers.iter()
+                .find(|sh| elf.shdr_strtab.get(sh.sh_name).and_then(|res| res.ok()) == Some(name))
+                .unwrap().sh_addr
+        };
+        let (text, rodata) = (address(".text"), address(".rodata"));
+        // Five offsets into `f`, three to one case, then four addresses into `g`.
+        let mut data = Vec::new();
+        for &target in &[0, 8, 8, 16, 8] {
+            data.extend_from_slice(&((text + target) as i32 - rodata as i32).to_le_bytes());
+        }
+        for &target in &[32, 40, 48, 56] {
+            data.extend_from_slice(&(text + target).to_le_bytes());
+        }
+        let report = jump_tables(&executable(&data)).unwrap();
+        // Functions with larger tables come first.
+        assert_eq!(tables(&report), vec![("g", rodata + 20, 32, "addresses"),
+                                         ("f", rodata, 20, "offsets")]);
+        let table = &report.functions[1].tables[0];
+        assert_eq!((table.entries, table.targets, table.default_entries),
+                   (Some(5), Some(3), Some(3)));
+    }
+}
@@ -0,0 +1,338 @@
+use exit::UsageError;
+use failure::Error;
+use goblin::elf::header::ET_REL;
+use goblin::elf::section_header::{SHN_UNDEF, SHT_NOBITS};
+use goblin::elf::sym::{Sym, STT_FUNC, STT_NOTYPE, STT_OBJECT, STT_TLS};
+use goblin::mach::constants::{S_ATTR_PURE_INSTRUCTIONS, S_GB_ZEROFILL, S_THREAD_LOCAL_ZEROFILL};
+use goblin::mach::constants::{SECTION_TYPE, SEG_DATA, S_ZEROFILL};
+use goblin::mach::symbols::{N_SECT, N_TYPE};
+use goblin::mach::Mach;
+use goblin::Object;
+use map_mach_name;
+use rustc_demangle;
+
+/// A sized symbol from an object file's symbol table.
+#[derive(Clone, Debug)]
+pub struct Symbol {
+    /// The symbol name as it appears in the symbol table (i.e. still mangled).
+    pub name: String,
+    /// The size of the symbol in bytes.
+    pub size: u64,
+    /// The address of the symbol (relative to its section in relocatable objects).
+    pub address: u64,
+    /// The name of the section the symbol is in, as in section reports.
+    pub section: Option<String>,
+    /// The offset of the symbol's contents in the file, if it occupies space in the file.
+    pub offset: Option<u64>,
+    /// Whether the symbol is a function.
+    pub code: bool,
+    /// Whether the symbol lives in writable memory.
+    pub writable: bool,
+    /// Whether the size was inferred from the address of the next symbol rather than recorded
+    /// in the symbol table.
+    pub inferred: bool,
+}
+
+impl Symbol {
+    /// Return the contents of the symbol, given the contents of the file it came from.
+    pub fn data<'a>(&self, buf: &'a [u8]) -> Option<&'a [u8]> {
+        let start = self.offset? as usize;
+        buf.get(start..start.checked_add(self.size as usize)?)
+    }
+}
+
+/// Parse `buf` as an object file and return all of the defined, non-empty symbols it contains.
+///
+/// Mach-O symbol tables don't record sizes, so each symbol is assumed to extend to the next
+/// symbol in the same section (or the end of the section). PE images don't carry a symbol table
+/// at all, so they produce no symbols.
+pub fn symbols(buf: &[u8]) -> Result<Vec<Symbol>, Error> {
+    let mut vec = Vec::new();
+    for_each_symbol(buf, false, |sym| vec.push(sym))?;
+    Ok(vec)
+}
+
+/// Like `symbols`, but with `infer_sizes`, ELF symbols with no size, as hand-written assembly
+/// and some toolchains emit, are sized like Mach-O symbols, by the gap to the next symbol in
+/// their section, and marked as `inferred`.
+pub fn symbols_inferring(buf: &[u8], infer_sizes: bool) -> Result<Vec<Symbol>, Error> {
+    let mut vec = Vec::new();
+    for_each_symbol(buf, infer_sizes, |sym| vec.push(sym))?;
+    Ok(vec)
+}
+
+/// Like `symbols_inferring`, but pass each symbol to `f` rather than collecting them. The
+/// symbols of ELF files are never all in memory at once unless sizes are inferred; those of
+/// Mach-O files are, since they are sized by sorting them by address.
+pub fn for_each_symbol<F: FnMut(Symbol)>(buf: &[u8], infer_sizes: bool, mut f: F)
+                                         -> Result<(), Error> {
+    match Object::parse(buf)? {
+        Object::Elf(elf) => {
+            // Prefer the full symbol table, but fall back to the dynamic symbols for stripped
+            // binaries.
+            let (syms, strtab) = if elf.syms.len() > 0 {
+                (&elf.syms, &elf.strtab)
+            } else {
+                (&elf.dynsyms, &elf.dynstrtab)
+            };
+            let symbol = |sym: &Sym, size: u64| {
+                let section = elf.section_headers.get(sym.st_shndx);
+                let offset = section
+                    .filter(|sh| sh.sh_type != SHT_NOBITS)
+                    .and_then(|sh| if elf.header.e_type == ET_REL {
+                        sh.sh_offset.checked_add(sym.st_value)
+                    } else {
+                        sym.st_value.checked_sub(sh.sh_addr)
+                            .and_then(|delta| sh.sh_offset.checked_add(delta))
+                    });
+                strtab.get(sym.st_name)
+                    .and_then(|res| res.ok())
+                    .map(|name| Symbol {
+                        name: name.to_string(),
+                        size,
+                        address: sym.st_value,
+                        section: section
+                            .and_then(|sh| elf.shdr_strtab.get(sh.sh_name))
+                            .and_then(|res| res.ok())
+                            .map(str::to_string),
+                        offset,
+                        code: sym.st_type() == STT_FUNC || sym.st_type() == STT_NOTYPE &&
+                            section.is_some_and(|sh| sh.is_executable()),
+                        writable: section.is_some_and(|sh| sh.is_writable()),
+                        inferred: size != sym.st_size,
+                    })
+            };
+            let defined = |sym: &Sym| {
+                sym.st_shndx != SHN_UNDEF as usize && sym.st_shndx < elf.section_headers.len()
+            };
+            if !infer_sizes {
+                let sized = syms.iter().filter(|sym| {
+                    defined(sym) && sym.st_size != 0 &&
+                        [STT_FUNC, STT_OBJECT, STT_TLS].contains(&sym.st_type())
+                });
+                for sym in sized.filter_map(|sym| symbol(&sym, sym.st_size)) {
+                    f(sym);
+                }
+                return Ok(());
+            }
+
+            // Unsized labels count too, except for ARM mapping symbols (`$x`, `$d`, ...),
+            // which mark the kind of contents that follow rather than an entity.
+            let mut candidates: Vec<Sym> = syms.iter().filter(|sym| {
+                defined(sym) && [STT_FUNC, STT_OBJECT, STT_TLS, STT_NOTYPE]
+                    .contains(&sym.st_type()) &&
+                    match strtab.get(sym.st_name) {
+                        Some(Ok(name)) => !name.is_empty() && !name.starts_with('$'),
+                        _ => false,
+                    }
+            }).collect();
+            candidates.sort_by_key(|sym| (sym.st_shndx, sym.st_value));
+            for (i, sym) in candidates.iter().enumerate() {
+                let size = if sym.st_size != 0 {
+                    sym.st_size
+                } else {
+                    let sh = &elf.section_headers[sym.st_shndx];
+                    let start = if elf.header.e_type == ET_REL { 0 } else { sh.sh_addr };
+                    let end = candidates[i + 1..].iter()
+                        .take_while(|next| next.st_shndx == sym.st_shndx)
+                        .map(|next| next.st_value)
+                        .find(|&address| address > sym.st_value)
+                        .unwrap_or(start.saturating_add(sh.sh_size));
+                    end.saturating_sub(sym.st_value)
+                };
+                if size == 0 {
+                    continue;
+                }
+                if let Some(sym) = symbol(sym, size) {
+                    f(sym);
+                }
+            }
+        },
+        Object::PE(_) => {},
+        Object::Mach(Mach::Binary(mach)) => {
+            // Section numbers in nlist entries are 1-based indices into the list of all sections
+            // in load command order.
+            let sections: Vec<_> = mach.segments.sections().flatten()
+                .filter_map(|s| s.ok())
+                .map(|(sec, _data)| sec)
+                .collect();
+            let mut syms: Vec<(usize, String, u64)> = mach.symbols().filter_map(|s| s.ok())
+                .filter(|(_, nlist)| {
+                    !nlist.is_stab() && (nlist.n_type & N_TYPE) == N_SECT && nlist.n_sect != 0
+                })
+                .map(|(name, nlist)| (nlist.n_sect, name.to_string(), nlist.n_value))
+                .collect();
+            syms.sort_by_key(|&(sect, _, address)| (sect, address));
+
+            for (i, &(sect, ref name, address)) in syms.iter().enumerate() {
+                let section = match sections.get(sect - 1) {
+                    Some(section) => section,
+                    None => continue,
+                };
+                let end = match syms.get(i + 1) {
+                    Some(&(next_sect, _, next_address)) if next_sect == sect => next_address,
+                    _ => section.addr.saturating_add(section.size),
+                };
+                let zerofill = matches!(section.flags & SECTION_TYPE,
+                                        S_ZEROFILL | S_GB_ZEROFILL | S_THREAD_LOCAL_ZEROFILL);
+                if end > address {
+                    f(Symbol {
+                        name: name.clone(),
+                        size: end - address,
+                        address,
+                        section: match (section.segname(), section.name()) {
+                            (Ok(seg), Ok(sect)) => Some(map_mach_name(seg, sect)),
+                            _ => None,
+                        },
+                        offset: if zerofill {
+                            None
+                        } else {
+                            address.checked_sub(section.addr)
+                                .map(|delta| section.offset as u64 + delta)
+                        },
+                        code: (section.flags & S_ATTR_PURE_INSTRUCTIONS) != 0,
+                        writable: section.segname().ok().is_some_and(|seg| {
+                            seg.starts_with(SEG_DATA)
+                        }),
+                        inferred: true,
+                    });
+                }
+            }
+        },
+        Object::Mach(Mach::Fat(_)) => {
+            return Err(UsageError("A universal binary has symbols for each architecture; \
+                                   extract one with `lipo -thin`".to_string()).into());
+        }
+        _ => bail!("Unhandled file type!"),
+    }
+    Ok(())
+}
+
+/// Components of a '.'-separated symbol suffix that are kept when canonicalizing a name, because
+/// they distinguish a compiler-generated clone from the function it was derived from.
+const KEPT_SUFFIXES: &[&str] = &["cold", "constprop", "isra", "part", "lto_priv"];
+
+/// Returns `name` with the parts that change between otherwise-identical builds removed, so that
+/// symbols can be paired up across a recompile.
+///
+/// This strips Rust symbol hashes (`::h<hash>`), and the numeric suffixes that compilers append
+/// to local and cloned symbols (`.llvm.1234`, `.constprop.0`, `counter.1234`), as well as the
+/// content hash in the names of Rust's anonymous constants (`anon.<hash>.3`).
+pub fn canonical_name(name: &str) -> String {
+    let mut components: Vec<&str> = name.split('.').collect();
+    let mut suffix = Vec::new();
+    while components.len() > 1 {
+        let last = components[components.len() - 1];
+        if (!last.is_empty() && last.bytes().all(|b| b.is_ascii_digit())) || last == "llvm" {
+            components.pop();
+        } else if KEPT_SUFFIXES.contains(&last) {
+            suffix.push(components.pop().unwrap());
+        } else {
+            break;
+        }
+    }
+    if components.len() == 2 && components[0] == "anon" &&
+        components[1].bytes().all(|b| b.is_ascii_hexdigit()) {
+        components.pop();
+    }
+
+    let base = components.join(".");
+    let mut canonical = match rustc_demangle::try_demangle(&base) {
+        Ok(demangled) => format!("{:#}", demangled),
+        Err(_) => base,
+    };
+    for s in suffix.iter().rev() {
+        canonical.push('.');
+        canonical.push_str(s);
+    }
+    canonical
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::elf::section_header::{SHF_ALLOC, SHF_EXECINSTR, SHF_WRITE, SHT_PROGBITS};
+    use testelf::{self, Elf};
+
+    #[test]
+    fn universal_binary_is_a_usage_error() {
+        // A universal header with one x86_64 slice, which the error comes before reading.
+        let mut fat = vec![0xca, 0xfe, 0xba, 0xbe, 0, 0, 0, 1, 1, 0, 0, 7, 0, 0, 0, 3,
+                           0, 0, 0, 28, 0, 0, 0, 4, 0, 0, 0, 0];
+        fat.extend_from_slice(&[0; 4]);
+        let err = symbols(&fat).unwrap_err();
+        assert!(err.downcast_ref::<UsageError>().is_some(), "{}", err);
+    }
+
+    #[test]
+    fn canonical_names_drop_build_specific_parts() {
+        assert_eq!(canonical_name("_ZN4core3fmt5write17h0123456789abcdefE"), "core::fmt::write");
+        assert_eq!(canonical_name("_ZN3foo3bar17hfedcba9876543210E.llvm.1234567"), "foo::bar");
+        assert_eq!(canonical_name("counter.1234"), "counter");
+        assert_eq!(canonical_name("anon.0123456789abcdef0123456789abcdef.3"), "anon");
+        // Clones keep what kind of clone they are.
+        assert_eq!(canonical_name("parse.constprop.0.isra.1"), "parse.constprop.isra");
+        assert_eq!(canonical_name("main.cold.7"), "main.cold");
+        assert_eq!(canonical_name("_ZN3foo3bar17h0000000000000000E.cold.1"), "foo::bar.cold");
+        // Only whole numeric components are suffixes.
+        assert_eq!(canonical_name(".rodata.str1.1"), ".rodata.str1");
+        assert_eq!(canonical_name("v1.2a"), "v1.2a");
+        assert_eq!(canonical_name("1234"), "1234");
+        assert_eq!(canonical_name("anon.xyz.3"), "anon.xyz");
+    }
+
+    #[test]
+    fn sizes_inferred_from_the_next_symbol() {
+        let buf = Elf::executable()
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &[0; 64])
+            .section(".data", SHT_PROGBITS, SHF_ALLOC | SHF_WRITE, &[0; 8])
+            .symbol("sized", STT_FUNC, ".text", 0, 16)
+            .symbol("label", STT_NOTYPE, ".text", 16, 0)
+            .symbol("alias", STT_NOTYPE, ".text", 16, 0)
+            .symbol("last", STT_FUNC, ".text", 40, 0)
+            .local("$x", STT_NOTYPE, ".text", 48, 0)
+            .symbol("table", STT_OBJECT, ".data", 0, 0)
+            .undefined("puts")
+            .build();
+        let sizes = |infer| -> Vec<_> {
+            let mut syms: Vec<_> = symbols_inferring(&buf, infer).unwrap().into_iter()
+                .map(|sym| (sym.name, sym.size, sym.code, sym.inferred))
+                .collect();
+            syms.sort();
+            syms
+        };
+        assert_eq!(sizes(false), vec![("sized".to_string(), 16, true, false)]);
+        // Symbols at the same address get the same size, and the last one in each section
+        // extends to its end; mapping symbols don't count.
+        assert_eq!(sizes(true), vec![
+            ("alias".to_string(), 24, true, true),
+            ("label".to_string(), 24, true, true),
+            ("last".to_string(), 24, true, true),
+            ("sized".to_string(), 16, true, false),
+            ("table".to_string(), 8, false, true),
+        ]);
+    }
+
+    #[test]
+    fn sizes_inferred_from_corrupt_sections() {
+        let mut buf = Elf::executable()
+            .section(".data", SHT_PROGBITS, SHF_ALLOC | SHF_WRITE, &[0; 8])
+            .symbol("table", STT_OBJECT, ".data", 0, 0)
+            .build();
+        let offset = goblin::elf::Elf::parse(&buf).unwrap().section_headers[1].sh_offset;
+        testelf::corrupt_section(&mut buf, 0, offset, u64::MAX);
+        let syms = symbols_inferring(&buf, true).unwrap();
+        assert_eq!(syms[0].size, u64::MAX - syms[0].address);
+    }
+
+    #[test]
+    fn no_offsets_past_the_end() {
+        let mut buf = Elf::object()
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &[0; 16])
+            .symbol("f", STT_FUNC, ".text", 8, 8)
+            .build();
+        testelf::corrupt_section(&mut buf, 0, u64::MAX - 2, 16);
+        let syms = symbols(&buf).unwrap();
+        assert_eq!((syms[0].address, syms[0].offset), (8, None));
+    }
+}
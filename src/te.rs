@@ -0,0 +1,174 @@
+use failure::Error;
+use flags;
+use goblin::pe::section_table::{SectionTable, SIZEOF_SECTION_TABLE};
+use goblin::pe::section_table::{IMAGE_SCN_MEM_READ, IMAGE_SCN_MEM_WRITE};
+use Section;
+use SectionRecord;
+
+/// The signature that starts a TE image, `VZ`.
+const TE_SIGNATURE: &[u8] = b"VZ";
+
+/// The size of the TE header, which the section table follows.
+const HEADER_SIZE: usize = 40;
+
+fn le16(buf: &[u8], offset: usize) -> Option<u16> {
+    let bytes = buf.get(offset..offset + 2)?;
+    Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn le32(buf: &[u8], offset: usize) -> Option<u32> {
+    let bytes = buf.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// The header of a TE (terse executable) image, which UEFI firmware uses for PEI and SEC
+/// modules: a PE image whose DOS, COFF and optional headers are replaced by this.
+pub struct Header {
+    pub machine: u16,
+    pub sections: usize,
+    pub subsystem: u16,
+    /// The bytes of the original headers that were stripped. Offsets in the section table are
+    /// still those of the PE image, which is `stripped_size - HEADER_SIZE` bytes longer.
+    pub stripped_size: u64,
+    /// The entry point, relative to the image base.
+    pub entry: u64,
+    pub image_base: u64,
+}
+
+impl Header {
+    pub fn parse(buf: &[u8]) -> Option<Header> {
+        if !buf.starts_with(TE_SIGNATURE) {
+            return None;
+        }
+        let sections = usize::from(*buf.get(4)?);
+        if HEADER_SIZE + sections * SIZEOF_SECTION_TABLE > buf.len() {
+            return None;
+        }
+        let image_base = u64::from(le32(buf, 16)?) | u64::from(le32(buf, 20)?) << 32;
+        Some(Header {
+            machine: le16(buf, 2)?,
+            sections,
+            subsystem: u16::from(buf[5]),
+            stripped_size: u64::from(le16(buf, 6)?),
+            entry: u64::from(le32(buf, 8)?),
+            image_base,
+        })
+    }
+
+    /// The size of the headers in the file: the TE header and the section table.
+    pub fn headers_size(&self) -> u64 {
+        (HEADER_SIZE + self.sections * SIZEOF_SECTION_TABLE) as u64
+    }
+}
+
+/// Whether `buf` is a TE image.
+pub fn is_te(buf: &[u8]) -> bool {
+    Header::parse(buf).is_some()
+}
+
+/// The machine type of the TE image `buf`.
+pub fn machine(buf: &[u8]) -> Option<u16> {
+    Header::parse(buf).map(|header| header.machine)
+}
+
+/// Parse `buf` as a TE image and return a record for each section, classified as for PE
+/// images, with the section offsets translated to the TE file.
+pub fn sections(buf: &[u8]) -> Result<Vec<SectionRecord>, Error> {
+    let header = match Header::parse(buf) {
+        Some(header) => header,
+        None => bail!("Not a TE image"),
+    };
+    let mut vec = Vec::new();
+    let mut bss = 0;
+    let mut offset = HEADER_SIZE;
+    for _ in 0..header.sections {
+        let section = SectionTable::parse(buf, &mut offset)?;
+        let characteristics = section.characteristics;
+        let mut size = u64::from(section.virtual_size);
+        let category = if characteristics & IMAGE_SCN_MEM_WRITE == 0 {
+            Section::Text
+        } else if characteristics & IMAGE_SCN_MEM_READ != 0 {
+            // As in PE images, uninitialized data is the part of the data sections that is
+            // allocated in memory but not stored in the file.
+            bss += u64::from(section.virtual_size.saturating_sub(section.size_of_raw_data));
+            size = u64::from(section.size_of_raw_data);
+            Section::Data
+        } else {
+            Section::Other
+        };
+        let end = section.name.iter().position(|&b| b == 0).unwrap_or(8);
+        let name = String::from_utf8_lossy(&section.name[..end]).into_owned();
+        let mut record = SectionRecord::synthetic(&name, size, category);
+        record.address = Some(u64::from(section.virtual_address));
+        if section.pointer_to_raw_data != 0 {
+            let stored = u64::from(section.pointer_to_raw_data) + HEADER_SIZE as u64;
+            record.offset = stored.checked_sub(header.stripped_size);
+        }
+        record.alignment = flags::pe_alignment(characteristics);
+        record.flags = Some(u64::from(characteristics));
+        record.flag_names = flags::pe_flag_names(characteristics);
+        vec.push(record);
+    }
+    vec.push(SectionRecord::synthetic(".bss", bss, Section::Bss));
+    Ok(vec)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A TE image for x86-64 UEFI (subsystem 11, a boot service driver) whose original
+    /// headers were `0x228` bytes, with the sections `(name, virtual address, virtual size,
+    /// size in the file, PE file offset, characteristics)`.
+    fn image(sections: &[(&str, u32, u32, u32, u32, u32)]) -> Vec<u8> {
+        let mut out = b"VZ".to_vec();
+        out.extend_from_slice(&0x8664u16.to_le_bytes());
+        out.extend_from_slice(&[sections.len() as u8, 11]);
+        out.extend_from_slice(&0x228u16.to_le_bytes());
+        for word in &[0x1000u32, 0x1000, 0x8000_0000, 1, 0, 0, 0, 0] {
+            out.extend_from_slice(&word.to_le_bytes());
+        }
+        for &(name, address, virtual_size, size, offset, characteristics) in sections {
+            let mut field = [0; 8];
+            field[..name.len()].copy_from_slice(name.as_bytes());
+            out.extend_from_slice(&field);
+            for word in &[virtual_size, address, size, offset, 0, 0, 0, characteristics] {
+                out.extend_from_slice(&word.to_le_bytes());
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn headers() {
+        let buf = image(&[(".text", 0x1000, 0x100, 0x100, 0x240, 0x6050_0020)]);
+        let header = Header::parse(&buf).unwrap();
+        assert_eq!((header.machine, header.sections, header.subsystem), (0x8664, 1, 11));
+        assert_eq!((header.stripped_size, header.entry), (0x228, 0x1000));
+        assert_eq!(header.image_base, 0x1_8000_0000);
+        assert_eq!(header.headers_size(), 80);
+        assert!(is_te(&buf));
+        assert_eq!(machine(&buf), Some(0x8664));
+        // The section table runs past the end of the file.
+        assert!(!is_te(&buf[..79]));
+        assert!(!is_te(b"MZ\x90\0"));
+        assert!(sections(b"MZ\x90\0").is_err());
+    }
+
+    #[test]
+    fn sections_and_their_offsets_in_the_te_file() {
+        let buf = image(&[(".text", 0x1000, 0x100, 0x100, 0x240, 0x6050_0020),
+                          (".data", 0x2000, 0x80, 0x20, 0x340, 0xc000_0040),
+                          (".bss", 0x3000, 0x40, 0, 0, 0xc000_0080)]);
+        let records: Vec<_> = sections(&buf).unwrap().into_iter()
+            .map(|r| (r.name, r.category, r.size, r.address, r.offset, r.alignment))
+            .collect();
+        assert_eq!(records, vec![
+            (".text".to_string(), Section::Text, 0x100, Some(0x1000), Some(0x40), Some(16)),
+            (".data".to_string(), Section::Data, 0x20, Some(0x2000), Some(0x140), None),
+            (".bss".to_string(), Section::Data, 0, Some(0x3000), None, None),
+            // The part of `.data` and `.bss` that isn't stored in the file.
+            (".bss".to_string(), Section::Bss, 0x60 + 0x40, None, None, None),
+        ]);
+    }
+}
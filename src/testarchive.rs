@@ -0,0 +1,21 @@
+//! `ar` archives, built in memory for tests.
+
+/// A member header for `name`, then `data`, padded to two bytes.
+pub fn member(name: &str, data: &[u8]) -> Vec<u8> {
+    let mut out = format!("{:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`\n", name, 0, 0, 0, 644,
+                          data.len()).into_bytes();
+    out.extend_from_slice(data);
+    if out.len() % 2 == 1 {
+        out.push(b'\n');
+    }
+    out
+}
+
+/// An archive of the encoded `members`.
+pub fn archive(members: &[Vec<u8>]) -> Vec<u8> {
+    let mut out = b"!<arch>\n".to_vec();
+    for m in members {
+        out.extend_from_slice(m);
+    }
+    out
+}
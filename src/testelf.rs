@@ -0,0 +1,528 @@
+//! Minimal 64-bit little-endian ELF files, for x86-64 unless set otherwise, built in memory for
+//! tests.
+
+use goblin::elf::dyn::{DT_JMPREL, DT_NULL, DT_PLTREL, DT_PLTRELSZ, DT_RELA, DT_RELAENT};
+use goblin::elf::dyn::{DT_RELASZ, DT_STRSZ, DT_STRTAB, DT_SYMENT, DT_SYMTAB};
+use goblin::elf::header::{EM_X86_64, ET_CORE, ET_DYN, ET_EXEC, ET_REL};
+use goblin::elf::program_header::{PF_R, PF_W, PT_DYNAMIC, PT_LOAD};
+use goblin::elf::section_header::{SHF_ALLOC, SHF_WRITE, SHT_DYNAMIC, SHT_DYNSYM, SHT_NOBITS};
+use goblin::elf::section_header::{SHT_RELA, SHT_STRTAB, SHT_SYMTAB};
+use goblin::elf::sym::{STB_GLOBAL, STB_LOCAL};
+
+/// Where executables are loaded: the address of their allocated sections is this plus their
+/// offset in the file.
+pub const BASE: u64 = 0x40_0000;
+
+struct Section {
+    name: String,
+    kind: u32,
+    flags: u64,
+    data: Vec<u8>,
+    /// The size of `SHT_NOBITS` sections, which have no data.
+    size: u64,
+    entsize: u64,
+    /// The `sh_info` of the section, if it names a section or symbol.
+    info: Info,
+    /// For `SHT_RELA` sections, the offsets of 64-bit absolute relocations and the names of
+    /// the symbols they refer to.
+    relocs: Vec<(u64, String)>,
+}
+
+/// What the `sh_info` of a section refers to.
+enum Info {
+    None,
+    /// The section at this index among those added.
+    Section(usize),
+    /// The symbol with this name.
+    Symbol(String),
+}
+
+/// `SHT_GROUP`, and the flag of its first word that makes a group a COMDAT group.
+const SHT_GROUP: u32 = 17;
+const GRP_COMDAT: u32 = 1;
+/// `R_X86_64_64`.
+const R_ABSOLUTE: u64 = 1;
+
+struct Symbol {
+    name: String,
+    /// The index of the section among those added, or `None` for an undefined symbol.
+    section: Option<usize>,
+    offset: u64,
+    size: u64,
+    info: u8,
+    /// The visibility, for dynamic symbols.
+    other: u8,
+}
+
+/// A relocation of `.rela.dyn` or `.rela.plt`.
+struct DynamicReloc {
+    plt: bool,
+    kind: u32,
+    /// The index of the section it applies to among those added, and the offset in it.
+    section: usize,
+    offset: u64,
+    /// The name of the dynamic symbol it refers to, or `None` for none.
+    symbol: Option<String>,
+}
+
+/// A program header, with its offset and addresses given by the sections it maps.
+struct Segment {
+    kind: u32,
+    flags: u32,
+    sections: Vec<usize>,
+    align: u64,
+    /// Whether the segment starts at the beginning of the file, taking in the headers, as the
+    /// first loaded segment does.
+    headers: bool,
+}
+
+/// An ELF file being built: its sections, in order, and the symbols defined in them.
+pub struct Elf {
+    kind: u16,
+    machine: u16,
+    sections: Vec<Section>,
+    symbols: Vec<Symbol>,
+    /// The symbols of `.dynsym`, which are exported or imported.
+    dynamic: Vec<Symbol>,
+    dynamic_relocs: Vec<DynamicReloc>,
+    /// Entries of `.dynamic` besides those describing the symbol and relocation tables.
+    dynamic_entries: Vec<(u64, u64)>,
+    segments: Vec<Segment>,
+}
+
+impl Elf {
+    /// A relocatable object.
+    pub fn object() -> Elf {
+        Elf {
+            kind: ET_REL,
+            machine: EM_X86_64,
+            sections: Vec::new(),
+            symbols: Vec::new(),
+            dynamic: Vec::new(),
+            dynamic_relocs: Vec::new(),
+            dynamic_entries: Vec::new(),
+            segments: Vec::new(),
+        }
+    }
+
+    /// An executable, whose allocated sections are loaded at `BASE` plus their file offsets.
+    pub fn executable() -> Elf {
+        Elf { kind: ET_EXEC, ..Elf::object() }
+    }
+
+    /// A shared library, laid out like an executable.
+    pub fn shared() -> Elf {
+        Elf { kind: ET_DYN, ..Elf::object() }
+    }
+
+    /// A core file, laid out like an executable.
+    pub fn core() -> Elf {
+        Elf { kind: ET_CORE, ..Elf::object() }
+    }
+
+    /// Set the machine (`EM_AARCH64`...).
+    pub fn machine(mut self, machine: u16) -> Elf {
+        self.machine = machine;
+        self
+    }
+
+    fn index(&self, name: &str) -> usize {
+        self.sections.iter().position(|s| s.name == name)
+            .unwrap_or_else(|| panic!("no section {}", name))
+    }
+
+    /// Add a section holding `data`.
+    pub fn section(mut self, name: &str, kind: u32, flags: u32, data: &[u8]) -> Elf {
+        self.sections.push(Section {
+            name: name.to_string(),
+            kind,
+            flags: flags as u64,
+            data: data.to_vec(),
+            size: data.len() as u64,
+            entsize: 0,
+            info: Info::None,
+            relocs: Vec::new(),
+        });
+        self
+    }
+
+    /// Add a mergeable section holding entries of `entsize` bytes.
+    pub fn merge_section(self, name: &str, kind: u32, flags: u32, entsize: u64, data: &[u8])
+                         -> Elf {
+        let mut elf = self.section(name, kind, flags, data);
+        elf.sections.last_mut().unwrap().entsize = entsize;
+        elf
+    }
+
+    /// Add a `SHT_NOBITS` section of `size` bytes.
+    pub fn nobits(mut self, name: &str, flags: u32, size: u64) -> Elf {
+        self.sections.push(Section {
+            name: name.to_string(),
+            kind: SHT_NOBITS,
+            flags: flags as u64,
+            data: Vec::new(),
+            size,
+            entsize: 0,
+            info: Info::None,
+            relocs: Vec::new(),
+        });
+        self
+    }
+
+    /// Add a local symbol, like `symbol`.
+    pub fn local(self, name: &str, kind: u8, section: &str, offset: u64, size: u64) -> Elf {
+        let mut elf = self.symbol(name, kind, section, offset, size);
+        elf.symbols.last_mut().unwrap().info = (STB_LOCAL << 4) | kind;
+        elf
+    }
+
+    /// Add an undefined global symbol.
+    pub fn undefined(mut self, name: &str) -> Elf {
+        self.symbols.push(Symbol {
+            name: name.to_string(),
+            section: None,
+            offset: 0,
+            size: 0,
+            info: STB_GLOBAL << 4,
+            other: 0,
+        });
+        self
+    }
+
+    /// Add a COMDAT group of the sections called `members`, whose signature is the symbol
+    /// called `signature`.
+    pub fn group(self, signature: &str, members: &[&str]) -> Elf {
+        let mut data = GRP_COMDAT.to_le_bytes().to_vec();
+        for name in members {
+            data.extend_from_slice(&(self.index(name) as u32 + 1).to_le_bytes());
+        }
+        let mut elf = self.section(".group", SHT_GROUP, 0, &data);
+        elf.sections.last_mut().unwrap().info = Info::Symbol(signature.to_string());
+        elf
+    }
+
+    /// Add the relocations of the section called `target`, as (offset, symbol name) pairs.
+    pub fn rela(self, target: &str, relocs: &[(u64, &str)]) -> Elf {
+        let target = self.index(target);
+        let name = format!(".rela{}", self.sections[target].name);
+        let mut elf = self.section(&name, SHT_RELA, 0, &[]);
+        let section = elf.sections.last_mut().unwrap();
+        section.info = Info::Section(target);
+        section.relocs = relocs.iter().map(|&(offset, sym)| (offset, sym.to_string())).collect();
+        section.entsize = 24;
+        elf
+    }
+
+    /// Add a global symbol of type `kind` (`STT_FUNC`, `STT_OBJECT`...) at `offset` in the
+    /// section called `section`.
+    pub fn symbol(mut self, name: &str, kind: u8, section: &str, offset: u64, size: u64) -> Elf {
+        let section = Some(self.index(section));
+        self.symbols.push(Symbol {
+            name: name.to_string(),
+            section,
+            offset,
+            size,
+            info: (STB_GLOBAL << 4) | kind,
+            other: 0,
+        });
+        self
+    }
+
+    /// Add a program header of type `kind` spanning the sections called `sections`.
+    pub fn segment(mut self, kind: u32, flags: u32, sections: &[&str], align: u64) -> Elf {
+        let sections = sections.iter().map(|name| self.index(name)).collect();
+        self.segments.push(Segment { kind, flags, sections, align, headers: false });
+        self
+    }
+
+    /// Add a dynamic symbol exported from the section called `section`, like `symbol`.
+    pub fn export(mut self, name: &str, kind: u8, section: &str, offset: u64, size: u64) -> Elf {
+        self = self.symbol(name, kind, section, offset, size);
+        let sym = self.symbols.pop().unwrap();
+        self.dynamic.push(sym);
+        self
+    }
+
+    /// Add an undefined dynamic symbol, which the file imports.
+    pub fn import(mut self, name: &str) -> Elf {
+        self = self.undefined(name);
+        let sym = self.symbols.pop().unwrap();
+        self.dynamic.push(sym);
+        self
+    }
+
+    /// Set the visibility (`STV_HIDDEN`...) of the dynamic symbol added last.
+    pub fn visibility(mut self, other: u8) -> Elf {
+        self.dynamic.last_mut().unwrap().other = other;
+        self
+    }
+
+    /// Add a dynamic relocation of type `kind` at `offset` in the section called `section`,
+    /// referring to the dynamic symbol called `symbol`, if any: to `.rela.plt` if `plt`, and
+    /// otherwise to `.rela.dyn`.
+    pub fn dynamic_reloc(mut self, plt: bool, kind: u32, section: &str, offset: u64,
+                         symbol: Option<&str>) -> Elf {
+        let section = self.index(section);
+        self.dynamic_relocs.push(DynamicReloc {
+            plt,
+            kind,
+            section,
+            offset,
+            symbol: symbol.map(|name| name.to_string()),
+        });
+        self
+    }
+
+    /// Add the entry `tag` (`DT_FLAGS_1`...) with the value `value` to `.dynamic`.
+    pub fn dynamic_entry(mut self, tag: u64, value: u64) -> Elf {
+        self.dynamic_entries.push((tag, value));
+        self
+    }
+
+    /// The contents of a symbol table of `symbols` and of its string table. The values are
+    /// filled in once the sections have addresses.
+    fn symbol_table(symbols: &[Symbol]) -> (Vec<u8>, Vec<u8>) {
+        let mut strtab = vec![0];
+        let mut symtab = vec![0; 24];
+        for sym in symbols {
+            let name = strtab.len() as u32;
+            strtab.extend_from_slice(sym.name.as_bytes());
+            strtab.push(0);
+            symtab.extend_from_slice(&name.to_le_bytes());
+            symtab.push(sym.info);
+            symtab.push(sym.other);
+            symtab.extend_from_slice(&(sym.section.map_or(0, |i| i + 1) as u16).to_le_bytes());
+            symtab.extend_from_slice(&[0; 8]);
+            symtab.extend_from_slice(&sym.size.to_le_bytes());
+        }
+        (symtab, strtab)
+    }
+
+    /// The bytes of the file: the header, the program headers, the section contents, the
+    /// symbol table, and the section headers.
+    pub fn build(mut self) -> Vec<u8> {
+        let dynsym = self.sections.len();
+        let is_dynamic = !self.dynamic.is_empty() || !self.dynamic_relocs.is_empty() ||
+            !self.dynamic_entries.is_empty();
+        // The sections whose addresses go in `.dynamic`, by the index of their entry.
+        let mut tables = vec![(0, dynsym), (1, dynsym + 1)];
+        let mut dynamic_index = dynsym;
+        if is_dynamic {
+            let (symtab, strtab) = Elf::symbol_table(&self.dynamic);
+            // The addresses of the tables are filled in once the sections have addresses, and
+            // the relocations once the sections they apply to have.
+            let mut entries = vec![(DT_SYMTAB, 0), (DT_STRTAB, 0), (DT_STRSZ, strtab.len() as u64),
+                                   (DT_SYMENT, 24)];
+            self = self.section(".dynsym", SHT_DYNSYM, SHF_ALLOC, &symtab)
+                .section(".dynstr", SHT_STRTAB, SHF_ALLOC, &strtab);
+            for &plt in &[false, true] {
+                let count = self.dynamic_relocs.iter().filter(|r| r.plt == plt).count() as u64;
+                if count == 0 {
+                    continue;
+                }
+                let name = if plt { ".rela.plt" } else { ".rela.dyn" };
+                tables.push((entries.len(), self.sections.len()));
+                entries.extend_from_slice(&if plt {
+                    [(DT_JMPREL, 0), (DT_PLTRELSZ, 24 * count), (DT_PLTREL, DT_RELA)]
+                } else {
+                    [(DT_RELA, 0), (DT_RELASZ, 24 * count), (DT_RELAENT, 24)]
+                });
+                self = self.section(name, SHT_RELA, SHF_ALLOC, &vec![0; 24 * count as usize]);
+                self.sections.last_mut().unwrap().entsize = 24;
+            }
+            entries.extend_from_slice(&self.dynamic_entries);
+            entries.push((DT_NULL, 0));
+            let mut dynamic = Vec::new();
+            for &(tag, value) in &entries {
+                dynamic.extend_from_slice(&tag.to_le_bytes());
+                dynamic.extend_from_slice(&value.to_le_bytes());
+            }
+            dynamic_index = self.sections.len();
+            self = self.section(".dynamic", SHT_DYNAMIC, SHF_ALLOC | SHF_WRITE, &dynamic);
+            self.segments.push(Segment {
+                kind: PT_LOAD,
+                flags: PF_R | PF_W,
+                sections: vec![dynamic_index],
+                align: 0x1000,
+                headers: true,
+            });
+            self.segments.push(Segment {
+                kind: PT_DYNAMIC,
+                flags: PF_R | PF_W,
+                sections: vec![dynamic_index],
+                align: 8,
+                headers: false,
+            });
+        }
+        let defined = self.sections.len();
+        if !self.symbols.is_empty() {
+            // Local symbols come first, as `sh_info` of the symbol table says.
+            self.symbols.sort_by_key(|sym| sym.info >> 4 != STB_LOCAL);
+            let (symtab, strtab) = Elf::symbol_table(&self.symbols);
+            self = self.section(".symtab", SHT_SYMTAB, 0, &symtab)
+                .section(".strtab", SHT_STRTAB, 0, &strtab);
+        }
+        let symbol_index = |symbols: &[Symbol], name: &str| {
+            symbols.iter().position(|sym| sym.name == name)
+                .unwrap_or_else(|| panic!("no symbol {}", name)) as u64 + 1
+        };
+        for i in 0..defined {
+            let mut data = Vec::new();
+            for &(offset, ref name) in &self.sections[i].relocs {
+                let info = (symbol_index(&self.symbols, name) << 32) | R_ABSOLUTE;
+                for field in &[offset, info, 0] {
+                    data.extend_from_slice(&field.to_le_bytes());
+                }
+            }
+            if !data.is_empty() {
+                self.sections[i].size = data.len() as u64;
+                self.sections[i].data = data;
+            }
+        }
+        let mut shstrtab = vec![0];
+        let mut names = Vec::new();
+        let section_names = self.sections.iter().map(|s| s.name.clone());
+        for name in section_names.chain(Some(".shstrtab".to_string())) {
+            names.push(shstrtab.len() as u32);
+            shstrtab.extend_from_slice(name.as_bytes());
+            shstrtab.push(0);
+        }
+        self = self.section(".shstrtab", SHT_STRTAB, 0, &shstrtab);
+
+        let mut out = vec![0; 64 + 56 * self.segments.len()];
+        let mut offsets = Vec::new();
+        for section in &self.sections {
+            while !out.len().is_multiple_of(16) {
+                out.push(0);
+            }
+            offsets.push(out.len() as u64);
+            out.extend_from_slice(&section.data);
+        }
+        let address = |i: usize, section: &Section| {
+            if self.kind != ET_REL && (section.flags & SHF_ALLOC as u64) != 0 {
+                BASE + offsets[i]
+            } else {
+                0
+            }
+        };
+        let mut fill_values = |symbols: &[Symbol], symtab: usize| {
+            let symtab = offsets[symtab] as usize;
+            for (i, sym) in symbols.iter().enumerate() {
+                let value = match sym.section {
+                    Some(s) => address(s, &self.sections[s]) + sym.offset,
+                    None => 0,
+                };
+                let at = symtab + 24 * (i + 1) + 8;
+                out[at..at + 8].copy_from_slice(&value.to_le_bytes());
+            }
+        };
+        if !self.symbols.is_empty() {
+            fill_values(&self.symbols, defined);
+        }
+        if is_dynamic {
+            fill_values(&self.dynamic, dynsym);
+            let dynamic = offsets[dynamic_index] as usize;
+            for &(entry, table) in &tables {
+                let at = dynamic + 16 * entry + 8;
+                let value = address(table, &self.sections[table]);
+                out[at..at + 8].copy_from_slice(&value.to_le_bytes());
+            }
+            for &(plt, name) in &[(false, ".rela.dyn"), (true, ".rela.plt")] {
+                let table = match self.sections.iter().position(|s| s.name == name) {
+                    Some(table) => offsets[table] as usize,
+                    None => continue,
+                };
+                let relocs = self.dynamic_relocs.iter().filter(|r| r.plt == plt);
+                for (i, reloc) in relocs.enumerate() {
+                    let target = address(reloc.section, &self.sections[reloc.section]);
+                    let sym = reloc.symbol.as_ref()
+                        .map_or(0, |name| symbol_index(&self.dynamic, name));
+                    let at = table + 24 * i;
+                    for (j, field) in [target + reloc.offset, (sym << 32) | reloc.kind as u64, 0]
+                        .iter().enumerate() {
+                        out[at + 8 * j..at + 8 * (j + 1)].copy_from_slice(&field.to_le_bytes());
+                    }
+                }
+            }
+        }
+        while !out.len().is_multiple_of(8) {
+            out.push(0);
+        }
+        let shoff = out.len() as u64;
+        out.extend_from_slice(&[0; 64]);
+        let strtab = self.sections.iter().position(|s| s.name == ".strtab").unwrap_or(0);
+        let locals = self.symbols.iter().filter(|sym| sym.info >> 4 == STB_LOCAL).count();
+        for (i, section) in self.sections.iter().enumerate() {
+            out.extend_from_slice(&names[i].to_le_bytes());
+            out.extend_from_slice(&section.kind.to_le_bytes());
+            out.extend_from_slice(&section.flags.to_le_bytes());
+            out.extend_from_slice(&address(i, section).to_le_bytes());
+            out.extend_from_slice(&offsets[i].to_le_bytes());
+            out.extend_from_slice(&section.size.to_le_bytes());
+            let info = match section.info {
+                Info::None => 0,
+                Info::Section(target) => target as u32 + 1,
+                Info::Symbol(ref name) => symbol_index(&self.symbols, name) as u32,
+            };
+            let (link, info, entsize) = match section.kind {
+                SHT_SYMTAB => (strtab as u32 + 1, locals as u32 + 1, 24),
+                SHT_DYNSYM => (dynsym as u32 + 2, 1, 24),
+                SHT_DYNAMIC => (dynsym as u32 + 2, 0, 16),
+                SHT_RELA if i >= dynsym && i < dynamic_index => (dynsym as u32 + 1, 0, 24),
+                SHT_RELA | SHT_GROUP => (defined as u32 + 1, info, section.entsize),
+                _ => (0, info, section.entsize),
+            };
+            out.extend_from_slice(&link.to_le_bytes());
+            out.extend_from_slice(&info.to_le_bytes());
+            out.extend_from_slice(&8u64.to_le_bytes());
+            out.extend_from_slice(&entsize.to_le_bytes());
+        }
+
+        let mut header = Vec::new();
+        header.extend_from_slice(b"\x7fELF\x02\x01\x01\0\0\0\0\0\0\0\0\0");
+        header.extend_from_slice(&self.kind.to_le_bytes());
+        header.extend_from_slice(&self.machine.to_le_bytes());
+        header.extend_from_slice(&1u32.to_le_bytes());
+        let entry = match self.sections.iter().position(|s| s.name == ".text") {
+            Some(text) => address(text, &self.sections[text]),
+            None => 0,
+        };
+        header.extend_from_slice(&entry.to_le_bytes());
+        let phoff: u64 = if self.segments.is_empty() { 0 } else { 64 };
+        header.extend_from_slice(&phoff.to_le_bytes());
+        header.extend_from_slice(&shoff.to_le_bytes());
+        header.extend_from_slice(&0u32.to_le_bytes());
+        for half in &[64, 56, self.segments.len() as u16, 64, self.sections.len() as u16 + 1,
+                      self.sections.len() as u16] {
+            header.extend_from_slice(&half.to_le_bytes());
+        }
+        out[..64].copy_from_slice(&header);
+
+        for (i, segment) in self.segments.iter().enumerate() {
+            let first = segment.sections[0];
+            let last = *segment.sections.last().unwrap();
+            let offset = if segment.headers { 0 } else { offsets[first] };
+            let vaddr = address(first, &self.sections[first]) - (offsets[first] - offset);
+            let end = offsets[last] + self.sections[last].data.len() as u64;
+            let memsz = address(last, &self.sections[last]) + self.sections[last].size - vaddr;
+            let mut phdr = Vec::new();
+            phdr.extend_from_slice(&segment.kind.to_le_bytes());
+            phdr.extend_from_slice(&segment.flags.to_le_bytes());
+            for field in &[offset, vaddr, vaddr, end - offset, memsz, segment.align] {
+                phdr.extend_from_slice(&field.to_le_bytes());
+            }
+            out[64 + 56 * i..64 + 56 * (i + 1)].copy_from_slice(&phdr);
+        }
+        out
+    }
+}
+
+/// Overwrite the `sh_offset` and `sh_size` of the section at `index` among those added to the
+/// ELF file `buf`, as a corrupt file might have them.
+pub fn corrupt_section(buf: &mut [u8], index: usize, offset: u64, size: u64) {
+    let mut shoff = [0; 8];
+    shoff.copy_from_slice(&buf[0x28..0x30]);
+    let header = u64::from_le_bytes(shoff) as usize + 64 * (index + 1);
+    buf[header + 24..header + 32].copy_from_slice(&offset.to_le_bytes());
+    buf[header + 32..header + 40].copy_from_slice(&size.to_le_bytes());
+}
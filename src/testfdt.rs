@@ -0,0 +1,109 @@
+//! Version 17 flattened device trees, built in memory for tests.
+
+/// A device tree being built: its memory reservations, its structure block, with nodes opened
+/// and closed in order, and the property names of its strings block.
+pub struct Tree {
+    reservations: Vec<(u64, u64)>,
+    structure: Vec<u8>,
+    strings: Vec<u8>,
+    /// The free space after the blocks.
+    padding: usize,
+}
+
+fn be32(out: &mut Vec<u8>, word: u32) {
+    out.extend_from_slice(&word.to_be_bytes());
+}
+
+/// Pad `out` with zeros to a multiple of 4 bytes.
+fn pad(out: &mut Vec<u8>) {
+    while !out.len().is_multiple_of(4) {
+        out.push(0);
+    }
+}
+
+impl Tree {
+    /// A tree whose root node is open.
+    pub fn root() -> Tree {
+        Tree { reservations: Vec::new(), structure: Vec::new(), strings: Vec::new(), padding: 0 }
+            .begin("")
+    }
+
+    /// Open a node called `name`, which the properties and nodes up to the matching `end`
+    /// belong to.
+    pub fn begin(mut self, name: &str) -> Tree {
+        be32(&mut self.structure, 1);
+        self.structure.extend_from_slice(name.as_bytes());
+        self.structure.push(0);
+        pad(&mut self.structure);
+        self
+    }
+
+    pub fn end(mut self) -> Tree {
+        be32(&mut self.structure, 2);
+        self
+    }
+
+    /// Add the property `name` with the value `value`.
+    pub fn property(mut self, name: &str, value: &[u8]) -> Tree {
+        be32(&mut self.structure, 3);
+        be32(&mut self.structure, value.len() as u32);
+        be32(&mut self.structure, self.strings.len() as u32);
+        self.strings.extend_from_slice(name.as_bytes());
+        self.strings.push(0);
+        self.structure.extend_from_slice(value);
+        pad(&mut self.structure);
+        self
+    }
+
+    /// Add a string property, with its terminating NUL.
+    pub fn string(self, name: &str, value: &str) -> Tree {
+        let mut bytes = value.as_bytes().to_vec();
+        bytes.push(0);
+        self.property(name, &bytes)
+    }
+
+    /// Add a property holding one 32-bit cell.
+    pub fn cell(self, name: &str, value: u32) -> Tree {
+        self.property(name, &value.to_be_bytes())
+    }
+
+    /// Reserve `size` bytes of memory at `address`.
+    pub fn reserve(mut self, address: u64, size: u64) -> Tree {
+        self.reservations.push((address, size));
+        self
+    }
+
+    /// Leave `padding` bytes of free space at the end of the tree.
+    pub fn padding(mut self, padding: usize) -> Tree {
+        self.padding = padding;
+        self
+    }
+
+    /// The bytes of the tree, with its root node closed: the header, the memory reservation
+    /// block, the structure block, the strings block and the padding.
+    pub fn build(mut self) -> Vec<u8> {
+        be32(&mut self.structure, 2);
+        be32(&mut self.structure, 9);
+        let reserve: usize = 40;
+        let structure = reserve + 16 * (self.reservations.len() + 1);
+        let strings = structure + self.structure.len();
+        let total = strings + self.strings.len() + self.padding;
+        let mut out = Vec::new();
+        // The magic, the offsets of the blocks, the version and the last version it is
+        // compatible with, the boot CPU, and the block sizes.
+        for &word in &[0xd00d_feed, total as u32, structure as u32, strings as u32,
+                       reserve as u32, 17, 16, 0, self.strings.len() as u32,
+                       self.structure.len() as u32] {
+            be32(&mut out, word);
+        }
+        for &(address, size) in &self.reservations {
+            out.extend_from_slice(&address.to_be_bytes());
+            out.extend_from_slice(&size.to_be_bytes());
+        }
+        out.extend_from_slice(&[0; 16]);
+        out.extend_from_slice(&self.structure);
+        out.extend_from_slice(&self.strings);
+        out.resize(total, 0);
+        out
+    }
+}
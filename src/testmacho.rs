@@ -0,0 +1,316 @@
+//! Minimal 64-bit little-endian x86-64 Mach-O files, built in memory for tests.
+
+use goblin::mach::constants::cputype::CPU_TYPE_X86_64;
+use goblin::mach::header::{MH_EXECUTE, MH_MAGIC_64, MH_OBJECT};
+use goblin::mach::load_command::{LC_DYLD_INFO_ONLY, LC_DYSYMTAB, LC_SEGMENT_64, LC_SYMTAB};
+use goblin::mach::load_command::LC_UUID;
+
+/// Where executables are loaded: the address of their sections is this plus their offset in
+/// the file.
+pub const BASE: u64 = 0x1_0000_0000;
+
+/// `N_SECT` and `N_EXT`, the `n_type` bits of defined and of external symbols.
+const N_SECT: u8 = 0xe;
+const N_EXT: u8 = 1;
+
+struct Section {
+    segment: String,
+    name: String,
+    flags: u32,
+    data: Vec<u8>,
+}
+
+struct Symbol {
+    name: String,
+    n_type: u8,
+    /// The index of the section among those added, or `None` for an undefined symbol.
+    section: Option<usize>,
+    offset: u64,
+}
+
+impl Symbol {
+    /// The part of the symbol table `LC_DYSYMTAB` puts the symbol in: local, external or
+    /// undefined.
+    fn partition(&self) -> u32 {
+        match (self.n_type & N_EXT, self.section) {
+            (0, _) => 0,
+            (_, Some(_)) => 1,
+            (_, None) => 2,
+        }
+    }
+}
+
+/// A Mach-O file being built: its sections, grouped into segments by name, its symbols, and
+/// the tables in `__LINKEDIT`.
+pub struct MachO {
+    filetype: u32,
+    sections: Vec<Section>,
+    symbols: Vec<Symbol>,
+    /// The number of indirect symbols, if there is an `LC_DYSYMTAB`.
+    dysymtab: Option<u32>,
+    /// The export trie of `LC_DYLD_INFO_ONLY`.
+    exports: Option<Vec<u8>>,
+    /// `linkedit_data_command`s, with the data they point at.
+    linkedit: Vec<(u32, Vec<u8>)>,
+    uuid: Option<[u8; 16]>,
+}
+
+/// 4-byte little-endian words.
+fn words(out: &mut Vec<u8>, words: &[u32]) {
+    for word in words {
+        out.extend_from_slice(&word.to_le_bytes());
+    }
+}
+
+/// A 16-byte segment or section name.
+fn name16(out: &mut Vec<u8>, name: &str) {
+    let mut field = [0; 16];
+    field[..name.len()].copy_from_slice(name.as_bytes());
+    out.extend_from_slice(&field);
+}
+
+impl MachO {
+    /// A relocatable object.
+    pub fn object() -> MachO {
+        MachO {
+            filetype: MH_OBJECT,
+            sections: Vec::new(),
+            symbols: Vec::new(),
+            dysymtab: None,
+            exports: None,
+            linkedit: Vec::new(),
+            uuid: None,
+        }
+    }
+
+    /// An executable, whose sections are loaded at `BASE` plus their file offsets.
+    pub fn executable() -> MachO {
+        MachO { filetype: MH_EXECUTE, ..MachO::object() }
+    }
+
+    fn index(&self, name: &str) -> usize {
+        self.sections.iter().position(|s| s.name == name)
+            .unwrap_or_else(|| panic!("no section {}", name))
+    }
+
+    /// Add the section `segment.name` holding `data`, with the type and attributes `flags`.
+    pub fn section(mut self, segment: &str, name: &str, flags: u32, data: &[u8]) -> MachO {
+        self.sections.push(Section {
+            segment: segment.to_string(),
+            name: name.to_string(),
+            flags,
+            data: data.to_vec(),
+        });
+        self
+    }
+
+    /// Add an external symbol at `offset` in the section called `section`.
+    pub fn symbol(mut self, name: &str, section: &str, offset: u64) -> MachO {
+        let section = Some(self.index(section));
+        self.symbols.push(Symbol { name: name.to_string(), n_type: N_SECT | N_EXT, section,
+                                   offset });
+        self
+    }
+
+    /// Add a local symbol, like `symbol`.
+    pub fn local(mut self, name: &str, section: &str, offset: u64) -> MachO {
+        self = self.symbol(name, section, offset);
+        self.symbols.last_mut().unwrap().n_type = N_SECT;
+        self
+    }
+
+    /// Add an undefined external symbol.
+    pub fn undefined(mut self, name: &str) -> MachO {
+        self.symbols.push(Symbol { name: name.to_string(), n_type: N_EXT, section: None,
+                                   offset: 0 });
+        self
+    }
+
+    /// Add an `LC_DYSYMTAB` partitioning the symbols, with `indirect` indirect symbols.
+    pub fn dysymtab(mut self, indirect: u32) -> MachO {
+        self.dysymtab = Some(indirect);
+        self
+    }
+
+    /// Add an `LC_DYLD_INFO_ONLY` with the export trie `exports`.
+    pub fn dyld_info(mut self, exports: &[u8]) -> MachO {
+        self.exports = Some(exports.to_vec());
+        self
+    }
+
+    /// Add a `linkedit_data_command` of type `cmd` pointing at `data` in `__LINKEDIT`.
+    pub fn linkedit_data(mut self, cmd: u32, data: &[u8]) -> MachO {
+        self.linkedit.push((cmd, data.to_vec()));
+        self
+    }
+
+    /// Add an `LC_UUID`.
+    pub fn uuid(mut self, uuid: [u8; 16]) -> MachO {
+        self.uuid = Some(uuid);
+        self
+    }
+
+    /// The bytes of the file: the header, the load commands, the section contents, and the
+    /// `__LINKEDIT` tables.
+    pub fn build(mut self) -> Vec<u8> {
+        // Sections are laid out segment by segment, in the order the segments first appear.
+        let mut segments: Vec<String> = Vec::new();
+        for section in &self.sections {
+            if !segments.contains(&section.segment) {
+                segments.push(section.segment.clone());
+            }
+        }
+        let mut order: Vec<usize> = Vec::new();
+        for segment in &segments {
+            let sections = &self.sections;
+            order.extend((0..sections.len()).filter(|&i| sections[i].segment == *segment));
+        }
+        // Symbols are sorted as `LC_DYSYMTAB` partitions them: local, external, undefined.
+        self.symbols.sort_by_key(Symbol::partition);
+        let has_linkedit = !self.symbols.is_empty() || self.exports.is_some() ||
+            !self.linkedit.is_empty();
+        let executable = self.filetype == MH_EXECUTE;
+
+        let mut ncmds = segments.len() + self.linkedit.len();
+        let mut sizeofcmds = 72 * segments.len() + 80 * self.sections.len() +
+            16 * self.linkedit.len();
+        if executable && has_linkedit {
+            ncmds += 1;
+            sizeofcmds += 72;
+        }
+        if self.exports.is_some() {
+            ncmds += 1;
+            sizeofcmds += 48;
+        }
+        if !self.symbols.is_empty() {
+            ncmds += 1;
+            sizeofcmds += 24;
+        }
+        if self.dysymtab.is_some() {
+            ncmds += 1;
+            sizeofcmds += 80;
+        }
+        if self.uuid.is_some() {
+            ncmds += 1;
+            sizeofcmds += 24;
+        }
+
+        let mut out = vec![0; 32 + sizeofcmds];
+        let mut offsets = vec![0; self.sections.len()];
+        for &i in &order {
+            while !out.len().is_multiple_of(16) {
+                out.push(0);
+            }
+            offsets[i] = out.len() as u64;
+            out.extend_from_slice(&self.sections[i].data);
+        }
+        let base = if executable { BASE } else { 0 };
+
+        // `__LINKEDIT`: the export trie and other linkedit data, then the symbol table, the
+        // indirect symbol table and the string table.
+        while !out.len().is_multiple_of(8) {
+            out.push(0);
+        }
+        let linkedit_start = out.len();
+        let exports = self.exports.as_ref().map(|trie| {
+            let offset = out.len() as u32;
+            out.extend_from_slice(trie);
+            (offset, trie.len() as u32)
+        });
+        let mut linkedit_offsets = Vec::new();
+        for (_, data) in &self.linkedit {
+            linkedit_offsets.push(out.len() as u32);
+            out.extend_from_slice(data);
+        }
+        while !out.len().is_multiple_of(8) {
+            out.push(0);
+        }
+        let symoff = out.len() as u32;
+        let mut strtab = vec![b' ', 0];
+        for sym in &self.symbols {
+            words(&mut out, &[strtab.len() as u32]);
+            strtab.extend_from_slice(sym.name.as_bytes());
+            strtab.push(0);
+            out.push(sym.n_type);
+            let n_sect = sym.section.map_or(0, |s| order.iter().position(|&i| i == s).unwrap() + 1);
+            out.push(n_sect as u8);
+            out.extend_from_slice(&[0; 2]);
+            let value = sym.section.map_or(0, |s| base + offsets[s] + sym.offset);
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+        let indirectsymoff = out.len() as u32;
+        let indirect = self.dysymtab.unwrap_or(0);
+        for i in 0..indirect {
+            words(&mut out, &[i]);
+        }
+        let stroff = out.len() as u32;
+        if !self.symbols.is_empty() {
+            out.extend_from_slice(&strtab);
+        }
+        let linkedit_end = out.len();
+
+        let mut cmds = Vec::new();
+        for segment in &segments {
+            let sections: Vec<usize> = order.iter().cloned()
+                .filter(|&i| self.sections[i].segment == *segment)
+                .collect();
+            let fileoff = offsets[sections[0]];
+            let last = *sections.last().unwrap();
+            let filesize = offsets[last] + self.sections[last].data.len() as u64 - fileoff;
+            words(&mut cmds, &[LC_SEGMENT_64, 72 + 80 * sections.len() as u32]);
+            name16(&mut cmds, segment);
+            for field in &[base + fileoff, filesize, fileoff, filesize] {
+                cmds.extend_from_slice(&field.to_le_bytes());
+            }
+            words(&mut cmds, &[7, 7, sections.len() as u32, 0]);
+            for &i in &sections {
+                let section = &self.sections[i];
+                name16(&mut cmds, &section.name);
+                name16(&mut cmds, segment);
+                for field in &[base + offsets[i], section.data.len() as u64] {
+                    cmds.extend_from_slice(&field.to_le_bytes());
+                }
+                words(&mut cmds, &[offsets[i] as u32, 4, 0, 0, section.flags, 0, 0, 0]);
+            }
+        }
+        if executable && has_linkedit {
+            let fileoff = linkedit_start as u64;
+            let filesize = (linkedit_end - linkedit_start) as u64;
+            words(&mut cmds, &[LC_SEGMENT_64, 72]);
+            name16(&mut cmds, "__LINKEDIT");
+            for field in &[base + fileoff, filesize, fileoff, filesize] {
+                cmds.extend_from_slice(&field.to_le_bytes());
+            }
+            words(&mut cmds, &[1, 1, 0, 0]);
+        }
+        if let Some((offset, size)) = exports {
+            words(&mut cmds, &[LC_DYLD_INFO_ONLY, 48, 0, 0, 0, 0, 0, 0, 0, 0, offset, size]);
+        }
+        if !self.symbols.is_empty() {
+            words(&mut cmds, &[LC_SYMTAB, 24, symoff, self.symbols.len() as u32, stroff,
+                               strtab.len() as u32]);
+        }
+        if self.dysymtab.is_some() {
+            let count = |n| self.symbols.iter().filter(|sym| sym.partition() == n).count() as u32;
+            let (locals, externals, undefined) = (count(0), count(1), count(2));
+            words(&mut cmds, &[LC_DYSYMTAB, 80, 0, locals, locals, externals,
+                               locals + externals, undefined, 0, 0, 0, 0, 0, 0,
+                               indirectsymoff, indirect, 0, 0, 0, 0]);
+        }
+        for (&(cmd, ref data), &offset) in self.linkedit.iter().zip(&linkedit_offsets) {
+            words(&mut cmds, &[cmd, 16, offset, data.len() as u32]);
+        }
+        if let Some(uuid) = self.uuid {
+            words(&mut cmds, &[LC_UUID, 24]);
+            cmds.extend_from_slice(&uuid);
+        }
+        assert_eq!(cmds.len(), sizeofcmds);
+
+        let mut header = Vec::new();
+        words(&mut header, &[MH_MAGIC_64, CPU_TYPE_X86_64, 3, self.filetype, ncmds as u32,
+                             sizeofcmds as u32, 0, 0]);
+        out[..32].copy_from_slice(&header);
+        out[32..32 + sizeofcmds].copy_from_slice(&cmds);
+        out
+    }
+}
@@ -0,0 +1,53 @@
+//! ZIP archives, built in memory for tests.
+
+use miniz_oxide::deflate::compress_to_vec;
+use zip::{CENTRAL_DIRECTORY_HEADER, DEFLATED, END_OF_CENTRAL_DIRECTORY, LOCAL_FILE_HEADER};
+
+/// A ZIP archive of `files`, as (name, method, local extra field length, contents), without
+/// checksums, which aren't checked.
+pub fn archive(files: &[(&str, u16, usize, &[u8])]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut central = Vec::new();
+    for &(name, method, extra, contents) in files {
+        let data = if method == DEFLATED { compress_to_vec(contents, 6) } else {
+            contents.to_vec()
+        };
+        let sizes = [data.len() as u32, contents.len() as u32];
+        let local = out.len() as u32;
+        out.extend_from_slice(&LOCAL_FILE_HEADER.to_le_bytes());
+        out.extend_from_slice(&[20, 0, 0, 0]);
+        out.extend_from_slice(&method.to_le_bytes());
+        out.extend_from_slice(&[0; 8]);
+        for field in &sizes {
+            out.extend_from_slice(&field.to_le_bytes());
+        }
+        out.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(extra as u16).to_le_bytes());
+        out.extend_from_slice(name.as_bytes());
+        out.extend_from_slice(&vec![0; extra]);
+        out.extend_from_slice(&data);
+
+        central.extend_from_slice(&CENTRAL_DIRECTORY_HEADER.to_le_bytes());
+        central.extend_from_slice(&[20, 0, 20, 0, 0, 0]);
+        central.extend_from_slice(&method.to_le_bytes());
+        central.extend_from_slice(&[0; 8]);
+        for field in &sizes {
+            central.extend_from_slice(&field.to_le_bytes());
+        }
+        central.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        central.extend_from_slice(&[0; 12]);
+        central.extend_from_slice(&local.to_le_bytes());
+        central.extend_from_slice(name.as_bytes());
+    }
+    let offset = out.len() as u32;
+    out.extend_from_slice(&central);
+    out.extend_from_slice(&END_OF_CENTRAL_DIRECTORY.to_le_bytes());
+    out.extend_from_slice(&[0; 4]);
+    for _ in 0..2 {
+        out.extend_from_slice(&(files.len() as u16).to_le_bytes());
+    }
+    out.extend_from_slice(&(central.len() as u32).to_le_bytes());
+    out.extend_from_slice(&offset.to_le_bytes());
+    out.extend_from_slice(b"\x07\0comment");
+    out
+}
@@ -0,0 +1,295 @@
+use analyze;
+use arch::{fat_slice, mach_arch};
+use exit::UsageError;
+use failure::Error;
+use goblin::mach::{Mach, MachO};
+use goblin::Object;
+use map_file;
+use miniz_oxide::deflate::compress_to_vec;
+use std::cmp;
+use std::fs;
+use std::path::Path;
+use zip;
+
+/// The deflate level to estimate download sizes with, that of the App Store's packages.
+const LEVEL: u8 = 6;
+
+/// What happens to one Mach-O file of an app when the App Store thins it for a device.
+#[derive(Clone, Debug, Serialize)]
+pub struct ThinnedBinary {
+    pub path: String,
+    /// The architectures of its slices.
+    pub arches: Vec<String>,
+    pub size: u64,
+    /// The slices for other architectures, which the device doesn't get.
+    pub other_slices: u64,
+    /// The `__LLVM` segments and sections of the bitcode that Xcode used to embed, which the App
+    /// Store strips.
+    pub bitcode: u64,
+    /// The size of the slice if the file is a library that the OS has in its shared region,
+    /// like the Swift runtime that older toolchains bundled with apps: the App Store leaves it
+    /// out and the app uses the OS's.
+    pub shared: u64,
+    /// What is left, which is private to the app.
+    pub private: u64,
+    /// The size of `private` compressed, as in the package the device downloads.
+    pub download: u64,
+}
+
+/// The bytes of an app that survive App Store thinning for one architecture, as emitted by
+/// `thinning --format json`.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ThinningReport {
+    pub arch: String,
+    pub binaries: Vec<ThinnedBinary>,
+    /// The files that aren't Mach-O, like resources, which thinning leaves alone.
+    pub resources: u64,
+    pub resources_download: u64,
+    pub size: u64,
+    pub other_slices: u64,
+    pub bitcode: u64,
+    pub shared: u64,
+    pub private: u64,
+    /// The approximate size of the thinned app's download.
+    pub download: u64,
+}
+
+/// Whether `buf` starts like a Mach-O file or a universal binary.
+pub fn is_mach(buf: &[u8]) -> bool {
+    matches!(buf.get(..4), Some([0xcf, 0xfa, 0xed, 0xfe]) | Some([0xce, 0xfa, 0xed, 0xfe]) |
+                           Some([0xca, 0xfe, 0xba, 0xbe]))
+}
+
+/// Whether the library with file name `name` and install name `id` is one that the OS has in
+/// its shared region, so that the App Store doesn't ship the app's copy.
+fn in_shared_region(name: &str, id: Option<&str>) -> bool {
+    (name.starts_with("libswift") && name.ends_with(".dylib")) ||
+        id.is_some_and(|id| id.starts_with("/usr/lib/") || id.starts_with("/System/"))
+}
+
+/// The file ranges of the bitcode in `mach`: its `__LLVM` segment, or in object files, where
+/// all sections are in one segment, its `__LLVM` sections.
+fn bitcode_ranges(mach: &MachO) -> Vec<(usize, usize)> {
+    let mut ranges = Vec::new();
+    for segment in mach.segments.iter() {
+        if segment.name().ok() == Some("__LLVM") {
+            ranges.push((segment.fileoff as usize, segment.filesize as usize));
+            continue;
+        }
+        for (section, _) in segment.sections().into_iter().flatten() {
+            if section.segname().ok() == Some("__LLVM") {
+                ranges.push((section.offset as usize, section.size as usize));
+            }
+        }
+    }
+    ranges
+}
+
+/// The contents of `slice` without the ranges in `ranges`.
+fn without(slice: &[u8], ranges: &[(usize, usize)]) -> Vec<u8> {
+    let mut kept = slice.to_vec();
+    let mut ranges = ranges.to_vec();
+    ranges.sort_by_key(|&(offset, _)| cmp::Reverse(offset));
+    for (offset, size) in ranges {
+        let start = offset.min(kept.len());
+        let end = offset.saturating_add(size).min(kept.len());
+        kept.drain(start..end);
+    }
+    kept
+}
+
+/// Thin the Mach-O file `buf`, with file name `name`, for `arch`. The slice for `arch` is the
+/// one kept, or the whole file if it has none, as the App Store can't thin it then.
+fn thin(path: &str, name: &str, buf: &[u8], arch: &str) -> Result<ThinnedBinary, Error> {
+    let (arches, slice, mach) = match Object::parse(buf)? {
+        Object::Mach(Mach::Fat(fat)) => {
+            let slices = fat.arches()?;
+            let arches: Vec<String> =
+                slices.iter().map(|slice| mach_arch(slice.cputype).to_string()).collect();
+            let slice = match arches.iter().position(|a| a == arch) {
+                Some(i) => fat_slice(buf, &slices[i])?,
+                None => buf,
+            };
+            let mach = if slice.len() < buf.len() { Some(MachO::parse(slice, 0)?) } else { None };
+            (arches, slice, mach)
+        }
+        Object::Mach(Mach::Binary(mach)) => {
+            (vec![mach_arch(mach.header.cputype()).to_string()], buf, Some(mach))
+        }
+        _ => bail!("{}: not a Mach-O file", path),
+    };
+    let size = buf.len() as u64;
+    let other_slices = (buf.len() - slice.len()) as u64;
+    let mut binary = ThinnedBinary {
+        path: path.to_string(),
+        arches,
+        size,
+        other_slices,
+        bitcode: 0,
+        shared: 0,
+        private: 0,
+        download: 0,
+    };
+    let mach = match mach {
+        Some(mach) => mach,
+        // A universal binary without the architecture only loses its bitcode, which isn't
+        // looked for in every slice.
+        None => {
+            binary.private = size;
+            binary.download = compress_to_vec(buf, LEVEL).len() as u64;
+            return Ok(binary);
+        }
+    };
+    if in_shared_region(name, mach.name) {
+        binary.shared = slice.len() as u64;
+        return Ok(binary);
+    }
+    let kept = without(slice, &bitcode_ranges(&mach));
+    binary.bitcode = (slice.len() - kept.len()) as u64;
+    binary.private = kept.len() as u64;
+    binary.download = compress_to_vec(&kept, LEVEL).len() as u64;
+    Ok(binary)
+}
+
+impl ThinningReport {
+    /// Count the file `buf` at `path`, whose size in the package is `compressed_size` if it
+    /// is in one. Files that start like Mach-O but don't parse as it, like Java class files,
+    /// which share the universal binary magic, count as resources.
+    fn add(&mut self, path: &str, buf: &[u8], compressed_size: Option<u64>) {
+        let name = path.rsplit(['/', '\\']).next().unwrap_or(path);
+        let binary = if is_mach(buf) { thin(path, name, buf, &self.arch).ok() } else { None };
+        match binary {
+            Some(binary) => {
+                self.size += binary.size;
+                self.other_slices += binary.other_slices;
+                self.bitcode += binary.bitcode;
+                self.shared += binary.shared;
+                self.private += binary.private;
+                self.download += binary.download;
+                self.binaries.push(binary);
+            }
+            None => {
+                let download = compressed_size
+                    .unwrap_or_else(|| compress_to_vec(buf, LEVEL).len() as u64);
+                self.size += buf.len() as u64;
+                self.resources += buf.len() as u64;
+                self.resources_download += download;
+                self.download += download;
+            }
+        }
+    }
+
+    /// Thin the app at `path`, an `.ipa`, an `.app` directory or a single Mach-O file, for
+    /// `arch`. Resources in an `.ipa` count at their size there; other files are compressed
+    /// to estimate the download.
+    pub fn new(path: &Path, arch: &str) -> Result<ThinningReport, Error> {
+        let mut report = ThinningReport { arch: arch.to_string(), ..Default::default() };
+        if fs::metadata(path).map_err(|e| format_err!("{}: {}", path.display(), e))?.is_dir() {
+            let mut paths = Vec::new();
+            analyze::walk(path, &mut paths)?;
+            for file in &paths {
+                report.add(file, &map_file(file.as_ref())?, None);
+            }
+        } else {
+            let buf = map_file(path.as_os_str())?;
+            if zip::is_zip(&buf) {
+                for entry in zip::entries(&buf)? {
+                    let compressed_size = entry.compressed_size as u64;
+                    report.add(&entry.name, &entry.read(&buf)?, Some(compressed_size));
+                }
+            } else if is_mach(&buf) {
+                report.add(&path.to_string_lossy(), &buf, None);
+            } else {
+                return Err(UsageError(format!("{}: not an .ipa, .app directory or Mach-O file",
+                                              path.display())).into());
+            }
+        }
+        report.binaries.sort_by(|a, b| (b.size, &a.path).cmp(&(a.size, &b.path)));
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::mach::constants::cputype::{CPU_TYPE_ARM64, CPU_TYPE_X86_64};
+    use goblin::mach::constants::S_ATTR_PURE_INSTRUCTIONS;
+    use std::{env, process};
+    use testmacho::MachO;
+    use testzip;
+
+    /// An executable with 256 bytes of bitcode.
+    fn executable() -> Vec<u8> {
+        MachO::executable()
+            .section("__TEXT", "__text", S_ATTR_PURE_INSTRUCTIONS, &[0xc3; 64])
+            .section("__LLVM", "__bundle", 0, &[0x42; 256])
+            .build()
+    }
+
+    /// A universal binary of `x86_64`, then `arm64`, a slice of noise that isn't looked at.
+    fn universal(x86_64: &[u8], arm64: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let offsets = [48, 48 + x86_64.len()];
+        for word in &[0xcafe_babe, 2, CPU_TYPE_X86_64, 3, offsets[0] as u32, x86_64.len() as u32,
+                      0, CPU_TYPE_ARM64, 0, offsets[1] as u32, arm64.len() as u32, 0] {
+            out.extend_from_slice(&u32::to_be_bytes(*word));
+        }
+        out.extend_from_slice(x86_64);
+        out.extend_from_slice(arm64);
+        out
+    }
+
+    #[test]
+    fn shared_region_and_bitcode() {
+        assert!(in_shared_region("libswiftCore.dylib", None));
+        assert!(in_shared_region("Foundation", Some("/System/Library/Foundation")));
+        assert!(!in_shared_region("libapp.dylib", Some("@rpath/libapp.dylib")));
+        assert_eq!(without(b"abcdefgh", &[(6, 5), (1, 2)]), b"adef");
+        assert!(is_mach(&executable()) && is_mach(&universal(&[], &[])));
+        assert!(!is_mach(b"\x7fELF"));
+
+        let binary = thin("App", "App", &executable(), "x86_64").unwrap();
+        assert_eq!((binary.bitcode, binary.other_slices, binary.shared), (256, 0, 0));
+        assert_eq!(binary.private, binary.size - 256);
+        let binary = thin("libswiftCore.dylib", "libswiftCore.dylib", &executable(), "x86_64")
+            .unwrap();
+        assert_eq!((binary.shared, binary.private, binary.download), (binary.size, 0, 0));
+    }
+
+    #[test]
+    fn universal_binaries_keep_one_slice() {
+        let buf = universal(&executable(), &[0x55; 1000]);
+        let binary = thin("App", "App", &buf, "x86_64").unwrap();
+        assert_eq!(binary.arches, vec!["x86_64", "aarch64"]);
+        assert_eq!((binary.other_slices, binary.bitcode), (48 + 1000, 256));
+        assert_eq!(binary.private, binary.size - 48 - 1000 - 256);
+        // Without a slice for the architecture, the whole binary is kept.
+        let binary = thin("App", "App", &buf, "arm64_32").unwrap();
+        assert_eq!((binary.other_slices, binary.private), (0, binary.size));
+    }
+
+    #[test]
+    fn thinned_ipa() {
+        let dir = env::temp_dir().join(format!("rust-size-{}-thinning", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (app, plist) = (executable(), vec![b'x'; 500]);
+        let ipa = testzip::archive(&[("Payload/App.app/App", zip::DEFLATED, 0, &app),
+                                     ("Payload/App.app/Info.plist", zip::DEFLATED, 0, &plist)]);
+        let path = dir.join("App.ipa");
+        fs::write(&path, &ipa).unwrap();
+        let report = ThinningReport::new(&path, "x86_64").unwrap();
+        assert_eq!(report.binaries.len(), 1);
+        assert_eq!(report.binaries[0].path, "Payload/App.app/App");
+        assert_eq!((report.size, report.bitcode), (app.len() as u64 + 500, 256));
+        assert_eq!(report.resources, 500);
+        assert!(report.resources_download < 500);
+        assert_eq!(report.download, report.binaries[0].download + report.resources_download);
+
+        fs::write(&path, b"not an app").unwrap();
+        match ThinningReport::new(&path, "x86_64") {
+            Err(err) => assert!(err.downcast_ref::<UsageError>().is_some()),
+            Ok(_) => panic!("not an app"),
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
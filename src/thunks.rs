@@ -0,0 +1,287 @@
+use arch;
+use failure::Error;
+use goblin::elf::header::ET_REL;
+use goblin::Object;
+use rustc_demangle;
+use section_records;
+use std::collections::{BTreeMap, HashMap};
+use symbols;
+use CODE_FLAGS;
+
+/// The AArch64 relocations of `b` and `bl`, `R_AARCH64_JUMP26` and `R_AARCH64_CALL26`.
+const AARCH64_BRANCH_RELOCATIONS: &[u32] = &[282, 283];
+
+/// The bytes of an AArch64 instruction, and so of a branch to an outlined function or of the
+/// return that ends one.
+const AARCH64_INSTRUCTION_SIZE: u64 = 4;
+
+/// A function that the compiler outlined from others, or that the compiler or linker generated
+/// to get to another function.
+#[derive(Clone, Debug, Serialize)]
+pub struct Thunk {
+    /// The demangled name, without the hash.
+    pub name: String,
+    /// `outlined` for the sequences that the compiler's outliner (`-moutline`, or
+    /// `-enable-machine-outliner`) shares between functions, `thunk` for compiler-generated
+    /// adjustors (C++ `this` adjustments, Rust shims, retpolines and PC thunks), or
+    /// `trampoline` for linker-generated veneers and branch islands.
+    pub kind: &'static str,
+    pub section: Option<String>,
+    pub address: u64,
+    pub size: u64,
+    /// The branches to the function, for outlined functions on AArch64.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub calls: Option<u64>,
+}
+
+/// The number and size of the functions of one kind.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct KindSize {
+    pub count: u64,
+    pub size: u64,
+}
+
+/// What machine outlining saves, estimated from the branches to outlined functions.
+#[derive(Clone, Debug, Serialize)]
+pub struct Outlining {
+    pub functions: u64,
+    pub calls: u64,
+    /// The bytes that the outlined sequences would take up in their callers, less the branches
+    /// to them and the outlined functions themselves; negative if outlining costs space.
+    pub saved: i64,
+}
+
+/// The thunks, trampolines and outlined functions of a binary, as emitted by
+/// `thunks --format json`.
+#[derive(Clone, Debug, Serialize)]
+pub struct ThunkReport {
+    pub kinds: BTreeMap<&'static str, KindSize>,
+    pub count: u64,
+    pub size: u64,
+    /// The size of all functions, counting aliases once.
+    pub code_size: u64,
+    /// Only known for AArch64, where the branches are easy to find.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outlining: Option<Outlining>,
+    /// The functions, largest first.
+    pub thunks: Vec<Thunk>,
+}
+
+/// The kind of the function `name` (see `Thunk::kind`), if it is a thunk, trampoline or
+/// outlined function. Names are tried without the underscore that Mach-O prepends, too.
+pub fn kind(name: &str) -> Option<&'static str> {
+    let names = [Some(name), name.strip_prefix('_')];
+    for name in names.iter().flatten() {
+        if name.starts_with("OUTLINED_FUNCTION_") {
+            return Some("outlined");
+        }
+        // PC thunks, retpolines and the C++ thunks that adjust `this` (`_ZTh`, `_ZTv`) or the
+        // result (`_ZTc`) before calling a virtual function.
+        let thunk_prefixes = ["__x86.get_pc_thunk.", "__x86_indirect_thunk", "__x86_return_thunk",
+                              "__llvm_retpoline_", "__llvm_lvi_thunk_", "_ZTh", "_ZTv", "_ZTc"];
+        if thunk_prefixes.iter().any(|prefix| name.starts_with(prefix)) {
+            return Some("thunk");
+        }
+        // lld's range extension thunks (`__AArch64AbsLongThunk_f`, `__ARMv7ABSLongThunk_f`),
+        // GNU ld's veneers (`__f_veneer`) and interworking stubs, and ld64's branch islands.
+        if name.starts_with("__") && name.contains("Thunk_") || name.ends_with("_veneer") ||
+            name.ends_with("_from_arm") || name.ends_with("_from_thumb") ||
+            name.ends_with(".island") {
+            return Some("trampoline");
+        }
+    }
+    // Rust's shims: `{vtable.shim}` and `{reify.shim}` with legacy mangling, `{shim:vtable#0}`
+    // with v0.
+    let demangled = format!("{:#}", rustc_demangle::demangle(name));
+    if demangled.contains(".shim}") || demangled.contains("{shim:") {
+        return Some("thunk");
+    }
+    None
+}
+
+/// Count the branches to the functions in `targets`, keyed by section and address, in the
+/// AArch64 binary `buf`: the `b` and `bl` instructions in its code sections, and in relocatable
+/// objects the branch relocations, which leave the instructions pointing at themselves.
+fn aarch64_calls(buf: &[u8], targets: &HashMap<(String, u64), usize>, calls: &mut [u64])
+                 -> Result<(), Error> {
+    let mut relocatable = false;
+    if let Object::Elf(elf) = Object::parse(buf)? {
+        relocatable = elf.header.e_type == ET_REL;
+        let section_name = |shndx: usize| {
+            elf.section_headers.get(shndx)
+                .and_then(|sh| elf.shdr_strtab.get(sh.sh_name))
+                .and_then(|res| res.ok())
+        };
+        for (_, relocs) in &elf.shdr_relocs {
+            for reloc in relocs {
+                if !AARCH64_BRANCH_RELOCATIONS.contains(&reloc.r_type) {
+                    continue;
+                }
+                let sym = match elf.syms.get(reloc.r_sym) {
+                    Some(sym) => sym,
+                    None => continue,
+                };
+                let address = (sym.st_value as i64 + reloc.r_addend.unwrap_or(0)) as u64;
+                let key = section_name(sym.st_shndx).map(|name| (name.to_string(), address));
+                if let Some(&i) = key.as_ref().and_then(|key| targets.get(key)) {
+                    calls[i] += 1;
+                }
+            }
+        }
+    }
+    for record in section_records(buf)? {
+        if !record.flag_names.iter().any(|flag| CODE_FLAGS.contains(flag)) {
+            continue;
+        }
+        let code = match record.offset {
+            Some(offset) => buf.get(offset as usize..(offset + record.size) as usize),
+            None => None,
+        };
+        let start = if relocatable { 0 } else { record.address.unwrap_or(0) };
+        let mut key = (record.name, 0);
+        for (i, word) in code.unwrap_or(&[]).chunks_exact(4).enumerate() {
+            let word = u32::from_le_bytes([word[0], word[1], word[2], word[3]]);
+            // `b` and `bl`, with a signed 26-bit offset in instructions.
+            if word & 0x7c00_0000 != 0x1400_0000 {
+                continue;
+            }
+            let offset = i64::from(((word << 6) as i32) >> 6) * 4;
+            if offset == 0 {
+                continue;
+            }
+            key.1 = (start as i64 + 4 * i as i64 + offset) as u64;
+            if let Some(&i) = targets.get(&key) {
+                calls[i] += 1;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Find the thunks, trampolines and outlined functions of `buf` by their names, and on AArch64
+/// estimate what outlining saves from the branches to the outlined functions.
+pub fn thunks(buf: &[u8]) -> Result<ThunkReport, Error> {
+    let mut functions: Vec<_> = symbols::symbols_inferring(buf, true)?.into_iter()
+        .filter(|sym| sym.code)
+        .map(|sym| (kind(&sym.name), sym))
+        .collect();
+    // Aliases share their function's address; keep the largest of each, preferring a name that
+    // says what the function is.
+    functions.sort_by(|&(a_kind, ref a), &(b_kind, ref b)| {
+        (&a.section, a.address, b.size, b_kind.is_some(), &a.name)
+            .cmp(&(&b.section, b.address, a.size, a_kind.is_some(), &b.name))
+    });
+    functions.dedup_by(|a, b| a.1.section == b.1.section && a.1.address == b.1.address);
+
+    let mut report = ThunkReport {
+        kinds: BTreeMap::new(),
+        count: 0,
+        size: 0,
+        code_size: functions.iter().map(|(_, sym)| sym.size).sum(),
+        outlining: None,
+        thunks: Vec::new(),
+    };
+    let mut outlined = HashMap::new();
+    for (kind, sym) in functions {
+        let kind = match kind {
+            Some(kind) => kind,
+            None => continue,
+        };
+        let totals = report.kinds.entry(kind).or_default();
+        totals.count += 1;
+        totals.size += sym.size;
+        report.count += 1;
+        report.size += sym.size;
+        if kind == "outlined" {
+            if let Some(ref section) = sym.section {
+                outlined.insert((section.clone(), sym.address), report.thunks.len());
+            }
+        }
+        report.thunks.push(Thunk {
+            name: format!("{:#}", rustc_demangle::demangle(&sym.name)),
+            kind,
+            section: sym.section,
+            address: sym.address,
+            size: sym.size,
+            calls: None,
+        });
+    }
+
+    if !outlined.is_empty() && arch::arch(buf)? == "aarch64" {
+        let mut calls = vec![0; report.thunks.len()];
+        aarch64_calls(buf, &outlined, &mut calls)?;
+        let mut outlining = Outlining { functions: outlined.len() as u64, calls: 0, saved: 0 };
+        for &i in outlined.values() {
+            let thunk = &mut report.thunks[i];
+            thunk.calls = Some(calls[i]);
+            outlining.calls += calls[i];
+            // Each caller had the function's instructions but its return, and now has a branch.
+            let size = thunk.size as i64;
+            let instruction = AARCH64_INSTRUCTION_SIZE as i64;
+            outlining.saved += calls[i] as i64 * (size - 2 * instruction) - size;
+        }
+        report.outlining = Some(outlining);
+    }
+    report.thunks.sort_by(|a, b| (b.size, &a.name).cmp(&(a.size, &b.name)));
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::elf::header::EM_AARCH64;
+    use goblin::elf::section_header::{SHF_ALLOC, SHF_EXECINSTR, SHT_PROGBITS};
+    use goblin::elf::sym::STT_FUNC;
+    use testelf::Elf;
+
+    #[test]
+    fn kinds_by_name() {
+        assert_eq!(kind("OUTLINED_FUNCTION_12"), Some("outlined"));
+        assert_eq!(kind("_OUTLINED_FUNCTION_0"), Some("outlined"));
+        assert_eq!(kind("__x86.get_pc_thunk.bx"), Some("thunk"));
+        assert_eq!(kind("__x86_indirect_thunk_rax"), Some("thunk"));
+        assert_eq!(kind("_ZThn8_N3Foo3barEv"), Some("thunk"));
+        assert_eq!(kind("_ZN4core3ops8function6FnOnce40call_once$u7b$$u7b$vtable.shim$u7d$$u7d$\
+                         17h0123456789abcdefE"), Some("thunk"));
+        assert_eq!(kind("__AArch64AbsLongThunk_main"), Some("trampoline"));
+        assert_eq!(kind("__memcpy_veneer"), Some("trampoline"));
+        assert_eq!(kind("_objc_msgSend.island"), Some("trampoline"));
+        assert_eq!(kind("main"), None);
+        assert_eq!(kind("get_pc_thunk"), None);
+    }
+
+    #[test]
+    fn branches_to_outlined_functions() {
+        let mut text = Vec::new();
+        // `main`: bl OUTLINED_FUNCTION_0, bl OUTLINED_FUNCTION_0, b ., ret; then the outlined
+        // function, and a veneer.
+        for &word in &[0x9400_0004u32, 0x9400_0003, 0x1400_0000, 0xd65f_03c0,
+                       0xd503_201f, 0xd503_201f, 0xd65f_03c0, 0xd65f_03c0] {
+            text.extend_from_slice(&word.to_le_bytes());
+        }
+        let buf = Elf::executable()
+            .machine(EM_AARCH64)
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &text)
+            .symbol("main", STT_FUNC, ".text", 0, 16)
+            .symbol("main_alias", STT_FUNC, ".text", 0, 16)
+            .symbol("OUTLINED_FUNCTION_0", STT_FUNC, ".text", 16, 12)
+            .symbol("__main_veneer", STT_FUNC, ".text", 28, 4)
+            .build();
+        let report = thunks(&buf).unwrap();
+        assert_eq!((report.count, report.size, report.code_size), (2, 16, 32));
+        let kinds: Vec<_> = report.kinds.iter().map(|(&k, s)| (k, s.count, s.size)).collect();
+        assert_eq!(kinds, vec![("outlined", 1, 12), ("trampoline", 1, 4)]);
+        let found: Vec<_> = report.thunks.iter().map(|t| (&*t.name, t.calls)).collect();
+        assert_eq!(found, vec![("OUTLINED_FUNCTION_0", Some(2)), ("__main_veneer", None)]);
+        // Each call saves 12 - 8 bytes, and the function costs 12.
+        let outlining = report.outlining.unwrap();
+        assert_eq!((outlining.functions, outlining.calls, outlining.saved), (1, 2, -4));
+
+        // Only AArch64 branches are counted.
+        let buf = Elf::executable()
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &text)
+            .symbol("OUTLINED_FUNCTION_0", STT_FUNC, ".text", 16, 12)
+            .build();
+        assert!(thunks(&buf).unwrap().outlining.is_none());
+    }
+}
@@ -0,0 +1,297 @@
+use failure::Error;
+use input_records;
+use isa;
+use normalize::normalize_name;
+use rustc_demangle;
+use serde_json;
+use std::collections::BTreeMap;
+use std::path::Path;
+use symbols;
+use {counts_toward_total, Section};
+
+/// A box of the treemap, an architecture, a category, a section or a symbol, and the boxes in
+/// it, largest first.
+#[derive(Clone, Debug, Serialize)]
+pub struct Node {
+    pub name: String,
+    pub size: u64,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<Node>,
+}
+
+impl Node {
+    fn new(name: String) -> Node {
+        Node { name, size: 0, children: Vec::new() }
+    }
+
+    /// The child named `name`, added if there is none.
+    fn child(&mut self, name: &str) -> &mut Node {
+        let i = match self.children.iter().position(|child| child.name == name) {
+            Some(i) => i,
+            None => {
+                self.children.push(Node::new(name.to_string()));
+                self.children.len() - 1
+            }
+        };
+        &mut self.children[i]
+    }
+
+    /// Add up the sizes of the nodes that have children from theirs, and sort them.
+    fn finish(&mut self) {
+        if self.children.is_empty() {
+            return;
+        }
+        for child in &mut self.children {
+            child.finish();
+        }
+        self.size = self.children.iter().map(|child| child.size).sum();
+        self.children.sort_by(|a, b| (b.size, &a.name).cmp(&(a.size, &b.name)));
+    }
+}
+
+/// The symbols of `buf` by section and demangled name, counting aliases once, or none if it has
+/// no symbol table that can be read, like archives and saved reports.
+fn symbol_sizes(buf: &[u8], normalize: bool) -> BTreeMap<String, BTreeMap<String, u64>> {
+    let mut syms = symbols::symbols(buf).unwrap_or_default();
+    syms.sort_by(|a, b| {
+        (&a.section, a.address, b.size, &a.name).cmp(&(&b.section, b.address, a.size, &b.name))
+    });
+    syms.dedup_by(|a, b| a.section == b.section && a.address == b.address);
+    let mut sizes: BTreeMap<String, BTreeMap<String, u64>> = BTreeMap::new();
+    for sym in syms {
+        let section = match sym.section {
+            Some(ref section) if normalize => normalize_name(section).to_string(),
+            Some(section) => section,
+            None => continue,
+        };
+        let name = format!("{:#}", rustc_demangle::demangle(&sym.name));
+        *sizes.entry(section).or_default().entry(name).or_insert(0) += sym.size;
+    }
+    sizes
+}
+
+/// The treemap of the loaded sections of the file `buf` at `path`: its categories, their
+/// sections and the symbols in those, and what of each section no symbol covers. The slices of
+/// a universal binary are boxes of their own.
+pub fn tree(path: &Path, buf: &[u8], normalize: bool) -> Result<Node, Error> {
+    let records = input_records(path, buf)?;
+    let slices: BTreeMap<Option<String>, &[u8]> = if records.iter().any(|r| r.arch.is_some()) {
+        isa::slices(buf)?.into_iter().map(|(arch, slice)| (Some(arch), slice)).collect()
+    } else {
+        vec![(None, buf)].into_iter().collect()
+    };
+
+    let mut root = Node::new(path.display().to_string());
+    let mut sections: BTreeMap<(Option<String>, Section, String), u64> = BTreeMap::new();
+    for record in records {
+        if record.name.is_empty() || !counts_toward_total(record.category, false) {
+            continue;
+        }
+        let name = if normalize { normalize_name(&record.name).to_string() } else { record.name };
+        *sections.entry((record.arch, record.category, name)).or_insert(0) += record.size;
+    }
+    let mut symbols = BTreeMap::new();
+    for ((arch, category, name), size) in sections {
+        let parent = match arch {
+            Some(ref arch) => root.child(arch),
+            None => &mut root,
+        };
+        let section = parent.child(&format!("{:?}", category)).child(&name);
+        let symbols = symbols.entry(arch.clone())
+            .or_insert_with(|| symbol_sizes(slices.get(&arch).unwrap_or(&buf), normalize));
+        let mut covered = 0;
+        for (symbol, &symbol_size) in symbols.get(&name).into_iter().flatten() {
+            let mut node = Node::new(symbol.clone());
+            node.size = symbol_size;
+            section.children.push(node);
+            covered += symbol_size;
+        }
+        if section.children.is_empty() {
+            section.size = size;
+        } else if size > covered {
+            let mut node = Node::new("(no symbol)".to_string());
+            node.size = size - covered;
+            section.children.push(node);
+        }
+    }
+    root.finish();
+    Ok(root)
+}
+
+/// The web page showing `root`, with everything it needs in it.
+pub fn html(root: &Node) -> Result<String, Error> {
+    // JSON only has `</` in strings, where `<\/` means the same without ending the script.
+    let data = serde_json::to_string(root)?.replace("</", "<\\/");
+    let title = root.name.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;");
+    Ok(PAGE.replace("{title}", &title).replace("{data}", &data))
+}
+
+/// The treemap page, laid out with the squarified algorithm of Bruls, Huizing and van Wijk.
+/// Clicking a box zooms into it and the path above the map zooms back out.
+const PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<style>
+body { margin: 0; font: 12px sans-serif; display: flex; flex-direction: column; height: 100vh; }
+#path { padding: 6px 8px; background: #333; color: #ccc; }
+#path span { color: #fff; cursor: pointer; }
+#path span:hover { text-decoration: underline; }
+#map { position: relative; flex: 1; overflow: hidden; }
+.box { position: absolute; box-sizing: border-box; border: 1px solid #fff; overflow: hidden;
+       cursor: pointer; white-space: nowrap; text-overflow: ellipsis; padding: 1px 3px; }
+.box:hover { filter: brightness(1.1); }
+</style>
+</head>
+<body>
+<div id="path"></div>
+<div id="map"></div>
+<script type="application/json" id="data">{data}</script>
+<script>
+var root = JSON.parse(document.getElementById("data").textContent);
+var hues = { Text: 210, Data: 120, Bss: 45 };
+var current = root;
+
+function link(node, parent, hue) {
+  node.parent = parent;
+  node.hue = node.name in hues && parent ? hues[node.name] : hue;
+  (node.children || []).forEach(function (child) { link(child, node, node.hue); });
+}
+link(root, null, 0);
+
+function size(bytes) {
+  var units = ["B", "KiB", "MiB", "GiB"], i = 0;
+  while (bytes >= 1024 && i < units.length - 1) { bytes /= 1024; i++; }
+  return (i ? bytes.toFixed(1) : bytes) + " " + units[i];
+}
+
+function worst(row, sum, side) {
+  var max = Math.max.apply(null, row), min = Math.min.apply(null, row);
+  return Math.max(side * side * max / (sum * sum), sum * sum / (side * side * min));
+}
+
+// Lay out `nodes` in the rectangle, as [node, x, y, width, height].
+function squarify(nodes, x, y, w, h) {
+  var total = nodes.reduce(function (sum, node) { return sum + node.size; }, 0);
+  var rest = nodes.filter(function (node) { return node.size > 0; });
+  var scale = w * h / total, boxes = [];
+  while (rest.length) {
+    var side = Math.min(w, h), row = [], sum = 0, best = Infinity;
+    while (rest.length) {
+      var area = rest[0].size * scale;
+      var ratio = worst(row.map(function (n) { return n.size * scale; }).concat(area),
+                        sum + area, side);
+      if (ratio > best) break;
+      best = ratio;
+      row.push(rest.shift());
+      sum += area;
+    }
+    var thickness = sum / side, offset = 0;
+    row.forEach(function (node) {
+      var length = node.size * scale / thickness;
+      boxes.push(w >= h ? [node, x, y + offset, thickness, length]
+                        : [node, x + offset, y, length, thickness]);
+      offset += length;
+    });
+    if (w >= h) { x += thickness; w -= thickness; } else { y += thickness; h -= thickness; }
+  }
+  return boxes;
+}
+
+function render(map, node, x, y, w, h, depth) {
+  var box = document.createElement("div");
+  box.className = "box";
+  box.style.left = x + "px";
+  box.style.top = y + "px";
+  box.style.width = w + "px";
+  box.style.height = h + "px";
+  box.style.background = "hsl(" + node.hue + ", " + (node.hue ? 60 : 0) + "%, " +
+                         (88 - 12 * depth) + "%)";
+  box.title = node.name + "\n" + size(node.size) + ", " +
+              (100 * node.size / root.size).toFixed(1) + "%";
+  if (w > 30 && h > 14) {
+    box.textContent = node.name + " " + size(node.size);
+  }
+  box.onclick = function (event) {
+    event.stopPropagation();
+    if (node.children) { current = node; draw(); }
+  };
+  map.appendChild(box);
+  if (depth < 3 && node.children && w > 8 && h > 24) {
+    squarify(node.children, x + 1, y + 16, w - 2, h - 17).forEach(function (b) {
+      render(map, b[0], b[1], b[2], b[3], b[4], depth + 1);
+    });
+  }
+}
+
+function draw() {
+  var path = document.getElementById("path"), map = document.getElementById("map");
+  path.textContent = "";
+  map.textContent = "";
+  for (var node = current, nodes = []; node; node = node.parent) { nodes.unshift(node); }
+  nodes.forEach(function (node, i) {
+    if (i) { path.appendChild(document.createTextNode(" / ")); }
+    var link = document.createElement("span");
+    link.textContent = node.name;
+    link.onclick = function () { current = node; draw(); };
+    path.appendChild(link);
+  });
+  path.appendChild(document.createTextNode(" " + size(current.size)));
+  squarify(current.children || [current], 0, 0, map.clientWidth, map.clientHeight)
+    .forEach(function (b) { render(map, b[0], b[1], b[2], b[3], b[4], 0); });
+}
+
+window.onresize = draw;
+draw();
+</script>
+</body>
+</html>
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::elf::section_header::{SHF_ALLOC, SHF_EXECINSTR, SHF_WRITE, SHT_PROGBITS};
+    use goblin::elf::sym::STT_FUNC;
+    use testelf::Elf;
+
+    /// The names and sizes of the boxes under `node`, depth first, indented by depth.
+    fn boxes(node: &Node, depth: usize, out: &mut Vec<String>) {
+        for child in &node.children {
+            out.push(format!("{}{} {}", "  ".repeat(depth), child.name, child.size));
+            boxes(child, depth + 1, out);
+        }
+    }
+
+    #[test]
+    fn categories_sections_and_symbols() {
+        let buf = Elf::object()
+            .section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &[0; 40])
+            .section(".data", SHT_PROGBITS, SHF_ALLOC | SHF_WRITE, &[0; 8])
+            .nobits(".bss", SHF_ALLOC | SHF_WRITE, 100)
+            .symbol("_ZN3foo3bar17h0123456789abcdefE", STT_FUNC, ".text", 0, 16)
+            .symbol("bar_alias", STT_FUNC, ".text", 0, 16)
+            .symbol("g", STT_FUNC, ".text", 16, 8)
+            .build();
+        let root = tree(Path::new("a.o"), &buf, false).unwrap();
+        assert_eq!((root.name.as_str(), root.size), ("a.o", 148));
+        let mut out = Vec::new();
+        boxes(&root, 0, &mut out);
+        // The alias counts once, and what no symbol covers is a box of its own.
+        assert_eq!(out, vec!["Bss 100", "  .bss 100",
+                             "Text 40", "  .text 40", "    (no symbol) 16", "    foo::bar 16",
+                             "    g 8",
+                             "Data 8", "  .data 8"]);
+    }
+
+    #[test]
+    fn pages_escape_names() {
+        let mut root = Node::new("<a & b>".to_string());
+        root.children.push(Node { name: "</script>".to_string(), size: 1, children: Vec::new() });
+        let page = html(&root).unwrap();
+        assert!(page.contains("<title>&lt;a &amp; b&gt;</title>"));
+        assert!(page.contains(r#""name":"<\/script>""#));
+        assert_eq!(page.matches("</script>").count(), PAGE.matches("</script>").count());
+    }
+}
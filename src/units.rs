@@ -0,0 +1,175 @@
+use exit::UsageError;
+use failure::Error;
+use std::env;
+use std::str::FromStr;
+
+/// The units sizes are written in, in human-readable output.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Units {
+    /// Plain byte counts.
+    Bytes,
+    /// Powers of 1000: kB, MB, GB.
+    Si,
+    /// Powers of 1024: KiB, MiB, GiB.
+    Iec,
+}
+
+impl FromStr for Units {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Units, Error> {
+        match s {
+            "bytes" => Ok(Units::Bytes),
+            "si" => Ok(Units::Si),
+            "iec" => Ok(Units::Iec),
+            _ => Err(UsageError(format!("Invalid units: {}", s)).into()),
+        }
+    }
+}
+
+/// How to write sizes in human-readable output. JSON output always uses plain byte counts.
+#[derive(Copy, Clone, Debug)]
+pub struct SizeFormat {
+    pub units: Units,
+    /// The digit grouping and decimal separators, if digits are grouped.
+    pub separators: Option<(char, char)>,
+}
+
+impl Default for SizeFormat {
+    fn default() -> SizeFormat {
+        SizeFormat { units: Units::Bytes, separators: None }
+    }
+}
+
+/// The digit grouping and decimal separators of the user's locale, going by `LC_ALL`,
+/// `LC_NUMERIC` and `LANG`, in that order. Only the language is taken into account, except
+/// for Swiss German; anything unknown gets the English separators.
+pub fn locale_separators() -> (char, char) {
+    let locale = ["LC_ALL", "LC_NUMERIC", "LANG"].iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|value| !value.is_empty())
+        .unwrap_or_default();
+    if locale.starts_with("de_CH") {
+        return ('\'', '.');
+    }
+    match locale.split(['_', '.', '@']).next().unwrap_or("") {
+        "de" | "da" | "el" | "es" | "id" | "it" | "nl" | "pt" | "ro" | "sl" | "tr" | "vi" => {
+            ('.', ',')
+        }
+        "cs" | "fi" | "fr" | "hu" | "nb" | "nn" | "no" | "pl" | "ru" | "sk" | "sv" | "uk" => {
+            (' ', ',')
+        }
+        _ => (',', '.'),
+    }
+}
+
+/// Insert `separator` between every group of three digits of `digits`.
+fn group(digits: &str, separator: char) -> String {
+    let mut grouped = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(separator);
+        }
+        grouped.push(c);
+    }
+    grouped
+}
+
+impl SizeFormat {
+    /// Format `bytes`, e.g. as `1234567`, `1,234,567`, `1.2 MB` or `1.2 MiB`.
+    pub fn size(&self, bytes: u64) -> String {
+        let (base, suffixes) = match self.units {
+            Units::Bytes => return self.number(&bytes.to_string()),
+            Units::Si => (1000.0, ["B", "kB", "MB", "GB", "TB"]),
+            Units::Iec => (1024.0, ["B", "KiB", "MiB", "GiB", "TiB"]),
+        };
+        if (bytes as f64) < base {
+            return format!("{} B", self.number(&bytes.to_string()));
+        }
+        let mut value = bytes as f64;
+        let mut suffix = 0;
+        while value >= base && suffix < suffixes.len() - 1 {
+            value /= base;
+            suffix += 1;
+        }
+        format!("{} {}", self.number(&format!("{:.1}", value)), suffixes[suffix])
+    }
+
+    /// Format a change in size, always with a sign, e.g. `+1,234` or `-1.2 KiB`.
+    pub fn delta(&self, delta: i64) -> String {
+        let sign = if delta < 0 { '-' } else { '+' };
+        format!("{}{}", sign, self.size(delta.unsigned_abs()))
+    }
+
+    /// Apply this format's separators to the decimal number `n`.
+    fn number(&self, n: &str) -> String {
+        match self.separators {
+            Some((grouping, decimal)) => {
+                let (int, frac) = match n.find('.') {
+                    Some(i) => (&n[..i], Some(&n[i + 1..])),
+                    None => (n, None),
+                };
+                let mut s = group(int, grouping);
+                if let Some(frac) = frac {
+                    s.push(decimal);
+                    s.push_str(frac);
+                }
+                s
+            }
+            None => n.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(units: Units, separators: Option<(char, char)>) -> SizeFormat {
+        SizeFormat { units, separators }
+    }
+
+    #[test]
+    fn parses_units() {
+        assert_eq!("si".parse::<Units>().unwrap(), Units::Si);
+        assert_eq!("iec".parse::<Units>().unwrap(), Units::Iec);
+        assert_eq!("bytes".parse::<Units>().unwrap(), Units::Bytes);
+        let err = "KiB".parse::<Units>().unwrap_err();
+        assert!(err.downcast_ref::<UsageError>().is_some());
+    }
+
+    #[test]
+    fn sizes_in_units() {
+        let bytes = SizeFormat::default();
+        assert_eq!(bytes.size(1_234_567), "1234567");
+        let si = format(Units::Si, None);
+        assert_eq!(si.size(999), "999 B");
+        assert_eq!(si.size(1000), "1.0 kB");
+        assert_eq!(si.size(1_234_567), "1.2 MB");
+        assert_eq!(si.size(5_000_000_000_000_000), "5000.0 TB");
+        let iec = format(Units::Iec, None);
+        assert_eq!(iec.size(1023), "1023 B");
+        assert_eq!(iec.size(1536), "1.5 KiB");
+        assert_eq!(iec.size(3 << 30), "3.0 GiB");
+    }
+
+    #[test]
+    fn grouped_digits_and_deltas() {
+        let english = format(Units::Bytes, Some((',', '.')));
+        assert_eq!(english.size(0), "0");
+        assert_eq!(english.size(999), "999");
+        assert_eq!(english.size(1000), "1,000");
+        assert_eq!(english.size(1_234_567), "1,234,567");
+        assert_eq!(english.delta(-1_234), "-1,234");
+        assert_eq!(english.delta(0), "+0");
+        let german = format(Units::Si, Some(('.', ',')));
+        assert_eq!(german.size(1_234_567_890), "1,2 GB");
+        assert_eq!(german.size(1_500), "1,5 kB");
+        let french = format(Units::Iec, Some((' ', ',')));
+        assert_eq!(french.delta(-1_500 << 20), "-1,5 GiB");
+        assert_eq!(french.size(2_000_000 << 40), "2 000 000,0 TiB");
+        assert_eq!(format(Units::Iec, None).delta(i64::MIN), "-8388608.0 TiB");
+        assert_eq!(group("1234", '_'), "1_234");
+        assert_eq!(group("123456", '_'), "123_456");
+    }
+}
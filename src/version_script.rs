@@ -0,0 +1,243 @@
+use failure::Error;
+use regex::{self, Regex};
+use rustc_demangle;
+use std::fs;
+use std::path::Path;
+
+/// A symbol pattern from a version script.
+struct Pattern {
+    regex: Regex,
+    /// Whether the pattern names one symbol, rather than being a glob. Exact matches take
+    /// precedence over globs.
+    exact: bool,
+    /// Whether the pattern is in an `extern "C++"` block, and so matches demangled names. Only
+    /// Rust names are demangled, so such patterns don't match C++ symbols.
+    demangled: bool,
+    global: bool,
+}
+
+/// Translate a version script glob, with `*`, `?` and `[...]`, into an anchored regex.
+pub fn glob_regex(glob: &str) -> Result<Regex, Error> {
+    let mut re = String::from("^");
+    let mut chars = glob.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => re.push_str(".*"),
+            '?' => re.push('.'),
+            '[' => {
+                re.push('[');
+                for c in chars.by_ref() {
+                    match c {
+                        ']' => break,
+                        '\\' | '^' => re.push_str(&regex::escape(&c.to_string())),
+                        '!' if re.ends_with('[') => re.push('^'),
+                        c => re.push(c),
+                    }
+                }
+                re.push(']');
+            }
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    re.push('$');
+    Regex::new(&re).map_err(|e| format_err!("Invalid pattern {}: {}", glob, e))
+}
+
+/// Remove `#` and `/* ... */` comments from `script`.
+fn strip_comments(script: &str) -> String {
+    let mut out = String::new();
+    let mut rest = script;
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix("/*") {
+            rest = after.find("*/").map_or("", |end| &after[end + 2..]);
+        } else if rest.starts_with('#') {
+            rest = rest.find('\n').map_or("", |end| &rest[end..]);
+        } else {
+            let c = rest.chars().next().unwrap();
+            out.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    out
+}
+
+/// Which symbols a GNU linker version script (or a list of symbol names to export, one per
+/// line) leaves exported.
+pub struct VersionScript {
+    patterns: Vec<Pattern>,
+}
+
+impl VersionScript {
+    /// Read the version script or export list at `path`.
+    pub fn load(path: &Path) -> Result<VersionScript, Error> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format_err!("{}: {}", path.display(), e))?;
+        VersionScript::parse(&contents).map_err(|e| format_err!("{}: {}", path.display(), e))
+    }
+
+    fn parse(contents: &str) -> Result<VersionScript, Error> {
+        let contents = strip_comments(contents);
+        let mut patterns = Vec::new();
+        if !contents.contains('{') {
+            // An export list: everything else becomes local.
+            for name in contents.split_whitespace() {
+                patterns.push(Pattern {
+                    regex: Regex::new(&format!("^{}$", regex::escape(name)))?,
+                    exact: true,
+                    demangled: false,
+                    global: true,
+                });
+            }
+            patterns.push(Pattern {
+                regex: glob_regex("*")?,
+                exact: false,
+                demangled: false,
+                global: false,
+            });
+            return Ok(VersionScript { patterns });
+        }
+
+        // Split into `{`, `}`, `;`, quoted strings, and words, with `global:` and `local:`
+        // (spelled with or without a space before the colon) as section markers.
+        let mut tokens = Vec::new();
+        let mut chars = contents.chars().peekable();
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() {
+                chars.next();
+            } else if c == '{' || c == '}' || c == ';' {
+                tokens.push((c.to_string(), false));
+                chars.next();
+            } else if c == '"' {
+                chars.next();
+                let quoted: String = chars.by_ref().take_while(|&c| c != '"').collect();
+                tokens.push((quoted, true));
+            } else {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || "{};\"".contains(c) {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                for section in &["global:", "local:"] {
+                    if let Some(rest) = word.strip_prefix(section) {
+                        tokens.push((section.to_string(), false));
+                        word = rest.to_string();
+                    }
+                }
+                if word == ":" && tokens.last()
+                    .is_some_and(|t: &(String, bool)| t.0 == "global" || t.0 == "local") {
+                    tokens.last_mut().unwrap().0.push(':');
+                } else if !word.is_empty() {
+                    tokens.push((word, false));
+                }
+            }
+        }
+
+        let (mut depth, mut global, mut demangled) = (0, true, false);
+        let mut tokens = tokens.into_iter();
+        while let Some((token, quoted)) = tokens.next() {
+            match &*token {
+                "{" if !quoted => depth += 1,
+                "}" if !quoted => {
+                    depth -= 1;
+                    demangled = false;
+                    if depth == 0 {
+                        global = true;
+                    }
+                }
+                ";" if !quoted => {},
+                "global:" if !quoted => global = true,
+                "local:" if !quoted => global = false,
+                "extern" if !quoted && depth == 1 => {
+                    let language = tokens.next().map(|t| t.0).unwrap_or_default();
+                    demangled = language == "C++";
+                }
+                // Version node names, and the names of the nodes they inherit from.
+                _ if depth == 0 => {},
+                pattern => {
+                    let exact = quoted || !pattern.contains(['*', '?', '[']);
+                    patterns.push(Pattern {
+                        regex: if exact {
+                            Regex::new(&format!("^{}$", regex::escape(pattern)))?
+                        } else {
+                            glob_regex(pattern)?
+                        },
+                        exact,
+                        demangled,
+                        global,
+                    });
+                }
+            }
+        }
+        Ok(VersionScript { patterns })
+    }
+
+    /// Whether the script leaves the symbol `name` exported. Exact names take precedence over
+    /// globs, and `global` globs over `local` ones; symbols no pattern matches stay exported.
+    pub fn exports(&self, name: &str) -> bool {
+        let demangled = format!("{:#}", rustc_demangle::demangle(name));
+        let matching: Vec<&Pattern> = self.patterns.iter()
+            .filter(|p| p.regex.is_match(if p.demangled { &demangled } else { name }))
+            .collect();
+        if let Some(p) = matching.iter().find(|p| p.exact) {
+            return p.global;
+        }
+        matching.iter().any(|p| p.global) || matching.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn script(contents: &str) -> VersionScript {
+        VersionScript::parse(contents).unwrap()
+    }
+
+    #[test]
+    fn globs_and_comments() {
+        let re = glob_regex("foo_*").unwrap();
+        assert!(re.is_match("foo_bar") && re.is_match("foo_") && !re.is_match("xfoo_bar"));
+        let re = glob_regex("a?[b-d][!x]").unwrap();
+        assert!(re.is_match("a.cy") && !re.is_match("a.ex") && !re.is_match("a.cx"));
+        assert!(glob_regex("a.b").unwrap().is_match("a.b"));
+        assert!(!glob_regex("a.b").unwrap().is_match("axb"));
+        assert_eq!(strip_comments("a # b\nc /* d\n e */f /* g"), "a \nc f ");
+    }
+
+    #[test]
+    fn export_lists_hide_everything_else() {
+        let list = script("foo\nbar # the bar\n/* baz */\n");
+        assert!(list.exports("foo") && list.exports("bar"));
+        assert!(!list.exports("baz") && !list.exports("foo2"));
+    }
+
+    #[test]
+    fn exact_names_then_global_globs_win() {
+        let vers = script("\
+VERS_1 {
+  global:
+    foo; bar_*;
+    extern \"C++\" {
+      mycrate::api::*;
+    };
+  local :
+    bar_secret;
+    *;
+};
+VERS_2 {
+  global: \"baz*\";
+} VERS_1;
+");
+        assert!(vers.exports("foo") && vers.exports("bar_x") && vers.exports("baz*"));
+        assert!(!vers.exports("bar_secret") && !vers.exports("other") && !vers.exports("bazz"));
+        assert!(vers.exports("_ZN7mycrate3api3run17h0123456789abcdefE"));
+        assert!(!vers.exports("_ZN7mycrate8internal17h0123456789abcdefE"));
+
+        // Without a `local: *`, what no pattern matches stays exported.
+        let vers = script("{ global: foo; local: f*; };");
+        assert!(vers.exports("foo") && vers.exports("bar") && !vers.exports("fo"));
+    }
+}
@@ -0,0 +1,133 @@
+use failure::Error;
+use Section;
+use SectionRecord;
+
+/// The magic number at the start of every WebAssembly module.
+const WASM_MAGIC: &[u8] = b"\0asm";
+
+/// Whether `buf` looks like a WebAssembly module.
+pub fn is_wasm(buf: &[u8]) -> bool {
+    buf.starts_with(WASM_MAGIC)
+}
+
+/// Read an unsigned LEB128 number from `buf` at `*pos`, advancing `*pos` past it.
+fn read_uleb128(buf: &[u8], pos: &mut usize) -> Result<u64, Error> {
+    let mut result = 0u64;
+    let mut shift = 0;
+    loop {
+        let byte = match buf.get(*pos) {
+            Some(&b) => b,
+            None => bail!("Truncated LEB128 number at offset {}", *pos),
+        };
+        *pos += 1;
+        if shift < 64 {
+            result |= u64::from(byte & 0x7f) << shift;
+        }
+        if byte & 0x80 == 0 {
+            return Ok(result);
+        }
+        shift += 7;
+    }
+}
+
+/// The name of the standard (non-custom) section with id `id`.
+fn section_name(id: u8) -> &'static str {
+    match id {
+        1 => "type",
+        2 => "import",
+        3 => "function",
+        4 => "table",
+        5 => "memory",
+        6 => "global",
+        7 => "export",
+        8 => "start",
+        9 => "element",
+        10 => "code",
+        11 => "data",
+        12 => "datacount",
+        13 => "tag",
+        _ => "unknown",
+    }
+}
+
+/// Parse `buf` as a WebAssembly module and return a record for each section.
+///
+/// The code section counts as `Text` and the data section (the initial contents of linear
+/// memory) as `Data`. The other standard sections only describe the module, and are counted as
+/// `Text` like other read-only metadata. Custom sections (names, producers, DWARF) are `Other`,
+/// and are listed under their own names.
+///
+/// Sections have no addresses, alignment or flags, only file offsets.
+pub fn sections(buf: &[u8]) -> Result<Vec<SectionRecord>, Error> {
+    if !is_wasm(buf) || buf.len() < 8 {
+        bail!("Not a WebAssembly module");
+    }
+    let mut vec = Vec::new();
+    let mut pos = 8;
+    while pos < buf.len() {
+        let id = buf[pos];
+        pos += 1;
+        let size = read_uleb128(buf, &mut pos)?;
+        let end = pos + size as usize;
+        if end > buf.len() {
+            bail!("Section {} extends past the end of the file", section_name(id));
+        }
+        let (name, category) = match id {
+            0 => {
+                let mut name_pos = pos;
+                let len = read_uleb128(buf, &mut name_pos)? as usize;
+                let name = buf.get(name_pos..name_pos + len)
+                    .map(|name| String::from_utf8_lossy(name).into_owned())
+                    .unwrap_or_else(|| "custom".to_string());
+                (name, Section::Other)
+            },
+            10 => (section_name(id).to_string(), Section::Text),
+            11 => (section_name(id).to_string(), Section::Data),
+            _ => (section_name(id).to_string(), Section::Text),
+        };
+        let mut record = SectionRecord::synthetic(&name, size, category);
+        record.offset = Some(pos as u64);
+        vec.push(record);
+        pos = end;
+    }
+    Ok(vec)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_leb128() {
+        let buf = [0x05, 0xe5, 0x8e, 0x26, 0x80, 0x80];
+        let mut pos = 0;
+        assert_eq!(read_uleb128(&buf, &mut pos).unwrap(), 5);
+        assert_eq!(read_uleb128(&buf, &mut pos).unwrap(), 624_485);
+        assert_eq!(pos, 4);
+        assert!(read_uleb128(&buf, &mut pos).is_err());
+    }
+
+    #[test]
+    fn sections_by_id_and_custom_name() {
+        let mut buf = b"\0asm\x01\0\0\0".to_vec();
+        buf.extend_from_slice(&[1, 2, 0xaa, 0xbb]);
+        buf.extend_from_slice(&[10, 3, 1, 2, 3]);
+        buf.extend_from_slice(&[11, 1, 0]);
+        buf.extend_from_slice(&[0, 6, 4]);
+        buf.extend_from_slice(b"name\0");
+        let records: Vec<_> = sections(&buf).unwrap().into_iter()
+            .map(|s| (s.name, s.size, s.category, s.offset))
+            .collect();
+        assert_eq!(records, vec![
+            ("type".to_string(), 2, Section::Text, Some(10)),
+            ("code".to_string(), 3, Section::Text, Some(14)),
+            ("data".to_string(), 1, Section::Data, Some(19)),
+            ("name".to_string(), 6, Section::Other, Some(22)),
+        ]);
+
+        buf.truncate(buf.len() - 1);
+        let err = sections(&buf).unwrap_err().to_string();
+        assert!(err.contains("past the end"), "{}", err);
+        assert!(sections(b"\0asm").is_err());
+    }
+}
@@ -0,0 +1,652 @@
+use digest::{crc32, crc64, Algorithm};
+use failure::Error;
+
+/// The magic numbers that start and end an xz stream.
+pub const XZ_MAGIC: &[u8] = b"\xfd7zXZ\0";
+const FOOTER_MAGIC: &[u8] = b"YZ";
+
+/// The ID of the LZMA2 filter, the only one that `xz` uses unless told otherwise.
+const LZMA2_FILTER: u64 = 0x21;
+
+/// The LZMA states, and the first state after a match rather than a literal.
+const STATES: usize = 12;
+const LITERAL_STATES: usize = 7;
+
+/// The sizes of the LZMA probability tables.
+const POS_STATES_MAX: usize = 16;
+const DIST_STATES: usize = 4;
+const DIST_SLOTS: usize = 64;
+const DIST_MODEL_START: u32 = 4;
+const DIST_MODEL_END: u32 = 14;
+const FULL_DISTANCES: usize = 128;
+const ALIGN_BITS: u32 = 4;
+const LEN_LOW_SYMBOLS: usize = 8;
+const LEN_HIGH_SYMBOLS: usize = 256;
+
+/// The probability that a bit is 0 starts at a half, in 11 bits.
+const PROB_INIT: u16 = 1 << 10;
+
+/// Read the variable-length integer at `*pos` in `buf`, 7 bits per byte from the lowest.
+fn varint(buf: &[u8], pos: &mut usize) -> Result<u64, Error> {
+    let mut value = 0;
+    for i in 0..9 {
+        let b = match buf.get(*pos) {
+            Some(&b) => b,
+            None => bail!("Truncated xz stream"),
+        };
+        *pos += 1;
+        value |= u64::from(b & 0x7f) << (7 * i);
+        if b & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    bail!("Invalid xz integer")
+}
+
+/// The range decoder that LZMA's bits are coded with.
+struct RangeDecoder<'a> {
+    data: &'a [u8],
+    pos: usize,
+    range: u32,
+    code: u32,
+}
+
+impl<'a> RangeDecoder<'a> {
+    fn new(data: &'a [u8]) -> Result<RangeDecoder<'a>, Error> {
+        if data.len() < 5 || data[0] != 0 {
+            bail!("Invalid LZMA2 chunk");
+        }
+        let code = u32::from_be_bytes([data[1], data[2], data[3], data[4]]);
+        Ok(RangeDecoder { data, pos: 5, range: 0xffff_ffff, code })
+    }
+
+    fn normalize(&mut self) {
+        if self.range < 1 << 24 {
+            // Reading past the end only happens with corrupt data, which yields garbage that
+            // the sizes then catch.
+            let b = self.data.get(self.pos).copied().unwrap_or(0);
+            self.pos += 1;
+            self.range <<= 8;
+            self.code = self.code << 8 | u32::from(b);
+        }
+    }
+
+    fn bit(&mut self, prob: &mut u16) -> u32 {
+        self.normalize();
+        let bound = (self.range >> 11) * u32::from(*prob);
+        if self.code < bound {
+            self.range = bound;
+            *prob += ((1 << 11) - *prob) >> 5;
+            0
+        } else {
+            self.range -= bound;
+            self.code -= bound;
+            *prob -= *prob >> 5;
+            1
+        }
+    }
+
+    /// Decode `bits` bits with the probabilities of a bit tree, from the highest bit.
+    fn tree(&mut self, probs: &mut [u16], bits: u32) -> u32 {
+        let mut m = 1;
+        for _ in 0..bits {
+            m = m << 1 | self.bit(&mut probs[m as usize]);
+        }
+        m - (1 << bits)
+    }
+
+    /// Decode `bits` bits with the probabilities of a bit tree, from the lowest bit.
+    fn reverse_tree(&mut self, probs: &mut [u16], bits: u32) -> u32 {
+        let (mut m, mut symbol) = (1, 0);
+        for i in 0..bits {
+            let bit = self.bit(&mut probs[m as usize]);
+            m = m << 1 | bit;
+            symbol |= bit << i;
+        }
+        symbol
+    }
+
+    /// Decode `bits` bits of even probability.
+    fn direct(&mut self, bits: u32) -> u32 {
+        let mut value = 0;
+        for _ in 0..bits {
+            self.normalize();
+            self.range >>= 1;
+            let bit = if self.code >= self.range { 1 } else { 0 };
+            self.code -= self.range * bit;
+            value = value << 1 | bit;
+        }
+        value
+    }
+}
+
+/// The probabilities that match lengths are decoded with.
+struct LengthDecoder {
+    choice: u16,
+    choice2: u16,
+    low: [[u16; LEN_LOW_SYMBOLS]; POS_STATES_MAX],
+    mid: [[u16; LEN_LOW_SYMBOLS]; POS_STATES_MAX],
+    high: [u16; LEN_HIGH_SYMBOLS],
+}
+
+impl LengthDecoder {
+    fn new() -> LengthDecoder {
+        LengthDecoder {
+            choice: PROB_INIT,
+            choice2: PROB_INIT,
+            low: [[PROB_INIT; LEN_LOW_SYMBOLS]; POS_STATES_MAX],
+            mid: [[PROB_INIT; LEN_LOW_SYMBOLS]; POS_STATES_MAX],
+            high: [PROB_INIT; LEN_HIGH_SYMBOLS],
+        }
+    }
+
+    fn decode(&mut self, rc: &mut RangeDecoder, pos_state: usize) -> usize {
+        if rc.bit(&mut self.choice) == 0 {
+            2 + rc.tree(&mut self.low[pos_state], 3) as usize
+        } else if rc.bit(&mut self.choice2) == 0 {
+            10 + rc.tree(&mut self.mid[pos_state], 3) as usize
+        } else {
+            18 + rc.tree(&mut self.high, 8) as usize
+        }
+    }
+}
+
+/// The state of an LZMA decoder, which carries over between the chunks of an LZMA2 stream
+/// until they reset it.
+struct Lzma {
+    lc: u32,
+    lp: u32,
+    pb: u32,
+    state: usize,
+    reps: [usize; 4],
+    literal: Vec<u16>,
+    is_match: [[u16; POS_STATES_MAX]; STATES],
+    is_rep: [u16; STATES],
+    is_rep0: [u16; STATES],
+    is_rep1: [u16; STATES],
+    is_rep2: [u16; STATES],
+    is_rep0_long: [[u16; POS_STATES_MAX]; STATES],
+    dist_slot: [[u16; DIST_SLOTS]; DIST_STATES],
+    /// The first entry is unused: the trees of the distance slots start one further in than
+    /// their first bits' probabilities.
+    dist_special: [u16; FULL_DISTANCES - DIST_MODEL_END as usize + 1],
+    dist_align: [u16; 1 << ALIGN_BITS],
+    match_len: LengthDecoder,
+    rep_len: LengthDecoder,
+}
+
+impl Lzma {
+    fn new() -> Lzma {
+        Lzma {
+            lc: 0,
+            lp: 0,
+            pb: 0,
+            state: 0,
+            reps: [0; 4],
+            literal: Vec::new(),
+            is_match: [[PROB_INIT; POS_STATES_MAX]; STATES],
+            is_rep: [PROB_INIT; STATES],
+            is_rep0: [PROB_INIT; STATES],
+            is_rep1: [PROB_INIT; STATES],
+            is_rep2: [PROB_INIT; STATES],
+            is_rep0_long: [[PROB_INIT; POS_STATES_MAX]; STATES],
+            dist_slot: [[PROB_INIT; DIST_SLOTS]; DIST_STATES],
+            dist_special: [PROB_INIT; FULL_DISTANCES - DIST_MODEL_END as usize + 1],
+            dist_align: [PROB_INIT; 1 << ALIGN_BITS],
+            match_len: LengthDecoder::new(),
+            rep_len: LengthDecoder::new(),
+        }
+    }
+
+    /// Take the `lc`, `lp` and `pb` parameters from the properties byte `props`.
+    fn set_props(&mut self, props: u8) -> Result<(), Error> {
+        let props = u32::from(props);
+        if props >= 9 * 5 * 5 {
+            bail!("Invalid LZMA properties");
+        }
+        let (lc, lp, pb) = (props % 9, props / 9 % 5, props / 45);
+        if lc + lp > 4 {
+            bail!("Invalid LZMA2 properties");
+        }
+        self.lc = lc;
+        self.lp = lp;
+        self.pb = pb;
+        Ok(())
+    }
+
+    /// Reset the state and the probabilities, keeping the parameters.
+    fn reset(&mut self) {
+        let (lc, lp, pb) = (self.lc, self.lp, self.pb);
+        *self = Lzma::new();
+        self.lc = lc;
+        self.lp = lp;
+        self.pb = pb;
+        self.literal = vec![PROB_INIT; 0x300 << (lc + lp)];
+    }
+
+    /// Decode the LZMA data `data` onto `out` until it holds `end` bytes. Matches can reach
+    /// back as far as `dict_start`, where the dictionary was last reset.
+    fn decode(&mut self, data: &[u8], out: &mut Vec<u8>, dict_start: usize, end: usize)
+              -> Result<(), Error> {
+        let mut rc = RangeDecoder::new(data)?;
+        let pb_mask = (1 << self.pb) - 1;
+        let lp_mask = (1 << self.lp) - 1;
+        while out.len() < end {
+            let pos = out.len() - dict_start;
+            let pos_state = pos & pb_mask;
+            let state = self.state;
+            if rc.bit(&mut self.is_match[state][pos_state]) == 0 {
+                let prev = if pos > 0 { u32::from(out[out.len() - 1]) } else { 0 };
+                let lit_state = ((pos & lp_mask) << self.lc) + (prev >> (8 - self.lc)) as usize;
+                let probs = &mut self.literal[0x300 * lit_state..0x300 * (lit_state + 1)];
+                let mut symbol: usize = 1;
+                if state < LITERAL_STATES {
+                    while symbol < 0x100 {
+                        symbol = symbol << 1 | rc.bit(&mut probs[symbol]) as usize;
+                    }
+                } else {
+                    let rep0 = self.reps[0];
+                    if rep0 >= pos {
+                        bail!("Invalid LZMA distance");
+                    }
+                    let mut match_byte = usize::from(out[out.len() - rep0 - 1]) << 1;
+                    let mut offset = 0x100;
+                    while symbol < 0x100 {
+                        let match_bit = match_byte & offset;
+                        match_byte <<= 1;
+                        if rc.bit(&mut probs[offset + match_bit + symbol]) == 1 {
+                            symbol = symbol << 1 | 1;
+                            offset = match_bit;
+                        } else {
+                            symbol <<= 1;
+                            offset ^= match_bit;
+                        }
+                    }
+                }
+                out.push(symbol as u8);
+                self.state = match state {
+                    0..=3 => 0,
+                    4..=9 => state - 3,
+                    _ => state - 6,
+                };
+                continue;
+            }
+
+            let len = if rc.bit(&mut self.is_rep[state]) == 0 {
+                let len = self.match_len.decode(&mut rc, pos_state);
+                self.state = if state < LITERAL_STATES { 7 } else { 10 };
+                self.reps = [self.distance(&mut rc, len), self.reps[0], self.reps[1],
+                             self.reps[2]];
+                len
+            } else {
+                if rc.bit(&mut self.is_rep0[state]) == 0 {
+                    if rc.bit(&mut self.is_rep0_long[state][pos_state]) == 0 {
+                        // A single byte from the last distance.
+                        self.state = if state < LITERAL_STATES { 9 } else { 11 };
+                        if self.reps[0] >= pos {
+                            bail!("Invalid LZMA distance");
+                        }
+                        let b = out[out.len() - self.reps[0] - 1];
+                        out.push(b);
+                        continue;
+                    }
+                } else {
+                    let rep = if rc.bit(&mut self.is_rep1[state]) == 0 {
+                        1
+                    } else if rc.bit(&mut self.is_rep2[state]) == 0 {
+                        2
+                    } else {
+                        3
+                    };
+                    let distance = self.reps[rep];
+                    for i in (1..=rep).rev() {
+                        self.reps[i] = self.reps[i - 1];
+                    }
+                    self.reps[0] = distance;
+                }
+                self.state = if state < LITERAL_STATES { 8 } else { 11 };
+                self.rep_len.decode(&mut rc, pos_state)
+            };
+            let distance = self.reps[0];
+            // The end marker is reported as a distance too far back to be valid.
+            if distance >= pos {
+                bail!("Invalid LZMA distance");
+            }
+            let len = len.min(end - out.len());
+            for _ in 0..len {
+                let b = out[out.len() - distance - 1];
+                out.push(b);
+            }
+        }
+        Ok(())
+    }
+
+    /// Decode the distance of a match of `len` bytes.
+    fn distance(&mut self, rc: &mut RangeDecoder, len: usize) -> usize {
+        let dist_state = (len - 2).min(DIST_STATES - 1);
+        let slot = rc.tree(&mut self.dist_slot[dist_state], 6);
+        if slot < DIST_MODEL_START {
+            return slot as usize;
+        }
+        let bits = (slot >> 1) - 1;
+        let mut distance = (2 | (slot & 1)) << bits;
+        if slot < DIST_MODEL_END {
+            let base = (distance - slot) as usize;
+            distance += rc.reverse_tree(&mut self.dist_special[base..], bits);
+        } else {
+            distance += rc.direct(bits - ALIGN_BITS) << ALIGN_BITS;
+            distance += rc.reverse_tree(&mut self.dist_align, ALIGN_BITS);
+        }
+        distance as usize
+    }
+}
+
+/// Decode the LZMA2 data of a block, `data`, onto `out`, returning the bytes it took up.
+fn lzma2(data: &[u8], out: &mut Vec<u8>) -> Result<usize, Error> {
+    let mut lzma = Lzma::new();
+    let mut dict_start = out.len();
+    let mut pos = 0;
+    loop {
+        let control = match data.get(pos) {
+            Some(&control) => control,
+            None => bail!("Truncated LZMA2 data"),
+        };
+        pos += 1;
+        if control == 0 {
+            return Ok(pos);
+        }
+        let header = match data.get(pos..pos + 2) {
+            Some(header) => header,
+            None => bail!("Truncated LZMA2 data"),
+        };
+        let size = usize::from(u16::from_be_bytes([header[0], header[1]])) + 1;
+        pos += 2;
+        if control < 0x80 {
+            // Uncompressed, with the dictionary reset first for 1.
+            if control > 2 {
+                bail!("Invalid LZMA2 chunk");
+            }
+            if control == 1 {
+                dict_start = out.len();
+            }
+            match data.get(pos..pos + size) {
+                Some(chunk) => out.extend_from_slice(chunk),
+                None => bail!("Truncated LZMA2 data"),
+            }
+            pos += size;
+            continue;
+        }
+        let unpacked = (usize::from(control & 0x1f) << 16) + size;
+        let packed = match data.get(pos..pos + 2) {
+            Some(packed) => usize::from(u16::from_be_bytes([packed[0], packed[1]])) + 1,
+            None => bail!("Truncated LZMA2 data"),
+        };
+        pos += 2;
+        // What the chunk resets: nothing, the state, the state and properties, or all of them
+        // and the dictionary.
+        let reset = (control >> 5) & 3;
+        if reset == 3 {
+            dict_start = out.len();
+        }
+        if reset >= 2 {
+            match data.get(pos) {
+                Some(&props) => lzma.set_props(props)?,
+                None => bail!("Truncated LZMA2 data"),
+            }
+            pos += 1;
+        }
+        if reset >= 1 {
+            lzma.reset();
+        } else if lzma.literal.is_empty() {
+            bail!("LZMA2 data doesn't set its properties");
+        }
+        let chunk = match data.get(pos..pos + packed) {
+            Some(chunk) => chunk,
+            None => bail!("Truncated LZMA2 data"),
+        };
+        let end = out.len() + unpacked;
+        lzma.decode(chunk, out, dict_start, end)?;
+        pos += packed;
+    }
+}
+
+/// The size of the check of type `check` that follows each block.
+fn check_size(check: u8) -> usize {
+    match check {
+        0 => 0,
+        _ => 4 << ((check - 1) / 3),
+    }
+}
+
+/// Whether `check`, of type `kind`, is the check of the uncompressed data of a block, `data`.
+/// Checks of types that can't be computed pass.
+fn check_matches(kind: u8, check: &[u8], data: &[u8]) -> bool {
+    match kind {
+        1 => check == crc32(data).to_le_bytes(),
+        4 => check == crc64(data).to_le_bytes(),
+        10 => check == &Algorithm::Sha256.digest(&[data])[..],
+        _ => true,
+    }
+}
+
+/// Decompress the blocks of the xz stream at `*pos` in `buf` onto `out`, verifying their
+/// checks, and skip its index and footer.
+fn stream(buf: &[u8], pos: &mut usize, out: &mut Vec<u8>) -> Result<(), Error> {
+    let header = match buf.get(*pos..*pos + 12) {
+        Some(header) if header.starts_with(XZ_MAGIC) => header,
+        _ => bail!("Not an xz stream"),
+    };
+    let check = header[7] & 0xf;
+    *pos += 12;
+    loop {
+        let size = match buf.get(*pos) {
+            Some(0) => break,
+            Some(&size) => (usize::from(size) + 1) * 4,
+            None => bail!("Truncated xz stream"),
+        };
+        let block_header = match buf.get(*pos..*pos + size) {
+            Some(block_header) => block_header,
+            None => bail!("Truncated xz stream"),
+        };
+        let (block_header, crc) = block_header.split_at(size - 4);
+        if crc != crc32(block_header).to_le_bytes() {
+            bail!("xz block header fails its integrity check");
+        }
+        let flags = block_header[1];
+        let mut i = 2;
+        if flags & 0x40 != 0 {
+            varint(block_header, &mut i)?;
+        }
+        let uncompressed_size = if flags & 0x80 != 0 {
+            Some(varint(block_header, &mut i)?)
+        } else {
+            None
+        };
+        if flags & 3 != 0 {
+            bail!("Unsupported xz filter chain (only LZMA2 is supported)");
+        }
+        if varint(block_header, &mut i)? != LZMA2_FILTER {
+            bail!("Unsupported xz filter (only LZMA2 is supported)");
+        }
+        *pos += size;
+        let start = out.len();
+        let data = &buf[*pos..];
+        let len = lzma2(data, out)?;
+        if uncompressed_size.is_some_and(|size| size != (out.len() - start) as u64) {
+            bail!("xz block has the wrong uncompressed size");
+        }
+        // The compressed data is padded to four bytes, and followed by the check.
+        *pos += len.div_ceil(4) * 4;
+        match buf.get(*pos..*pos + check_size(check)) {
+            Some(expected) if check_matches(check, expected, &out[start..]) => {}
+            Some(_) => bail!("xz block fails its integrity check"),
+            None => bail!("Truncated xz stream"),
+        }
+        *pos += check_size(check);
+    }
+
+    // The index: its indicator, the number of records, each record's sizes, the padding and
+    // a CRC32.
+    let mut i = *pos + 1;
+    let records = varint(buf, &mut i)?;
+    for _ in 0..records * 2 {
+        varint(buf, &mut i)?;
+    }
+    *pos = (i - *pos).div_ceil(4) * 4 + *pos + 4;
+    match buf.get(*pos + 10..*pos + 12) {
+        Some(magic) if magic == FOOTER_MAGIC => *pos += 12,
+        _ => bail!("Invalid xz stream footer"),
+    }
+    Ok(())
+}
+
+/// Decompress the xz file `buf`, which can hold several streams, as `xz` compresses each of its
+/// single-threaded and multi-threaded outputs, separated by zero padding.
+pub fn decompress(buf: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut out = Vec::new();
+    let mut pos = 0;
+    while pos < buf.len() {
+        stream(buf, &mut pos, &mut out)?;
+        while buf.get(pos..pos + 4) == Some(&[0; 4]) {
+            pos += 4;
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `n` bytes of text, which LZMA compresses with matches.
+    fn text(n: usize) -> Vec<u8> {
+        b"rust-size measures the sections of binaries. ".iter().cycle().take(n).cloned().collect()
+    }
+
+    /// `n` bytes of noise from a linear congruential generator, which doesn't compress.
+    fn noise(n: usize) -> Vec<u8> {
+        let mut x: u32 = 1;
+        (0..n).map(|_| {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (x >> 24) as u8
+        }).collect()
+    }
+
+    /// `text(200)` as `xz -9e -C crc64` compresses it: one block of LZMA chunks.
+    const SINGLE_BLOCK: &[u8] = &[
+        0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x00, 0x00, 0x04, 0xe6, 0xd6, 0xb4, 0x46, 0x04, 0xc0, 0x3a,
+        0xc8, 0x01, 0x21, 0x01, 0x1c, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xc9, 0xb3,
+        0x72, 0xfb, 0xe0, 0x00, 0xc7, 0x00, 0x32, 0x5d, 0x00, 0x39, 0x1d, 0x4a, 0xb1, 0xf2, 0xe3,
+        0x17, 0x8a, 0x1e, 0xf0, 0x1d, 0x13, 0xf3, 0x27, 0x2a, 0x6e, 0x8a, 0x01, 0x35, 0xe7, 0x84,
+        0xef, 0xe0, 0xef, 0x14, 0x1d, 0x8d, 0xff, 0x3e, 0x8d, 0x9f, 0x06, 0x38, 0x04, 0x44, 0x3a,
+        0xdb, 0xea, 0x64, 0xa5, 0x16, 0xaf, 0xa8, 0x72, 0xba, 0xce, 0xdb, 0x32, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x4f, 0xf5, 0xa7, 0xc9, 0xdd, 0x6d, 0xb9, 0x19, 0x00, 0x01, 0x56, 0xc8, 0x01,
+        0x00, 0x00, 0x00, 0x48, 0xfb, 0x5b, 0x78, 0xb1, 0xc4, 0x67, 0xfb, 0x02, 0x00, 0x00, 0x00,
+        0x00, 0x04, 0x59, 0x5a,
+    ];
+
+    /// `text(300)` as `xz -9e -C crc32 --block-size=100` compresses it: three blocks.
+    const MULTI_BLOCK: &[u8] = &[
+        0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x00, 0x00, 0x01, 0x69, 0x22, 0xde, 0x36, 0x02, 0xc0, 0x3a,
+        0x64, 0x21, 0x01, 0x1c, 0x00, 0xe8, 0x4d, 0x72, 0xdd, 0xe0, 0x00, 0x63, 0x00, 0x32, 0x5d,
+        0x00, 0x39, 0x1d, 0x4a, 0xb1, 0xf2, 0xe3, 0x17, 0x8a, 0x1e, 0xf0, 0x1d, 0x13, 0xf3, 0x27,
+        0x2a, 0x6e, 0x8a, 0x01, 0x35, 0xe7, 0x84, 0xef, 0xe0, 0xef, 0x14, 0x1d, 0x8d, 0xff, 0x3e,
+        0x8d, 0x9f, 0x06, 0x38, 0x04, 0x44, 0x3a, 0xdb, 0xea, 0x64, 0xa5, 0x16, 0xaf, 0xa8, 0x72,
+        0x98, 0x32, 0xff, 0x32, 0x00, 0x00, 0x00, 0x00, 0x00, 0x8e, 0x93, 0x11, 0x90, 0x02, 0xc0,
+        0x3b, 0x64, 0x21, 0x01, 0x1c, 0x00, 0x4d, 0x9e, 0x2e, 0x16, 0xe0, 0x00, 0x63, 0x00, 0x33,
+        0x5d, 0x00, 0x36, 0x99, 0x48, 0x49, 0xb4, 0xff, 0xcc, 0x58, 0x7b, 0xd3, 0xe6, 0xd5, 0xd1,
+        0x24, 0x8b, 0xa7, 0xf1, 0x96, 0x47, 0xd2, 0xdc, 0x10, 0x8a, 0x9c, 0x05, 0xb3, 0x35, 0xf9,
+        0x48, 0xbc, 0x95, 0x10, 0x77, 0x32, 0xcd, 0x11, 0x2d, 0x1f, 0x2b, 0xa7, 0x6a, 0x34, 0x3e,
+        0x73, 0x63, 0x18, 0x85, 0xba, 0x96, 0x00, 0x00, 0x00, 0x00, 0xe5, 0xfd, 0x0b, 0x7e, 0x02,
+        0xc0, 0x3b, 0x64, 0x21, 0x01, 0x1c, 0x00, 0x4d, 0x9e, 0x2e, 0x16, 0xe0, 0x00, 0x63, 0x00,
+        0x33, 0x5d, 0x00, 0x34, 0x19, 0x40, 0x06, 0x31, 0xa4, 0xab, 0xff, 0xc2, 0xce, 0x49, 0xec,
+        0xf2, 0x16, 0xc6, 0x3a, 0x9b, 0x86, 0xda, 0x7a, 0x6d, 0x43, 0xde, 0x32, 0xc3, 0x34, 0x10,
+        0x95, 0x25, 0xf8, 0x41, 0x86, 0x29, 0xed, 0xba, 0xcd, 0x4a, 0x81, 0x6d, 0xc0, 0xbd, 0xf7,
+        0xae, 0x80, 0xe0, 0x6f, 0xd9, 0xad, 0x8c, 0x00, 0x00, 0x00, 0x00, 0xd0, 0x08, 0xe9, 0x4c,
+        0x00, 0x03, 0x4a, 0x64, 0x4b, 0x64, 0x4b, 0x64, 0x3c, 0xb2, 0x22, 0xcc, 0x3e, 0x30, 0x0d,
+        0x8b, 0x02, 0x00, 0x00, 0x00, 0x00, 0x01, 0x59, 0x5a,
+    ];
+
+    /// `noise(64)` as `xz -9e -C sha256` compresses it: an uncompressed LZMA2 chunk, as
+    /// noise doesn't compress.
+    const UNCOMPRESSED: &[u8] = &[
+        0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x00, 0x00, 0x0a, 0xe1, 0xfb, 0x0c, 0xa1, 0x04, 0xc0, 0x44,
+        0x40, 0x21, 0x01, 0x1c, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x64, 0x4c,
+        0xe6, 0x26, 0x01, 0x00, 0x3f, 0x41, 0x96, 0x27, 0xc4, 0xf9, 0x95, 0xd9, 0x9c, 0xbf, 0x0f,
+        0x0a, 0x31, 0x23, 0xaf, 0x7d, 0xc4, 0xe2, 0xd2, 0xe2, 0xe3, 0xe9, 0x93, 0x50, 0x28, 0x2c,
+        0x75, 0x42, 0xb3, 0x4d, 0xe4, 0xf7, 0xef, 0xee, 0x56, 0xe1, 0xca, 0x31, 0xad, 0x99, 0x69,
+        0xb5, 0x3b, 0x7d, 0x10, 0x1b, 0x7a, 0xde, 0xb4, 0xe3, 0x61, 0x7a, 0x83, 0x28, 0xe0, 0x9f,
+        0x4b, 0x85, 0xfa, 0x28, 0x87, 0x38, 0x75, 0x49, 0x8f, 0x00, 0xdb, 0x95, 0xe4, 0x89, 0xd2,
+        0x2a, 0x0e, 0x3e, 0xec, 0x56, 0x49, 0x99, 0xc5, 0xd3, 0xf2, 0x0a, 0x99, 0x12, 0x70, 0xc3,
+        0x92, 0x15, 0x1a, 0x3c, 0xde, 0x20, 0xa9, 0xe8, 0x72, 0x58, 0xf7, 0x5c, 0x00, 0x01, 0x78,
+        0x40, 0x45, 0x07, 0xbc, 0xb1, 0x18, 0x9b, 0x4b, 0x9a, 0x01, 0x00, 0x00, 0x00, 0x00, 0x0a,
+        0x59, 0x5a,
+    ];
+
+    /// `ab` with no check and `cd` with CRC-32, compressed separately and joined with four
+    /// bytes of stream padding, as `xz -dc` reads them.
+    const TWO_STREAMS: &[u8] = &[
+        0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x00, 0x00, 0x00, 0xff, 0x12, 0xd9, 0x41, 0x04, 0xc0, 0x06,
+        0x02, 0x21, 0x01, 0x1c, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xd8, 0x5b,
+        0xe0, 0x5b, 0x01, 0x00, 0x01, 0x61, 0x62, 0x00, 0x00, 0x00, 0x00, 0x01, 0x1a, 0x02, 0xdc,
+        0x2e, 0xa5, 0x7e, 0x06, 0x72, 0x9e, 0x7a, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x59, 0x5a,
+        0x00, 0x00, 0x00, 0x00, 0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x00, 0x00, 0x01, 0x69, 0x22, 0xde,
+        0x36, 0x04, 0xc0, 0x06, 0x02, 0x21, 0x01, 0x1c, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0xd8, 0x5b, 0xe0, 0x5b, 0x01, 0x00, 0x01, 0x63, 0x64, 0x00, 0x00, 0x00, 0xda,
+        0x8f, 0xd6, 0x45, 0x00, 0x01, 0x1e, 0x02, 0xd8, 0xeb, 0xc9, 0x1a, 0x90, 0x42, 0x99, 0x0d,
+        0x01, 0x00, 0x00, 0x00, 0x00, 0x01, 0x59, 0x5a,
+    ];
+
+    #[test]
+    fn single_block() {
+        assert_eq!(decompress(SINGLE_BLOCK).unwrap(), text(200));
+    }
+
+    #[test]
+    fn multi_block() {
+        assert_eq!(decompress(MULTI_BLOCK).unwrap(), text(300));
+    }
+
+    #[test]
+    fn uncompressed_chunk() {
+        assert_eq!(decompress(UNCOMPRESSED).unwrap(), noise(64));
+    }
+
+    #[test]
+    fn streams_with_padding() {
+        assert_eq!(decompress(TWO_STREAMS).unwrap(), b"abcd");
+    }
+
+    #[test]
+    fn corrupt_check() {
+        // The CRC-64 is the 8 bytes before the 12 bytes of the index and the 12 of the footer.
+        let mut corrupt = SINGLE_BLOCK.to_vec();
+        let check = corrupt.len() - 32;
+        corrupt[check] ^= 1;
+        let err = decompress(&corrupt).unwrap_err().to_string();
+        assert!(err.contains("integrity check"), "{}", err);
+
+        // A byte of the uncompressed chunk, which only the SHA-256 catches, and one of the
+        // padding of the block header, which its CRC-32 does.
+        let mut corrupt = UNCOMPRESSED.to_vec();
+        corrupt[40] ^= 1;
+        let err = decompress(&corrupt).unwrap_err().to_string();
+        assert!(err.contains("block fails its integrity check"), "{}", err);
+        let mut corrupt = UNCOMPRESSED.to_vec();
+        corrupt[30] ^= 1;
+        let err = decompress(&corrupt).unwrap_err().to_string();
+        assert!(err.contains("header fails its integrity check"), "{}", err);
+
+        let mut corrupt = MULTI_BLOCK.to_vec();
+        corrupt[12 + 76 - 4] ^= 0x80;
+        assert!(decompress(&corrupt).is_err());
+    }
+
+    #[test]
+    fn truncated() {
+        for len in [6, 12, 40, SINGLE_BLOCK.len() - 1] {
+            assert!(decompress(&SINGLE_BLOCK[..len]).is_err(), "{}", len);
+        }
+        assert!(decompress(b"not xz at all").is_err());
+    }
+}
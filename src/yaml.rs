@@ -0,0 +1,320 @@
+use failure::Error;
+use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::Serialize;
+use serde_json;
+use std::fmt;
+use std::io::Write;
+
+/// A value of a report, as its JSON serialization has it, with the fields of maps in order.
+enum Node {
+    Null,
+    Bool(bool),
+    Number(String),
+    String(String),
+    Seq(Vec<Node>),
+    Map(Vec<(String, Node)>),
+}
+
+struct NodeVisitor;
+
+impl<'de> Visitor<'de> for NodeVisitor {
+    type Value = Node;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a JSON value")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Node, E> {
+        Ok(Node::Null)
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Node, E> {
+        Ok(Node::Bool(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Node, E> {
+        Ok(Node::Number(v.to_string()))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Node, E> {
+        Ok(Node::Number(v.to_string()))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Node, E> {
+        Ok(Node::Number(serde_json::to_string(&v).map_err(E::custom)?))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Node, E> {
+        Ok(Node::String(v.to_string()))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Node, A::Error> {
+        let mut items = Vec::new();
+        while let Some(item) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(Node::Seq(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Node, A::Error> {
+        let mut entries = Vec::new();
+        while let Some(entry) = map.next_entry()? {
+            entries.push(entry);
+        }
+        Ok(Node::Map(entries))
+    }
+}
+
+impl<'de> Deserialize<'de> for Node {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Node, D::Error> {
+        deserializer.deserialize_any(NodeVisitor)
+    }
+}
+
+/// `s` as a YAML scalar: plain if it can't be mistaken for anything else, and otherwise double
+/// quoted, with the escapes of JSON, which YAML shares.
+fn scalar(s: &str) -> String {
+    let plain = !s.is_empty() &&
+        s.chars().all(|c| c.is_alphanumeric() || "_-./+()<>$~ ".contains(c)) &&
+        !s.starts_with(['-', '+', ' ', '~', '>']) && !s.ends_with(' ') &&
+        !s.starts_with(|c: char| c.is_ascii_digit()) &&
+        !["true", "false", "null", "yes", "no", "on", "off", "y", "n"]
+            .contains(&s.to_lowercase().as_str());
+    if plain {
+        s.to_string()
+    } else {
+        serde_json::to_string(s).unwrap()
+    }
+}
+
+/// Write `node` at `indent`, after a key or a `-` that is already written.
+fn write_node(out: &mut String, node: &Node, indent: usize) {
+    let pad = " ".repeat(indent);
+    match *node {
+        Node::Null => out.push_str(" null\n"),
+        Node::Bool(b) => out.push_str(&format!(" {}\n", b)),
+        Node::Number(ref n) => out.push_str(&format!(" {}\n", n)),
+        Node::String(ref s) => out.push_str(&format!(" {}\n", scalar(s))),
+        Node::Seq(ref items) if items.is_empty() => out.push_str(" []\n"),
+        Node::Map(ref entries) if entries.is_empty() => out.push_str(" {}\n"),
+        Node::Seq(ref items) => {
+            out.push('\n');
+            for item in items {
+                out.push_str(&pad);
+                out.push('-');
+                match *item {
+                    // A mapping in a sequence starts on the line of its `-`.
+                    Node::Map(ref entries) if !entries.is_empty() => {
+                        out.push(' ');
+                        write_entries(out, entries, indent + 2, true);
+                    }
+                    _ => write_node(out, item, indent + 2),
+                }
+            }
+        }
+        Node::Map(ref entries) => {
+            out.push('\n');
+            write_entries(out, entries, indent, false);
+        }
+    }
+}
+
+/// Write the `entries` of a mapping at `indent`, the first of them on the line already written
+/// if `inline`.
+fn write_entries(out: &mut String, entries: &[(String, Node)], indent: usize, inline: bool) {
+    let pad = " ".repeat(indent);
+    for (i, (key, value)) in entries.iter().enumerate() {
+        if i > 0 || !inline {
+            out.push_str(&pad);
+        }
+        out.push_str(&format!("{}:", scalar(key)));
+        write_node(out, value, indent + 2);
+    }
+}
+
+/// Write `value` to `out` as a YAML document with the same structure as its JSON: the fields of
+/// structs in the same order, as block mappings and sequences.
+pub fn to_writer<W: Write, T: Serialize>(mut out: W, value: &T) -> Result<(), Error> {
+    let node: Node = serde_json::from_str(&serde_json::to_string(value)?)?;
+    let mut yaml = String::from("---");
+    write_node(&mut yaml, &node, 0);
+    out.write_all(yaml.as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{Map, Value};
+    use std::collections::BTreeMap;
+
+    fn yaml<T: Serialize>(value: &T) -> String {
+        let mut out = Vec::new();
+        to_writer(&mut out, value).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    /// A key of a mapping, plain or double quoted, and what follows its `:`.
+    fn key(line: &str) -> Option<(String, &str)> {
+        let (key, rest) = if line.starts_with('"') {
+            let mut strings = serde_json::Deserializer::from_str(line).into_iter::<String>();
+            let key = strings.next()?.ok()?;
+            (key, &line[strings.byte_offset()..])
+        } else {
+            let colon = line.find(':')?;
+            (line[..colon].to_string(), &line[colon..])
+        };
+        let rest = rest.strip_prefix(':')?;
+        if rest.is_empty() || rest.starts_with(' ') { Some((key, rest)) } else { None }
+    }
+
+    fn plain(scalar: &str) -> Value {
+        match scalar {
+            "[]" => Value::Array(Vec::new()),
+            "{}" => Value::Object(Map::new()),
+            _ => serde_json::from_str(scalar).unwrap_or_else(|_| Value::String(scalar.into())),
+        }
+    }
+
+    /// The block mapping or sequence at `indent` that starts at `lines[*i]`.
+    fn block(lines: &mut [(usize, String)], i: &mut usize, indent: usize) -> Value {
+        if lines[*i].1.starts_with('-') {
+            let mut items = Vec::new();
+            while *i < lines.len() && lines[*i].0 == indent && lines[*i].1.starts_with('-') {
+                let item = lines[*i].1[1..].trim_start().to_string();
+                if item.is_empty() {
+                    *i += 1;
+                    items.push(block(lines, i, indent + 2));
+                } else if key(&item).is_some() {
+                    lines[*i] = (indent + 2, item);
+                    items.push(block(lines, i, indent + 2));
+                } else {
+                    *i += 1;
+                    items.push(plain(&item));
+                }
+            }
+            Value::Array(items)
+        } else {
+            let mut map = Map::new();
+            while *i < lines.len() && lines[*i].0 == indent {
+                let line = lines[*i].1.clone();
+                let (key, rest) = key(&line).unwrap();
+                *i += 1;
+                let value = match rest {
+                    "" => block(lines, i, indent + 2),
+                    _ => plain(&rest[1..]),
+                };
+                map.insert(key, value);
+            }
+            Value::Object(map)
+        }
+    }
+
+    /// Read back the YAML that `to_writer` writes.
+    fn parse(yaml: &str) -> Value {
+        let mut lines: Vec<(usize, String)> = yaml.lines().map(|line| {
+            let text = line.trim_start();
+            (line.len() - text.len(), text.to_string())
+        }).collect();
+        let start = lines.remove(0);
+        if let Some(scalar) = start.1.strip_prefix("--- ") {
+            return plain(scalar);
+        }
+        assert_eq!(start.1, "---");
+        let mut i = 0;
+        let value = block(&mut lines, &mut i, 0);
+        assert_eq!(i, lines.len());
+        value
+    }
+
+    #[test]
+    fn quotes_what_isnt_a_plain_string() {
+        for s in &["yes", "No", "ON", "off", "y", "null", "true", "False", "~"] {
+            assert_eq!(scalar(s), format!("\"{}\"", s));
+        }
+        for s in &["1abc", "0x10", "1.5", "-x", "+1", "", " lead", "trail ", "a: b", "a#b",
+                   "[x]", "{x}", "'x'", "x, y", "*x", "&x", "!x", "|", ">", "%x", "@x", "`x`"] {
+            assert!(scalar(s).starts_with('"'), "{:?} isn't quoted", s);
+        }
+        assert_eq!(scalar("tab\there"), "\"tab\\there\"");
+        for s in &[".text", "x86_64", "<T as Tr>", "(no crate)", "src/main.rs", "a b", "yesno"] {
+            assert_eq!(scalar(s), *s);
+        }
+    }
+
+    #[test]
+    fn keys_with_colons_and_hashes() {
+        let map: BTreeMap<&str, u64> =
+            vec![("#count", 1), ("a:b", 2), ("plain", 3), ("1st", 4)].into_iter().collect();
+        assert_eq!(yaml(&map), "---\n\"#count\": 1\n\"1st\": 4\n\"a:b\": 2\nplain: 3\n");
+    }
+
+    #[derive(Serialize)]
+    struct Row {
+        name: &'static str,
+        size: u64,
+        percent: f64,
+        extra: BTreeMap<String, u64>,
+    }
+
+    #[derive(Serialize)]
+    struct Report {
+        total: u64,
+        rows: Vec<Row>,
+        empty: Vec<u64>,
+        nested: Vec<Vec<&'static str>>,
+        missing: Option<u64>,
+    }
+
+    fn report() -> Report {
+        Report {
+            total: 12,
+            rows: vec![
+                Row { name: ".text", size: 8, percent: -93.553_719_008_264_48,
+                      extra: BTreeMap::new() },
+                Row { name: "no", size: 4, percent: 2.0,
+                      extra: vec![("a".to_string(), 1)].into_iter().collect() },
+            ],
+            empty: Vec::new(),
+            nested: vec![vec!["1", "null"], vec![]],
+            missing: None,
+        }
+    }
+
+    #[test]
+    fn block_layout_in_field_order() {
+        assert_eq!(yaml(&report()), "\
+---
+total: 12
+rows:
+  - name: .text
+    size: 8
+    percent: -93.55371900826448
+    extra: {}
+  - name: \"no\"
+    size: 4
+    percent: 2.0
+    extra:
+      a: 1
+empty: []
+nested:
+  -
+    - \"1\"
+    - \"null\"
+  - []
+missing: null
+");
+    }
+
+    #[test]
+    fn reads_back_as_the_json() {
+        let report = report();
+        assert_eq!(parse(&yaml(&report)), serde_json::to_value(&report).unwrap());
+        let strings = vec!["yes", "", " x", "a: b", "#", "\"", "é", "0", "-1", "1e3", "~"];
+        assert_eq!(parse(&yaml(&strings)), serde_json::to_value(&strings).unwrap());
+        for value in &[serde_json::json!(5), serde_json::json!("on"), serde_json::json!([]),
+                       serde_json::json!({}), serde_json::json!([{}, [[]], {"k": [1]}])] {
+            assert_eq!(parse(&yaml(value)), *value);
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use failure::Error;
+use miniz_oxide::inflate::decompress_to_vec_with_limit;
+use std::borrow::Cow;
+
+pub const END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4b50;
+pub const CENTRAL_DIRECTORY_HEADER: u32 = 0x0201_4b50;
+pub const LOCAL_FILE_HEADER: u32 = 0x0403_4b50;
+pub const STORED: u16 = 0;
+pub const DEFLATED: u16 = 8;
+
+fn u16_at(buf: &[u8], offset: usize) -> Option<u16> {
+    buf.get(offset..offset + 2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn u32_at(buf: &[u8], offset: usize) -> Option<u32> {
+    buf.get(offset..offset + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/// Whether `buf` looks like a ZIP archive (including APKs, AABs and JARs).
+pub fn is_zip(buf: &[u8]) -> bool {
+    u32_at(buf, 0) == Some(LOCAL_FILE_HEADER)
+}
+
+/// A file in a ZIP archive.
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub name: String,
+    method: u16,
+    /// The size in the archive.
+    pub compressed_size: usize,
+    /// The uncompressed size.
+    pub size: usize,
+    /// The offset of the (possibly compressed) contents in the archive.
+    pub data_offset: usize,
+}
+
+impl Entry {
+    /// Whether the entry is stored uncompressed, so that it can be used in place.
+    pub fn is_stored(&self) -> bool {
+        self.method == STORED
+    }
+
+    /// Return the contents of the entry, decompressing them if needed.
+    pub fn read<'a>(&self, buf: &'a [u8]) -> Result<Cow<'a, [u8]>, Error> {
+        let data = match buf.get(self.data_offset..self.data_offset + self.compressed_size) {
+            Some(data) => data,
+            None => bail!("{}: contents extend past the end of the archive", self.name),
+        };
+        match self.method {
+            STORED => Ok(Cow::Borrowed(data)),
+            DEFLATED => decompress_to_vec_with_limit(data, self.size)
+                .map(Cow::Owned)
+                .map_err(|e| {
+                    format_err!("{}: invalid compressed data ({:?})", self.name, e.status)
+                }),
+            method => bail!("{}: unsupported compression method {}", self.name, method),
+        }
+    }
+}
+
+/// Return the files in the ZIP archive `buf`, from its central directory. ZIP64 archives (over
+/// 4GB, or with over 65535 files) aren't supported.
+pub fn entries(buf: &[u8]) -> Result<Vec<Entry>, Error> {
+    // The end of central directory record is at least 22 bytes, followed by a comment of up to
+    // 64KB.
+    let search_start = buf.len().saturating_sub(22 + 0xffff);
+    let eocd = match (search_start..buf.len().saturating_sub(21)).rev()
+        .find(|&i| u32_at(buf, i) == Some(END_OF_CENTRAL_DIRECTORY)) {
+        Some(eocd) => eocd,
+        None => bail!("Missing ZIP end of central directory record"),
+    };
+    let count = u16_at(buf, eocd + 10).unwrap() as usize;
+    let mut pos = u32_at(buf, eocd + 16).unwrap() as usize;
+    if count == 0xffff || pos == 0xffff_ffff {
+        bail!("ZIP64 archives are not supported");
+    }
+
+    let mut entries = Vec::with_capacity(count);
+    for _ in 0..count {
+        if u32_at(buf, pos) != Some(CENTRAL_DIRECTORY_HEADER) {
+            bail!("Invalid ZIP central directory header at offset {}", pos);
+        }
+        let field = |offset| u16_at(buf, pos + offset).unwrap_or(0) as usize;
+        let (name_len, extra_len, comment_len) = (field(28), field(30), field(32));
+        let method = u16_at(buf, pos + 10).unwrap_or(0);
+        let compressed_size = u32_at(buf, pos + 20).unwrap_or(0) as usize;
+        let size = u32_at(buf, pos + 24).unwrap_or(0) as usize;
+        let local = u32_at(buf, pos + 42).unwrap_or(0) as usize;
+        let name = match buf.get(pos + 46..pos + 46 + name_len) {
+            Some(name) => String::from_utf8_lossy(name).into_owned(),
+            None => bail!("Truncated ZIP central directory"),
+        };
+        // The local header repeats the name, but its extra field can differ (e.g. the alignment
+        // padding zipalign adds), so its own lengths determine where the contents start.
+        if u32_at(buf, local) != Some(LOCAL_FILE_HEADER) {
+            bail!("{}: invalid ZIP local file header", name);
+        }
+        let data_offset = local + 30 + u16_at(buf, local + 26).unwrap_or(0) as usize +
+            u16_at(buf, local + 28).unwrap_or(0) as usize;
+        entries.push(Entry { name, method, compressed_size, size, data_offset });
+        pos += 46 + name_len + extra_len + comment_len;
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use testzip::archive;
+
+    #[test]
+    fn stored_and_deflated_entries() {
+        let text = b"hello hello hello hello hello".to_vec();
+        let buf = archive(&[("lib/x86/libfoo.so", STORED, 3, b"\x7fELF"),
+                            ("classes.dex", DEFLATED, 0, &text)]);
+        assert!(is_zip(&buf));
+        let entries = entries(&buf).unwrap();
+        assert_eq!(entries.len(), 2);
+        let so = &entries[0];
+        assert_eq!((so.name.as_str(), so.is_stored(), so.size), ("lib/x86/libfoo.so", true, 4));
+        // The local header's extra field moves the contents.
+        assert_eq!(so.data_offset, 30 + so.name.len() + 3);
+        assert!(matches!(so.read(&buf).unwrap(), Cow::Borrowed(b"\x7fELF")));
+        let dex = &entries[1];
+        assert!(!dex.is_stored() && dex.compressed_size < dex.size);
+        assert_eq!(dex.read(&buf).unwrap().into_owned(), text);
+    }
+
+    #[test]
+    fn malformed_archives() {
+        assert!(!is_zip(b"\x7fELF"));
+        let err = entries(&[0; 100]).unwrap_err().to_string();
+        assert!(err.contains("end of central directory"), "{}", err);
+        let mut buf = archive(&[("a", DEFLATED, 0, b"aaaaaaaaaaaaaaaaaaaaaaaa")]);
+        let entry = entries(&buf).unwrap().remove(0);
+        buf[entry.data_offset] ^= 0xff;
+        buf[entry.data_offset + 1] ^= 0xff;
+        assert!(entry.read(&buf).is_err());
+        let buf = archive(&[("b", 12, 0, b"bzip2")]);
+        let err = entries(&buf).unwrap().remove(0).read(&buf).unwrap_err().to_string();
+        assert_eq!(err, "b: unsupported compression method 12");
+        let mut buf = archive(&[("c", STORED, 0, b"c")]);
+        buf[0] = 0;
+        assert!(entries(&buf).is_err());
+    }
+}